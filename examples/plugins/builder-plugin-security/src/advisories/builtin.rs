@@ -0,0 +1,204 @@
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::SystemTime;
+
+use super::{AdvisorySource, Estimate, Matcher, Provenance, RawFinding};
+use crate::ecosystems::{Dependency, Ecosystem};
+use crate::report::DbSnapshot;
+use crate::Vulnerability;
+
+/// A small set of historical advisories compiled into the plugin, for demos
+/// and smoke tests. Used only when no other source is configured and
+/// `demo_db` is on; every log line about it is marked
+/// [`BuiltinDemo::MARK`].
+pub struct BuiltinDemo;
+
+struct DemoAdvisory {
+    /// `None` derives a made-up but stable id from the package and version.
+    id: Option<&'static str>,
+    aliases: &'static [&'static str],
+    ecosystem: Ecosystem,
+    package: &'static str,
+    version: &'static str,
+    severity: &'static str,
+    description: &'static str,
+    fixed_in: &'static str,
+}
+
+const fn advisory(
+    id: Option<&'static str>,
+    aliases: &'static [&'static str],
+    (ecosystem, package, version): (Ecosystem, &'static str, &'static str),
+    severity: &'static str,
+    description: &'static str,
+    fixed_in: &'static str,
+) -> DemoAdvisory {
+    DemoAdvisory { id, aliases, ecosystem, package, version, severity, description, fixed_in }
+}
+
+const KNOWN_VULNERABLE: &[DemoAdvisory] = &[
+    advisory(None, &[], (Ecosystem::Npm, "lodash", "4.17.15"), "HIGH", "Prototype pollution", "4.17.21"),
+    advisory(None, &[], (Ecosystem::PyPI, "django", "2.2.0"), "CRITICAL", "SQL injection vulnerability", "2.2.24"),
+    advisory(None, &[], (Ecosystem::Npm, "express", "4.16.0"), "MEDIUM", "Open redirect vulnerability", "4.17.1"),
+    advisory(None, &[], (Ecosystem::PyPI, "requests", "2.25.0"), "LOW", "Information disclosure", "2.26.0"),
+    advisory(
+        Some("CVE-2020-1747"),
+        &[],
+        (Ecosystem::PyPI, "pyyaml", "5.3"),
+        "CRITICAL",
+        "Arbitrary code execution in full_load and FullLoader",
+        "5.3.1",
+    ),
+    advisory(
+        Some("CVE-2019-10906"),
+        &[],
+        (Ecosystem::PyPI, "jinja2", "2.10"),
+        "HIGH",
+        "Sandbox escape through str.format_map",
+        "2.10.1",
+    ),
+    advisory(
+        Some("CVE-2019-11324"),
+        &[],
+        (Ecosystem::PyPI, "urllib3", "1.24.1"),
+        "HIGH",
+        "Improper certificate validation when custom CA certificates are given",
+        "1.24.2",
+    ),
+    advisory(
+        Some("CVE-2021-44906"),
+        &[],
+        (Ecosystem::Npm, "minimist", "1.2.5"),
+        "CRITICAL",
+        "Prototype pollution through constructor and prototype keys",
+        "1.2.6",
+    ),
+    advisory(
+        Some("CVE-2020-28168"),
+        &[],
+        (Ecosystem::Npm, "axios", "0.21.0"),
+        "MEDIUM",
+        "Server-side request forgery by following redirects to restricted hosts",
+        "0.21.1",
+    ),
+    advisory(
+        Some("CVE-2020-15168"),
+        &[],
+        (Ecosystem::Npm, "node-fetch", "2.6.0"),
+        "LOW",
+        "The size option is not honored after following a redirect",
+        "2.6.1",
+    ),
+    advisory(
+        Some("RUSTSEC-2021-0003"),
+        &["CVE-2021-25900"],
+        (Ecosystem::CratesIo, "smallvec", "1.6.0"),
+        "CRITICAL",
+        "Buffer overflow in SmallVec::insert_many",
+        "1.6.1",
+    ),
+    advisory(
+        Some("RUSTSEC-2022-0013"),
+        &["CVE-2022-24713"],
+        (Ecosystem::CratesIo, "regex", "1.5.4"),
+        "HIGH",
+        "Denial of service when parsing crafted regular expressions",
+        "1.5.5",
+    ),
+    advisory(
+        Some("RUSTSEC-2020-0071"),
+        &["CVE-2020-26235"],
+        (Ecosystem::CratesIo, "time", "0.1.43"),
+        "MEDIUM",
+        "Potential segfault while another thread changes the environment",
+        "0.2.23",
+    ),
+];
+
+impl BuiltinDemo {
+    /// The `source` of the dataset's snapshot and provenance.
+    pub const SOURCE: &'static str = "builtin-demo";
+
+    /// Appended to log lines about demo data, so they aren't mistaken for a
+    /// real audit.
+    pub const MARK: &'static str = "[demo data]";
+
+    /// How many advisories the dataset has.
+    pub fn len() -> usize {
+        KNOWN_VULNERABLE.len()
+    }
+
+    /// Whether any of `vuln`'s advisory data came from the dataset.
+    pub fn found(vuln: &Vulnerability) -> bool {
+        vuln.provenance.iter().any(|p| p.source == Self::SOURCE)
+    }
+}
+
+impl AdvisorySource for BuiltinDemo {
+    fn refresh(&mut self, _cache: &Path, _now: SystemTime) -> Result<DbSnapshot, String> {
+        Ok(DbSnapshot {
+            source: Self::SOURCE.to_string(),
+            id: snapshot_id(),
+            sha256: None,
+            fetched_at: None,
+            age_secs: None,
+            freshness: Vec::new(),
+        })
+    }
+
+    fn estimate(&self) -> Option<Estimate> {
+        Some(Estimate { setting: "demo_db", path: None, bytes: 0 })
+    }
+
+    fn query(&self, deps: &[Dependency]) -> Vec<RawFinding> {
+        deps.iter()
+            .enumerate()
+            .filter_map(|(index, dep)| {
+                let known = KNOWN_VULNERABLE.iter().find(|known| {
+                    known.ecosystem == dep.ecosystem && dep.name.contains(known.package) && dep.version == known.version
+                })?;
+                Some(RawFinding {
+                    dependency: index,
+                    id: known.id.map_or_else(|| demo_id(&dep.name, &dep.version), |id| id.to_string()),
+                    aliases: known.aliases.iter().map(|a| a.to_string()).collect(),
+                    severity: known.severity.to_string(),
+                    cvss_score: None,
+                    summary: String::new(),
+                    description: known.description.to_string(),
+                    fixed_in: Some(known.fixed_in.to_string()),
+                    references: Vec::new(),
+                    packages: Vec::new(),
+                    published: None,
+                    cwe: Vec::new(),
+                    categories: Vec::new(),
+                    provenance: Provenance {
+                        source: Self::SOURCE.to_string(),
+                        database: None,
+                        snapshot: snapshot_id(),
+                        // Names containing a demo package match it too
+                        matcher: if dep.name == known.package { Matcher::Exact } else { Matcher::Heuristic },
+                        range: format!("={}", known.version),
+                        confidence: (dep.name != known.package).then(|| name_similarity(known.package, &dep.name)),
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+fn snapshot_id() -> String {
+    format!("{}@{}", BuiltinDemo::SOURCE, crate::PLUGIN_VERSION)
+}
+
+/// Share of `name` the demo package's name covers, to two decimals.
+fn name_similarity(package: &str, name: &str) -> f64 {
+    (package.len() as f64 / name.len() as f64 * 100.0).round() / 100.0
+}
+
+/// A made-up but stable CVE-style id, so repeated scans agree on what is
+/// new.
+fn demo_id(package: &str, version: &str) -> String {
+    let digest = Sha256::new().chain_update(package).chain_update("@").chain_update(version).finalize();
+    let number = u16::from_be_bytes([digest[0], digest[1]]) % 10000;
+    format!("CVE-2021-{:04}", number)
+}
@@ -0,0 +1,479 @@
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::osv::{self, Affected};
+use super::private;
+use super::{AdvisorySource, Estimate, RawFinding, Reference};
+use crate::ecosystems::{Dependency, Ecosystem};
+use crate::fsutil;
+use crate::report::{self, DbSnapshot};
+
+/// The scanner whose output a file is, told apart by its shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tool {
+    /// `npm audit --json`: npm 6's `advisories`, or the
+    /// `auditReportVersion: 2` report of npm 7 and later.
+    Npm,
+    /// `pip-audit --format json`: a list of dependencies before 2.0, an
+    /// object of `dependencies` and `fixes` since.
+    Pip,
+    /// `cargo audit --json`.
+    Cargo,
+}
+
+impl Tool {
+    fn detect(report: &Value) -> Option<Tool> {
+        if report.get("auditReportVersion").is_some() || report.get("advisories").is_some_and(Value::is_object) {
+            return Some(Tool::Npm);
+        }
+        if report.is_array() || report.get("dependencies").is_some_and(Value::is_array) {
+            return Some(Tool::Pip);
+        }
+        if report.pointer("/vulnerabilities/list").is_some_and(Value::is_array) {
+            return Some(Tool::Cargo);
+        }
+        None
+    }
+
+    /// The source name findings are attributed to.
+    fn source(self) -> &'static str {
+        match self {
+            Tool::Npm => "npm-audit",
+            Tool::Pip => "pip-audit",
+            Tool::Cargo => "cargo-audit",
+        }
+    }
+
+    fn ecosystem(self) -> Ecosystem {
+        match self {
+            Tool::Npm => Ecosystem::Npm,
+            Tool::Pip => Ecosystem::PyPI,
+            Tool::Cargo => Ecosystem::CratesIo,
+        }
+    }
+}
+
+/// What an imported result says of one package. The tools report what
+/// OSV-style advisories don't carry, so the score and fix are kept beside
+/// the matching entry.
+struct Imported {
+    affected: Affected,
+    cvss_score: Option<f64>,
+    /// The fix the tool recommends for the version it found.
+    fixed_in: Option<String>,
+}
+
+/// Findings of another scanner, read from one file of its JSON output
+/// (`import_results`). Each is matched against the scan's own dependencies
+/// like an advisory, so it is deduplicated, suppressed and decided by
+/// policy together with the plugin's findings. Packages the scan didn't
+/// read any dependency file for are left out.
+pub struct ImportedResults {
+    path: PathBuf,
+    tool: Option<Tool>,
+    imported: HashMap<String, Vec<Imported>>,
+    /// Id of the file's contents, for provenance.
+    snapshot: String,
+}
+
+impl ImportedResults {
+    pub fn new(path: PathBuf) -> Self {
+        ImportedResults {
+            path,
+            tool: None,
+            imported: HashMap::new(),
+            snapshot: String::new(),
+        }
+    }
+
+    /// Read the tool's report into entries by index key.
+    fn load(&self, content: &str) -> Result<(Tool, HashMap<String, Vec<Imported>>), String> {
+        let report: Value = serde_json::from_str(content).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        let Some(tool) = Tool::detect(&report) else {
+            return Err(format!(
+                "{}: not npm audit, pip-audit or cargo audit JSON output",
+                self.path.display()
+            ));
+        };
+        let entries = match tool {
+            Tool::Npm if report.get("auditReportVersion").is_some() => npm_audit_v2(&report),
+            Tool::Npm => npm_audit_v1(&report),
+            Tool::Pip => Ok(pip_audit(&report)),
+            Tool::Cargo => Ok(cargo_audit(&report)),
+        }
+        .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+
+        let mut imported: HashMap<String, Vec<Imported>> = HashMap::new();
+        for (package, entry) in entries {
+            imported.entry(osv::key(tool.ecosystem().as_str(), &package)).or_default().push(entry);
+        }
+        Ok((tool, imported))
+    }
+}
+
+impl AdvisorySource for ImportedResults {
+    fn refresh(&mut self, _cache: &Path, now: SystemTime) -> Result<DbSnapshot, String> {
+        let content = fsutil::read_to_string(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        let (tool, imported) = self.load(&content)?;
+        let sha256: String = Sha256::digest(content.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+        self.snapshot = format!("{}@{}", tool.source(), &sha256[..16]);
+        self.tool = Some(tool);
+        self.imported = imported;
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        Ok(DbSnapshot {
+            source: tool.source().to_string(),
+            id: self.snapshot.clone(),
+            sha256: Some(sha256),
+            fetched_at: modified.map(report::format_timestamp),
+            age_secs: modified.and_then(|modified| now.duration_since(modified).ok()).map(|age| age.as_secs()),
+            freshness: Vec::new(),
+        })
+    }
+
+    fn estimate(&self) -> Option<Estimate> {
+        let bytes = fs::metadata(&self.path).ok()?.len();
+        Some(Estimate { setting: "import_results", path: Some(self.path.display().to_string()), bytes })
+    }
+
+    fn query(&self, deps: &[Dependency]) -> Vec<RawFinding> {
+        let Some(tool) = self.tool else {
+            return Vec::new();
+        };
+        let mut findings = Vec::new();
+        for (i, dep) in deps.iter().enumerate().filter(|(_, dep)| dep.ecosystem == tool.ecosystem()) {
+            let Some(entries) = self.imported.get(&osv::key(dep.ecosystem.as_str(), &dep.name)) else {
+                continue;
+            };
+            for entry in entries {
+                let Some(mut finding) = osv::finding(i, dep, &entry.affected, tool.source(), &self.snapshot) else {
+                    continue;
+                };
+                finding.cvss_score = entry.cvss_score;
+                finding.fixed_in = finding.fixed_in.or_else(|| entry.fixed_in.clone());
+                findings.push(finding);
+            }
+        }
+        findings
+    }
+}
+
+/// An entry affecting `versions` and the versions in `ranges`, with the
+/// rest of the advisory left empty.
+fn affected(id: String, severity: &str, versions: Vec<String>, ranges: Vec<osv::Interval>) -> Affected {
+    Affected {
+        id,
+        aliases: Vec::new(),
+        release: None,
+        severity: match severity {
+            "" => "UNKNOWN".to_string(),
+            severity => severity.to_string(),
+        },
+        summary: String::new(),
+        details: String::new(),
+        versions,
+        ranges,
+        references: Vec::new(),
+        packages: Vec::new(),
+        published: None,
+        cwe: Vec::new(),
+        categories: Vec::new(),
+    }
+}
+
+fn text<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// A string or a list of strings, as the tools variously give CWE ids.
+fn strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(one)) => vec![one.clone()],
+        Some(Value::Array(many)) => many.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn advisory_reference(url: &str) -> Vec<Reference> {
+    match url {
+        "" => Vec::new(),
+        url => vec![Reference {
+            kind: "ADVISORY".to_string(),
+            url: url.to_string(),
+        }],
+    }
+}
+
+/// The advisory id npm links to: GitHub's from its advisory URL, since
+/// npm's own numbers name nothing other databases know.
+fn npm_id(url: &str, number: &str) -> String {
+    match url.rsplit('/').next().filter(|id| id.starts_with("GHSA-")) {
+        Some(id) => id.to_string(),
+        None => format!("NPM-{}", number),
+    }
+}
+
+/// npm 7 and later: `vulnerabilities` by package, each advisory in `via`
+/// with the range it affects. `via` entries naming another package only
+/// say where the advisory came in from, and are that package's.
+fn npm_audit_v2(report: &Value) -> Result<Vec<(String, Imported)>, String> {
+    let mut entries = Vec::new();
+    let packages = report.get("vulnerabilities").and_then(Value::as_object).into_iter().flatten();
+    for (name, package) in packages {
+        for via in package.get("via").and_then(Value::as_array).into_iter().flatten().filter(|v| v.is_object()) {
+            let url = text(via, "url");
+            let id = npm_id(url, &via.get("source").map(Value::to_string).unwrap_or_default());
+            let range = text(via, "range");
+            let (versions, ranges) = private::affecting(Ecosystem::Npm, range)
+                .map_err(|e| format!("advisory {} ({}): range '{}': {}", id, name, range, e))?;
+            let mut affected = affected(id, text(via, "severity"), versions, ranges);
+            affected.summary = text(via, "title").to_string();
+            affected.references = advisory_reference(url);
+            affected.cwe = strings(via.get("cwe"));
+            let cvss_score = via.pointer("/cvss/score").and_then(Value::as_f64).filter(|score| *score > 0.0);
+            let package = text(via, "name");
+            let imported = Imported {
+                affected,
+                cvss_score,
+                fixed_in: None,
+            };
+            entries.push((if package.is_empty() { name.clone() } else { package.to_string() }, imported));
+        }
+    }
+    Ok(entries)
+}
+
+/// npm 6: `advisories` by npm's id, each with the range it affects and
+/// the installed versions it was found in.
+fn npm_audit_v1(report: &Value) -> Result<Vec<(String, Imported)>, String> {
+    let mut entries = Vec::new();
+    for (number, advisory) in report.get("advisories").and_then(Value::as_object).into_iter().flatten() {
+        let url = text(advisory, "url");
+        let id = match text(advisory, "github_advisory_id") {
+            "" => npm_id(url, number),
+            id => id.to_string(),
+        };
+        let name = text(advisory, "module_name");
+        let range = text(advisory, "vulnerable_versions");
+        let (mut versions, ranges) = private::affecting(Ecosystem::Npm, range)
+            .map_err(|e| format!("advisory {} ({}): range '{}': {}", id, name, range, e))?;
+        let findings = advisory.get("findings").and_then(Value::as_array).into_iter().flatten();
+        versions.extend(findings.map(|finding| text(finding, "version").to_string()).filter(|v| !v.is_empty()));
+        let mut affected = affected(id, text(advisory, "severity"), versions, ranges);
+        affected.aliases = strings(advisory.get("cves"));
+        affected.summary = text(advisory, "title").to_string();
+        affected.details = text(advisory, "overview").to_string();
+        affected.references = advisory_reference(url);
+        affected.cwe = strings(advisory.get("cwe"));
+        affected.published = advisory.get("created").and_then(Value::as_str).map(str::to_string);
+        let imported = Imported {
+            affected,
+            cvss_score: None,
+            fixed_in: None,
+        };
+        entries.push((name.to_string(), imported));
+    }
+    Ok(entries)
+}
+
+/// pip-audit: each dependency with the version it found and its `vulns`;
+/// dependencies it skipped have none. Only the version found is affected,
+/// as pip-audit names no ranges.
+fn pip_audit(report: &Value) -> Vec<(String, Imported)> {
+    let dependencies = match report {
+        Value::Array(dependencies) => Some(dependencies),
+        report => report.get("dependencies").and_then(Value::as_array),
+    };
+    let mut entries = Vec::new();
+    for dependency in dependencies.into_iter().flatten() {
+        let (name, version) = (text(dependency, "name"), text(dependency, "version"));
+        for vuln in dependency.get("vulns").and_then(Value::as_array).into_iter().flatten() {
+            let mut affected = affected(text(vuln, "id").to_string(), "", vec![version.to_string()], Vec::new());
+            affected.aliases = strings(vuln.get("aliases"));
+            affected.details = text(vuln, "description").to_string();
+            let fixes = strings(vuln.get("fix_versions"));
+            let imported = Imported {
+                affected,
+                cvss_score: None,
+                fixed_in: first_fix(Ecosystem::PyPI, version, fixes),
+            };
+            entries.push((name.to_string(), imported));
+        }
+    }
+    entries
+}
+
+/// cargo audit: the advisories found, each for the locked version of its
+/// package, with its `patched` requirements. RustSec gives no severity
+/// label, only a CVSS vector, so the findings' severity is unknown unless
+/// another source rates them.
+fn cargo_audit(report: &Value) -> Vec<(String, Imported)> {
+    let list = report.pointer("/vulnerabilities/list").and_then(Value::as_array);
+    let mut entries = Vec::new();
+    for found in list.into_iter().flatten() {
+        let advisory = found.get("advisory").unwrap_or(&Value::Null);
+        if advisory.get("withdrawn").is_some_and(|withdrawn| !withdrawn.is_null()) {
+            continue;
+        }
+        let name = text(found.get("package").unwrap_or(&Value::Null), "name");
+        let version = found.pointer("/package/version").and_then(Value::as_str).unwrap_or_default();
+        let mut affected = affected(text(advisory, "id").to_string(), "", vec![version.to_string()], Vec::new());
+        affected.aliases = strings(advisory.get("aliases"));
+        affected.summary = text(advisory, "title").to_string();
+        affected.details = text(advisory, "description").to_string();
+        affected.references = advisory_reference(text(advisory, "url"));
+        for url in strings(advisory.get("references")) {
+            affected.references.push(Reference {
+                kind: "WEB".to_string(),
+                url,
+            });
+        }
+        affected.categories = strings(advisory.get("categories"));
+        affected.published = Some(text(advisory, "date"))
+            .filter(|date| !date.is_empty())
+            .map(|date| format!("{}T00:00:00Z", date));
+        // `>= 0.6.14, < 1.0.0`: the lower bound is the fix of its series
+        let patched = strings(found.pointer("/versions/patched"));
+        let fixes = patched.iter().filter_map(|requirement| {
+            let lower = requirement.split(',').next()?.trim();
+            let bound = lower.strip_prefix(">=").or_else(|| lower.strip_prefix('^'))?;
+            Some(bound.trim().to_string())
+        });
+        let imported = Imported {
+            affected,
+            cvss_score: None,
+            fixed_in: first_fix(Ecosystem::CratesIo, version, fixes.collect()),
+        };
+        entries.push((name.to_string(), imported));
+    }
+    entries
+}
+
+/// The lowest of `fixes` above `version`.
+fn first_fix(ecosystem: Ecosystem, version: &str, fixes: Vec<String>) -> Option<String> {
+    fixes
+        .into_iter()
+        .filter(|fix| ecosystem.compare_versions(fix, version) == Ordering::Greater)
+        .min_by(|a, b| ecosystem.compare_versions(a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisories::Matcher;
+    use crate::ecosystems::Scope;
+
+    fn dep(ecosystem: Ecosystem, name: &str, version: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+            ecosystem,
+            scope: Scope::Runtime,
+            file: "manifest".to_string(),
+            line: 1,
+            col: None,
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    /// Refresh an import of `testdata/imports/<name>` and query it.
+    fn import(name: &str, deps: &[Dependency]) -> (DbSnapshot, Vec<RawFinding>) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/imports").join(name);
+        let mut source = ImportedResults::new(path);
+        let snapshot = source.refresh(Path::new("unused"), SystemTime::now()).unwrap();
+        (snapshot, source.query(deps))
+    }
+
+    fn ids(findings: &[RawFinding]) -> Vec<(usize, &str, Option<&str>)> {
+        let mut ids: Vec<_> = findings.iter().map(|f| (f.dependency, f.id.as_str(), f.fixed_in.as_deref())).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn npm_audit_reports_of_either_version_match_the_installed_packages() {
+        let deps = [
+            dep(Ecosystem::Npm, "lodash", "4.17.20"),
+            dep(Ecosystem::Npm, "minimist", "1.2.5"),
+            dep(Ecosystem::Npm, "mkdirp", "0.5.1"),
+            dep(Ecosystem::Npm, "lodash", "4.17.21"),
+            dep(Ecosystem::PyPI, "lodash", "4.17.20"),
+        ];
+        let (snapshot, v1) = import("npm-audit-v1.json", &deps);
+        assert_eq!(snapshot.source, "npm-audit");
+        assert!(snapshot.id.starts_with("npm-audit@"));
+        // npm's own id for an advisory it doesn't give GitHub's for
+        assert_eq!(ids(&v1), [(0, "NPM-1673", Some("4.17.21")), (1, "GHSA-xvch-5gv4-984h", Some("1.2.6"))]);
+        let lodash = &v1[ids(&v1).iter().position(|(i, ..)| *i == 0).unwrap()];
+        assert_eq!(lodash.aliases, ["CVE-2021-23337"]);
+        assert_eq!((lodash.severity.as_str(), &lodash.cwe[..]), ("high", &["CWE-77".to_string()][..]));
+        assert_eq!(lodash.provenance.matcher, Matcher::Exact);
+
+        let (_, v2) = import("npm-audit-v2.json", &deps);
+        assert_eq!(
+            ids(&v2),
+            [
+                (0, "GHSA-29mw-wpgm-hmr9", Some("4.17.21")),
+                (0, "GHSA-35jh-r3h4-6jhm", Some("4.17.21")),
+                (1, "GHSA-xvch-5gv4-984h", Some("1.2.6")),
+            ]
+        );
+        let minimist = v2.iter().find(|f| f.dependency == 1).unwrap();
+        assert_eq!((minimist.cvss_score, minimist.provenance.matcher), (Some(9.8), Matcher::Range));
+        assert_eq!(minimist.provenance.source, "npm-audit");
+        assert_eq!(minimist.references[0].url, "https://github.com/advisories/GHSA-xvch-5gv4-984h");
+    }
+
+    #[test]
+    fn pip_audit_reports_before_and_since_2_0_name_the_fix_of_the_version_found() {
+        let deps = [dep(Ecosystem::PyPI, "Django", "2.2.0"), dep(Ecosystem::PyPI, "django", "2.2.4")];
+        let (_, old) = import("pip-audit-1.json", &deps);
+        let (snapshot, new) = import("pip-audit-2.json", &deps);
+        assert_eq!(snapshot.source, "pip-audit");
+        assert_eq!(ids(&old), [(0, "PYSEC-2019-12", Some("2.2.4"))]);
+        assert_eq!(ids(&new), ids(&old));
+        assert!(old[0].aliases.is_empty());
+        assert_eq!(new[0].aliases, ["CVE-2019-14234", "GHSA-6r97-cj55-9hrq"]);
+        assert_eq!(new[0].severity, "UNKNOWN");
+        assert!(new[0].description.contains("JSONField allowed SQL injection"));
+    }
+
+    #[test]
+    fn cargo_audit_reports_of_either_version_match_the_locked_packages() {
+        let deps = [dep(Ecosystem::CratesIo, "smallvec", "1.6.0"), dep(Ecosystem::CratesIo, "time", "0.1.45")];
+        let (_, old) = import("cargo-audit-0.14.json", &deps);
+        let (snapshot, new) = import("cargo-audit-0.18.json", &deps);
+        assert_eq!(snapshot.source, "cargo-audit");
+        assert_eq!(ids(&old), [(0, "RUSTSEC-2021-0003", Some("1.6.1"))]);
+        assert_eq!(
+            ids(&new),
+            [(0, "RUSTSEC-2021-0003", Some("1.6.1")), (1, "RUSTSEC-2020-0071", Some("0.2.23"))]
+        );
+        let smallvec = &new[0];
+        assert_eq!(smallvec.aliases, ["CVE-2021-25900", "GHSA-43w2-9j62-hq99"]);
+        assert_eq!(smallvec.published.as_deref(), Some("2021-01-08T00:00:00Z"));
+        assert_eq!(smallvec.categories, ["memory-corruption"]);
+        assert_eq!(smallvec.references.len(), 2);
+        assert_eq!(smallvec.provenance.database.as_deref(), Some("RUSTSEC"));
+    }
+
+    #[test]
+    fn files_of_other_shapes_are_refused() {
+        let root = crate::testutil::scratch_dir("imported");
+        fs::write(root.join("sarif.json"), r#"{"version": "2.1.0", "runs": []}"#).unwrap();
+        let mut source = ImportedResults::new(root.join("sarif.json"));
+        let error = source.refresh(&root, SystemTime::now()).err().unwrap();
+        assert!(error.ends_with("sarif.json: not npm audit, pip-audit or cargo audit JSON output"), "{}", error);
+        assert!(source.query(&[dep(Ecosystem::Npm, "lodash", "4.17.20")]).is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
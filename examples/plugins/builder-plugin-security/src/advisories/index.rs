@@ -0,0 +1,231 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::fsutil;
+
+/// Bookkeeping charged per buffered entry on top of its bytes, so budgets
+/// stay honest for many small entries.
+const ENTRY_OVERHEAD: usize = 64;
+
+/// Builds an [`Index`] from unsorted entries with bounded memory: entries are
+/// buffered up to the budget, spilled as sorted runs, and merged at the end.
+pub struct IndexWriter {
+    dir: PathBuf,
+    budget: usize,
+    buffer: Vec<(String, String)>,
+    buffered: usize,
+    runs: Vec<PathBuf>,
+    /// Tells apart the runs of writers at work at once in the process.
+    id: usize,
+}
+
+static WRITERS: AtomicUsize = AtomicUsize::new(0);
+
+impl IndexWriter {
+    /// A writer spilling its runs into `dir`.
+    pub fn new(dir: &Path, budget_bytes: usize) -> Self {
+        IndexWriter {
+            dir: dir.to_path_buf(),
+            budget: budget_bytes,
+            buffer: Vec::new(),
+            buffered: 0,
+            runs: Vec::new(),
+            id: WRITERS.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    pub fn insert(&mut self, key: String, value: String) -> io::Result<()> {
+        debug_assert!(!key.contains(['\t', '\n']) && !value.contains('\n'));
+        self.buffered += key.len() + value.len() + ENTRY_OVERHEAD;
+        self.buffer.push((key, value));
+        if self.buffered >= self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.buffer.sort();
+        let path = self.dir.join(format!(".index-run-{}-{}-{}", std::process::id(), self.id, self.runs.len()));
+        let mut out = BufWriter::new(fsutil::create(&path)?);
+        for (key, value) in self.buffer.drain(..) {
+            writeln!(out, "{}\t{}", key, value)?;
+        }
+        out.flush()?;
+        self.buffer.shrink_to_fit();
+        self.buffered = 0;
+        self.runs.push(path);
+        Ok(())
+    }
+
+    /// Merge everything inserted into the index at `path`, replacing it
+    /// atomically. Returns the number of entries written.
+    pub fn finish(mut self, path: &Path) -> io::Result<usize> {
+        self.spill()?;
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("index");
+        let tmp = path.with_file_name(format!(".{}.tmp.{}.{}", file_name, std::process::id(), self.id));
+        let result = merge_runs(&self.runs, &tmp).and_then(|entries| {
+            fsutil::rename(&tmp, path)?;
+            fsutil::sync_dir(path.parent().unwrap_or(Path::new(".")));
+            Ok(entries)
+        });
+        if result.is_err() {
+            let _ = fsutil::remove_file(&tmp);
+        }
+        for run in &self.runs {
+            let _ = fsutil::remove_file(run);
+        }
+        result
+    }
+}
+
+/// Merge the sorted `runs` into `tmp`, synced to disk before it is renamed
+/// into place.
+fn merge_runs(runs: &[PathBuf], tmp: &Path) -> io::Result<usize> {
+    let mut out = BufWriter::new(fsutil::create(tmp)?);
+
+    let mut readers = runs
+        .iter()
+        .map(|run| fsutil::open(run).map(|f| BufReader::new(f).lines()))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::new();
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(line) = reader.next().transpose()? {
+            heap.push(Reverse((line, i)));
+        }
+    }
+
+    let mut entries = 0;
+    while let Some(Reverse((line, i))) = heap.pop() {
+        out.write_all(line.as_bytes())?;
+        out.write_all(b"\n")?;
+        entries += 1;
+        if let Some(next) = readers[i].next().transpose()? {
+            heap.push(Reverse((next, i)));
+        }
+    }
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(entries)
+}
+
+/// Sorted key-value file used as the on-disk advisory index.
+///
+/// Each line is `key\tvalue\n`, sorted, with keys free of tabs and newlines
+/// and values free of newlines (compact JSON). Lookups binary-search the file
+/// through seeks, so only the lines on the search path and the matching
+/// entries are ever in memory.
+pub struct Index {
+    reader: BufReader<File>,
+    len: u64,
+}
+
+/// One line of the index, with where it starts and where the next begins.
+struct Entry {
+    key: String,
+    value: String,
+    next: u64,
+}
+
+impl Index {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = fsutil::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Index {
+            reader: BufReader::new(file),
+            len,
+        })
+    }
+
+    /// Values stored under `key`, in sorted order.
+    pub fn get(&mut self, key: &str) -> io::Result<Vec<String>> {
+        // Smallest position whose following line has a key >= `key`
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.entry_at_or_after(mid)? {
+                Some(entry) if entry.key.as_str() < key => lo = entry.next,
+                _ => hi = mid,
+            }
+        }
+
+        let mut values = Vec::new();
+        let mut position = lo;
+        while let Some(entry) = self.entry_at_or_after(position)? {
+            if entry.key != key {
+                break;
+            }
+            values.push(entry.value);
+            position = entry.next;
+        }
+        Ok(values)
+    }
+
+    /// The first line starting at or after byte `position`.
+    fn entry_at_or_after(&mut self, position: u64) -> io::Result<Option<Entry>> {
+        let mut start = position;
+        if position > 0 {
+            // Skip to the end of the line holding `position - 1`, so a
+            // `position` that starts a line finds that line
+            self.reader.seek(SeekFrom::Start(position - 1))?;
+            let mut partial = Vec::new();
+            start = position - 1 + self.reader.read_until(b'\n', &mut partial)? as u64;
+        } else {
+            self.reader.seek(SeekFrom::Start(0))?;
+        }
+        if start >= self.len {
+            return Ok(None);
+        }
+
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line)?;
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        let (key, value) = line
+            .split_once('\t')
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "index line without a key"))?;
+        Ok(Some(Entry {
+            key: key.to_string(),
+            value: value.to_string(),
+            next: start + read as u64,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn spilled_runs_merge_into_a_searchable_index() {
+        let dir = crate::testutil::scratch_dir("index");
+
+        // A tiny budget forces a run per few entries
+        let mut writer = IndexWriter::new(&dir, 200);
+        for i in (0..500).rev() {
+            writer.insert(format!("npm/pkg-{:04}", i), format!("{{\"n\":{}}}", i)).unwrap();
+        }
+        writer.insert("npm/pkg-0042".to_string(), "{\"n\":\"dup\"}".to_string()).unwrap();
+        let path = dir.join("index.kv");
+        assert_eq!(writer.finish(&path).unwrap(), 501);
+
+        let leftovers: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(leftovers, vec!["index.kv"]);
+
+        let mut index = Index::open(&path).unwrap();
+        assert_eq!(index.get("npm/pkg-0000").unwrap(), vec!["{\"n\":0}"]);
+        assert_eq!(index.get("npm/pkg-0499").unwrap(), vec!["{\"n\":499}"]);
+        assert_eq!(index.get("npm/pkg-0042").unwrap(), vec!["{\"n\":\"dup\"}", "{\"n\":42}"]);
+        assert!(index.get("npm/pkg-0500").unwrap().is_empty());
+        assert!(index.get("PyPI/django").unwrap().is_empty());
+        assert!(index.get("").unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,741 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+use crate::config::ScanConfig;
+use crate::ecosystems::{Dependency, Ecosystem};
+use crate::paths;
+use crate::report::DbSnapshot;
+use crate::sanitize;
+use crate::severity::Severity;
+use crate::suppressions::Suppression;
+use crate::Vulnerability;
+
+mod builtin;
+mod imported;
+mod index;
+mod osv;
+mod private;
+mod querycache;
+pub mod schedule;
+
+pub use builtin::BuiltinDemo;
+pub use imported::ImportedResults;
+pub use osv::LocalDb;
+pub use private::PrivateAdvisories;
+pub use querycache::QueryCache;
+
+/// Whether refreshes and query caches are kept in memory for the scans
+/// after them, as in `--daemon` mode.
+static KEEP_WARM: AtomicBool = AtomicBool::new(false);
+
+/// Keep what the local database refreshes to, and each source's query
+/// cache, in memory for the later scans in the process, each within
+/// `db_memory_budget_mb`. Nothing is kept otherwise.
+pub fn keep_warm() {
+    KEEP_WARM.store(true, Ordering::Relaxed);
+}
+
+/// Drop what [`keep_warm`] kept, so the next scan reads it from disk.
+pub fn forget_warm() {
+    osv::forget_warm();
+    querycache::forget_warm();
+}
+
+/// Whether the process keeps warm, by [`keep_warm`].
+pub fn keeping_warm() -> bool {
+    KEEP_WARM.load(Ordering::Relaxed)
+}
+
+/// A finding as a source reports it, before merging.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RawFinding {
+    /// Index of the affected dependency in the slice passed to `query`.
+    #[serde(skip)]
+    pub dependency: usize,
+    pub id: String,
+    /// Other ids the source knows this advisory by (CVE, GHSA, ...).
+    pub aliases: Vec<String>,
+    /// Severity label as the source spells it; normalized when merged.
+    pub severity: String,
+    pub cvss_score: Option<f64>,
+    /// The advisory's own short title; empty when it has none.
+    pub summary: String,
+    pub description: String,
+    pub fixed_in: Option<String>,
+    pub references: Vec<Reference>,
+    /// Go packages (import paths) of the module the advisory is limited
+    /// to; empty when it affects the whole module.
+    pub packages: Vec<String>,
+    /// When the advisory was published (RFC 3339), if the source says.
+    #[serde(default)]
+    pub published: Option<String>,
+    /// CWE ids as the source spells them; normalized when merged.
+    #[serde(default)]
+    pub cwe: Vec<String>,
+    /// The source's categories of the advisory (RustSec's
+    /// `memory-corruption`, `denial-of-service`, ...).
+    #[serde(default)]
+    pub categories: Vec<String>,
+    pub provenance: Provenance,
+}
+
+/// CWE id of findings without CWE data.
+pub const UNKNOWN_CWE: &str = "UNKNOWN";
+
+/// The CWE ids of a finding without any: just [`UNKNOWN_CWE`].
+pub fn unknown_cwe() -> Vec<String> {
+    vec![UNKNOWN_CWE.to_string()]
+}
+
+/// `CWE-79` for `CWE-79`, `cwe-79` and `79`; `None` for anything naming no
+/// weakness class, such as NVD's `NVD-CWE-noinfo` and `NVD-CWE-Other`.
+fn normalize_cwe(id: &str) -> Option<String> {
+    let id = id.trim();
+    let number = match id.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("CWE-") => &id[4..],
+        _ => id,
+    };
+    if !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    number.parse::<u32>().ok().map(|number| format!("CWE-{}", number))
+}
+
+/// A finding's CWE ids, sorted and normalized; [`unknown_cwe`] when none of
+/// `ids` names a weakness class.
+pub fn cwe_ids<'a>(ids: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let mut cwe: Vec<String> = ids.into_iter().filter_map(|id| normalize_cwe(id)).collect();
+    cwe.sort_by_key(|id| id[4..].parse::<u32>().unwrap_or(u32::MAX));
+    cwe.dedup();
+    if cwe.is_empty() {
+        return unknown_cwe();
+    }
+    cwe
+}
+
+/// Where a finding came from and why it matched, so one that looks wrong
+/// can be traced back to the data and rule behind it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Provenance {
+    /// The advisory source, as the report's `databases` name it.
+    pub source: String,
+    /// Upstream database of the advisory, from its id prefix (`GHSA`,
+    /// `RUSTSEC`, `PYSEC`); absent when the id has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub database: Option<String>,
+    /// Id of the source's snapshot that was queried.
+    pub snapshot: String,
+    pub matcher: Matcher,
+    /// The affected versions that matched: `>=2.0, <2.2.24`, or `=2.2.0`
+    /// for a version the advisory lists.
+    pub range: String,
+    /// How likely a heuristic match is to be right, from 0 to 1; absent
+    /// for exact and range matches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+/// How a dependency was matched against an advisory.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Matcher {
+    /// The advisory lists the dependency's version.
+    Exact,
+    /// The version falls within one of the advisory's ranges.
+    Range,
+    /// The package name only resembles the advisory's.
+    Heuristic,
+}
+
+/// Why a finding is reported at a lower severity than its advisory's.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Downgrade {
+    /// Severity the advisory gives.
+    pub from: Severity,
+    pub reason: String,
+}
+
+/// Set when `severity_overrides` replaced the severity the sources gave.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SeverityOverride {
+    pub original_severity: Severity,
+    /// The `severity_overrides` entry that matched.
+    pub overridden_by: String,
+}
+
+/// Reason given for Go findings whose affected packages no source imports.
+pub const NOT_IMPORTED: &str = "affected package not imported";
+
+/// A link from an advisory to more about it, as OSV lists them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Reference {
+    /// `ADVISORY`, `FIX`, `REPORT`, `WEB`, ...
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    pub url: String,
+}
+
+/// One advisory database.
+///
+/// Sources only fetch and match; deduplication, severity normalization and
+/// suppression happen once for all of them in [`merge`]. They are `Send` so
+/// a refresh can be abandoned on a helper thread when it overruns its
+/// budget.
+pub trait AdvisorySource: Send {
+    /// Load or update the source's data, using `cache` for anything it
+    /// stores between runs. `now` is the scan's clock, for the data's age.
+    fn refresh(&mut self, cache: &Path, now: SystemTime) -> Result<DbSnapshot, String>;
+
+    /// Advisories affecting any of `deps`.
+    fn query(&self, deps: &[Dependency]) -> Vec<RawFinding>;
+
+    /// Only `ecosystems` will be queried; called before `refresh`, so a
+    /// source can skip loading the rest.
+    fn restrict(&mut self, _ecosystems: &[Ecosystem]) {}
+
+    /// The ecosystems of the scan's dependency files; called before
+    /// `refresh`, so a partial refresh can load those first.
+    fn prioritize(&mut self, _ecosystems: &[Ecosystem]) {}
+
+    /// Whether `refresh` writes to `cache`; such refreshes hold the cache's
+    /// exclusive lock, and scans querying the data a shared one.
+    fn caches(&self) -> bool {
+        false
+    }
+
+    /// What `refresh` would read, without reading it, for a dry run;
+    /// `None` when the source can't tell.
+    fn estimate(&self) -> Option<Estimate> {
+        None
+    }
+}
+
+/// The advisory data a source's refresh would read.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Estimate {
+    /// The setting the source comes from: `db_path`, `db_snapshot`,
+    /// `extra_advisories`, `import_results` or `demo_db`.
+    pub setting: &'static str,
+    /// The file or directory read; `None` for data compiled into the plugin.
+    pub path: Option<String>,
+    /// Bytes the refresh reads at most.
+    pub bytes: u64,
+}
+
+/// The sources enabled by `config`, in priority order: when two sources
+/// report the same advisory, the first one's id is kept. The demo dataset
+/// stands in only when nothing else is configured. Files are looked up
+/// relative to `workspace_root`.
+pub fn configured(config: &ScanConfig, workspace_root: &Path) -> Vec<Box<dyn AdvisorySource>> {
+    let mut sources: Vec<Box<dyn AdvisorySource>> = Vec::new();
+    if config.db_path.is_some() || config.db_snapshot.is_some() {
+        let path = config.db_path.as_deref().map(|path| paths::join_source(workspace_root, path));
+        let path = path.as_deref().map(Path::to_string_lossy).unwrap_or_default();
+        let db = LocalDb::new(&path, &config.db_index, config.db_memory_budget_mb);
+        let db = db.pinned(config.db_snapshot.as_deref()).refreshing(&config.db_refresh);
+        let db = db.every(config.db_refresh_interval());
+        sources.push(Box::new(db.warm(keeping_warm())));
+    }
+    if let Some(path) = &config.extra_advisories {
+        sources.push(Box::new(PrivateAdvisories::new(paths::join_source(workspace_root, path))));
+    }
+    for path in &config.import_results {
+        sources.push(Box::new(ImportedResults::new(paths::join_source(workspace_root, path))));
+    }
+    if sources.is_empty() && config.demo_db {
+        sources.push(Box::new(BuiltinDemo));
+    }
+    sources
+}
+
+/// Findings that survived merging, and those that were suppressed.
+pub struct Merged {
+    pub vulnerabilities: Vec<Vulnerability>,
+    pub suppressed: Vec<Vulnerability>,
+}
+
+/// Turn every source's raw findings into the reported vulnerabilities.
+///
+/// Findings for the same dependency that share an id or alias are folded
+/// into one, keeping the most severe rating, the highest CVSS score, the
+/// first known fix and every reference. References that aren't http(s)
+/// URLs are dropped here, before anything can emit them. Findings on the
+/// ignore list or covered by an unexpired suppression are dropped; expired
+/// suppressions that would have matched are logged so they get re-triaged
+/// rather than silently lapsing. The result is in report order.
+///
+/// `severity_overrides` then replace the severities they match, before
+/// anything is decided by severity.
+///
+/// `go_imports` are the packages the target's Go sources import, when it
+/// has any: Go findings limited to packages none of them imports are
+/// downgraded to `INFO`, with the reason recorded on the finding.
+pub fn merge(
+    deps: &[Dependency],
+    findings: Vec<RawFinding>,
+    config: &ScanConfig,
+    suppressions: &[Suppression],
+    today: &str,
+    go_imports: Option<&BTreeSet<String>>,
+    logs: &mut Vec<String>,
+) -> Merged {
+    let mut merged: Vec<(usize, Vulnerability)> = Vec::new();
+
+    for finding in findings {
+        let Some(dep) = deps.get(finding.dependency) else {
+            continue;
+        };
+        let severity = Severity::normalize(&finding.severity);
+
+        let existing = merged.iter_mut().find(|(index, vuln)| {
+            *index == finding.dependency && ids(vuln).any(|id| *id == finding.id || finding.aliases.contains(id))
+        });
+        match existing {
+            Some((_, vuln)) => {
+                vuln.severity = vuln.severity.min(severity);
+                vuln.cvss_score = match (vuln.cvss_score, finding.cvss_score) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    (a, b) => a.or(b),
+                };
+                if vuln.fixed_in.is_none() {
+                    vuln.fixed_in = finding.fixed_in;
+                }
+                if vuln.published.is_none() {
+                    vuln.published = finding.published;
+                }
+                vuln.cwe.extend(finding.cwe);
+                vuln.categories.extend(finding.categories);
+                if vuln.description.is_empty() {
+                    vuln.summary = sanitize::summary(&finding.summary, &finding.description);
+                    vuln.description = finding.description;
+                }
+                for alias in std::iter::once(finding.id).chain(finding.aliases) {
+                    if alias != vuln.id && !vuln.aliases.contains(&alias) {
+                        vuln.aliases.push(alias);
+                    }
+                }
+                add_references(&mut vuln.references, finding.references);
+                if !vuln.provenance.contains(&finding.provenance) {
+                    vuln.provenance.push(finding.provenance);
+                }
+                // A source that doesn't narrow the advisory down wins
+                if finding.packages.is_empty() {
+                    vuln.affected_packages.clear();
+                } else if !vuln.affected_packages.is_empty() {
+                    vuln.affected_packages.extend(finding.packages);
+                    vuln.affected_packages.sort();
+                    vuln.affected_packages.dedup();
+                }
+            }
+            None => merged.push((
+                finding.dependency,
+                Vulnerability {
+                    aliases: finding.aliases,
+                    cvss_score: finding.cvss_score,
+                    summary: sanitize::summary(&finding.summary, &finding.description),
+                    fixed_in: finding.fixed_in,
+                    file: Some(dep.file.clone()),
+                    line: Some(dep.line),
+                    column: dep.col,
+                    project: dep.project.clone(),
+                    registry: dep.registry.clone(),
+                    references: {
+                        let mut references = Vec::new();
+                        add_references(&mut references, finding.references);
+                        references
+                    },
+                    affected_packages: finding.packages,
+                    cwe: finding.cwe,
+                    categories: finding.categories,
+                    provenance: vec![finding.provenance],
+                    published: finding.published,
+                    ..Vulnerability::new(&finding.id, severity, &dep.name, &dep.version, finding.description)
+                },
+            )),
+        }
+    }
+
+    let mut vulnerabilities = Vec::new();
+    let mut suppressed = Vec::new();
+    for (index, mut vuln) in merged {
+        vuln.url = canonical_url(&vuln.references, deps[index].ecosystem);
+        vuln.cwe = cwe_ids(&vuln.cwe);
+        vuln.categories = vuln.categories.iter().map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty()).collect();
+        vuln.categories.sort();
+        vuln.categories.dedup();
+        let known_as: Vec<&str> = ids(&vuln).map(String::as_str).collect();
+        if let Some((key, severity)) = config.severity_override(&known_as) {
+            vuln.severity_override = Some(SeverityOverride {
+                original_severity: vuln.severity,
+                overridden_by: key.to_string(),
+            });
+            vuln.severity = severity;
+        }
+        if let Some(imports) = go_imports.filter(|_| deps[index].ecosystem == Ecosystem::Go) {
+            if !vuln.affected_packages.is_empty() && !vuln.affected_packages.iter().any(|p| is_imported(p, imports)) {
+                vuln.downgrade = Some(Downgrade {
+                    from: vuln.severity,
+                    reason: NOT_IMPORTED.to_string(),
+                });
+                vuln.severity = Severity::Info;
+            }
+        }
+        if ids(&vuln).any(|id| config.is_ignored(id)) {
+            continue;
+        }
+        if is_suppressed(&vuln, suppressions, today, logs) {
+            suppressed.push(vuln);
+            continue;
+        }
+        vulnerabilities.push(vuln);
+    }
+
+    sort(&mut vulnerabilities);
+
+    Merged {
+        vulnerabilities,
+        suppressed,
+    }
+}
+
+/// Put findings in report order, a total order so repeated scans produce
+/// identical reports: severity, then CVSS score (highest first, unscored
+/// last), package, id.
+pub fn sort(vulnerabilities: &mut [Vulnerability]) {
+    vulnerabilities.sort_by(|a, b| {
+        a.severity
+            .cmp(&b.severity)
+            .then_with(|| {
+                let score = |v: &Vulnerability| v.cvss_score.unwrap_or(f64::NEG_INFINITY);
+                score(b).total_cmp(&score(a))
+            })
+            .then_with(|| a.package.cmp(&b.package))
+            .then_with(|| a.id.cmp(&b.id))
+    });
+}
+
+/// Add the http(s) references not already listed.
+fn add_references(references: &mut Vec<Reference>, new: Vec<Reference>) {
+    for reference in new {
+        if sanitize::is_web_url(&reference.url) && !references.iter().any(|r| r.url == reference.url) {
+            references.push(reference);
+        }
+    }
+}
+
+/// The link a finding is shown with: the ecosystem's own advisory page,
+/// else the first `ADVISORY` reference, else the first reference.
+fn canonical_url(references: &[Reference], ecosystem: Ecosystem) -> Option<String> {
+    let on_advisory_page = |r: &&Reference| {
+        let location = r.url.split_once("://").map_or("", |(_, rest)| rest);
+        let location = location.strip_prefix("www.").unwrap_or(location);
+        ecosystem.advisory_pages().iter().any(|page| location.starts_with(page))
+    };
+    references
+        .iter()
+        .find(on_advisory_page)
+        .or_else(|| references.iter().find(|r| r.kind == "ADVISORY"))
+        .or_else(|| references.first())
+        .map(|r| r.url.clone())
+}
+
+/// Whether `package` or one of its subpackages is imported. Subpackages
+/// count so the check errs towards keeping a finding's severity.
+fn is_imported(package: &str, imports: &BTreeSet<String>) -> bool {
+    imports
+        .iter()
+        .any(|import| import.strip_prefix(package).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+}
+
+/// A finding's id, then its aliases.
+pub fn ids(vuln: &Vulnerability) -> impl Iterator<Item = &String> {
+    std::iter::once(&vuln.id).chain(&vuln.aliases)
+}
+
+/// Whether a finding is covered by an unexpired suppression entry, under
+/// its id or any alias.
+fn is_suppressed(vuln: &Vulnerability, suppressions: &[Suppression], today: &str, logs: &mut Vec<String>) -> bool {
+    let mut suppressed = false;
+    for entry in suppressions
+        .iter()
+        .filter(|s| ids(vuln).any(|id| s.matches(id, &vuln.package, &vuln.cwe)))
+    {
+        if entry.is_expired(today) {
+            logs.push(format!(
+                "  ⚠ Suppression of {} ({}) expired on {}",
+                vuln.id,
+                vuln.package,
+                entry.expires.as_deref().unwrap_or_default()
+            ));
+        } else {
+            suppressed = true;
+        }
+    }
+    suppressed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::{Ecosystem, Scope};
+
+    /// A source returning canned findings.
+    struct Fixed(Vec<RawFinding>);
+
+    impl AdvisorySource for Fixed {
+        fn refresh(&mut self, _cache: &Path, _now: SystemTime) -> Result<DbSnapshot, String> {
+            Ok(DbSnapshot {
+                source: "fixed".to_string(),
+                id: "fixed@1".to_string(),
+                sha256: None,
+                fetched_at: None,
+                age_secs: None,
+                freshness: Vec::new(),
+            })
+        }
+
+        fn query(&self, _deps: &[Dependency]) -> Vec<RawFinding> {
+            self.0.clone()
+        }
+    }
+
+    fn dep(name: &str, line: usize) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            ecosystem: Ecosystem::Npm,
+            scope: Scope::Runtime,
+            file: "package.json".to_string(),
+            line,
+            col: Some(5),
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    fn raw(dependency: usize, id: &str, aliases: &[&str], severity: &str, cvss: Option<f64>) -> RawFinding {
+        RawFinding {
+            dependency,
+            id: id.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            severity: severity.to_string(),
+            cvss_score: cvss,
+            summary: String::new(),
+            description: String::new(),
+            fixed_in: None,
+            references: Vec::new(),
+            packages: Vec::new(),
+            published: None,
+            cwe: Vec::new(),
+            categories: Vec::new(),
+            provenance: Provenance {
+                source: "fixed".to_string(),
+                database: id.split_once('-').map(|(prefix, _)| prefix.to_string()),
+                snapshot: "fixed@1".to_string(),
+                matcher: Matcher::Exact,
+                range: "=1.0.0".to_string(),
+                confidence: None,
+            },
+        }
+    }
+
+    #[test]
+    fn aliases_across_sources_are_merged() {
+        let deps = [dep("lodash", 3), dep("minimist", 4)];
+        let osv = Fixed(vec![
+            raw(0, "GHSA-p6mc", &["CVE-2020-8203"], "moderate", Some(5.3)),
+            raw(1, "GHSA-xvch", &[], "low", None),
+        ]);
+        let nvd = Fixed(vec![
+            raw(0, "CVE-2020-8203", &[], "HIGH", Some(7.4)),
+            // Same id on another dependency stays a separate finding
+            raw(1, "CVE-2020-8203", &[], "high", None),
+        ]);
+        let findings = [osv, nvd].iter().flat_map(|s| s.query(&deps)).collect();
+
+        let merged = merge(&deps, findings, &ScanConfig::default(), &[], "2024-01-01", None, &mut Vec::new());
+        let summary: Vec<_> = merged
+            .vulnerabilities
+            .iter()
+            .map(|v| (v.id.as_str(), v.package.as_str(), v.severity, v.cvss_score, v.aliases.clone(), v.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("GHSA-p6mc", "lodash", Severity::High, Some(7.4), vec!["CVE-2020-8203".to_string()], Some(3)),
+                ("CVE-2020-8203", "minimist", Severity::High, None, vec![], Some(4)),
+                ("GHSA-xvch", "minimist", Severity::Low, None, vec![], Some(4)),
+            ]
+        );
+        // Each source that reported a finding is on record
+        let databases: Vec<_> = merged.vulnerabilities[0].provenance.iter().map(|p| p.database.as_deref()).collect();
+        assert_eq!(databases, vec![Some("GHSA"), Some("CVE")]);
+    }
+
+    #[test]
+    fn every_source_records_provenance() {
+        let mut builtin_dep = dep("lodash-es", 1);
+        builtin_dep.version = "4.17.15".to_string();
+        let found = BuiltinDemo.query(&[builtin_dep]);
+        let expected = Provenance {
+            source: "builtin-demo".to_string(),
+            database: None,
+            snapshot: format!("builtin-demo@{}", crate::PLUGIN_VERSION),
+            matcher: Matcher::Heuristic,
+            range: "=4.17.15".to_string(),
+            confidence: Some(0.67),
+        };
+        assert_eq!(found[0].provenance, expected);
+
+        let dir = crate::testutil::scratch_dir("advisory-provenance");
+        std::fs::create_dir_all(dir.join("db")).unwrap();
+        let advisory = serde_json::json!({
+            "id": "GHSA-p6mc",
+            "affected": [
+                {"package": {"ecosystem": "npm", "name": "lodash"}, "versions": ["1.0.0"]},
+                {"package": {"ecosystem": "npm", "name": "minimist"},
+                 "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0.1"}, {"fixed": "1.2.2"}]}]}
+            ]
+        });
+        std::fs::write(dir.join("db/GHSA-p6mc.json"), advisory.to_string()).unwrap();
+        for mode in ["memory", "disk"] {
+            let mut osv = LocalDb::new(dir.join("db").to_str().unwrap(), mode, 1);
+            let snapshot = osv.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+            let found: Vec<_> = osv
+                .query(&[dep("lodash", 1), dep("minimist", 2)])
+                .into_iter()
+                .map(|f| (f.provenance.matcher, f.provenance.range, f.provenance.database, f.provenance.snapshot))
+                .collect();
+            let database = Some("GHSA".to_string());
+            assert_eq!(
+                found,
+                vec![
+                    (Matcher::Exact, "=1.0.0".to_string(), database.clone(), snapshot.id.clone()),
+                    (Matcher::Range, ">=0.1, <1.2.2".to_string(), database, snapshot.id),
+                ],
+                "{}",
+                mode
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ignore_list_and_suppressions_apply_to_aliases() {
+        let deps = [dep("lodash", 3), dep("express", 4)];
+        let findings = vec![
+            raw(0, "GHSA-p6mc", &["CVE-2020-8203"], "high", None),
+            raw(1, "GHSA-rv95", &["CVE-2022-24999"], "high", None),
+        ];
+        let config = ScanConfig {
+            ignore_vulnerabilities: vec!["CVE-2022-24999".to_string()],
+            ..ScanConfig::default()
+        };
+        let suppressions = [Suppression {
+            id: "CVE-2020-8203".to_string(),
+            reason: "not reachable".to_string(),
+            ..Suppression::default()
+        }];
+
+        let merged = merge(&deps, findings, &config, &suppressions, "2024-01-01", None, &mut Vec::new());
+        assert!(merged.vulnerabilities.is_empty());
+        assert_eq!(merged.suppressed.len(), 1);
+    }
+
+    #[test]
+    fn cwe_ids_and_categories_are_read_merged_and_suppressible() {
+        let dir = crate::testutil::scratch_dir("cwe");
+        std::fs::create_dir_all(dir.join("db")).unwrap();
+        let advisories = [
+            serde_json::json!({
+                "id": "GHSA-redos",
+                "database_specific": {"cwe_ids": ["CWE-1333", "NVD-CWE-noinfo", "cwe-400"]},
+                "affected": [{"package": {"ecosystem": "npm", "name": "lodash"}, "versions": ["1.0.0"],
+                              "database_specific": {"categories": ["Denial-Of-Service"]}}]
+            }),
+            serde_json::json!({
+                "id": "GHSA-overflow",
+                "database_specific": {"cwe_ids": ["CWE-787"]},
+                "affected": [{"package": {"ecosystem": "npm", "name": "express"}, "versions": ["1.0.0"]}]
+            }),
+            serde_json::json!({
+                "id": "GHSA-noinfo",
+                "database_specific": {"cwe_ids": ["NVD-CWE-noinfo"]},
+                "affected": [{"package": {"ecosystem": "npm", "name": "minimist"}, "versions": ["1.0.0"]}]
+            }),
+        ];
+        for advisory in &advisories {
+            let path = dir.join(format!("db/{}.json", advisory["id"].as_str().unwrap()));
+            std::fs::write(path, advisory.to_string()).unwrap();
+        }
+        let mut osv = LocalDb::new(dir.join("db").to_str().unwrap(), "disk", 1);
+        osv.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+        let deps = [dep("lodash", 1), dep("express", 2), dep("minimist", 3)];
+        let findings = osv.query(&deps);
+        let suppressions = [Suppression {
+            cwe: Some("CWE-787".to_string()),
+            reason: "bounds checked by the caller".to_string(),
+            ..Suppression::default()
+        }];
+
+        let merged = merge(&deps, findings, &ScanConfig::default(), &suppressions, "2024-01-01", None, &mut Vec::new());
+        let tagged: Vec<(&str, Vec<&str>, Vec<&str>)> = merged
+            .vulnerabilities
+            .iter()
+            .map(|v| {
+                let cwe = v.cwe.iter().map(String::as_str).collect();
+                (v.id.as_str(), cwe, v.categories.iter().map(String::as_str).collect())
+            })
+            .collect();
+        assert_eq!(
+            tagged,
+            [
+                ("GHSA-redos", vec!["CWE-400", "CWE-1333"], vec!["denial-of-service"]),
+                ("GHSA-noinfo", vec![UNKNOWN_CWE], vec![]),
+            ]
+        );
+        assert_eq!(merged.suppressed[0].id, "GHSA-overflow");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn references_are_merged_and_link_the_ecosystem_advisory_page() {
+        let reference = |kind: &str, url: &str| Reference {
+            kind: kind.to_string(),
+            url: url.to_string(),
+        };
+        let deps = [dep("lodash", 3)];
+        let mut nvd = raw(0, "CVE-2020-8203", &[], "high", None);
+        nvd.references = vec![
+            reference("ADVISORY", "https://nvd.nist.gov/vuln/detail/CVE-2020-8203"),
+            reference("WEB", "javascript:alert(1)"),
+        ];
+        let mut osv = raw(0, "GHSA-p6mc", &["CVE-2020-8203"], "high", None);
+        osv.references = vec![
+            reference("FIX", "https://github.com/lodash/lodash/commit/c84fe82"),
+            reference("ADVISORY", "https://github.com/advisories/GHSA-p6mc-m468-83gw"),
+            reference("ADVISORY", "https://nvd.nist.gov/vuln/detail/CVE-2020-8203"),
+        ];
+
+        let merged = merge(&deps, vec![nvd, osv], &ScanConfig::default(), &[], "2024-01-01", None, &mut Vec::new());
+        let finding = &merged.vulnerabilities[0];
+        assert_eq!(finding.url.as_deref(), Some("https://github.com/advisories/GHSA-p6mc-m468-83gw"));
+        let urls: Vec<&str> = finding.references.iter().map(|r| r.url.as_str()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://nvd.nist.gov/vuln/detail/CVE-2020-8203",
+                "https://github.com/lodash/lodash/commit/c84fe82",
+                "https://github.com/advisories/GHSA-p6mc-m468-83gw",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1262 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime};
+
+use super::index::{Index, IndexWriter};
+use super::schedule::Schedule;
+use super::{AdvisorySource, Estimate, Matcher, Provenance, RawFinding, Reference};
+use crate::clock;
+use crate::ecosystems::{Dependency, Ecosystem};
+use crate::fsutil;
+use crate::permissions;
+use crate::report::{self, DbSnapshot, EcosystemFreshness};
+use crate::snapshots::{self, Snapshot};
+
+/// Version of the indexed entry layout; part of the fingerprint and snapshot
+/// id so indexes written by older versions are rebuilt rather than misread.
+const INDEX_FORMAT: u32 = 8;
+
+/// OSV-format advisories (one JSON document per file, as in the OSV
+/// exports) read from `db_path`, or the cached snapshot it is pinned to.
+pub struct LocalDb {
+    path: PathBuf,
+    mode: String,
+    budget_bytes: usize,
+    pinned: Option<String>,
+    /// `full` or `partial`, as `db_refresh` says.
+    refresh: String,
+    /// Ecosystems the memory store keeps; `None` for every one.
+    only: Option<Vec<Ecosystem>>,
+    /// Ecosystems a partial refresh loads first, and a scheduled one
+    /// records as used.
+    needed: Option<Vec<Ecosystem>>,
+    /// How long a scheduled refresh serves the used ecosystems from their
+    /// snapshot before a changed database is read again.
+    interval: Duration,
+    store: Store,
+    /// Id of the snapshot the last refresh loaded.
+    snapshot: String,
+    /// Whether a full refresh is kept for later ones in the process.
+    warm: bool,
+}
+
+const SOURCE: &str = "osv-local";
+
+/// Fingerprints of the snapshots being built in the background after a
+/// partial refresh, so later scans in the process don't build them again.
+static BUILDING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// The last full refresh of a [`LocalDb::warm`] database. Later refreshes
+/// of the same database with the same settings take it instead of reading
+/// the files.
+static WARM: Mutex<Option<Warm>> = Mutex::new(None);
+
+struct Warm {
+    /// The cache, database and settings it was refreshed with.
+    key: String,
+    snapshot: Snapshot,
+    store: Store,
+}
+
+/// Drop the kept refresh, so the next one reads the database.
+pub(super) fn forget_warm() {
+    *WARM.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+#[derive(Clone)]
+enum Store {
+    Unloaded,
+    Memory(Arc<HashMap<String, Vec<Affected>>>),
+    Disk(PathBuf),
+    /// After a partial refresh: the current advisories of `fresh_ecosystems`
+    /// in memory, the others from the index of snapshot `stale`.
+    Partial {
+        fresh: HashMap<String, Vec<Affected>>,
+        fresh_ecosystems: Vec<Ecosystem>,
+        stale: String,
+        index: PathBuf,
+    },
+}
+
+/// What the index keeps of an advisory for one affected package.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(super) struct Affected {
+    pub(super) id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) aliases: Vec<String>,
+    /// Distribution release of the ecosystem (`12` of `Debian:12`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) release: Option<String>,
+    pub(super) severity: String,
+    pub(super) summary: String,
+    /// The advisory's full text, often markdown.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(super) details: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) versions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) ranges: Vec<Interval>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) references: Vec<Reference>,
+    /// Go packages the advisory is limited to (`ecosystem_specific.imports`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) packages: Vec<String>,
+    /// When the advisory was published, as RFC 3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) published: Option<String>,
+    /// `database_specific.cwe_ids` of the advisory and the package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) cwe: Vec<String>,
+    /// `database_specific.categories` of the advisory and the package.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) categories: Vec<String>,
+}
+
+/// `introduced <= v < fixed`, or `<= last_affected`; open ends are `None`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(super) struct Interval {
+    pub(super) introduced: Option<String>,
+    pub(super) fixed: Option<String>,
+    pub(super) last_affected: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OsvAdvisory {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    details: String,
+    #[serde(default)]
+    published: Option<String>,
+    #[serde(default)]
+    withdrawn: Option<String>,
+    #[serde(default)]
+    affected: Vec<OsvAffected>,
+    #[serde(default)]
+    database_specific: Option<Value>,
+    #[serde(default)]
+    references: Vec<Reference>,
+}
+
+#[derive(Deserialize)]
+struct OsvAffected {
+    package: OsvPackage,
+    #[serde(default)]
+    versions: Vec<String>,
+    #[serde(default)]
+    ranges: Vec<OsvRange>,
+    #[serde(default)]
+    ecosystem_specific: Option<Value>,
+    #[serde(default)]
+    database_specific: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct OsvPackage {
+    ecosystem: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct OsvRange {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    events: Vec<HashMap<String, String>>,
+}
+
+impl LocalDb {
+    pub const SOURCE: &'static str = SOURCE;
+
+    pub fn new(path: &str, mode: &str, budget_mb: u64) -> Self {
+        LocalDb {
+            path: PathBuf::from(path),
+            mode: mode.to_string(),
+            budget_bytes: usize::try_from(budget_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX),
+            pinned: None,
+            refresh: "full".to_string(),
+            only: None,
+            needed: None,
+            interval: Duration::from_secs(86_400),
+            store: Store::Unloaded,
+            snapshot: String::new(),
+            warm: false,
+        }
+    }
+
+    /// Keep a full refresh for the later refreshes in the process, until
+    /// `plugin.reload_db`: a memory store when its advisory files fit in
+    /// the budget, a disk store always.
+    pub fn warm(mut self, warm: bool) -> Self {
+        self.warm = warm;
+        self
+    }
+
+    /// Refresh a changed database by `strategy`: `full`, `partial` or
+    /// `scheduled`.
+    pub fn refreshing(mut self, strategy: &str) -> Self {
+        self.refresh = strategy.to_string();
+        self
+    }
+
+    /// With `scheduled` refreshes, read a changed database at most once per
+    /// `interval`.
+    pub fn every(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Query the cached snapshot `id` instead of the files under the path,
+    /// whatever they hold now.
+    pub fn pinned(mut self, id: Option<&str>) -> Self {
+        self.pinned = id.map(str::to_string);
+        self
+    }
+
+    /// Whether an index key's ecosystem is one the scan queries.
+    fn considers(&self, key: &str) -> bool {
+        self.only.as_ref().is_none_or(|only| keyed_in(key, only))
+    }
+
+    /// Whether a file may hold advisories the scan queries.
+    fn may_hold_considered(&self, file: &Path) -> bool {
+        self.only.as_ref().is_none_or(|only| self.may_hold(file, only))
+    }
+
+    /// Whether a file may hold advisories of `ecosystems`: not when it is in
+    /// a directory named after another ecosystem, as in the per-ecosystem
+    /// OSV exports (`npm/GHSA-....json`).
+    fn may_hold(&self, file: &Path, ecosystems: &[Ecosystem]) -> bool {
+        let relative = file.strip_prefix(&self.path).unwrap_or(file);
+        let dirs = relative.parent().into_iter().flat_map(|dir| dir.components());
+        let mut named = dirs.filter_map(|dir| Ecosystem::named(&dir.as_os_str().to_string_lossy()));
+        named.all(|ecosystem| ecosystems.contains(&ecosystem))
+    }
+
+    /// The snapshot of the files as they are: the cached one if they haven't
+    /// changed since, else a new one with its index. With `of`, it holds
+    /// only those ecosystems' advisories, and only files that may hold them
+    /// are read.
+    fn snapshot(
+        &self,
+        files: &[PathBuf],
+        dir: &Path,
+        fingerprint: &str,
+        of: Option<&[Ecosystem]>,
+    ) -> Result<Snapshot, String> {
+        if let Some(snapshot) = snapshots::find(dir, SOURCE, fingerprint) {
+            return Ok(snapshot);
+        }
+        let files: Vec<PathBuf> = match of {
+            Some(ecosystems) => files.iter().filter(|file| self.may_hold(file, ecosystems)).cloned().collect(),
+            None => files.to_vec(),
+        };
+        let ecosystems: Vec<String> = of.into_iter().flatten().map(|e| e.as_str().to_string()).collect();
+
+        // Name the snapshot by its contents, so the same data always gets
+        // the same id
+        let mut hasher = Sha256::new();
+        hasher.update(INDEX_FORMAT.to_le_bytes());
+        hasher.update(ecosystems.join(",").as_bytes());
+        let mut newest = None;
+        for file in &files {
+            let contents = fsutil::read(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            let name = file.strip_prefix(&self.path).unwrap_or(file).to_string_lossy().replace('\\', "/");
+            hasher.update(name.as_bytes());
+            hasher.update((contents.len() as u64).to_le_bytes());
+            hasher.update(&contents);
+            newest = newest.max(fs::metadata(file).and_then(|m| m.modified()).ok());
+        }
+        let sha256 = report::hex(&hasher.finalize());
+        let snapshot = Snapshot {
+            id: format!("{}@{}", SOURCE, &sha256[..16]),
+            source: SOURCE.to_string(),
+            sha256,
+            fingerprint: fingerprint.to_string(),
+            fetched_at: newest.map(report::format_timestamp),
+            fetched_at_unix: newest.map(clock::unix_secs),
+            last_used_unix: 0,
+            index_bytes: None,
+            ecosystems,
+        };
+        let index_path = snapshots::index_path(dir, &snapshot.id);
+        self.build_disk_index(&files, dir, &index_path, of)?;
+        let index_bytes = fs::metadata(&index_path).map_err(|e| format!("{}: {}", index_path.display(), e))?.len();
+        Ok(Snapshot { index_bytes: Some(index_bytes), ..snapshot })
+    }
+
+    /// With `db_refresh = "partial"` and files that changed since the last
+    /// snapshot, load only the current advisories of the needed ecosystems,
+    /// serve the others from the most recently used snapshot and build the
+    /// new one on a background thread. `None` when there is nothing to
+    /// refresh, no needed ecosystems were given or no snapshot can serve the
+    /// rest, for a full refresh instead.
+    ///
+    /// The background build writes its index and then its metadata
+    /// atomically, without the cache lock; it ends with the process.
+    fn refresh_partially(
+        &mut self,
+        files: &[PathBuf],
+        dir: &Path,
+        fingerprint: &str,
+        now: SystemTime,
+    ) -> Result<Option<DbSnapshot>, String> {
+        if self.refresh != "partial" || snapshots::find(dir, SOURCE, fingerprint).is_some() {
+            return Ok(None);
+        }
+        let Some(needed) = &self.needed else {
+            return Ok(None);
+        };
+        let latest = snapshots::list(dir).into_iter().map(|(s, _)| s);
+        // A scheduled refresh's snapshot lacks the unused ecosystems
+        let Some(mut stale) = latest.filter(|s| s.ecosystems.is_empty()).find(|s| s.source == SOURCE) else {
+            return Ok(None);
+        };
+        // Used now, so the background build's pruning keeps it
+        snapshots::touch(dir, &mut stale, now).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        let considered = self.only.as_deref().unwrap_or(&Ecosystem::ALL);
+        let fresh_ecosystems: Vec<Ecosystem> = needed.iter().copied().filter(|e| considered.contains(e)).collect();
+        let mut fresh: HashMap<String, Vec<Affected>> = HashMap::new();
+        for file in files.iter().filter(|file| self.may_hold(file, &fresh_ecosystems)) {
+            for (key, affected) in read_advisory(file)? {
+                if keyed_in(&key, &fresh_ecosystems) {
+                    fresh.entry(key).or_default().push(affected);
+                }
+            }
+        }
+
+        if BUILDING.lock().unwrap_or_else(|e| e.into_inner()).insert(fingerprint.to_string()) {
+            let builder = LocalDb::new("", &self.mode, 0);
+            let builder = LocalDb { path: self.path.clone(), budget_bytes: self.budget_bytes, ..builder };
+            let (files, dir, fingerprint) = (files.to_vec(), dir.to_path_buf(), fingerprint.to_string());
+            let carried = permissions::carried();
+            std::thread::spawn(move || {
+                let _permissions = carried.map(permissions::carry);
+                if let Ok(mut snapshot) = builder.snapshot(&files, &dir, &fingerprint, None) {
+                    let _ = snapshots::touch(&dir, &mut snapshot, now);
+                }
+                BUILDING.lock().unwrap_or_else(|e| e.into_inner()).remove(&fingerprint);
+            });
+        }
+
+        let newest = files.iter().filter_map(|file| fs::metadata(file).and_then(|m| m.modified()).ok()).max();
+        let fetched_at = newest.map(report::format_timestamp);
+        let freshness = considered
+            .iter()
+            .map(|ecosystem| {
+                let current = fresh_ecosystems.contains(ecosystem);
+                EcosystemFreshness {
+                    ecosystem: ecosystem.as_str(),
+                    fresh: current,
+                    snapshot: (!current).then(|| stale.id.clone()),
+                    fetched_at: if current { fetched_at.clone() } else { stale.fetched_at.clone() },
+                    last_used: None,
+                    next_refresh: None,
+                }
+            })
+            .collect();
+        // Not a cached snapshot, so a name no pin can mistake for one
+        self.snapshot = format!("{}@partial-{}", SOURCE, fingerprint);
+        self.store = Store::Partial {
+            fresh,
+            fresh_ecosystems,
+            index: snapshots::index_path(dir, &stale.id),
+            stale: stale.id,
+        };
+        Ok(Some(DbSnapshot {
+            source: SOURCE.to_string(),
+            id: self.snapshot.clone(),
+            sha256: None,
+            fetched_at,
+            age_secs: newest.map(|at| clock::unix_secs(now).saturating_sub(clock::unix_secs(at))),
+            freshness,
+        }))
+    }
+
+    /// With `db_refresh = "scheduled"`, record the scan's ecosystems as used
+    /// by the workspace and query the snapshot of the used ones. It is
+    /// built again, from the files that may hold their advisories, when an
+    /// ecosystem is used for the first time, or when the database changed
+    /// and the `interval` since the last build has passed; the ecosystems
+    /// no scan uses are never read. `None` when no needed ecosystems were
+    /// given, for a full refresh instead.
+    fn refresh_scheduled(
+        &mut self,
+        files: &[PathBuf],
+        cache: &Path,
+        fingerprint: &str,
+        now: SystemTime,
+    ) -> Result<Option<DbSnapshot>, String> {
+        if self.refresh != "scheduled" {
+            return Ok(None);
+        }
+        let Some(needed) = &self.needed else {
+            return Ok(None);
+        };
+        let dir = snapshots::snapshots_dir(cache);
+        let considered = self.only.as_deref().unwrap_or(&Ecosystem::ALL);
+        let mut schedule = Schedule::load(cache);
+        schedule.mark_used(needed.iter().filter(|e| considered.contains(e)), now);
+        let used = schedule.used();
+        if used.is_empty() {
+            // Nothing to query, and an index of no ecosystems would be one of all
+            self.store = Store::Memory(Arc::default());
+            self.snapshot = format!("{}@unused", SOURCE);
+            return Ok(Some(DbSnapshot {
+                source: SOURCE.to_string(),
+                id: self.snapshot.clone(),
+                sha256: None,
+                fetched_at: None,
+                age_secs: None,
+                freshness: Vec::new(),
+            }));
+        }
+
+        // A snapshot missing a used ecosystem is rebuilt whatever the schedule
+        let names: Vec<&str> = used.iter().map(|e| e.as_str()).collect();
+        let current = schedule
+            .snapshot
+            .as_deref()
+            .and_then(|id| snapshots::load(&dir, id))
+            .filter(|current| current.ecosystems == names);
+        let changed = schedule.fingerprint.as_deref() != Some(fingerprint);
+        let mut snapshot = match current {
+            Some(current) if !changed || !schedule.due(self.interval, now) => current,
+            _ => {
+                let scoped = format!("{}:{}", fingerprint, names.join(","));
+                let snapshot = self.snapshot(files, &dir, &scoped, Some(&used))?;
+                schedule.refreshed(&snapshot.id, fingerprint, now);
+                snapshot
+            }
+        };
+        snapshots::touch(&dir, &mut snapshot, now).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        schedule.save(cache).map_err(|e| format!("{}: {}", cache.display(), e))?;
+
+        self.store = Store::Disk(snapshots::index_path(&dir, &snapshot.id));
+        self.snapshot = snapshot.id.clone();
+        let freshness = schedule.freshness(fingerprint, snapshot.fetched_at.as_deref(), self.interval);
+        Ok(Some(DbSnapshot { freshness, ..describe(&snapshot, now) }))
+    }
+
+    /// Build the sorted index of the advisories in `files`, of `of` only
+    /// when given.
+    fn build_disk_index(
+        &self,
+        files: &[PathBuf],
+        dir: &Path,
+        index_path: &Path,
+        of: Option<&[Ecosystem]>,
+    ) -> Result<(), String> {
+        fsutil::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let mut writer = IndexWriter::new(dir, self.budget_bytes);
+        for file in files {
+            let advisories = read_advisory(file)?;
+            for (key, affected) in advisories.into_iter().filter(|(key, _)| of.is_none_or(|of| keyed_in(key, of))) {
+                let value = serde_json::to_string(&affected).map_err(|e| e.to_string())?;
+                writer.insert(key, value).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            }
+        }
+        writer
+            .finish(index_path)
+            .map(drop)
+            .map_err(|e| format!("{}: {}", index_path.display(), e))
+    }
+}
+
+impl AdvisorySource for LocalDb {
+    fn caches(&self) -> bool {
+        true
+    }
+
+    fn refresh(&mut self, cache: &Path, now: SystemTime) -> Result<DbSnapshot, String> {
+        if !matches!(self.mode.as_str(), "memory" | "disk") {
+            return Err(format!("db_index: expected 'memory' or 'disk', got '{}'", self.mode));
+        }
+        if !matches!(self.refresh.as_str(), "full" | "partial" | "scheduled") {
+            return Err(format!("db_refresh: expected 'full', 'partial' or 'scheduled', got '{}'", self.refresh));
+        }
+        let dir = snapshots::snapshots_dir(cache);
+
+        // A pinned snapshot is queried from its index, in either mode
+        if let Some(id) = &self.pinned {
+            let mut snapshot = snapshots::load(&dir, id)
+                .ok_or_else(|| format!("db_snapshot '{}' is not cached in {}", id, dir.display()))?;
+            snapshots::touch(&dir, &mut snapshot, now).map_err(|e| format!("{}: {}", dir.display(), e))?;
+            self.store = Store::Disk(snapshots::index_path(&dir, id));
+            self.snapshot = snapshot.id.clone();
+            return Ok(describe(&snapshot, now));
+        }
+
+        let warm_key = format!(
+            "{}\n{}\n{}\n{}\n{:?}",
+            cache.display(),
+            self.path.display(),
+            self.mode,
+            self.refresh,
+            self.only
+        );
+        if self.warm {
+            let warm = WARM.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(warm) = warm.as_ref().filter(|warm| warm.key == warm_key) {
+                let mut snapshot = warm.snapshot.clone();
+                snapshots::touch(&dir, &mut snapshot, now).map_err(|e| format!("{}: {}", dir.display(), e))?;
+                self.store = warm.store.clone();
+                self.snapshot = snapshot.id.clone();
+                return Ok(describe(&snapshot, now));
+            }
+        }
+
+        let mut files = Vec::new();
+        collect_json_files(&self.path, &mut files).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        files.sort();
+
+        // Identify the database by its files, sizes and modification times
+        let mut hasher = Sha256::new();
+        hasher.update(INDEX_FORMAT.to_le_bytes());
+        for file in &files {
+            let metadata = fs::metadata(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos());
+            hasher.update(file.to_string_lossy().as_bytes());
+            hasher.update(metadata.len().to_le_bytes());
+            hasher.update(mtime.to_le_bytes());
+        }
+        let fingerprint = report::hex(&hasher.finalize()[..8]);
+        if let Some(partial) = self.refresh_partially(&files, &dir, &fingerprint, now)? {
+            return Ok(partial);
+        }
+        if let Some(scheduled) = self.refresh_scheduled(&files, cache, &fingerprint, now)? {
+            return Ok(scheduled);
+        }
+
+        // Every refresh is cached as a snapshot, so later scans can pin it
+        let mut snapshot = self.snapshot(&files, &dir, &fingerprint, None)?;
+        snapshots::touch(&dir, &mut snapshot, now).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        remove_unversioned_indexes(cache);
+
+        let mut read_bytes = 0;
+        self.store = if self.mode == "memory" {
+            let mut advisories: HashMap<String, Vec<Affected>> = HashMap::new();
+            for file in files.iter().filter(|file| self.may_hold_considered(file)) {
+                read_bytes += fs::metadata(file).map_or(0, |metadata| metadata.len());
+                for (key, affected) in read_advisory(file)? {
+                    if self.considers(&key) {
+                        advisories.entry(key).or_default().push(affected);
+                    }
+                }
+            }
+            Store::Memory(Arc::new(advisories))
+        } else {
+            Store::Disk(snapshots::index_path(&dir, &snapshot.id))
+        };
+        self.snapshot = snapshot.id.clone();
+        // A memory store is kept when its files fit in the budget
+        if self.warm {
+            let fits = usize::try_from(read_bytes).is_ok_and(|bytes| bytes <= self.budget_bytes);
+            let mut warm = WARM.lock().unwrap_or_else(PoisonError::into_inner);
+            *warm = fits.then(|| Warm {
+                key: warm_key,
+                snapshot: snapshot.clone(),
+                store: self.store.clone(),
+            });
+        }
+        Ok(describe(&snapshot, now))
+    }
+
+    fn restrict(&mut self, ecosystems: &[Ecosystem]) {
+        self.only = Some(ecosystems.to_vec());
+    }
+
+    fn prioritize(&mut self, ecosystems: &[Ecosystem]) {
+        self.needed = Some(ecosystems.to_vec());
+    }
+
+    /// Every file that may hold the ecosystems a refresh loads, or with
+    /// `partial` and `scheduled` refreshes those it loads before the scan;
+    /// an unchanged database is read less. A pinned snapshot reads none.
+    fn estimate(&self) -> Option<Estimate> {
+        if self.pinned.is_some() {
+            return Some(Estimate { setting: "db_snapshot", path: self.pinned.clone(), bytes: 0 });
+        }
+        let mut files = Vec::new();
+        collect_json_files(&self.path, &mut files).ok()?;
+        let first = self.needed.as_ref().filter(|_| matches!(self.refresh.as_str(), "partial" | "scheduled"));
+        let bytes = files
+            .iter()
+            .filter(|file| first.map_or_else(|| self.may_hold_considered(file), |needed| self.may_hold(file, needed)))
+            .map(|file| fs::metadata(file).map_or(0, |metadata| metadata.len()))
+            .sum();
+        Some(Estimate { setting: "db_path", path: Some(self.path.display().to_string()), bytes })
+    }
+
+    fn query(&self, deps: &[Dependency]) -> Vec<RawFinding> {
+        let mut index = match &self.store {
+            Store::Disk(path) | Store::Partial { index: path, .. } => Index::open(path).ok(),
+            _ => None,
+        };
+
+        // Look every package up once, however many files declare it
+        let mut cache: HashMap<String, Vec<Affected>> = HashMap::new();
+        let mut findings = Vec::new();
+        for (i, dep) in deps.iter().enumerate() {
+            let key = key(dep.ecosystem.as_str(), &dep.name);
+            let stale = match &self.store {
+                Store::Partial { fresh_ecosystems, stale, .. } if !fresh_ecosystems.contains(&dep.ecosystem) => {
+                    Some(stale)
+                }
+                _ => None,
+            };
+            if !cache.contains_key(&key) {
+                let entries = match (&self.store, index.as_mut()) {
+                    (Store::Memory(advisories), _) => advisories.get(&key).cloned().unwrap_or_default(),
+                    (Store::Partial { fresh, .. }, _) if stale.is_none() => {
+                        fresh.get(&key).cloned().unwrap_or_default()
+                    }
+                    (Store::Disk(_) | Store::Partial { .. }, Some(index)) => index
+                        .get(&key)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|value| serde_json::from_str(value).ok())
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                cache.insert(key.clone(), entries);
+            }
+
+            let snapshot = stale.unwrap_or(&self.snapshot);
+            findings.extend(cache[&key].iter().filter_map(|affected| finding(i, dep, affected, SOURCE, snapshot)));
+        }
+        findings
+    }
+}
+
+/// The finding of `affected` for dependency `i` of a query, if its version
+/// is affected.
+pub(super) fn finding(
+    i: usize,
+    dep: &Dependency,
+    affected: &Affected,
+    source: &str,
+    snapshot: &str,
+) -> Option<RawFinding> {
+    // Packages of an unknown release match every release
+    if affected.release.is_some() && dep.release.is_some() && affected.release != dep.release {
+        return None;
+    }
+    let (fixed_in, matcher, range) = affected.matches(dep.ecosystem, &dep.version)?;
+    Some(RawFinding {
+        dependency: i,
+        id: affected.id.clone(),
+        aliases: affected.aliases.clone(),
+        severity: affected.severity.clone(),
+        cvss_score: None,
+        summary: affected.summary.clone(),
+        description: match affected.details.as_str() {
+            "" => affected.summary.clone(),
+            details => details.to_string(),
+        },
+        fixed_in,
+        references: affected.references.clone(),
+        packages: affected.packages.clone(),
+        published: affected.published.clone(),
+        cwe: affected.cwe.clone(),
+        categories: affected.categories.clone(),
+        provenance: Provenance {
+            source: source.to_string(),
+            database: affected.id.split_once('-').map(|(prefix, _)| prefix.to_string()),
+            snapshot: snapshot.to_string(),
+            matcher,
+            range,
+            confidence: None,
+        },
+    })
+}
+
+fn describe(snapshot: &Snapshot, now: SystemTime) -> DbSnapshot {
+    let age_secs = snapshot.fetched_at_unix.map(|at| clock::unix_secs(now).saturating_sub(at));
+    DbSnapshot {
+        source: snapshot.source.clone(),
+        id: snapshot.id.clone(),
+        sha256: Some(snapshot.sha256.clone()),
+        fetched_at: snapshot.fetched_at.clone(),
+        age_secs,
+        freshness: Vec::new(),
+    }
+}
+
+/// Indexes cached before snapshots were named by content are dead weight.
+fn remove_unversioned_indexes(cache: &Path) {
+    for entry in fsutil::read_dir(cache).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with("osv-") && name.ends_with(".kv") {
+            let _ = fsutil::remove_file(entry.path());
+        }
+    }
+}
+
+impl Affected {
+    /// Whether `version` is affected; if so, the version that fixes it and
+    /// how it matched: listed, or within a range (which `range` spells out).
+    fn matches(&self, ecosystem: Ecosystem, version: &str) -> Option<(Option<String>, Matcher, String)> {
+        if self.versions.iter().any(|v| v == version) {
+            let fixed = self.ranges.iter().find_map(|r| r.fixed.clone());
+            return Some((fixed, Matcher::Exact, format!("={}", version)));
+        }
+        self.ranges
+            .iter()
+            .find(|r| r.contains(ecosystem, version))
+            .map(|r| (r.fixed.clone(), Matcher::Range, r.to_string()))
+    }
+}
+
+/// `>=2.0, <2.2.24`; `*` when both ends are open.
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bounds = [(">=", &self.introduced), ("<", &self.fixed), ("<=", &self.last_affected)];
+        let bounds: Vec<String> = bounds
+            .iter()
+            .filter_map(|(op, bound)| bound.as_ref().map(|bound| format!("{}{}", op, bound)))
+            .collect();
+        if bounds.is_empty() {
+            return write!(f, "*");
+        }
+        write!(f, "{}", bounds.join(", "))
+    }
+}
+
+impl Interval {
+    fn contains(&self, ecosystem: Ecosystem, version: &str) -> bool {
+        let compare = |bound: &str| ecosystem.compare_versions(version, bound);
+        let at_least = |bound: &Option<String>| {
+            bound
+                .as_deref()
+                .is_none_or(|b| b == "0" || compare(b) != Ordering::Less)
+        };
+        let below_fixed = self.fixed.as_deref().is_none_or(|f| compare(f) == Ordering::Less);
+        let within_last = self.last_affected.as_deref().is_none_or(|l| compare(l) != Ordering::Greater);
+        at_least(&self.introduced) && below_fixed && within_last
+    }
+}
+
+/// Whether an index key's ecosystem is among `ecosystems`.
+fn keyed_in(key: &str, ecosystems: &[Ecosystem]) -> bool {
+    let ecosystem = key.split('/').next().and_then(Ecosystem::named);
+    ecosystem.is_some_and(|ecosystem| ecosystems.contains(&ecosystem))
+}
+
+/// Index key of a package: the ecosystem with any release suffix dropped
+/// (`Debian:12` → `Debian`) and the name as the ecosystem compares it.
+pub(super) fn key(ecosystem: &str, name: &str) -> String {
+    let ecosystem = ecosystem.split(':').next().unwrap_or(ecosystem);
+    let name = match ecosystem {
+        "PyPI" => Ecosystem::PyPI.canonical_name(name),
+        "Packagist" => Ecosystem::Packagist.canonical_name(name),
+        _ => name.to_string(),
+    };
+    format!("{}/{}", ecosystem, name).replace(['\t', '\n'], " ")
+}
+
+fn collect_json_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    for entry in fsutil::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_json_files(&path, files)?;
+        } else if path.extension().is_some_and(|e| e == "json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The index entries for one advisory file. Withdrawn advisories and
+/// commit-based (`GIT`) ranges are skipped.
+fn read_advisory(path: &Path) -> Result<Vec<(String, Affected)>, String> {
+    let content = fsutil::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let advisory: OsvAdvisory = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if advisory.withdrawn.is_some() {
+        return Ok(Vec::new());
+    }
+
+    let severity_of = |v: &Option<Value>| v.as_ref()?.get("severity")?.as_str().map(str::to_string);
+    // GitHub's `cwe_ids`, RustSec's `categories`
+    let strings_of = |key: &str, specifics: [&Option<Value>; 2]| -> Vec<String> {
+        let values = specifics.into_iter().filter_map(|v| v.as_ref()?.get(key)?.as_array());
+        values.flatten().filter_map(|value| value.as_str().map(str::to_string)).collect()
+    };
+
+    let mut entries = Vec::new();
+    for affected in advisory.affected {
+        let key = key(&affected.package.ecosystem, &affected.package.name);
+        let ranges = affected
+            .ranges
+            .iter()
+            .filter(|r| r.kind != "GIT")
+            .flat_map(|r| intervals(&r.events))
+            .collect();
+        let severity = severity_of(&affected.ecosystem_specific)
+            .or_else(|| severity_of(&affected.database_specific))
+            .or_else(|| severity_of(&advisory.database_specific))
+            .unwrap_or_else(|| "UNKNOWN".to_string());
+        // The Go vulnerability database names the affected packages (and
+        // symbols) of a module
+        let packages = affected
+            .ecosystem_specific
+            .as_ref()
+            .and_then(|specific| specific.get("imports")?.as_array().cloned())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|import| Some(import.get("path")?.as_str()?.to_string()))
+            .collect();
+        entries.push((
+            key,
+            Affected {
+                id: advisory.id.clone(),
+                aliases: advisory.aliases.clone(),
+                release: affected.package.ecosystem.split_once(':').map(|(_, release)| release.to_string()),
+                severity,
+                summary: advisory.summary.clone(),
+                details: advisory.details.clone(),
+                versions: affected.versions,
+                ranges,
+                references: advisory.references.clone(),
+                packages,
+                published: advisory.published.clone(),
+                cwe: strings_of("cwe_ids", [&advisory.database_specific, &affected.database_specific]),
+                categories: strings_of("categories", [&advisory.database_specific, &affected.database_specific]),
+            },
+        ));
+    }
+    Ok(entries)
+}
+
+/// Pair up a range's `introduced` events with the `fixed` or
+/// `last_affected` event that closes them.
+pub(super) fn intervals(events: &[HashMap<String, String>]) -> Vec<Interval> {
+    let mut intervals = Vec::new();
+    let mut open: Option<Interval> = None;
+    for event in events {
+        if let Some(introduced) = event.get("introduced") {
+            if let Some(interval) = open.take() {
+                intervals.push(interval);
+            }
+            open = Some(Interval {
+                introduced: Some(introduced.clone()),
+                fixed: None,
+                last_affected: None,
+            });
+        } else if let Some(interval) = open.as_mut() {
+            interval.fixed = event.get("fixed").cloned();
+            interval.last_affected = event.get("last_affected").cloned();
+            intervals.extend(open.take());
+        }
+    }
+    intervals.extend(open);
+    intervals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::{Ecosystem, Scope};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::time::Instant;
+
+    /// Tracks live and peak heap use for the memory benchmark.
+    struct Counting;
+
+    static LIVE: AtomicUsize = AtomicUsize::new(0);
+    static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let ptr = System.alloc(layout);
+            if !ptr.is_null() {
+                let live = LIVE.fetch_add(layout.size(), AtomicOrdering::Relaxed) + layout.size();
+                PEAK.fetch_max(live, AtomicOrdering::Relaxed);
+            }
+            ptr
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout);
+            LIVE.fetch_sub(layout.size(), AtomicOrdering::Relaxed);
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    /// Peak heap growth while running `f`.
+    fn peak_during<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let base = LIVE.load(AtomicOrdering::Relaxed);
+        PEAK.store(base, AtomicOrdering::Relaxed);
+        let result = f();
+        (result, PEAK.load(AtomicOrdering::Relaxed) - base)
+    }
+
+    fn dep(ecosystem: Ecosystem, name: &str, version: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+            ecosystem,
+            scope: Scope::Runtime,
+            file: "fixture".to_string(),
+            line: 1,
+            col: None,
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    fn advisory(id: &str, ecosystem: &str, name: &str, introduced: &str, fixed: &str, details: &str) -> String {
+        serde_json::json!({
+            "id": id,
+            "aliases": [format!("CVE-{}", id)],
+            "details": details,
+            "database_specific": {"severity": "MODERATE"},
+            "affected": [{
+                "package": {"ecosystem": ecosystem, "name": name},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": introduced}, {"fixed": fixed}]}]
+            }]
+        })
+        .to_string()
+    }
+
+    fn found(source: &LocalDb, deps: &[Dependency]) -> Vec<(usize, String, String, Option<String>)> {
+        source
+            .query(deps)
+            .into_iter()
+            .map(|f| (f.dependency, f.id, f.severity, f.fixed_in))
+            .collect()
+    }
+
+    #[test]
+    fn memory_and_disk_indexes_agree() {
+        let dir = crate::testutil::scratch_dir("osv-agree");
+        let db = dir.join("db");
+        fs::create_dir_all(&db).unwrap();
+        fs::write(db.join("a.json"), advisory("GHSA-1", "PyPI", "Django", "2.0", "2.2.24", "SQL injection")).unwrap();
+        fs::write(db.join("b.json"), advisory("GHSA-2", "npm", "lodash", "0", "4.17.21", "Prototype pollution")).unwrap();
+        fs::write(
+            db.join("c.json"),
+            r#"{"id": "GHSA-3", "withdrawn": "2024-01-01T00:00:00Z", "affected": [{"package": {"ecosystem": "npm", "name": "lodash"}, "versions": ["4.17.15"]}]}"#,
+        )
+        .unwrap();
+        fs::write(
+            db.join("d.json"),
+            r#"{"id": "RUSTSEC-1", "affected": [{"package": {"ecosystem": "crates.io", "name": "regex"}, "versions": ["1.5.4"], "ecosystem_specific": {"severity": "high"}}]}"#,
+        )
+        .unwrap();
+
+        let deps = [
+            dep(Ecosystem::PyPI, "django", "2.2.0"),
+            dep(Ecosystem::Npm, "lodash", "4.17.15"),
+            dep(Ecosystem::Npm, "lodash", "4.17.21"),
+            dep(Ecosystem::CratesIo, "regex", "1.5.4"),
+            dep(Ecosystem::PyPI, "flask", "1.0"),
+        ];
+        let expected = vec![
+            (0, "GHSA-1".to_string(), "MODERATE".to_string(), Some("2.2.24".to_string())),
+            (1, "GHSA-2".to_string(), "MODERATE".to_string(), Some("4.17.21".to_string())),
+            (3, "RUSTSEC-1".to_string(), "high".to_string(), None),
+        ];
+
+        let mut ids = Vec::new();
+        for mode in ["memory", "disk"] {
+            let mut source = LocalDb::new(db.to_str().unwrap(), mode, 1);
+            let snapshot = source.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+            assert!(snapshot.id.starts_with("osv-local@"));
+            assert_eq!(&snapshot.sha256.unwrap()[..16], &snapshot.id["osv-local@".len()..]);
+            assert_eq!(found(&source, &deps), expected, "{}", mode);
+            ids.push(snapshot.id);
+        }
+        assert_eq!(ids[0], ids[1]);
+        let mut restricted = LocalDb::new(db.to_str().unwrap(), "memory", 1);
+        restricted.restrict(&[Ecosystem::PyPI]);
+        restricted.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+        assert_eq!(found(&restricted, &deps), expected[..1]);
+        let index_files = fs::read_dir(dir.join("cache")).unwrap().count();
+        assert_eq!(index_files, 1);
+
+        // A pinned scan keeps its snapshot's data after the database changes
+        fs::remove_file(db.join("b.json")).unwrap();
+        let mut pinned = LocalDb::new(db.to_str().unwrap(), "memory", 1).pinned(Some(&ids[0]));
+        assert_eq!(pinned.refresh(&dir.join("cache"), SystemTime::now()).unwrap().id, ids[0]);
+        assert_eq!(found(&pinned, &deps), expected);
+        let mut missing = LocalDb::new(db.to_str().unwrap(), "disk", 1).pinned(Some("osv-local@0123456789abcdef"));
+        let error = missing.refresh(&dir.join("cache"), SystemTime::now()).err().unwrap();
+        assert!(error.contains("is not cached"), "{}", error);
+
+        let mut unknown = LocalDb::new(db.to_str().unwrap(), "sqlite", 1);
+        let error = unknown.refresh(&dir.join("cache"), SystemTime::now()).err().unwrap();
+        assert!(error.contains("db_index"), "{}", error);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_warm_refresh_is_reused_until_forgotten_and_only_within_the_budget() {
+        let dir = crate::testutil::scratch_dir("osv-warm");
+        let (db, cache) = (dir.join("db"), dir.join("cache"));
+        fs::create_dir_all(&db).unwrap();
+        let write = |fixed: &str, details: &str| {
+            fs::write(db.join("a.json"), advisory("GHSA-1", "npm", "lodash", "0", fixed, details)).unwrap();
+        };
+        let deps = [dep(Ecosystem::Npm, "lodash", "4.17.21")];
+        let refreshed = |budget_mb: u64| {
+            let mut source = LocalDb::new(db.to_str().unwrap(), "memory", budget_mb).warm(true);
+            let snapshot = source.refresh(&cache, SystemTime::now()).unwrap();
+            (snapshot.id, found(&source, &deps).len())
+        };
+        write("4.17.21", "Prototype pollution");
+        let (first, _) = refreshed(1);
+
+        // The kept refresh answers after the database changed, until dropped
+        write("4.17.22", "Prototype pollution, again");
+        assert_eq!(refreshed(1), (first.clone(), 0));
+        forget_warm();
+        let (second, found_now) = refreshed(1);
+        assert_ne!(second, first);
+        assert_eq!(found_now, 1);
+
+        // A memory store larger than the budget isn't kept
+        forget_warm();
+        assert_eq!(refreshed(0), (second, 1));
+        write("4.17.21", "Prototype pollution");
+        assert_eq!(refreshed(0).1, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_partial_refresh_serves_the_other_ecosystems_from_the_previous_snapshot() {
+        let dir = crate::testutil::scratch_dir("osv-partial");
+        let (db, cache) = (dir.join("db"), dir.join("cache"));
+        fs::create_dir_all(&db).unwrap();
+        fs::write(db.join("a.json"), advisory("GHSA-1", "PyPI", "Django", "2.0", "2.2.24", "SQL injection")).unwrap();
+        fs::write(db.join("b.json"), advisory("GHSA-2", "npm", "lodash", "0", "4.17.21", "Prototype pollution")).unwrap();
+        let partial = || {
+            let mut source = LocalDb::new(db.to_str().unwrap(), "memory", 1).refreshing("partial");
+            source.restrict(&[Ecosystem::PyPI, Ecosystem::Npm]);
+            source.prioritize(&[Ecosystem::Npm]);
+            source
+        };
+        // Without a previous snapshot the refresh is a full one
+        let previous = partial().refresh(&cache, SystemTime::now()).unwrap();
+        assert!(previous.freshness.is_empty());
+
+        fs::write(db.join("a.json"), advisory("GHSA-1", "PyPI", "Django", "2.0", "3.0", "SQL injection, again")).unwrap();
+        fs::write(db.join("b.json"), advisory("GHSA-2", "npm", "lodash", "0", "4.17.22", "Prototype pollution!"))
+            .unwrap();
+        let mut source = partial();
+        let snapshot = source.refresh(&cache, SystemTime::now()).unwrap();
+        let fingerprint = snapshot.id.strip_prefix("osv-local@partial-").unwrap().to_string();
+        let states: Vec<(&str, bool, Option<&str>)> =
+            snapshot.freshness.iter().map(|f| (f.ecosystem, f.fresh, f.snapshot.as_deref())).collect();
+        assert_eq!(states, [("PyPI", false, Some(previous.id.as_str())), ("npm", true, None)]);
+
+        let deps = [dep(Ecosystem::PyPI, "django", "2.5"), dep(Ecosystem::Npm, "lodash", "4.17.21")];
+        let findings = source.query(&deps);
+        let served: Vec<(usize, &str)> =
+            findings.iter().map(|f| (f.dependency, f.provenance.snapshot.as_str())).collect();
+        assert_eq!(served, [(1, snapshot.id.as_str())]);
+
+        // The full snapshot is built in the background, for the next scan
+        let dir_of_snapshots = snapshots::snapshots_dir(&cache);
+        let started = std::time::Instant::now();
+        let built = loop {
+            if let Some(built) = snapshots::find(&dir_of_snapshots, SOURCE, &fingerprint) {
+                break built;
+            }
+            assert!(started.elapsed() < std::time::Duration::from_secs(10), "background refresh never finished");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        let mut source = partial();
+        let refreshed = source.refresh(&cache, SystemTime::now()).unwrap();
+        assert_eq!((refreshed.id, refreshed.freshness.len()), (built.id, 0));
+        assert_eq!(found(&source, &deps).len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_scheduled_refresh_reads_only_the_ecosystems_the_workspace_uses() {
+        let dir = crate::testutil::scratch_dir("osv-scheduled");
+        let (db, cache) = (dir.join("db"), dir.join("cache"));
+        fs::create_dir_all(&db).unwrap();
+        fs::create_dir_all(db.join("PyPI")).unwrap();
+        fs::create_dir_all(db.join("npm")).unwrap();
+        let django = |fixed: &str| advisory("GHSA-1", "PyPI", "Django", "2.0", fixed, "SQL injection");
+        fs::write(db.join("PyPI/a.json"), django("2.2.24")).unwrap();
+        fs::write(db.join("npm/b.json"), advisory("GHSA-2", "npm", "lodash", "0", "4.17.21", "Prototype pollution"))
+            .unwrap();
+        let day = std::time::Duration::from_secs(86_400);
+        let start = SystemTime::now();
+        let scheduled = |needed: &[Ecosystem], days: u32| {
+            let mut source = LocalDb::new(db.to_str().unwrap(), "memory", 1).refreshing("scheduled").every(day);
+            source.prioritize(needed);
+            let snapshot = source.refresh(&cache, start + day * days).unwrap();
+            (source, snapshot)
+        };
+        let deps = [dep(Ecosystem::PyPI, "django", "2.5"), dep(Ecosystem::Npm, "lodash", "4.17.15")];
+        let dir_of_snapshots = snapshots::snapshots_dir(&cache);
+
+        // Only PyPI is read: the npm advisory isn't in the snapshot
+        let (source, first) = scheduled(&[Ecosystem::PyPI], 0);
+        assert!(found(&source, &deps).is_empty());
+        assert_eq!(snapshots::load(&dir_of_snapshots, &first.id).unwrap().ecosystems, ["PyPI"]);
+        let states: Vec<(&str, bool)> = first.freshness.iter().map(|f| (f.ecosystem, f.fresh)).collect();
+        assert_eq!(states, [("PyPI", true)]);
+
+        // A changed database is read again once the interval has passed
+        fs::write(db.join("PyPI/a.json"), django("3.0")).unwrap();
+        let (source, stale) = scheduled(&[Ecosystem::PyPI], 0);
+        assert_eq!((stale.id.as_str(), stale.freshness[0].fresh), (first.id.as_str(), false));
+        assert_eq!(stale.freshness[0].snapshot.as_deref(), Some(first.id.as_str()));
+        assert!(found(&source, &deps).is_empty());
+        let (source, refreshed) = scheduled(&[Ecosystem::PyPI], 1);
+        assert_ne!(refreshed.id, first.id);
+        assert_eq!(found(&source, &deps).len(), 1);
+
+        // A new ecosystem is read at once, with the others
+        let (source, both) = scheduled(&[Ecosystem::Npm], 1);
+        assert_ne!(both.id, refreshed.id);
+        assert_eq!(found(&source, &deps).len(), 2);
+        let schedule = Schedule::load(&cache);
+        assert_eq!(schedule.used(), [Ecosystem::PyPI, Ecosystem::Npm]);
+        assert_eq!(schedule.snapshot.as_deref(), Some(both.id.as_str()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The OSV records in `testdata/osv-recorded`, one known-vulnerable
+    /// package each of RubyGems, Packagist and Maven, lock down how those
+    /// ecosystems are named and their packages compared.
+    #[test]
+    fn recorded_osv_advisories_match_by_ecosystem_name_rules() {
+        let dir = crate::testutil::scratch_dir("osv-recorded");
+        let recorded = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/osv-recorded");
+        let mut source = LocalDb::new(recorded.to_str().unwrap(), "memory", 1);
+        source.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+
+        let deps = [
+            dep(Ecosystem::RubyGems, "nokogiri", "1.13.3"),
+            // Gem names are exact
+            dep(Ecosystem::RubyGems, "Nokogiri", "1.13.3"),
+            dep(Ecosystem::RubyGems, "nokogiri", "1.13.4"),
+            // Composer names are not
+            dep(Ecosystem::Packagist, "GuzzleHttp/Guzzle", "7.4.2"),
+            dep(Ecosystem::Packagist, "guzzlehttp/guzzle", "6.5.6"),
+            dep(Ecosystem::Maven, "org.apache.logging.log4j:log4j-core", "2.14.1"),
+            dep(Ecosystem::Maven, "org.apache.logging.log4j:log4j-core", "2.1"),
+            dep(Ecosystem::Maven, "log4j-core", "2.14.1"),
+            dep(Ecosystem::Npm, "guzzlehttp/guzzle", "7.4.2"),
+        ];
+        let fixed = |version: &str| Some(version.to_string());
+        assert_eq!(
+            found(&source, &deps),
+            vec![
+                (0, "GHSA-crjr-9rc5-ghw8".to_string(), "HIGH".to_string(), fixed("1.13.4")),
+                (3, "GHSA-cwmx-hcrq-mhc3".to_string(), "HIGH".to_string(), fixed("7.4.3")),
+                (5, "GHSA-jfh8-c2jp-5v3q".to_string(), "CRITICAL".to_string(), fixed("2.15.0")),
+                (6, "GHSA-jfh8-c2jp-5v3q".to_string(), "CRITICAL".to_string(), fixed("2.3.1")),
+            ]
+        );
+        for ecosystem in ["RubyGems", "Packagist", "Maven"] {
+            assert_eq!(Ecosystem::named(ecosystem).map(Ecosystem::as_str), Some(ecosystem));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prereleases_of_the_fixed_version_are_affected() {
+        let dir = crate::testutil::scratch_dir("osv-prerelease");
+        let db = dir.join("db");
+        fs::create_dir_all(&db).unwrap();
+        fs::write(db.join("a.json"), advisory("GHSA-2", "npm", "foo", "0", "2.0.0", "Prototype pollution")).unwrap();
+        fs::write(db.join("b.json"), advisory("GHSA-3", "PyPI", "bar", "0", "2.0", "Path traversal")).unwrap();
+        let mut source = LocalDb::new(db.to_str().unwrap(), "memory", 1);
+        source.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+
+        let deps = [
+            dep(Ecosystem::Npm, "foo", "1.9.0"),
+            dep(Ecosystem::Npm, "foo", "2.0.0-rc.1"),
+            dep(Ecosystem::Npm, "foo", "2.0.0"),
+            dep(Ecosystem::PyPI, "bar", "1.9"),
+            dep(Ecosystem::PyPI, "bar", "2.0rc1"),
+            dep(Ecosystem::PyPI, "bar", "2.0"),
+            dep(Ecosystem::PyPI, "bar", "2.0.post1"),
+        ];
+        let affected: Vec<usize> = found(&source, &deps).into_iter().map(|(dependency, ..)| dependency).collect();
+        assert_eq!(affected, vec![0, 1, 3, 4]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Benchmark: peak heap of a refresh and a 50-package query over 20,000
+    /// advisories, in memory and on disk with a 1 MB budget. Run with
+    /// `cargo test --release -- --ignored --nocapture --test-threads=1 index_memory`.
+    #[test]
+    #[ignore]
+    fn index_memory_benchmark() {
+        const ADVISORIES: usize = 20_000;
+        const BUDGET_MB: u64 = 1;
+
+        let dir = crate::testutil::scratch_dir("osv-bench");
+        let db = dir.join("db");
+        fs::create_dir_all(&db).unwrap();
+        let details = "x".repeat(1000);
+        for i in 0..ADVISORIES {
+            let body = advisory(&format!("GHSA-{}", i), "npm", &format!("pkg-{}", i), "0", "2.0.0", &details);
+            fs::write(db.join(format!("{}.json", i)), body).unwrap();
+        }
+        let deps: Vec<_> = (0..50)
+            .map(|i| dep(Ecosystem::Npm, &format!("pkg-{}", i * 397), "1.0.0"))
+            .collect();
+
+        let mut peaks = Vec::new();
+        for mode in ["memory", "disk"] {
+            let started = Instant::now();
+            let mut source = LocalDb::new(db.to_str().unwrap(), mode, BUDGET_MB);
+            let (findings, peak) = peak_during(|| {
+                source.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+                source.query(&deps).len()
+            });
+            assert_eq!(findings, deps.len());
+            println!(
+                "{:>6}: peak {:>6} KiB, {:>5} ms",
+                mode,
+                peak / 1024,
+                started.elapsed().as_millis()
+            );
+            peaks.push(peak);
+        }
+
+        // The disk index stays within its budget plus the sorted list of
+        // advisory file paths (about 100 bytes each)
+        let bound = (BUDGET_MB * 1024 * 1024) as usize + ADVISORIES * 200;
+        assert!(peaks[1] < bound, "disk peak {} exceeds {}", peaks[1], bound);
+        assert!(peaks[1] * 4 < peaks[0]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
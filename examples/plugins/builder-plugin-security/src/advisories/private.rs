@@ -0,0 +1,632 @@
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use toml::Spanned;
+
+use super::osv::{self, Affected, Interval};
+use super::{AdvisorySource, Estimate, RawFinding, Reference};
+use crate::config;
+use crate::ecosystems::{Dependency, Ecosystem};
+use crate::fsutil;
+use crate::report::{self, DbSnapshot};
+
+const SOURCE: &str = "private";
+
+/// Advisories for a team's own packages, read from the `extra_advisories`
+/// TOML file. Affected versions are given as ranges in the ecosystem's own
+/// syntax, exact versions or OSV events, and are matched like OSV ones.
+pub struct PrivateAdvisories {
+    path: PathBuf,
+    advisories: HashMap<String, Vec<Affected>>,
+    /// Id of the file's contents, for provenance.
+    snapshot: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AdvisoryFile {
+    #[serde(default)]
+    advisories: Vec<PrivateAdvisory>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrivateAdvisory {
+    id: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    summary: String,
+    #[serde(default)]
+    details: String,
+    #[serde(default)]
+    severity: Option<String>,
+    #[serde(default)]
+    published: Option<Spanned<String>>,
+    #[serde(default)]
+    cwe: Vec<String>,
+    #[serde(default)]
+    references: Vec<String>,
+    affected: Vec<PrivateAffected>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrivateAffected {
+    ecosystem: Spanned<String>,
+    package: String,
+    /// Range expressions, any of which affects a version.
+    #[serde(default)]
+    ranges: Vec<Spanned<String>>,
+    #[serde(default)]
+    versions: Vec<String>,
+    /// OSV `ECOSYSTEM` range events.
+    #[serde(default)]
+    events: Option<Spanned<Vec<BTreeMap<String, String>>>>,
+}
+
+/// How an ecosystem writes version ranges.
+#[derive(Clone, Copy, PartialEq)]
+enum Syntax {
+    /// PEP 440 specifiers: `>=1.0, <2.0`, `~=1.4.2`.
+    Pep440,
+    /// npm-style ranges: `>=1.0.0 <2.0.0 || ^3.1.0`, `~1.2`, `1.0.0 - 1.4.2`,
+    /// and RubyGems' `~> 2.3`.
+    Semver,
+    /// Plain comparisons of distribution versions: `<2.36-9`.
+    Comparisons,
+}
+
+impl Syntax {
+    fn of(ecosystem: Ecosystem) -> Syntax {
+        match ecosystem {
+            Ecosystem::PyPI => Syntax::Pep440,
+            Ecosystem::Debian | Ecosystem::Alpine => Syntax::Comparisons,
+            _ => Syntax::Semver,
+        }
+    }
+}
+
+/// One alternative of a range expression: a single version, or an interval
+/// as OSV events would give it.
+#[derive(Debug, PartialEq)]
+enum Alternative {
+    Exact(String),
+    Within(Interval),
+}
+
+impl PrivateAdvisories {
+    pub const SOURCE: &'static str = SOURCE;
+
+    pub fn new(path: PathBuf) -> Self {
+        PrivateAdvisories {
+            path,
+            advisories: HashMap::new(),
+            snapshot: String::new(),
+        }
+    }
+
+    /// Read and validate the file: every advisory's entries by index key.
+    fn load(&self, content: &str) -> Result<HashMap<String, Vec<Affected>>, String> {
+        let at = |offset: usize| {
+            let (line, column) = config::line_and_column(content, offset);
+            format!("{}:{}:{}", self.path.display(), line, column)
+        };
+        let file: AdvisoryFile = toml::from_str(content).map_err(|e| match e.span() {
+            Some(span) => format!("{}: {}", at(span.start), e.message()),
+            None => format!("{}: {}", self.path.display(), e.message()),
+        })?;
+
+        let mut advisories: HashMap<String, Vec<Affected>> = HashMap::new();
+        for advisory in file.advisories {
+            if let Some(published) = &advisory.published {
+                if report::parse_timestamp(published.get_ref()).is_none() {
+                    let error = format!("advisory {}: published '{}' isn't an RFC 3339 time", advisory.id, published);
+                    return Err(format!("{}: {}", at(published.span().start), error));
+                }
+            }
+            if advisory.affected.is_empty() {
+                return Err(format!("{}: advisory {} affects no packages", self.path.display(), advisory.id));
+            }
+            for affected in &advisory.affected {
+                let Some(ecosystem) = Ecosystem::named(affected.ecosystem.get_ref()) else {
+                    let names: Vec<&str> = Ecosystem::ALL.iter().map(|e| e.as_str()).collect();
+                    let error = format!(
+                        "advisory {}: unknown ecosystem '{}' (expected one of {})",
+                        advisory.id,
+                        affected.ecosystem.get_ref(),
+                        names.join(", ")
+                    );
+                    return Err(format!("{}: {}", at(affected.ecosystem.span().start), error));
+                };
+                let mut versions = affected.versions.clone();
+                let mut ranges = Vec::new();
+                for range in &affected.ranges {
+                    let (exact, within) = affecting(ecosystem, range.get_ref()).map_err(|e| {
+                        let what = format!("advisory {} ({} {})", advisory.id, ecosystem.as_str(), affected.package);
+                        format!("{}: {}: range '{}': {}", at(range.span().start), what, range.get_ref(), e)
+                    })?;
+                    versions.extend(exact);
+                    ranges.extend(within);
+                }
+                if let Some(events) = &affected.events {
+                    let events = check_events(events.get_ref()).map_err(|e| {
+                        let what = format!("advisory {} ({} {})", advisory.id, ecosystem.as_str(), affected.package);
+                        format!("{}: {}: events: {}", at(events.span().start), what, e)
+                    })?;
+                    ranges.extend(osv::intervals(&events));
+                }
+                if versions.is_empty() && ranges.is_empty() {
+                    return Err(format!(
+                        "{}: advisory {} ({} {}) affects no versions: give ranges, versions or events",
+                        self.path.display(),
+                        advisory.id,
+                        ecosystem.as_str(),
+                        affected.package
+                    ));
+                }
+                advisories.entry(osv::key(ecosystem.as_str(), &affected.package)).or_default().push(Affected {
+                    id: advisory.id.clone(),
+                    aliases: advisory.aliases.clone(),
+                    release: None,
+                    severity: advisory.severity.clone().unwrap_or_else(|| "UNKNOWN".to_string()),
+                    summary: advisory.summary.clone(),
+                    details: advisory.details.clone(),
+                    versions,
+                    ranges,
+                    references: advisory
+                        .references
+                        .iter()
+                        .map(|url| Reference {
+                            kind: "ADVISORY".to_string(),
+                            url: url.clone(),
+                        })
+                        .collect(),
+                    packages: Vec::new(),
+                    published: advisory.published.as_ref().map(|p| p.get_ref().clone()),
+                    cwe: advisory.cwe.clone(),
+                    categories: Vec::new(),
+                });
+            }
+        }
+        Ok(advisories)
+    }
+}
+
+impl AdvisorySource for PrivateAdvisories {
+    fn refresh(&mut self, _cache: &Path, now: SystemTime) -> Result<DbSnapshot, String> {
+        let content = fsutil::read_to_string(&self.path).map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        self.advisories = self.load(&content)?;
+        let sha256: String = Sha256::digest(content.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect();
+        self.snapshot = format!("{}@{}", SOURCE, &sha256[..16]);
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        Ok(DbSnapshot {
+            source: SOURCE.to_string(),
+            id: self.snapshot.clone(),
+            sha256: Some(sha256),
+            fetched_at: modified.map(report::format_timestamp),
+            age_secs: modified.and_then(|modified| now.duration_since(modified).ok()).map(|age| age.as_secs()),
+            freshness: Vec::new(),
+        })
+    }
+
+    fn estimate(&self) -> Option<Estimate> {
+        let bytes = fs::metadata(&self.path).ok()?.len();
+        Some(Estimate { setting: "extra_advisories", path: Some(self.path.display().to_string()), bytes })
+    }
+
+    fn query(&self, deps: &[Dependency]) -> Vec<RawFinding> {
+        let mut findings = Vec::new();
+        for (i, dep) in deps.iter().enumerate() {
+            let Some(entries) = self.advisories.get(&osv::key(dep.ecosystem.as_str(), &dep.name)) else {
+                continue;
+            };
+            let found = entries.iter().filter_map(|affected| osv::finding(i, dep, affected, SOURCE, &self.snapshot));
+            findings.extend(found);
+        }
+        findings
+    }
+}
+
+/// OSV events as they are read from advisory files, once they are known to
+/// open with `introduced` and to close every interval they open once.
+fn check_events(events: &[BTreeMap<String, String>]) -> Result<Vec<HashMap<String, String>>, String> {
+    let mut open = false;
+    for (i, event) in events.iter().enumerate() {
+        let (kind, version) = match event.iter().collect::<Vec<_>>()[..] {
+            [(kind, version)] => (kind.as_str(), version),
+            _ => return Err(format!("event {} must have exactly one of introduced, fixed or last_affected", i + 1)),
+        };
+        if version.trim().is_empty() {
+            return Err(format!("event {} ({}) has no version", i + 1, kind));
+        }
+        match (kind, open) {
+            ("introduced", _) => open = true,
+            ("fixed" | "last_affected", true) => open = false,
+            ("fixed" | "last_affected", false) => {
+                return Err(format!("event {} ({} {}) closes no introduced version", i + 1, kind, version));
+            }
+            _ => {
+                return Err(format!(
+                    "event {}: unknown event '{}' (expected introduced, fixed or last_affected)",
+                    i + 1,
+                    kind
+                ));
+            }
+        }
+    }
+    if events.is_empty() {
+        return Err("no events".to_string());
+    }
+    Ok(events.iter().map(|event| event.clone().into_iter().collect()).collect())
+}
+
+/// The versions and intervals a range expression in `ecosystem`'s syntax
+/// affects.
+pub(super) fn affecting(ecosystem: Ecosystem, expression: &str) -> Result<(Vec<String>, Vec<Interval>), String> {
+    let mut versions = Vec::new();
+    let mut intervals = Vec::new();
+    for alternative in parse_range(ecosystem, expression)? {
+        match alternative {
+            Alternative::Exact(version) => versions.push(version),
+            Alternative::Within(interval) => intervals.push(interval),
+        }
+    }
+    Ok((versions, intervals))
+}
+
+/// Parse a range expression in `ecosystem`'s syntax into its alternatives.
+fn parse_range(ecosystem: Ecosystem, expression: &str) -> Result<Vec<Alternative>, String> {
+    let syntax = Syntax::of(ecosystem);
+    if syntax != Syntax::Semver && expression.contains("||") {
+        return Err(format!(
+            "{} ranges have no '||'; list the alternatives as separate ranges",
+            ecosystem.as_str()
+        ));
+    }
+    expression.split("||").map(|alternative| parse_alternative(syntax, alternative.trim())).collect()
+}
+
+/// The comparisons an alternative is made of, as operator and version.
+fn comparisons(syntax: Syntax, alternative: &str) -> Result<Vec<(String, String)>, String> {
+    const OPERATORS: [&str; 12] = ["===", "==", "~=", "!=", "~>", "<=", ">=", "<", ">", "=", "^", "~"];
+    let separators: &[char] = if syntax == Syntax::Pep440 { &[','] } else { &[',', ' ', '\t'] };
+    let tokens: Vec<&str> = alternative.split(separators).map(str::trim).filter(|t| !t.is_empty()).collect();
+
+    let mut comparisons = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        // `1.0.0 - 1.4.2`
+        if syntax == Syntax::Semver && tokens.peek() == Some(&"-") {
+            tokens.next();
+            let last = tokens.next().ok_or("expected a version after '-'")?;
+            comparisons.push((">=".to_string(), token.to_string()));
+            comparisons.push(("<=".to_string(), last.to_string()));
+            continue;
+        }
+        let operator = OPERATORS.iter().find(|op| token.starts_with(*op)).copied().unwrap_or("");
+        let mut version = token[operator.len()..].trim().to_string();
+        // `>= 1.2.3` and `~> 2.3`
+        if version.is_empty() && !operator.is_empty() {
+            version = tokens.next().ok_or_else(|| format!("expected a version after '{}'", operator))?.to_string();
+        }
+        if !version.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err(format!("'{}{}': expected a version after '{}'", operator, version, operator));
+        }
+        comparisons.push((operator.to_string(), version));
+    }
+    Ok(comparisons)
+}
+
+fn parse_alternative(syntax: Syntax, alternative: &str) -> Result<Alternative, String> {
+    if alternative == "*" {
+        return Ok(Alternative::Within(Interval {
+            introduced: None,
+            fixed: None,
+            last_affected: None,
+        }));
+    }
+    let comparisons = comparisons(syntax, alternative)?;
+    if comparisons.is_empty() {
+        return Err("expected a range".to_string());
+    }
+    let mut interval = Interval {
+        introduced: None,
+        fixed: None,
+        last_affected: None,
+    };
+    let lower = |interval: &mut Interval, version: &str| match &interval.introduced {
+        Some(other) => Err(format!("'>={}' and '>={}' both bound it from below", other, version)),
+        None => {
+            interval.introduced = Some(version.to_string());
+            Ok(())
+        }
+    };
+    let upper = |interval: &mut Interval, fixed: Option<String>, last: Option<String>| {
+        if let Some(other) = interval.fixed.as_ref().map(|f| format!("<{}", f)) {
+            return Err(format!("'{}' and another upper bound both bound it from above", other));
+        }
+        if let Some(other) = interval.last_affected.as_ref().map(|l| format!("<={}", l)) {
+            return Err(format!("'{}' and another upper bound both bound it from above", other));
+        }
+        interval.fixed = fixed;
+        interval.last_affected = last;
+        Ok(())
+    };
+
+    for (operator, version) in &comparisons {
+        match (syntax, operator.as_str()) {
+            (_, "" | "=" | "==" | "===") => {
+                if version.contains('*') || version.ends_with(".x") {
+                    let prefix = version.trim_end_matches(['*', 'x']).trim_end_matches('.');
+                    return Err(format!(
+                        "wildcards aren't supported; write '>={}, <' and the next version instead",
+                        prefix
+                    ));
+                }
+                if comparisons.len() > 1 {
+                    return Err(format!("'{}{}' is an exact version and can't be combined", operator, version));
+                }
+                if syntax == Syntax::Pep440 && operator.is_empty() {
+                    return Err(format!("'{}': PEP 440 specifiers need an operator, like '=={}'", version, version));
+                }
+                return Ok(Alternative::Exact(version.clone()));
+            }
+            (_, ">=") => lower(&mut interval, version)?,
+            (_, "<") => upper(&mut interval, Some(version.clone()), None)?,
+            (_, "<=") => upper(&mut interval, None, Some(version.clone()))?,
+            (_, ">") => {
+                return Err(format!(
+                    "'>{}' can't be expressed as affected versions, which start at one: use '>=' with the first \
+                     affected version",
+                    version
+                ));
+            }
+            (_, "!=") => {
+                return Err(format!(
+                    "'!={}' can't be expressed as affected versions: split the range around it",
+                    version
+                ));
+            }
+            (Syntax::Pep440, "~=") | (Syntax::Semver, "^" | "~" | "~>") => {
+                let next = next_version(operator, version)?;
+                lower(&mut interval, version)?;
+                upper(&mut interval, Some(next), None)?;
+            }
+            _ => {
+                let allowed = match syntax {
+                    Syntax::Pep440 => "==, ~=, <, <= and >=",
+                    Syntax::Semver => "=, ^, ~, ~>, <, <=, >= and -",
+                    Syntax::Comparisons => "=, <, <= and >=",
+                };
+                return Err(format!("'{}' isn't an operator here (expected {})", operator, allowed));
+            }
+        }
+    }
+    Ok(Alternative::Within(interval))
+}
+
+/// The first version a `^`, `~`, `~>` or `~=` range leaves out:
+/// `^1.2.3` → `2.0.0`, `^0.2.3` → `0.3.0`, `~1.2.3` → `1.3.0`,
+/// `~> 2.3` and `~=2.3` → `3`, `~=2.3.1` → `2.4`.
+fn next_version(operator: &str, version: &str) -> Result<String, String> {
+    let (prefix, release) = version.strip_prefix('v').map_or(("", version), |release| ("v", release));
+    let release = release.split(['-', '+']).next().unwrap_or(release);
+    let parts: Vec<u64> = release
+        .split('.')
+        .map(|part| part.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("'{}{}': {} needs a version of numbers and dots", operator, version, operator))?;
+    let (bump, keep) = match operator {
+        // The first non-zero part, or the last when all are zero
+        "^" => (parts.iter().position(|&p| p != 0).unwrap_or(parts.len() - 1), parts.len()),
+        "~" => (usize::from(parts.len() > 1), parts.len()),
+        "~=" if parts.len() < 2 => {
+            return Err(format!("'~={}': ~= needs at least two parts, like ~={}.0", version, version));
+        }
+        _ => (parts.len().saturating_sub(2), parts.len().saturating_sub(1).max(1)),
+    };
+    let next: Vec<String> = (0..keep)
+        .map(|i| match i.cmp(&bump) {
+            std::cmp::Ordering::Less => parts[i],
+            std::cmp::Ordering::Equal => parts[i] + 1,
+            std::cmp::Ordering::Greater => 0,
+        })
+        .map(|part| part.to_string())
+        .collect();
+    Ok(format!("{}{}", prefix, next.join(".")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisories::osv::LocalDb;
+    use crate::ecosystems::Scope;
+
+    fn dep(ecosystem: Ecosystem, name: &str, version: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+            ecosystem,
+            scope: Scope::Runtime,
+            file: "fixture".to_string(),
+            line: 1,
+            col: None,
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    fn within(introduced: Option<&str>, fixed: Option<&str>, last_affected: Option<&str>) -> Alternative {
+        Alternative::Within(Interval {
+            introduced: introduced.map(str::to_string),
+            fixed: fixed.map(str::to_string),
+            last_affected: last_affected.map(str::to_string),
+        })
+    }
+
+    #[test]
+    fn ranges_are_read_in_each_ecosystems_syntax() {
+        let parse = |ecosystem, expression| parse_range(ecosystem, expression).unwrap();
+        assert_eq!(parse(Ecosystem::Npm, "<3.2.1"), [within(None, Some("3.2.1"), None)]);
+        assert_eq!(
+            parse(Ecosystem::Npm, ">= 1.0.0 <1.4.0 || ^2.1.3 || 3.0.0"),
+            [
+                within(Some("1.0.0"), Some("1.4.0"), None),
+                within(Some("2.1.3"), Some("3.0.0"), None),
+                Alternative::Exact("3.0.0".to_string())
+            ]
+        );
+        assert_eq!(parse(Ecosystem::CratesIo, "^0.2.3"), [within(Some("0.2.3"), Some("0.3.0"), None)]);
+        assert_eq!(parse(Ecosystem::CratesIo, "^0.0.3"), [within(Some("0.0.3"), Some("0.0.4"), None)]);
+        assert_eq!(parse(Ecosystem::Npm, "~1.2.3"), [within(Some("1.2.3"), Some("1.3.0"), None)]);
+        assert_eq!(parse(Ecosystem::Go, "~v1.2.3"), [within(Some("v1.2.3"), Some("v1.3.0"), None)]);
+        assert_eq!(parse(Ecosystem::Npm, "1.0.0 - 1.4.2"), [within(Some("1.0.0"), None, Some("1.4.2"))]);
+        assert_eq!(parse(Ecosystem::RubyGems, "~> 2.3"), [within(Some("2.3"), Some("3"), None)]);
+        assert_eq!(parse(Ecosystem::PyPI, ">=1.0, <2.0"), [within(Some("1.0"), Some("2.0"), None)]);
+        assert_eq!(parse(Ecosystem::PyPI, "~=1.4.5"), [within(Some("1.4.5"), Some("1.5"), None)]);
+        assert_eq!(parse(Ecosystem::PyPI, "==2.2.0"), [Alternative::Exact("2.2.0".to_string())]);
+        assert_eq!(parse(Ecosystem::Debian, "<= 2.36-9"), [within(None, None, Some("2.36-9"))]);
+        assert_eq!(parse(Ecosystem::Maven, "*"), [within(None, None, None)]);
+
+        let error = |ecosystem, expression| parse_range(ecosystem, expression).unwrap_err();
+        assert!(error(Ecosystem::PyPI, ">1.0").contains("use '>=' with the first affected version"));
+        assert!(error(Ecosystem::PyPI, "<2 || >=3").contains("PyPI ranges have no '||'"));
+        assert!(error(Ecosystem::PyPI, "1.0").contains("PEP 440 specifiers need an operator"));
+        assert!(error(Ecosystem::PyPI, "^1.0").contains("'^' isn't an operator here (expected ==, ~=, <, <= and >=)"));
+        assert!(error(Ecosystem::PyPI, "~=1").contains("~= needs at least two parts"));
+        assert!(error(Ecosystem::Debian, "^1.0").contains("'^' isn't an operator here"));
+        assert!(error(Ecosystem::Npm, ">=1.0.0 >=1.2.0").contains("both bound it from below"));
+        assert!(error(Ecosystem::Npm, "<1 <=2").contains("both bound it from above"));
+        assert!(error(Ecosystem::Npm, "1.x").contains("wildcards aren't supported; write '>=1, <'"));
+        assert!(error(Ecosystem::Npm, "=1.0.0 <2").contains("is an exact version and can't be combined"));
+        assert!(error(Ecosystem::Npm, ">=").contains("expected a version after '>='"));
+        assert!(error(Ecosystem::Npm, "^1.x.0").contains("^ needs a version of numbers and dots"));
+    }
+
+    #[test]
+    fn load_errors_name_the_place_and_the_problem() {
+        let dir = crate::testutil::scratch_dir("private-errors");
+        let path = dir.join("advisories.toml");
+        let load = |content: &str| {
+            fs::write(&path, content).unwrap();
+            let mut source = PrivateAdvisories::new(path.clone());
+            source.refresh(&dir, SystemTime::now()).map(|_| ()).unwrap_err()
+        };
+        let file = path.display().to_string();
+
+        let error = load(
+            "[[advisories]]\nid = \"ACME-1\"\n[[advisories.affected]]\necosystem = \"npm\"\npackage = \"auth\"\n\
+             ranges = [\"<1.0.0\", \">2.0.0\"]\n",
+        );
+        let expected = format!("{}:6:21: advisory ACME-1 (npm auth): range '>2.0.0': ", file);
+        assert!(error.starts_with(&expected), "{}", error);
+        let affected = "[[advisories]]\nid = \"ACME-1\"\n[[advisories.affected]]\n";
+        let error = load(&format!("{}ecosystem = \"pip\"\npackage = \"a\"\n", affected));
+        assert!(error.starts_with(&format!("{}:4:13: advisory ACME-1: unknown ecosystem 'pip'", file)), "{}", error);
+        let error = load(&format!("{}ecosystem = \"npm\"\npackage = \"a\"\n", affected));
+        assert!(error.contains("advisory ACME-1 (npm a) affects no versions"), "{}", error);
+        let error = load(
+            "[[advisories]]\nid = \"ACME-1\"\n[[advisories.affected]]\necosystem = \"npm\"\npackage = \"a\"\n\
+             events = [{fixed = \"1.0.0\"}]\n",
+        );
+        assert!(error.contains("events: event 1 (fixed 1.0.0) closes no introduced version"), "{}", error);
+        let error = load("[[advisories]]\nid = \"ACME-1\"\nseverity = \"HIGH\"\nfixed = \"1.0\"\naffected = []\n");
+        assert!(error.starts_with(&format!("{}:4:1: unknown field `fixed`", file)), "{}", error);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn private_advisories_match_like_their_osv_equivalents() {
+        let dir = crate::testutil::scratch_dir("private-osv");
+        fs::create_dir_all(dir.join("osv")).unwrap();
+        let osv = serde_json::json!({
+            "id": "ACME-2026-1",
+            "aliases": ["CVE-2026-0001"],
+            "summary": "Session tokens are logged",
+            "published": "2026-03-02T00:00:00Z",
+            "database_specific": {"severity": "HIGH", "cwe_ids": ["CWE-532"]},
+            "affected": [
+                {
+                    "package": {"ecosystem": "npm", "name": "@acme/auth"},
+                    "ranges": [{"type": "SEMVER", "events": [
+                        {"introduced": "0"}, {"fixed": "3.2.1"}, {"introduced": "4.0.0"}, {"fixed": "4.1.2"}
+                    ]}],
+                    "versions": ["5.0.0-rc.1"]
+                },
+                {
+                    "package": {"ecosystem": "PyPI", "name": "acme_client"},
+                    "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "1.4.5"}, {"fixed": "1.5"}]}]
+                },
+                {
+                    "package": {"ecosystem": "crates.io", "name": "acme-rpc"},
+                    "ranges": [{"type": "SEMVER", "events": [{"introduced": "0.2.0"}, {"last_affected": "0.2.7"}]}]
+                }
+            ]
+        });
+        fs::write(dir.join("osv/ACME-2026-1.json"), osv.to_string()).unwrap();
+        let private = r#"
+            [[advisories]]
+            id = "ACME-2026-1"
+            aliases = ["CVE-2026-0001"]
+            summary = "Session tokens are logged"
+            published = "2026-03-02T00:00:00Z"
+            severity = "HIGH"
+            cwe = ["CWE-532"]
+
+            [[advisories.affected]]
+            ecosystem = "npm"
+            package = "@acme/auth"
+            ranges = ["<3.2.1 || >=4.0.0 <4.1.2", "=5.0.0-rc.1"]
+
+            [[advisories.affected]]
+            ecosystem = "PyPI"
+            package = "Acme-Client"
+            ranges = ["~=1.4.5"]
+
+            [[advisories.affected]]
+            ecosystem = "crates.io"
+            package = "acme-rpc"
+            events = [{introduced = "0.2.0"}, {last_affected = "0.2.7"}]
+        "#;
+        fs::write(dir.join("advisories.toml"), private).unwrap();
+
+        let mut db = LocalDb::new(dir.join("osv").to_str().unwrap(), "memory", 1);
+        db.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+        let mut source = PrivateAdvisories::new(dir.join("advisories.toml"));
+        let snapshot = source.refresh(&dir.join("cache"), SystemTime::now()).unwrap();
+        assert_eq!(snapshot.source, "private");
+
+        let mut deps = Vec::new();
+        for version in ["0.1.0", "3.2.0", "3.2.1", "4.0.0", "4.1.1", "4.1.2", "5.0.0-rc.1", "5.0.0"] {
+            deps.push(dep(Ecosystem::Npm, "@acme/auth", version));
+        }
+        for version in ["1.4.4", "1.4.5", "1.4.9", "1.5", "1.5.0"] {
+            deps.push(dep(Ecosystem::PyPI, "acme.client", version));
+        }
+        for version in ["0.1.9", "0.2.0", "0.2.7", "0.2.8"] {
+            deps.push(dep(Ecosystem::CratesIo, "acme-rpc", version));
+        }
+        let matched = |findings: Vec<RawFinding>| -> Vec<_> {
+            findings
+                .into_iter()
+                .map(|f| (f.dependency, f.id, f.severity, f.fixed_in, f.provenance.matcher, f.cwe))
+                .collect()
+        };
+        let from_osv = matched(db.query(&deps));
+        let affected: Vec<&str> = from_osv.iter().map(|f| deps[f.0].version.as_str()).collect();
+        assert_eq!(affected, ["0.1.0", "3.2.0", "4.0.0", "4.1.1", "5.0.0-rc.1", "1.4.5", "1.4.9", "0.2.0", "0.2.7"]);
+        assert_eq!(matched(source.query(&deps)), from_osv);
+        // Ranges are reported as written, without OSV's `introduced: 0`
+        let ranges: Vec<String> = source.query(&deps[..2]).into_iter().map(|f| f.provenance.range).collect();
+        assert_eq!(ranges, ["<3.2.1", "<3.2.1"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use super::{AdvisorySource, RawFinding};
+use crate::ecosystems::Dependency;
+use crate::fingerprint;
+use crate::fsutil;
+use crate::summary;
+
+/// Version of the cache file layout. Files of another version are ignored
+/// rather than misread, and replaced on the next save.
+const FORMAT: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    format: u32,
+    /// By purl without version, with `?distro=<release>` for OS packages.
+    entries: BTreeMap<String, Entry>,
+}
+
+/// The entries of the query caches last saved with a [`QueryCache::warm_within`]
+/// budget they fit in, by path; loading one of them doesn't read its file.
+static WARM: Mutex<BTreeMap<PathBuf, BTreeMap<String, Entry>>> = Mutex::new(BTreeMap::new());
+
+/// Drop the kept entries, so the next load reads the files.
+pub(super) fn forget_warm() {
+    WARM.lock().unwrap_or_else(PoisonError::into_inner).clear();
+}
+
+/// What a source found for one version of a package.
+#[derive(Serialize, Deserialize, Clone)]
+struct Entry {
+    version: String,
+    /// Id of the snapshot that was queried.
+    snapshot: String,
+    findings: Vec<RawFinding>,
+}
+
+/// A source's findings per package, kept between scans in
+/// `db/queries/<source>.json`. An entry answers only for the package
+/// version and snapshot it was recorded with; the others are queried again.
+pub struct QueryCache {
+    path: PathBuf,
+    snapshot: String,
+    file: CacheFile,
+    /// Bytes the entries may take to be kept in memory after a save.
+    warm_budget: usize,
+    /// Package versions answered from the cache.
+    pub hits: usize,
+    /// Package versions the source was queried for.
+    pub misses: usize,
+}
+
+impl QueryCache {
+    /// The cache of `source` in `db_cache`, keeping the entries of
+    /// `snapshot`; empty when the file is missing, unreadable, damaged or of
+    /// another format.
+    pub fn load(db_cache: &Path, source: &str, snapshot: &str) -> QueryCache {
+        let path = db_cache.join("queries").join(format!("{}.json", summary::file_stem(source)));
+        let warm = WARM.lock().unwrap_or_else(PoisonError::into_inner).get(&path).cloned();
+        let mut entries = warm.unwrap_or_else(|| {
+            fsutil::read_checked(&path)
+                .ok()
+                .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+                .filter(|file| file.format == FORMAT)
+                .map(|file| file.entries)
+                .unwrap_or_default()
+        });
+        entries.retain(|_, entry| entry.snapshot == snapshot);
+        QueryCache {
+            path,
+            snapshot: snapshot.to_string(),
+            file: CacheFile { format: FORMAT, entries },
+            warm_budget: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Keep the entries in memory after a save, for the next load in the
+    /// process, while they fit in `budget_mb`.
+    pub fn warm_within(mut self, budget_mb: u64) -> Self {
+        self.warm_budget = usize::try_from(budget_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX);
+        self
+    }
+
+    /// Advisories `source` reports for any of `deps`, querying it only for
+    /// the package versions the cache has no entry for, once each.
+    pub fn query(&mut self, source: &dyn AdvisorySource, deps: &[Dependency]) -> Vec<RawFinding> {
+        let keys: Vec<String> = deps.iter().map(key).collect();
+        let mut answers: HashMap<(&str, &str), Vec<RawFinding>> = HashMap::new();
+        let mut missed = Vec::new();
+        for (i, dep) in deps.iter().enumerate() {
+            let lookup = (keys[i].as_str(), dep.version.as_str());
+            if answers.contains_key(&lookup) {
+                continue;
+            }
+            match self.file.entries.get(&keys[i]).filter(|entry| entry.version == dep.version) {
+                Some(entry) => {
+                    self.hits += 1;
+                    answers.insert(lookup, entry.findings.clone());
+                }
+                None => {
+                    self.misses += 1;
+                    missed.push(i);
+                    answers.insert(lookup, Vec::new());
+                }
+            }
+        }
+
+        if !missed.is_empty() {
+            let queried: Vec<Dependency> = missed.iter().map(|&i| deps[i].clone()).collect();
+            for finding in source.query(&queried) {
+                let i = missed[finding.dependency];
+                if let Some(found) = answers.get_mut(&(keys[i].as_str(), deps[i].version.as_str())) {
+                    found.push(finding);
+                }
+            }
+            for &i in &missed {
+                let entry = Entry {
+                    version: deps[i].version.clone(),
+                    snapshot: self.snapshot.clone(),
+                    findings: answers[&(keys[i].as_str(), deps[i].version.as_str())].clone(),
+                };
+                self.file.entries.insert(keys[i].clone(), entry);
+            }
+        }
+
+        deps.iter()
+            .enumerate()
+            .flat_map(|(i, dep)| {
+                let found = &answers[&(keys[i].as_str(), dep.version.as_str())];
+                found.iter().map(move |finding| RawFinding {
+                    dependency: i,
+                    ..finding.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Write the cache back, when a query added to it.
+    pub fn save(&self) -> io::Result<()> {
+        if self.misses == 0 {
+            return Ok(());
+        }
+        let bytes = serde_json::to_vec(&self.file)?;
+        fsutil::write_checked(&self.path, &bytes)?;
+        if self.warm_budget > 0 {
+            let mut warm = WARM.lock().unwrap_or_else(PoisonError::into_inner);
+            if bytes.len() <= self.warm_budget {
+                warm.insert(self.path.clone(), self.file.entries.clone());
+            } else {
+                warm.remove(&self.path);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn key(dep: &Dependency) -> String {
+    let purl = fingerprint::purl(dep.ecosystem.as_str(), &dep.name);
+    match &dep.release {
+        Some(release) => format!("{}?distro={}", purl, release),
+        None => purl,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisories::{Matcher, Provenance};
+    use crate::ecosystems::{Ecosystem, Scope};
+    use crate::report::DbSnapshot;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::SystemTime;
+
+    /// Reports every lodash below 4.17.21, counting the packages queried.
+    #[derive(Default)]
+    struct Counting(AtomicUsize);
+
+    impl AdvisorySource for Counting {
+        fn refresh(&mut self, _cache: &Path, _now: SystemTime) -> Result<DbSnapshot, String> {
+            Err("not refreshed".to_string())
+        }
+
+        fn query(&self, deps: &[Dependency]) -> Vec<RawFinding> {
+            self.0.fetch_add(deps.len(), Ordering::Relaxed);
+            let affected = |dep: &Dependency| dep.name == "lodash" && dep.version.as_str() < "4.17.21";
+            deps.iter()
+                .enumerate()
+                .filter(|(_, dep)| affected(dep))
+                .map(|(dependency, _)| RawFinding {
+                    dependency,
+                    id: "GHSA-35jh-r3h4-6jhm".to_string(),
+                    aliases: vec!["CVE-2021-23337".to_string()],
+                    severity: "HIGH".to_string(),
+                    cvss_score: Some(7.2),
+                    summary: String::new(),
+                    description: "Command injection in lodash".to_string(),
+                    fixed_in: Some("4.17.21".to_string()),
+                    references: Vec::new(),
+                    packages: Vec::new(),
+                    published: None,
+                    cwe: vec!["CWE-77".to_string()],
+                    categories: Vec::new(),
+                    provenance: Provenance {
+                        source: "osv-local".to_string(),
+                        database: Some("GHSA".to_string()),
+                        snapshot: "osv-local@1".to_string(),
+                        matcher: Matcher::Range,
+                        range: "<4.17.21".to_string(),
+                        confidence: None,
+                    },
+                })
+                .collect()
+        }
+    }
+
+    fn npm(name: &str, version: &str, file: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+            ecosystem: Ecosystem::Npm,
+            scope: Scope::Runtime,
+            file: file.to_string(),
+            line: 1,
+            col: None,
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    #[test]
+    fn entries_answer_only_for_their_version_and_snapshot() {
+        let dir = crate::testutil::scratch_dir("querycache");
+        let source = Counting::default();
+        let deps = [npm("lodash", "4.17.15", "package.json"), npm("left-pad", "1.3.0", "package.json")];
+        let queried = || source.0.load(Ordering::Relaxed);
+
+        let mut cache = QueryCache::load(&dir, "osv-local", "osv-local@1");
+        let mut twice = deps.to_vec();
+        twice.push(npm("lodash", "4.17.15", "web/package.json"));
+        let found = cache.query(&source, &twice);
+        assert_eq!(found.iter().map(|f| f.dependency).collect::<Vec<_>>(), [0, 2]);
+        assert_eq!((cache.hits, cache.misses, queried()), (0, 2, 2));
+        cache.save().unwrap();
+
+        let mut cache = QueryCache::load(&dir, "osv-local", "osv-local@1");
+        let found = cache.query(&source, &deps);
+        assert_eq!((found.len(), found[0].id.as_str()), (1, "GHSA-35jh-r3h4-6jhm"));
+        assert_eq!(found[0].fixed_in.as_deref(), Some("4.17.21"));
+        assert_eq!((cache.hits, cache.misses, queried()), (2, 0, 2));
+
+        let upgraded = [npm("lodash", "4.17.21", "package.json"), deps[1].clone()];
+        assert!(cache.query(&source, &upgraded).is_empty());
+        assert_eq!((cache.hits, cache.misses, queried()), (3, 1, 3));
+
+        let mut cache = QueryCache::load(&dir, "osv-local", "osv-local@2");
+        cache.query(&source, &deps);
+        assert_eq!((cache.hits, cache.misses), (0, 2));
+
+        let path = dir.join("queries").join("osv-local.json");
+        let mut file: serde_json::Value = serde_json::from_slice(&fsutil::read_checked(&path).unwrap()).unwrap();
+        file["format"] = serde_json::json!(FORMAT + 1);
+        fs::write(&path, file.to_string()).unwrap();
+        let mut cache = QueryCache::load(&dir, "osv-local", "osv-local@1");
+        cache.query(&source, &deps);
+        assert_eq!((cache.hits, cache.misses), (0, 2));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::clock;
+use crate::ecosystems::Ecosystem;
+use crate::fsutil;
+use crate::report::{self, EcosystemFreshness};
+
+/// Version of the schedule file layout. A file of another version is
+/// ignored rather than misread, as if no scan had run yet.
+const FORMAT: u32 = 1;
+
+/// What `db_refresh = "scheduled"` knows of a workspace between scans, in
+/// `db/schedule.json`: the ecosystems its scans have used, and the snapshot
+/// serving them. Ecosystems no scan has used are never read; one appearing
+/// is refreshed right away, with the others.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct Schedule {
+    format: u32,
+    /// Id of the snapshot of the used ecosystems.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    /// Fingerprint of the database files the snapshot was built from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// By OSV ecosystem name.
+    #[serde(default)]
+    pub ecosystems: BTreeMap<String, EcosystemUse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EcosystemUse {
+    pub last_used_unix: u64,
+    /// When its advisories were last read from the database; `None` until
+    /// the snapshot including it is built.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refreshed_unix: Option<u64>,
+}
+
+/// The schedule file in the advisory cache `db_cache`.
+pub fn path(db_cache: &Path) -> PathBuf {
+    db_cache.join("schedule.json")
+}
+
+impl Schedule {
+    /// The schedule kept in `db_cache`; empty when there is none, or it is
+    /// damaged or of another format.
+    pub fn load(db_cache: &Path) -> Schedule {
+        fsutil::read_checked(&path(db_cache))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Schedule>(&bytes).ok())
+            .filter(|schedule| schedule.format == FORMAT)
+            .unwrap_or_default()
+    }
+
+    pub fn save(&mut self, db_cache: &Path) -> io::Result<()> {
+        self.format = FORMAT;
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        fsutil::create_dir_all(db_cache)?;
+        fsutil::write_checked(&path(db_cache), &json)
+    }
+
+    /// Record that a scan at `now` used `ecosystems`.
+    pub fn mark_used<'a>(&mut self, ecosystems: impl IntoIterator<Item = &'a Ecosystem>, now: SystemTime) {
+        let now = clock::unix_secs(now);
+        for ecosystem in ecosystems {
+            let used = self.ecosystems.entry(ecosystem.as_str().to_string()).or_insert(EcosystemUse {
+                last_used_unix: now,
+                refreshed_unix: None,
+            });
+            used.last_used_unix = now;
+        }
+    }
+
+    /// Every ecosystem a scan has used, in [`Ecosystem::ALL`] order.
+    pub fn used(&self) -> Vec<Ecosystem> {
+        Ecosystem::ALL.iter().copied().filter(|e| self.ecosystems.contains_key(e.as_str())).collect()
+    }
+
+    /// Whether the used ecosystems are due a refresh at `now`: one was never
+    /// refreshed, or the oldest refresh was `interval` ago.
+    pub fn due(&self, interval: Duration, now: SystemTime) -> bool {
+        let now = clock::unix_secs(now);
+        self.ecosystems
+            .values()
+            .any(|used| used.refreshed_unix.is_none_or(|at| at.saturating_add(interval.as_secs()) <= now))
+    }
+
+    /// Record that snapshot `id` of the database with `fingerprint` now
+    /// serves every used ecosystem, read at `now`.
+    pub fn refreshed(&mut self, id: &str, fingerprint: &str, now: SystemTime) {
+        let now = clock::unix_secs(now);
+        for used in self.ecosystems.values_mut() {
+            used.refreshed_unix = Some(now);
+        }
+        self.snapshot = Some(id.to_string());
+        self.fingerprint = Some(fingerprint.to_string());
+    }
+
+    /// How current each used ecosystem is, for the report's `freshness`:
+    /// fresh while the database hasn't changed from `fingerprint` since its
+    /// refresh, with when it was last used and next refreshed.
+    pub fn freshness(
+        &self,
+        fingerprint: &str,
+        fetched_at: Option<&str>,
+        interval: Duration,
+    ) -> Vec<EcosystemFreshness> {
+        let fresh = self.fingerprint.as_deref() == Some(fingerprint);
+        let at = |secs: u64| report::format_timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        self.used()
+            .into_iter()
+            .map(|ecosystem| {
+                let used = &self.ecosystems[ecosystem.as_str()];
+                EcosystemFreshness {
+                    ecosystem: ecosystem.as_str(),
+                    fresh,
+                    snapshot: (!fresh).then(|| self.snapshot.clone()).flatten(),
+                    fetched_at: fetched_at.map(str::to_string),
+                    last_used: Some(at(used.last_used_unix)),
+                    next_refresh: used.refreshed_unix.map(|secs| at(secs.saturating_add(interval.as_secs()))),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn ecosystems_are_due_when_new_or_past_the_interval() {
+        let dir = crate::testutil::scratch_dir("schedule");
+        let day = Duration::from_secs(86_400);
+        let at = |days: u64| UNIX_EPOCH + day * days as u32;
+
+        let mut schedule = Schedule::load(&dir);
+        assert_eq!(schedule, Schedule::default());
+        schedule.mark_used(&[Ecosystem::Npm, Ecosystem::PyPI], at(1));
+        assert_eq!(schedule.used(), [Ecosystem::PyPI, Ecosystem::Npm]);
+        assert!(schedule.due(day, at(1)));
+        schedule.refreshed("osv-local@0123456789abcdef", "fp1", at(1));
+        assert!(!schedule.due(day, at(1)) && schedule.due(day, at(2)));
+
+        // A new ecosystem is due at once, until the next refresh takes it in
+        schedule.mark_used(&[Ecosystem::Go], at(1));
+        assert!(schedule.due(day, at(1)));
+        schedule.ecosystems.remove("Go");
+
+        schedule.save(&dir).unwrap();
+        let loaded = Schedule::load(&dir);
+        assert_eq!(loaded.ecosystems, schedule.ecosystems);
+        let freshness = loaded.freshness("fp2", Some("1970-01-02T00:00:00Z"), day);
+        let rows: Vec<(&str, bool, Option<&str>, Option<&str>)> = freshness
+            .iter()
+            .map(|f| (f.ecosystem, f.fresh, f.snapshot.as_deref(), f.next_refresh.as_deref()))
+            .collect();
+        let stale = Some("osv-local@0123456789abcdef");
+        assert_eq!(
+            rows,
+            [
+                ("PyPI", false, stale, Some("1970-01-03T00:00:00Z")),
+                ("npm", false, stale, Some("1970-01-03T00:00:00Z"))
+            ]
+        );
+        assert_eq!(freshness[0].last_used.as_deref(), Some("1970-01-02T00:00:00Z"));
+
+        // A file of another format is no schedule
+        fs::write(path(&dir), r#"{"format": 99, "ecosystems": {}}"#).unwrap();
+        assert_eq!(Schedule::load(&dir), Schedule::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::ecosystems::{Dependency, Ecosystem};
+
+/// Dependencies under the names advisories know them by.
+pub struct Aliased {
+    /// The dependencies, index for index, with `name_aliases` applied.
+    pub deps: Vec<Dependency>,
+    /// Per dependency, the name it was renamed to, if any.
+    pub renamed: Vec<Option<String>>,
+    /// Aliases left unapplied, one line each.
+    pub conflicts: Vec<String>,
+}
+
+/// Rename `deps` by `aliases`, which maps each ecosystem's manifest names
+/// to advisory names. Names compare as the ecosystem compares them.
+///
+/// An alias naming a package that is itself among `deps` is a conflict
+/// and isn't applied: the advisories of that package would otherwise be
+/// reported twice, once under each name.
+pub fn apply(deps: &[Dependency], aliases: &BTreeMap<String, BTreeMap<String, String>>) -> Aliased {
+    let mut by_name: BTreeMap<(&str, String), &String> = BTreeMap::new();
+    for (ecosystem, names) in aliases {
+        let Some(ecosystem) = Ecosystem::named(ecosystem) else {
+            continue;
+        };
+        for (name, alias) in names {
+            by_name.insert((ecosystem.as_str(), ecosystem.canonical_name(name)), alias);
+        }
+    }
+    let present: BTreeSet<(&str, String)> =
+        deps.iter().map(|dep| (dep.ecosystem.as_str(), dep.ecosystem.canonical_name(&dep.name))).collect();
+
+    let mut aliased = Aliased {
+        deps: Vec::with_capacity(deps.len()),
+        renamed: Vec::with_capacity(deps.len()),
+        conflicts: Vec::new(),
+    };
+    for dep in deps {
+        let canonical = dep.ecosystem.canonical_name(&dep.name);
+        let alias = by_name
+            .get(&(dep.ecosystem.as_str(), canonical.clone()))
+            .filter(|alias| dep.ecosystem.canonical_name(alias) != canonical);
+        let Some(alias) = alias else {
+            aliased.deps.push(dep.clone());
+            aliased.renamed.push(None);
+            continue;
+        };
+        if present.contains(&(dep.ecosystem.as_str(), dep.ecosystem.canonical_name(alias))) {
+            let conflict = format!(
+                "name_aliases: {} {} is not matched as {}, which is declared as well",
+                dep.ecosystem.as_str(),
+                dep.name,
+                alias
+            );
+            if !aliased.conflicts.contains(&conflict) {
+                aliased.conflicts.push(conflict);
+            }
+            aliased.deps.push(dep.clone());
+            aliased.renamed.push(None);
+            continue;
+        }
+        aliased.deps.push(Dependency {
+            name: alias.to_string(),
+            ..dep.clone()
+        });
+        aliased.renamed.push(Some(alias.to_string()));
+    }
+    aliased
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::Scope;
+
+    fn dep(ecosystem: Ecosystem, name: &str, file: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            ecosystem,
+            scope: Scope::Runtime,
+            file: file.to_string(),
+            line: 1,
+            col: None,
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    #[test]
+    fn aliases_rename_declared_names_unless_the_alias_is_declared_too() {
+        let aliases: BTreeMap<String, BTreeMap<String, String>> = serde_json::from_value(serde_json::json!({
+            "PyPI": {"PIL": "pillow", "python_dateutil": "python-dateutil", "yaml": "PyYAML"},
+            "npm": {"left-pad": "leftpad"}
+        }))
+        .unwrap();
+        let deps = [
+            dep(Ecosystem::PyPI, "pil", "requirements.txt"),
+            dep(Ecosystem::PyPI, "python-dateutil", "requirements.txt"),
+            dep(Ecosystem::PyPI, "yaml", "requirements.txt"),
+            dep(Ecosystem::PyPI, "pyyaml", "requirements.txt"),
+            dep(Ecosystem::Npm, "PIL", "package.json"),
+        ];
+        let aliased = apply(&deps, &aliases);
+
+        let names: Vec<&str> = aliased.deps.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["pillow", "python-dateutil", "yaml", "pyyaml", "PIL"]);
+        assert_eq!(aliased.renamed[0].as_deref(), Some("pillow"));
+        // An alias to the name it already has renames nothing
+        assert!(aliased.renamed[1..].iter().all(Option::is_none));
+        assert_eq!(
+            aliased.conflicts,
+            ["name_aliases: PyPI yaml is not matched as PyYAML, which is declared as well"]
+        );
+    }
+}
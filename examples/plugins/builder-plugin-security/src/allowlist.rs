@@ -0,0 +1,270 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ecosystems::{self, Dependency};
+use crate::fingerprint;
+use crate::fsutil;
+use crate::remediate;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// Finding for a dependency no allowlist approves.
+pub const UNAPPROVED: &str = "UNAPPROVED-DEPENDENCY";
+
+/// Severity of unapproved dependencies when a list doesn't set one.
+const DEFAULT_SEVERITY: Severity = Severity::High;
+
+/// An allowlist file as the data it holds, for `plugin.schema`.
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AllowlistFile {
+    /// Further lists whose packages are approved too, relative to this one,
+    /// e.g. a base list checked out from a central security repository.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Severity of the dependencies this list leaves unapproved; `HIGH`
+    /// when unset.
+    #[serde(default)]
+    pub severity: Option<Severity>,
+    /// Approved package URLs, each optionally with a version range after
+    /// `@` as the ecosystem's manifests write requirements:
+    /// `pkg:pypi/django@>=4.2,<5`, `pkg:npm/lodash@^4.17.21`,
+    /// `pkg:cargo/serde`.
+    #[serde(default)]
+    pub packages: Vec<String>,
+}
+
+/// One approved package.
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    /// The package URL without version, as [`fingerprint::purl`] writes it.
+    purl: String,
+    /// Versions approved; any when `None`.
+    range: Option<String>,
+    /// Severity of the list approving it.
+    severity: Severity,
+}
+
+/// The approved packages of an allowlist file and the files it includes.
+#[derive(Clone, Debug)]
+pub struct Allowlist {
+    /// Severity of dependencies no list names: that of the configured file.
+    severity: Severity,
+    entries: Vec<Entry>,
+}
+
+/// Load the allowlist file at `path` with everything it includes.
+///
+/// Unlike the suppression file, a missing allowlist is an error: scanning
+/// a locked-down target without its list would approve nothing silently.
+pub fn load(path: &Path) -> Result<Allowlist, String> {
+    let mut allowlist = Allowlist { severity: DEFAULT_SEVERITY, entries: Vec::new() };
+    let mut chain = Vec::new();
+    allowlist.severity = read(path, &mut chain, &mut BTreeSet::new(), &mut allowlist.entries)?;
+    Ok(allowlist)
+}
+
+/// Read one list, its includes first, into `entries` and return its
+/// severity. `chain` is the includes leading to it, which it may not be
+/// among; a file included twice is read once.
+fn read(
+    path: &Path,
+    chain: &mut Vec<PathBuf>,
+    seen: &mut BTreeSet<PathBuf>,
+    entries: &mut Vec<Entry>,
+) -> Result<Severity, String> {
+    let content = fsutil::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let canonical = fs::canonicalize(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if chain.contains(&canonical) {
+        return Err(format!("{}: included by itself", path.display()));
+    }
+    let file: AllowlistFile = toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let severity = file.severity.unwrap_or(DEFAULT_SEVERITY);
+    if !seen.insert(canonical.clone()) {
+        return Ok(severity);
+    }
+
+    chain.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for include in &file.include {
+        read(&dir.join(include), chain, seen, entries)?;
+    }
+    chain.pop();
+
+    for (index, package) in file.packages.iter().enumerate() {
+        let (purl, range) = parse(package).ok_or_else(|| {
+            format!("{}: packages[{}]: expected a package URL, got '{}'", path.display(), index, package)
+        })?;
+        entries.push(Entry { purl, range, severity });
+    }
+    Ok(severity)
+}
+
+/// Split `pkg:type/name@range` into the package URL, normalized as
+/// [`fingerprint::purl`] writes it, and the range. Qualifiers and subpaths
+/// are ignored. npm scopes may be written `@scope` or `%40scope`.
+fn parse(package: &str) -> Option<(String, Option<String>)> {
+    let package = package.trim().split(['?', '#']).next()?.replace("%40", "@");
+    let (kind, rest) = package.strip_prefix("pkg:")?.split_once('/')?;
+    let name_start = rest.rfind('/').map_or(0, |slash| slash + 1);
+    let (name, range) = match rest[name_start..].find('@').filter(|&at| at > 0) {
+        Some(at) => (&rest[..name_start + at], Some(rest[name_start + at + 1..].trim().to_string())),
+        None => (rest, None),
+    };
+    if kind.is_empty() || name.is_empty() || range.as_deref() == Some("") {
+        return None;
+    }
+    let kind = kind.to_lowercase();
+    let name = match kind.as_str() {
+        "npm" => name.replace('@', "%40"),
+        "pypi" => name.to_lowercase().replace('_', "-"),
+        "composer" => name.to_lowercase(),
+        _ => name.to_string(),
+    };
+    Some((format!("pkg:{}/{}", kind, name), range))
+}
+
+impl Allowlist {
+    /// Why `dep` isn't approved, and how severe that is; `None` when an
+    /// entry for its package admits its version.
+    fn refuse(&self, dep: &Dependency) -> Option<(Severity, String)> {
+        let purl = fingerprint::purl(dep.ecosystem.as_str(), &dep.name);
+        let named: Vec<&Entry> = self.entries.iter().filter(|entry| entry.purl == purl).collect();
+        if named.is_empty() {
+            let description = format!("{} {} ({}) is not on the allowlist", dep.name, dep.version, purl);
+            return Some((self.severity, description));
+        }
+        // A range the ecosystem's requirements can't express approves nothing
+        let admits = |range: &str| remediate::admits(dep.ecosystem, range, &dep.version) == Some(true);
+        if named.iter().any(|entry| entry.range.as_deref().is_none_or(admits)) {
+            return None;
+        }
+        let ranges: Vec<&str> = named.iter().filter_map(|entry| entry.range.as_deref()).collect();
+        let severity = named.iter().map(|entry| entry.severity).min().unwrap_or(self.severity);
+        let description = format!(
+            "{} {} is outside the versions the allowlist approves ({})",
+            dep.name,
+            dep.version,
+            ranges.join(" or ")
+        );
+        Some((severity, description))
+    }
+}
+
+/// Report every dependency `allowlist` doesn't approve, whether or not it
+/// has advisories. A manifest declaration is only checked when no lockfile
+/// among `deps` lists the package, since the locked version is the one
+/// installed.
+pub fn check(deps: &[Dependency], allowlist: &Allowlist, logs: &mut Vec<String>) -> Vec<Vulnerability> {
+    let locked: BTreeSet<(&str, &str)> = deps
+        .iter()
+        .filter(|dep| !ecosystems::is_manifest(&dep.file))
+        .map(|dep| (dep.ecosystem.as_str(), dep.name.as_str()))
+        .collect();
+    let mut checked = BTreeSet::new();
+    let mut findings = Vec::new();
+    for dep in deps {
+        if ecosystems::is_manifest(&dep.file) && locked.contains(&(dep.ecosystem.as_str(), dep.name.as_str())) {
+            continue;
+        }
+        if !checked.insert((dep.ecosystem.as_str(), dep.name.as_str(), dep.version.as_str(), dep.file.as_str())) {
+            continue;
+        }
+        let Some((severity, description)) = allowlist.refuse(dep) else {
+            continue;
+        };
+        findings.push(Vulnerability {
+            file: Some(dep.file.clone()),
+            line: Some(dep.line),
+            column: dep.col,
+            project: dep.project.clone(),
+            registry: dep.registry.clone(),
+            ..Vulnerability::new(UNAPPROVED, severity, &dep.name, &dep.version, description)
+        });
+    }
+    logs.push(format!(
+        "  Checked {} dependencies against the allowlist: {} unapproved",
+        checked.len(),
+        findings.len()
+    ));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::{Ecosystem, Scope};
+
+    fn dep(name: &str, version: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+            ecosystem: Ecosystem::PyPI,
+            scope: Scope::Runtime,
+            file: "requirements.txt".to_string(),
+            line: 1,
+            col: None,
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    #[test]
+    fn parses_package_urls_with_ranges() {
+        let django = parse("pkg:pypi/Django_Rest@>=4.2,<5");
+        assert_eq!(django, Some(("pkg:pypi/django-rest".into(), Some(">=4.2,<5".into()))));
+        assert_eq!(parse("pkg:npm/@ourorg/ui@^1.2.0"), Some(("pkg:npm/%40ourorg/ui".into(), Some("^1.2.0".into()))));
+        assert_eq!(parse("pkg:npm/%40ourorg/ui"), Some(("pkg:npm/%40ourorg/ui".into(), None)));
+        let errors = parse("pkg:golang/github.com/pkg/errors?vcs=git");
+        assert_eq!(errors, Some(("pkg:golang/github.com/pkg/errors".into(), None)));
+        assert_eq!(parse("pkg:cargo/serde@"), None);
+        assert_eq!(parse("lodash"), None);
+    }
+
+    #[test]
+    fn refuses_unlisted_packages_and_versions_outside_the_ranges() {
+        let dir = crate::testutil::scratch_dir("allowlist");
+        fs::create_dir_all(dir.join("central")).unwrap();
+        fs::write(
+            dir.join("central/base.toml"),
+            "severity = \"CRITICAL\"\npackages = [\"pkg:pypi/django@>=4.2,<5\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("allowlist.toml"),
+            "include = [\"central/base.toml\"]\nseverity = \"MEDIUM\"\npackages = [\"pkg:pypi/requests\"]\n",
+        )
+        .unwrap();
+        let allowlist = load(&dir.join("allowlist.toml")).unwrap();
+
+        let deps = [
+            dep("Django", "4.2.7"),
+            dep("django", "3.2.0"),
+            dep("requests", "2.31.0"),
+            dep("flask", "3.0.0"),
+        ];
+        let mut logs = Vec::new();
+        let found = check(&deps, &allowlist, &mut logs);
+        let refused: Vec<(&str, &str, Severity)> =
+            found.iter().map(|v| (v.package.as_str(), v.version.as_str(), v.severity)).collect();
+        // The included list rates its own packages; the configured one the rest
+        assert_eq!(refused, [("django", "3.2.0", Severity::Critical), ("flask", "3.0.0", Severity::Medium)]);
+        assert!(found[0].description.contains("outside the versions the allowlist approves (>=4.2,<5)"));
+        assert!(found.iter().all(|v| v.id == UNAPPROVED));
+        assert_eq!(logs, ["  Checked 4 dependencies against the allowlist: 2 unapproved"]);
+
+        fs::write(dir.join("central/base.toml"), "include = [\"../allowlist.toml\"]\n").unwrap();
+        assert!(load(&dir.join("allowlist.toml")).unwrap_err().contains("included by itself"));
+        fs::write(dir.join("central/base.toml"), "packages = [\"django\"]\n").unwrap();
+        assert!(load(&dir.join("allowlist.toml")).unwrap_err().contains("packages[0]: expected a package URL"));
+        assert!(load(&dir.join("missing.toml")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
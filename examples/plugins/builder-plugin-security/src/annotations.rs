@@ -0,0 +1,132 @@
+use crate::sanitize;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// GitHub shows at most this many annotations of one level per step; more
+/// are dropped silently, so the rest are summarized instead.
+pub const GITHUB_ANNOTATION_LIMIT: usize = 10;
+
+/// Whether annotations should be emitted for an `annotations` setting of
+/// `auto`, `github` or `off`.
+pub fn github_enabled(setting: &str, github_actions_env: Option<&str>) -> bool {
+    match setting {
+        "github" => true,
+        "auto" => github_actions_env == Some("true"),
+        _ => false,
+    }
+}
+
+/// GitHub Actions workflow commands for every finding with a known
+/// location, most severe first, capped at the annotation limit.
+///
+/// CRITICAL and HIGH findings become `::error`, everything else
+/// `::warning`; each level has its own limit. Messages carry the finding's
+/// summary, sanitized again and cut to `description_chars`.
+pub fn github(findings: &[Vulnerability], description_chars: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut truncated = 0;
+    let (mut errors, mut warnings) = (0, 0);
+
+    for finding in findings {
+        let (Some(file), Some(line)) = (&finding.file, finding.line) else {
+            continue;
+        };
+        let (level, emitted) = match finding.severity {
+            Severity::Critical | Severity::High => ("error", &mut errors),
+            _ => ("warning", &mut warnings),
+        };
+        if *emitted == GITHUB_ANNOTATION_LIMIT {
+            truncated += 1;
+            continue;
+        }
+        *emitted += 1;
+
+        let mut message = format!(
+            "{} {} — {} ({}): {}",
+            finding.package,
+            finding.version,
+            finding.id,
+            finding.severity,
+            sanitize::for_log(&finding.summary, description_chars)
+        );
+        if let Some(fixed) = &finding.fixed_in {
+            message.push_str(&format!(". Fixed in {}", fixed));
+        }
+        if let Some(url) = &finding.url {
+            message.push_str(&format!(". Details: {}", url));
+        }
+        let col = finding.column.map(|c| format!(",col={}", c)).unwrap_or_default();
+        lines.push(format!(
+            "::{} file={},line={}{},title={}::{}",
+            level,
+            escape_property(file),
+            line,
+            col,
+            escape_property(&format!("{} in {}", finding.id, finding.package)),
+            escape_data(&message)
+        ));
+    }
+
+    if truncated > 0 {
+        lines.push(format!(
+            "::notice::{} more vulnerabilities were not annotated (GitHub limit); see the security report",
+            truncated
+        ));
+    }
+    lines
+}
+
+fn escape_data(value: &str) -> String {
+    value.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(id: usize, severity: Severity) -> Vulnerability {
+        Vulnerability {
+            fixed_in: Some("4.17.21".to_string()),
+            file: Some("web/package.json".to_string()),
+            line: Some(12),
+            column: Some(5),
+            ..Vulnerability::new(
+                &format!("CVE-2021-{}", id),
+                severity,
+                "lodash",
+                "4.17.15",
+                "Prototype pollution".to_string(),
+            )
+        }
+    }
+
+    #[test]
+    fn annotations_are_capped_per_level() {
+        let mut findings: Vec<_> = (0..12).map(|i| finding(i, Severity::High)).collect();
+        findings.push(finding(99, Severity::Low));
+        let lines = github(&findings, 200);
+
+        assert_eq!(lines.len(), 12);
+        assert_eq!(
+            lines[0],
+            "::error file=web/package.json,line=12,col=5,title=CVE-2021-0 in lodash::lodash 4.17.15 — CVE-2021-0 (HIGH): Prototype pollution. Fixed in 4.17.21"
+        );
+        assert!(lines[10].starts_with("::warning "));
+        assert!(lines[11].starts_with("::notice::2 more"));
+    }
+
+    #[test]
+    fn summaries_are_sanitized() {
+        let mut hostile = finding(1, Severity::Medium);
+        hostile.description = "Full advisory text".to_string();
+        hostile.summary = format!("\u{1b}]0;owned\u{7}\u{1b}[1;31m{}\u{1b}[0m", "A".repeat(5000));
+        let lines = github(&[hostile], 50);
+
+        assert!(!lines[0].contains('\u{1b}') && !lines[0].contains("Full advisory text"));
+        assert!(lines[0].contains(&format!(": {}…. Fixed in", "A".repeat(49))));
+    }
+}
@@ -0,0 +1,846 @@
+use flate2::read::{DeflateDecoder, GzDecoder};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::config::ScanConfig;
+use crate::fsutil;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// Finding for an archive entry whose path climbs out of the archive.
+pub const PATH_TRAVERSAL: &str = "ARCHIVE-PATH-TRAVERSAL";
+/// Finding for an archive entry with an absolute path.
+pub const ABSOLUTE_PATH: &str = "ARCHIVE-ABSOLUTE-PATH";
+/// Finding for a link member pointing outside the archive.
+pub const LINK_OUTSIDE: &str = "ARCHIVE-LINK-OUTSIDE";
+/// Finding for an entry that decompresses beyond `archive_max_entry_size`.
+pub const ENTRY_TOO_LARGE: &str = "ARCHIVE-ENTRY-TOO-LARGE";
+/// Finding for an archive that decompresses beyond `archive_max_total_size`.
+pub const TOO_LARGE: &str = "ARCHIVE-TOO-LARGE";
+/// Finding for an archive with more than `archive_max_entries` entries.
+pub const TOO_MANY_ENTRIES: &str = "ARCHIVE-TOO-MANY-ENTRIES";
+
+/// Largest tar metadata entry (pax header, GNU long name) that is read.
+const MAX_METADATA: u64 = 1024 * 1024;
+/// Largest zip central directory that is read.
+const MAX_CENTRAL_DIRECTORY: u64 = 64 * 1024 * 1024;
+/// Longest symlink target that is read from a zip entry.
+const MAX_LINK_TARGET: u64 = 4096;
+
+/// What extracting one archive may take.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub entries: u64,
+    pub entry_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl Limits {
+    pub fn of(config: &ScanConfig) -> Self {
+        Limits {
+            entries: config.archive_max_entries,
+            entry_bytes: config.archive_max_entry_size,
+            total_bytes: config.archive_max_total_size,
+        }
+    }
+}
+
+/// Something about an archive that a build shouldn't produce. Offending
+/// entries are not extracted; the size and entry caps stop extraction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    PathTraversal(String),
+    AbsolutePath(String),
+    LinkOutside { entry: String, target: String },
+    EntryTooLarge { entry: String, limit: u64 },
+    TooLarge { limit: u64 },
+    TooManyEntries { limit: u64 },
+}
+
+impl Violation {
+    fn id(&self) -> &'static str {
+        match self {
+            Violation::PathTraversal(_) => PATH_TRAVERSAL,
+            Violation::AbsolutePath(_) => ABSOLUTE_PATH,
+            Violation::LinkOutside { .. } => LINK_OUTSIDE,
+            Violation::EntryTooLarge { .. } => ENTRY_TOO_LARGE,
+            Violation::TooLarge { .. } => TOO_LARGE,
+            Violation::TooManyEntries { .. } => TOO_MANY_ENTRIES,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Violation::PathTraversal(entry) => format!("entry {} climbs out of the archive with '..'", entry),
+            Violation::AbsolutePath(entry) => format!("entry {} has an absolute path", entry),
+            Violation::LinkOutside { entry, target } => {
+                format!("link {} points outside the archive, to {}", entry, target)
+            }
+            Violation::EntryTooLarge { entry, limit } => {
+                format!("entry {} decompresses to more than {} bytes (archive_max_entry_size)", entry, limit)
+            }
+            Violation::TooLarge { limit } => {
+                format!("the archive decompresses to more than {} bytes (archive_max_total_size)", limit)
+            }
+            Violation::TooManyEntries { limit } => {
+                format!("the archive has more than {} entries (archive_max_entries)", limit)
+            }
+        }
+    }
+}
+
+/// What extracting one archive did.
+#[derive(Debug, Default)]
+pub struct Extraction {
+    /// Regular files written.
+    pub files: u64,
+    /// Bytes decompressed, including entries that were skipped.
+    pub bytes: u64,
+    /// Links, encrypted entries and entries in unsupported formats, which
+    /// are never extracted.
+    pub skipped: u64,
+    pub violations: Vec<Violation>,
+    /// Why extraction ended early, when the archive is corrupt or the
+    /// extraction directory can't be written.
+    pub error: Option<String>,
+}
+
+enum Format {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+fn format(path: &str) -> Option<Format> {
+    let path = path.to_ascii_lowercase();
+    if path.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else if path.ends_with(".zip") {
+        Some(Format::Zip)
+    } else {
+        None
+    }
+}
+
+/// Whether `path` names an archive [`extract`] can open: `.tar`, `.tar.gz`,
+/// `.tgz` or `.zip`.
+pub fn is_archive(path: &str) -> bool {
+    format(path).is_some()
+}
+
+/// Extract `archive` into `dest`, which is replaced, within `limits`.
+///
+/// Sizes are counted as the data decompresses rather than taken from the
+/// archive's headers, entries are only written below `dest`, and links are
+/// never created. Nothing an archive holds makes this fail; what went wrong
+/// is in the returned [`Extraction`].
+pub fn extract(archive: &Path, dest: &Path, limits: Limits) -> Extraction {
+    extract_as(archive, format(&archive.to_string_lossy()), dest, limits)
+}
+
+/// Extract a container image layer like [`extract`]: a tar, gzip-compressed
+/// or not, told apart by its first bytes since layer blobs are named by
+/// digest.
+pub fn extract_layer(blob: &Path, dest: &Path, limits: Limits) -> Extraction {
+    let mut magic = [0u8; 2];
+    let gzip = fsutil::open(blob).and_then(|mut file| file.read_exact(&mut magic)).is_ok() && magic == [0x1f, 0x8b];
+    extract_as(blob, Some(if gzip { Format::TarGz } else { Format::Tar }), dest, limits)
+}
+
+fn extract_as(archive: &Path, format: Option<Format>, dest: &Path, limits: Limits) -> Extraction {
+    let mut extractor = Extractor {
+        dest: dest.to_path_buf(),
+        limits,
+        left: limits.total_bytes,
+        entries: 0,
+        extraction: Extraction::default(),
+    };
+    let result = fsutil::remove_dir_all(dest)
+        .or_else(|e| if e.kind() == io::ErrorKind::NotFound { Ok(()) } else { Err(e) })
+        .and_then(|()| fsutil::create_dir_all(dest))
+        .map_err(|e| format!("{}: {}", dest.display(), e))
+        .and_then(|()| fsutil::open(archive).map_err(|e| e.to_string()))
+        .and_then(|file| match format {
+            Some(Format::Tar) => extractor.tar(BufReader::new(file)),
+            Some(Format::TarGz) => extractor.tar(GzDecoder::new(BufReader::new(file))),
+            Some(Format::Zip) => extractor.zip(file),
+            None => Err("not a .tar, .tar.gz, .tgz or .zip archive".to_string()),
+        });
+    let mut extraction = extractor.extraction;
+    extraction.bytes = limits.total_bytes - extractor.left;
+    if let Err(e) = result {
+        extraction.error = Some(e);
+    }
+    extraction
+}
+
+/// Why an archive's extraction stopped before its end.
+enum Stop {
+    /// A cap was reached; the violation is recorded.
+    Capped,
+    Failed(String),
+}
+
+impl From<String> for Stop {
+    fn from(e: String) -> Self {
+        Stop::Failed(e)
+    }
+}
+
+struct Extractor {
+    dest: PathBuf,
+    limits: Limits,
+    /// Bytes that may still be decompressed.
+    left: u64,
+    entries: u64,
+    extraction: Extraction,
+}
+
+/// A reader that fails once more than its budget has been read from it.
+struct Budgeted<'a, R> {
+    inner: R,
+    left: &'a mut u64,
+    exceeded: bool,
+}
+
+impl<R: Read> Read for Budgeted<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if *self.left == 0 {
+            // Only data beyond the budget counts against it
+            if self.inner.read(&mut [0])? == 0 {
+                return Ok(0);
+            }
+            self.exceeded = true;
+            return Err(io::Error::other("decompressed size budget exceeded"));
+        }
+        let len = buf.len().min(usize::try_from(*self.left).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..len])?;
+        *self.left -= read as u64;
+        Ok(read)
+    }
+}
+
+impl Extractor {
+    /// Count an entry, stopping at the entry cap.
+    fn count_entry(&mut self) -> Result<(), Stop> {
+        self.entries += 1;
+        if self.entries > self.limits.entries {
+            let limit = self.limits.entries;
+            self.extraction.violations.push(Violation::TooManyEntries { limit });
+            return Err(Stop::Capped);
+        }
+        Ok(())
+    }
+
+    /// Where `name` is extracted to, or `None` when it isn't: the archive
+    /// root itself, or a path that leaves `dest`.
+    fn destination(&mut self, name: &str) -> Option<PathBuf> {
+        match sanitize_path(name) {
+            Ok(path) => path.map(|path| self.dest.join(path)),
+            Err(violation) => {
+                self.extraction.violations.push(violation);
+                None
+            }
+        }
+    }
+
+    /// Record a link member, which is skipped either way.
+    fn link(&mut self, name: &str, target: &str, from_root: bool) {
+        self.extraction.skipped += 1;
+        let Ok(Some(path)) = sanitize_path(name) else {
+            self.destination(name);
+            return;
+        };
+        let base = if from_root { Path::new("") } else { path.parent().unwrap_or(Path::new("")) };
+        if escapes(base, target) {
+            let (entry, target) = (name.to_string(), target.to_string());
+            self.extraction.violations.push(Violation::LinkOutside { entry, target });
+        }
+    }
+
+    /// Write `reader` to `path` unless it holds more than the entry budget,
+    /// returning whether it was written. A partial file is removed.
+    fn write(&mut self, name: &str, path: &Path, reader: &mut dyn Read) -> Result<bool, String> {
+        if let Some(parent) = path.parent() {
+            fsutil::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+        }
+        let mut file = fsutil::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let written = io::copy(&mut reader.take(self.limits.entry_bytes + 1), &mut file);
+        let written = written.map_err(|e| format!("{}: {}", name, e))?;
+        if written > self.limits.entry_bytes {
+            drop(file);
+            let _ = fsutil::remove_file(path);
+            let limit = self.limits.entry_bytes;
+            self.extraction.violations.push(Violation::EntryTooLarge { entry: name.to_string(), limit });
+            return Ok(false);
+        }
+        self.extraction.files += 1;
+        Ok(true)
+    }
+
+    /// Map a read that ran out of decompression budget to the cap.
+    fn read_error(&mut self, exceeded: bool, e: String) -> Stop {
+        if exceeded {
+            self.extraction.violations.push(Violation::TooLarge { limit: self.limits.total_bytes });
+            Stop::Capped
+        } else {
+            Stop::Failed(e)
+        }
+    }
+
+    fn tar(&mut self, reader: impl Read) -> Result<(), String> {
+        let mut left = self.left;
+        let mut stream = Budgeted { inner: reader, left: &mut left, exceeded: false };
+        let result = self.tar_entries(&mut stream);
+        let exceeded = stream.exceeded;
+        self.left = left;
+        match result {
+            Ok(()) | Err(Stop::Capped) => Ok(()),
+            Err(Stop::Failed(e)) => match self.read_error(exceeded, e) {
+                Stop::Capped => Ok(()),
+                Stop::Failed(e) => Err(e),
+            },
+        }
+    }
+
+    fn tar_entries<R: Read>(&mut self, stream: &mut Budgeted<'_, R>) -> Result<(), Stop> {
+        // Names set by pax headers and GNU long name entries for the next entry
+        let (mut long_name, mut long_link) = (None, None);
+        loop {
+            let mut header = [0u8; 512];
+            match read_full(stream, &mut header)? {
+                0 => return Err(Stop::Failed("truncated tar archive: no end-of-archive marker".to_string())),
+                512 => {}
+                _ => return Err(Stop::Failed("truncated tar header".to_string())),
+            }
+            if header.iter().all(|&b| b == 0) {
+                return Ok(());
+            }
+            if !checksum_matches(&header) {
+                return Err(Stop::Failed("corrupt tar header: checksum mismatch".to_string()));
+            }
+            let size = tar_size(&header[124..136]).ok_or_else(|| "corrupt tar header: invalid size".to_string())?;
+            let padded = size.div_ceil(512) * 512;
+            let name = long_name.take().unwrap_or_else(|| {
+                let (prefix, name) = (tar_field(&header[345..500]), tar_field(&header[..100]));
+                if prefix.is_empty() || &header[257..262] != b"ustar" {
+                    name
+                } else {
+                    format!("{}/{}", prefix, name)
+                }
+            });
+            let target = long_link.take().unwrap_or_else(|| tar_field(&header[157..257]));
+
+            let mut body = (&mut *stream).take(padded);
+            match header[156] {
+                b'x' | b'L' | b'K' => {
+                    if size > MAX_METADATA {
+                        return Err(Stop::Failed(format!("tar metadata entry of {} bytes", size)));
+                    }
+                    let mut data = Vec::new();
+                    body.read_to_end(&mut data).map_err(|e| e.to_string())?;
+                    data.truncate(size as usize);
+                    match header[156] {
+                        b'x' => {
+                            let records = pax_records(&data);
+                            long_name = records.iter().find(|(k, _)| k == "path").map(|(_, v)| v.clone());
+                            long_link = records.iter().find(|(k, _)| k == "linkpath").map(|(_, v)| v.clone());
+                        }
+                        b'L' => long_name = Some(tar_field(&data)),
+                        _ => long_link = Some(tar_field(&data)),
+                    }
+                    continue;
+                }
+                b'0' | 0 | b'7' => {
+                    self.count_entry()?;
+                    if let Some(path) = self.destination(&name) {
+                        let mut data = (&mut body).take(size);
+                        self.write(&name, &path, &mut data)?;
+                    }
+                }
+                b'5' => {
+                    self.count_entry()?;
+                    if let Some(path) = self.destination(&name) {
+                        fsutil::create_dir_all(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                    }
+                }
+                kind @ (b'1' | b'2') => {
+                    self.count_entry()?;
+                    // Hard link targets are archive paths, symlinks are relative to the link
+                    self.link(&name, &target, kind == b'1');
+                }
+                _ => {
+                    self.count_entry()?;
+                    self.extraction.skipped += 1;
+                }
+            }
+            io::copy(&mut body, &mut io::sink()).map_err(|e| e.to_string())?;
+        }
+    }
+
+    fn zip(&mut self, mut file: File) -> Result<(), String> {
+        let entries = central_directory(&mut file)?;
+        for entry in entries {
+            if let Err(stop) = self.count_entry() {
+                return match stop {
+                    Stop::Capped => Ok(()),
+                    Stop::Failed(e) => Err(e),
+                };
+            }
+            if entry.encrypted || !matches!(entry.method, 0 | 8) {
+                self.extraction.skipped += 1;
+                continue;
+            }
+            let symlink = entry.unix_mode.is_some_and(|mode| mode & 0o170000 == 0o120000);
+            let path = if symlink || entry.name.ends_with('/') {
+                None
+            } else {
+                match self.destination(&entry.name) {
+                    Some(path) => Some(path),
+                    None => continue,
+                }
+            };
+
+            let offset = entry.data_offset(&mut file)?;
+            file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+            let compressed = (&mut file).take(entry.compressed_size);
+            let inner: Box<dyn Read + '_> =
+                if entry.method == 8 { Box::new(DeflateDecoder::new(compressed)) } else { Box::new(compressed) };
+            let mut left = self.left;
+            let mut data = Budgeted { inner, left: &mut left, exceeded: false };
+            let result = if symlink {
+                let mut target = Vec::new();
+                let read = (&mut data).take(MAX_LINK_TARGET).read_to_end(&mut target).map_err(|e| e.to_string());
+                read.map(|_| self.link(&entry.name, &String::from_utf8_lossy(&target), false))
+            } else if let Some(path) = path {
+                self.write(&entry.name, &path, &mut data).map(|_| ())
+            } else {
+                // A directory entry
+                match self.destination(&entry.name) {
+                    Some(dir) => fsutil::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e)),
+                    None => Ok(()),
+                }
+            };
+            let exceeded = data.exceeded;
+            drop(data);
+            self.left = left;
+            if let Err(e) = result {
+                return match self.read_error(exceeded, e) {
+                    Stop::Capped => Ok(()),
+                    Stop::Failed(e) => Err(e),
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read until `buf` is full or the reader ends, returning how much was read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize, String> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    Ok(filled)
+}
+
+/// The relative path an entry is extracted to; `None` for the archive root
+/// (`./`).
+fn sanitize_path(name: &str) -> Result<Option<PathBuf>, Violation> {
+    let normalized = name.replace('\\', "/");
+    let bytes = normalized.as_bytes();
+    let drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if normalized.starts_with('/') || drive {
+        return Err(Violation::AbsolutePath(name.to_string()));
+    }
+    let mut path = PathBuf::new();
+    for part in normalized.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => return Err(Violation::PathTraversal(name.to_string())),
+            part => path.push(part),
+        }
+    }
+    Ok((!path.as_os_str().is_empty()).then_some(path))
+}
+
+/// Whether `target`, resolved from the archive directory `base`, leaves the
+/// archive.
+fn escapes(base: &Path, target: &str) -> bool {
+    let target = target.replace('\\', "/");
+    if target.starts_with('/') || target.as_bytes().get(1) == Some(&b':') {
+        return true;
+    }
+    let mut depth = base.components().count();
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if depth == 0 => return true,
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+    }
+    false
+}
+
+/// A NUL-terminated tar header field.
+fn tar_field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// A tar size field: octal, or GNU base-256 when the high bit is set.
+fn tar_size(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        let mut size: u64 = (field[0] & 0x7f).into();
+        for &byte in &field[1..] {
+            size = size.checked_mul(256)?.checked_add(byte.into())?;
+        }
+        return Some(size);
+    }
+    let text = tar_field(field);
+    let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+/// The header checksum: the byte sum with the checksum field as spaces.
+fn checksum_matches(header: &[u8; 512]) -> bool {
+    let Some(stored) = tar_size(&header[148..156]) else {
+        return false;
+    };
+    let sum: u64 = header.iter().enumerate().map(|(i, &b)| if (148..156).contains(&i) { 32 } else { b.into() }).sum();
+    sum == stored
+}
+
+/// `length key=value\n` records of a pax extended header.
+fn pax_records(data: &[u8]) -> Vec<(String, String)> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let Some(length) = std::str::from_utf8(&rest[..space]).ok().and_then(|l| l.parse::<usize>().ok()) else {
+            break;
+        };
+        if length <= space + 1 || length > rest.len() {
+            break;
+        }
+        let record = String::from_utf8_lossy(&rest[space + 1..length]);
+        if let Some((key, value)) = record.trim_end_matches('\n').split_once('=') {
+            records.push((key.to_string(), value.to_string()));
+        }
+        rest = &rest[length..];
+    }
+    records
+}
+
+/// An entry of a zip archive's central directory.
+struct ZipEntry {
+    name: String,
+    method: u16,
+    encrypted: bool,
+    compressed_size: u64,
+    /// File mode, when the entry was made on Unix.
+    unix_mode: Option<u32>,
+    local_header: u64,
+}
+
+impl ZipEntry {
+    /// Where the entry's data starts, after its local header.
+    fn data_offset(&self, file: &mut File) -> Result<u64, String> {
+        let mut header = [0u8; 30];
+        file.seek(SeekFrom::Start(self.local_header)).map_err(|e| e.to_string())?;
+        if read_full(file, &mut header)? != 30 || u32_at(&header, 0) != 0x0403_4b50 {
+            return Err(format!("corrupt zip archive: no local header for {}", self.name));
+        }
+        Ok(self.local_header + 30 + u64::from(u16_at(&header, 26)) + u64::from(u16_at(&header, 28)))
+    }
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// The entries listed by the central directory at the end of a zip file.
+fn central_directory(file: &mut File) -> Result<Vec<ZipEntry>, String> {
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    // The end record is 22 bytes plus a comment of at most 65535
+    let tail_len = len.min(22 + 65535);
+    let mut tail = vec![0u8; tail_len as usize];
+    file.seek(SeekFrom::Start(len - tail_len)).map_err(|e| e.to_string())?;
+    read_full(file, &mut tail)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == 0x0605_4b50)
+        .ok_or("corrupt zip archive: no end of central directory record")?;
+    let (count, size, offset) = (u16_at(&tail, end + 10), u32_at(&tail, end + 12), u32_at(&tail, end + 16));
+    if count == 0xffff || size == 0xffff_ffff || offset == 0xffff_ffff {
+        return Err("zip64 archives are not supported".to_string());
+    }
+    let (size, offset) = (u64::from(size), u64::from(offset));
+    if size > MAX_CENTRAL_DIRECTORY || offset + size > len {
+        return Err("corrupt zip archive: central directory out of bounds".to_string());
+    }
+
+    let mut directory = vec![0u8; size as usize];
+    file.seek(SeekFrom::Start(offset)).map_err(|e| e.to_string())?;
+    read_full(file, &mut directory)?;
+    let mut entries = Vec::new();
+    let mut at = 0;
+    while at + 46 <= directory.len() && u32_at(&directory, at) == 0x0201_4b50 {
+        let name_len = usize::from(u16_at(&directory, at + 28));
+        let extra_len = usize::from(u16_at(&directory, at + 30));
+        let comment_len = usize::from(u16_at(&directory, at + 32));
+        let name = directory.get(at + 46..at + 46 + name_len).ok_or("corrupt zip archive: truncated entry name")?;
+        let attributes = u32_at(&directory, at + 38);
+        entries.push(ZipEntry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method: u16_at(&directory, at + 10),
+            encrypted: u16_at(&directory, at + 8) & 1 != 0,
+            compressed_size: u32_at(&directory, at + 20).into(),
+            unix_mode: (directory[at + 5] == 3).then_some(attributes >> 16),
+            local_header: u32_at(&directory, at + 42).into(),
+        });
+        at += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(entries)
+}
+
+/// CRITICAL findings for `archive`'s violations, one per kind, listing the
+/// first few offending entries.
+pub fn findings(archive: &str, violations: &[Violation]) -> Vec<Vulnerability> {
+    let mut findings: Vec<Vulnerability> = Vec::new();
+    for violation in violations {
+        let id = violation.id();
+        if findings.iter().any(|f| f.id == id) {
+            continue;
+        }
+        let all: Vec<String> = violations.iter().filter(|v| v.id() == id).map(Violation::describe).collect();
+        let listed = all[..all.len().min(5)].join("; ");
+        let mut description = format!("{} may have been tampered with: {}", archive, listed);
+        if all.len() > 5 {
+            description.push_str(&format!("; and {} more", all.len() - 5));
+        }
+        findings.push(Vulnerability {
+            file: Some(archive.to_string()),
+            ..Vulnerability::new(id, Severity::Critical, archive, "", description)
+        });
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::{DeflateEncoder, GzEncoder};
+    use std::fs;
+    use std::io::Write;
+
+    const LIMITS: Limits = Limits { entries: 100, entry_bytes: 1000, total_bytes: 100_000 };
+
+    fn tar_header(name: &str, kind: u8, size: usize, link: &str) -> [u8; 512] {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = kind;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[148..156].fill(b' ');
+        let sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        header
+    }
+
+    fn tar(entries: &[(&str, u8, &[u8], &str)]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        for (name, kind, data, link) in entries {
+            tar.extend_from_slice(&tar_header(name, *kind, data.len(), link));
+            tar.extend_from_slice(data);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        }
+        tar.extend_from_slice(&[0; 1024]);
+        tar
+    }
+
+    /// A zip whose entries are `(name, unix mode, deflated, data)`; the
+    /// sizes it declares are those of the compressed data.
+    fn zip(entries: &[(&str, u32, bool, &[u8])]) -> Vec<u8> {
+        let (mut zip, mut directory) = (Vec::new(), Vec::new());
+        for (name, mode, deflated, data) in entries {
+            let data = if *deflated {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                data.to_vec()
+            };
+            let offset = zip.len() as u32;
+            let method: u16 = if *deflated { 8 } else { 0 };
+            let (size, name_len) = (data.len() as u32, name.len() as u16);
+            zip.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+            zip.extend_from_slice(&[20, 0, 0, 0]);
+            zip.extend_from_slice(&method.to_le_bytes());
+            zip.extend_from_slice(&[0; 8]);
+            for value in [size, size] {
+                zip.extend_from_slice(&value.to_le_bytes());
+            }
+            zip.extend_from_slice(&name_len.to_le_bytes());
+            zip.extend_from_slice(&[0, 0]);
+            zip.extend_from_slice(name.as_bytes());
+            zip.extend_from_slice(&data);
+
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&[20, 3, 20, 0, 0, 0]);
+            directory.extend_from_slice(&method.to_le_bytes());
+            directory.extend_from_slice(&[0; 8]);
+            for value in [size, size] {
+                directory.extend_from_slice(&value.to_le_bytes());
+            }
+            directory.extend_from_slice(&name_len.to_le_bytes());
+            directory.extend_from_slice(&[0; 8]);
+            directory.extend_from_slice(&(mode << 16).to_le_bytes());
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let (count, size, offset) = (entries.len() as u16, directory.len() as u32, zip.len() as u32);
+        zip.extend_from_slice(&directory);
+        zip.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        zip.extend_from_slice(&[0; 4]);
+        zip.extend_from_slice(&count.to_le_bytes());
+        zip.extend_from_slice(&count.to_le_bytes());
+        zip.extend_from_slice(&size.to_le_bytes());
+        zip.extend_from_slice(&offset.to_le_bytes());
+        zip.extend_from_slice(&[0, 0]);
+        zip
+    }
+
+    fn extract_bytes(dir: &Path, name: &str, bytes: &[u8], limits: Limits) -> Extraction {
+        fs::create_dir_all(dir).unwrap();
+        let archive = dir.join(name);
+        fs::write(&archive, bytes).unwrap();
+        extract(&archive, &dir.join("out"), limits)
+    }
+
+    #[test]
+    fn tar_entries_stay_inside_the_destination_and_within_budget() {
+        let dir = crate::testutil::scratch_dir("archives-tar");
+
+        let archive = tar(&[
+            ("./var/lib/dpkg/status", b'0', b"Package: zlib1g\n", ""),
+            ("../../evil.sh", b'0', b"rm -rf /\n", ""),
+            ("/etc/cron.d/evil", b'0', b"* * * * * root sh\n", ""),
+            ("lib/ok", b'2', b"", "../var/lib/dpkg/status"),
+            ("lib/escape", b'2', b"", "../../../etc/shadow"),
+            ("lib/hard", b'1', b"", "../etc/passwd"),
+            ("big.bin", b'0', &[7; 1500], ""),
+            ("after.txt", b'0', b"still extracted", ""),
+        ]);
+        let extraction = extract_bytes(&dir, "rootfs.tar", &archive, LIMITS);
+        assert_eq!(extraction.error, None);
+        assert_eq!(
+            extraction.violations,
+            vec![
+                Violation::PathTraversal("../../evil.sh".to_string()),
+                Violation::AbsolutePath("/etc/cron.d/evil".to_string()),
+                Violation::LinkOutside { entry: "lib/escape".to_string(), target: "../../../etc/shadow".to_string() },
+                Violation::LinkOutside { entry: "lib/hard".to_string(), target: "../etc/passwd".to_string() },
+                Violation::EntryTooLarge { entry: "big.bin".to_string(), limit: 1000 },
+            ]
+        );
+        assert_eq!((extraction.files, extraction.skipped), (2, 3));
+        assert_eq!(fs::read(dir.join("out/var/lib/dpkg/status")).unwrap(), b"Package: zlib1g\n");
+        assert!(dir.join("out/after.txt").is_file() && !dir.join("out/big.bin").exists());
+        assert!(!dir.join("evil.sh").exists() && !dir.join("out/lib/ok").exists());
+
+        // A gzip bomb: a megabyte of zeros in one entry, under a 64 KiB budget
+        let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gz.write_all(&tar(&[("zeros", b'0', &vec![0; 1 << 20], "")])).unwrap();
+        let limits = Limits { entry_bytes: 1 << 30, total_bytes: 64 * 1024, ..LIMITS };
+        let extraction = extract_bytes(&dir, "bomb.tar.gz", &gz.finish().unwrap(), limits);
+        assert_eq!(extraction.violations, vec![Violation::TooLarge { limit: 64 * 1024 }]);
+        assert_eq!((extraction.bytes, extraction.error), (64 * 1024, None));
+
+        let many: Vec<(String, u8, &[u8], &str)> = (0..5).map(|i| (format!("f{}", i), b'0', &b"x"[..], "")).collect();
+        let many: Vec<(&str, u8, &[u8], &str)> = many.iter().map(|(n, k, d, l)| (n.as_str(), *k, *d, *l)).collect();
+        let extraction = extract_bytes(&dir, "many.tar", &tar(&many), Limits { entries: 3, ..LIMITS });
+        assert_eq!(extraction.violations, vec![Violation::TooManyEntries { limit: 3 }]);
+        assert_eq!(extraction.files, 3);
+
+        let found = findings("dist/rootfs.tar", &extract_bytes(&dir, "rootfs.tar", &archive, LIMITS).violations);
+        let ids: Vec<&str> = found.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, vec![PATH_TRAVERSAL, ABSOLUTE_PATH, LINK_OUTSIDE, ENTRY_TOO_LARGE]);
+        assert!(found.iter().all(|f| f.severity == Severity::Critical));
+        assert!(found[2].description.contains("link lib/escape points outside"));
+        assert!(found[2].description.contains("link lib/hard points outside"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_entries_are_sanitized_and_their_declared_sizes_are_not_trusted() {
+        let dir = crate::testutil::scratch_dir("archives-zip");
+
+        let archive = zip(&[
+            ("lib/apk/db/installed", 0o100644, true, b"P:musl\nV:1.2.4-r1\n"),
+            ("..\\..\\evil.dll", 0o100644, false, b"MZ"),
+            ("C:\\Windows\\evil.dll", 0o100644, false, b"MZ"),
+            ("link", 0o120777, false, b"/etc/passwd"),
+            // Deflates to a few bytes, decompresses to 2000
+            ("bomb", 0o100644, true, &[0; 2000]),
+            ("docs/", 0o040755, false, b""),
+        ]);
+        let extraction = extract_bytes(&dir, "image.zip", &archive, LIMITS);
+        assert_eq!(extraction.error, None);
+        assert_eq!(
+            extraction.violations,
+            vec![
+                Violation::PathTraversal("..\\..\\evil.dll".to_string()),
+                Violation::AbsolutePath("C:\\Windows\\evil.dll".to_string()),
+                Violation::LinkOutside { entry: "link".to_string(), target: "/etc/passwd".to_string() },
+                Violation::EntryTooLarge { entry: "bomb".to_string(), limit: 1000 },
+            ]
+        );
+        assert_eq!(fs::read(dir.join("out/lib/apk/db/installed")).unwrap(), b"P:musl\nV:1.2.4-r1\n");
+        assert!(dir.join("out/docs").is_dir() && !dir.join("out/link").exists() && !dir.join("out/bomb").exists());
+
+        let limits = Limits { entry_bytes: 1 << 30, total_bytes: 1500, ..LIMITS };
+        let extraction = extract_bytes(&dir, "image.zip", &archive, limits);
+        assert_eq!(extraction.violations.last(), Some(&Violation::TooLarge { limit: 1500 }));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_archives_end_extraction_with_an_error() {
+        let dir = crate::testutil::scratch_dir("archives-corrupt");
+        let mut bad_checksum = tar(&[("a", b'0', b"x", "")]);
+        bad_checksum[0] = b'b';
+        let mut truncated = tar(&[("a", b'0', &[1; 600], "")]);
+        truncated.truncate(700);
+        for (name, bytes) in [
+            ("garbage.tar.gz", &b"not gzip"[..]),
+            ("checksum.tar", &bad_checksum),
+            ("truncated.tar", &truncated),
+            ("garbage.zip", b"PK\x03\x04"),
+        ] {
+            let extraction = extract_bytes(&dir, name, bytes, LIMITS);
+            assert!(extraction.error.is_some(), "{}", name);
+            assert!(extraction.violations.is_empty(), "{}", name);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use crate::ecosystems::{self, Ecosystem};
+use crate::fsutil;
+use crate::paths;
+
+/// Where OS package databases live in a container filesystem.
+const PACKAGE_DBS: &[&str] = &["var/lib/dpkg/status", "lib/apk/db/installed"];
+
+/// An OS package database among a build's outputs.
+#[derive(Debug, PartialEq)]
+pub struct PackageDb {
+    /// Path as a scanner source: the output itself or a path inside it.
+    pub source: String,
+    pub ecosystem: Ecosystem,
+    /// Release named by the filesystem's `os-release`, as OSV spells it.
+    pub release: Option<String>,
+}
+
+/// The package databases in `outputs`: outputs that are one, and those
+/// inside outputs that are container filesystems (only at their top level;
+/// outputs are not searched).
+pub fn package_dbs(root: &Path, outputs: &[String]) -> Vec<PackageDb> {
+    let mut found = Vec::new();
+    for output in outputs {
+        let output = paths::normalize_separators(output);
+        let path = paths::join_source(root, &output);
+        let candidates: Vec<(String, &str)> = if path.is_dir() {
+            PACKAGE_DBS
+                .iter()
+                .filter(|db| path.join(db).is_file())
+                .map(|db| (format!("{}/{}", output.trim_end_matches('/'), db), output.as_str()))
+                .collect()
+        } else {
+            PACKAGE_DBS
+                .iter()
+                .filter_map(|db| Some((output.clone(), output.strip_suffix(db)?)))
+                .collect()
+        };
+
+        for (source, fs_root) in candidates {
+            let Some(scanner) = ecosystems::detect(&source) else {
+                continue;
+            };
+            let fs_root = paths::join_source(root, fs_root);
+            found.push(PackageDb {
+                ecosystem: scanner.ecosystem(),
+                release: os_release(&fs_root).and_then(|content| release(&content, scanner.ecosystem())),
+                source,
+            });
+        }
+    }
+    found
+}
+
+/// Where a filesystem's `os-release` is, in the order systemd looks.
+pub const OS_RELEASE: &[&str] = &["etc/os-release", "usr/lib/os-release"];
+
+/// A filesystem's `os-release`.
+fn os_release(fs_root: &Path) -> Option<String> {
+    OS_RELEASE.iter().find_map(|file| fsutil::read_to_string(fs_root.join(file)).ok())
+}
+
+/// The release of `ecosystem` an `os-release` names, as OSV spells it; none
+/// when it names another distribution.
+pub fn release(os_release: &str, ecosystem: Ecosystem) -> Option<String> {
+    let field = |key: &str| {
+        os_release.lines().find_map(|line| {
+            let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+            Some(value.trim_matches(|c| c == '"' || c == '\'').to_string())
+        })
+    };
+    release_of(ecosystem, &field("ID")?, &field("VERSION_ID")?)
+}
+
+/// The OSV release for an os-release `ID` and `VERSION_ID`, when the
+/// distribution is the one the package database belongs to.
+fn release_of(ecosystem: Ecosystem, id: &str, version_id: &str) -> Option<String> {
+    match (ecosystem, id) {
+        // Debian advisories are per major release: `Debian:12`
+        (Ecosystem::Debian, "debian") => version_id.split('.').next().map(str::to_string),
+        // Alpine advisories are per branch: `Alpine:v3.18`, also for a
+        // pre-release of it (`3.20.0_alpha20240329`)
+        (Ecosystem::Alpine, "alpine") => {
+            let mut parts = version_id.split(|c: char| !c.is_ascii_digit());
+            Some(format!("v{}.{}", parts.next()?, parts.next().filter(|minor| !minor.is_empty())?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn finds_package_dbs_with_their_release() {
+        let root = crate::testutil::scratch_dir("artifacts");
+        let write = |path: &str, content: &str| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("out/debian/var/lib/dpkg/status", "");
+        write("out/debian/etc/os-release", "PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\nID=debian\nVERSION_ID=\"12\"\n");
+        write("out/alpine/lib/apk/db/installed", "");
+        write("out/alpine/usr/lib/os-release", "ID=alpine\nVERSION_ID=3.18.4\n");
+        write("out/ubuntu/var/lib/dpkg/status", "");
+        write("out/ubuntu/etc/os-release", "ID=ubuntu\nID_LIKE=debian\nVERSION_ID=\"22.04\"\n");
+        write("out/app.tar", "");
+
+        let outputs: Vec<String> = [
+            "out/debian/",
+            "out/alpine/lib/apk/db/installed",
+            r"out\ubuntu",
+            "out/app.tar",
+            "out/missing",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let found = package_dbs(&root, &outputs);
+        let db = |source: &str, ecosystem, release: Option<&str>| PackageDb {
+            source: source.to_string(),
+            ecosystem,
+            release: release.map(str::to_string),
+        };
+        assert_eq!(
+            found,
+            vec![
+                db("out/debian/var/lib/dpkg/status", Ecosystem::Debian, Some("12")),
+                db("out/alpine/lib/apk/db/installed", Ecosystem::Alpine, Some("v3.18")),
+                db("out/ubuntu/var/lib/dpkg/status", Ecosystem::Debian, None),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn os_release_selects_the_osv_release() {
+        let cases = [
+            ("ID=debian\nVERSION_ID=\"11\"\n", Ecosystem::Debian, Some("11")),
+            ("ID=alpine\nVERSION_ID=3.19.1\n", Ecosystem::Alpine, Some("v3.19")),
+            ("ID=alpine\nVERSION_ID=3.20.0_alpha20240329\n", Ecosystem::Alpine, Some("v3.20")),
+            // Debian testing and unstable have no VERSION_ID
+            ("ID=debian\nVERSION_CODENAME=trixie\n", Ecosystem::Debian, None),
+            ("ID=alpine\nVERSION_ID=3\n", Ecosystem::Alpine, None),
+            ("ID=debian\nVERSION_ID=\"12\"\n", Ecosystem::Alpine, None),
+        ];
+        for (os_release, ecosystem, expected) in cases {
+            assert_eq!(release(os_release, ecosystem).as_deref(), expected, "{}", os_release);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::io::{self, Read};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::config::ScanConfig;
+use crate::permissions;
+
+/// A scan phase with its own time budget.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    DbRefresh,
+    Extract,
+    Match,
+}
+
+impl Phase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::DbRefresh => "db_refresh",
+            Phase::Extract => "extract",
+            Phase::Match => "match",
+        }
+    }
+
+    fn setting(self) -> &'static str {
+        match self {
+            Phase::DbRefresh => "db_refresh_budget_secs",
+            Phase::Extract => "extract_budget_secs",
+            Phase::Match => "match_budget_secs",
+        }
+    }
+}
+
+/// The time limits of one scan.
+///
+/// Deadlines are plain values owned by the scan they belong to; nothing is
+/// process-wide, so one hook running out of time never affects another.
+pub struct Budget {
+    scan_deadline: Option<Instant>,
+    db_refresh: Option<Duration>,
+    extract: Option<Duration>,
+    matching: Option<Duration>,
+}
+
+impl Budget {
+    /// The limits configured for a scan that began at `started`.
+    pub fn new(config: &ScanConfig, started: Instant) -> Self {
+        Budget {
+            scan_deadline: config.scan_timeout_secs.map(|secs| started + Duration::from_secs(secs)),
+            db_refresh: config.db_refresh_budget_secs.map(Duration::from_secs),
+            extract: config.extract_budget_secs.map(Duration::from_secs),
+            matching: config.match_budget_secs.map(Duration::from_secs),
+        }
+    }
+
+    /// The deadline of `phase` starting now: the earlier of its own budget
+    /// and the scan timeout.
+    pub fn start(&self, phase: Phase) -> Deadline {
+        let own = match phase {
+            Phase::DbRefresh => self.db_refresh,
+            Phase::Extract => self.extract,
+            Phase::Match => self.matching,
+        }
+        .map(|budget| Instant::now() + budget);
+
+        match (own, self.scan_deadline) {
+            (Some(own), Some(scan)) if scan < own => Deadline::at(scan, "scan_timeout_secs"),
+            (Some(own), _) => Deadline::at(own, phase.setting()),
+            (None, Some(scan)) => Deadline::at(scan, "scan_timeout_secs"),
+            (None, None) => Deadline::none(),
+        }
+    }
+}
+
+/// When a phase has to stop, and which setting said so.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    at: Option<Instant>,
+    limit: &'static str,
+}
+
+impl Deadline {
+    pub fn none() -> Self {
+        Deadline { at: None, limit: "" }
+    }
+
+    fn at(at: Instant, limit: &'static str) -> Self {
+        Deadline { at: Some(at), limit }
+    }
+
+    pub fn expired(&self) -> bool {
+        self.at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// The setting that imposed this deadline.
+    pub fn limit(&self) -> &'static str {
+        self.limit
+    }
+}
+
+/// Run `work` on a helper thread and wait for it until `deadline`.
+///
+/// Returns `None` if the deadline passes first (or the work panics). An
+/// overrunning job can't be interrupted, so it is left to finish on its own
+/// and its result is discarded; the caller moves on immediately. Without a
+/// deadline the work simply runs on the calling thread.
+pub fn run_until<T, F>(deadline: Deadline, work: F) -> Option<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let Some(at) = deadline.at else {
+        return Some(work());
+    };
+    let (tx, rx) = mpsc::channel();
+    // The helper is held to the same permissions as its caller
+    let carried = permissions::carried();
+    std::thread::spawn(move || {
+        let _permissions = carried.map(permissions::carry);
+        let _ = tx.send(work());
+    });
+    rx.recv_timeout(at.saturating_duration_since(Instant::now())).ok()
+}
+
+/// Fails reads with `TimedOut` once the deadline has passed, so parsers
+/// stop at the next read on their own.
+pub struct DeadlineReader<R> {
+    pub inner: R,
+    pub deadline: Deadline,
+}
+
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.deadline.expired() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} exceeded", self.deadline.limit),
+            ));
+        }
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phases_stop_at_the_earlier_of_their_budget_and_the_scan_timeout() {
+        let config = ScanConfig {
+            scan_timeout_secs: Some(60),
+            extract_budget_secs: Some(0),
+            match_budget_secs: Some(3600),
+            ..ScanConfig::default()
+        };
+        let budget = Budget::new(&config, Instant::now());
+
+        let extract = budget.start(Phase::Extract);
+        assert!(extract.expired());
+        assert_eq!(extract.limit(), "extract_budget_secs");
+        let matching = budget.start(Phase::Match);
+        assert!(!matching.expired());
+        assert_eq!(matching.limit(), "scan_timeout_secs");
+        assert_eq!(budget.start(Phase::DbRefresh).limit(), "scan_timeout_secs");
+
+        let unlimited = Budget::new(&ScanConfig::default(), Instant::now());
+        assert!(!unlimited.start(Phase::DbRefresh).expired());
+
+        let mut reader = DeadlineReader {
+            inner: &b"django==2.2.0\n"[..],
+            deadline: extract,
+        };
+        let error = reader.read(&mut [0; 16]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(error.to_string(), "extract_budget_secs exceeded");
+    }
+
+    #[test]
+    fn overrunning_work_is_abandoned_at_the_deadline() {
+        let deadline = Deadline::at(Instant::now() + Duration::from_millis(50), "scan_timeout_secs");
+        assert_eq!(run_until(deadline, || 7), Some(7));
+
+        let started = Instant::now();
+        let hung = run_until(deadline, || std::thread::sleep(Duration::from_secs(10)));
+        assert!(hung.is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        assert_eq!(run_until(Deadline::none(), || "inline"), Some("inline"));
+    }
+}
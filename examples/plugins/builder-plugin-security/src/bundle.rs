@@ -0,0 +1,235 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::fsutil;
+
+/// Name of the bundle `report_bundle` writes next to the JSON report.
+pub const BUNDLE_FILE_NAME: &str = "security-bundle.json";
+
+/// Version of the bundle document's layout.
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Every file a scan wrote, in one JSON document, for artifact stores that
+/// would rather take one object than several.
+#[derive(Serialize, Deserialize)]
+pub struct Bundle {
+    pub bundle_version: u32,
+    /// The files by name: `security-report.json`, `metadata.json`,
+    /// `delta.json`, template reports, the metrics file and remediation
+    /// patches.
+    pub artifacts: BTreeMap<String, Artifact>,
+}
+
+/// One file of a bundle: a JSON document inline, anything else in base64.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "encoding", rename_all = "lowercase")]
+pub enum Artifact {
+    Json { content: Value },
+    Base64 { media_type: String, content: String },
+}
+
+impl Artifact {
+    /// The artifact for a file's contents: inline when the name ends in
+    /// `.json` and the contents parse, base64 otherwise.
+    pub fn of(name: &str, bytes: &[u8]) -> Artifact {
+        if name.ends_with(".json") {
+            if let Ok(content) = serde_json::from_slice(bytes) {
+                return Artifact::Json { content };
+            }
+        }
+        let media_type = match Path::new(name).extension().and_then(|e| e.to_str()) {
+            Some("prom") => "text/plain; version=0.0.4",
+            Some("patch") => "text/x-diff",
+            _ => "application/octet-stream",
+        };
+        Artifact::Base64 {
+            media_type: media_type.to_string(),
+            content: BASE64.encode(bytes),
+        }
+    }
+
+    /// The file's contents. A JSON artifact comes back pretty-printed, as
+    /// equivalent JSON rather than the bytes it was made from.
+    pub fn bytes(&self) -> Result<Vec<u8>, String> {
+        match self {
+            Artifact::Json { content } => serde_json::to_vec_pretty(content).map_err(|e| e.to_string()),
+            Artifact::Base64 { content, .. } => BASE64.decode(content).map_err(|e| format!("invalid base64: {}", e)),
+        }
+    }
+}
+
+impl Default for Bundle {
+    fn default() -> Self {
+        Bundle {
+            bundle_version: BUNDLE_VERSION,
+            artifacts: BTreeMap::new(),
+        }
+    }
+}
+
+impl Bundle {
+    pub fn add(&mut self, name: &str, artifact: Artifact) {
+        self.artifacts.insert(name.to_string(), artifact);
+    }
+
+    /// Write the bundle so readers never see part of it.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fsutil::write_atomic(path, &serde_json::to_vec(self)?)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = fsutil::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let bundle: Bundle = serde_json::from_slice(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if bundle.bundle_version > BUNDLE_VERSION {
+            return Err(format!(
+                "{}: bundle version {} is newer than this plugin reads ({})",
+                path.display(),
+                bundle.bundle_version,
+                BUNDLE_VERSION
+            ));
+        }
+        Ok(bundle)
+    }
+}
+
+/// Whether an artifact name can be written as a file of the output
+/// directory: a plain file name, never a path out of it.
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+const USAGE: &str = "usage: builder-plugin-security unbundle BUNDLE [--list] [--out DIR] [ARTIFACT...]";
+
+/// `builder-plugin-security unbundle BUNDLE [ARTIFACT...]`: write the named
+/// artifacts of a bundle, or all of them, into `--out` (the current
+/// directory by default), or list them with `--list`.
+pub fn cli(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut bundle_path = None;
+    let mut dir = ".".to_string();
+    let mut list = false;
+    let mut names = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--list" => list = true,
+            "--out" => match args.next() {
+                Some(out) => dir = out.clone(),
+                None => {
+                    let _ = writeln!(err, "{}", USAGE);
+                    return 2;
+                }
+            },
+            flag if flag.starts_with("--") => {
+                let _ = writeln!(err, "{}", USAGE);
+                return 2;
+            }
+            _ if bundle_path.is_none() => bundle_path = Some(arg.clone()),
+            _ => names.push(arg.clone()),
+        }
+    }
+    let Some(bundle_path) = bundle_path else {
+        let _ = writeln!(err, "{}", USAGE);
+        return 2;
+    };
+
+    let bundle = match Bundle::read(Path::new(&bundle_path)) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            let _ = writeln!(err, "{}", e);
+            return 1;
+        }
+    };
+    if list {
+        for (name, artifact) in &bundle.artifacts {
+            let kind = match artifact {
+                Artifact::Json { .. } => "application/json",
+                Artifact::Base64 { media_type, .. } => media_type,
+            };
+            let _ = writeln!(out, "{}\t{}", name, kind);
+        }
+        return 0;
+    }
+    if names.is_empty() {
+        names = bundle.artifacts.keys().cloned().collect();
+    }
+
+    let mut code = 0;
+    for name in &names {
+        let Some(artifact) = bundle.artifacts.get(name) else {
+            let known: Vec<&str> = bundle.artifacts.keys().map(String::as_str).collect();
+            let _ = writeln!(err, "{}: no artifact '{}' (has {})", bundle_path, name, known.join(", "));
+            code = 1;
+            continue;
+        };
+        if !is_file_name(name) {
+            let _ = writeln!(err, "{}: refusing artifact name '{}', which isn't a file name", bundle_path, name);
+            code = 1;
+            continue;
+        }
+        let path = Path::new(&dir).join(name);
+        match artifact.bytes().and_then(|bytes| fsutil::write_atomic(&path, &bytes).map_err(|e| e.to_string())) {
+            Ok(()) => {
+                let _ = writeln!(out, "{}", path.display());
+            }
+            Err(e) => {
+                let _ = writeln!(err, "{}: {}", path.display(), e);
+                code = 1;
+            }
+        }
+    }
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn unbundling_writes_back_each_artifact_and_refuses_paths() {
+        let dir = crate::testutil::scratch_dir("bundle");
+        let mut bundle = Bundle::default();
+        bundle.add("security-report.json", Artifact::of("security-report.json", br#"{"schema_version": 1}"#));
+        bundle.add("security.prom", Artifact::of("security.prom", b"security_scan_files_scanned 2\n"));
+        bundle.add("../escape.patch", Artifact::of("../escape.patch", b"--- a\n+++ b\n"));
+        let path = dir.join(BUNDLE_FILE_NAME);
+        bundle.write(&path).unwrap();
+        let written: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["artifacts"]["security-report.json"]["content"]["schema_version"], 1);
+        assert_eq!(written["artifacts"]["security.prom"]["encoding"], "base64");
+
+        let run = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let code = cli(&args, &mut out, &mut err);
+            (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+        };
+        let bundle_arg = path.display().to_string();
+        let out_dir = dir.join("out").display().to_string();
+
+        let (code, out, _) = run(&[&bundle_arg, "--list"]);
+        assert_eq!(code, 0);
+        assert!(out.contains("security.prom\ttext/plain; version=0.0.4\n"), "{}", out);
+
+        let (code, _, err) = run(&[&bundle_arg, "--out", &out_dir, "security.prom", "security-report.json"]);
+        assert_eq!(code, 0, "{}", err);
+        assert_eq!(fs::read(dir.join("out/security.prom")).unwrap(), b"security_scan_files_scanned 2\n");
+        let report: Value = serde_json::from_slice(&fs::read(dir.join("out/security-report.json")).unwrap()).unwrap();
+        assert_eq!(report["schema_version"], 1);
+
+        let (code, _, err) = run(&[&bundle_arg, "--out", &out_dir]);
+        assert_eq!(code, 1);
+        assert!(err.contains("refusing artifact name '../escape.patch'"), "{}", err);
+        assert!(!dir.join("escape.patch").exists());
+        let (code, _, err) = run(&[&bundle_arg, "--out", &out_dir, "sbom.json"]);
+        assert_eq!(code, 1);
+        assert!(err.contains("no artifact 'sbom.json'"), "{}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::fsutil;
+use crate::lock;
+use crate::report::{self, Compression};
+use crate::snapshots;
+use crate::summary;
+
+/// Cache directories owned by the scanner, under `<report_dir>/security/`.
+/// Reports and remediation patches live next to them but are outputs, not
+/// caches, and are left alone by `cache.clear`.
+pub const CACHE_DIRS: &[(&str, &str)] = &[
+    ("db", "Advisory database snapshots"),
+    ("scan", "Per-file scan results"),
+    ("history", "Previous scan reports, findings histories and install script lists"),
+    ("npm", "npm registry keys, metadata and attestations"),
+    ("profiles", "Scan profiles written with `profile`"),
+];
+
+/// Root of the scanner's cache directories.
+pub fn cache_root(workspace_root: &Path, report_dir: &str) -> PathBuf {
+    workspace_root.join(report_dir).join("security")
+}
+
+/// Footprint of one cache directory.
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub path: String,
+    pub files: u64,
+    pub bytes: u64,
+    /// Age of the least recently modified file, in seconds.
+    pub oldest_age_secs: Option<u64>,
+    /// Age of the most recently modified file, in seconds.
+    pub newest_age_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct ClearResult {
+    pub bytes_freed: u64,
+    pub files_removed: u64,
+    pub cleared: Vec<&'static str>,
+}
+
+#[derive(Default)]
+struct Walk {
+    files: u64,
+    bytes: u64,
+    oldest: Option<SystemTime>,
+    newest: Option<SystemTime>,
+}
+
+/// Sizes and ages of every cache directory; missing ones report zero.
+pub fn stats(root: &Path) -> io::Result<Vec<CacheStats>> {
+    let now = SystemTime::now();
+    let age = |time: Option<SystemTime>| time.map(|t| now.duration_since(t).map_or(0, |d| d.as_secs()));
+
+    CACHE_DIRS
+        .iter()
+        .map(|&(name, description)| {
+            let path = root.join(name);
+            let walk = walk(&path)?;
+            Ok(CacheStats {
+                name,
+                description,
+                path: path.display().to_string(),
+                files: walk.files,
+                bytes: walk.bytes,
+                oldest_age_secs: age(walk.oldest),
+                newest_age_secs: age(walk.newest),
+            })
+        })
+        .collect()
+}
+
+/// Remove every cache directory, reporting what was freed.
+pub fn clear(root: &Path) -> io::Result<ClearResult> {
+    let mut result = ClearResult {
+        bytes_freed: 0,
+        files_removed: 0,
+        cleared: Vec::new(),
+    };
+
+    for &(name, _) in CACHE_DIRS {
+        let path = root.join(name);
+        let walk = walk(&path)?;
+        match fsutil::remove_dir_all(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", path.display(), e))),
+        }
+        result.bytes_freed += walk.bytes;
+        result.files_removed += walk.files;
+        result.cleared.push(name);
+    }
+
+    Ok(result)
+}
+
+/// Previous reports of every target, one directory per target, under the
+/// cache root.
+pub fn report_history_dir(root: &Path) -> PathBuf {
+    root.join("history").join("reports")
+}
+
+/// Keep a copy of a target's report in its history, named by the time of
+/// the scan in milliseconds and compressed like the report.
+pub fn archive_report(
+    root: &Path,
+    target: &str,
+    contents: &[u8],
+    compression: Compression,
+    now: SystemTime,
+) -> io::Result<PathBuf> {
+    let millis = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+    let path = report_history_dir(root).join(summary::file_stem(target)).join(format!("{}.json", millis));
+    report::write_report(&path, contents, compression)
+}
+
+/// The newest report in a target's history.
+pub fn latest_report(root: &Path, target: &str) -> Option<PathBuf> {
+    let dir = report_history_dir(root).join(summary::file_stem(target));
+    archived(&dir).into_iter().next().map(|(_, path)| path)
+}
+
+/// The reports in a target's history, newest first, with when each was
+/// archived.
+pub fn history(root: &Path, target: &str) -> Vec<(SystemTime, PathBuf)> {
+    let dir = report_history_dir(root).join(summary::file_stem(target));
+    let archived_at = |millis: u128| UNIX_EPOCH + Duration::from_millis(u64::try_from(millis).unwrap_or(u64::MAX));
+    archived(&dir).into_iter().map(|(millis, path)| (archived_at(millis), path)).collect()
+}
+
+/// The reports archived in a target's history directory, newest first.
+fn archived(dir: &Path) -> Vec<(u128, PathBuf)> {
+    let mut reports: Vec<(u128, PathBuf)> = fsutil::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let millis = name.to_str()?.split('.').next()?.parse().ok()?;
+            Some((millis, entry.path()))
+        })
+        .collect();
+    reports.sort_by(|a, b| b.cmp(a));
+    reports
+}
+
+/// What the retention pass at the end of a post_hook keeps.
+pub struct Retention {
+    /// Archived reports kept per target, newest first.
+    pub reports_per_target: usize,
+    /// Bytes the advisory database snapshots may take up.
+    pub db_budget_bytes: u64,
+}
+
+/// Enforce `retention` on the cache directories below `root`, logging what
+/// was removed.
+///
+/// Each directory is pruned under its exclusive lock, taken without
+/// waiting: one that another scan is using is left for a later pass. Only
+/// regular files inside `root` are ever removed.
+pub fn prune(root: &Path, retention: &Retention, logs: &mut Vec<String>) {
+    let history = report_history_dir(root);
+    if history.is_dir() {
+        match lock::acquire_now(&history, lock::Mode::Exclusive, "pruning report history") {
+            Ok(Some(_lock)) => prune_reports(root, &history, retention.reports_per_target, logs),
+            Ok(None) => logs.push("  Report history pruning skipped: another scan holds its lock".to_string()),
+            Err(e) => logs.push(format!("  ⚠ Report history pruning skipped: {}", e)),
+        }
+    }
+
+    let db = root.join("db");
+    let dir = snapshots::snapshots_dir(&db);
+    if dir.is_dir() {
+        match lock::acquire_now(&db, lock::Mode::Exclusive, "pruning advisory snapshots") {
+            Ok(Some(_lock)) => prune_snapshots(root, &dir, retention.db_budget_bytes, logs),
+            Ok(None) => logs.push("  Snapshot pruning skipped: another scan holds the advisory cache".to_string()),
+            Err(e) => logs.push(format!("  ⚠ Snapshot pruning skipped: {}", e)),
+        }
+    }
+}
+
+fn prune_reports(root: &Path, history: &Path, kept: usize, logs: &mut Vec<String>) {
+    for target in fsutil::read_dir(history).into_iter().flatten().flatten().filter(|e| e.path().is_dir()) {
+        let reports = archived(&target.path());
+        let (mut removed, mut bytes) = (0, 0);
+        for (_, path) in reports.iter().skip(kept) {
+            match remove_inside(root, path) {
+                Ok(size) => {
+                    removed += 1;
+                    bytes += size;
+                }
+                Err(e) => logs.push(format!("  ⚠ Could not prune {}: {}", path.display(), e)),
+            }
+        }
+        if removed > 0 {
+            logs.push(format!(
+                "  Pruned {} old reports of {} ({} bytes, report_history)",
+                removed,
+                target.file_name().to_string_lossy(),
+                bytes
+            ));
+        }
+    }
+}
+
+fn prune_snapshots(root: &Path, dir: &Path, budget: u64, logs: &mut Vec<String>) {
+    for (snapshot, _) in snapshots::over_budget(dir, budget) {
+        let mut bytes = 0;
+        for path in snapshots::files(dir, &snapshot.id) {
+            match remove_inside(root, &path) {
+                Ok(size) => bytes += size,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => logs.push(format!("  ⚠ Could not prune {}: {}", path.display(), e)),
+            }
+        }
+        logs.push(format!(
+            "  Evicted advisory snapshot {} ({} bytes, last used {}, db_cache_budget_mb)",
+            snapshot.id,
+            bytes,
+            report::format_timestamp(UNIX_EPOCH + std::time::Duration::from_secs(snapshot.last_used_unix))
+        ));
+    }
+}
+
+/// Remove `path`, returning its size, provided it is a regular file (not a
+/// symlink) whose real location is inside `root`.
+fn remove_inside(root: &Path, path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    let parent = path.parent().map(fs::canonicalize).transpose()?;
+    let root = fs::canonicalize(root)?;
+    if !metadata.is_file() || !parent.is_some_and(|parent| parent.starts_with(&root)) {
+        return Err(io::Error::other("not a file of the scanner's cache"));
+    }
+    fsutil::remove_file(path)?;
+    Ok(metadata.len())
+}
+
+/// Total the regular files below `path` without following symlinks.
+fn walk(path: &Path) -> io::Result<Walk> {
+    let mut total = Walk::default();
+    let mut pending = vec![path.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match fsutil::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                total.files += 1;
+                total.bytes += metadata.len();
+                if let Ok(modified) = metadata.modified() {
+                    total.oldest = Some(total.oldest.map_or(modified, |t| t.min(modified)));
+                    total.newest = Some(total.newest.map_or(modified, |t| t.max(modified)));
+                }
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clear_removes_caches_but_keeps_patches() {
+        let root = crate::testutil::scratch_dir("cache");
+        fs::create_dir_all(root.join("db")).unwrap();
+        fs::create_dir_all(root.join("history/2026")).unwrap();
+        fs::create_dir_all(root.join("patches")).unwrap();
+        fs::write(root.join("db/osv.json"), b"0123456789").unwrap();
+        fs::write(root.join("history/2026/scan.json"), b"{}").unwrap();
+        fs::write(root.join("patches/summary.json"), b"[]").unwrap();
+
+        let before = stats(&root).unwrap();
+        assert_eq!(before.iter().map(|s| s.bytes).sum::<u64>(), 12);
+        assert!(before[0].newest_age_secs.is_some());
+        assert!(before[1].oldest_age_secs.is_none());
+
+        let cleared = clear(&root).unwrap();
+        assert_eq!((cleared.bytes_freed, cleared.files_removed), (12, 2));
+        assert_eq!(cleared.cleared, vec!["db", "history"]);
+        assert!(root.join("patches/summary.json").exists());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_reports_and_snapshots_within_budget() {
+        let root = crate::testutil::scratch_dir("prune");
+        let at = |secs: u64| UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        for secs in 1..=4 {
+            archive_report(&root, "//app:main", b"{}", Compression::None, at(secs)).unwrap();
+        }
+        let outside_dir = crate::testutil::scratch_dir("prune-outside");
+        let outside = outside_dir.join("report.json");
+        fs::write(&outside, b"keep").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, report_history_dir(&root).join("__app_main/0.json")).unwrap();
+
+        let dir = snapshots::snapshots_dir(&root.join("db"));
+        fs::create_dir_all(&dir).unwrap();
+        for i in 1..=3 {
+            let mut snapshot = snapshots::Snapshot {
+                id: format!("osv-local@{:016x}", i),
+                source: "osv-local".to_string(),
+                sha256: "00".repeat(32),
+                fingerprint: format!("fp{}", i),
+                fetched_at: None,
+                fetched_at_unix: None,
+                last_used_unix: 0,
+                index_bytes: None,
+                ecosystems: Vec::new(),
+            };
+            fs::write(snapshots::index_path(&dir, &snapshot.id), [b'x'; 10]).unwrap();
+            snapshots::touch(&dir, &mut snapshot, at(i)).unwrap();
+        }
+        let retention = Retention {
+            reports_per_target: 2,
+            db_budget_bytes: 15,
+        };
+
+        // Snapshots are left alone while another scan reads the advisory
+        // cache; the symlink out of the cache is never followed
+        let mut logs = Vec::new();
+        let reader = lock::acquire_now(&root.join("db"), lock::Mode::Shared, "reading").unwrap().unwrap();
+        prune(&root, &retention, &mut logs);
+        assert_eq!(snapshots::list(&dir).len(), 3);
+        assert!(logs.iter().any(|l| l.contains("Snapshot pruning skipped")), "{:?}", logs);
+        assert!(logs.iter().any(|l| l.starts_with("  Pruned 2 old reports of __app_main (4 bytes")), "{:?}", logs);
+        drop(reader);
+
+        logs.clear();
+        prune(&root, &retention, &mut logs);
+        let mut kept: Vec<String> = fs::read_dir(report_history_dir(&root).join("__app_main"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        kept.sort();
+        assert_eq!(kept.iter().filter(|name| name.ends_with("000.json")).count(), 2);
+        assert!(kept.contains(&"4000.json".to_string()), "{:?}", kept);
+        assert_eq!(fs::read(&outside).unwrap(), b"keep");
+        let left: Vec<String> = snapshots::list(&dir).into_iter().map(|(s, _)| s.id).collect();
+        assert_eq!(left, vec![format!("osv-local@{:016x}", 3)]);
+        assert_eq!(logs.iter().filter(|l| l.starts_with("  Evicted advisory snapshot")).count(), 2);
+
+        let _ = fs::remove_dir_all(&outside_dir);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where the scanner gets the current time from.
+///
+/// Everything a scan decides by the date (suppression expiry, database age,
+/// summary freshness) asks its clock, so tests can pin and advance time.
+/// Durations of the scan's own phases still use `Instant`.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    /// The current UTC date as `YYYY-MM-DD`.
+    fn today(&self) -> String {
+        crate::report::format_timestamp(self.now())[..10].to_string()
+    }
+}
+
+/// The system's wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock stopped at one instant, as reproducible scans use.
+pub struct FixedClock(pub SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+/// Seconds since the Unix epoch; zero for times before it.
+pub fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// A clock tests move forward by hand.
+#[cfg(test)]
+pub struct MockClock(std::sync::Mutex<SystemTime>);
+
+#[cfg(test)]
+impl MockClock {
+    pub fn at_unix(secs: u64) -> Self {
+        MockClock(std::sync::Mutex::new(UNIX_EPOCH + std::time::Duration::from_secs(secs)))
+    }
+
+    pub fn advance(&self, by: std::time::Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap()
+    }
+}
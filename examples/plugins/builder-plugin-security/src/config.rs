@@ -0,0 +1,1277 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ecosystems::Ecosystem;
+use crate::fsutil;
+use crate::policy::Policy;
+use crate::remediate;
+use crate::report;
+use crate::severity::Severity;
+
+/// Prefix shared by every environment variable override.
+pub const ENV_PREFIX: &str = "BUILDER_SECURITY_";
+
+/// Config file looked up in the workspace root when params don't name one.
+pub const CONFIG_FILE_NAME: &str = ".builder-security.toml";
+
+/// One layer of scanner settings as supplied by Builder.
+///
+/// Every field is optional so that a layer only overrides what it
+/// explicitly sets. Layers are applied on top of each other with
+/// [`ScanConfig::apply`].
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigLayer {
+    pub fail_on_critical: Option<bool>,
+    pub fail_on_high: Option<bool>,
+    pub fail_on_partial: Option<bool>,
+    pub fail_on_introduced: Option<bool>,
+    pub fail_on_regression: Option<bool>,
+    pub on_error: Option<String>,
+    pub strict_version_check: Option<bool>,
+    pub strict_permissions: Option<bool>,
+    pub grace_period: Option<String>,
+    pub allowlist: Option<String>,
+    pub ignore_vulnerabilities: Option<Vec<String>>,
+    pub report_formats: Option<Vec<String>>,
+    pub report_templates: Option<BTreeMap<String, String>>,
+    pub report_dir: Option<String>,
+    pub report_compression: Option<String>,
+    pub report_history: Option<usize>,
+    pub report_group_by: Option<String>,
+    pub verbosity: Option<String>,
+    pub offline: Option<bool>,
+    pub db_path: Option<String>,
+    pub db_index: Option<String>,
+    pub db_refresh: Option<String>,
+    pub db_refresh_interval: Option<String>,
+    pub db_memory_budget_mb: Option<u64>,
+    pub db_cache_budget_mb: Option<u64>,
+    pub db_snapshot: Option<String>,
+    pub demo_db: Option<bool>,
+    pub proxy: Option<String>,
+    pub timeout_secs: Option<u64>,
+    pub max_file_size: Option<u64>,
+    pub archive_max_entries: Option<u64>,
+    pub archive_max_entry_size: Option<u64>,
+    pub archive_max_total_size: Option<u64>,
+    pub allow_external_paths: Option<bool>,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub ecosystems: Option<Vec<String>>,
+    pub scan_timeout_secs: Option<u64>,
+    pub db_refresh_budget_secs: Option<u64>,
+    pub extract_budget_secs: Option<u64>,
+    pub match_budget_secs: Option<u64>,
+    pub cache_lock_timeout_secs: Option<u64>,
+    pub import_hints: Option<bool>,
+    pub dry_run: Option<bool>,
+    pub report_legacy_format: Option<bool>,
+    pub report_bundle: Option<bool>,
+    pub attestation: Option<bool>,
+    pub attestation_signing: Option<String>,
+    pub attestation_key: Option<String>,
+    pub remediate: Option<String>,
+    pub remediation_pr: Option<bool>,
+    pub remediation_pr_branch: Option<String>,
+    pub remediation_pr_commit_message: Option<String>,
+    pub allow_constraint_changes: Option<bool>,
+    pub notify_webhook: Option<String>,
+    pub notify_format: Option<String>,
+    pub notify_dry_run: Option<bool>,
+    pub metrics_file: Option<String>,
+    pub findings_stream: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub profile: Option<bool>,
+    pub annotations: Option<String>,
+    pub log_description_length: Option<usize>,
+    pub log_line_limit: Option<usize>,
+    pub reproducible: Option<bool>,
+    pub npm_provenance: Option<bool>,
+    pub npm_provenance_publishers: Option<Vec<String>>,
+    pub private_packages: Option<Vec<String>>,
+    pub private_registry: Option<String>,
+    pub public_registries: Option<Vec<String>>,
+    pub internal_registries: Option<Vec<String>>,
+    pub policy: Option<String>,
+    pub test_policy: Option<String>,
+    pub policies: Option<BTreeMap<String, Policy>>,
+    pub kev_catalog: Option<String>,
+    pub extra_advisories: Option<String>,
+    pub import_results: Option<Vec<String>>,
+    pub severity_overrides: Option<BTreeMap<String, Severity>>,
+    pub ecosystem_overrides: Option<BTreeMap<String, String>>,
+    pub name_aliases: Option<BTreeMap<String, BTreeMap<String, String>>>,
+}
+
+impl ConfigLayer {
+    /// Parse a layer from a JSON-RPC params object.
+    ///
+    /// A missing or null value yields an empty layer; a malformed one is
+    /// reported so the hook can surface it instead of silently ignoring it.
+    pub fn from_value(value: Option<&Value>) -> Result<Self, String> {
+        match value {
+            None | Some(Value::Null) => Ok(ConfigLayer::default()),
+            Some(value) => serde_json::from_value(value.clone()).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Build a layer from `BUILDER_SECURITY_*` variables.
+    ///
+    /// List settings are comma-separated. Unknown variables with the prefix
+    /// are rejected so typos don't go unnoticed in CI.
+    pub fn from_env<I>(vars: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut layer = ConfigLayer::default();
+
+        for (key, value) in vars {
+            let Some(setting) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let value = value.trim();
+
+            match setting {
+                "FAIL_ON_CRITICAL" => layer.fail_on_critical = Some(parse_bool(&key, value)?),
+                "FAIL_ON_HIGH" => layer.fail_on_high = Some(parse_bool(&key, value)?),
+                "FAIL_ON_PARTIAL" => layer.fail_on_partial = Some(parse_bool(&key, value)?),
+                "FAIL_ON_INTRODUCED" => layer.fail_on_introduced = Some(parse_bool(&key, value)?),
+                "FAIL_ON_REGRESSION" => layer.fail_on_regression = Some(parse_bool(&key, value)?),
+                "ON_ERROR" => layer.on_error = Some(value.to_string()),
+                "STRICT_VERSION_CHECK" => layer.strict_version_check = Some(parse_bool(&key, value)?),
+                "STRICT_PERMISSIONS" => layer.strict_permissions = Some(parse_bool(&key, value)?),
+                "GRACE_PERIOD" => layer.grace_period = Some(value.to_string()),
+                "ALLOWLIST" => layer.allowlist = Some(value.to_string()),
+                "IGNORE_VULNERABILITIES" => layer.ignore_vulnerabilities = Some(parse_list(value)),
+                "REPORT_FORMATS" => layer.report_formats = Some(parse_list(value)),
+                "REPORT_TEMPLATES" => {
+                    layer.report_templates = Some(
+                        serde_json::from_str(value)
+                            .map_err(|e| format!("{}: expected a JSON object of template paths: {}", key, e))?,
+                    )
+                }
+                "REPORT_DIR" => layer.report_dir = Some(value.to_string()),
+                "REPORT_COMPRESSION" => layer.report_compression = Some(value.to_string()),
+                "REPORT_GROUP_BY" => layer.report_group_by = Some(value.to_string()),
+                "REPORT_HISTORY" => layer.report_history = Some(parse_number(&key, value, "reports")?),
+                "VERBOSITY" => layer.verbosity = Some(value.to_string()),
+                "OFFLINE" => layer.offline = Some(parse_bool(&key, value)?),
+                "DB_PATH" => layer.db_path = Some(value.to_string()),
+                "DB_INDEX" => layer.db_index = Some(value.to_string()),
+                "DB_REFRESH" => layer.db_refresh = Some(value.to_string()),
+                "DB_REFRESH_INTERVAL" => layer.db_refresh_interval = Some(value.to_string()),
+                "DB_SNAPSHOT" => layer.db_snapshot = Some(value.to_string()),
+                "DEMO_DB" => layer.demo_db = Some(parse_bool(&key, value)?),
+                "DB_MEMORY_BUDGET_MB" => layer.db_memory_budget_mb = Some(parse_number(&key, value, "megabytes")?),
+                "DB_CACHE_BUDGET_MB" => layer.db_cache_budget_mb = Some(parse_number(&key, value, "megabytes")?),
+                "PROXY" => layer.proxy = Some(value.to_string()),
+                "TIMEOUT_SECS" => layer.timeout_secs = Some(parse_number(&key, value, "seconds")?),
+                "MAX_FILE_SIZE" => layer.max_file_size = Some(parse_number(&key, value, "bytes")?),
+                "ARCHIVE_MAX_ENTRIES" => layer.archive_max_entries = Some(parse_number(&key, value, "entries")?),
+                "ARCHIVE_MAX_ENTRY_SIZE" => layer.archive_max_entry_size = Some(parse_number(&key, value, "bytes")?),
+                "ARCHIVE_MAX_TOTAL_SIZE" => layer.archive_max_total_size = Some(parse_number(&key, value, "bytes")?),
+                "ALLOW_EXTERNAL_PATHS" => layer.allow_external_paths = Some(parse_bool(&key, value)?),
+                "INCLUDE" => layer.include = Some(parse_list(value)),
+                "EXCLUDE" => layer.exclude = Some(parse_list(value)),
+                "ECOSYSTEMS" => layer.ecosystems = Some(parse_list(value)),
+                "CACHE_LOCK_TIMEOUT_SECS" => layer.cache_lock_timeout_secs = Some(parse_number(&key, value, "seconds")?),
+                "IMPORT_HINTS" => layer.import_hints = Some(parse_bool(&key, value)?),
+                "DRY_RUN" => layer.dry_run = Some(parse_bool(&key, value)?),
+                "SCAN_TIMEOUT_SECS" => layer.scan_timeout_secs = Some(parse_number(&key, value, "seconds")?),
+                "DB_REFRESH_BUDGET_SECS" => layer.db_refresh_budget_secs = Some(parse_number(&key, value, "seconds")?),
+                "EXTRACT_BUDGET_SECS" => layer.extract_budget_secs = Some(parse_number(&key, value, "seconds")?),
+                "MATCH_BUDGET_SECS" => layer.match_budget_secs = Some(parse_number(&key, value, "seconds")?),
+                "REPORT_LEGACY_FORMAT" => layer.report_legacy_format = Some(parse_bool(&key, value)?),
+                "REPORT_BUNDLE" => layer.report_bundle = Some(parse_bool(&key, value)?),
+                "ATTESTATION" => layer.attestation = Some(parse_bool(&key, value)?),
+                "ATTESTATION_SIGNING" => layer.attestation_signing = Some(value.to_string()),
+                "ATTESTATION_KEY" => layer.attestation_key = Some(value.to_string()),
+                "REMEDIATE" => layer.remediate = Some(value.to_string()),
+                "REMEDIATION_PR" => layer.remediation_pr = Some(parse_bool(&key, value)?),
+                "REMEDIATION_PR_BRANCH" => layer.remediation_pr_branch = Some(value.to_string()),
+                "REMEDIATION_PR_COMMIT_MESSAGE" => layer.remediation_pr_commit_message = Some(value.to_string()),
+                "ALLOW_CONSTRAINT_CHANGES" => layer.allow_constraint_changes = Some(parse_bool(&key, value)?),
+                "NOTIFY_WEBHOOK" => layer.notify_webhook = Some(value.to_string()),
+                "NOTIFY_FORMAT" => layer.notify_format = Some(value.to_string()),
+                "NOTIFY_DRY_RUN" => layer.notify_dry_run = Some(parse_bool(&key, value)?),
+                "METRICS_FILE" => layer.metrics_file = Some(value.to_string()),
+                "FINDINGS_STREAM" => layer.findings_stream = Some(value.to_string()),
+                "OTLP_ENDPOINT" => layer.otlp_endpoint = Some(value.to_string()),
+                "PROFILE" => layer.profile = Some(parse_bool(&key, value)?),
+                "ANNOTATIONS" => layer.annotations = Some(value.to_string()),
+                "LOG_DESCRIPTION_LENGTH" => layer.log_description_length = Some(parse_number(&key, value, "characters")?),
+                "LOG_LINE_LIMIT" => layer.log_line_limit = Some(parse_number(&key, value, "lines")?),
+                "REPRODUCIBLE" => layer.reproducible = Some(parse_bool(&key, value)?),
+                "NPM_PROVENANCE" => layer.npm_provenance = Some(parse_bool(&key, value)?),
+                "NPM_PROVENANCE_PUBLISHERS" => layer.npm_provenance_publishers = Some(parse_list(value)),
+                "PRIVATE_PACKAGES" => layer.private_packages = Some(parse_list(value)),
+                "PRIVATE_REGISTRY" => layer.private_registry = Some(value.to_string()),
+                "PUBLIC_REGISTRIES" => layer.public_registries = Some(parse_list(value)),
+                "INTERNAL_REGISTRIES" => layer.internal_registries = Some(parse_list(value)),
+                "POLICY" => layer.policy = Some(value.to_string()),
+                "TEST_POLICY" => layer.test_policy = Some(value.to_string()),
+                "POLICIES" => {
+                    layer.policies = Some(
+                        serde_json::from_str(value)
+                            .map_err(|e| format!("{}: expected a JSON object of policies: {}", key, e))?,
+                    )
+                }
+                "KEV_CATALOG" => layer.kev_catalog = Some(value.to_string()),
+                "EXTRA_ADVISORIES" => layer.extra_advisories = Some(value.to_string()),
+                "IMPORT_RESULTS" => layer.import_results = Some(parse_list(value)),
+                "SEVERITY_OVERRIDES" => {
+                    layer.severity_overrides = Some(
+                        serde_json::from_str(value)
+                            .map_err(|e| format!("{}: expected a JSON object of severities: {}", key, e))?,
+                    )
+                }
+                "ECOSYSTEM_OVERRIDES" => {
+                    layer.ecosystem_overrides = Some(
+                        serde_json::from_str(value)
+                            .map_err(|e| format!("{}: expected a JSON object of ecosystems: {}", key, e))?,
+                    )
+                }
+                "NAME_ALIASES" => {
+                    layer.name_aliases = Some(
+                        serde_json::from_str(value)
+                            .map_err(|e| format!("{}: expected a JSON object of name aliases: {}", key, e))?,
+                    )
+                }
+                _ => return Err(format!("{}: unknown setting", key)),
+            }
+        }
+
+        Ok(layer)
+    }
+}
+
+/// On-disk layout of `.builder-security.toml`.
+///
+/// The file groups the same settings accepted by `plugin.configure` into
+/// sections; unknown keys are rejected so typos are reported with a location.
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    verbosity: Option<String>,
+    sources: SourcesSection,
+    policy: PolicySection,
+    ignores: IgnoresSection,
+    reports: ReportsSection,
+    scan: ScanSection,
+    notify: NotifySection,
+    telemetry: TelemetrySection,
+    npm: NpmSection,
+    private: PrivateSection,
+    registries: RegistriesSection,
+    remediation: RemediationSection,
+    policies: Option<BTreeMap<String, Policy>>,
+    severity_overrides: Option<BTreeMap<String, Severity>>,
+    ecosystem_overrides: Option<BTreeMap<String, String>>,
+    name_aliases: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    remediate: Option<String>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct SourcesSection {
+    offline: Option<bool>,
+    db_path: Option<String>,
+    db_index: Option<String>,
+    db_refresh: Option<String>,
+    db_refresh_interval: Option<String>,
+    db_memory_budget_mb: Option<u64>,
+    db_cache_budget_mb: Option<u64>,
+    db_snapshot: Option<String>,
+    demo_db: Option<bool>,
+    proxy: Option<String>,
+    timeout_secs: Option<u64>,
+    kev_catalog: Option<String>,
+    extra_advisories: Option<String>,
+    import_results: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct PolicySection {
+    name: Option<String>,
+    test: Option<String>,
+    fail_on_critical: Option<bool>,
+    fail_on_high: Option<bool>,
+    fail_on_partial: Option<bool>,
+    fail_on_introduced: Option<bool>,
+    fail_on_regression: Option<bool>,
+    on_error: Option<String>,
+    strict_version_check: Option<bool>,
+    strict_permissions: Option<bool>,
+    grace_period: Option<String>,
+    allowlist: Option<String>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct IgnoresSection {
+    vulnerabilities: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct ReportsSection {
+    formats: Option<Vec<String>>,
+    templates: Option<BTreeMap<String, String>>,
+    dir: Option<String>,
+    compression: Option<String>,
+    history: Option<usize>,
+    group_by: Option<String>,
+    legacy_format: Option<bool>,
+    bundle: Option<bool>,
+    attestation: Option<bool>,
+    attestation_signing: Option<String>,
+    attestation_key: Option<String>,
+    metrics_file: Option<String>,
+    findings_stream: Option<String>,
+    annotations: Option<String>,
+    log_description_length: Option<usize>,
+    log_line_limit: Option<usize>,
+    reproducible: Option<bool>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct ScanSection {
+    max_file_size: Option<u64>,
+    archive_max_entries: Option<u64>,
+    archive_max_entry_size: Option<u64>,
+    archive_max_total_size: Option<u64>,
+    allow_external_paths: Option<bool>,
+    include: Option<Vec<String>>,
+    exclude: Option<Vec<String>>,
+    ecosystems: Option<Vec<String>>,
+    timeout_secs: Option<u64>,
+    db_refresh_budget_secs: Option<u64>,
+    extract_budget_secs: Option<u64>,
+    match_budget_secs: Option<u64>,
+    cache_lock_timeout_secs: Option<u64>,
+    import_hints: Option<bool>,
+    dry_run: Option<bool>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct NotifySection {
+    webhook: Option<String>,
+    format: Option<String>,
+    dry_run: Option<bool>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct TelemetrySection {
+    otlp_endpoint: Option<String>,
+    profile: Option<bool>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct NpmSection {
+    provenance: Option<bool>,
+    provenance_publishers: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct PrivateSection {
+    packages: Option<Vec<String>>,
+    registry: Option<String>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct RegistriesSection {
+    public: Option<Vec<String>>,
+    internal: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+struct RemediationSection {
+    pr: Option<bool>,
+    pr_branch: Option<String>,
+    pr_commit_message: Option<String>,
+    allow_constraint_changes: Option<bool>,
+}
+
+impl ConfigLayer {
+    /// Load a layer from a TOML config file.
+    ///
+    /// Returns `Ok(None)` when the file doesn't exist. Parse and schema errors
+    /// are prefixed with `path:line:column` so they can be fixed directly.
+    pub fn from_file(path: &Path) -> Result<Option<Self>, String> {
+        let content = match fsutil::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        let file: ConfigFile = toml::from_str(&content).map_err(|e| {
+            let location = e
+                .span()
+                .map(|span| {
+                    let (line, column) = line_and_column(&content, span.start);
+                    format!(":{}:{}", line, column)
+                })
+                .unwrap_or_default();
+            format!("{}{}: {}", path.display(), location, e.message())
+        })?;
+
+        Ok(Some(ConfigLayer {
+            fail_on_critical: file.policy.fail_on_critical,
+            fail_on_high: file.policy.fail_on_high,
+            fail_on_partial: file.policy.fail_on_partial,
+            fail_on_introduced: file.policy.fail_on_introduced,
+            fail_on_regression: file.policy.fail_on_regression,
+            on_error: file.policy.on_error,
+            strict_version_check: file.policy.strict_version_check,
+            strict_permissions: file.policy.strict_permissions,
+            grace_period: file.policy.grace_period,
+            allowlist: file.policy.allowlist,
+            ignore_vulnerabilities: file.ignores.vulnerabilities,
+            report_formats: file.reports.formats,
+            report_templates: file.reports.templates,
+            report_dir: file.reports.dir,
+            report_compression: file.reports.compression,
+            report_history: file.reports.history,
+            report_group_by: file.reports.group_by,
+            verbosity: file.verbosity,
+            offline: file.sources.offline,
+            db_path: file.sources.db_path,
+            db_index: file.sources.db_index,
+            db_refresh: file.sources.db_refresh,
+            db_refresh_interval: file.sources.db_refresh_interval,
+            db_memory_budget_mb: file.sources.db_memory_budget_mb,
+            db_cache_budget_mb: file.sources.db_cache_budget_mb,
+            db_snapshot: file.sources.db_snapshot,
+            demo_db: file.sources.demo_db,
+            proxy: file.sources.proxy,
+            timeout_secs: file.sources.timeout_secs,
+            max_file_size: file.scan.max_file_size,
+            archive_max_entries: file.scan.archive_max_entries,
+            archive_max_entry_size: file.scan.archive_max_entry_size,
+            archive_max_total_size: file.scan.archive_max_total_size,
+            allow_external_paths: file.scan.allow_external_paths,
+            include: file.scan.include,
+            exclude: file.scan.exclude,
+            ecosystems: file.scan.ecosystems,
+            scan_timeout_secs: file.scan.timeout_secs,
+            db_refresh_budget_secs: file.scan.db_refresh_budget_secs,
+            extract_budget_secs: file.scan.extract_budget_secs,
+            match_budget_secs: file.scan.match_budget_secs,
+            cache_lock_timeout_secs: file.scan.cache_lock_timeout_secs,
+            import_hints: file.scan.import_hints,
+            dry_run: file.scan.dry_run,
+            report_legacy_format: file.reports.legacy_format,
+            report_bundle: file.reports.bundle,
+            attestation: file.reports.attestation,
+            attestation_signing: file.reports.attestation_signing,
+            attestation_key: file.reports.attestation_key,
+            remediate: file.remediate,
+            remediation_pr: file.remediation.pr,
+            remediation_pr_branch: file.remediation.pr_branch,
+            remediation_pr_commit_message: file.remediation.pr_commit_message,
+            allow_constraint_changes: file.remediation.allow_constraint_changes,
+            notify_webhook: file.notify.webhook,
+            notify_format: file.notify.format,
+            notify_dry_run: file.notify.dry_run,
+            metrics_file: file.reports.metrics_file,
+            findings_stream: file.reports.findings_stream,
+            otlp_endpoint: file.telemetry.otlp_endpoint,
+            profile: file.telemetry.profile,
+            annotations: file.reports.annotations,
+            log_description_length: file.reports.log_description_length,
+            log_line_limit: file.reports.log_line_limit,
+            reproducible: file.reports.reproducible,
+            npm_provenance: file.npm.provenance,
+            npm_provenance_publishers: file.npm.provenance_publishers,
+            private_packages: file.private.packages,
+            private_registry: file.private.registry,
+            public_registries: file.registries.public,
+            internal_registries: file.registries.internal,
+            policy: file.policy.name,
+            test_policy: file.policy.test,
+            policies: file.policies,
+            kev_catalog: file.sources.kev_catalog,
+            extra_advisories: file.sources.extra_advisories,
+            import_results: file.sources.import_results,
+            severity_overrides: file.severity_overrides,
+            ecosystem_overrides: file.ecosystem_overrides,
+            name_aliases: file.name_aliases,
+        }))
+    }
+}
+
+/// 1-based line and column of a byte offset.
+pub(crate) fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("{}: expected a boolean, got '{}'", key, value)),
+    }
+}
+
+/// A whole number of `unit`s.
+fn parse_number<T: FromStr>(key: &str, value: &str, unit: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{}: expected a number of {}, got '{}'", key, unit, value))
+}
+
+/// A period of whole hours or days: `72h`, `3d`.
+pub(crate) fn parse_period(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (count, hours) = match value.strip_suffix('h') {
+        Some(count) => (count, 1),
+        None => (value.strip_suffix('d')?, 24),
+    };
+    let count: u64 = count.parse().ok()?;
+    Some(Duration::from_secs(count.checked_mul(hours * 3600)?))
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Effective scanner configuration after all layers have been merged;
+/// `Config` in the library's API.
+#[derive(Serialize, Clone)]
+#[non_exhaustive]
+pub struct ScanConfig {
+    pub fail_on_critical: bool,
+    pub fail_on_high: bool,
+    /// Fail the pre_hook when a time limit cut the scan short, instead of
+    /// judging only what was found.
+    pub fail_on_partial: bool,
+    /// With `changed_files` in the pre_hook params, decide pass or fail on
+    /// the findings the change introduced only.
+    pub fail_on_introduced: bool,
+    /// Instead of the `fail_on_critical`/`fail_on_high` thresholds, fail
+    /// only when a severity has more findings than in the target's
+    /// previous scan.
+    pub fail_on_regression: bool,
+    /// `open` reports a scan whose advisory sources, workspace or time
+    /// limits failed it as degraded; `closed` fails it.
+    pub on_error: String,
+    /// Refuse hooks when `plugin.info` said the host is older than
+    /// `minBuilderVersion`, instead of only warning about it.
+    pub strict_version_check: bool,
+    /// Refuse, in hooks, every file access and connection outside what
+    /// `plugin.info` declares in `permissions`.
+    pub strict_permissions: bool,
+    /// How long after an advisory is published a finding without a fix
+    /// only warns, e.g. `72h` or `3d`.
+    pub grace_period: Option<String>,
+    /// Allowlist file, relative to the workspace root: every dependency it
+    /// doesn't approve is an `UNAPPROVED-DEPENDENCY` finding (see
+    /// [`crate::allowlist`]).
+    pub allowlist: Option<String>,
+    pub ignore_vulnerabilities: Vec<String>,
+    /// `json`, or the name of one of the `report_templates`.
+    pub report_formats: Vec<String>,
+    /// Report formats rendered from templates, by name: template files
+    /// relative to the workspace root, written as
+    /// `security-report-<name>.<ext>` (`ci/jira.md.hbs` gives `.md`).
+    pub report_templates: BTreeMap<String, String>,
+    pub report_dir: String,
+    /// `none`, `gzip` (`.json.gz`) or `zstd` (`.json.zst`, with the `zstd`
+    /// feature) for the report files.
+    pub report_compression: String,
+    /// Previous reports kept per target under `security/history/reports`;
+    /// 0 keeps none.
+    pub report_history: usize,
+    /// `none`, or `project` to order findings by project and add per-project
+    /// counts to the JSON report.
+    pub report_group_by: String,
+    pub verbosity: String,
+    pub offline: bool,
+    pub db_path: Option<String>,
+    /// How a `db_path` advisory database is indexed: `memory` (loaded into
+    /// memory) or `disk` (sorted index file, read per package).
+    pub db_index: String,
+    /// How a changed `db_path` database is refreshed: `full` (all of it
+    /// before the scan), `partial` (the scan's ecosystems first, the rest
+    /// in the background while the previous snapshot serves it) or
+    /// `scheduled` (only the ecosystems the workspace uses, once per
+    /// `db_refresh_interval`; see [`crate::advisories::schedule`]).
+    pub db_refresh: String,
+    /// With `db_refresh = "scheduled"`, how long the workspace's ecosystems
+    /// are served from their snapshot before a changed database refreshes
+    /// them, e.g. `24h` or `7d`.
+    pub db_refresh_interval: String,
+    /// Memory the `disk` index may use while it is being built, and in
+    /// `--daemon` mode the most advisory data or a query cache kept in
+    /// memory between scans may take.
+    pub db_memory_budget_mb: u64,
+    /// Disk space the cached advisory snapshots may take up; the least
+    /// recently used are evicted at the end of a post_hook.
+    pub db_cache_budget_mb: u64,
+    /// Id of a cached advisory database snapshot to scan against instead of
+    /// the current `db_path` contents.
+    pub db_snapshot: Option<String>,
+    /// Scan against the built-in demo advisories when no other source is
+    /// configured.
+    pub demo_db: bool,
+    pub proxy: Option<String>,
+    pub timeout_secs: u64,
+    /// Dependency files larger than this many bytes are skipped.
+    pub max_file_size: u64,
+    /// Archives among the build outputs with more entries than this are
+    /// only extracted up to it.
+    pub archive_max_entries: u64,
+    /// Archive entries that decompress to more bytes than this are skipped.
+    pub archive_max_entry_size: u64,
+    /// Extraction of an archive stops once it has decompressed this many
+    /// bytes.
+    pub archive_max_total_size: u64,
+    /// Permit sources that resolve outside the workspace root.
+    pub allow_external_paths: bool,
+    /// Globs over workspace-relative paths; when non-empty, only matching
+    /// sources are scanned.
+    pub include: Vec<String>,
+    /// Globs over workspace-relative paths that are never scanned, even when
+    /// they match `include`.
+    pub exclude: Vec<String>,
+    /// Ecosystems (as OSV names them) whose files are scanned and advisories
+    /// loaded; `["all"]` for every one. Empty follows the target's language
+    /// and type.
+    pub ecosystems: Vec<String>,
+    /// Limit on the whole scan; phases still running when it passes stop.
+    pub scan_timeout_secs: Option<u64>,
+    /// Limits on single phases, each counted from the phase's start.
+    pub db_refresh_budget_secs: Option<u64>,
+    pub extract_budget_secs: Option<u64>,
+    pub match_budget_secs: Option<u64>,
+    /// How long a scan waits for another scan's hold on the cache before it
+    /// treats the lock as stale and goes ahead.
+    pub cache_lock_timeout_secs: u64,
+    /// Check whether the target's Python and JavaScript sources import the
+    /// packages with findings; see [`crate::reachability`].
+    pub import_hints: bool,
+    /// Find and read the dependency files, but only return what a scan
+    /// would cover; see [`crate::plan`].
+    pub dry_run: bool,
+    /// Write the report as a bare findings array, as before the envelope.
+    pub report_legacy_format: bool,
+    /// Also write every file the pre_hook writes, with the scan's metadata,
+    /// into one JSON document; see [`crate::bundle`].
+    pub report_bundle: bool,
+    /// In the post_hook, write an in-toto statement that the build outputs
+    /// were scanned; see [`crate::attestation`].
+    pub attestation: bool,
+    /// `off`, `key` (a DSSE envelope signed with `attestation_key`) or
+    /// `keyless` (`cosign sign-blob` with the CI's OIDC identity); needs the
+    /// `attestation-signing` feature.
+    pub attestation_signing: String,
+    /// PKCS#8 ECDSA P-256 private key signing the statement.
+    pub attestation_key: Option<String>,
+    /// `off`, `suggest` (write patch files) or `apply` (also edit manifests).
+    pub remediate: String,
+    /// Also write the updates as a pull request to open: grouped by file,
+    /// with a Markdown body and a branch and commit message; see
+    /// [`crate::remediate::pull_request`].
+    pub remediation_pr: bool,
+    /// Template of the pull request's branch name.
+    pub remediation_pr_branch: String,
+    /// Template of the pull request's commit message; its first line is the
+    /// title.
+    pub remediation_pr_commit_message: String,
+    /// Propose updates a declared constraint doesn't admit, changing the
+    /// constraint, instead of leaving them out of the pull request.
+    pub allow_constraint_changes: bool,
+    /// Webhook receiving policy-violation alerts.
+    pub notify_webhook: Option<String>,
+    /// `slack` (incoming-webhook message) or `json` (generic payload).
+    pub notify_format: String,
+    /// Log the notification payload instead of sending it.
+    pub notify_dry_run: bool,
+    /// Prometheus textfile written after every scan.
+    pub metrics_file: Option<String>,
+    /// NDJSON file receiving each finding as its dependency file is matched.
+    pub findings_stream: Option<String>,
+    /// OTLP/HTTP collector receiving scan traces; falls back to the
+    /// standard `OTEL_EXPORTER_OTLP_*` variables.
+    pub otlp_endpoint: Option<String>,
+    /// Time every phase and dependency file, for the debug logs and a
+    /// profile under `<report_dir>/security/profiles/`.
+    pub profile: bool,
+    /// `auto` (GitHub annotations when `GITHUB_ACTIONS=true`), `github` or
+    /// `off`.
+    pub annotations: String,
+    /// Advisory summaries in annotations are cut to this many characters;
+    /// file reports keep the full description.
+    pub log_description_length: usize,
+    /// Most lines a hook result's `logs` carries; the rest are elided, least
+    /// important first, and the complete log is written next to the
+    /// reports. `0` keeps every line.
+    pub log_line_limit: usize,
+    /// Make file reports bit-identical across runs on the same inputs:
+    /// timestamps come from `SOURCE_DATE_EPOCH` and durations are zero.
+    pub reproducible: bool,
+    /// In the post_hook, verify the registry signatures and provenance of
+    /// installed and cached npm packages.
+    pub npm_provenance: bool,
+    /// npm users and `@scope`s whose packages must have provenance.
+    pub npm_provenance_publishers: Vec<String>,
+    /// Name globs of packages that must only come from `private_registry`,
+    /// as a guard against dependency confusion.
+    pub private_packages: Vec<String>,
+    /// URL prefix of the registry serving `private_packages`.
+    pub private_registry: Option<String>,
+    /// URL prefixes of mirrors and private indexes serving the public
+    /// registries' packages, matched against advisories as usual.
+    pub public_registries: Vec<String>,
+    /// URL prefixes of registries serving the organization's own packages,
+    /// which advisories for same-named public packages don't apply to.
+    pub internal_registries: Vec<String>,
+    /// Name of the entry of `policies` deciding pass or fail instead of the
+    /// `fail_on_critical`/`fail_on_high` thresholds.
+    pub policy: Option<String>,
+    /// Name of the entry of `policies` deciding `test.pre_hook` instead of
+    /// `policy` or the thresholds.
+    pub test_policy: Option<String>,
+    pub policies: BTreeMap<String, Policy>,
+    /// CISA Known Exploited Vulnerabilities catalog (JSON, relative to the
+    /// workspace root) marking findings as known exploited.
+    pub kev_catalog: Option<String>,
+    /// The team's own advisories (TOML, relative to the workspace root),
+    /// matched alongside the configured databases.
+    pub extra_advisories: Option<String>,
+    /// Output of other scanners (`npm audit --json`, `pip-audit --format
+    /// json`, `cargo audit --json`; relative to the workspace root) whose
+    /// findings are merged with the plugin's own.
+    pub import_results: Vec<String>,
+    /// Severities replacing the advisories' own, by advisory id or id prefix
+    /// ending in `*` (`RUSTSEC-2023-*`).
+    pub severity_overrides: BTreeMap<String, Severity>,
+    /// Formats decided by path instead of file name: workspace-relative
+    /// paths or globs to an ecosystem, or `ignore` to skip the file.
+    pub ecosystem_overrides: BTreeMap<String, String>,
+    /// Per ecosystem, the names advisories know packages by, keyed by the
+    /// names manifests declare them under (`PIL` to `pillow`).
+    pub name_aliases: BTreeMap<String, BTreeMap<String, String>>,
+
+    /// Which layer each setting was last taken from.
+    #[serde(skip)]
+    pub origins: BTreeMap<&'static str, String>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        ScanConfig {
+            fail_on_critical: false,
+            fail_on_high: false,
+            fail_on_partial: false,
+            fail_on_introduced: false,
+            fail_on_regression: false,
+            on_error: "open".to_string(),
+            strict_version_check: false,
+            strict_permissions: false,
+            grace_period: None,
+            allowlist: None,
+            ignore_vulnerabilities: Vec::new(),
+            report_formats: vec!["json".to_string()],
+            report_templates: BTreeMap::new(),
+            report_dir: ".builder-cache".to_string(),
+            report_compression: "none".to_string(),
+            report_history: 10,
+            report_group_by: "none".to_string(),
+            verbosity: "info".to_string(),
+            offline: false,
+            db_path: None,
+            db_index: "memory".to_string(),
+            db_refresh: "full".to_string(),
+            db_refresh_interval: "24h".to_string(),
+            db_memory_budget_mb: 64,
+            db_cache_budget_mb: 1024,
+            db_snapshot: None,
+            demo_db: true,
+            proxy: None,
+            timeout_secs: 300,
+            max_file_size: 100 * 1024 * 1024,
+            archive_max_entries: 100_000,
+            archive_max_entry_size: 1024 * 1024 * 1024,
+            archive_max_total_size: 4 * 1024 * 1024 * 1024,
+            allow_external_paths: false,
+            include: Vec::new(),
+            exclude: vec!["**/node_modules/**".to_string(), "**/.git/**".to_string()],
+            ecosystems: Vec::new(),
+            scan_timeout_secs: None,
+            db_refresh_budget_secs: None,
+            extract_budget_secs: None,
+            match_budget_secs: None,
+            cache_lock_timeout_secs: 300,
+            import_hints: false,
+            dry_run: false,
+            report_legacy_format: false,
+            report_bundle: false,
+            attestation: false,
+            attestation_signing: "off".to_string(),
+            attestation_key: None,
+            remediate: "off".to_string(),
+            remediation_pr: false,
+            remediation_pr_branch: remediate::DEFAULT_PR_BRANCH.to_string(),
+            remediation_pr_commit_message: remediate::DEFAULT_PR_COMMIT_MESSAGE.to_string(),
+            allow_constraint_changes: false,
+            notify_webhook: None,
+            notify_format: "slack".to_string(),
+            notify_dry_run: false,
+            metrics_file: None,
+            findings_stream: None,
+            otlp_endpoint: None,
+            profile: false,
+            annotations: "auto".to_string(),
+            log_description_length: 200,
+            log_line_limit: 300,
+            reproducible: false,
+            npm_provenance: false,
+            npm_provenance_publishers: Vec::new(),
+            private_packages: Vec::new(),
+            private_registry: None,
+            public_registries: Vec::new(),
+            internal_registries: Vec::new(),
+            policy: None,
+            test_policy: None,
+            policies: BTreeMap::new(),
+            kev_catalog: None,
+            extra_advisories: None,
+            import_results: Vec::new(),
+            severity_overrides: BTreeMap::new(),
+            ecosystem_overrides: BTreeMap::new(),
+            name_aliases: BTreeMap::new(),
+            origins: BTreeMap::new(),
+        }
+    }
+}
+
+impl ScanConfig {
+    /// Resolve the effective configuration for one hook invocation.
+    ///
+    /// Precedence, lowest to highest: defaults, the config file, workspace
+    /// config, per-target `target_config`, then `BUILDER_SECURITY_*`
+    /// environment variables.
+    pub fn resolve<I>(
+        config_file: Option<&Path>,
+        workspace_config: Option<&Value>,
+        target_config: Option<&Value>,
+        env: I,
+    ) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut config = ScanConfig::default();
+
+        if let Some(path) = config_file {
+            if let Some(file) = ConfigLayer::from_file(path)? {
+                config.apply(&file, "file");
+            }
+        }
+
+        let workspace = ConfigLayer::from_value(workspace_config)
+            .map_err(|e| format!("Invalid workspace config: {}", e))?;
+        config.apply(&workspace, "workspace");
+
+        let target = ConfigLayer::from_value(target_config)
+            .map_err(|e| format!("Invalid target_config: {}", e))?;
+        config.apply(&target, "target");
+
+        let env = ConfigLayer::from_env(env).map_err(|e| format!("Invalid environment override: {}", e))?;
+        config.apply(&env, "env");
+
+        for (setting, name) in [("policy", &config.policy), ("test_policy", &config.test_policy)] {
+            if let Some(name) = name.as_ref().filter(|name| !config.policies.contains_key(*name)) {
+                return Err(format!("{} '{}' is not defined in policies", setting, name));
+            }
+        }
+        if !matches!(config.on_error.as_str(), "open" | "closed") {
+            return Err(format!("on_error: expected open or closed, got '{}'", config.on_error));
+        }
+        if !matches!(config.remediate.as_str(), "off" | "suggest" | "apply") {
+            return Err(format!("remediate: expected off, suggest or apply, got '{}'", config.remediate));
+        }
+        if !matches!(config.annotations.as_str(), "auto" | "github" | "off") {
+            return Err(format!("annotations: expected auto, github or off, got '{}'", config.annotations));
+        }
+        if !matches!(config.notify_format.as_str(), "slack" | "json") {
+            return Err(format!("notify_format: expected slack or json, got '{}'", config.notify_format));
+        }
+        if !matches!(config.report_group_by.as_str(), "none" | "project") {
+            return Err(format!("report_group_by: expected none or project, got '{}'", config.report_group_by));
+        }
+        if let Some(period) = config.grace_period.as_ref().filter(|period| parse_period(period).is_none()) {
+            return Err(format!("grace_period: expected hours or days such as 72h or 3d, got '{}'", period));
+        }
+        if parse_period(&config.db_refresh_interval).is_none() {
+            return Err(format!(
+                "db_refresh_interval: expected hours or days such as 24h or 7d, got '{}'",
+                config.db_refresh_interval
+            ));
+        }
+        if !matches!(config.attestation_signing.as_str(), "off" | "key" | "keyless") {
+            return Err(format!(
+                "attestation_signing: expected off, key or keyless, got '{}'",
+                config.attestation_signing
+            ));
+        }
+        if config.attestation_signing != "off" && !cfg!(feature = "attestation-signing") {
+            return Err("attestation_signing needs a build with the `attestation-signing` feature".to_string());
+        }
+        if config.attestation_signing == "key" && config.attestation_key.is_none() {
+            return Err("attestation_signing: key needs attestation_key".to_string());
+        }
+        for (path, ecosystem) in &config.ecosystem_overrides {
+            match Ecosystem::named(ecosystem) {
+                None if ecosystem != "ignore" => {
+                    let names: Vec<&str> =
+                        Ecosystem::ALL.iter().filter(|e| e.has_format()).map(|e| e.as_str()).collect();
+                    return Err(format!(
+                        "ecosystem_overrides: '{}' maps to unknown ecosystem '{}' (expected ignore, {})",
+                        path,
+                        ecosystem,
+                        names.join(", ")
+                    ));
+                }
+                Some(named) if !named.has_format() => {
+                    return Err(format!(
+                        "ecosystem_overrides: '{}' maps to {}, whose dependency files aren't read",
+                        path,
+                        named.as_str()
+                    ));
+                }
+                _ => {}
+            }
+        }
+        report::Compression::parse(&config.report_compression)?;
+        if !config.report_templates.is_empty() && !cfg!(feature = "templates") {
+            return Err("report_templates needs a build with the `templates` feature".to_string());
+        }
+        for name in config.report_templates.keys() {
+            let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+            if !valid || name == "json" {
+                return Err(format!(
+                    "report_templates: '{}' isn't a template name (letters, digits, '-' and '_', other than json)",
+                    name
+                ));
+            }
+        }
+        for (ecosystem, aliases) in &config.name_aliases {
+            let Some(named) = Ecosystem::named(ecosystem) else {
+                let names: Vec<&str> = Ecosystem::ALL.iter().map(|e| e.as_str()).collect();
+                return Err(format!(
+                    "name_aliases: unknown ecosystem '{}' (expected one of {})",
+                    ecosystem,
+                    names.join(", ")
+                ));
+            };
+            // Names the ecosystem takes for one package can't alias two
+            let mut seen: BTreeMap<String, &String> = BTreeMap::new();
+            for name in aliases.keys() {
+                if let Some(other) = seen.insert(named.canonical_name(name), name) {
+                    return Err(format!(
+                        "name_aliases: '{}' and '{}' are the same {} package",
+                        other,
+                        name,
+                        named.as_str()
+                    ));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Merge a layer on top of the current settings.
+    ///
+    /// Scalar settings and report formats are replaced by the layer's value;
+    /// ignore lists are unioned so a target can add suppressions without
+    /// having to repeat the workspace-wide ones.
+    pub fn apply(&mut self, layer: &ConfigLayer, origin: &str) {
+        macro_rules! replace {
+            ($field:ident) => {
+                if let Some(v) = &layer.$field {
+                    self.$field = v.clone();
+                    self.origins.insert(stringify!($field), origin.to_string());
+                }
+            };
+            ($field:ident, optional) => {
+                if let Some(v) = &layer.$field {
+                    self.$field = Some(v.clone());
+                    self.origins.insert(stringify!($field), origin.to_string());
+                }
+            };
+        }
+
+        replace!(fail_on_critical);
+        replace!(fail_on_high);
+        replace!(fail_on_partial);
+        replace!(fail_on_introduced);
+        replace!(fail_on_regression);
+        replace!(on_error);
+        replace!(strict_version_check);
+        replace!(strict_permissions);
+        replace!(grace_period, optional);
+        replace!(allowlist, optional);
+        replace!(report_formats);
+        replace!(report_dir);
+        replace!(report_compression);
+        replace!(report_history);
+        replace!(report_group_by);
+        replace!(verbosity);
+        replace!(offline);
+        replace!(db_path, optional);
+        replace!(db_index);
+        replace!(db_refresh);
+        replace!(db_refresh_interval);
+        replace!(db_memory_budget_mb);
+        replace!(db_cache_budget_mb);
+        replace!(db_snapshot, optional);
+        replace!(demo_db);
+        replace!(proxy, optional);
+        replace!(timeout_secs);
+        replace!(max_file_size);
+        replace!(archive_max_entries);
+        replace!(archive_max_entry_size);
+        replace!(archive_max_total_size);
+        replace!(allow_external_paths);
+        replace!(include);
+        replace!(exclude);
+        replace!(ecosystems);
+        replace!(scan_timeout_secs, optional);
+        replace!(db_refresh_budget_secs, optional);
+        replace!(extract_budget_secs, optional);
+        replace!(match_budget_secs, optional);
+        replace!(cache_lock_timeout_secs);
+        replace!(import_hints);
+        replace!(dry_run);
+        replace!(report_legacy_format);
+        replace!(report_bundle);
+        replace!(attestation);
+        replace!(attestation_signing);
+        replace!(attestation_key, optional);
+        replace!(remediate);
+        replace!(remediation_pr);
+        replace!(remediation_pr_branch);
+        replace!(remediation_pr_commit_message);
+        replace!(allow_constraint_changes);
+        replace!(notify_webhook, optional);
+        replace!(notify_format);
+        replace!(notify_dry_run);
+        replace!(metrics_file, optional);
+        replace!(findings_stream, optional);
+        replace!(otlp_endpoint, optional);
+        replace!(profile);
+        replace!(annotations);
+        replace!(log_description_length);
+        replace!(log_line_limit);
+        replace!(reproducible);
+        replace!(npm_provenance);
+        replace!(npm_provenance_publishers);
+        replace!(private_packages);
+        replace!(private_registry, optional);
+        replace!(public_registries);
+        replace!(internal_registries);
+        replace!(policy, optional);
+        replace!(test_policy, optional);
+        replace!(kev_catalog, optional);
+        replace!(extra_advisories, optional);
+        replace!(import_results);
+
+        // Policies are merged by name, a later layer's replacing an earlier
+        if let Some(policies) = &layer.policies {
+            self.policies.extend(policies.iter().map(|(name, policy)| (name.clone(), policy.clone())));
+            self.note_merged("policies", origin);
+        }
+        // Overrides are merged by id or path the same way
+        if let Some(overrides) = &layer.severity_overrides {
+            self.severity_overrides.extend(overrides.iter().map(|(id, severity)| (id.clone(), *severity)));
+            self.note_merged("severity_overrides", origin);
+        }
+        if let Some(overrides) = &layer.ecosystem_overrides {
+            self.ecosystem_overrides.extend(overrides.iter().map(|(path, e)| (path.clone(), e.clone())));
+            self.note_merged("ecosystem_overrides", origin);
+        }
+        if let Some(templates) = &layer.report_templates {
+            self.report_templates.extend(templates.iter().map(|(name, path)| (name.clone(), path.clone())));
+            self.note_merged("report_templates", origin);
+        }
+        // Aliases by ecosystem, then by name
+        if let Some(aliases) = &layer.name_aliases {
+            for (ecosystem, names) in aliases {
+                let merged = self.name_aliases.entry(ecosystem.clone()).or_default();
+                merged.extend(names.iter().map(|(name, alias)| (name.clone(), alias.clone())));
+            }
+            self.note_merged("name_aliases", origin);
+        }
+
+        if let Some(ids) = &layer.ignore_vulnerabilities {
+            for id in ids {
+                if !self.ignore_vulnerabilities.contains(id) {
+                    self.ignore_vulnerabilities.push(id.clone());
+                }
+            }
+            // Unioned lists keep track of every contributing layer
+            self.note_merged("ignore_vulnerabilities", origin);
+        }
+    }
+
+    /// Record `origin` among the layers a merged setting came from, as
+    /// `file+target`.
+    fn note_merged(&mut self, setting: &'static str, origin: &str) {
+        let origins = self.origins.entry(setting).or_default();
+        if !origins.is_empty() {
+            origins.push('+');
+        }
+        origins.push_str(origin);
+    }
+
+    /// Origin of every setting, with untouched ones reported as `default`.
+    pub fn setting_sources(&self) -> BTreeMap<&'static str, String> {
+        SETTINGS
+            .iter()
+            .map(|key| {
+                let origin = self.origins.get(key).cloned().unwrap_or_else(|| "default".to_string());
+                (*key, origin)
+            })
+            .collect()
+    }
+
+    /// `db_refresh_interval` as a duration; checked when the configuration
+    /// was resolved.
+    pub fn db_refresh_interval(&self) -> Duration {
+        parse_period(&self.db_refresh_interval).unwrap_or(Duration::from_secs(86_400))
+    }
+
+    /// `grace_period` as a duration, when one is set.
+    pub fn grace_period(&self) -> Option<Duration> {
+        self.grace_period.as_deref().and_then(parse_period)
+    }
+
+    pub fn is_debug(&self) -> bool {
+        self.verbosity == "debug"
+    }
+
+    pub fn is_ignored(&self, id: &str) -> bool {
+        self.ignore_vulnerabilities.iter().any(|i| i == id)
+    }
+
+    /// The `severity_overrides` entry for an advisory known by `ids`: an
+    /// exact id before the longest matching prefix.
+    pub fn severity_override(&self, ids: &[&str]) -> Option<(&str, Severity)> {
+        let exact = ids.iter().find_map(|id| self.severity_overrides.get_key_value(*id));
+        let by_prefix = || {
+            self.severity_overrides
+                .iter()
+                .filter_map(|(key, severity)| Some((key, key.strip_suffix('*')?, severity)))
+                .filter(|(_, prefix, _)| ids.iter().any(|id| id.starts_with(prefix)))
+                .max_by_key(|(_, prefix, _)| prefix.len())
+                .map(|(key, _, severity)| (key, severity))
+        };
+        exact.or_else(by_prefix).map(|(key, severity)| (key.as_str(), *severity))
+    }
+}
+
+/// Names of all settings, as used in config objects and origin reports.
+const SETTINGS: &[&str] = &[
+    "fail_on_critical",
+    "fail_on_high",
+    "fail_on_partial",
+    "fail_on_introduced",
+    "fail_on_regression",
+    "on_error",
+    "strict_version_check",
+    "strict_permissions",
+    "grace_period",
+    "allowlist",
+    "ignore_vulnerabilities",
+    "report_formats",
+    "report_templates",
+    "report_dir",
+    "report_compression",
+    "report_history",
+    "report_group_by",
+    "verbosity",
+    "offline",
+    "db_path",
+    "db_index",
+    "db_refresh",
+    "db_refresh_interval",
+    "db_memory_budget_mb",
+    "db_cache_budget_mb",
+    "db_snapshot",
+    "demo_db",
+    "proxy",
+    "timeout_secs",
+    "max_file_size",
+    "archive_max_entries",
+    "archive_max_entry_size",
+    "archive_max_total_size",
+    "allow_external_paths",
+    "include",
+    "exclude",
+    "ecosystems",
+    "scan_timeout_secs",
+    "db_refresh_budget_secs",
+    "extract_budget_secs",
+    "match_budget_secs",
+    "cache_lock_timeout_secs",
+    "import_hints",
+    "dry_run",
+    "report_legacy_format",
+    "report_bundle",
+    "attestation",
+    "attestation_signing",
+    "attestation_key",
+    "remediate",
+    "remediation_pr",
+    "remediation_pr_branch",
+    "remediation_pr_commit_message",
+    "allow_constraint_changes",
+    "notify_webhook",
+    "notify_format",
+    "notify_dry_run",
+    "metrics_file",
+    "findings_stream",
+    "otlp_endpoint",
+    "profile",
+    "annotations",
+    "log_description_length",
+    "log_line_limit",
+    "reproducible",
+    "npm_provenance",
+    "npm_provenance_publishers",
+    "private_packages",
+    "private_registry",
+    "public_registries",
+    "internal_registries",
+    "policy",
+    "test_policy",
+    "policies",
+    "kev_catalog",
+    "extra_advisories",
+    "import_results",
+    "severity_overrides",
+    "ecosystem_overrides",
+    "name_aliases",
+];
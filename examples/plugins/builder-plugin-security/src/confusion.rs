@@ -0,0 +1,177 @@
+use std::collections::BTreeSet;
+
+use crate::config::ScanConfig;
+use crate::ecosystems::{self, Dependency};
+use crate::globs;
+use crate::registries;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// Finding for a private package resolved from outside the private
+/// registry.
+pub const PUBLIC_RESOLUTION: &str = "DEPENDENCY-CONFUSION";
+/// Finding for a private package nothing pins to a registry.
+pub const UNPINNED: &str = "PRIVATE-PACKAGE-UNPINNED";
+
+/// Check where the dependencies named like `private_packages` come from.
+///
+/// A private package a lockfile resolves from anywhere but
+/// `private_registry` is CRITICAL: a same-named public package was
+/// installed. One without a resolution URL in a lockfile that records them,
+/// or declared in a manifest that no lockfile pins, is HIGH. Lockfiles that
+/// don't record resolution URLs at all (`go.sum`) can't be checked, which is
+/// logged rather than reported.
+pub fn check(deps: &[Dependency], config: &ScanConfig, logs: &mut Vec<String>) -> Vec<Vulnerability> {
+    if config.private_packages.is_empty() {
+        return Vec::new();
+    }
+    let private: Vec<&Dependency> =
+        deps.iter().filter(|dep| globs::matches_any(&config.private_packages, &dep.name)).collect();
+    if private.is_empty() {
+        return Vec::new();
+    }
+    if config.private_registry.is_none() {
+        logs.push("  ⚠ private_registry is not set: every registry counts as public".to_string());
+    }
+    // Private packages some lockfile lists, whether or not it says from where
+    let locked: BTreeSet<(&str, &str)> = private
+        .iter()
+        .filter(|dep| !ecosystems::is_manifest(&dep.file))
+        .map(|dep| (dep.ecosystem.as_str(), dep.name.as_str()))
+        .collect();
+
+    let mut degraded = BTreeSet::new();
+    let mut findings = Vec::new();
+    for dep in &private {
+        let records_resolution = ecosystems::detect(&dep.file).is_some_and(|scanner| scanner.records_resolution());
+        let (severity, id, description) = match &dep.resolved {
+            Some(url) if is_private(url, config) => continue,
+            Some(url) => (
+                Severity::Critical,
+                PUBLIC_RESOLUTION,
+                format!(
+                    "{} matches a private package pattern but {} resolves it from {}, not the private registry",
+                    dep.name,
+                    dep.file,
+                    host(url)
+                ),
+            ),
+            None if records_resolution => (
+                Severity::High,
+                UNPINNED,
+                format!(
+                    "{} matches a private package pattern but {} doesn't say where it resolves from",
+                    dep.name, dep.file
+                ),
+            ),
+            None if ecosystems::is_manifest(&dep.file) => {
+                if locked.contains(&(dep.ecosystem.as_str(), dep.name.as_str())) {
+                    continue;
+                }
+                (
+                    Severity::High,
+                    UNPINNED,
+                    format!("{} matches a private package pattern but no lockfile pins it to a registry", dep.name),
+                )
+            }
+            None => {
+                degraded.insert(dep.file.as_str());
+                continue;
+            }
+        };
+        findings.push(Vulnerability {
+            file: Some(dep.file.clone()),
+            line: Some(dep.line),
+            column: dep.col,
+            project: dep.project.clone(),
+            registry: dep.registry.clone(),
+            ..Vulnerability::new(id, severity, &dep.name, &dep.version, description)
+        });
+    }
+
+    for file in degraded {
+        logs.push(format!(
+            "  ⚠ Dependency confusion check degraded: {} doesn't record where packages resolve from",
+            file
+        ));
+    }
+    logs.push(format!(
+        "  Checked {} private packages for dependency confusion: {} problems",
+        private.len(),
+        findings.len()
+    ));
+    findings
+}
+
+/// Whether `url` is under the private registry's URL.
+fn is_private(url: &str, config: &ScanConfig) -> bool {
+    config.private_registry.as_ref().is_some_and(|registry| registries::is_under(url, registry))
+}
+
+/// The host of a URL, or the whole string when it has none.
+fn host(url: &str) -> &str {
+    match url.split_once("://") {
+        Some((_, rest)) => rest.split(['/', '?', '#']).next().unwrap_or(rest),
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(file: &str, content: &str) -> Vec<Dependency> {
+        ecosystems::extract_str(ecosystems::detect(file).unwrap(), file, content)
+    }
+
+    #[test]
+    fn private_packages_must_resolve_from_the_private_registry() {
+        let config = ScanConfig {
+            private_packages: vec!["@ourorg/*".to_string(), "internal-*".to_string()],
+            private_registry: Some("https://npm.ourorg.dev/".to_string()),
+            ..ScanConfig::default()
+        };
+        let mut deps = extract(
+            "web/yarn.lock",
+            concat!(
+                "\"@ourorg/ui@^1.0.0\":\n",
+                "  version \"1.2.0\"\n",
+                "  resolved \"https://npm.ourorg.dev/@ourorg/ui/-/ui-1.2.0.tgz\"\n",
+                "\n",
+                "internal-auth@^2.0.0:\n",
+                "  version \"2.0.1\"\n",
+                "  resolved \"https://registry.yarnpkg.com/internal-auth/-/internal-auth-2.0.1.tgz\"\n",
+                "\n",
+                "internal-logs@^1.0.0:\n",
+                "  version \"1.0.0\"\n",
+                "\n",
+                "lodash@^4.17.15:\n",
+                "  version \"4.17.15\"\n",
+                "  resolved \"https://registry.yarnpkg.com/lodash/-/lodash-4.17.15.tgz\"\n",
+            ),
+        );
+        deps.extend(extract(
+            "web/package.json",
+            "{\n  \"dependencies\": {\n    \"@ourorg/ui\": \"^1.0.0\",\n    \"@ourorg/theme\": \"3.0.0\"\n  }\n}\n",
+        ));
+        deps.extend(extract("svc/go.sum", "internal-rpc v1.0.0 h1:abc=\n"));
+
+        let mut logs = Vec::new();
+        let found: Vec<(String, Severity, String)> = check(&deps, &config, &mut logs)
+            .into_iter()
+            .map(|v| (v.id, v.severity, v.package))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (PUBLIC_RESOLUTION.to_string(), Severity::Critical, "internal-auth".to_string()),
+                (UNPINNED.to_string(), Severity::High, "internal-logs".to_string()),
+                (UNPINNED.to_string(), Severity::High, "@ourorg/theme".to_string()),
+            ]
+        );
+        assert!(logs.contains(
+            &"  ⚠ Dependency confusion check degraded: svc/go.sum doesn't record where packages resolve from".to_string()
+        ));
+        assert!(!is_private("https://npm.ourorg.dev.evil.com/x.tgz", &config));
+    }
+}
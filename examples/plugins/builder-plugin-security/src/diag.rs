@@ -0,0 +1,96 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use crate::iotrace;
+
+/// How much a diagnostic matters to the host's operator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+        }
+    }
+}
+
+/// Diagnostics for the host's operator, kept apart from the JSON-RPC
+/// stream: collected while a request is handled, then written to stderr in
+/// one write, so a batch is never interleaved with another writer's lines.
+///
+/// Each diagnostic is one `<timestamp> <LEVEL> <module>: <message>` line.
+#[derive(Default)]
+pub struct Diagnostics {
+    lines: Vec<String>,
+}
+
+impl Diagnostics {
+    pub fn log(&mut self, level: Level, module: &str, message: impl Display) {
+        // One line per diagnostic, whatever the message holds
+        let message = message.to_string().replace(['\r', '\n'], " ");
+        self.lines.push(format!("{} {:<5} {}: {}", iotrace::timestamp(), level.as_str(), module, message));
+    }
+
+    pub fn error(&mut self, module: &str, message: impl Display) {
+        self.log(Level::Error, module, message);
+    }
+
+    pub fn warn(&mut self, module: &str, message: impl Display) {
+        self.log(Level::Warn, module, message);
+    }
+
+    pub fn info(&mut self, module: &str, message: impl Display) {
+        self.log(Level::Info, module, message);
+    }
+
+    /// The diagnostics not yet written.
+    #[cfg(test)]
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// Write the diagnostics collected since the last flush to `err`.
+    pub fn flush(&mut self, err: &mut dyn Write) -> io::Result<()> {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+        let mut batch = self.lines.join("\n");
+        batch.push('\n');
+        self.lines.clear();
+        err.write_all(batch.as_bytes())?;
+        err.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagnostics_are_single_structured_lines_written_in_one_batch() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.warn("handshake", "Builder 0.9.0 is older than 1.0.0");
+        diagnostics.error("plugin", "Parse error:\nexpected value");
+        let [warning, error] = diagnostics.lines() else {
+            panic!("{:?}", diagnostics.lines());
+        };
+        let (timestamp, rest) = warning.split_once(' ').unwrap();
+        assert!(timestamp.ends_with('Z') && timestamp.contains('T'), "{}", timestamp);
+        assert_eq!(rest, "WARN  handshake: Builder 0.9.0 is older than 1.0.0");
+        assert!(error.ends_with(" ERROR plugin: Parse error: expected value"), "{}", error);
+
+        let mut err = Vec::new();
+        diagnostics.flush(&mut err).unwrap();
+        assert_eq!(String::from_utf8(err).unwrap().lines().count(), 2);
+        let mut err = Vec::new();
+        diagnostics.flush(&mut err).unwrap();
+        assert!(err.is_empty());
+    }
+}
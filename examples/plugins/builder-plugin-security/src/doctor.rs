@@ -0,0 +1,385 @@
+use serde::Serialize;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::advisories::schedule::Schedule;
+use crate::advisories::{AdvisorySource, ImportedResults, PrivateAdvisories};
+use crate::cache;
+use crate::config::{ScanConfig, CONFIG_FILE_NAME};
+use crate::fsutil;
+use crate::http;
+use crate::ecosystems;
+use crate::paths;
+use crate::suppressions;
+
+/// Embedded extractor fixtures: file name, file content, and one dependency
+/// the scanner detected for that name must find in it.
+const FIXTURES: &[(&str, &str, (&str, &str))] = &[
+    ("requirements.txt", "# pinned\ndjango==2.2.0\r\n", ("django", "2.2.0")),
+    (
+        "package.json",
+        "{\n  \"name\": \"app\",\n  \"dependencies\": {\n    \"lodash\": \"^4.17.15\"\n  }\n}\n",
+        ("lodash", "4.17.15"),
+    ),
+    (
+        "yarn.lock",
+        "lodash@^4.17.15:\n  version \"4.17.15\"\n  resolved \"https://registry.yarnpkg.com/lodash\"\n",
+        ("lodash", "4.17.15"),
+    ),
+    (
+        "Cargo.toml",
+        "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { version = \"1.0.1\" }\n",
+        ("serde", "1.0.1"),
+    ),
+    (
+        "go.sum",
+        "golang.org/x/net v0.7.0 h1:abc=\ngolang.org/x/net v0.7.0/go.mod h1:def=\n",
+        ("golang.org/x/net", "0.7.0"),
+    ),
+    (
+        "var/lib/dpkg/status",
+        "Package: openssl\nStatus: install ok installed\nVersion: 1:3.0.9-1\n",
+        ("openssl", "1:3.0.9-1"),
+    ),
+    ("lib/apk/db/installed", "P:musl\nV:1.2.4-r2\nA:x86_64\n", ("musl", "1.2.4-r2")),
+];
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Outcome of one diagnostic check.
+#[derive(Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub message: String,
+    /// What to do about a warning or failure.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            status: Status::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Run every check. `config` is the result of resolving the request's
+/// configuration layers; later checks are skipped when it failed.
+pub fn run(workspace_root: &Path, config_file: &Path, config: Result<ScanConfig, String>) -> Vec<Check> {
+    let mut checks = vec![check_workspace(workspace_root)];
+
+    let config = match config {
+        Ok(config) => {
+            checks.push(if config_file.is_file() {
+                Check::pass("config", format!("{} is valid", config_file.display()))
+            } else {
+                Check::pass("config", format!("No {}, using defaults and overrides", CONFIG_FILE_NAME))
+            });
+            config
+        }
+        Err(e) => {
+            checks.push(Check::fail(
+                "config",
+                e,
+                "Fix the reported setting; `plugin.configure` shows where each value comes from",
+            ));
+            return checks;
+        }
+    };
+
+    checks.extend(check_sources(workspace_root, &config));
+    // Probing the cache would create directories under a bogus root
+    if workspace_root.is_dir() {
+        checks.push(check_cache(workspace_root, &config));
+    }
+    checks.push(check_suppressions(workspace_root));
+    checks.push(check_extractors());
+    checks
+}
+
+fn check_workspace(root: &Path) -> Check {
+    if root.is_dir() {
+        Check::pass("workspace", format!("Workspace root {} exists", root.display()))
+    } else {
+        Check::fail(
+            "workspace",
+            format!("Workspace root {} is not a directory", root.display()),
+            "Pass the repository root in workspace.root (or --workspace); sources are resolved relative to it",
+        )
+    }
+}
+
+fn check_sources(root: &Path, config: &ScanConfig) -> Vec<Check> {
+    let mut checks = Vec::new();
+    if config.db_path.is_none() && config.db_snapshot.is_none() {
+        checks.push(if config.extra_advisories.is_some() {
+            Check::warn(
+                "source:extra_advisories",
+                "Only the private advisories in extra_advisories are configured",
+                "Set db_path to a local OSV database to match public advisories as well",
+            )
+        } else if !config.import_results.is_empty() {
+            Check::warn(
+                "source:import_results",
+                "Only the results imported by import_results are configured",
+                "Set db_path to a local OSV database to match the dependency files the other scanners didn't read",
+            )
+        } else if config.demo_db {
+            Check::warn(
+                "source:builtin-demo",
+                "Only the built-in demo advisories are configured",
+                "Set db_path to a local OSV database for a real audit",
+            )
+        } else {
+            Check::fail(
+                "source:none",
+                "No advisory source is configured",
+                "Set db_path to a local OSV database, or demo_db = true to try the built-in demo data",
+            )
+        });
+    }
+
+    if let Some(db_path) = &config.db_path {
+        checks.push(match fs::metadata(db_path) {
+            Ok(_) => Check::pass("source:db_path", format!("{} is readable", db_path)),
+            Err(e) => Check::warn(
+                "source:db_path",
+                format!("{}: {}", db_path, e),
+                "Point db_path at an existing advisory database or remove the setting",
+            ),
+        });
+    }
+
+    if config.db_path.is_some() && config.db_refresh == "scheduled" {
+        checks.extend(check_schedule(root, config));
+    }
+
+    if let Some(extra) = &config.extra_advisories {
+        let mut source = PrivateAdvisories::new(paths::join_source(root, extra));
+        checks.push(match source.refresh(root, SystemTime::now()) {
+            Ok(_) => Check::pass("source:extra_advisories", format!("{} is valid", extra)),
+            Err(e) => Check::warn(
+                "source:extra_advisories",
+                e,
+                "Fix the reported advisory; until then none of the file's advisories are matched",
+            ),
+        });
+    }
+
+    for path in &config.import_results {
+        let mut source = ImportedResults::new(paths::join_source(root, path));
+        checks.push(match source.refresh(root, SystemTime::now()) {
+            Ok(snapshot) => Check::pass("source:import_results", format!("{} is {} output", path, snapshot.source)),
+            Err(e) => Check::warn(
+                "source:import_results",
+                e,
+                "Point import_results at npm audit --json, pip-audit --format json or cargo audit --json output",
+            ),
+        });
+    }
+
+    #[cfg(feature = "network-sources")]
+    if config.proxy.is_some() || config.notify_webhook.is_some() {
+        checks.push(match http::agent(config) {
+            Ok(_) => Check::pass("network", "HTTP client configuration is valid"),
+            Err(e) => Check::fail("network", e, "Use a proxy URL like http://proxy.example:3128"),
+        });
+    }
+    #[cfg(not(feature = "network-sources"))]
+    if config.notify_webhook.is_some() || config.npm_provenance && !config.offline {
+        checks.push(Check::warn(
+            "network",
+            http::NOT_BUILT,
+            "Build with `cargo build --release --features network-sources` to connect",
+        ));
+    }
+
+    if config.offline {
+        checks.push(Check::warn(
+            "network",
+            "offline is set; no advisory source will be contacted",
+            "Unset offline to fetch fresh advisories",
+        ));
+    }
+
+    checks
+}
+
+/// When each ecosystem `db_refresh = "scheduled"` reads was last used by a
+/// scan, and when a changed database is next read for it.
+fn check_schedule(root: &Path, config: &ScanConfig) -> Vec<Check> {
+    let schedule = Schedule::load(&cache::cache_root(root, &config.report_dir).join("db"));
+    let fingerprint = schedule.fingerprint.clone().unwrap_or_default();
+    let freshness = schedule.freshness(&fingerprint, None, config.db_refresh_interval());
+    if freshness.is_empty() {
+        return vec![Check::pass(
+            "source:schedule",
+            "No ecosystem used yet; each is read on the first scan needing it",
+        )];
+    }
+    freshness
+        .iter()
+        .map(|f| {
+            let next = f.next_refresh.as_deref().unwrap_or("on the next scan");
+            let last_used = f.last_used.as_deref().unwrap_or("never");
+            Check::pass(
+                "source:schedule",
+                format!("{}: last used {}, next refresh {}", f.ecosystem, last_used, next),
+            )
+        })
+        .collect()
+}
+
+fn check_cache(root: &Path, config: &ScanConfig) -> Check {
+    let dir = cache::cache_root(root, &config.report_dir);
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+
+    let result = fsutil::create_dir_all(&dir)
+        .and_then(|_| fsutil::write(&probe, b"ok"))
+        .and_then(|_| fsutil::remove_file(&probe));
+    match result {
+        Ok(()) => Check::pass("cache", format!("{} is writable", dir.display())),
+        Err(e) => Check::fail(
+            "cache",
+            format!("Cannot write to {}: {}", dir.display(), e),
+            "Check permissions or set report_dir to a writable directory",
+        ),
+    }
+}
+
+fn check_suppressions(root: &Path) -> Check {
+    let path = root.join(suppressions::SUPPRESSIONS_FILE_NAME);
+    match suppressions::load(&path) {
+        Ok(entries) if path.is_file() => {
+            Check::pass("suppressions", format!("{} entries in {}", entries.len(), path.display()))
+        }
+        Ok(_) => Check::pass("suppressions", "No suppression file"),
+        Err(e) => Check::warn(
+            "suppressions",
+            e,
+            "The file is ignored until fixed; `triage` writes entries in the expected format",
+        ),
+    }
+}
+
+fn check_extractors() -> Check {
+    let broken: Vec<String> = FIXTURES
+        .iter()
+        .filter(|(file, content, (name, version))| {
+            !ecosystems::detect(file).is_some_and(|scanner| {
+                ecosystems::extract_str(scanner, file, content)
+                    .iter()
+                    .any(|dep| dep.name == *name && dep.version == *version)
+            })
+        })
+        .map(|(file, _, _)| file.to_string())
+        .collect();
+
+    if broken.is_empty() {
+        Check::pass("extractors", format!("{} dependency file formats parsed", FIXTURES.len()))
+    } else {
+        Check::fail(
+            "extractors",
+            format!("Fixture parsing failed for: {}", broken.join(", ")),
+            "This is a plugin bug; please report it with the plugin version",
+        )
+    }
+}
+
+/// Entry point of the `doctor` subcommand. Returns the process exit code:
+/// 1 if any check failed.
+pub fn cli(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut workspace = ".".to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--workspace", Some(dir)) => workspace = dir.clone(),
+            _ => {
+                let _ = writeln!(err, "usage: builder-plugin-security doctor [--workspace DIR]");
+                return 2;
+            }
+        }
+    }
+
+    let root = Path::new(&workspace);
+    let config_file = root.join(CONFIG_FILE_NAME);
+    let config = ScanConfig::resolve(Some(&config_file), None, None, std::env::vars());
+    let checks = run(root, &config_file, config);
+
+    for check in &checks {
+        let marker = match check.status {
+            Status::Pass => "✓",
+            Status::Warn => "⚠",
+            Status::Fail => "✗",
+        };
+        let _ = writeln!(out, "{} {:<22} {}", marker, check.name, check.message);
+        if let Some(hint) = &check.hint {
+            let _ = writeln!(out, "  {:<22} {}", "", hint);
+        }
+    }
+
+    i32::from(checks.iter().any(|c| c.status == Status::Fail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedded_fixtures_parse() {
+        assert!(check_extractors().status == Status::Pass);
+    }
+
+    #[test]
+    fn scheduled_refreshes_list_each_used_ecosystem() {
+        let root = crate::testutil::scratch_dir("doctor-schedule");
+        let config = ScanConfig {
+            db_path: Some("osv".to_string()),
+            db_refresh: "scheduled".to_string(),
+            ..ScanConfig::default()
+        };
+        let messages = |root: &Path| -> Vec<String> {
+            check_schedule(root, &config).into_iter().map(|check| check.message).collect()
+        };
+        assert_eq!(messages(&root), ["No ecosystem used yet; each is read on the first scan needing it"]);
+
+        let db_cache = cache::cache_root(&root, &config.report_dir).join("db");
+        let mut schedule = Schedule::load(&db_cache);
+        let day = std::time::Duration::from_secs(86_400);
+        schedule.mark_used(&[crate::ecosystems::Ecosystem::Go], SystemTime::UNIX_EPOCH + day);
+        schedule.save(&db_cache).unwrap();
+        assert_eq!(messages(&root), ["Go: last used 1970-01-02T00:00:00Z, next refresh on the next scan"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
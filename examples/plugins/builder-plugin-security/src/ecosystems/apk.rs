@@ -0,0 +1,231 @@
+use std::cmp::Ordering;
+use std::io::BufRead;
+
+use super::{for_each_stanza, Dependency, Ecosystem, EcosystemScanner, Extracted, Scope};
+
+/// Alpine package database (`lib/apk/db/installed`) of a container
+/// filesystem.
+pub struct ApkInstalled;
+
+impl EcosystemScanner for ApkInstalled {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Alpine
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("lib/apk/db/installed")
+    }
+
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        let mut extracted = for_each_stanza(reader, |stanza| {
+            let (Some((number, line, name)), Some((_, _, version))) = (stanza.field("P"), stanza.field("V")) else {
+                return;
+            };
+            let entry = (name.to_string(), version.to_string());
+            let mut dep = Dependency::new(self.ecosystem(), file, entry, Scope::Runtime, number, line);
+            dep.arch = stanza.field("A").map(|(_, _, arch)| arch.to_string());
+            deps.push(dep);
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+}
+
+/// Suffixes in apk's order; the first four sort before the release they
+/// suffix, the others after it.
+const SUFFIXES: &[&str] = &["alpha", "beta", "pre", "rc", "cvs", "svn", "git", "hg", "p"];
+const PRE_RELEASE_SUFFIXES: usize = 4;
+
+/// One token of an apk version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token<'a> {
+    /// Something out of place; versions equal up to one compare equal.
+    Invalid,
+    /// A number, `first` for the one the version starts with.
+    Number { digits: &'a str, first: bool },
+    Letter(u8),
+    /// A suffix, as its index in [`SUFFIXES`], and its number.
+    Suffix(usize, u64),
+    Revision(u64),
+    End,
+}
+
+impl Token<'_> {
+    /// Where the token's kind sorts: a version going on past the other is
+    /// greater when its next token sorts lower.
+    fn rank(self) -> u8 {
+        match self {
+            Token::Invalid => 0,
+            Token::Number { .. } => 1,
+            Token::Letter(_) => 2,
+            Token::Suffix(..) => 3,
+            Token::Revision(_) => 4,
+            Token::End => 5,
+        }
+    }
+
+    fn is_pre_release(self) -> bool {
+        matches!(self, Token::Suffix(suffix, _) if suffix < PRE_RELEASE_SUFFIXES)
+    }
+}
+
+/// The tokens of `upstream[letter][_suffix[N]...][-rN]`, ending with
+/// [`Token::End`] or, where the version stops making sense,
+/// [`Token::Invalid`].
+fn tokens(version: &str) -> Vec<Token<'_>> {
+    let digits = |s: &str| s.bytes().take_while(u8::is_ascii_digit).count();
+    let number = |s: &str| s.bytes().fold(0u64, |n, c| n.saturating_mul(10).saturating_add(u64::from(c - b'0')));
+    let mut tokens = Vec::new();
+    let mut rest = version;
+    loop {
+        let len = digits(rest);
+        if len == 0 {
+            tokens.push(Token::Invalid);
+            return tokens;
+        }
+        tokens.push(Token::Number { digits: &rest[..len], first: tokens.is_empty() });
+        rest = &rest[len..];
+        match rest.strip_prefix('.') {
+            Some(next) => rest = next,
+            None => break,
+        }
+    }
+    if let Some(letter) = rest.bytes().next().filter(u8::is_ascii_lowercase) {
+        tokens.push(Token::Letter(letter));
+        rest = &rest[1..];
+    }
+    while let Some(suffix) = rest.strip_prefix('_') {
+        let Some(index) = SUFFIXES.iter().position(|name| suffix.starts_with(name)) else {
+            tokens.push(Token::Invalid);
+            return tokens;
+        };
+        let suffix = &suffix[SUFFIXES[index].len()..];
+        let len = digits(suffix);
+        tokens.push(Token::Suffix(index, number(&suffix[..len])));
+        rest = &suffix[len..];
+    }
+    if let Some(release) = rest.strip_prefix("-r") {
+        let len = digits(release);
+        tokens.push(Token::Revision(number(&release[..len])));
+        rest = &release[len..];
+    }
+    tokens.push(if rest.is_empty() { Token::End } else { Token::Invalid });
+    tokens
+}
+
+/// Numbers compare numerically, except that past the first, a leading zero
+/// in either makes both compare as strings without trailing zeros: `1.01`
+/// sorts before `1.1` and after `1.001`.
+fn compare_numbers(a: &str, b: &str, first: bool) -> Ordering {
+    if !first && (a.starts_with('0') || b.starts_with('0')) {
+        return a.trim_end_matches('0').cmp(b.trim_end_matches('0'));
+    }
+    let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// Compare Alpine versions as apk does: token by token, with `alpha`,
+/// `beta`, `pre` and `rc` suffixes before the release they suffix, `cvs`,
+/// `svn`, `git`, `hg` and `p` after it, then the package release (`-rN`).
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    for (x, y) in tokens(a).into_iter().zip(tokens(b)) {
+        let ordering = match (x, y) {
+            (Token::Number { digits: x, first }, Token::Number { digits: y, .. }) => compare_numbers(x, y, first),
+            (Token::Letter(x), Token::Letter(y)) => x.cmp(&y),
+            (Token::Suffix(x, m), Token::Suffix(y, n)) => (x, m).cmp(&(y, n)),
+            (Token::Revision(x), Token::Revision(y)) => x.cmp(&y),
+            (Token::Invalid, Token::Invalid) | (Token::End, Token::End) => return Ordering::Equal,
+            // One goes on where the other stops: it is greater, unless with
+            // a pre-release suffix
+            _ if x.is_pre_release() => return Ordering::Less,
+            _ if y.is_pre_release() => return Ordering::Greater,
+            _ => return y.rank().cmp(&x.rank()),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::extract_str;
+
+    #[test]
+    fn installed_db_lists_packages_and_versions_compare_like_apk() {
+        let content = "C:Q1abc=\nP:musl\nV:1.2.4-r2\nA:x86_64\nT:the musl c library\n\nC:Q1def=\nP:busybox\nV:1.36.1-r5\nA:x86_64\n";
+        let deps = extract_str(&ApkInstalled, "rootfs/lib/apk/db/installed", content);
+        let found: Vec<_> = deps
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.as_str(), d.arch.as_deref(), d.line, d.col))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("musl", "1.2.4-r2", Some("x86_64"), 2, Some(3)),
+                ("busybox", "1.36.1-r5", Some("x86_64"), 8, Some(3)),
+            ]
+        );
+
+        for (lower, higher) in [
+            ("1.2.4-r1", "1.2.4-r2"),
+            ("1.2.4_rc1", "1.2.4"),
+            ("1.2.4_alpha2", "1.2.4_beta1"),
+            ("1.2.4", "1.2.4_p1"),
+            ("3.1.4-r0", "3.1.10-r0"),
+            ("1.2.3", "1.2.3a"),
+            ("1.2.4", "1.2.4-r0"),
+        ] {
+            assert_eq!(compare_versions(lower, higher), Ordering::Less, "{} < {}", lower, higher);
+            assert_eq!(compare_versions(higher, lower), Ordering::Greater, "{} > {}", higher, lower);
+        }
+    }
+
+    #[test]
+    fn versions_compare_like_apk_tools_test_vectors() {
+        // The first cases are from apk-tools' test/version.data
+        for line in [
+            "2.34 > 0.1.0_alpha",
+            "23_foo > 4_beta",
+            "1.0 < 1.0bc",
+            "0.1.0_alpha = 0.1.0_alpha",
+            "0.1.0_alpha < 0.1.3_alpha",
+            "0.1.0_alpha2 > 0.1.0_alpha",
+            "0.1.0_alpha < 0.1.0_beta",
+            "0.1.0_beta < 0.1.0_beta2",
+            "0.1.0_beta < 0.1.0",
+            "0.1.0_pre < 0.1.0_rc",
+            "0.1.0_rc < 0.1.0",
+            "0.1.0 < 0.1.0_cvs",
+            "0.1.0_cvs < 0.1.0_svn",
+            "0.1.0_svn < 0.1.0_git",
+            "0.1.0_git < 0.1.0_hg",
+            "0.1.0_hg < 0.1.0_p",
+            "0.1.0_p < 0.1.0_p1",
+            "0.1.0 < 0.1.0-r1",
+            "0.1.0-r1 < 0.1.0_p",
+            "0.1.0_alpha-r3 < 0.1.0",
+            "1.0 < 1.0.1",
+            "1.0a < 1.0.1",
+            "1.0a < 1.0b",
+            "1.01 < 1.1",
+            "1.001 < 1.01",
+            "1.0 = 1.00",
+            "1.10 > 1.9",
+            "1.0_rc1_p2 > 1.0_rc1",
+            "1.0_rc1_p2 < 1.0",
+        ] {
+            let [a, op, b] = line.split(' ').collect::<Vec<_>>()[..] else { unreachable!() };
+            let expected = match op {
+                "<" => Ordering::Less,
+                "=" => Ordering::Equal,
+                _ => Ordering::Greater,
+            };
+            assert_eq!(compare_versions(a, b), expected, "{}", line);
+            assert_eq!(compare_versions(b, a), expected.reverse(), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::BufRead;
+
+use super::{for_each_line, strip_range_operator, Dependency, Ecosystem, EcosystemScanner, Extracted, Scope};
+
+/// Rust `Cargo.toml`.
+pub struct CargoToml;
+
+impl EcosystemScanner for CargoToml {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::CratesIo
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("Cargo.toml")
+    }
+
+    /// Handles `name = "1.2"` and inline tables such as
+    /// `name = { version = "1.2", features = [...] }` inside any
+    /// `[*dependencies]` table.
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        // Scope of the dependency table we're in
+        let mut scope = None;
+        let mut extracted = for_each_line(reader, |number, line| {
+            if let Some((name, requirement)) = parse_cargo_toml_line(line, &mut scope) {
+                let scope = scope.unwrap_or(Scope::Runtime);
+                let entry = (name, strip_range_operator(&requirement).to_string());
+                let dep = Dependency::new(self.ecosystem(), file, entry, scope, number, line);
+                deps.push(dep.declared_as(&requirement));
+            }
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+
+    fn rewritable(&self) -> bool {
+        true
+    }
+}
+
+/// The name and version requirement of a dependency table entry.
+fn parse_cargo_toml_line(line: &str, scope: &mut Option<Scope>) -> Option<(String, String)> {
+    let trimmed = line.trim();
+
+    if trimmed.starts_with('[') {
+        // [dependencies], [dev-dependencies], [target.'cfg(unix)'.build-dependencies], ...
+        let table = trimmed.trim_matches(|c| c == '[' || c == ']');
+        *scope = if table.ends_with("dev-dependencies") {
+            Some(Scope::Development)
+        } else if table.ends_with("build-dependencies") {
+            Some(Scope::Build)
+        } else if table.ends_with("dependencies") {
+            Some(Scope::Runtime)
+        } else {
+            None
+        };
+        return None;
+    }
+    if scope.is_none() || trimmed.starts_with('#') {
+        return None;
+    }
+
+    let (name, value) = trimmed.split_once('=')?;
+    let name = name.trim().trim_matches('"');
+    let value = value.trim();
+
+    let requirement = if value.starts_with('{') {
+        let after = &value[value.find("version")? + "version".len()..];
+        after.trim_start().strip_prefix('=')?.trim_start().strip_prefix('"')?.split('"').next()?
+    } else {
+        value.strip_prefix('"')?.split('"').next()?
+    };
+
+    let version = strip_range_operator(requirement);
+    if name.is_empty() || version.is_empty() {
+        return None;
+    }
+    Some((name.to_string(), requirement.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::extract_str;
+
+    #[test]
+    fn cargo_toml_locations_and_scopes() {
+        let content = r#"[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+serde = "1.0.100"
+tokio = { version = "1.2", features = ["full"] }
+
+[build-dependencies]
+cc = "1.0"
+"#;
+        let deps = extract_str(&CargoToml, "fixture", content);
+        let locations: Vec<_> = deps
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.as_str(), d.line, d.col, d.scope))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                ("serde", "1.0.100", 6, Some(1), Scope::Runtime),
+                ("tokio", "1.2", 7, Some(1), Scope::Runtime),
+                ("cc", "1.0", 10, Some(1), Scope::Build),
+            ]
+        );
+        assert_eq!(deps[0].ecosystem, Ecosystem::CratesIo);
+    }
+}
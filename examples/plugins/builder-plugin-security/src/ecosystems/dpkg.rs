@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+use std::io::BufRead;
+
+use super::{for_each_stanza, Dependency, Ecosystem, EcosystemScanner, Extracted, Scope};
+
+/// Debian package database (`var/lib/dpkg/status`) of a container
+/// filesystem.
+pub struct DpkgStatus;
+
+impl EcosystemScanner for DpkgStatus {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Debian
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("var/lib/dpkg/status")
+    }
+
+    /// Packages whose `Status` says they are installed; removed packages
+    /// that only left config files behind are skipped.
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        let mut extracted = for_each_stanza(reader, |stanza| {
+            let installed = stanza
+                .field("Status")
+                .is_none_or(|(_, _, status)| status.split_whitespace().last() == Some("installed"));
+            let (Some((number, line, name)), Some((_, _, version)), true) =
+                (stanza.field("Package"), stanza.field("Version"), installed)
+            else {
+                return;
+            };
+            let entry = (name.to_string(), version.to_string());
+            let mut dep = Dependency::new(self.ecosystem(), file, entry, Scope::Runtime, number, line);
+            dep.arch = stanza.field("Architecture").map(|(_, _, arch)| arch.to_string());
+            deps.push(dep);
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+}
+
+/// Compare Debian versions (`[epoch:]upstream[-revision]`) as dpkg does:
+/// epochs first, then the upstream part, then the revision, where `~`
+/// sorts before everything (even the end of the string).
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (epoch_a, upstream_a, revision_a) = split_version(a);
+    let (epoch_b, upstream_b, revision_b) = split_version(b);
+    epoch_a
+        .cmp(&epoch_b)
+        .then_with(|| compare_fragments(upstream_a, upstream_b))
+        .then_with(|| compare_fragments(revision_a, revision_b))
+}
+
+fn split_version(version: &str) -> (u64, &str, &str) {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) if epoch.bytes().all(|b| b.is_ascii_digit()) => (epoch.parse().unwrap_or(0), rest),
+        _ => (0, version),
+    };
+    let (upstream, revision) = rest.rsplit_once('-').unwrap_or((rest, ""));
+    (epoch, upstream, revision)
+}
+
+/// dpkg's `verrevcmp`: alternate runs of non-digits, compared character by
+/// character with letters before other symbols, and runs of digits,
+/// compared numerically.
+fn compare_fragments(a: &str, b: &str) -> Ordering {
+    fn order(c: Option<u8>) -> i32 {
+        match c {
+            None => 0,
+            Some(b'~') => -1,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+            Some(c) => i32::from(c) + 256,
+        }
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while a.get(i).is_some_and(|c| !c.is_ascii_digit()) || b.get(j).is_some_and(|c| !c.is_ascii_digit()) {
+            let ordering = order(a.get(i).copied()).cmp(&order(b.get(j).copied()));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+            i += 1;
+            j += 1;
+        }
+
+        let (next_i, run_a) = digit_run(a, i);
+        let (next_j, run_b) = digit_run(b, j);
+        let ordering = run_a.len().cmp(&run_b.len()).then_with(|| run_a.cmp(run_b));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        (i, j) = (next_i, next_j);
+    }
+    Ordering::Equal
+}
+
+/// The run of digits at `start`, without leading zeros, and where it ends.
+fn digit_run(s: &[u8], start: usize) -> (usize, &[u8]) {
+    let run = &s[start..];
+    let end = run.iter().position(|c| !c.is_ascii_digit()).unwrap_or(run.len());
+    let zeros = run[..end].iter().take_while(|&&c| c == b'0').count();
+    (start + end, &run[zeros..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::extract_str;
+
+    #[test]
+    fn status_file_lists_installed_packages() {
+        let content = "Package: openssl\nStatus: install ok installed\nArchitecture: amd64\nVersion: 3.0.9-1\nDescription: Secure Sockets Layer toolkit\n with a continuation line\n\nPackage: old-tool\nStatus: deinstall ok config-files\nVersion: 1.0-1\n\nPackage: tzdata\nStatus: install ok installed\nArchitecture: all\nVersion: 2024a-0+deb12u1\n";
+        let deps = extract_str(&DpkgStatus, "rootfs/var/lib/dpkg/status", content);
+        let found: Vec<_> = deps
+            .iter()
+            .map(|d| (d.name.as_str(), d.version.as_str(), d.arch.as_deref(), d.line, d.col))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("openssl", "3.0.9-1", Some("amd64"), 1, Some(10)),
+                ("tzdata", "2024a-0+deb12u1", Some("all"), 12, Some(10)),
+            ]
+        );
+    }
+
+    #[test]
+    fn versions_compare_like_dpkg() {
+        for (lower, higher) in [
+            ("2.0-1", "1:1.0-1"),
+            ("1:2.0", "2:0.1"),
+            ("1.0~rc1-1", "1.0-1"),
+            ("3.0.11-1~deb12u1", "3.0.11-1"),
+            ("1.2.9", "1.2.10"),
+            ("1.0", "1.0a"),
+            ("1.0", "1.0+b1"),
+            ("1.0-9", "1.0-10"),
+            ("1.2-3-4", "1.2-3-5"),
+        ] {
+            assert_eq!(compare_versions(lower, higher), Ordering::Less, "{} < {}", lower, higher);
+            assert_eq!(compare_versions(higher, lower), Ordering::Greater, "{} > {}", higher, lower);
+        }
+        assert_eq!(compare_versions("0:1.2.3-4", "1.2.3-4"), Ordering::Equal);
+        assert_eq!(compare_versions("1.02", "1.2"), Ordering::Equal);
+    }
+
+    #[test]
+    fn versions_compare_like_dpkg_test_vectors() {
+        // Debian Policy 5.6.12's tilde example, then epoch, upstream and
+        // revision cases as dpkg's lib/dpkg/t/t-version.c writes them, and
+        // suffixes common in the archive
+        let policy = ["1.0~~", "1.0~~a", "1.0~", "1.0", "1.0a"];
+        for pair in policy.windows(2) {
+            assert_eq!(compare_versions(pair[0], pair[1]), Ordering::Less, "{} < {}", pair[0], pair[1]);
+        }
+        for line in [
+            "0:0-0 = 0:0-0",
+            "0:0-0 = 0:0-00",
+            "0:0-0 = 0:0",
+            "1:0-0 > 0:0-0",
+            "0:1-0 > 0:0-0",
+            "0:0-1 > 0:0-0",
+            "0:a-0 < 0:b-0",
+            "0:0-a < 0:0-b",
+            "1:2-3 > 0:4-5",
+            "0:1.0~rc1-1 < 0:1.0-1",
+            "0:1.0-1~bpo1 < 0:1.0-1",
+            "0:1.0+dfsg-1 > 0:1.0-1",
+            "0:1.0.0-1 > 0:1.0-1",
+            "0:1.10-1 > 0:1.9-1",
+            "0:2.7.4-0ubuntu1 < 0:2.7.4-0ubuntu1.1",
+        ] {
+            let [a, op, b] = line.split(' ').collect::<Vec<_>>()[..] else { unreachable!() };
+            let expected = match op {
+                "<" => Ordering::Less,
+                "=" => Ordering::Equal,
+                _ => Ordering::Greater,
+            };
+            assert_eq!(compare_versions(a, b), expected, "{}", line);
+            assert_eq!(compare_versions(b, a), expected.reverse(), "{}", line);
+        }
+    }
+}
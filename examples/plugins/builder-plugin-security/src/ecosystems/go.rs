@@ -0,0 +1,171 @@
+use std::io::BufRead;
+
+use super::{for_each_line, parse_pinned_line, Dependency, Ecosystem, EcosystemScanner, Extracted, Scope};
+
+/// Go `go.mod`.
+pub struct GoMod;
+
+/// Go `go.sum` checksums.
+pub struct GoSum;
+
+impl EcosystemScanner for GoMod {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Go
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("go.mod")
+    }
+
+    fn is_manifest(&self) -> bool {
+        true
+    }
+
+    /// Only `name==version` lines are recognized for now, so `require`
+    /// directives yield nothing; go.sum carries the resolved versions.
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        let mut extracted = for_each_line(reader, |number, line| {
+            if let Some(pinned) = parse_pinned_line(line) {
+                deps.push(Dependency::new(self.ecosystem(), file, pinned, Scope::Runtime, number, line));
+            }
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+}
+
+impl EcosystemScanner for GoSum {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Go
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("go.sum")
+    }
+
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        let mut extracted = for_each_line(reader, |number, line| {
+            if let Some(entry) = parse_go_sum_line(line) {
+                deps.push(Dependency::new(self.ecosystem(), file, entry, Scope::Runtime, number, line));
+            }
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+}
+
+/// Parse one `module version[/go.mod] hash` line.
+///
+/// Each module appears with and without the `/go.mod` suffix; only the
+/// plain entry is taken so modules aren't counted twice.
+fn parse_go_sum_line(line: &str) -> Option<(String, String)> {
+    let mut parts = line.split_whitespace();
+    let module = parts.next()?;
+    let version = parts.next()?;
+
+    if version.ends_with("/go.mod") {
+        return None;
+    }
+
+    Some((module.to_string(), version.trim_start_matches('v').to_string()))
+}
+
+/// Import paths of a Go source file, read textually: the `import`
+/// declarations before its first other declaration. Build constraints are
+/// not evaluated, so imports of files for other platforms count too.
+pub fn go_imports(source: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    let mut in_block = false;
+    let mut in_comment = false;
+    for line in source.lines() {
+        let mut line = line.trim();
+        if in_comment {
+            let Some(end) = line.find("*/") else {
+                continue;
+            };
+            line = line[end + 2..].trim();
+            in_comment = false;
+        }
+        if let Some(start) = line.find("/*") {
+            in_comment = !line[start..].contains("*/");
+            line = &line[..start];
+        }
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if in_block {
+            match line.strip_prefix(')') {
+                Some(_) => in_block = false,
+                None => imports.extend(line.split(';').filter_map(quoted)),
+            }
+        } else if let Some(specs) = line.strip_prefix("import") {
+            let specs = specs.trim_start();
+            match specs.strip_prefix('(') {
+                Some(specs) => {
+                    in_block = !specs.trim_end().ends_with(')');
+                    imports.extend(specs.trim_end_matches(')').split(';').filter_map(quoted));
+                }
+                None => imports.extend(quoted(specs)),
+            }
+        } else if !line.starts_with("package") {
+            break;
+        }
+    }
+    imports
+}
+
+/// The path of one import spec: `"fmt"`, `f "fmt"`, `_ "net/http/pprof"`.
+fn quoted(spec: &str) -> Option<String> {
+    let start = spec.find(['"', '`'])?;
+    let quote = spec[start..].chars().next()?;
+    let rest = &spec[start + 1..];
+    Some(rest[..rest.find(quote)?].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::extract_str;
+
+    #[test]
+    fn go_sum_skips_go_mod_hashes() {
+        let content = "golang.org/x/net v0.7.0 h1:abc=\ngolang.org/x/net v0.7.0/go.mod h1:def=\ngithub.com/pkg/errors v0.9.1/go.mod h1:ghi=\n";
+        let deps = extract_str(&GoSum, "svc/go.sum", content);
+        let found: Vec<_> = deps.iter().map(|d| (d.name.as_str(), d.version.as_str(), d.line, d.col)).collect();
+        assert_eq!(found, vec![("golang.org/x/net", "0.7.0", 1, Some(1))]);
+        assert_eq!(deps[0].ecosystem, Ecosystem::Go);
+    }
+
+    #[test]
+    fn reads_imports_up_to_the_first_declaration() {
+        let source = r#"// Package api serves requests.
+package api
+
+import "fmt"
+import h "golang.org/x/net/html"
+
+/* "golang.org/x/crypto/ssh" is gone
+   for good */
+import (
+	"net/http" // server
+	_ "net/http/pprof"
+	. `github.com/pkg/errors`
+)
+
+func main() {
+	fmt.Println("import \"os\"")
+}
+
+import "too/late"
+"#;
+        assert_eq!(
+            go_imports(source),
+            vec!["fmt", "golang.org/x/net/html", "net/http", "net/http/pprof", "github.com/pkg/errors"]
+        );
+        assert_eq!(go_imports("package a\nimport (\"x\"; \"y\")\n"), vec!["x", "y"]);
+    }
+}
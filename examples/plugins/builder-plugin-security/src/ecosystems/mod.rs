@@ -0,0 +1,619 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+
+use crate::fsutil;
+use crate::globs;
+use crate::paths;
+
+mod apk;
+mod cargo;
+mod dpkg;
+mod go;
+mod npm;
+mod pypi;
+mod semver;
+
+pub use go::go_imports;
+pub use semver::compare as compare_semver;
+
+/// Longest line the parsers see; the rest of a longer line is read past
+/// without being buffered.
+pub const MAX_LINE_BYTES: usize = 1024 * 1024;
+
+/// Most lines read from one dependency file before it is skipped as
+/// exceeding limits.
+pub const MAX_LINES: usize = 2_000_000;
+
+/// Most lines in one dpkg/apk record before the file is skipped as
+/// exceeding limits.
+const MAX_STANZA_LINES: usize = 10_000;
+
+/// Package ecosystem, named as OSV names it.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ecosystem {
+    #[serde(rename = "PyPI")]
+    PyPI,
+    #[serde(rename = "npm")]
+    Npm,
+    #[serde(rename = "crates.io")]
+    CratesIo,
+    #[serde(rename = "Go")]
+    Go,
+    #[serde(rename = "Debian")]
+    Debian,
+    #[serde(rename = "Alpine")]
+    Alpine,
+    #[serde(rename = "RubyGems")]
+    RubyGems,
+    #[serde(rename = "Packagist")]
+    Packagist,
+    /// Packages named `group:artifact`.
+    #[serde(rename = "Maven")]
+    Maven,
+}
+
+impl Ecosystem {
+    pub const ALL: [Ecosystem; 9] = [
+        Ecosystem::PyPI,
+        Ecosystem::Npm,
+        Ecosystem::CratesIo,
+        Ecosystem::Go,
+        Ecosystem::Debian,
+        Ecosystem::Alpine,
+        Ecosystem::RubyGems,
+        Ecosystem::Packagist,
+        Ecosystem::Maven,
+    ];
+
+    /// The ecosystem OSV calls `name` (case-insensitively), ignoring a
+    /// `:release` suffix.
+    pub fn named(name: &str) -> Option<Self> {
+        let name = name.split(':').next().unwrap_or(name);
+        Self::ALL.into_iter().find(|e| e.as_str().eq_ignore_ascii_case(name))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Ecosystem::PyPI => "PyPI",
+            Ecosystem::Npm => "npm",
+            Ecosystem::CratesIo => "crates.io",
+            Ecosystem::Go => "Go",
+            Ecosystem::Debian => "Debian",
+            Ecosystem::Alpine => "Alpine",
+            Ecosystem::RubyGems => "RubyGems",
+            Ecosystem::Packagist => "Packagist",
+            Ecosystem::Maven => "Maven",
+        }
+    }
+
+    /// Whether any format in [`REGISTRY`] reads this ecosystem's files.
+    /// Advisories of the others are matched, but nothing declares their
+    /// packages yet.
+    pub fn has_format(self) -> bool {
+        REGISTRY.iter().any(|scanner| scanner.ecosystem() == self)
+    }
+
+    /// Where this ecosystem's own advisory pages live (host and path
+    /// prefix); a finding links to one of these when it can.
+    pub fn advisory_pages(self) -> &'static [&'static str] {
+        match self {
+            Ecosystem::PyPI => &["github.com/pypa/advisory-database/"],
+            Ecosystem::Npm => &["github.com/advisories/"],
+            Ecosystem::CratesIo => &["rustsec.org/advisories/"],
+            Ecosystem::Go => &["pkg.go.dev/vuln/"],
+            Ecosystem::Debian => &["security-tracker.debian.org/"],
+            Ecosystem::Alpine => &["security.alpinelinux.org/"],
+            Ecosystem::RubyGems => &["rubysec.com/advisories/"],
+            Ecosystem::Packagist => &["github.com/FriendsOfPHP/security-advisories/"],
+            Ecosystem::Maven => &["github.com/advisories/"],
+        }
+    }
+
+    /// Order two versions the way this ecosystem's package manager does.
+    pub fn compare_versions(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Ecosystem::Debian => dpkg::compare_versions(a, b),
+            Ecosystem::Alpine => apk::compare_versions(a, b),
+            Ecosystem::Npm | Ecosystem::CratesIo | Ecosystem::Go => semver::compare_versions(a, b),
+            Ecosystem::PyPI => pypi::compare_versions(a, b),
+            _ => crate::remediate::compare_versions(a, b),
+        }
+    }
+
+    /// A package name as this ecosystem compares names. RubyGems and Maven
+    /// (`group:artifact`) names, among others, are compared exactly.
+    pub fn canonical_name(self, name: &str) -> String {
+        match self {
+            // PEP 503: case-insensitive, runs of `-`, `_` and `.` are equal
+            Ecosystem::PyPI => {
+                let mut normalized = String::with_capacity(name.len());
+                for c in name.chars() {
+                    if matches!(c, '-' | '_' | '.') {
+                        if !normalized.ends_with('-') {
+                            normalized.push('-');
+                        }
+                    } else {
+                        normalized.push(c.to_ascii_lowercase());
+                    }
+                }
+                normalized
+            }
+            // Composer package names are case-insensitive
+            Ecosystem::Packagist => name.to_ascii_lowercase(),
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// The ecosystems a target of Builder's `language` can declare dependencies
+/// in; `None` for languages without a package ecosystem here.
+pub fn for_language(language: &str) -> Option<&'static [Ecosystem]> {
+    match language.to_ascii_lowercase().as_str() {
+        "python" => Some(&[Ecosystem::PyPI]),
+        "javascript" | "typescript" | "node" | "nodejs" => Some(&[Ecosystem::Npm]),
+        "rust" => Some(&[Ecosystem::CratesIo]),
+        "go" | "golang" => Some(&[Ecosystem::Go]),
+        _ => None,
+    }
+}
+
+/// The ecosystems a target of Builder's `type` can declare dependencies in;
+/// `None` for types that don't say.
+pub fn for_target_type(kind: &str) -> Option<&'static [Ecosystem]> {
+    match kind.to_ascii_lowercase().as_str() {
+        "container" | "image" | "docker" => Some(&[Ecosystem::Debian, Ecosystem::Alpine]),
+        _ => None,
+    }
+}
+
+/// What a dependency is needed for, as declared by the manifest.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Runtime,
+    Development,
+    Build,
+    Optional,
+    Peer,
+}
+
+/// A dependency found in a manifest or lockfile, with where it was declared.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Dependency {
+    pub name: String,
+    pub version: String,
+    pub ecosystem: Ecosystem,
+    pub scope: Scope,
+    /// Workspace-relative, separator-normalized path of the declaring file.
+    pub file: String,
+    /// 1-based line of the declaration. For yarn.lock this is the entry
+    /// header naming the package, not its `version` line.
+    pub line: usize,
+    /// 1-based column of the package name on that line, when it appears
+    /// there verbatim.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub col: Option<usize>,
+    /// Distribution release an OS package was built for, as OSV spells it
+    /// (`12` for `Debian:12`, `v3.18` for `Alpine:v3.18`); `None` matches
+    /// advisories for every release.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    /// CPU architecture of an OS package (`amd64`, `x86_64`, ...).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    /// Where a lockfile says the package is downloaded from (`resolved` in
+    /// yarn.lock).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+    /// Project the declaring file belongs to (see [`crate::projects`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Registry an npm or PyPI package comes from, when it isn't the public
+    /// one (see [`crate::registries`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// Version requirement as a manifest declares it (`^4.17.15`,
+    /// `==2.2.0`); `None` in lockfiles.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraint: Option<String>,
+}
+
+impl Dependency {
+    /// A dependency declared on `line` of `file`, located by where `name`
+    /// appears in `text`.
+    fn new(
+        ecosystem: Ecosystem,
+        file: &str,
+        (name, version): (String, String),
+        scope: Scope,
+        line: usize,
+        text: &str,
+    ) -> Self {
+        Dependency {
+            col: column_of(text, &name),
+            ecosystem,
+            scope,
+            file: file.to_string(),
+            line,
+            name,
+            version,
+            release: None,
+            arch: None,
+            resolved: None,
+            project: None,
+            registry: None,
+            constraint: None,
+        }
+    }
+
+    fn declared_as(self, constraint: &str) -> Self {
+        Dependency {
+            constraint: Some(constraint.to_string()),
+            ..self
+        }
+    }
+}
+
+/// Result of reading one dependency file.
+#[derive(Default)]
+pub struct Extracted {
+    pub dependencies: Vec<Dependency>,
+    /// Lines that weren't valid UTF-8 and were decoded lossily.
+    pub lossy_lines: usize,
+    /// Lines longer than [`MAX_LINE_BYTES`], parsed cut off at it.
+    pub truncated_lines: usize,
+    /// Which limit the file exceeded; reading stopped there and the file is
+    /// skipped rather than partially reported.
+    pub exceeded: Option<String>,
+    /// Read error that stopped extraction early; `dependencies` holds what
+    /// was found before it.
+    pub error: Option<io::Error>,
+}
+
+/// One dependency file format.
+///
+/// Each format lives in its own module and is listed in [`REGISTRY`]; the
+/// scanner only goes through this trait.
+pub trait EcosystemScanner: Sync {
+    fn ecosystem(&self) -> Ecosystem;
+
+    /// Whether a (separator-normalized) source path is in this format.
+    fn detect(&self, path: &str) -> bool;
+
+    /// Read the dependencies declared in `file` from `reader`.
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted;
+
+    /// Whether remediation may rewrite versions in place: true for
+    /// manifests, false for lockfiles owned by a package manager.
+    fn rewritable(&self) -> bool {
+        false
+    }
+
+    /// Whether this is a project manifest rather than a lockfile or package
+    /// database; a directory holding one is the root of a project.
+    fn is_manifest(&self) -> bool {
+        self.rewritable()
+    }
+
+    /// Whether entries record the URL each package was resolved from, so
+    /// [`Dependency::resolved`] is set for every one that was.
+    fn records_resolution(&self) -> bool {
+        false
+    }
+}
+
+/// Every supported format, in detection order.
+pub static REGISTRY: &[&dyn EcosystemScanner] = &[
+    &pypi::Requirements,
+    &npm::PackageJson,
+    &npm::YarnLock,
+    &cargo::CargoToml,
+    &go::GoMod,
+    &go::GoSum,
+    &dpkg::DpkgStatus,
+    &apk::ApkInstalled,
+    #[cfg(test)]
+    &NoisyRequirements,
+];
+
+/// What [`NoisyRequirements`] prints.
+#[cfg(test)]
+pub const NOISE: &str = "noisy extractor was here";
+
+/// `requirements.txt` contents in a `deps.noisy` file, extracted by an
+/// extractor that prints to stdout, for tests of the JSON-RPC stream.
+#[cfg(test)]
+struct NoisyRequirements;
+
+#[cfg(test)]
+impl EcosystemScanner for NoisyRequirements {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::PyPI
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("deps.noisy")
+    }
+
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        use std::io::Write;
+
+        println!("{}", NOISE);
+        let _ = writeln!(io::stdout(), "{}: {}", NOISE, file);
+        pypi::Requirements.extract(file, reader)
+    }
+}
+
+/// The scanner for a (separator-normalized) source path, if any.
+pub fn detect(path: &str) -> Option<&'static dyn EcosystemScanner> {
+    REGISTRY.iter().copied().find(|scanner| scanner.detect(path))
+}
+
+/// How a dependency file's format was decided.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectedBy {
+    /// The file's name, as each format recognizes it.
+    Filename,
+    /// An `ecosystem_overrides` entry.
+    Override,
+}
+
+/// The scanner for a (separator-normalized) workspace-relative path, with
+/// the `ecosystem_overrides` entry that decided it, if one did.
+///
+/// An exact path wins over a glob and a longer glob over a shorter one. A
+/// path overridden to `ignore` has no scanner. One overridden to an
+/// ecosystem is read in that ecosystem's format its name matches, else in
+/// its first format (`requirements.txt` for PyPI, `package.json` for npm).
+pub fn detect_overridden<'a>(
+    path: &str,
+    overrides: &'a BTreeMap<String, String>,
+) -> (Option<&'static dyn EcosystemScanner>, Option<&'a str>) {
+    let exact = overrides.get_key_value(path);
+    let by_glob = || {
+        overrides
+            .iter()
+            .filter(|(pattern, _)| globs::is_glob(pattern) && globs::matches(pattern, path))
+            .max_by_key(|(pattern, _)| pattern.len())
+    };
+    let Some((pattern, ecosystem)) = exact.or_else(by_glob) else {
+        return (detect(path), None);
+    };
+    let ecosystem = Ecosystem::named(ecosystem);
+    let formats = || REGISTRY.iter().copied().filter(|scanner| Some(scanner.ecosystem()) == ecosystem);
+    let scanner = formats().find(|scanner| scanner.detect(path)).or_else(|| formats().next());
+    (scanner, Some(pattern.as_str()))
+}
+
+/// Whether a (separator-normalized) path is a project manifest.
+pub fn is_manifest(path: &str) -> bool {
+    detect(path).is_some_and(|scanner| scanner.is_manifest())
+}
+
+/// Stream `reader` line by line without line endings, 1-based line numbers
+/// first. Lines are decoded lossily so a stray latin-1 byte only affects the
+/// line it appears on. Lines are cut off at [`MAX_LINE_BYTES`], and reading
+/// stops after [`MAX_LINES`].
+fn for_each_line(reader: &mut dyn BufRead, mut f: impl FnMut(usize, &str)) -> Extracted {
+    let mut extracted = Extracted::default();
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        buf.clear();
+        let truncated = match fsutil::read_line_capped(reader, &mut buf, MAX_LINE_BYTES) {
+            Ok(None) => break,
+            Ok(Some(truncated)) => truncated,
+            Err(e) => {
+                extracted.error = Some(e);
+                break;
+            }
+        };
+
+        line_number += 1;
+        if line_number > MAX_LINES {
+            extracted.exceeded = Some(format!("more than {} lines", MAX_LINES));
+            break;
+        }
+        if truncated {
+            extracted.truncated_lines += 1;
+        }
+        let decoded = String::from_utf8_lossy(&buf);
+        // Cutting a line off can split a character; that isn't the file's fault
+        if let (Cow::Owned(_), false) = (&decoded, truncated) {
+            extracted.lossy_lines += 1;
+        }
+        // Drop the line ending, including the '\r' of CRLF files
+        f(line_number, paths::strip_line_ending(&decoded));
+    }
+
+    extracted
+}
+
+/// Blank-line separated records of `Key: value` lines, as in the dpkg and
+/// apk package databases.
+#[derive(Default)]
+struct Stanza {
+    lines: Vec<(usize, String)>,
+}
+
+impl Stanza {
+    /// The line number, line and trimmed value of field `key`.
+    fn field(&self, key: &str) -> Option<(usize, &str, &str)> {
+        self.lines.iter().find_map(|(number, line)| {
+            let (k, value) = line.split_once(':')?;
+            (k == key).then(|| (*number, line.as_str(), value.trim()))
+        })
+    }
+}
+
+/// Stream `reader` record by record. A record cut off by a read error is
+/// not passed on.
+fn for_each_stanza(reader: &mut dyn BufRead, mut f: impl FnMut(&Stanza)) -> Extracted {
+    let mut stanza = Stanza::default();
+    let mut oversized = None;
+    let mut extracted = for_each_line(reader, |number, line| {
+        if oversized.is_some() {
+            return;
+        }
+        if !line.trim().is_empty() {
+            if stanza.lines.len() == MAX_STANZA_LINES {
+                let start = stanza.lines[0].0;
+                oversized = Some(format!("record at line {} has more than {} lines", start, MAX_STANZA_LINES));
+                return;
+            }
+            stanza.lines.push((number, line.to_string()));
+        } else if !stanza.lines.is_empty() {
+            f(&stanza);
+            stanza.lines.clear();
+        }
+    });
+    extracted.exceeded = extracted.exceeded.or(oversized);
+    if extracted.error.is_none() && extracted.exceeded.is_none() && !stanza.lines.is_empty() {
+        f(&stanza);
+    }
+    extracted
+}
+
+fn column_of(line: &str, name: &str) -> Option<usize> {
+    line.find(name).map(|i| line[..i].chars().count() + 1)
+}
+
+/// Parse a pinned `name==version` line, as found in requirements.txt.
+///
+/// Simple parsing (would use proper parsers in a real implementation):
+/// ranges, extras and markers aren't understood.
+fn parse_pinned_line(line: &str) -> Option<(String, String)> {
+    let line = line.trim();
+
+    if line.contains("==") {
+        let parts: Vec<&str> = line.split("==").collect();
+        if parts.len() == 2 {
+            return Some((parts[0].to_string(), parts[1].to_string()));
+        }
+    }
+
+    None
+}
+
+/// Split a version requirement into its operator prefix and version,
+/// e.g. `^4.17.15` into (`^`, `4.17.15`).
+pub fn split_range_operator(requirement: &str) -> (&str, &str) {
+    let version_start = requirement
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(requirement.len());
+    requirement.split_at(version_start)
+}
+
+fn strip_range_operator(requirement: &str) -> &str {
+    split_range_operator(requirement).1
+}
+
+/// Extract the dependencies declared in in-memory `content`.
+pub fn extract_str(scanner: &dyn EcosystemScanner, file: &str, content: &str) -> Vec<Dependency> {
+    scanner.extract(file, &mut content.as_bytes()).dependencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_format_is_detected_by_exactly_one_scanner() {
+        for path in [
+            "requirements.txt",
+            "web/package.json",
+            "web/yarn.lock",
+            "Cargo.toml",
+            "svc/go.mod",
+            "svc/go.sum",
+            "image/rootfs/var/lib/dpkg/status",
+            "image/rootfs/lib/apk/db/installed",
+        ] {
+            assert_eq!(REGISTRY.iter().filter(|s| s.detect(path)).count(), 1, "{}", path);
+        }
+        assert!(detect("README.md").is_none());
+    }
+
+    #[test]
+    fn long_lines_are_cut_off_and_oversized_files_exceed_limits() {
+        let long = format!("django==2.{}\nrequests==2.25.0\n", "2".repeat(3 * MAX_LINE_BYTES));
+        let extracted = pypi::Requirements.extract("requirements.txt", &mut long.as_bytes());
+        let found: Vec<(usize, usize)> =
+            extracted.dependencies.iter().map(|d| (d.line, d.version.len())).collect();
+        assert_eq!(found, vec![(1, MAX_LINE_BYTES - "django==".len()), (2, "2.25.0".len())]);
+        assert_eq!((extracted.truncated_lines, extracted.lossy_lines), (1, 0));
+        assert_eq!(extracted.exceeded, None);
+
+        let many = "\n".repeat(MAX_LINES + 1);
+        let extracted = pypi::Requirements.extract("requirements.txt", &mut many.as_bytes());
+        assert_eq!(extracted.exceeded, Some(format!("more than {} lines", MAX_LINES)));
+
+        let record = format!("Package: big\n{}", "Description: x\n".repeat(MAX_STANZA_LINES));
+        let extracted = dpkg::DpkgStatus.extract("var/lib/dpkg/status", &mut record.as_bytes());
+        assert!(extracted.exceeded.unwrap().starts_with("record at line 1 has more than"));
+        assert!(extracted.dependencies.is_empty());
+    }
+
+    /// Fuzzing campaign: random mutations of well-formed files through every
+    /// parser, which must neither panic nor hang. Run with
+    /// `cargo test --release -- --ignored --nocapture parsers_survive_mutated_input`.
+    #[test]
+    #[ignore]
+    fn parsers_survive_mutated_input() {
+        const ROUNDS: usize = 200_000;
+        let seeds: &[(&str, &str)] = &[
+            ("requirements.txt", "django==2.2.0\n# c\nrequests==2.25.0\r\n"),
+            ("package.json", "{\n  \"dependencies\": {\n    \"@a/b\": \"^1.2.3\",\n    \"c\": \"2\"\n  }\n}\n"),
+            ("yarn.lock", "\"@a/b@^1.0.0\", c@1:\n  version \"1.2.0\"\n  resolved \"https://r/x.tgz\"\n"),
+            ("Cargo.toml", "[dependencies]\nserde = \"1.0\"\nx = { version = \"0.1\", features = [] }\n"),
+            ("go.mod", "module m\nrequire (\n\tgolang.org/x/net v0.1.0 // indirect\n)\nreplace a => b v1\n"),
+            ("go.sum", "golang.org/x/net v0.1.0 h1:abc=\ngolang.org/x/net v0.1.0/go.mod h1:def=\n"),
+            (
+                "var/lib/dpkg/status",
+                "Package: a\nStatus: install ok installed\nVersion: 1:2.3-4\nArchitecture: amd64\n\n",
+            ),
+            ("lib/apk/db/installed", "P:musl\nV:1.2.4-r2\nA:x86_64\n\n"),
+            ("main.go", "package m\nimport (\n\t\"fmt\" /* x */\n\tn \"net/http\"\n)\n"),
+        ];
+        let alphabet = b"\n\r\t \"'=:@,{}[]()#/*^~<>v0123456789.-abc\xc3\xa9\xff";
+
+        // xorshift64*, seeded so failures replay
+        let seed = std::env::var("FUZZ_SEED").ok().and_then(|s| s.parse().ok());
+        let mut state: u64 = seed.filter(|&s| s != 0).unwrap_or(0x9e37_79b9_7f4a_7c15);
+        let mut next = move || {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+            state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        };
+        for round in 0..ROUNDS {
+            let (file, seed) = seeds[next() as usize % seeds.len()];
+            let mut input = seed.as_bytes().to_vec();
+            for _ in 0..1 + next() % 8 {
+                let at = next() as usize % (input.len() + 1);
+                match next() % 4 {
+                    0 => input.insert(at, alphabet[next() as usize % alphabet.len()]),
+                    1 if at < input.len() => {
+                        input.remove(at);
+                    }
+                    2 => {
+                        let copy = input[at..].iter().take(next() as usize % 32).copied().collect::<Vec<u8>>();
+                        input.splice(at..at, copy);
+                    }
+                    _ => input.truncate(at),
+                }
+            }
+            let result = std::panic::catch_unwind(|| match detect(file) {
+                Some(scanner) => scanner.extract(file, &mut input.as_slice()).dependencies.len(),
+                None => go_imports(&String::from_utf8_lossy(&input)).len(),
+            });
+            assert!(result.is_ok(), "round {}: {} panicked on {:?}", round, file, String::from_utf8_lossy(&input));
+        }
+    }
+}
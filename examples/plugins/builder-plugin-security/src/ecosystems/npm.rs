@@ -0,0 +1,219 @@
+use std::io::BufRead;
+
+use super::{column_of, for_each_line, strip_range_operator, Dependency, Ecosystem, EcosystemScanner, Extracted, Scope};
+
+/// npm `package.json`.
+pub struct PackageJson;
+
+/// Yarn v1 `yarn.lock`.
+pub struct YarnLock;
+
+/// Dependency objects in package.json whose entries are packages.
+const DEPENDENCY_KEYS: &[(&str, Scope)] = &[
+    ("dependencies", Scope::Runtime),
+    ("devDependencies", Scope::Development),
+    ("optionalDependencies", Scope::Optional),
+    ("peerDependencies", Scope::Peer),
+];
+
+impl EcosystemScanner for PackageJson {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Npm
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("package.json")
+    }
+
+    /// Assumes the conventional one entry per line layout that npm itself
+    /// writes. Versions are returned without their range operator
+    /// (`^4.17.15` yields `4.17.15`), i.e. the lowest version the range
+    /// admits.
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        // Scope of the dependency object we're in
+        let mut scope = None;
+        let mut extracted = for_each_line(reader, |number, line| {
+            if let Some((name, range)) = parse_package_json_line(line, &mut scope) {
+                let scope = scope.unwrap_or(Scope::Runtime);
+                let entry = (name, strip_range_operator(&range).to_string());
+                deps.push(Dependency::new(self.ecosystem(), file, entry, scope, number, line).declared_as(&range));
+            }
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+
+    fn rewritable(&self) -> bool {
+        true
+    }
+}
+
+/// The name and version range of a package.json entry.
+fn parse_package_json_line(line: &str, scope: &mut Option<Scope>) -> Option<(String, String)> {
+    let trimmed = line.trim();
+
+    if let Some(key) = trimmed.strip_suffix('{').map(str::trim).and_then(|k| k.strip_suffix(':')) {
+        let key = key.trim().trim_matches('"');
+        *scope = DEPENDENCY_KEYS
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, scope)| *scope);
+        return None;
+    }
+    if trimmed.starts_with('}') {
+        *scope = None;
+        return None;
+    }
+    scope.as_ref()?;
+
+    let (name, value) = trimmed.trim_end_matches(',').split_once(':')?;
+    let name = name.trim().trim_matches('"');
+    let range = value.trim().trim_matches('"');
+    let version = strip_range_operator(range);
+
+    if name.is_empty() || !version.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((name.to_string(), range.to_string()))
+}
+
+impl EcosystemScanner for YarnLock {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::Npm
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("yarn.lock")
+    }
+
+    /// Entries span several lines: a header naming the package
+    /// (`lodash@^4.17.15, lodash@^4.17.19:`) followed by an indented
+    /// `version "4.17.21"` line and the `resolved` tarball URL.
+    /// Dependencies are located at their header.
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        // Package named by the last entry header, with the header's line
+        // and column
+        let mut current = None;
+        let mut header = (0, None);
+        let mut extracted = for_each_line(reader, |number, line| {
+            let parsed = parse_yarn_lock_line(line, &mut current);
+            // Only entry headers are unindented (besides comments)
+            let trimmed = line.trim();
+            if !line.starts_with(' ') && !trimmed.is_empty() && !trimmed.starts_with('#') {
+                if let Some(name) = &current {
+                    header = (number, column_of(line, name));
+                }
+            }
+            // `resolved` follows the `version` line of its entry
+            if let Some(url) = line.trim().strip_prefix("resolved ") {
+                if let Some(dep) = deps.last_mut().filter(|dep: &&mut Dependency| dep.line == header.0) {
+                    dep.resolved = Some(url.trim().trim_matches('"').to_string());
+                }
+            }
+            if let Some((name, version)) = parsed {
+                deps.push(Dependency {
+                    name,
+                    version,
+                    ecosystem: self.ecosystem(),
+                    scope: Scope::Runtime,
+                    file: file.to_string(),
+                    line: header.0,
+                    col: header.1,
+                    release: None,
+                    arch: None,
+                    resolved: None,
+                    project: None,
+                    registry: None,
+                    constraint: None,
+                });
+            }
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+
+    fn records_resolution(&self) -> bool {
+        true
+    }
+}
+
+/// The header's package name is carried in `current` until its version
+/// line is seen.
+fn parse_yarn_lock_line(line: &str, current: &mut Option<String>) -> Option<(String, String)> {
+    let trimmed = line.trim();
+
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+
+    if !line.starts_with(' ') && trimmed.ends_with(':') {
+        // Header: take the first specifier and strip the version range.
+        // Scoped packages start with '@', so search for the separator after it.
+        let spec = trimmed.trim_end_matches(':').split(',').next()?.trim().trim_matches('"');
+        let name = match spec.get(1..).and_then(|rest| rest.find('@')) {
+            Some(at) => &spec[..at + 1],
+            None => spec,
+        };
+        *current = Some(name.to_string());
+        return None;
+    }
+
+    let version = trimmed.strip_prefix("version ")?.trim().trim_matches('"');
+    current.take().map(|name| (name, version.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::extract_str;
+
+    fn locations(scanner: &dyn EcosystemScanner, content: &str) -> Vec<(String, String, usize, Option<usize>, Scope)> {
+        extract_str(scanner, "fixture", content)
+            .into_iter()
+            .map(|d| (d.name, d.version, d.line, d.col, d.scope))
+            .collect()
+    }
+
+    #[test]
+    fn package_json_locations_and_scopes() {
+        let content = r#"{
+  "name": "web",
+  "dependencies": {
+    "express": "4.16.0",
+    "lodash": "^4.17.15"
+  },
+  "devDependencies": {
+    "jest": "~29.0.0"
+  }
+}
+"#;
+        assert_eq!(
+            locations(&PackageJson, content),
+            vec![
+                ("express".into(), "4.16.0".into(), 4, Some(6), Scope::Runtime),
+                ("lodash".into(), "4.17.15".into(), 5, Some(6), Scope::Runtime),
+                ("jest".into(), "29.0.0".into(), 8, Some(6), Scope::Development),
+            ]
+        );
+    }
+
+    #[test]
+    fn yarn_lock_points_at_entry_header() {
+        let content = "# yarn lockfile v1\n\n\"@babel/core@^7.0.0\":\n  version \"7.1.0\"\n\nlodash@^4.17.15, lodash@^4.17.19:\n  version \"4.17.15\"\n  resolved \"https://registry.yarnpkg.com/lodash/-/lodash-4.17.15.tgz#abc\"\n";
+        assert_eq!(
+            locations(&YarnLock, content),
+            vec![
+                ("@babel/core".into(), "7.1.0".into(), 3, Some(2), Scope::Runtime),
+                ("lodash".into(), "4.17.15".into(), 6, Some(1), Scope::Runtime),
+            ]
+        );
+        let resolved: Vec<Option<String>> =
+            extract_str(&YarnLock, "fixture", content).into_iter().map(|d| d.resolved).collect();
+        assert_eq!(
+            resolved,
+            vec![None, Some("https://registry.yarnpkg.com/lodash/-/lodash-4.17.15.tgz#abc".to_string())]
+        );
+    }
+}
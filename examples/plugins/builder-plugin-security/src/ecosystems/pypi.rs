@@ -0,0 +1,230 @@
+use std::cmp::Ordering;
+use std::io::BufRead;
+
+use super::{for_each_line, parse_pinned_line, Dependency, Ecosystem, EcosystemScanner, Extracted, Scope};
+
+/// Python `requirements.txt`.
+pub struct Requirements;
+
+impl EcosystemScanner for Requirements {
+    fn ecosystem(&self) -> Ecosystem {
+        Ecosystem::PyPI
+    }
+
+    fn detect(&self, path: &str) -> bool {
+        path.ends_with("requirements.txt")
+    }
+
+    fn extract(&self, file: &str, reader: &mut dyn BufRead) -> Extracted {
+        let mut deps = Vec::new();
+        let mut extracted = for_each_line(reader, |number, line| {
+            if let Some(pinned) = parse_pinned_line(line) {
+                let constraint = format!("=={}", pinned.1);
+                let dep = Dependency::new(self.ecosystem(), file, pinned, Scope::Runtime, number, line);
+                deps.push(dep.declared_as(&constraint));
+            }
+        });
+        extracted.dependencies = deps;
+        extracted
+    }
+
+    fn rewritable(&self) -> bool {
+        true
+    }
+}
+
+/// Compare Python versions as PEP 440 orders them: by epoch, release (with
+/// trailing zeros ignored), then a dev release before its prereleases
+/// (`a`, `b`, `rc`), those before the release, and post releases after
+/// it, each of them a dev release of its own before it; local versions
+/// (`+ubuntu1`) after the public one. Spellings PEP 440 normalizes
+/// (`1.0-RC.1`, `1.0-1`, `v1.0`) compare as their normal form. Versions
+/// that aren't PEP 440 fall back to [`crate::remediate::compare_versions`].
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (Pep440::parse(a), Pep440::parse(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => crate::remediate::compare_versions(a, b),
+    }
+}
+
+/// A part of a version that may be missing, where missing sorts before or
+/// after any value depending on the part.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Part<T> {
+    Before,
+    Is(T),
+    After,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum LocalSegment {
+    Text(String),
+    Number(u64),
+}
+
+/// A PEP 440 version as its sort key; field order is precedence.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Pep440 {
+    epoch: u64,
+    release: Vec<u64>,
+    /// The kind (0 `a`, 1 `b`, 2 `rc`) and number.
+    pre: Part<(u8, u64)>,
+    post: Part<u64>,
+    dev: Part<u64>,
+    local: Vec<LocalSegment>,
+}
+
+impl Pep440 {
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().to_ascii_lowercase();
+        let version = version.strip_prefix('v').unwrap_or(&version);
+        let (public, local) = match version.split_once('+') {
+            Some((public, local)) => (public, Some(local)),
+            None => (version, None),
+        };
+        let (epoch, rest) = match public.split_once('!') {
+            Some((epoch, rest)) => (epoch.parse().ok()?, rest),
+            None => (0, public),
+        };
+
+        let (first, mut rest) = number(rest)?;
+        let mut release = vec![first];
+        while let Some((n, after)) = rest.strip_prefix('.').and_then(number) {
+            release.push(n);
+            rest = after;
+        }
+        while release.len() > 1 && release.last() == Some(&0) {
+            release.pop();
+        }
+
+        const PRE: &[(&str, u8)] =
+            &[("alpha", 0), ("a", 0), ("beta", 1), ("b", 1), ("preview", 2), ("pre", 2), ("rc", 2), ("c", 2)];
+        let pre = tag(&mut rest, PRE);
+        let post = match rest.strip_prefix('-').and_then(number) {
+            // `1.0-1` is `1.0.post1`
+            Some((n, after)) => {
+                rest = after;
+                Some(n)
+            }
+            None => tag(&mut rest, &[("post", ()), ("rev", ()), ("r", ())]).map(|((), n)| n),
+        };
+        let dev = tag(&mut rest, &[("dev", ())]).map(|((), n)| n);
+        if !rest.is_empty() {
+            return None;
+        }
+
+        let local = match local {
+            Some(local) => {
+                let segments = local.split(['.', '-', '_']);
+                let segment = |s: &str| match s.parse() {
+                    Ok(n) => Some(LocalSegment::Number(n)),
+                    Err(_) if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                        Some(LocalSegment::Text(s.to_string()))
+                    }
+                    Err(_) => None,
+                };
+                segments.map(segment).collect::<Option<Vec<_>>>()?
+            }
+            None => Vec::new(),
+        };
+
+        Some(Pep440 {
+            epoch,
+            release,
+            // A dev release of the release itself comes before its prereleases
+            pre: match (pre, post, dev) {
+                (Some(pre), ..) => Part::Is(pre),
+                (None, None, Some(_)) => Part::Before,
+                (None, ..) => Part::After,
+            },
+            post: post.map_or(Part::Before, Part::Is),
+            dev: dev.map_or(Part::After, Part::Is),
+            local,
+        })
+    }
+}
+
+/// The number `version` starts with, and the rest.
+fn number(version: &str) -> Option<(u64, &str)> {
+    let digits = version.find(|c: char| !c.is_ascii_digit()).unwrap_or(version.len());
+    (digits > 0).then(|| (version[..digits].parse().unwrap_or(u64::MAX), &version[digits..]))
+}
+
+/// Take a tag among `names` off the start of `rest`, with its optional
+/// separators (`.`, `-` or `_`) and number (`0` when there is none).
+fn tag<T: Copy>(rest: &mut &str, names: &[(&str, T)]) -> Option<(T, u64)> {
+    let separated = rest.strip_prefix(['.', '-', '_']).unwrap_or(rest);
+    let (value, after) = names
+        .iter()
+        .find_map(|(name, value)| separated.strip_prefix(name).map(|after| (*value, after)))?;
+    let (n, after) = number(after.strip_prefix(['.', '-', '_']).unwrap_or(after)).unwrap_or((0, after));
+    *rest = after;
+    Some((value, n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems::extract_str;
+
+    #[test]
+    fn requirements_txt_locations() {
+        let deps = extract_str(&Requirements, "fixture", "# web\ndjango==2.2.0\n\n  requests==2.25.0\r\nflask>=1.0\n");
+        let locations: Vec<_> = deps.iter().map(|d| (d.name.as_str(), d.version.as_str(), d.line, d.col)).collect();
+        assert_eq!(
+            locations,
+            vec![("django", "2.2.0", 2, Some(1)), ("requests", "2.25.0", 4, Some(3))]
+        );
+        assert_eq!(deps[0].ecosystem, Ecosystem::PyPI);
+        assert_eq!(deps[0].scope, Scope::Runtime);
+        assert_eq!(deps[0].file, "fixture");
+    }
+
+    #[test]
+    fn versions_compare_like_pep_440() {
+        // PEP 440's "Summary of permitted suffixes and relative ordering"
+        let order = [
+            "1.dev0",
+            "1.0.dev456",
+            "1.0a1",
+            "1.0a2.dev456",
+            "1.0a12.dev456",
+            "1.0a12",
+            "1.0b1.dev456",
+            "1.0b2",
+            "1.0b2.post345.dev456",
+            "1.0b2.post345",
+            "1.0rc1.dev456",
+            "1.0rc1",
+            "1.0",
+            "1.0+abc.5",
+            "1.0+abc.7",
+            "1.0+5",
+            "1.0.post456.dev34",
+            "1.0.post456",
+            "1.0.15",
+            "1.1.dev1",
+            "2.0rc1",
+            "2.0",
+            "1!0.1",
+        ];
+        for pair in order.windows(2) {
+            assert_eq!(compare_versions(pair[0], pair[1]), Ordering::Less, "{} < {}", pair[0], pair[1]);
+            assert_eq!(compare_versions(pair[1], pair[0]), Ordering::Greater, "{} > {}", pair[1], pair[0]);
+        }
+        // Spellings PEP 440 normalizes
+        for (a, b) in [
+            ("1.0", "1.0.0"),
+            ("1.0RC1", "1.0rc1"),
+            ("1.0-rc.1", "1.0rc1"),
+            ("1.0c1", "1.0rc1"),
+            ("1.0alpha", "1.0a0"),
+            ("1.0-1", "1.0.post1"),
+            ("1.0-r1", "1.0.post1"),
+            ("1.0.dev", "1.0.dev0"),
+            ("v1.0", "1.0"),
+        ] {
+            assert_eq!(compare_versions(a, b), Ordering::Equal, "{} = {}", a, b);
+        }
+    }
+}
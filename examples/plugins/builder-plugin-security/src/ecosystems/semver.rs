@@ -0,0 +1,96 @@
+use std::cmp::Ordering;
+
+/// Order two semantic versions by precedence (semver 2.0.0, section 11):
+/// numerically by major, minor and patch, a pre-release before its
+/// release, pre-release identifiers numerically or by ASCII, and build
+/// metadata ignored. A leading `v` is allowed. `None` when either isn't a
+/// semantic version.
+pub fn compare(a: &str, b: &str) -> Option<Ordering> {
+    let (a, b) = (parse(a)?, parse(b)?);
+    let ordering = a.0.cmp(&b.0).then_with(|| match (a.1.is_empty(), b.1.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => {
+            let left = a.1.iter().map(|id| prerelease_key(id));
+            left.cmp(b.1.iter().map(|id| prerelease_key(id)))
+        }
+    });
+    Some(ordering)
+}
+
+/// Numeric identifiers sort before alphanumeric ones.
+fn prerelease_key(identifier: &str) -> (bool, u64, &str) {
+    match identifier.parse::<u64>() {
+        Ok(n) => (false, n, ""),
+        Err(_) => (true, 0, identifier),
+    }
+}
+
+fn parse(version: &str) -> Option<([u64; 3], Vec<&str>)> {
+    let version = version.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    let version = version.split_once('+').map_or(version, |(version, _)| version);
+    let (core, prerelease) = match version.split_once('-') {
+        Some((core, prerelease)) => (core, prerelease.split('.').collect()),
+        None => (version, Vec::new()),
+    };
+    if prerelease.iter().any(|id: &&str| id.is_empty()) {
+        return None;
+    }
+    let mut parts = core.split('.');
+    let mut numbers = [0; 3];
+    for number in &mut numbers {
+        let part = parts.next()?;
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        *number = part.parse().ok()?;
+    }
+    parts.next().is_none().then_some((numbers, prerelease))
+}
+
+/// Order two versions of npm, Cargo or Go packages by semver precedence;
+/// versions that aren't semantic versions (`1.2`, `1.2.3.4`) fall back to
+/// [`crate::remediate::compare_versions`].
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    compare(a, b).unwrap_or_else(|| crate::remediate::compare_versions(a, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_compare_by_semver_precedence() {
+        // The precedence example of semver 2.0.0, section 11
+        let order = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "2.0.0-rc.1",
+            "2.0.0",
+            "2.1.0",
+            "2.1.1",
+        ];
+        for pair in order.windows(2) {
+            assert_eq!(compare(pair[0], pair[1]), Some(Ordering::Less), "{:?}", pair);
+            assert_eq!(compare_versions(pair[1], pair[0]), Ordering::Greater, "{:?}", pair);
+        }
+        assert_eq!(compare("1.10.0", "1.9.3"), Some(Ordering::Greater));
+        assert_eq!(compare("v1.0.0+build.5", "1.0.0"), Some(Ordering::Equal));
+        assert_eq!(compare("1.0", "1.0.0"), None);
+        assert_eq!(compare("1.0.0-", "1.0.0"), None);
+        // Go pseudo-versions are prereleases of the next patch
+        let pseudo = "v0.0.0-20210101000000-abcdef123456";
+        assert_eq!(compare_versions(pseudo, "v0.0.1"), Ordering::Less);
+        assert_eq!(compare_versions("v1.2.4-0.20210101000000-abcdef123456", "v1.2.4"), Ordering::Less);
+        // Not semver: compared as dotted versions
+        assert_eq!(compare_versions("1.2.3.4", "1.2.3"), Ordering::Greater);
+    }
+}
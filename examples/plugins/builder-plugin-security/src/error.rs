@@ -0,0 +1,203 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io;
+
+/// Everything that can go wrong in a request, and how it is reported.
+///
+/// Errors that stop a request from producing a result become JSON-RPC
+/// errors; the others are listed in the hook result's `errors` next to the
+/// findings. Either way the wire code, `kind` and retry advice come from
+/// here.
+#[derive(Debug, thiserror::Error)]
+pub enum ScanError {
+    #[error("parse error: {0}")]
+    ParseError(String),
+    #[error("method not found: {0}")]
+    MethodNotFound(String),
+    #[error("{0}")]
+    ConfigInvalid(String),
+    #[error("advisory source unavailable: {0}")]
+    DbUnreachable(String),
+    #[error("security policy violated: {}", .0.join("; "))]
+    PolicyViolated(Vec<String>),
+    #[error("workspace {path} is unreadable: {source}")]
+    WorkspaceUnreadable {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("{phase} phase stopped: {limit} exceeded ({completed})")]
+    ScanTimedOut {
+        phase: &'static str,
+        limit: &'static str,
+        completed: String,
+    },
+    #[error("scan degraded and on_error is \"closed\": {}", .0.join("; "))]
+    FailedClosed(Vec<String>),
+    #[error("Builder {host} is older than {required}, the oldest this plugin supports (strict_version_check)")]
+    VersionUnsupported { host: String, required: &'static str },
+    #[error("undeclared access refused (strict_permissions): {}", .0.join("; "))]
+    PermissionDenied(Vec<String>),
+    #[error("attestation signing failed: {0}")]
+    SigningFailed(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+/// An entry of a hook result's `errors`.
+#[derive(Serialize, JsonSchema, Debug)]
+pub struct ResultEntry {
+    pub kind: &'static str,
+    pub detail: String,
+    pub retryable: bool,
+    pub code: i32,
+}
+
+impl ScanError {
+    /// JSON-RPC error code. Plugin-domain errors use -32001..-32009 from the
+    /// range JSON-RPC reserves for implementation-defined server errors.
+    pub fn code(&self) -> i32 {
+        match self {
+            ScanError::ParseError(_) => -32700,
+            ScanError::MethodNotFound(_) => -32601,
+            ScanError::ConfigInvalid(_) => -32001,
+            ScanError::DbUnreachable(_) => -32002,
+            ScanError::PolicyViolated(_) => -32003,
+            ScanError::WorkspaceUnreadable { .. } => -32004,
+            ScanError::ScanTimedOut { .. } => -32005,
+            ScanError::FailedClosed(_) => -32006,
+            ScanError::VersionUnsupported { .. } => -32007,
+            ScanError::PermissionDenied(_) => -32008,
+            ScanError::SigningFailed(_) => -32009,
+            ScanError::Internal(_) => -32603,
+        }
+    }
+
+    /// Machine-readable name of the error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScanError::ParseError(_) => "parse_error",
+            ScanError::MethodNotFound(_) => "method_not_found",
+            ScanError::ConfigInvalid(_) => "config_invalid",
+            ScanError::DbUnreachable(_) => "db_unreachable",
+            ScanError::PolicyViolated(_) => "policy_violated",
+            ScanError::WorkspaceUnreadable { .. } => "workspace_unreadable",
+            ScanError::ScanTimedOut { .. } => "scan_timed_out",
+            ScanError::FailedClosed(_) => "failed_closed",
+            ScanError::VersionUnsupported { .. } => "version_unsupported",
+            ScanError::PermissionDenied(_) => "permission_denied",
+            ScanError::SigningFailed(_) => "signing_failed",
+            ScanError::Internal(_) => "internal",
+        }
+    }
+
+    /// Whether repeating the same request may succeed: network and time
+    /// limits are transient, everything else needs a change first.
+    pub fn retryable(&self) -> bool {
+        matches!(
+            self,
+            ScanError::DbUnreachable(_) | ScanError::ScanTimedOut { .. } | ScanError::FailedClosed(_)
+        )
+    }
+
+    /// Whether the error is in the scan's surroundings rather than in what
+    /// it found, so the findings may be incomplete; `on_error` decides what
+    /// comes of these.
+    pub fn is_infrastructure(&self) -> bool {
+        matches!(
+            self,
+            ScanError::DbUnreachable(_) | ScanError::WorkspaceUnreadable { .. } | ScanError::ScanTimedOut { .. }
+        )
+    }
+
+    /// Short summary used as the JSON-RPC `message`.
+    fn summary(&self) -> &'static str {
+        match self {
+            ScanError::ParseError(_) => "Parse error",
+            ScanError::MethodNotFound(_) => "Method not found",
+            ScanError::ConfigInvalid(_) => "Invalid configuration",
+            ScanError::DbUnreachable(_) => "Advisory database unreachable",
+            ScanError::PolicyViolated(_) => "Security policy violated",
+            ScanError::WorkspaceUnreadable { .. } => "Workspace unreadable",
+            ScanError::ScanTimedOut { .. } => "Scan timed out",
+            ScanError::FailedClosed(_) => "Scan failed closed",
+            ScanError::VersionUnsupported { .. } => "Builder version unsupported",
+            ScanError::PermissionDenied(_) => "Undeclared access refused",
+            ScanError::SigningFailed(_) => "Attestation signing failed",
+            ScanError::Internal(_) => "Internal error",
+        }
+    }
+
+    /// `kind`, `detail` and `retryable`, as in a JSON-RPC error's `data`.
+    pub fn data(&self) -> Value {
+        json!({
+            "kind": self.kind(),
+            "detail": self.to_string(),
+            "retryable": self.retryable(),
+        })
+    }
+
+    /// The error in a hook result's `errors`: `data` plus the code.
+    pub fn to_result_entry(&self) -> ResultEntry {
+        ResultEntry {
+            kind: self.kind(),
+            detail: self.to_string(),
+            retryable: self.retryable(),
+            code: self.code(),
+        }
+    }
+
+    /// A JSON-RPC error response to request `id`, `null` when the request
+    /// had none.
+    pub fn to_response(&self, id: &Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {
+                "code": self.code(),
+                "message": self.summary(),
+                "data": self.data()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_carry_code_kind_detail_and_retry_advice() {
+        let error = ScanError::WorkspaceUnreadable {
+            path: "/ws".to_string(),
+            source: io::Error::new(io::ErrorKind::NotFound, "No such file or directory"),
+        };
+        assert_eq!(
+            error.to_response(&json!(7)),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 7,
+                "error": {
+                    "code": -32004,
+                    "message": "Workspace unreadable",
+                    "data": {
+                        "kind": "workspace_unreadable",
+                        "detail": "workspace /ws is unreadable: No such file or directory",
+                        "retryable": false
+                    }
+                }
+            })
+        );
+
+        let timeout = ScanError::ScanTimedOut {
+            phase: "extract",
+            limit: "scan_timeout_secs",
+            completed: "1 of 3 files read".to_string(),
+        };
+        let entry = timeout.to_result_entry();
+        assert_eq!(entry.code, -32005);
+        assert!(entry.retryable);
+        assert_eq!(entry.detail, "extract phase stopped: scan_timeout_secs exceeded (1 of 3 files read)");
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+
+use crate::attestation;
+use crate::cache;
+use crate::config::ScanConfig;
+
+/// Notification Builder sends about its own events, with params
+/// `{event, target, data}`. It is never answered.
+pub const NOTIFY_METHOD: &str = "event.notify";
+
+/// An artifact of a target was published: `data` names it as `artifact`,
+/// or several as `artifacts`.
+pub const ARTIFACT_PUBLISHED: &str = "artifact.published";
+
+/// The events the plugin reacts to, as `plugin.info` advertises them; any
+/// other is ignored.
+pub const SUBSCRIPTIONS: &[&str] = &[ARTIFACT_PUBLISHED];
+
+/// A target's artifacts published, with the security reports about them.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Published {
+    pub target: String,
+    pub artifacts: Vec<String>,
+    /// The target's newest report in its history and the attestation of
+    /// the last post_hook, those there were when it was published.
+    pub reports: Vec<String>,
+}
+
+/// What the events since the last post_hook left for the next one.
+#[derive(Default, Debug)]
+pub struct Pending {
+    published: Vec<Published>,
+}
+
+impl Pending {
+    /// Take in an `event.notify` notification's params. `workspace_root`
+    /// and `config` are those the params resolve to, as for a hook.
+    pub fn notify(&mut self, params: Option<&Value>, workspace_root: &Path, config: &ScanConfig) {
+        let Some(params) = params.filter(|p| p.get("event").and_then(Value::as_str) == Some(ARTIFACT_PUBLISHED)) else {
+            return;
+        };
+        let target = match params.get("target") {
+            Some(Value::String(name)) => Some(name.as_str()),
+            Some(target) => target.get("name").and_then(Value::as_str),
+            None => None,
+        };
+        let data = params.get("data");
+        let one = data.and_then(|d| d.get("artifact")).into_iter();
+        let many = data.and_then(|d| d.get("artifacts")).and_then(Value::as_array).into_iter().flatten();
+        let artifacts: Vec<String> = one.chain(many).filter_map(Value::as_str).map(str::to_string).collect();
+        let (Some(target), false) = (target, artifacts.is_empty()) else {
+            return;
+        };
+
+        let report = cache::latest_report(&cache::cache_root(workspace_root, &config.report_dir), target);
+        let statement = workspace_root.join(&config.report_dir).join(attestation::STATEMENT_FILE_NAME);
+        let reports = report.into_iter().chain(Some(statement).filter(|path| path.is_file()));
+        self.published.push(Published {
+            target: target.to_string(),
+            artifacts,
+            reports: reports.map(|path| path.display().to_string()).collect(),
+        });
+    }
+
+    /// Add what was published since the last post_hook to a post_hook's
+    /// response: a line per target in its `logs`, and `published`. Kept
+    /// for the next one when the response is an error.
+    pub fn flush(&mut self, response: &mut Value) {
+        let Some(result) = response.get_mut("result").and_then(Value::as_object_mut) else {
+            return;
+        };
+        if self.published.is_empty() {
+            return;
+        }
+        let published = std::mem::take(&mut self.published);
+        if let Some(logs) = result.get_mut("logs").and_then(Value::as_array_mut) {
+            let artifacts: usize = published.iter().map(|p| p.artifacts.len()).sum();
+            logs.push(json!(format!("\n  Published since the last post_hook ({} artifacts):", artifacts)));
+            for entry in &published {
+                let reports = match entry.reports.as_slice() {
+                    [] => "no security report".to_string(),
+                    reports => reports.join(", "),
+                };
+                logs.push(json!(format!("    {}: {} ({})", entry.target, entry.artifacts.join(", "), reports)));
+            }
+        }
+        result.insert("published".to_string(), json!(published));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn published_artifacts_are_flushed_into_the_next_post_hook_with_their_reports() {
+        let root = crate::testutil::scratch_dir("events");
+        let config = ScanConfig::default();
+        let cache_root = cache::cache_root(&root, &config.report_dir);
+        let at = std::time::UNIX_EPOCH + std::time::Duration::from_millis(1_000);
+        let archived = cache::archive_report(&cache_root, "//app", b"{}", crate::report::Compression::None, at);
+        let archived = archived.unwrap().display().to_string();
+
+        let mut pending = Pending::default();
+        let event = |event: &str, target: Value, data: Value| json!({"event": event, "target": target, "data": data});
+        pending.notify(Some(&event("build.started", json!("//app"), json!({}))), &root, &config);
+        pending.notify(Some(&event(ARTIFACT_PUBLISHED, json!("//app"), json!({}))), &root, &config);
+        let app = event(ARTIFACT_PUBLISHED, json!({"name": "//app"}), json!({"artifact": "dist/app.whl"}));
+        pending.notify(Some(&app), &root, &config);
+        let cli = event(ARTIFACT_PUBLISHED, json!("//cli"), json!({"artifacts": ["dist/cli", "dist/cli.sig"]}));
+        pending.notify(Some(&cli), &root, &config);
+
+        // An error keeps them for the next post_hook
+        let mut error = json!({"error": {"code": -32602}});
+        pending.flush(&mut error);
+        assert!(error.get("result").is_none());
+
+        let logs = ["[Security] Post-build security check complete"];
+        let mut response = json!({"result": {"success": true, "logs": logs}});
+        pending.flush(&mut response);
+        let result = &response["result"];
+        assert_eq!(
+            result["logs"],
+            json!([
+                "[Security] Post-build security check complete",
+                "\n  Published since the last post_hook (3 artifacts):",
+                format!("    //app: dist/app.whl ({})", archived),
+                "    //cli: dist/cli, dist/cli.sig (no security report)",
+            ])
+        );
+        let app = json!({"target": "//app", "artifacts": ["dist/app.whl"], "reports": [archived]});
+        assert_eq!(result["published"][0], app);
+
+        let mut next = json!({"result": {"success": true, "logs": []}});
+        pending.flush(&mut next);
+        assert_eq!(next, json!({"result": {"success": true, "logs": []}}));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
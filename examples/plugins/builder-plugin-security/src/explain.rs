@@ -0,0 +1,278 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::advisories::{Matcher, Provenance};
+use crate::config::{ScanConfig, CONFIG_FILE_NAME};
+use crate::report;
+use crate::writers;
+use crate::Vulnerability;
+
+const USAGE: &str = "usage: builder-plugin-security explain [--workspace DIR] [--report FILE] FINGERPRINT";
+
+/// Why `vuln` matched, in one line, from the first source that reported it:
+/// `Cargo.lock has openssl 0.10.45; RUSTSEC-2023-0044 affects >=0.10.0,
+/// <0.10.55`. `None` for the plugin's own checks, which have no provenance.
+pub fn match_reason(vuln: &Vulnerability) -> Option<String> {
+    let first = vuln.provenance.first()?;
+    let mut reason = format!("{} has {} {}", vuln.file.as_deref().unwrap_or("The target"), vuln.package, vuln.version);
+    if let Some(name) = &vuln.matched_as {
+        reason.push_str(&format!(", matched as {}", name));
+    }
+    reason.push_str(&format!("; {} {}", vuln.id, matched(first)));
+
+    let mut others: Vec<&str> = Vec::new();
+    for provenance in &vuln.provenance[1..] {
+        if provenance.source != first.source && !others.contains(&provenance.source.as_str()) {
+            others.push(&provenance.source);
+        }
+    }
+    if !others.is_empty() {
+        reason.push_str(&format!("; also reported by {}", others.join(", ")));
+    }
+    Some(reason)
+}
+
+/// What the advisory says about the version, as `provenance` matched it.
+fn matched(provenance: &Provenance) -> String {
+    match provenance.matcher {
+        Matcher::Exact => format!("lists {} as affected", provenance.range.trim_start_matches('=')),
+        Matcher::Range => format!("affects {}", provenance.range),
+        Matcher::Heuristic => {
+            let mut matched = format!("affects {} of a package with a similar name", provenance.range);
+            if let Some(confidence) = provenance.confidence {
+                matched.push_str(&format!(" (confidence {:.2})", confidence));
+            }
+            matched
+        }
+    }
+}
+
+fn matcher_name(matcher: Matcher) -> &'static str {
+    match matcher {
+        Matcher::Exact => "exact",
+        Matcher::Range => "range",
+        Matcher::Heuristic => "heuristic",
+    }
+}
+
+/// `builder-plugin-security explain`: print why one finding of the latest
+/// report matched, with every source that reported it. The finding is named
+/// by its fingerprint, or a prefix only it has.
+pub fn cli(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut workspace = PathBuf::from(".");
+    let mut report_path = None;
+    let mut fingerprint = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), fingerprint.is_none()) {
+            ("--workspace", _) => match args.next() {
+                Some(dir) => workspace = PathBuf::from(dir),
+                None => return usage(err),
+            },
+            ("--report", _) => match args.next() {
+                Some(file) => report_path = Some(PathBuf::from(file)),
+                None => return usage(err),
+            },
+            (value, true) if !value.starts_with('-') => fingerprint = Some(value.to_string()),
+            _ => return usage(err),
+        }
+    }
+    let Some(fingerprint) = fingerprint else {
+        return usage(err);
+    };
+
+    let report_path = match report_path {
+        Some(path) => path,
+        None => match ScanConfig::resolve(Some(&workspace.join(CONFIG_FILE_NAME)), None, None, std::env::vars()) {
+            Ok(config) => workspace.join(&config.report_dir).join(writers::JSON_REPORT_FILE_NAME),
+            Err(e) => {
+                let _ = writeln!(err, "explain: {}", e);
+                return 2;
+            }
+        },
+    };
+    match explain(&report_path, &fingerprint, out) {
+        Ok(()) => 0,
+        Err(e) => {
+            let _ = writeln!(err, "explain: {}", e);
+            1
+        }
+    }
+}
+
+fn usage(err: &mut dyn Write) -> i32 {
+    let _ = writeln!(err, "{}", USAGE);
+    2
+}
+
+fn explain(report_path: &Path, fingerprint: &str, out: &mut dyn Write) -> Result<(), String> {
+    let findings = report::load_report::<Vulnerability>(report_path)?;
+    let matching: Vec<&Vulnerability> = findings
+        .iter()
+        .filter(|vuln| !vuln.fingerprint.is_empty() && vuln.fingerprint.starts_with(fingerprint))
+        .collect();
+    let vuln = match matching[..] {
+        [vuln] => vuln,
+        [] => return Err(format!("no finding with fingerprint {} in {}", fingerprint, report_path.display())),
+        _ => {
+            return Err(format!(
+                "{} findings have fingerprints starting with {}; give more of it",
+                matching.len(),
+                fingerprint
+            ))
+        }
+    };
+    print(vuln, out).map_err(|e| e.to_string())
+}
+
+fn print(vuln: &Vulnerability, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "{} ({})", vuln.id, vuln.severity)?;
+    // Reports from before reasons were recorded
+    if let Some(reason) = vuln.match_reason.clone().or_else(|| match_reason(vuln)) {
+        writeln!(out, "  {}", reason)?;
+    }
+    writeln!(out, "  Fingerprint: {}", vuln.fingerprint)?;
+    writeln!(out, "  Package:     {} {}", vuln.package, vuln.version)?;
+    if let Some(name) = &vuln.matched_as {
+        writeln!(out, "  Matched as:  {}", name)?;
+    }
+    if let Some(file) = &vuln.file {
+        let at = match (vuln.line, vuln.column) {
+            (Some(line), Some(column)) => format!(":{}:{}", line, column),
+            (Some(line), None) => format!(":{}", line),
+            _ => String::new(),
+        };
+        writeln!(out, "  Declared in: {}{}", file, at)?;
+    }
+    if let Some(project) = &vuln.project {
+        writeln!(out, "  Project:     {}", project)?;
+    }
+    if let Some(registry) = &vuln.registry {
+        writeln!(out, "  Registry:    {}", registry)?;
+    }
+    if let Some(layer) = &vuln.layer {
+        writeln!(out, "  Layer:       {}", layer)?;
+    }
+    if !vuln.aliases.is_empty() {
+        writeln!(out, "  Aliases:     {}", vuln.aliases.join(", "))?;
+    }
+    writeln!(out, "  Fixed in:    {}", vuln.fixed_in.as_deref().unwrap_or("no fix available"))?;
+
+    if vuln.provenance.is_empty() {
+        writeln!(out, "  Reported by the plugin's own {} check", vuln.id)?;
+        return Ok(());
+    }
+    writeln!(out, "  Provenance:")?;
+    for (i, provenance) in vuln.provenance.iter().enumerate() {
+        let database = provenance.database.as_deref().map(|db| format!(" ({} database)", db)).unwrap_or_default();
+        writeln!(out, "    {}. {}{}, snapshot {}", i + 1, provenance.source, database, provenance.snapshot)?;
+        let mut how = format!("{} match: {}", matcher_name(provenance.matcher), provenance.range);
+        if let Some(confidence) = provenance.confidence {
+            how.push_str(&format!(", confidence {:.2}", confidence));
+        }
+        writeln!(out, "       {}", how)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn provenance(source: &str, matcher: Matcher, range: &str) -> Provenance {
+        Provenance {
+            source: source.to_string(),
+            database: Some("RUSTSEC".to_string()),
+            snapshot: format!("{}@0123456789abcdef", source),
+            matcher,
+            range: range.to_string(),
+            confidence: None,
+        }
+    }
+
+    fn openssl() -> Vulnerability {
+        let mut vuln: Vulnerability = serde_json::from_value(serde_json::json!({
+            "id": "RUSTSEC-2023-0044",
+            "severity": "HIGH",
+            "package": "openssl",
+            "version": "0.10.45",
+            "description": "",
+            "fixed_in": "0.10.55",
+            "file": "Cargo.lock",
+            "line": 12,
+            "fingerprint": "5d41402abc4b2a76b9719d911017c592",
+        }))
+        .unwrap();
+        vuln.provenance = vec![
+            provenance("osv-local", Matcher::Range, ">=0.10.0, <0.10.55"),
+            provenance("cargo-audit", Matcher::Exact, "=0.10.45"),
+        ];
+        vuln
+    }
+
+    #[test]
+    fn reasons_are_put_together_from_the_provenance() {
+        let mut vuln = openssl();
+        assert_eq!(
+            match_reason(&vuln).unwrap(),
+            "Cargo.lock has openssl 0.10.45; RUSTSEC-2023-0044 affects >=0.10.0, <0.10.55; also reported by cargo-audit"
+        );
+
+        vuln.provenance.reverse();
+        vuln.matched_as = Some("openssl-sys".to_string());
+        assert_eq!(
+            match_reason(&vuln).unwrap(),
+            "Cargo.lock has openssl 0.10.45, matched as openssl-sys; RUSTSEC-2023-0044 lists 0.10.45 as affected; \
+             also reported by osv-local"
+        );
+
+        vuln.provenance = vec![Provenance {
+            confidence: Some(0.75),
+            ..provenance("builtin-demo", Matcher::Heuristic, "=0.10.45")
+        }];
+        vuln.matched_as = None;
+        let reason = match_reason(&vuln).unwrap();
+        assert!(reason.ends_with("affects =0.10.45 of a package with a similar name (confidence 0.75)"), "{}", reason);
+
+        vuln.provenance.clear();
+        assert_eq!(match_reason(&vuln), None);
+    }
+
+    #[test]
+    fn a_finding_is_explained_by_a_prefix_of_its_fingerprint() {
+        let dir = crate::testutil::scratch_dir("explain");
+        let report = dir.join("security-report.json");
+        let mut other = openssl();
+        other.fingerprint = "5d4fffffffffffffffffffffffffffff".to_string();
+        fs::create_dir_all(&dir).unwrap();
+        let envelope = serde_json::json!({"vulnerabilities": [openssl(), other]});
+        fs::write(&report, envelope.to_string()).unwrap();
+
+        let run = |fingerprint: &str| {
+            let args = ["--report".to_string(), report.display().to_string(), fingerprint.to_string()];
+            let (mut out, mut err) = (Vec::new(), Vec::new());
+            let code = cli(&args, &mut out, &mut err);
+            (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+        };
+        let (code, out, _) = run("5d414");
+        assert_eq!(code, 0);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "RUSTSEC-2023-0044 (HIGH)");
+        assert!(lines[1].starts_with("  Cargo.lock has openssl 0.10.45; RUSTSEC-2023-0044 affects"), "{}", out);
+        assert!(out.contains("  Declared in: Cargo.lock:12\n"), "{}", out);
+        assert!(out.contains(
+            "    1. osv-local (RUSTSEC database), snapshot osv-local@0123456789abcdef\n       \
+             range match: >=0.10.0, <0.10.55\n"
+        ));
+        assert!(out.contains("    2. cargo-audit (RUSTSEC database), snapshot cargo-audit@0123456789abcdef\n"));
+
+        let (code, _, err) = run("5d4");
+        assert_eq!(code, 1);
+        assert!(err.contains("2 findings have fingerprints starting with 5d4"), "{}", err);
+        let (code, _, err) = run("beef");
+        assert_eq!((code, err.contains("no finding with fingerprint beef")), (1, true));
+        assert_eq!(cli(&[], &mut Vec::new(), &mut Vec::new()), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,242 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::advisories::LocalDb;
+use crate::cache;
+use crate::clock;
+use crate::config::{self, ScanConfig, CONFIG_FILE_NAME};
+use crate::snapshots;
+use crate::{Scanner, Severity, Vulnerability, Workspace};
+
+const USAGE: &str = "usage: builder-plugin-security --fast [--workspace DIR] [--fail-on SEVERITY] \
+                     [--max-db-age PERIOD] FILE...";
+
+/// Age past which the cached advisory database is too stale to decide a
+/// commit by.
+const DEFAULT_MAX_DB_AGE: &str = "7d";
+
+/// `builder-plugin-security --fast`: scan only the files given, as
+/// pre-commit passes the changed ones, against advisory data already
+/// cached, and print a line per finding. Exits 1 when a finding is at or
+/// above the threshold: `--fail-on`, else HIGH with `fail_on_high` and
+/// CRITICAL otherwise.
+///
+/// Nothing is fetched, sent or written besides the caches. Without a cached
+/// snapshot of `db_path`, or with one older than `--max-db-age`, it warns
+/// and exits 0: a commit isn't blocked on data it can't get.
+pub fn cli(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut workspace = PathBuf::from(".");
+    let mut fail_on = None;
+    let mut max_age = DEFAULT_MAX_DB_AGE.to_string();
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), arg.starts_with('-')) {
+            ("--workspace", _) => match args.next() {
+                Some(dir) => workspace = PathBuf::from(dir),
+                None => return usage(err),
+            },
+            ("--fail-on", _) => match args.next().map(|s| Severity::normalize(s)) {
+                Some(Severity::Unknown) | None => return usage(err),
+                Some(severity) => fail_on = Some(severity),
+            },
+            ("--max-db-age", _) => match args.next() {
+                Some(period) => max_age = period.clone(),
+                None => return usage(err),
+            },
+            (file, false) => files.push(file.to_string()),
+            _ => return usage(err),
+        }
+    }
+    let Some(max_age) = config::parse_period(&max_age) else {
+        return usage(err);
+    };
+
+    let mut config = match ScanConfig::resolve(Some(&workspace.join(CONFIG_FILE_NAME)), None, None, std::env::vars())
+    {
+        Ok(config) => config,
+        Err(e) => {
+            let _ = writeln!(err, "fast: {}", e);
+            return 2;
+        }
+    };
+    let fail_on = fail_on.unwrap_or(match config.fail_on_high {
+        true => Severity::High,
+        false => Severity::Critical,
+    });
+    if files.is_empty() {
+        return 0;
+    }
+
+    let db_cache = cache::cache_root(&workspace, &config.report_dir).join("db");
+    if let Err(warning) = pin_cached(&mut config, &db_cache, max_age, SystemTime::now()) {
+        let _ = writeln!(err, "fast: {}; not scanned", warning);
+        return 0;
+    }
+    quiet(&mut config);
+
+    let result = match Scanner::new(config).scan(&Workspace::new(&workspace, files)) {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = writeln!(err, "fast: {}", e);
+            return 2;
+        }
+    };
+    for vuln in &result.vulnerabilities {
+        let _ = writeln!(out, "{}", line(vuln));
+    }
+    match result.vulnerabilities.iter().any(|vuln| vuln.severity <= fail_on) {
+        true => 1,
+        false => 0,
+    }
+}
+
+fn usage(err: &mut dyn Write) -> i32 {
+    let _ = writeln!(err, "{}", USAGE);
+    2
+}
+
+/// Pin the scan to the cached snapshot of `db_path` used last (or the one
+/// `db_snapshot` names), so the database isn't read or indexed again. Fails
+/// with a warning when there is none or it is older than `max_age`.
+fn pin_cached(config: &mut ScanConfig, db_cache: &Path, max_age: Duration, now: SystemTime) -> Result<(), String> {
+    if config.db_path.is_none() && config.db_snapshot.is_none() {
+        return Ok(());
+    }
+    let dir = snapshots::snapshots_dir(db_cache);
+    let snapshot = match &config.db_snapshot {
+        Some(id) => snapshots::load(&dir, id).ok_or_else(|| format!("db_snapshot '{}' is not cached", id))?,
+        None => snapshots::list(&dir)
+            .into_iter()
+            .map(|(snapshot, _)| snapshot)
+            .find(|snapshot| snapshot.source == LocalDb::SOURCE)
+            .ok_or("no cached advisory database; a full scan caches one")?,
+    };
+    if let Some(at) = snapshot.fetched_at_unix {
+        let age = clock::unix_secs(now).saturating_sub(at);
+        if age > max_age.as_secs() {
+            return Err(format!(
+                "advisory database {} is {} old, past --max-db-age",
+                snapshot.id,
+                snapshots::describe_age(age)
+            ));
+        }
+    }
+    config.db_snapshot = Some(snapshot.id);
+    Ok(())
+}
+
+/// Turn off everything a scan does besides matching: connections, reports
+/// and the files written with them.
+fn quiet(config: &mut ScanConfig) {
+    config.offline = true;
+    config.notify_webhook = None;
+    config.otlp_endpoint = None;
+    config.npm_provenance = false;
+    config.report_formats.clear();
+    config.report_history = 0;
+    config.report_bundle = false;
+    config.remediate = "off".to_string();
+    config.remediation_pr = false;
+    config.metrics_file = None;
+    config.findings_stream = None;
+    config.profile = false;
+    config.annotations = "off".to_string();
+    config.log_line_limit = 0;
+}
+
+/// `requirements.txt:2:1: CRITICAL CVE-2019-19844 django 2.2.0 (fixed in
+/// 2.2.24)`
+fn line(vuln: &Vulnerability) -> String {
+    let mut at = vuln.file.clone().unwrap_or_default();
+    for n in [vuln.line, vuln.column].into_iter().map_while(|n| n) {
+        at.push_str(&format!(":{}", n));
+    }
+    let mut line = format!("{}: {} {} {} {}", at, vuln.severity, vuln.id, vuln.package, vuln.version);
+    if let Some(fixed) = &vuln.fixed_in {
+        line.push_str(&format!(" (fixed in {})", fixed));
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshots::Snapshot;
+    use std::fs;
+    use std::time::UNIX_EPOCH;
+
+    fn workspace(name: &str, config: &str) -> PathBuf {
+        let root = crate::testutil::scratch_dir(&format!("fast-{}", name));
+        fs::write(root.join("requirements.txt"), "requests==2.25.0\ndjango==2.2.0\n").unwrap();
+        fs::write(root.join(CONFIG_FILE_NAME), config).unwrap();
+        root
+    }
+
+    fn fast(root: &Path, args: &[&str]) -> (i32, String, String) {
+        let mut args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        args.splice(0..0, ["--workspace".to_string(), root.display().to_string()]);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = cli(&args, &mut out, &mut err);
+        (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+    }
+
+    #[test]
+    fn findings_print_a_line_each_and_fail_at_the_threshold() {
+        let root = workspace("threshold", "");
+        let (code, out, err) = fast(&root, &["requirements.txt", "README.md"]);
+        assert_eq!(code, 1, "{}", err);
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines.iter().all(|line| line.starts_with("requirements.txt:")), "{}", out);
+        let django = lines.iter().find(|line| line.contains(" django 2.2.0")).unwrap();
+        assert!(django.starts_with("requirements.txt:2:1: CRITICAL "), "{}", django);
+
+        // Only the files given are scanned, and nothing is written
+        assert_eq!(fast(&root, &["README.md"]).0, 0);
+        assert!(!root.join(".builder-cache/security-report.json").exists());
+        fs::write(root.join("requirements.txt"), "requests==2.25.0\n").unwrap();
+        let (code, out, _) = fast(&root, &["requirements.txt"]);
+        assert!(!out.is_empty() && code == 0, "{}", out);
+        assert_eq!(fast(&root, &["--fail-on", "medium", "requirements.txt"]).0, 0);
+        assert_eq!(fast(&root, &["--fail-on", "low", "requirements.txt"]).0, 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_missing_or_stale_database_warns_without_blocking() {
+        let root = workspace("stale", "[sources]\ndb_path = \"osv\"\n");
+        let (code, out, err) = fast(&root, &["requirements.txt"]);
+        assert_eq!((code, out.as_str()), (0, ""));
+        assert_eq!(err, "fast: no cached advisory database; a full scan caches one; not scanned\n");
+
+        let db_cache = cache::cache_root(&root, ".builder-cache").join("db");
+        let dir = snapshots::snapshots_dir(&db_cache);
+        fs::create_dir_all(&dir).unwrap();
+        let mut snapshot = Snapshot {
+            id: "osv-local@0123456789abcdef".to_string(),
+            source: LocalDb::SOURCE.to_string(),
+            sha256: "00".repeat(32),
+            fingerprint: String::new(),
+            fetched_at: None,
+            fetched_at_unix: Some(1_000_000),
+            last_used_unix: 0,
+            index_bytes: None,
+            ecosystems: Vec::new(),
+        };
+        fs::write(snapshots::index_path(&dir, &snapshot.id), b"").unwrap();
+        snapshots::touch(&dir, &mut snapshot, UNIX_EPOCH).unwrap();
+
+        let mut config = ScanConfig::resolve(Some(&root.join(CONFIG_FILE_NAME)), None, None, Vec::new()).unwrap();
+        let week = Duration::from_secs(7 * 86_400);
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000) + Duration::from_secs(8 * 86_400);
+        let warning = pin_cached(&mut config, &db_cache, week, now).unwrap_err();
+        assert_eq!(warning, "advisory database osv-local@0123456789abcdef is 8d old, past --max-db-age");
+        let (code, _, err) = fast(&root, &["requirements.txt"]);
+        assert!(code == 0 && err.ends_with("past --max-db-age; not scanned\n"), "{}", err);
+
+        pin_cached(&mut config, &db_cache, week, now - Duration::from_secs(2 * 86_400)).unwrap();
+        assert_eq!(config.db_snapshot.as_deref(), Some("osv-local@0123456789abcdef"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
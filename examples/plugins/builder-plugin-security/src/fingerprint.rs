@@ -0,0 +1,55 @@
+use sha2::{Digest, Sha256};
+
+use crate::report;
+
+/// How [`compute`] derives a fingerprint, as the report envelope states it.
+pub const ALGORITHM: &str = "fp1: first 32 hex digits of sha256(\"fp1\" NUL advisory id NUL package URL \
+without version NUL declaring file)";
+
+/// A finding's identity across version bumps: the same advisory for the
+/// same package declared in the same file, whatever version is installed.
+pub fn compute(id: &str, ecosystem: &str, package: &str, file: Option<&str>) -> String {
+    let mut hasher = Sha256::new();
+    for part in ["fp1", id, &purl(ecosystem, package), file.unwrap_or("")] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    report::hex(&hasher.finalize())[..32].to_string()
+}
+
+/// The package URL of `package` without a version, e.g. `pkg:pypi/django`.
+pub fn purl(ecosystem: &str, package: &str) -> String {
+    let (kind, name) = match ecosystem {
+        "npm" => ("npm", package.replace('@', "%40")),
+        // PyPI names compare case-insensitively and with `_` as `-`
+        "PyPI" => ("pypi", package.to_lowercase().replace('_', "-")),
+        "crates.io" => ("cargo", package.to_string()),
+        "Go" => ("golang", package.to_string()),
+        "Debian" => ("deb", format!("debian/{}", package)),
+        "Alpine" => ("apk", format!("alpine/{}", package)),
+        "RubyGems" => ("gem", package.to_string()),
+        "Packagist" => ("composer", package.to_lowercase()),
+        "Maven" => ("maven", package.replacen(':', "/", 1)),
+        _ => ("generic", package.to_string()),
+    };
+    format!("pkg:{}/{}", kind, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprints_ignore_the_version_but_not_the_file() {
+        assert_eq!(purl("npm", "@ourorg/ui"), "pkg:npm/%40ourorg/ui");
+        assert_eq!(purl("PyPI", "Django_Rest"), "pkg:pypi/django-rest");
+        let log4j = purl("Maven", "org.apache.logging.log4j:log4j-core");
+        assert_eq!(log4j, "pkg:maven/org.apache.logging.log4j/log4j-core");
+
+        let fp = compute("CVE-2021-2732", "PyPI", "Django", Some("requirements.txt"));
+        assert_eq!(fp.len(), 32);
+        assert_eq!(fp, compute("CVE-2021-2732", "PyPI", "django", Some("requirements.txt")));
+        assert_ne!(fp, compute("CVE-2021-2732", "PyPI", "django", Some("svc/requirements.txt")));
+        assert_ne!(fp, compute("CVE-2021-2733", "PyPI", "django", Some("requirements.txt")));
+    }
+}
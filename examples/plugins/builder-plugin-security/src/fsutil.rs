@@ -0,0 +1,333 @@
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::permissions;
+use crate::report;
+
+static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Starts the header line [`write_checked`] puts before the contents.
+const CHECKED_MAGIC: &str = "builder-security-checked";
+
+/// Times a rename over a file another process has open is retried on
+/// Windows, which refuses it until the file is closed.
+const RENAME_RETRIES: u32 = 5;
+
+/// Write a file so readers never observe partial content, even after a
+/// crash.
+///
+/// The data goes to a temporary sibling first, is flushed to disk and
+/// renamed over the destination, which is atomic on the same filesystem;
+/// the directory is flushed too where the platform allows, so the rename
+/// survives a power loss. The file replaced keeps its permissions.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    permissions::check_write(path)?;
+    let path = long_path(path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    // Unique per write, so concurrent scans in one process don't share it
+    let unique = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let tmp = path.with_file_name(format!(".{}.tmp.{}.{}", file_name, std::process::id(), unique));
+
+    let written = File::create(&tmp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    if let Ok(metadata) = fs::metadata(&path) {
+        let _ = fs::set_permissions(&tmp, metadata.permissions());
+    }
+    let mut retries = 0;
+    loop {
+        match fs::rename(&tmp, &path) {
+            Ok(()) => {
+                sync_dir(path.parent().unwrap_or(Path::new(".")));
+                return Ok(());
+            }
+            Err(e) if cfg!(windows) && e.kind() == io::ErrorKind::PermissionDenied && retries < RENAME_RETRIES => {
+                retries += 1;
+                std::thread::sleep(Duration::from_millis(10 * u64::from(retries)));
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Flush a directory's entries to disk, after a file in it was renamed or
+/// created. Best effort, and nothing where directories can't be opened
+/// for it (Windows).
+pub fn sync_dir(dir: &Path) {
+    if cfg!(unix) {
+        let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    }
+}
+
+/// [`write_atomic`] a cache entry behind a header holding its length and
+/// SHA-256, for [`read_checked`] to tell a damaged entry from a good one.
+pub fn write_checked(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let header = format!("{} {} {}\n", CHECKED_MAGIC, contents.len(), report::hex(&Sha256::digest(contents)));
+    let mut checked = header.into_bytes();
+    checked.extend_from_slice(contents);
+    write_atomic(path, &checked)
+}
+
+/// The contents of a cache entry [`write_checked`] wrote. An entry that
+/// doesn't match its header, cut short or overwritten by whatever, is
+/// removed so it is regenerated, and is an `InvalidData` error. Entries
+/// from before headers were written are returned as they are; parsing them
+/// is their check.
+pub fn read_checked(path: &Path) -> io::Result<Vec<u8>> {
+    let bytes = read(path)?;
+    let Some(rest) = bytes.strip_prefix(CHECKED_MAGIC.as_bytes()) else {
+        return Ok(bytes);
+    };
+    let damaged = || {
+        let _ = fs::remove_file(long_path(path));
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: damaged cache entry, removed", path.display()))
+    };
+    let end = rest.iter().position(|&b| b == b'\n').ok_or_else(damaged)?;
+    let header = std::str::from_utf8(&rest[..end]).map_err(|_| damaged())?;
+    let contents = &rest[end + 1..];
+    let mut fields = header.split_whitespace();
+    let (len, sha256) = (fields.next(), fields.next());
+    let intact = len.and_then(|len| len.parse::<usize>().ok()) == Some(contents.len())
+        && sha256 == Some(report::hex(&Sha256::digest(contents)).as_str());
+    if !intact {
+        return Err(damaged());
+    }
+    Ok(contents.to_vec())
+}
+
+// The file system calls a scan makes, each refused outside the scope
+// `strict_permissions` enforces and made on the `long_path` of its path.
+
+pub fn read(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    permissions::check_read(path)?;
+    fs::read(long_path(path))
+}
+
+pub fn read_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let path = path.as_ref();
+    permissions::check_read(path)?;
+    fs::read_to_string(long_path(path))
+}
+
+pub fn read_dir(path: impl AsRef<Path>) -> io::Result<fs::ReadDir> {
+    let path = path.as_ref();
+    permissions::check_read(path)?;
+    fs::read_dir(long_path(path))
+}
+
+pub fn open(path: impl AsRef<Path>) -> io::Result<File> {
+    let path = path.as_ref();
+    permissions::check_read(path)?;
+    File::open(long_path(path))
+}
+
+pub fn create(path: impl AsRef<Path>) -> io::Result<File> {
+    let path = path.as_ref();
+    permissions::check_write(path)?;
+    File::create(long_path(path))
+}
+
+/// Open `path` for writing as `options` say.
+pub fn open_with(options: &fs::OpenOptions, path: impl AsRef<Path>) -> io::Result<File> {
+    let path = path.as_ref();
+    permissions::check_write(path)?;
+    options.open(long_path(path))
+}
+
+pub fn write(path: impl AsRef<Path>, contents: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    permissions::check_write(path)?;
+    fs::write(long_path(path), contents)
+}
+
+pub fn create_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    permissions::check_write(path)?;
+    fs::create_dir_all(long_path(path))
+}
+
+pub fn remove_file(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    permissions::check_write(path)?;
+    fs::remove_file(long_path(path))
+}
+
+pub fn remove_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    permissions::check_write(path)?;
+    fs::remove_dir_all(long_path(path))
+}
+
+pub fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    permissions::check_write(from)?;
+    permissions::check_write(to)?;
+    fs::rename(long_path(from), long_path(to))
+}
+
+/// `path` as Windows takes it past `MAX_PATH` (260 characters): absolute,
+/// in its `\\?\` verbatim form. Elsewhere, where paths have no such
+/// limit, `path` itself.
+///
+/// For file system calls only: messages keep naming the path as given.
+pub fn long_path(path: &Path) -> PathBuf {
+    if !cfg!(windows) {
+        return path.to_path_buf();
+    }
+    let Ok(absolute) = std::path::absolute(path) else {
+        return path.to_path_buf();
+    };
+    match absolute.to_str().and_then(verbatim) {
+        Some(verbatim) => PathBuf::from(verbatim),
+        None => absolute,
+    }
+}
+
+/// The verbatim form of an absolute Windows path, `C:\dir` as `\\?\C:\dir`
+/// and `\\server\share` as `\\?\UNC\server\share`. Verbatim paths are
+/// taken as they are, so separators are made backslashes and `.` and `..`
+/// resolved here. `None` for paths already verbatim, device paths, and
+/// anything not absolute.
+fn verbatim(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+    let path = path.replace('/', r"\");
+    let (prefix, root, rest) = if let Some(unc) = path.strip_prefix(r"\\") {
+        let mut parts = unc.splitn(3, '\\');
+        let (server, share) = (parts.next()?, parts.next()?);
+        if server.is_empty() || share.is_empty() {
+            return None;
+        }
+        (r"\\?\UNC\", format!(r"{}\{}", server, share), parts.next().unwrap_or(""))
+    } else {
+        let drive = path.get(..2).filter(|d| d.ends_with(':') && d.as_bytes()[0].is_ascii_alphabetic())?;
+        let rest = path[2..].strip_prefix('\\')?;
+        (r"\\?\", drive.to_string(), rest)
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    let mut verbatim = format!("{}{}", prefix, root);
+    for component in components {
+        verbatim.push('\\');
+        verbatim.push_str(component);
+    }
+    if verbatim.ends_with(':') {
+        verbatim.push('\\');
+    }
+    Some(verbatim)
+}
+
+/// Read one line, newline included, keeping at most `cap` bytes of it in
+/// `buf`. `None` at the end of input, else whether the line was cut off.
+pub fn read_line_capped(reader: &mut dyn BufRead, buf: &mut Vec<u8>, cap: usize) -> io::Result<Option<bool>> {
+    let mut read = false;
+    let mut truncated = false;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            return Ok(read.then_some(truncated));
+        }
+        read = true;
+        let end = available.iter().position(|&b| b == b'\n');
+        let chunk = &available[..end.map_or(available.len(), |i| i + 1)];
+        let room = cap.saturating_sub(buf.len());
+        truncated |= chunk.len() > room;
+        buf.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        let used = chunk.len();
+        reader.consume(used);
+        if end.is_some() {
+            return Ok(Some(truncated));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damaged_checked_entries_are_removed_and_legacy_ones_read_as_they_are() {
+        let dir = crate::testutil::scratch_dir("checked");
+        let path = dir.join("entry.json");
+        let contents = br#"{"entries": {"pkg:npm/a": []}}"#;
+        write_checked(&path, contents).unwrap();
+        assert_eq!(read_checked(&path).unwrap(), contents);
+        let leftovers: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(leftovers, ["entry.json"]);
+
+        // Cut at every offset, and with a byte flipped
+        let whole = fs::read(&path).unwrap();
+        let mut flipped = whole.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        let damaged = (CHECKED_MAGIC.len()..whole.len()).map(|cut| whole[..cut].to_vec()).chain([flipped]);
+        for damaged in damaged {
+            fs::write(&path, &damaged).unwrap();
+            let e = read_checked(&path).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidData, "{:?}", String::from_utf8_lossy(&damaged));
+            assert!(!path.exists());
+        }
+
+        fs::write(&path, contents).unwrap();
+        assert_eq!(read_checked(&path).unwrap(), contents);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn atomic_writes_keep_the_permissions_of_the_file_replaced() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = crate::testutil::scratch_dir("atomic");
+        let path = dir.join("package.json");
+        write_atomic(&path, b"{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&path, b"{\"name\": \"app\"}").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read(&path).unwrap(), b"{\"name\": \"app\"}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn windows_paths_get_their_verbatim_form() {
+        assert_eq!(verbatim(r"C:\builds\.\out\..\cache").as_deref(), Some(r"\\?\C:\builds\cache"));
+        assert_eq!(verbatim("d:/work/report.json").as_deref(), Some(r"\\?\d:\work\report.json"));
+        assert_eq!(verbatim(r"C:\").as_deref(), Some(r"\\?\C:\"));
+        assert_eq!(verbatim(r"\\ci\artifacts\a\..\b").as_deref(), Some(r"\\?\UNC\ci\artifacts\b"));
+        // Already verbatim, a device, relative to the drive's current
+        // directory, and relative
+        for path in [r"\\?\C:\cache", r"\\.\pipe\scan", "C:cache", r"cache\osv", r"\\server"] {
+            assert_eq!(verbatim(path), None, "{}", path);
+        }
+    }
+}
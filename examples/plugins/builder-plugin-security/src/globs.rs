@@ -0,0 +1,152 @@
+/// A path glob: `*` and `?` match within one path segment, a `**` segment
+/// matches any number of segments (including none).
+struct Glob {
+    pattern: String,
+    segments: Vec<String>,
+}
+
+impl Glob {
+    fn new(pattern: &str) -> Self {
+        Glob {
+            pattern: pattern.to_string(),
+            segments: split(pattern).map(str::to_string).collect(),
+        }
+    }
+
+    fn matches(&self, path: &[&str]) -> bool {
+        match_segments(&self.segments, path)
+    }
+}
+
+/// Decides which workspace-relative paths are scanned.
+pub struct PathFilter {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Self {
+        PathFilter {
+            include: include.iter().map(|p| Glob::new(p)).collect(),
+            exclude: exclude.iter().map(|p| Glob::new(p)).collect(),
+        }
+    }
+
+    /// Whether `path` (with `/` separators) matches no exclude glob and,
+    /// when there are include globs, at least one of them.
+    pub fn allows(&self, path: &str) -> bool {
+        let segments: Vec<&str> = split(path).collect();
+        if self.exclude.iter().any(|glob| glob.matches(&segments)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|glob| glob.matches(&segments))
+    }
+
+    /// The first exclude glob `path` matches.
+    pub fn excluded_by(&self, path: &str) -> Option<&str> {
+        let segments: Vec<&str> = split(path).collect();
+        self.exclude.iter().find(|glob| glob.matches(&segments)).map(|glob| glob.pattern.as_str())
+    }
+}
+
+/// Whether a package name matches any of `patterns`, which are globs over
+/// its `/`-separated parts (`@ourorg/*`, `internal-*`, `github.com/ourorg/**`).
+pub fn matches_any(patterns: &[String], name: &str) -> bool {
+    let segments: Vec<&str> = split(name).collect();
+    patterns.iter().any(|pattern| Glob::new(pattern).matches(&segments))
+}
+
+/// Whether a workspace-relative path (with `/` separators) matches one glob.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    Glob::new(pattern).matches(&split(path).collect::<Vec<_>>())
+}
+
+/// Whether a pattern has wildcards, rather than naming a single path.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Path segments, ignoring empty ones and `.`.
+fn split(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty() && *s != ".")
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, tail)) => match_segment(first, segment) && match_segments(rest, tail),
+            None => false,
+        },
+    }
+}
+
+/// Wildcard match of one segment, backtracking to the last `*`.
+fn match_segment(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+        let owned = |globs: &[&str]| globs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        PathFilter::new(&owned(include), &owned(exclude))
+    }
+
+    #[test]
+    fn globs_match_segments_and_any_depth() {
+        let defaults = filter(&[], &["**/node_modules/**", "**/.git/**"]);
+        assert!(defaults.allows("requirements.txt"));
+        assert!(defaults.allows("web/package.json"));
+        assert!(!defaults.allows("node_modules/left-pad/package.json"));
+        assert!(!defaults.allows("./web/node_modules/a/b/package.json"));
+        assert!(!defaults.allows(".git/modules/x/Cargo.toml"));
+        assert!(defaults.allows("web/node_modules_backup/package.json"));
+
+        let patterns = filter(&[], &["vendor/*", "fixtures/**/*.lock", "req?.txt"]);
+        assert!(!patterns.allows("vendor/Cargo.toml"));
+        assert!(patterns.allows("vendor/crate/Cargo.toml"));
+        assert!(!patterns.allows("fixtures/yarn.lock"));
+        assert!(!patterns.allows("fixtures/a/b/yarn.lock"));
+        assert!(patterns.allows("fixtures/a/package.json"));
+        assert!(!patterns.allows("req1.txt"));
+        assert!(patterns.allows("req10.txt"));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let filter = filter(&["services/**", "*.txt"], &["**/testdata/**"]);
+        assert!(filter.allows("services/api/go.mod"));
+        assert!(filter.allows("requirements.txt"));
+        assert!(!filter.allows("web/package.json"));
+        assert!(!filter.allows("services/api/testdata/go.mod"));
+        assert!(!filter.allows("services/testdata/requirements.txt"));
+    }
+}
@@ -0,0 +1,227 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+use crate::ecosystems::compare_semver;
+use crate::events;
+use crate::stream;
+
+/// Oldest Builder the plugin works with, as `plugin.info` states it.
+pub const MIN_BUILDER_VERSION: &str = "1.0.0";
+
+/// Newest plugin API the plugin speaks. A host announcing an older
+/// `maxPluginApi` gets responses without what came later.
+pub const PLUGIN_API: u64 = 3;
+
+/// Methods the plugin answers beyond `plugin.info`, `build.pre_hook` and
+/// `build.post_hook`, with the plugin API that introduced each.
+pub const METHODS: &[(&str, u64)] = &[
+    ("plugin.configure", 2),
+    ("plugin.doctor", 2),
+    ("cache.clear", 2),
+    ("cache.stats", 2),
+    ("workspace.pre_hook", 3),
+    ("test.pre_hook", 3),
+    ("plugin.schema", 3),
+    ("plugin.reload_db", 3),
+    (events::NOTIFY_METHOD, 3),
+];
+
+/// Hook result fields beyond `success` and `logs`, with the plugin API that
+/// introduced each.
+pub const RESULT_FIELDS: &[(&str, u64)] = &[
+    ("skipped", 2),
+    ("errors", 2),
+    ("outputs", 2),
+    ("scanned", 2),
+    ("summary", 2),
+    ("not_scanned", 3),
+    ("partial", 3),
+    ("timed_out", 3),
+    ("degraded", 3),
+    ("degraded_reasons", 3),
+    ("projects", 3),
+    ("policy", 3),
+    ("recommendations", 3),
+    ("remediation", 3),
+    ("remediation_pr", 3),
+    ("metrics", 3),
+    ("bundle", 3),
+    ("artifacts", 3),
+    ("extraction", 3),
+    ("reused", 3),
+    ("lifecycle", 3),
+    ("published", 3),
+];
+
+/// Notifications the plugin sends during a request to a host that lists
+/// them in the `notifications` of its `plugin.info` params.
+pub const NOTIFICATIONS: &[&str] = &[stream::FINDING_NOTIFICATION];
+
+/// A problem with the host found during the handshake, reported in the
+/// `plugin.info` result.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub kind: &'static str,
+    pub detail: String,
+}
+
+/// What `plugin.info` learned about the host, and what events left, for
+/// the requests after it.
+#[derive(Default)]
+pub struct Session {
+    /// The host's version, when it is older than [`MIN_BUILDER_VERSION`].
+    unsupported_host: Option<String>,
+    /// The host's `maxPluginApi`, when it sent one.
+    max_api: Option<u64>,
+    /// The [`NOTIFICATIONS`] the host asked for.
+    notifications: Vec<&'static str>,
+    /// What `event.notify` notifications left for the next post_hook.
+    pending: events::Pending,
+}
+
+impl Session {
+    /// Take in the `builderVersion`, `maxPluginApi` and `notifications` of
+    /// `plugin.info` params, returning the warnings to report.
+    pub fn handshake(&mut self, params: Option<&Value>) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        self.unsupported_host = None;
+        if let Some(host) = params.and_then(|p| p.get("builderVersion")).and_then(|v| v.as_str()) {
+            match compare_semver(host, MIN_BUILDER_VERSION) {
+                Some(Ordering::Less) => {
+                    warnings.push(Warning {
+                        kind: "builder_version_unsupported",
+                        detail: format!(
+                            "Builder {} is older than {}, the oldest this plugin supports; hook params may not \
+                             have the shape the plugin expects",
+                            host, MIN_BUILDER_VERSION
+                        ),
+                    });
+                    self.unsupported_host = Some(host.to_string());
+                }
+                Some(_) => {}
+                None => warnings.push(Warning {
+                    kind: "builder_version_unparseable",
+                    detail: format!("Builder version '{}' is not a semantic version; it was not checked", host),
+                }),
+            }
+        }
+
+        self.max_api = None;
+        match params.and_then(|p| p.get("maxPluginApi")) {
+            None => {}
+            Some(api) => match api.as_u64().filter(|api| *api > 0) {
+                Some(api) => self.max_api = Some(api),
+                None => warnings.push(Warning {
+                    kind: "plugin_api_invalid",
+                    detail: format!("maxPluginApi {} is not a positive integer; it was ignored", api),
+                }),
+            },
+        }
+
+        self.notifications.clear();
+        let requested = params.and_then(|p| p.get("notifications")).and_then(|v| v.as_array());
+        for method in requested.into_iter().flatten() {
+            match NOTIFICATIONS.iter().find(|known| method.as_str() == Some(**known)) {
+                Some(known) if !self.notifications.contains(known) => self.notifications.push(known),
+                Some(_) => {}
+                None => warnings.push(Warning {
+                    kind: "notification_unknown",
+                    detail: format!("Notification {} is not one the plugin sends; it was ignored", method),
+                }),
+            }
+        }
+        warnings
+    }
+
+    /// The notifications the host asked for, in the order it listed them.
+    pub fn notifications(&self) -> &[&'static str] {
+        &self.notifications
+    }
+
+    /// Whether the host asked for `method` notifications.
+    pub fn notifies(&self, method: &str) -> bool {
+        self.notifications.contains(&method)
+    }
+
+    /// The events' state awaiting the next post_hook.
+    pub fn pending(&mut self) -> &mut events::Pending {
+        &mut self.pending
+    }
+
+    /// The host's version, when `plugin.info` found it older than
+    /// [`MIN_BUILDER_VERSION`].
+    pub fn unsupported_host(&self) -> Option<&str> {
+        self.unsupported_host.as_deref()
+    }
+
+    /// The plugin API responses follow.
+    pub fn api(&self) -> u64 {
+        self.max_api.map_or(PLUGIN_API, |api| api.min(PLUGIN_API))
+    }
+
+    /// Whether the plugin API in effect has `method`.
+    pub fn allows(&self, method: &str) -> bool {
+        METHODS.iter().all(|(name, api)| *name != method || *api <= self.api())
+    }
+
+    /// What the plugin API in effect leaves out, one line each.
+    pub fn disabled(&self) -> Vec<String> {
+        let api = self.api();
+        let methods = METHODS.iter().filter(|(_, since)| *since > api);
+        let fields = RESULT_FIELDS.iter().filter(|(_, since)| *since > api);
+        methods
+            .map(|(name, since)| format!("method {} (plugin API {})", name, since))
+            .chain(fields.map(|(name, since)| format!("hook result field {} (plugin API {})", name, since)))
+            .collect()
+    }
+
+    /// Remove from a hook response's result the fields the plugin API in
+    /// effect leaves out.
+    pub fn downgrade(&self, response: &mut Value) {
+        let api = self.api();
+        if let Some(result) = response.get_mut("result").and_then(|r| r.as_object_mut()) {
+            for (name, _) in RESULT_FIELDS.iter().filter(|(_, since)| *since > api) {
+                result.remove(*name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn the_host_api_disables_newer_methods_and_result_fields() {
+        let mut session = Session::default();
+        let warnings = session.handshake(Some(&json!({"builderVersion": "0.9.2", "maxPluginApi": 2})));
+        assert_eq!(warnings[0].kind, "builder_version_unsupported");
+        assert_eq!(session.unsupported_host(), Some("0.9.2"));
+        assert_eq!(session.api(), 2);
+        assert!(session.allows("cache.stats") && !session.allows("workspace.pre_hook"));
+        assert!(session.disabled().contains(&"method plugin.schema (plugin API 3)".to_string()));
+
+        let mut response = json!({"result": {"success": true, "logs": [], "errors": [], "not_scanned": []}});
+        session.downgrade(&mut response);
+        assert_eq!(response["result"], json!({"success": true, "logs": [], "errors": []}));
+
+        assert!(session.handshake(Some(&json!({"builderVersion": "1.2.0", "maxPluginApi": 9}))).is_empty());
+        assert_eq!((session.unsupported_host(), session.api()), (None, PLUGIN_API));
+        assert!(session.disabled().is_empty());
+    }
+
+    #[test]
+    fn notifications_are_those_the_host_asks_for_and_the_plugin_sends() {
+        let mut session = Session::default();
+        let warnings = session.handshake(Some(&json!({"notifications": ["plugin.finding", "plugin.progress"]})));
+        assert_eq!(warnings[0].kind, "notification_unknown");
+        assert_eq!(session.notifications(), ["plugin.finding"]);
+        assert!(session.notifies("plugin.finding"));
+
+        // Each plugin.info starts over
+        session.handshake(None);
+        assert!(!session.notifies("plugin.finding"));
+    }
+}
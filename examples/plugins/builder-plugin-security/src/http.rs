@@ -0,0 +1,75 @@
+#[cfg(feature = "network-sources")]
+use std::time::Duration;
+
+use crate::config::ScanConfig;
+use crate::permissions;
+
+/// Why every request fails in a build without the `network-sources`
+/// feature.
+#[cfg(not(feature = "network-sources"))]
+pub const NOT_BUILT: &str = "this build makes no connections (it has no `network-sources` feature)";
+
+#[cfg(feature = "network-sources")]
+pub type Agent = ureq::Agent;
+
+/// The HTTP client of a build without `network-sources`, which there is
+/// never one of.
+#[cfg(not(feature = "network-sources"))]
+pub enum Agent {}
+
+/// HTTP client honoring the configured proxy and timeout.
+#[cfg(feature = "network-sources")]
+pub fn agent(config: &ScanConfig) -> Result<Agent, String> {
+    let proxy = match &config.proxy {
+        Some(proxy) => Some(ureq::Proxy::new(proxy).map_err(|e| format!("invalid proxy '{}': {}", proxy, e))?),
+        None => None,
+    };
+
+    Ok(ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(config.timeout_secs)))
+        .proxy(proxy)
+        .build()
+        .into())
+}
+
+#[cfg(not(feature = "network-sources"))]
+pub fn agent(_config: &ScanConfig) -> Result<Agent, String> {
+    Err(NOT_BUILT.to_string())
+}
+
+/// POST a JSON body with extra headers, treating any non-2xx status as an
+/// error.
+pub fn post_json(config: &ScanConfig, url: &str, body: &str, headers: &[(String, String)]) -> Result<(), String> {
+    permissions::check_url(url).map_err(|e| e.to_string())?;
+    send(agent(config)?, url, body, headers)
+}
+
+#[cfg(feature = "network-sources")]
+fn send(agent: Agent, url: &str, body: &str, headers: &[(String, String)]) -> Result<(), String> {
+    let mut request = agent.post(url).header("Content-Type", "application/json");
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    request
+        .send(body)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "network-sources"))]
+fn send(agent: Agent, _url: &str, _body: &str, _headers: &[(String, String)]) -> Result<(), String> {
+    match agent {}
+}
+
+/// GET `url` and read the body as text.
+pub fn get(agent: &Agent, url: &str) -> Result<String, String> {
+    permissions::check_url(url).map_err(|e| e.to_string())?;
+    #[cfg(feature = "network-sources")]
+    return agent
+        .get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| e.to_string());
+    #[cfg(not(feature = "network-sources"))]
+    match *agent {}
+}
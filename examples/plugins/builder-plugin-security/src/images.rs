@@ -0,0 +1,423 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Component, Path, PathBuf};
+
+use crate::ecosystems::EcosystemScanner;
+use crate::fsutil;
+
+/// Largest image index, manifest or config that is read.
+const MAX_METADATA: u64 = 1024 * 1024;
+
+/// Annotations of an OCI layout's `index.json` naming the image, the full
+/// reference first.
+const REFERENCE_ANNOTATIONS: &[&str] = &["io.containerd.image.name", "org.opencontainers.image.ref.name"];
+
+/// A layer file deleting `<name>` from the layers below.
+const WHITEOUT: &str = ".wh.";
+/// A layer file hiding everything the layers below hold in its directory.
+const OPAQUE: &str = ".wh..wh..opq";
+
+/// A container image among a build's outputs: an OCI image layout, or what
+/// a `docker save` archive was extracted to.
+#[derive(Serialize, Debug)]
+pub struct Image {
+    /// The output, as the build named it.
+    pub output: String,
+    /// The name the image was saved under, e.g. `docker.io/library/app:1.0`.
+    pub reference: Option<String>,
+    /// Digest of the image's manifest; for a `docker save` archive without
+    /// an OCI layout, of its config, which is the image id.
+    pub digest: Option<String>,
+    pub layers: Vec<Layer>,
+    /// What of the final filesystem the scan reads, once the layers are
+    /// extracted.
+    #[serde(skip)]
+    pub files: Vec<ImageFile>,
+}
+
+/// One filesystem layer of an image; layers are listed lowest first.
+#[derive(Serialize, Debug)]
+pub struct Layer {
+    pub digest: String,
+    /// The build step that created it, from the image config's history:
+    /// the Dockerfile instruction.
+    pub created_by: Option<String>,
+    /// Findings in the packages the layer introduced.
+    pub findings: usize,
+    /// The layer's tarball.
+    #[serde(skip)]
+    pub blob: PathBuf,
+    /// Where the layer is extracted to.
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+/// A file of an image's final filesystem.
+#[derive(Debug, PartialEq)]
+pub struct ImageFile {
+    /// Path in the image, without the leading `/`.
+    pub path: String,
+    /// The layers holding a version of the file since the layers above last
+    /// deleted it, lowest first. The last holds the one in the image.
+    pub layers: Vec<usize>,
+}
+
+/// An entry of `manifests` in an image index, or a manifest's `config` or
+/// layer.
+#[derive(Deserialize)]
+struct Descriptor {
+    digest: String,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+    #[serde(default)]
+    platform: Option<Platform>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    #[serde(default)]
+    os: String,
+}
+
+/// An image index (`index.json` among them) or an image manifest, told
+/// apart by whether it has a `config`.
+#[derive(Deserialize)]
+struct OciDocument {
+    #[serde(default)]
+    manifests: Vec<Descriptor>,
+    #[serde(default)]
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+}
+
+/// An entry of a `docker save` archive's `manifest.json`.
+#[derive(Deserialize)]
+struct DockerManifest {
+    #[serde(rename = "Config")]
+    config: String,
+    #[serde(rename = "RepoTags", default)]
+    repo_tags: Option<Vec<String>>,
+    #[serde(rename = "Layers")]
+    layers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    history: Vec<History>,
+    #[serde(default)]
+    rootfs: Option<RootFs>,
+}
+
+#[derive(Deserialize)]
+struct History {
+    #[serde(default)]
+    created_by: Option<String>,
+    #[serde(default)]
+    empty_layer: bool,
+}
+
+#[derive(Deserialize)]
+struct RootFs {
+    #[serde(default)]
+    diff_ids: Vec<String>,
+}
+
+/// Whether `dir` holds an image: an OCI layout (`oci-layout` and
+/// `index.json`) or the `manifest.json` of a `docker save` archive.
+pub fn is_image(dir: &Path) -> bool {
+    is_oci_layout(dir) || dir.join("manifest.json").is_file()
+}
+
+fn is_oci_layout(dir: &Path) -> bool {
+    dir.join("oci-layout").is_file() && dir.join("index.json").is_file()
+}
+
+/// Read the image in `dir`, which [`is_image`], built as `output`. Of
+/// several images the first is read, and of a multi-platform image its
+/// first platform. Blobs are only looked for inside `dir`.
+pub fn open(dir: &Path, output: &str) -> Result<Image, String> {
+    if is_oci_layout(dir) {
+        open_oci(dir, output)
+    } else {
+        open_docker(dir, output)
+    }
+}
+
+fn open_oci(dir: &Path, output: &str) -> Result<Image, String> {
+    let index: OciDocument = read_json(&dir.join("index.json"))?;
+    let top = index.manifests.into_iter().next().ok_or("index.json lists no image")?;
+    let reference = REFERENCE_ANNOTATIONS.iter().find_map(|key| top.annotations.get(*key).cloned());
+
+    // A multi-platform image is an index of per-platform manifests and
+    // attestations, whose platform is `unknown`
+    let mut document: OciDocument = read_json(&blob(dir, &top.digest)?)?;
+    for _ in 0..4 {
+        if document.config.is_some() {
+            break;
+        }
+        let Some(manifest) = document.manifests.iter().find(|m| m.platform.as_ref().is_none_or(|p| p.os != "unknown"))
+        else {
+            break;
+        };
+        document = read_json(&blob(dir, &manifest.digest)?)?;
+    }
+    let config = document.config.ok_or_else(|| format!("{} leads to no image manifest", top.digest))?;
+    let config: Config = read_json(&blob(dir, &config.digest)?)?;
+    let layers = document
+        .layers
+        .into_iter()
+        .map(|layer| Ok((blob(dir, &layer.digest)?, layer.digest)))
+        .collect::<Result<_, String>>()?;
+    Ok(image(output, reference, Some(top.digest), layers, &config))
+}
+
+fn open_docker(dir: &Path, output: &str) -> Result<Image, String> {
+    let manifests: Vec<DockerManifest> = read_json(&dir.join("manifest.json"))?;
+    let manifest = manifests.into_iter().next().ok_or("manifest.json lists no image")?;
+    let config: Config = read_json(&member(dir, &manifest.config)?)?;
+    let diff_ids = config.rootfs.as_ref().map_or(&[][..], |rootfs| &rootfs.diff_ids);
+    // Older archives name layers `<id>/layer.tar`, which isn't a digest;
+    // the config's digest of the uncompressed layer is
+    let layers = manifest
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let digest = digest_of(path).or_else(|| diff_ids.get(i).cloned()).unwrap_or_else(|| path.clone());
+            Ok((member(dir, path)?, digest))
+        })
+        .collect::<Result<_, String>>()?;
+    let reference = manifest.repo_tags.and_then(|tags| tags.into_iter().next());
+    Ok(image(output, reference, digest_of(&manifest.config), layers, &config))
+}
+
+fn image(
+    output: &str,
+    reference: Option<String>,
+    digest: Option<String>,
+    layers: Vec<(PathBuf, String)>,
+    config: &Config,
+) -> Image {
+    // The history has an entry per build step; those that changed the
+    // filesystem made the layers, in order
+    let steps: Vec<Option<String>> =
+        config.history.iter().filter(|step| !step.empty_layer).map(|step| step.created_by.clone()).collect();
+    let aligned = steps.len() == layers.len();
+    Image {
+        output: output.to_string(),
+        reference,
+        digest,
+        layers: layers
+            .into_iter()
+            .enumerate()
+            .map(|(i, (blob, digest))| Layer {
+                digest,
+                created_by: if aligned { steps[i].clone() } else { None },
+                findings: 0,
+                blob,
+                dir: PathBuf::new(),
+            })
+            .collect(),
+        files: Vec::new(),
+    }
+}
+
+/// Path of the blob with `digest` in an OCI layout.
+fn blob(dir: &Path, digest: &str) -> Result<PathBuf, String> {
+    match digest.split_once(':') {
+        Some((algorithm, hex)) if is_name(algorithm) && is_hex(hex) => Ok(dir.join("blobs").join(algorithm).join(hex)),
+        _ => Err(format!("malformed digest '{}'", digest)),
+    }
+}
+
+/// Path of a member a `docker save` manifest names, which mustn't leave `dir`.
+fn member(dir: &Path, path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(path);
+    if relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        Ok(dir.join(relative))
+    } else {
+        Err(format!("manifest.json names '{}', outside the archive", path))
+    }
+}
+
+/// The digest a `docker save` member is named by: `blobs/sha256/<hex>`, or
+/// `<hex>.json` for the config of an older archive.
+fn digest_of(path: &str) -> Option<String> {
+    let hex = path.strip_prefix("blobs/sha256/").or_else(|| path.strip_suffix(".json"))?;
+    is_hex(hex).then(|| format!("sha256:{}", hex))
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+fn is_hex(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let name = path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned());
+    let size = fs::metadata(path).map_err(|e| format!("{}: {}", name, e))?.len();
+    if size > MAX_METADATA {
+        return Err(format!("{}: larger than {} bytes", name, MAX_METADATA));
+    }
+    let bytes = fsutil::read(path).map_err(|e| format!("{}: {}", name, e))?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("{}: {}", name, e))
+}
+
+/// The files `wanted` accepts in the final filesystem of an image whose
+/// layers are extracted to `layers`, lowest first.
+///
+/// A layer's whiteouts delete what the layers below hold: `.wh.<name>` the
+/// file or directory `<name>`, `.wh..wh..opq` everything in its directory.
+/// `node_modules` directories aren't searched.
+pub fn files(layers: &[PathBuf], wanted: impl Fn(&str) -> bool) -> Vec<ImageFile> {
+    let mut found: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, layer) in layers.iter().enumerate() {
+        let mut paths = Vec::new();
+        walk(layer, "", &mut paths);
+        for path in &paths {
+            let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+            let deleted = match name {
+                OPAQUE => dir.to_string(),
+                _ => match name.strip_prefix(WHITEOUT) {
+                    Some(name) if dir.is_empty() => name.to_string(),
+                    Some(name) => format!("{}/{}", dir, name),
+                    None => continue,
+                },
+            };
+            let within = format!("{}/", deleted);
+            found.retain(|file, _| !(deleted.is_empty() || *file == deleted || file.starts_with(&within)));
+        }
+        for path in paths {
+            let whiteout = path.rsplit('/').next().is_some_and(|name| name.starts_with(WHITEOUT));
+            if !whiteout && wanted(&path) {
+                found.entry(path).or_default().push(i);
+            }
+        }
+    }
+    found.into_iter().map(|(path, layers)| ImageFile { path, layers }).collect()
+}
+
+fn walk(dir: &Path, prefix: &str, paths: &mut Vec<String>) {
+    let Ok(entries) = fsutil::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let path = if prefix.is_empty() { name.clone() } else { format!("{}/{}", prefix, name) };
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() && name != "node_modules" => walk(&entry.path(), &path, paths),
+            Ok(kind) if kind.is_file() => paths.push(path),
+            _ => {}
+        }
+    }
+}
+
+/// The packages, by name and version, `scanner` reads from a layer's
+/// version of a file; `None` when it is larger than `max_size` or can't be
+/// read.
+pub fn declared(scanner: &dyn EcosystemScanner, path: &Path, max_size: u64) -> Option<BTreeSet<(String, String)>> {
+    if fs::metadata(path).ok()?.len() > max_size {
+        return None;
+    }
+    let file = fsutil::open(path).ok()?;
+    let extracted = scanner.extract(&path.to_string_lossy(), &mut BufReader::new(file));
+    Some(extracted.dependencies.into_iter().map(|dep| (dep.name, dep.version)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn images_are_read_from_oci_layouts_and_docker_save_archives() {
+        let dir = crate::testutil::scratch_dir("images-open");
+        let (oci, docker) = (dir.join("oci"), dir.join("docker"));
+        let hex = |c: char| c.to_string().repeat(64);
+        let config = json!({
+            "history": [
+                {"created_by": "/bin/sh -c #(nop) ADD file:base in /"},
+                {"created_by": "/bin/sh -c #(nop)  CMD [\"bash\"]", "empty_layer": true},
+                {"created_by": "RUN apt-get install -y openssl"}
+            ],
+            "rootfs": {"diff_ids": [format!("sha256:{}", hex('d')), format!("sha256:{}", hex('e'))]}
+        });
+
+        write(&oci.join("oci-layout"), r#"{"imageLayoutVersion": "1.0.0"}"#);
+        let index = json!({"manifests": [{
+            "digest": format!("sha256:{}", hex('1')),
+            "annotations": {"org.opencontainers.image.ref.name": "1.0", "io.containerd.image.name": "app:1.0"}
+        }]});
+        write(&oci.join("index.json"), &index.to_string());
+        let platforms = json!({"manifests": [
+            {"digest": format!("sha256:{}", hex('3')), "platform": {"os": "unknown"}},
+            {"digest": format!("sha256:{}", hex('2')), "platform": {"os": "linux"}}
+        ]});
+        write(&oci.join("blobs/sha256").join(hex('1')), &platforms.to_string());
+        let manifest = json!({
+            "config": {"digest": format!("sha256:{}", hex('c'))},
+            "layers": [{"digest": format!("sha256:{}", hex('a'))}, {"digest": format!("sha256:{}", hex('b'))}]
+        });
+        write(&oci.join("blobs/sha256").join(hex('2')), &manifest.to_string());
+        write(&oci.join("blobs/sha256").join(hex('c')), &config.to_string());
+
+        let image = open(&oci, "dist/app").unwrap();
+        assert_eq!(image.reference.as_deref(), Some("app:1.0"));
+        assert_eq!(image.digest, Some(format!("sha256:{}", hex('1'))));
+        let layers: Vec<(&str, Option<&str>)> =
+            image.layers.iter().map(|l| (&l.digest[7..8], l.created_by.as_deref())).collect();
+        let base = "/bin/sh -c #(nop) ADD file:base in /";
+        assert_eq!(layers, [("a", Some(base)), ("b", Some("RUN apt-get install -y openssl"))]);
+        assert_eq!(image.layers[1].blob, oci.join("blobs/sha256").join(hex('b')));
+
+        write(&docker.join(format!("{}.json", hex('c'))), &config.to_string());
+        let manifest = json!([{
+            "Config": format!("{}.json", hex('c')),
+            "RepoTags": null,
+            "Layers": ["0123/layer.tar", "4567/layer.tar"]
+        }]);
+        write(&docker.join("manifest.json"), &manifest.to_string());
+        assert!(is_image(&docker) && !is_image(&dir));
+        let image = open(&docker, "dist/app.tar").unwrap();
+        assert_eq!((image.reference, image.digest), (None, Some(format!("sha256:{}", hex('c')))));
+        let digests: Vec<&str> = image.layers.iter().map(|l| &l.digest[7..8]).collect();
+        assert_eq!(digests, ["d", "e"]);
+
+        write(&docker.join("manifest.json"), r#"[{"Config": "../../etc/passwd", "Layers": []}]"#);
+        assert!(open(&docker, "dist/app.tar").unwrap_err().contains("outside the archive"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn whiteouts_delete_what_the_layers_below_hold() {
+        let dir = crate::testutil::scratch_dir("images-files");
+        let layers: Vec<PathBuf> = (0..3).map(|i| dir.join(i.to_string())).collect();
+        write(&layers[0].join("var/lib/dpkg/status"), "");
+        write(&layers[0].join("app/requirements.txt"), "");
+        write(&layers[0].join("old/requirements.txt"), "");
+        write(&layers[0].join("node_modules/x/package.json"), "");
+        write(&layers[1].join("var/lib/dpkg/status"), "");
+        write(&layers[1].join(".wh.old"), "");
+        write(&layers[1].join("app/.wh..wh..opq"), "");
+        write(&layers[2].join("app/requirements.txt"), "");
+
+        let found = files(&layers, |path| path.ends_with("status") || path.ends_with("requirements.txt"));
+        let file = |path: &str, layers: &[usize]| ImageFile { path: path.to_string(), layers: layers.to_vec() };
+        assert_eq!(found, [file("app/requirements.txt", &[2]), file("var/lib/dpkg/status", &[0, 1])]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
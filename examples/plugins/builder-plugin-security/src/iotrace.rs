@@ -0,0 +1,167 @@
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::report;
+
+/// Environment variable naming the trace file when `--trace-io` isn't given.
+pub const TRACE_ENV: &str = "BUILDER_PLUGIN_TRACE";
+
+/// Extra comma-separated key fragments to redact, on top of the defaults.
+pub const REDACT_ENV: &str = "BUILDER_PLUGIN_TRACE_REDACT";
+
+/// Object keys containing any of these (case-insensitively) are redacted.
+const SECRET_KEYS: &[&str] = &["token", "secret", "password", "webhook", "authorization", "api_key", "apikey"];
+
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl Direction {
+    fn marker(self) -> &'static str {
+        match self {
+            Direction::Inbound => "-->",
+            Direction::Outbound => "<--",
+        }
+    }
+}
+
+/// Appends every protocol message to a file for debugging host/plugin
+/// mismatches.
+///
+/// Each message becomes one line, formatted in full before a single locked
+/// write so concurrent handlers never interleave partial lines.
+pub struct IoTrace {
+    file: Mutex<File>,
+    redactor: Redactor,
+}
+
+impl IoTrace {
+    /// Open the trace file for appending, creating it if needed.
+    pub fn open(path: &str, extra_secret_keys: Option<&str>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(IoTrace {
+            file: Mutex::new(file),
+            redactor: Redactor::new(extra_secret_keys),
+        })
+    }
+
+    /// Record a parsed message.
+    pub fn message(&self, direction: Direction, message: &Value) {
+        let mut message = message.clone();
+        self.redactor.redact(&mut message);
+        self.write_line(direction, &message.to_string());
+    }
+
+    /// Record an inbound line that isn't valid JSON. Only its length is
+    /// written, since an unparsed line can't be redacted.
+    pub fn unparseable(&self, line: &str, error: &serde_json::Error) {
+        self.write_line(
+            Direction::Inbound,
+            &format!("[unparseable, {} bytes: {}]", line.len(), error),
+        );
+    }
+
+    fn write_line(&self, direction: Direction, body: &str) {
+        let line = format!("{} {} {}\n", timestamp(), direction.marker(), body);
+        // Tracing is best-effort and must never take the plugin down
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
+
+/// Replaces the values of secret-looking object keys with `[REDACTED]`.
+pub struct Redactor {
+    secret_keys: Vec<String>,
+}
+
+impl Redactor {
+    /// The default key fragments plus comma-separated `extra` ones.
+    pub fn new(extra: Option<&str>) -> Self {
+        let mut secret_keys: Vec<String> = SECRET_KEYS.iter().map(|k| k.to_string()).collect();
+        secret_keys.extend(
+            extra
+                .unwrap_or_default()
+                .split(',')
+                .map(|k| k.trim().to_ascii_lowercase())
+                .filter(|k| !k.is_empty()),
+        );
+        Redactor { secret_keys }
+    }
+
+    pub fn redact(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let key = key.to_ascii_lowercase();
+                    if self.secret_keys.iter().any(|secret| key.contains(secret.as_str())) {
+                        *value = Value::String(REDACTED.to_string());
+                    } else {
+                        self.redact(value);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact(item)),
+            _ => {}
+        }
+    }
+}
+
+/// Split a trace line into its direction and message body, skipping the
+/// timestamp.
+pub fn parse_line(line: &str) -> Option<(Direction, &str)> {
+    let (_timestamp, rest) = line.split_once(' ')?;
+    let (marker, body) = rest.split_once(' ')?;
+    let direction = match marker {
+        "-->" => Direction::Inbound,
+        "<--" => Direction::Outbound,
+        _ => return None,
+    };
+    Some((direction, body))
+}
+
+/// RFC 3339 UTC timestamp with millisecond precision.
+pub fn timestamp() -> String {
+    let now = SystemTime::now();
+    let millis = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_millis());
+    let seconds = report::format_timestamp(now);
+    format!("{}.{:03}Z", seconds.trim_end_matches('Z'), millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn secrets_are_redacted_at_any_depth() {
+        let dir = crate::testutil::scratch_dir("iotrace");
+        let path = dir.join("trace.log");
+        let trace = IoTrace::open(path.to_str().unwrap(), Some("proxy")).unwrap();
+
+        trace.message(
+            Direction::Inbound,
+            &json!({
+                "params": {
+                    "workspace": {"config": {"notify_webhook": "https://hooks/x", "proxy": "http://u:p@proxy"}},
+                    "targets": [{"GITHUB_TOKEN": "ghp_123", "name": "app"}]
+                }
+            }),
+        );
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.contains(" --> "));
+        assert!(written.contains("\"name\":\"app\""));
+        assert!(!written.contains("hooks/x") && !written.contains("ghp_123") && !written.contains("u:p@"));
+        assert_eq!(written.matches(REDACTED).count(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
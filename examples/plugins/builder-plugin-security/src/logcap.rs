@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+use crate::fsutil;
+use crate::summary;
+
+/// How much a log line matters when the log has to be cut down. Lines are
+/// dropped least important first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Weight {
+    /// Debug output and per-file listings.
+    Detail,
+    /// Section headers, counts, top findings and recommendations.
+    Summary,
+    /// Warnings, errors and workflow commands.
+    Problem,
+}
+
+/// Line ending of the full log: the platform's, so Notepad and the like
+/// show it line by line.
+const LINE_ENDING: &str = if cfg!(windows) { "\r\n" } else { "\n" };
+
+/// Where a scan's complete log is written, under the cache root.
+pub fn full_log_path(cache_root: &Path, target: &str) -> PathBuf {
+    cache_root.join(format!("scan-{}.log", summary::file_stem(target)))
+}
+
+fn indent(line: &str) -> usize {
+    let line = line.trim_start_matches('\n');
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn own_weight(line: &str) -> Weight {
+    if line.contains('⚠') || line.contains('⛔') || line.starts_with("::") {
+        Weight::Problem
+    } else if line.contains("[debug]") {
+        Weight::Detail
+    } else {
+        Weight::Summary
+    }
+}
+
+/// Weight of every line. Lines indented under a header weigh at least as
+/// much as it, except the entries of a "Not scanned" list, which are one
+/// per file.
+fn weights(logs: &[String]) -> Vec<Weight> {
+    let mut header = Weight::Summary;
+    let mut listing_files = false;
+    logs.iter()
+        .map(|line| {
+            let own = own_weight(line);
+            if indent(line) <= 2 {
+                header = own;
+                listing_files = line.contains("Not scanned");
+                return own;
+            }
+            if listing_files {
+                Weight::Detail
+            } else {
+                own.max(header)
+            }
+        })
+        .collect()
+}
+
+/// `logs` as one text with `line_ending` after every line but the last.
+/// Lines of tool output may carry line endings of their own; every `\r\n`
+/// and lone `\r` in them is taken for one as well, never left half
+/// converted.
+fn log_text(logs: &[String], line_ending: &str) -> String {
+    let lines: Vec<String> = logs.iter().map(|line| line.replace("\r\n", "\n").replace('\r', "\n")).collect();
+    let text = lines.join("\n");
+    if line_ending == "\n" {
+        return text;
+    }
+    text.replace('\n', line_ending)
+}
+
+/// Write the complete `logs` to `path` and return at most `limit` of them
+/// (all with `0`): the heaviest lines, in their original order, followed by
+/// a line saying how many were elided and where the rest are.
+pub fn cap(logs: Vec<String>, limit: usize, path: &Path) -> Vec<String> {
+    let written = fsutil::write_atomic(path, log_text(&logs, LINE_ENDING).as_bytes());
+    if limit == 0 || logs.len() <= limit {
+        let mut logs = logs;
+        if let Err(e) = written {
+            logs.push(format!("  ⚠ Could not write the full log to {}: {}", path.display(), e));
+        }
+        return logs;
+    }
+
+    let weights = weights(&logs);
+    let mut order: Vec<usize> = (0..logs.len()).collect();
+    order.sort_by_key(|&i| (std::cmp::Reverse(weights[i]), i));
+    let mut keep = vec![false; logs.len()];
+    for &i in order.iter().take(limit - 1) {
+        keep[i] = true;
+    }
+
+    let elided = logs.len() - (limit - 1);
+    let mut capped: Vec<String> =
+        logs.into_iter().zip(keep).filter_map(|(line, keep)| keep.then_some(line)).collect();
+    capped.push(match written {
+        Ok(()) => format!("  … {} more log lines elided; the full log is in {}", elided, path.display()),
+        Err(e) => {
+            format!("  ⚠ {} log lines elided; could not write the full log to {}: {}", elided, path.display(), e)
+        }
+    });
+    capped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problems_and_summaries_outlast_details() {
+        let dir = crate::testutil::scratch_dir("logcap");
+        let path = full_log_path(&dir, "//app:main");
+        assert!(path.ends_with("scan-__app_main.log"));
+
+        let mut logs = vec!["[Security] Initializing security scan".to_string()];
+        logs.extend((0..20).map(|i| format!("  [debug]   src/file{}.py", i)));
+        logs.push("\n  ⚠ Not scanned (20 files):".to_string());
+        logs.extend((0..20).map(|i| format!("    - src/file{}.py: unsupported", i)));
+        logs.push("  ⚠ Found 1 vulnerabilities".to_string());
+        logs.push("\n  Top vulnerabilities:".to_string());
+        logs.push("    1. CVE-1 - lodash (HIGH)".to_string());
+        logs.push("\n  ⛔ Security policy violated:".to_string());
+        logs.push("    - 1 HIGH findings".to_string());
+        let full = log_text(&logs, LINE_ENDING);
+
+        let capped = cap(logs.clone(), 8, &path);
+        assert_eq!(capped.len(), 8);
+        assert_eq!(
+            capped[..7],
+            [
+                "[Security] Initializing security scan",
+                "\n  ⚠ Not scanned (20 files):",
+                "  ⚠ Found 1 vulnerabilities",
+                "\n  Top vulnerabilities:",
+                "    1. CVE-1 - lodash (HIGH)",
+                "\n  ⛔ Security policy violated:",
+                "    - 1 HIGH findings",
+            ]
+        );
+        assert!(capped[7].contains("40 more log lines elided") && capped[7].contains("scan-__app_main.log"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), full);
+
+        assert_eq!(cap(logs.clone(), 0, &path), logs);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn full_logs_take_the_platform_line_ending_throughout() {
+        let logs = ["[Security] Scanning", "  npm: audit\r\n  npm: done\r", "\n  Done"].map(String::from);
+        assert_eq!(log_text(&logs, "\n"), "[Security] Scanning\n  npm: audit\n  npm: done\n\n\n  Done");
+        assert_eq!(
+            log_text(&logs, "\r\n"),
+            "[Security] Scanning\r\n  npm: audit\r\n  npm: done\r\n\r\n\r\n  Done"
+        );
+    }
+}
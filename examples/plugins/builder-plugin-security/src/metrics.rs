@@ -0,0 +1,158 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::fmt::Write;
+
+use crate::severity::Severity;
+
+/// Description of one exported metric, echoed in the hook result so
+/// dashboards can rely on names and labels staying put.
+#[derive(Serialize, JsonSchema)]
+pub struct MetricInfo {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub help: &'static str,
+    pub labels: &'static [&'static str],
+}
+
+/// The hook result's `metrics`: where the textfile went and what it holds.
+#[derive(Serialize, JsonSchema)]
+pub struct Written {
+    pub file: String,
+    pub metrics: &'static [MetricInfo],
+}
+
+/// Every metric written to the textfile. Names and labels are part of the
+/// plugin's public interface: add new ones, never rename.
+pub const METRICS: &[MetricInfo] = &[
+    MetricInfo {
+        name: "security_scan_vulnerabilities",
+        kind: "gauge",
+        help: "Unsuppressed vulnerabilities found by the last scan",
+        labels: &["target", "severity", "ecosystem"],
+    },
+    MetricInfo {
+        name: "security_scan_duration_seconds",
+        kind: "gauge",
+        help: "Wall-clock duration of the last scan",
+        labels: &["target"],
+    },
+    MetricInfo {
+        name: "security_scan_dependencies_scanned",
+        kind: "gauge",
+        help: "Dependencies extracted from manifests and lockfiles",
+        labels: &["target"],
+    },
+    MetricInfo {
+        name: "security_scan_files_scanned",
+        kind: "gauge",
+        help: "Dependency files read",
+        labels: &["target"],
+    },
+    MetricInfo {
+        name: "security_scan_cache_hit_ratio",
+        kind: "gauge",
+        help: "Fraction of advisory lookups answered from cache",
+        labels: &["target"],
+    },
+    MetricInfo {
+        name: "security_scan_last_run_timestamp_seconds",
+        kind: "gauge",
+        help: "Unix time the last scan finished",
+        labels: &["target"],
+    },
+];
+
+/// Values for one scan.
+pub struct ScanMetrics<'a> {
+    pub target: &'a str,
+    /// (severity, ecosystem) of every reported finding.
+    pub findings: Vec<(Severity, &'a str)>,
+    /// Ecosystems that were scanned, so zero counts are exported too.
+    pub ecosystems: Vec<&'a str>,
+    pub duration_secs: f64,
+    pub dependencies: usize,
+    pub files: usize,
+    pub cache_hit_ratio: f64,
+    pub finished_at: u64,
+}
+
+/// Render the metrics in the Prometheus text exposition format.
+///
+/// Every severity/ecosystem pair is written, including zeros, so series
+/// don't disappear when a vulnerability is fixed.
+pub fn render(metrics: &ScanMetrics) -> String {
+    let mut out = String::new();
+    let target = escape_label(metrics.target);
+
+    let mut ecosystems = metrics.ecosystems.clone();
+    ecosystems.sort_unstable();
+    ecosystems.dedup();
+
+    for info in METRICS {
+        let _ = writeln!(out, "# HELP {} {}", info.name, info.help);
+        let _ = writeln!(out, "# TYPE {} {}", info.name, info.kind);
+
+        match info.name {
+            "security_scan_vulnerabilities" => {
+                for ecosystem in &ecosystems {
+                    for severity in Severity::ALL {
+                        let count = metrics
+                            .findings
+                            .iter()
+                            .filter(|(s, e)| *s == severity && e == ecosystem)
+                            .count();
+                        let _ = writeln!(
+                            out,
+                            "{}{{target=\"{}\",severity=\"{}\",ecosystem=\"{}\"}} {}",
+                            info.name,
+                            target,
+                            severity.as_str().to_lowercase(),
+                            escape_label(ecosystem),
+                            count
+                        );
+                    }
+                }
+            }
+            name => {
+                let value = match name {
+                    "security_scan_duration_seconds" => metrics.duration_secs,
+                    "security_scan_dependencies_scanned" => metrics.dependencies as f64,
+                    "security_scan_files_scanned" => metrics.files as f64,
+                    "security_scan_cache_hit_ratio" => metrics.cache_hit_ratio,
+                    _ => metrics.finished_at as f64,
+                };
+                let _ = writeln!(out, "{}{{target=\"{}\"}} {}", name, target, value);
+            }
+        }
+    }
+    out
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_severity_is_exported_per_ecosystem() {
+        let text = render(&ScanMetrics {
+            target: "web \"app\"",
+            findings: vec![(Severity::High, "npm"), (Severity::High, "npm")],
+            ecosystems: vec!["npm", "PyPI", "npm"],
+            duration_secs: 1.5,
+            dependencies: 12,
+            files: 2,
+            cache_hit_ratio: 0.0,
+            finished_at: 0,
+        });
+
+        assert!(text.contains(r#"security_scan_vulnerabilities{target="web \"app\"",severity="high",ecosystem="npm"} 2"#));
+        assert!(text.contains(r#"security_scan_vulnerabilities{target="web \"app\"",severity="high",ecosystem="PyPI"} 0"#));
+        assert_eq!(text.matches("security_scan_vulnerabilities{").count(), 2 * Severity::ALL.len());
+        assert!(text.contains("security_scan_duration_seconds{target=\"web \\\"app\\\"\"} 1.5"));
+    }
+}
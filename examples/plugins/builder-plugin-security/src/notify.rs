@@ -0,0 +1,133 @@
+use serde_json::{json, Value};
+
+use crate::config::ScanConfig;
+use crate::http;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// Findings listed individually in a notification.
+const TOP_FINDINGS: usize = 5;
+
+/// What a notification is about.
+pub struct Alert<'a> {
+    pub target: &'a str,
+    pub findings: &'a [Vulnerability],
+    pub violations: &'a [String],
+    /// CRITICAL findings that weren't in the previous report.
+    pub new_critical: &'a [&'a Vulnerability],
+    pub report_path: &'a str,
+}
+
+impl Alert<'_> {
+    /// Notifications only fire for policy failures or new CRITICAL findings.
+    pub fn should_fire(&self) -> bool {
+        !self.violations.is_empty() || !self.new_critical.is_empty()
+    }
+
+    fn headline(&self) -> String {
+        if self.violations.is_empty() {
+            format!(
+                "{} new critical vulnerabilities in {}",
+                self.new_critical.len(),
+                self.target
+            )
+        } else {
+            format!("Security policy violated in {}", self.target)
+        }
+    }
+}
+
+/// Build the payload in the configured format (`slack` or `json`).
+pub fn payload(format: &str, alert: &Alert) -> Value {
+    let counts: serde_json::Map<String, Value> = Severity::ALL
+        .iter()
+        .map(|s| {
+            let count = alert.findings.iter().filter(|f| f.severity == *s).count();
+            (s.as_str().to_string(), json!(count))
+        })
+        .collect();
+
+    let top: Vec<&Vulnerability> = alert.findings.iter().take(TOP_FINDINGS).collect();
+
+    match format {
+        "json" => json_payload(alert, counts, &top),
+        "slack" => slack_payload(alert, &counts, &top),
+        other => unreachable!("notify_format '{}' passed ScanConfig::resolve", other),
+    }
+}
+
+fn json_payload(alert: &Alert, counts: serde_json::Map<String, Value>, top: &[&Vulnerability]) -> Value {
+    json!({
+        "event": "security.alert",
+        "target": alert.target,
+        "summary": alert.headline(),
+        "counts": counts,
+        "violations": alert.violations,
+        "new_critical": alert.new_critical.iter().map(|f| &f.id).collect::<Vec<_>>(),
+        "top_findings": top.iter().map(|f| json!({
+            "id": f.id,
+            "severity": f.severity,
+            "package": f.package,
+            "version": f.version,
+            "summary": f.summary,
+            "fixed_in": f.fixed_in,
+        })).collect::<Vec<_>>(),
+        "report": alert.report_path,
+    })
+}
+
+/// A Slack-compatible incoming-webhook message.
+fn slack_payload(alert: &Alert, counts: &serde_json::Map<String, Value>, top: &[&Vulnerability]) -> Value {
+    let mut lines = vec![format!(":rotating_light: *{}*", alert.headline())];
+    let summary: Vec<String> = Severity::ALL
+        .iter()
+        .filter_map(|s| {
+            let count = counts[s.as_str()].as_u64().unwrap_or(0);
+            (count > 0).then(|| format!("{} {}", count, s.as_str().to_lowercase()))
+        })
+        .collect();
+    if !summary.is_empty() {
+        lines.push(summary.join(", "));
+    }
+    for violation in alert.violations {
+        lines.push(format!("• {}", violation));
+    }
+    for finding in top {
+        let mut line = format!(
+            "• `{}` {} {} ({})",
+            finding.id, finding.package, finding.version, finding.severity
+        );
+        if !finding.summary.is_empty() {
+            line.push_str(&format!(": {}", finding.summary));
+        }
+        lines.push(line);
+    }
+    lines.push(format!("Report: `{}`", alert.report_path));
+
+    json!({ "text": lines.join("\n") })
+}
+
+/// Send the alert to the configured webhook, if any.
+///
+/// Delivery problems are logged as warnings only; a notification must never
+/// change the outcome of the scan.
+pub fn send(config: &ScanConfig, alert: &Alert, logs: &mut Vec<String>) {
+    let Some(url) = &config.notify_webhook else {
+        return;
+    };
+    if !alert.should_fire() {
+        return;
+    }
+
+    let body = payload(&config.notify_format, alert).to_string();
+
+    if config.notify_dry_run {
+        logs.push(format!("  [dry run] Notification payload: {}", body));
+        return;
+    }
+
+    match http::post_json(config, url, &body, &[]) {
+        Ok(()) => logs.push("  Notification sent".to_string()),
+        Err(e) => logs.push(format!("  ⚠ Notification delivery failed: {}", e)),
+    }
+}
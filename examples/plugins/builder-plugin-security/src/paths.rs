@@ -0,0 +1,188 @@
+use std::path::{Path, PathBuf};
+
+/// Convert Windows separators to forward slashes.
+///
+/// Builder on Windows may send `src\requirements.txt` or mix both styles;
+/// every filename check runs on the normalized form.
+pub fn normalize_separators(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// Whether a (normalized) path starts with a drive letter, e.g. `C:/work`.
+pub fn is_drive_absolute(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'/'
+}
+
+/// Whether a (normalized) path is absolute on any platform: rooted at `/`
+/// or at a drive letter.
+pub fn is_absolute(path: &str) -> bool {
+    path.starts_with('/') || is_drive_absolute(path)
+}
+
+/// Join a source entry onto the workspace root.
+///
+/// Drive-letter paths under the root (compared case-insensitively, as on
+/// Windows) are rewritten relative to it, so the result is the same whether
+/// Builder sent `C:\work\app\requirements.txt` or `app\requirements.txt`.
+/// Other absolute paths are used as-is, rather than joined, on every
+/// platform.
+pub fn join_source(root: &Path, source: &str) -> PathBuf {
+    let source = normalize_separators(source);
+
+    if is_drive_absolute(&source) {
+        let root_str = normalize_separators(&root.to_string_lossy());
+        let root_str = root_str.trim_end_matches('/');
+
+        let prefix_len = root_str.len();
+        let under_root = source.len() > prefix_len
+            && source.as_bytes()[prefix_len] == b'/'
+            && source[..prefix_len].eq_ignore_ascii_case(root_str);
+
+        if under_root {
+            return root.join(&source[prefix_len + 1..]);
+        }
+        return PathBuf::from(source);
+    }
+    if source.starts_with('/') {
+        return PathBuf::from(source);
+    }
+
+    root.join(source)
+}
+
+/// A source entry relative to the workspace root, with `/` separators and
+/// `.` and `..` segments resolved, as globs and reports see it. Entries
+/// outside the root are returned whole. An absolute entry that only reaches
+/// the (canonical) root through a symlink is matched by its canonical form.
+pub fn workspace_relative(root: &Path, source: &str) -> String {
+    let root_str = normalize_separators(&root.to_string_lossy());
+    let root_str = root_str.trim_end_matches('/');
+    let under_root = |path: &str| {
+        let rest = path.strip_prefix(root_str)?;
+        if rest.is_empty() {
+            return Some(".".to_string());
+        }
+        rest.strip_prefix('/').map(str::to_string)
+    };
+
+    let joined = join_source(root, source);
+    let lexical = resolve_dots(&normalize_separators(&joined.to_string_lossy()));
+    if let Some(relative) = under_root(&lexical) {
+        return relative;
+    }
+    if is_absolute(&normalize_separators(source)) {
+        let canonical = joined.canonicalize().ok().map(|c| normalize_separators(&c.to_string_lossy()));
+        if let Some(relative) = canonical.as_deref().and_then(under_root) {
+            return relative;
+        }
+    }
+    lexical
+}
+
+/// Drop `.` segments and fold `..` into the segment before it, textually.
+/// `..` that climbs above a relative path's start is kept; above an
+/// absolute path's root it is dropped, as the filesystem would.
+fn resolve_dots(path: &str) -> String {
+    let (prefix, rest) = match path {
+        _ if is_drive_absolute(path) => path.split_at(3),
+        _ if path.starts_with('/') => path.split_at(1),
+        _ => ("", path),
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in rest.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if segments.last().is_some_and(|last| *last != "..") => {
+                segments.pop();
+            }
+            ".." if !prefix.is_empty() => {}
+            segment => segments.push(segment),
+        }
+    }
+    format!("{}{}", prefix, segments.join("/"))
+}
+
+/// Strip a trailing `\n` or `\r\n` from a line read with `read_until`.
+pub fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn normalizes_backslashes_and_mixed_separators() {
+        assert_eq!(normalize_separators(r"app\requirements.txt"), "app/requirements.txt");
+        assert_eq!(normalize_separators(r"app\sub/go.mod"), "app/sub/go.mod");
+        assert!(normalize_separators(r"web\package.json").ends_with("package.json"));
+    }
+
+    #[test]
+    fn detects_drive_letter_paths() {
+        assert!(is_drive_absolute("C:/work/requirements.txt"));
+        assert!(is_drive_absolute("d:/x"));
+        assert!(!is_drive_absolute("C:relative"));
+        assert!(!is_drive_absolute("/home/user"));
+        assert!(!is_drive_absolute("requirements.txt"));
+    }
+
+    #[test]
+    fn joins_drive_letter_sources_under_the_root() {
+        let root = Path::new(r"C:\Work\Project");
+        assert_eq!(
+            join_source(root, r"c:\work\project\app\requirements.txt"),
+            root.join("app/requirements.txt")
+        );
+        assert_eq!(join_source(root, r"app\requirements.txt"), root.join("app/requirements.txt"));
+    }
+
+    #[test]
+    fn keeps_drive_letter_sources_outside_the_root() {
+        let root = Path::new(r"C:\Work\Project");
+        assert_eq!(
+            join_source(root, r"D:\other\requirements.txt"),
+            PathBuf::from("D:/other/requirements.txt")
+        );
+        // A sibling directory sharing the root's name as a prefix is not under it
+        assert_eq!(
+            join_source(root, r"C:\Work\Project2\go.mod"),
+            PathBuf::from("C:/Work/Project2/go.mod")
+        );
+    }
+
+    #[test]
+    fn reconciles_absolute_relative_and_dotted_sources() {
+        let root = crate::testutil::scratch_dir("paths");
+        fs::create_dir_all(root.join("app")).unwrap();
+        let root = root.canonicalize().unwrap();
+        let absolute = format!("{}/app/requirements.txt", normalize_separators(&root.to_string_lossy()));
+
+        for source in [
+            "app/requirements.txt",
+            "./app/requirements.txt",
+            "app/./lib/../requirements.txt",
+            r"app\requirements.txt",
+            absolute.as_str(),
+        ] {
+            assert_eq!(workspace_relative(&root, source), "app/requirements.txt", "{}", source);
+        }
+        assert_eq!(join_source(&root, &absolute), PathBuf::from(&absolute));
+        assert_eq!(workspace_relative(&root, "/etc/../etc/passwd"), "/etc/passwd");
+        assert_eq!(workspace_relative(&root, "/../etc/passwd"), "/etc/passwd");
+        let outside = workspace_relative(&root, "app/../../escape.txt");
+        assert!(is_absolute(&outside) && outside.ends_with("/escape.txt"), "{}", outside);
+        assert_eq!(resolve_dots("../../a/./b"), "../../a/b");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn strips_crlf_and_lf_line_endings() {
+        assert_eq!(strip_line_ending("django==2.2.0\r\n"), "django==2.2.0");
+        assert_eq!(strip_line_ending("django==2.2.0\n"), "django==2.2.0");
+        assert_eq!(strip_line_ending("django==2.2.0"), "django==2.2.0");
+    }
+}
@@ -0,0 +1,270 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+use crate::advisories::Estimate;
+use crate::config::{ScanConfig, CONFIG_FILE_NAME};
+use crate::ecosystems::Dependency;
+use crate::fsutil;
+use crate::globs;
+use crate::{Scanner, Workspace};
+
+const USAGE: &str = "usage: builder-plugin-security plan [--workspace DIR] [--json] [FILE...]";
+
+/// What a scan would cover, as a dry run (`dry_run`) finds it: the
+/// dependency files found and read exactly as a scan finds and reads them,
+/// the advisory data it would refresh, and what would decide it.
+#[derive(Serialize, Debug, JsonSchema)]
+pub struct Plan {
+    /// The dependency files that would be parsed.
+    pub files: Vec<PlannedFile>,
+    pub ecosystems: Vec<PlannedEcosystem>,
+    /// Dependencies read from the files, a package in several files once
+    /// per file.
+    pub dependencies: usize,
+    /// Distinct package versions that would be matched.
+    pub packages: usize,
+    /// The advisory sources that would be refreshed.
+    pub databases: Vec<Estimate>,
+    /// The databases' bytes, summed.
+    pub download_bytes: u64,
+    /// What would decide pass or fail: the named policy or the thresholds,
+    /// and every other setting that can fail the scan.
+    pub policies: Vec<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq, JsonSchema)]
+pub struct PlannedFile {
+    pub path: String,
+    pub ecosystem: &'static str,
+    pub size: u64,
+    pub dependencies: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq, JsonSchema)]
+pub struct PlannedEcosystem {
+    pub ecosystem: &'static str,
+    pub files: usize,
+    pub dependencies: usize,
+    pub packages: usize,
+}
+
+impl Plan {
+    pub fn new(files: Vec<PlannedFile>, deps: &[Dependency], databases: Vec<Estimate>, policies: Vec<String>) -> Self {
+        let mut ecosystems: BTreeMap<&'static str, PlannedEcosystem> = BTreeMap::new();
+        for file in &files {
+            let entry = ecosystems.entry(file.ecosystem).or_insert(PlannedEcosystem {
+                ecosystem: file.ecosystem,
+                files: 0,
+                dependencies: 0,
+                packages: 0,
+            });
+            entry.files += 1;
+            entry.dependencies += file.dependencies;
+        }
+        let packages: BTreeSet<(&str, &str, &str)> =
+            deps.iter().map(|dep| (dep.ecosystem.as_str(), dep.name.as_str(), dep.version.as_str())).collect();
+        for (ecosystem, ..) in &packages {
+            if let Some(entry) = ecosystems.get_mut(ecosystem) {
+                entry.packages += 1;
+            }
+        }
+        Plan {
+            dependencies: files.iter().map(|file| file.dependencies).sum(),
+            packages: packages.len(),
+            files,
+            ecosystems: ecosystems.into_values().collect(),
+            download_bytes: databases.iter().map(|database| database.bytes).sum(),
+            databases,
+            policies,
+        }
+    }
+
+    /// Log the plan as the scan's logs describe a scan.
+    pub fn log(&self, logs: &mut Vec<String>) {
+        logs.push(format!(
+            "  Would parse {} dependency files: {} dependencies, {} distinct packages",
+            self.files.len(),
+            self.dependencies,
+            self.packages
+        ));
+        for ecosystem in &self.ecosystems {
+            logs.push(format!(
+                "    {}: {} files, {} packages",
+                ecosystem.ecosystem, ecosystem.files, ecosystem.packages
+            ));
+        }
+        logs.push(format!(
+            "  Would refresh {} advisory sources, reading at most {} bytes",
+            self.databases.len(),
+            self.download_bytes
+        ));
+        for database in &self.databases {
+            let path = database.path.as_deref().unwrap_or("built in");
+            logs.push(format!("    {} ({}): {} bytes", database.setting, path, database.bytes));
+        }
+        logs.push(format!("  Would decide by: {}", self.policies.join("; ")));
+    }
+}
+
+/// The settings that would decide a scan by `config`, as the policy check
+/// applies them: a named policy, else `fail_on_regression`, else the
+/// severity thresholds; then the settings failing a scan whatever its
+/// findings.
+pub fn policies(config: &ScanConfig) -> Vec<String> {
+    let mut policies = Vec::new();
+    if let Some(name) = &config.policy {
+        match config.policies.get(name) {
+            Some(policy) => policies.push(format!("policy '{}' ({} rules)", name, policy.rules.len())),
+            None => policies.push(format!("policy '{}' (not defined; decides nothing)", name)),
+        }
+    } else if config.fail_on_regression {
+        policies.push("fail_on_regression".to_string());
+    } else {
+        let thresholds = [("fail_on_critical", config.fail_on_critical), ("fail_on_high", config.fail_on_high)];
+        policies.extend(thresholds.iter().filter(|(_, on)| *on).map(|(name, _)| name.to_string()));
+    }
+    if config.fail_on_introduced {
+        policies.push("fail_on_introduced".to_string());
+    }
+    if config.fail_on_partial {
+        policies.push("fail_on_partial".to_string());
+    }
+    if let Some(path) = &config.allowlist {
+        policies.push(format!("allowlist {}", path));
+    }
+    if config.on_error == "closed" {
+        policies.push("on_error = \"closed\"".to_string());
+    }
+    if !config.ignore_vulnerabilities.is_empty() {
+        policies.push(format!("{} ignored advisories", config.ignore_vulnerabilities.len()));
+    }
+    if policies.is_empty() {
+        policies.push("nothing; the scan always passes".to_string());
+    }
+    policies
+}
+
+/// `builder-plugin-security plan`: dry-run a scan of the files given, or of
+/// every file under the workspace, and print what it would cover, or with
+/// `--json` the plan as the hook result's `plan`. Directories an exclude
+/// glob covers whole aren't walked.
+pub fn cli(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut workspace = ".".to_string();
+    let mut json = false;
+    let mut files = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--workspace" => match args.next() {
+                Some(dir) => workspace = dir.clone(),
+                None => return usage(err),
+            },
+            "--json" => json = true,
+            file if !file.starts_with('-') => files.push(file.to_string()),
+            _ => return usage(err),
+        }
+    }
+
+    let root = Path::new(&workspace);
+    let mut config = match ScanConfig::resolve(Some(&root.join(CONFIG_FILE_NAME)), None, None, std::env::vars()) {
+        Ok(config) => config,
+        Err(e) => {
+            let _ = writeln!(err, "plan: {}", e);
+            return 2;
+        }
+    };
+    config.dry_run = true;
+    if files.is_empty() {
+        files = walk(root, &config.exclude);
+    }
+    let result = match Scanner::new(config).scan(&Workspace::new(root, files)) {
+        Ok(result) => result,
+        Err(e) => {
+            let _ = writeln!(err, "plan: {}", e);
+            return 2;
+        }
+    };
+    if json {
+        let _ = writeln!(out, "{}", serde_json::to_string_pretty(&result.plan).unwrap_or_default());
+    } else {
+        for line in &result.logs {
+            let _ = writeln!(out, "{}", line);
+        }
+    }
+    0
+}
+
+fn usage(err: &mut dyn Write) -> i32 {
+    let _ = writeln!(err, "{}", USAGE);
+    2
+}
+
+/// Every regular file below `root`, workspace-relative, without following
+/// symlinks or entering directories one of `exclude` covers whole
+/// (`**/node_modules/**`).
+fn walk(root: &Path, exclude: &[String]) -> Vec<String> {
+    let covered = |dir: &str| {
+        exclude.iter().filter_map(|glob| glob.strip_suffix("/**")).any(|prefix| globs::matches(prefix, dir))
+    };
+    let mut files = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fsutil::read_dir(root.join(&dir)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = if dir.is_empty() { name } else { format!("{}/{}", dir, name) };
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() && !covered(&path) => pending.push(path),
+                Ok(kind) if kind.is_file() => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn the_cli_walks_the_workspace_but_not_excluded_directories() {
+        let root = crate::testutil::scratch_dir("plan");
+        fs::create_dir_all(root.join("app/node_modules/left-pad")).unwrap();
+        fs::create_dir_all(root.join("vendor")).unwrap();
+        fs::write(root.join("app/requirements.txt"), "django==2.2.0\nrequests==2.25.0\n").unwrap();
+        fs::write(root.join("app/node_modules/left-pad/package.json"), "{}").unwrap();
+        fs::write(root.join("vendor/requirements.txt"), "flask==1.0\n").unwrap();
+        let config = "[policy]\nfail_on_critical = true\n\n[scan]\nexclude = [\"**/node_modules/**\", \"vendor/*\"]\n";
+        fs::write(root.join(CONFIG_FILE_NAME), config).unwrap();
+
+        let exclude = ["**/node_modules/**".to_string(), "vendor/*".to_string()];
+        let walked = walk(&root, &exclude);
+        // Only a `/**` glob prunes a directory; the rest are left to the scan
+        let expected = [CONFIG_FILE_NAME, "app/requirements.txt", "vendor/requirements.txt"];
+        assert_eq!(walked, expected);
+
+        let args = ["--workspace".to_string(), root.display().to_string(), "--json".to_string()];
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!(cli(&args, &mut out, &mut err), 0, "{}", String::from_utf8_lossy(&err));
+        let plan: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(plan["files"].as_array().unwrap().len(), 1, "{}", plan);
+        assert_eq!(plan["files"][0]["path"], "app/requirements.txt");
+        assert_eq!((plan["dependencies"].as_u64(), plan["packages"].as_u64()), (Some(2), Some(2)));
+        assert_eq!(plan["policies"], serde_json::json!(["fail_on_critical"]));
+        // Nothing is written besides what discovery reads
+        assert!(!root.join(".builder-cache").exists());
+
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        assert_eq!(cli(&["--verbose".to_string()], &mut out, &mut err), 2);
+        assert_eq!(String::from_utf8(err).unwrap(), format!("{}\n", USAGE));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,934 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::{self, ScanConfig};
+use crate::diag::Diagnostics;
+use crate::error::ScanError;
+use crate::report::{format_timestamp, ScannedFile};
+use crate::telemetry::{Attr, Tracer};
+use crate::unscanned::NotScanned;
+use crate::{advisories, bundle, cache, clock, doctor, fsutil, handshake, iotrace, logcap, paths, permissions, replay};
+use crate::{attestation, events, explain, fast, plan, schema, snapshots, status, stdio, stream, summary, triage};
+use crate::{describe_counts, log_policy_warnings, ScanResult, Scanner, SecurityScanner, Workspace, PLUGIN_VERSION};
+
+pub use crate::stdio::capture_stdout;
+
+/// Longest JSON-RPC request line read; longer ones are skipped unbuffered.
+const MAX_REQUEST_BYTES: usize = 64 * 1024 * 1024;
+
+/// The cargo features the binary was built with.
+const FEATURES: &[&str] = &[
+    #[cfg(feature = "archive-scan")]
+    "archive-scan",
+    #[cfg(feature = "attestation-signing")]
+    "attestation-signing",
+    #[cfg(feature = "network-sources")]
+    "network-sources",
+    #[cfg(feature = "otel")]
+    "otel",
+    #[cfg(feature = "sarif")]
+    "sarif",
+    #[cfg(feature = "templates")]
+    "templates",
+    #[cfg(feature = "zstd")]
+    "zstd",
+];
+
+#[derive(Serialize)]
+struct PluginInfo {
+    name: String,
+    version: String,
+    author: String,
+    description: String,
+    homepage: String,
+    capabilities: Vec<String>,
+    #[serde(rename = "minBuilderVersion")]
+    min_builder_version: String,
+    license: String,
+    /// The cargo features the plugin was built with, which decide what it
+    /// can do beyond scanning offline.
+    features: &'static [&'static str],
+    /// Plugin API the responses follow: the host's `maxPluginApi`, up to
+    /// the plugin's own.
+    #[serde(rename = "pluginApi")]
+    plugin_api: u64,
+    /// What that plugin API leaves out.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    disabled: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<handshake::Warning>,
+    /// The notifications the plugin will send, of those the host asked for.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    notifications: Vec<&'static str>,
+    /// The `event.notify` events the plugin reacts to, so the host can
+    /// leave the others unsent.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    subscriptions: &'static [&'static str],
+    /// The advisory sources' freshness and health, once a scan has created
+    /// the cache it is read from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<status::Status>,
+    /// What the hooks read, write and connect to, with the configuration
+    /// the params resolve to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permissions: Option<permissions::Permissions>,
+}
+
+/// Run the `builder-plugin-security` binary with its arguments: one of its
+/// subcommands, or by default the JSON-RPC loop Builder talks to, reading
+/// requests from `input` and writing responses to `out`, which carries
+/// nothing else (see [`stdio::ProtocolOut`]). Diagnostics go to `err`, a
+/// request's batch after its response. Returns the process exit code.
+pub fn run(args: &[String], input: &mut dyn BufRead, out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    match args.first().map(String::as_str) {
+        Some("triage") => return triage::run(&args[1..], input, out, err),
+        Some("doctor") => return doctor::cli(&args[1..], out, err),
+        Some("explain") => return explain::cli(&args[1..], out, err),
+        Some("snapshots") => return snapshots::cli(&args[1..], out, err),
+        Some("schema") => return schema::cli(&args[1..], out, err),
+        Some("plan") => return plan::cli(&args[1..], out, err),
+        Some("unbundle") => return bundle::cli(&args[1..], out, err),
+        Some("--fast") => return fast::cli(&args[1..], out, err),
+        _ => {}
+    }
+    if args.iter().any(|a| a == "--daemon") {
+        advisories::keep_warm();
+    }
+    if args.iter().any(|a| a == "--replay") {
+        let mut session = handshake::Session::default();
+        // Written once the replay is done, as the handler can't share `err`
+        let mut diagnostics = Diagnostics::default();
+        let handle = |request| handle_request(&mut session, request, &mut io::sink(), &mut diagnostics);
+        let code = replay::run(args, handle, out, err);
+        let _ = diagnostics.flush(err);
+        return code;
+    }
+
+    let trace_path = args
+        .iter()
+        .position(|a| a == "--trace-io")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| std::env::var(iotrace::TRACE_ENV).ok().filter(|p| !p.is_empty()));
+    let mut diagnostics = Diagnostics::default();
+    let trace = trace_path.and_then(|path| {
+        let redact = std::env::var(iotrace::REDACT_ENV).ok();
+        iotrace::IoTrace::open(&path, redact.as_deref())
+            .inspect_err(|e| diagnostics.error("iotrace", format!("Cannot open trace file {}: {}", path, e)))
+            .ok()
+    });
+    let _ = diagnostics.flush(err);
+
+    let mut out = stdio::ProtocolOut::new(out);
+    let mut session = handshake::Session::default();
+    let mut buf = Vec::new();
+    loop {
+        let _ = diagnostics.flush(err);
+        buf.clear();
+        match fsutil::read_line_capped(input, &mut buf, MAX_REQUEST_BYTES) {
+            Ok(None) => break,
+            Ok(Some(false)) => {}
+            Ok(Some(true)) => {
+                diagnostics.warn("plugin", format!("Request longer than {} bytes ignored", MAX_REQUEST_BYTES));
+                continue;
+            }
+            Err(e) => {
+                diagnostics.error("plugin", format!("Read error: {}", e));
+                break;
+            }
+        }
+        let line = match std::str::from_utf8(&buf) {
+            Ok(line) => paths::strip_line_ending(line),
+            Err(e) => {
+                diagnostics.error("plugin", format!("Read error: {}", e));
+                continue;
+            }
+        };
+        // serde_json rejects documents nested more than 128 levels deep
+        let response = match serde_json::from_str::<Value>(line) {
+            Ok(request) => {
+                if let Some(trace) = &trace {
+                    trace.message(iotrace::Direction::Inbound, &request);
+                }
+                let response = handle_request(&mut session, request, &mut out, &mut diagnostics);
+                if response.is_null() {
+                    continue;
+                }
+                response
+            }
+            Err(e) => {
+                if let Some(trace) = &trace {
+                    trace.unparseable(line, &e);
+                }
+                diagnostics.error("plugin", format!("Parse error: {}", e));
+                // The id can't be read from a line that isn't JSON
+                ScanError::ParseError(e.to_string()).to_response(&Value::Null)
+            }
+        };
+        if let Some(trace) = &trace {
+            trace.message(iotrace::Direction::Outbound, &response);
+        }
+        if let Err(e) = writeln!(out, "{}", serde_json::to_string(&response).unwrap()) {
+            diagnostics.error("plugin", format!("Could not write response: {}", e));
+        }
+    }
+    let _ = diagnostics.flush(err);
+    0
+}
+
+/// Answer one JSON-RPC request. The notifications the host asked for go to
+/// `notifications` before the response; diagnostics for the host's
+/// operator go to `diagnostics`. An `event.notify` notification is taken in
+/// and answered with `null`, which is not sent.
+pub(crate) fn handle_request(
+    session: &mut handshake::Session,
+    request: Value,
+    notifications: &mut dyn Write,
+    diagnostics: &mut Diagnostics,
+) -> Value {
+    let method = request["method"].as_str().unwrap_or("");
+    let id = &request_id(&request);
+    let params = request.get("params");
+
+    if method == events::NOTIFY_METHOD {
+        // Unknown events, and those of a host the params don't resolve for, are dropped quietly
+        if let (true, Ok((root, config))) = (session.allows(method), request_config(params)) {
+            session.pending().notify(params, Path::new(&root), &config);
+        }
+        return Value::Null;
+    }
+    if !session.allows(method) {
+        return ScanError::MethodNotFound(format!("{} (needs a newer plugin API than the host's)", method))
+            .to_response(id);
+    }
+    let hook = matches!(method, "build.pre_hook" | "workspace.pre_hook" | "build.post_hook" | "test.pre_hook");
+    if let Some(host) = session.unsupported_host().filter(|_| hook) {
+        // Only the hook's own config knows whether to refuse it
+        if request_config(params).is_ok_and(|(_, config)| config.strict_version_check) {
+            let required = handshake::MIN_BUILDER_VERSION;
+            return ScanError::VersionUnsupported { host: host.to_string(), required }.to_response(id);
+        }
+    }
+
+    // With strict_permissions, the hook may touch only what it declares
+    let guard = match hook.then(|| request_scope(params)) {
+        Some(Ok((scope, config))) if config.strict_permissions => Some(permissions::enforce(scope)),
+        _ => None,
+    };
+
+    let findings = session.notifies(stream::FINDING_NOTIFICATION).then_some(notifications);
+    let mut response = match method {
+        "plugin.info" => handle_info(id, params, session, diagnostics),
+        "build.pre_hook" => handle_pre_hook(id, params, findings),
+        "workspace.pre_hook" => handle_workspace_pre_hook(id, params, findings),
+        "build.post_hook" => {
+            let mut response = handle_post_hook(id, params);
+            session.pending().flush(&mut response);
+            response
+        }
+        "test.pre_hook" => handle_test_pre_hook(id, params, findings),
+        "plugin.configure" => handle_configure(id, params),
+        "plugin.doctor" => handle_doctor(id, params),
+        "cache.clear" => handle_cache_clear(id, params),
+        "cache.stats" => handle_cache_stats(id, params),
+        "plugin.schema" => handle_schema(id, params),
+        "plugin.reload_db" => handle_reload_db(id, params),
+        other => ScanError::MethodNotFound(other.to_string()).to_response(id),
+    };
+    if let Some(refused) = guard.map(|guard| guard.refused()).filter(|refused| !refused.is_empty()) {
+        response = ScanError::PermissionDenied(refused).to_response(id);
+    }
+    if hook {
+        session.downgrade(&mut response);
+    }
+    response
+}
+
+/// The request's `id`, echoed as sent: a string, or a number whatever its
+/// size or fraction. Ids of other types, and a missing one, are `null`.
+fn request_id(request: &Value) -> Value {
+    match request.get("id") {
+        Some(id @ (Value::String(_) | Value::Number(_))) => id.clone(),
+        _ => Value::Null,
+    }
+}
+
+/// Describe the plugin, and take in what the host says about itself: its
+/// `builderVersion`, checked against `minBuilderVersion`, and the
+/// `maxPluginApi` later responses are held to. Warnings and disabled
+/// features also go to `diagnostics`.
+fn handle_info(
+    id: &Value,
+    params: Option<&Value>,
+    session: &mut handshake::Session,
+    diagnostics: &mut Diagnostics,
+) -> Value {
+    let warnings = session.handshake(params);
+    for warning in &warnings {
+        diagnostics.warn("handshake", &warning.detail);
+    }
+    let disabled = session.disabled();
+    for feature in &disabled {
+        diagnostics.info("handshake", format!("Disabled for plugin API {}: {}", session.api(), feature));
+    }
+    let capabilities = [
+        "build.pre_hook",
+        "workspace.pre_hook",
+        "build.post_hook",
+        "test.pre_hook",
+        "plugin.configure",
+        "plugin.doctor",
+        "cache.clear",
+        "cache.stats",
+        "plugin.schema",
+        "plugin.reload_db",
+    ];
+    let info = PluginInfo {
+        name: "security".to_string(),
+        version: PLUGIN_VERSION.to_string(),
+        author: "Griffin".to_string(),
+        description: "Dependency vulnerability scanner".to_string(),
+        homepage: "https://github.com/GriffinCanCode/Builder".to_string(),
+        capabilities: capabilities.iter().filter(|method| session.allows(method)).map(|m| m.to_string()).collect(),
+        min_builder_version: handshake::MIN_BUILDER_VERSION.to_string(),
+        license: "MIT".to_string(),
+        features: FEATURES,
+        plugin_api: session.api(),
+        disabled,
+        warnings,
+        notifications: session.notifications().to_vec(),
+        subscriptions: if session.allows(events::NOTIFY_METHOD) { events::SUBSCRIPTIONS } else { &[] },
+        status: request_config(params)
+            .ok()
+            .and_then(|(root, config)| status::status(Path::new(&root), &config, SystemTime::now())),
+        permissions: request_scope(params).ok().map(|(scope, config)| scope.declare(config.strict_permissions)),
+    };
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": info
+    })
+}
+
+/// Scan the target's sources with [`Scanner::scan`], with the configuration
+/// the params resolve to, sending `plugin.finding` notifications to
+/// `findings` when the host asked for them.
+pub(crate) fn handle_pre_hook(id: &Value, params: Option<&Value>, findings: Option<&mut dyn Write>) -> Value {
+    let Some((params, target, workspace)) =
+        params.and_then(|params| Some((params, params.get("target")?, params.get("workspace")?)))
+    else {
+        let result = ScanResult::empty(vec!["[Security] Initializing security scan".to_string()]);
+        return json!({"jsonrpc": "2.0", "id": id, "result": result});
+    };
+    let root = workspace.get("root").and_then(|r| r.as_str()).unwrap_or(".");
+
+    let config_file = config_file_path(root, params);
+    let mut config = match ScanConfig::resolve(
+        Some(&config_file),
+        workspace.get("config"),
+        params.get("target_config"),
+        std::env::vars(),
+    ) {
+        Ok(config) => config,
+        Err(e) => return ScanError::ConfigInvalid(e).to_response(id),
+    };
+    pin_snapshot(&mut config, params);
+
+    let workspace = hook_workspace(root, target, params);
+    let scanner = Scanner::new(config);
+    let result = if workspace.roots.is_empty() {
+        scanner.scan_with(&workspace, findings).map(|result| json!(result))
+    } else {
+        scanner.scan_roots_with(&workspace, findings).map(|result| json!(result))
+    };
+    match result {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }),
+        Err(e) => e.to_response(id),
+    }
+}
+
+/// The workspace a hook's params describe: the target's sources under the
+/// workspace root, and the `workspace.roots` under it, if any.
+fn hook_workspace(root: &str, target: &Value, params: &Value) -> Workspace {
+    let strings = |value: Option<&Value>| -> Vec<String> {
+        let values = value.and_then(|v| v.as_array()).into_iter().flatten();
+        values.filter_map(|v| v.as_str().map(str::to_string)).collect()
+    };
+    let text = |key: &str| target.get(key).and_then(|v| v.as_str()).map(str::to_string);
+
+    let mut workspace = Workspace::new(root, strings(target.get("sources")));
+    workspace.target = text("name");
+    workspace.language = text("language");
+    workspace.kind = text("type");
+    workspace.changed_files = params.get("changed_files").map(|files| strings(Some(files)));
+    workspace.roots = strings(params.get("workspace").and_then(|w| w.get("roots")));
+    workspace
+}
+
+/// Decide the target by `test_policy` before its tests run.
+///
+/// The build pre_hook may not have run for the build under test (a cache
+/// hit skips it), so the hook doesn't count on its result: it decides the
+/// newest report in the target's history, and scans the target itself
+/// only when there is none. The result has the shape of a `build.pre_hook`
+/// result, with `scanned` telling whether this hook scanned and `reused`
+/// naming the report it decided otherwise.
+pub(crate) fn handle_test_pre_hook(id: &Value, params: Option<&Value>, findings: Option<&mut dyn Write>) -> Value {
+    let mut logs = vec!["[Security] Checking security before tests".to_string()];
+    let (workspace_root, mut config) = match request_config(params) {
+        Ok(resolved) => resolved,
+        Err(e) => return e.to_response(id),
+    };
+    if let Some(name) = config.test_policy.clone() {
+        config.policy = Some(name);
+    }
+    let target = params.and_then(|p| p.get("target"));
+    let name = target.and_then(|t| t.get("name")).and_then(|n| n.as_str());
+    let cache_root = cache::cache_root(Path::new(&workspace_root), &config.report_dir);
+    let history = match name {
+        Some(name) if config.report_history > 0 => cache::history(&cache_root, name),
+        _ => Vec::new(),
+    };
+
+    if let Some((archived_at, report)) = history.first() {
+        if let Err(e) = snapshots::check_pinned(Path::new(&workspace_root), &config) {
+            return ScanError::ConfigInvalid(e).to_response(id);
+        }
+        let mut scanner = SecurityScanner::new(workspace_root.clone(), config.clone());
+        scanner.target_name = name.map(str::to_string);
+        scanner.changed_files = params.and_then(|params| changed_files(&scanner.workspace_root, params));
+        match scanner.decide_archived(&history, &mut logs) {
+            Ok(result) => {
+                let scanned_at = format_timestamp(*archived_at);
+                let reused = json!({"report": report.display().to_string(), "scanned_at": scanned_at});
+                return json!({"jsonrpc": "2.0", "id": id, "result": test_hook_result(result, Some(reused))});
+            }
+            Err(e) => logs.push(format!("  ⚠ Previous scan unreadable, scanning again: {}", e)),
+        }
+    } else {
+        logs.push(format!("  No previous scan of {} to reuse; scanning it now", name.unwrap_or("the workspace")));
+    }
+
+    let no_params = json!({});
+    let params = params.unwrap_or(&no_params);
+    let workspace = hook_workspace(&workspace_root, target.unwrap_or(&no_params), params);
+    match Scanner::new(config).scan_with(&workspace, findings) {
+        Ok(mut result) => {
+            // The build's post_hook already ran; the next one mustn't report
+            // this scan as its build's
+            if let Some(name) = name {
+                if let Err(e) = summary::discard(&summary::summaries_dir(&cache_root), name) {
+                    result.logs.push(format!("  ⚠ Could not discard the scan summary: {}", e));
+                }
+            }
+            logs.append(&mut result.logs);
+            result.logs = logs;
+            json!({"jsonrpc": "2.0", "id": id, "result": test_hook_result(result, None)})
+        }
+        Err(e) => e.to_response(id),
+    }
+}
+
+/// A `test.pre_hook` result: the scan result with whether the hook scanned
+/// and, when it didn't, the report it reused.
+fn test_hook_result(result: ScanResult, reused: Option<Value>) -> Value {
+    let mut result = json!(result);
+    result["scanned"] = json!(reused.is_none());
+    result["reused"] = json!(reused);
+    result
+}
+
+/// Scan every target of a workspace build in one pass.
+///
+/// The dependency files of all targets are read and matched once; each
+/// target then gets the slice of the findings in its own files, its own
+/// policy decision and a summary for its post_hook. Targets without
+/// dependency files get an empty entry rather than none.
+pub(crate) fn handle_workspace_pre_hook(id: &Value, params: Option<&Value>, findings: Option<&mut dyn Write>) -> Value {
+    let mut logs = vec!["[Security] Initializing workspace security scan".to_string()];
+    let (workspace_root, config) = match request_config(params) {
+        Ok(resolved) => resolved,
+        Err(e) => return e.to_response(id),
+    };
+    if let Err(source) = fsutil::read_dir(&workspace_root) {
+        let path = workspace_root;
+        return ScanError::WorkspaceUnreadable { path, source }.to_response(id);
+    }
+    if let Err(e) = snapshots::check_pinned(Path::new(&workspace_root), &config) {
+        return ScanError::ConfigInvalid(e).to_response(id);
+    }
+
+    let mut scanner = SecurityScanner::new(workspace_root, config);
+    scanner.changed_files = params.and_then(|params| changed_files(&scanner.workspace_root, params));
+    let mut targets: Vec<(String, BTreeSet<String>)> = Vec::new();
+    // Every target's sources once, in the order they first appear
+    let mut union: Vec<String> = Vec::new();
+    let mut seen: BTreeSet<String> = BTreeSet::new();
+    for target in params.and_then(|p| p.get("targets")).and_then(|t| t.as_array()).into_iter().flatten() {
+        let Some(name) = target.get("name").and_then(|n| n.as_str()) else {
+            logs.push("  ⚠ Ignoring a target without a name".to_string());
+            continue;
+        };
+        let sources: BTreeSet<String> = target
+            .get("sources")
+            .and_then(|s| s.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|source| source.as_str())
+            .map(|source| paths::workspace_relative(&scanner.workspace_root, source))
+            .collect();
+        union.extend(sources.iter().filter(|source| seen.insert(source.to_string())).cloned());
+        targets.push((name.to_string(), sources));
+    }
+    logs.push(format!("  {} targets, {} distinct sources", targets.len(), union.len()));
+
+    scanner.tracer = Tracer::from_config(&scanner.config, |key| std::env::var(key).ok());
+    let hook_span = scanner.tracer.start("security.workspace_pre_hook");
+    logs.extend(scanner.consider_ecosystems(None, None));
+    if scanner.config.dry_run {
+        let (plan, plan_logs) = scanner.plan(&union);
+        logs.extend(plan_logs);
+        return json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "success": true,
+                "logs": logs,
+                "plan": plan,
+                "skipped": scanner.skipped,
+                "not_scanned": scanner.not_scanned,
+                "partial": !scanner.timed_out.is_empty(),
+                "timed_out": scanner.timed_out
+            }
+        });
+    }
+    logs.extend(scanner.scan_dependencies(&union, findings));
+    logs.extend(scanner.generate_report());
+    scanner.write_profile("workspace", &mut logs);
+    let outputs = scanner.outputs.clone();
+    let cache_root = cache::cache_root(&scanner.workspace_root, &scanner.config.report_dir);
+
+    let mut success = true;
+    let mut slices = serde_json::Map::new();
+    for (name, sources) in &targets {
+        let slice = scanner.as_target(name, sources, |scanner| {
+            let violations = scanner.policy_violations();
+            let summary = scanner.target_summary(name, &violations, outputs.clone());
+            if let Err(e) = summary::record(&summary::summaries_dir(&cache_root), &summary) {
+                logs.push(format!("  ⚠ Could not record scan summary for {}: {}", name, e));
+            }
+            let files: Vec<&ScannedFile> = scanner.scanned_files.iter().filter(|f| sources.contains(&f.path)).collect();
+            let not_scanned: Vec<&NotScanned> =
+                scanner.not_scanned.iter().filter(|n| sources.contains(&n.path)).collect();
+            json!({
+                "success": violations.is_empty() && scanner.failed_closed().is_none(),
+                "files": files,
+                "not_scanned": not_scanned,
+                "errors": scanner.result_errors(&violations),
+                "policy": scanner.evaluate_policy(),
+                "summary": summary,
+                "vulnerabilities": scanner.vulnerabilities,
+            })
+        });
+        if slice["success"] == false {
+            success = false;
+            if slice["errors"].as_array().into_iter().flatten().any(|e| e["kind"] == "policy_violated") {
+                logs.push(format!("  ⛔ {}: security policy violated", name));
+            }
+        }
+        slices.insert(name.clone(), slice);
+    }
+
+    let violations = scanner.policy_violations();
+    let workspace_summary = scanner.target_summary("workspace", &violations, outputs.clone());
+    let mut tracer = std::mem::replace(&mut scanner.tracer, Tracer::disabled());
+    tracer.end(hook_span, || {
+        vec![
+            ("security.targets", Attr::from(targets.len())),
+            ("security.files", Attr::from(scanner.scanned_files.len())),
+            ("security.findings", Attr::from(scanner.vulnerabilities.len())),
+        ]
+    });
+    tracer.export(&scanner.config, &mut logs);
+    let logs = logcap::cap(logs, scanner.config.log_line_limit, &logcap::full_log_path(&cache_root, "workspace"));
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": {
+            "success": success,
+            "logs": logs,
+            "targets": slices,
+            "summary": workspace_summary,
+            "skipped": scanner.skipped,
+            "not_scanned": scanner.not_scanned,
+            "partial": !scanner.timed_out.is_empty(),
+            "timed_out": scanner.timed_out,
+            "degraded": !scanner.degraded_reasons().is_empty(),
+            "degraded_reasons": scanner.degraded_reasons(),
+            "errors": scanner.result_errors(&[]),
+            "outputs": outputs
+        }
+    })
+}
+
+/// Resolve the configuration a pre_hook with the same params would use,
+/// without scanning, and report where every setting came from.
+pub(crate) fn handle_configure(id: &Value, params: Option<&Value>) -> Value {
+    match request_config(params) {
+        Ok((_, config)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "config": config,
+                "sources": config.setting_sources()
+            }
+        }),
+        Err(e) => e.to_response(id),
+    }
+}
+
+/// JSON Schemas of the plugin's outputs and input files: the one named by
+/// the `name` param, or all of them.
+pub(crate) fn handle_schema(id: &Value, params: Option<&Value>) -> Value {
+    let name = params.and_then(|p| p.get("name")).and_then(|n| n.as_str());
+    match schema::requested(name) {
+        Ok(schemas) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "schemas": schemas
+            }
+        }),
+        Err(e) => ScanError::ConfigInvalid(e).to_response(id),
+    }
+}
+
+/// Diagnose the plugin setup for a workspace. Problems are reported as
+/// failed checks, never as a JSON-RPC error.
+pub(crate) fn handle_doctor(id: &Value, params: Option<&Value>) -> Value {
+    let workspace_root = params
+        .and_then(|p| p.get("workspace"))
+        .and_then(|w| w.get("root"))
+        .and_then(|r| r.as_str())
+        .unwrap_or(".");
+    let config_file = config_file_path(workspace_root, params.unwrap_or(&Value::Null));
+    let config = request_config(params).map(|(_, config)| config).map_err(|e| e.to_string());
+
+    let checks = doctor::run(Path::new(workspace_root), &config_file, config);
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": {
+            "ok": checks.iter().all(|c| c.status != doctor::Status::Fail),
+            "checks": checks
+        }
+    })
+}
+
+/// Remove the scanner's advisory DB, scan and history caches.
+pub(crate) fn handle_cache_clear(id: &Value, params: Option<&Value>) -> Value {
+    let (workspace_root, config) = match request_config(params) {
+        Ok(resolved) => resolved,
+        Err(e) => return e.to_response(id),
+    };
+
+    let root = cache::cache_root(Path::new(&workspace_root), &config.report_dir);
+    match cache::clear(&root) {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }),
+        Err(e) => ScanError::Internal(format!("Failed to clear cache: {}", e)).to_response(id),
+    }
+}
+
+/// Report the size and age of each cache directory.
+pub(crate) fn handle_cache_stats(id: &Value, params: Option<&Value>) -> Value {
+    let (workspace_root, config) = match request_config(params) {
+        Ok(resolved) => resolved,
+        Err(e) => return e.to_response(id),
+    };
+
+    let root = cache::cache_root(Path::new(&workspace_root), &config.report_dir);
+    match cache::stats(&root) {
+        Ok(caches) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "root": root.display().to_string(),
+                "total_bytes": caches.iter().map(|c| c.bytes).sum::<u64>(),
+                "caches": caches
+            }
+        }),
+        Err(e) => ScanError::Internal(format!("Failed to read cache: {}", e)).to_response(id),
+    }
+}
+
+/// Read the advisory database again without restarting the plugin: what a
+/// `--daemon` process keeps in memory is dropped, and the sources the
+/// params configure are refreshed.
+pub(crate) fn handle_reload_db(id: &Value, params: Option<&Value>) -> Value {
+    let (workspace_root, config) = match request_config(params) {
+        Ok(resolved) => resolved,
+        Err(e) => return e.to_response(id),
+    };
+    if let Err(e) = snapshots::check_pinned(Path::new(&workspace_root), &config) {
+        return ScanError::ConfigInvalid(e).to_response(id);
+    }
+
+    match SecurityScanner::new(workspace_root, config).reload_db() {
+        Ok((databases, logs)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {"databases": databases, "logs": logs}
+        }),
+        Err(e) => e.to_response(id),
+    }
+}
+
+/// Resolve the workspace root and configuration for a request the way a
+/// pre_hook with the same params would.
+pub(crate) fn request_config(params: Option<&Value>) -> Result<(String, ScanConfig), ScanError> {
+    let workspace = params.and_then(|p| p.get("workspace"));
+    let workspace_root = workspace
+        .and_then(|w| w.get("root"))
+        .and_then(|r| r.as_str())
+        .unwrap_or(".");
+    let config_file = params.map(|p| config_file_path(workspace_root, p));
+
+    let mut config = ScanConfig::resolve(
+        config_file.as_deref(),
+        workspace.and_then(|w| w.get("config")),
+        params.and_then(|p| p.get("target_config")),
+        std::env::vars(),
+    )
+    .map_err(ScanError::ConfigInvalid)?;
+    if let Some(params) = params {
+        pin_snapshot(&mut config, params);
+    }
+    Ok((workspace_root.to_string(), config))
+}
+
+/// What a hook with these params may read, write and connect to. The build
+/// outputs and npm package cache it is given are read wherever they are,
+/// and so are sources outside the workspace if `allow_external_paths` lets
+/// them in.
+pub(crate) fn request_scope(params: Option<&Value>) -> Result<(permissions::Scope, ScanConfig), ScanError> {
+    let (workspace_root, config) = request_config(params)?;
+    let root = Path::new(&workspace_root);
+    let config_file = params.map(|p| config_file_path(&workspace_root, p));
+    let mut scope = permissions::Scope::of(root, &config, config_file.as_deref());
+    let outputs = params.and_then(|p| p.get("outputs")).and_then(|o| o.as_array()).into_iter().flatten();
+    let package_cache = params.and_then(|p| p.get("npm_package_cache")).into_iter();
+    for output in outputs.chain(package_cache).filter_map(|output| output.as_str()) {
+        scope.grant_read_path(root, &paths::join_source(root, output));
+    }
+    if config.allow_external_paths {
+        let targets = params.and_then(|p| p.get("targets")).and_then(|t| t.as_array()).into_iter().flatten();
+        for target in params.and_then(|p| p.get("target")).into_iter().chain(targets) {
+            let sources = target.get("sources").and_then(|s| s.as_array()).into_iter().flatten();
+            for source in sources.filter_map(|source| source.as_str()) {
+                scope.grant_read_path(root, &paths::join_source(root, source));
+            }
+        }
+    }
+    Ok((scope, config))
+}
+
+/// The `changed_files` param, workspace-relative; `None` without it, so a
+/// scan with an empty change still tells findings introduced or not.
+pub(crate) fn changed_files(workspace_root: &Path, params: &Value) -> Option<BTreeSet<String>> {
+    let files = params.get("changed_files")?.as_array()?;
+    let files = files.iter().filter_map(|file| file.as_str());
+    Some(files.map(|file| paths::workspace_relative(workspace_root, file)).collect())
+}
+
+/// A `db_snapshot` param pins the scan to a cached advisory database
+/// snapshot, over any configured one.
+pub(crate) fn pin_snapshot(config: &mut ScanConfig, params: &Value) {
+    if let Some(snapshot) = params.get("db_snapshot").and_then(|s| s.as_str()) {
+        config.db_snapshot = Some(snapshot.to_string());
+        config.origins.insert("db_snapshot", "params".to_string());
+    }
+}
+
+/// Config file for a request: `config_file` from params if given, otherwise
+/// `.builder-security.toml` in the workspace root. Relative paths are taken
+/// relative to the workspace root.
+pub(crate) fn config_file_path(workspace_root: &str, params: &Value) -> PathBuf {
+    let name = params
+        .get("config_file")
+        .and_then(|f| f.as_str())
+        .unwrap_or(config::CONFIG_FILE_NAME);
+    Path::new(workspace_root).join(name)
+}
+
+/// Report the pre_hook scan of the target as a compact `summary`.
+///
+/// When no pre_hook ran for this build (for example because Builder's cache
+/// skipped it), `scanned` is false and `summary` is null rather than the
+/// numbers of an earlier build.
+pub(crate) fn handle_post_hook(id: &Value, params: Option<&Value>) -> Value {
+    let mut logs = vec!["[Security] Post-build security check complete".to_string()];
+
+    let (workspace_root, config) = match request_config(params) {
+        Ok(resolved) => resolved,
+        Err(e) => return e.to_response(id),
+    };
+    if let Err(e) = snapshots::check_pinned(Path::new(&workspace_root), &config) {
+        return ScanError::ConfigInvalid(e).to_response(id);
+    }
+    if config.dry_run {
+        logs.push("  ℹ dry_run: the build outputs were not scanned".to_string());
+        return json!({"jsonrpc": "2.0", "id": id, "result": {"success": true, "logs": logs, "scanned": false}});
+    }
+    let target = params
+        .and_then(|p| p.get("target"))
+        .and_then(|t| t.get("name"))
+        .and_then(|n| n.as_str());
+    let build_secs = params
+        .and_then(|p| p.get("duration_ms"))
+        .and_then(|d| d.as_u64())
+        .unwrap_or(0)
+        / 1000;
+
+    let lookup = match target {
+        Some(target) => {
+            let dir = summary::summaries_dir(&cache::cache_root(Path::new(&workspace_root), &config.report_dir));
+            let now = clock::unix_secs(SystemTime::now());
+            summary::take(&dir, target, now, build_secs).unwrap_or_else(|e| {
+                logs.push(format!("  ⚠ Ignoring unreadable scan summary: {}", e));
+                summary::Lookup::Missing
+            })
+        }
+        None => summary::Lookup::Missing,
+    };
+
+    let (scanned, summary) = match lookup {
+        summary::Lookup::Found(summary) => {
+            let counts = describe_counts(&summary.counts);
+            logs.push(if counts.is_empty() {
+                "  ✓ No vulnerabilities".to_string()
+            } else {
+                format!("  {} ({} new)", counts, summary.new)
+            });
+            (true, Some(summary))
+        }
+        summary::Lookup::Stale(summary) => {
+            logs.push(format!(
+                "  ℹ Last scan of this target ({}) predates this build; no scan ran",
+                summary.scanned_at
+            ));
+            (false, None)
+        }
+        summary::Lookup::Missing => {
+            logs.push("  ℹ No security scan ran for this target in this build".to_string());
+            (false, None)
+        }
+    };
+
+    // Container filesystems among the outputs get their OS packages scanned
+    let outputs: Vec<String> = params
+        .and_then(|p| p.get("outputs"))
+        .and_then(|o| o.as_array())
+        .map(|arr| arr.iter().filter_map(|v| v.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    let mut scanner = SecurityScanner::new(workspace_root, config);
+    scanner.target_name = target.map(str::to_string);
+    let mut artifact_logs = scanner.scan_artifacts(&outputs);
+    artifact_logs.extend(scanner.check_install_scripts(&outputs));
+    if scanner.config.npm_provenance {
+        let package_cache = params.and_then(|p| p.get("npm_package_cache")).and_then(|c| c.as_str());
+        artifact_logs.extend(scanner.verify_npm_packages(package_cache));
+    }
+    let mut success = true;
+    let mut artifacts = None;
+    let mut errors = Vec::new();
+    let mut violations = Vec::new();
+    if !artifact_logs.is_empty() {
+        logs.extend(artifact_logs);
+        scanner.mark_known_exploited(&mut logs);
+        scanner.mark_in_grace(&mut logs);
+        violations = scanner.policy_violations();
+        errors = scanner.result_errors(&violations);
+        let evaluation = scanner.evaluate_policy();
+        if let Some(evaluation) = &evaluation {
+            log_policy_warnings(evaluation, &mut logs);
+        }
+        if !violations.is_empty() {
+            success = false;
+            logs.push("\n  ⛔ Security policy violated by the build outputs:".to_string());
+            for violation in &violations {
+                logs.push(format!("    - {}", violation));
+            }
+        }
+        artifacts = Some(json!({
+            "files": scanner.scanned_files,
+            "vulnerabilities": scanner.vulnerabilities,
+            "skipped": scanner.skipped,
+            "not_scanned": scanner.not_scanned,
+            "timed_out": scanner.timed_out,
+            "images": scanner.images,
+            "policy": evaluation,
+        }));
+    }
+
+    let mut attested = None;
+    if scanner.config.attestation {
+        let digests = params.and_then(|p| p.get("output_digests"));
+        let subject = attestation::subjects(&scanner.workspace_root, &outputs, digests, &mut logs);
+        if subject.is_empty() {
+            logs.push("  ⚠ No build outputs to attest; no attestation written".to_string());
+        } else {
+            let statement = attestation::Statement {
+                statement_type: attestation::STATEMENT_TYPE,
+                subject,
+                predicate_type: attestation::PREDICATE_TYPE,
+                predicate: attestation::predicate(target, summary.as_ref(), &violations),
+            };
+            match attestation::write(&scanner.workspace_root, &scanner.config, &statement) {
+                Ok(written) => {
+                    logs.push(format!("  Attestation written: {}", written.statement));
+                    // The statement stands unsigned; the scan's result is unchanged
+                    if let Some(e) = &written.signing_error {
+                        logs.push(format!("  ⚠ Attestation not signed: {}", e));
+                        errors.push(ScanError::SigningFailed(e.clone()).to_result_entry());
+                    }
+                    attested = Some(written);
+                }
+                Err(e) => logs.push(format!("  ⚠ Could not write the attestation: {}", e)),
+            }
+        }
+    }
+
+    let retention = cache::Retention {
+        reports_per_target: scanner.config.report_history,
+        db_budget_bytes: scanner.config.db_cache_budget_mb * 1024 * 1024,
+    };
+    cache::prune(&cache::cache_root(&scanner.workspace_root, &scanner.config.report_dir), &retention, &mut logs);
+
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "result": {
+            "success": success,
+            "logs": logs,
+            "scanned": scanned,
+            "summary": summary,
+            "artifacts": artifacts,
+            "attestation": attested,
+            "errors": errors
+        }
+    })
+}
+
@@ -0,0 +1,268 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::severity::Severity;
+
+/// Rule name recorded for findings no rule matched.
+pub const DEFAULT_RULE: &str = "default";
+
+/// What happens to the findings a rule matches.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Fail,
+    #[default]
+    Warn,
+    Ignore,
+}
+
+/// Which findings a rule applies to. Every condition that is set must hold,
+/// so an empty match applies to every finding.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleMatch {
+    /// Least severe severity matched (`HIGH` matches HIGH and CRITICAL).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<Severity>,
+    /// Most severe severity matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_severity: Option<Severity>,
+    /// Finding categories (`vulnerability`, `provenance`, ...); any when
+    /// empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<String>,
+    /// Ecosystems of the declaring file, as OSV names them; any when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ecosystems: Vec<String>,
+    /// CWE ids (`CWE-1333`), of which the finding must have one; `UNKNOWN`
+    /// matches findings without CWE data. Any when empty.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cwe: Vec<String>,
+    /// Whether the advisory is in the known-exploited catalog.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kev: Option<bool>,
+    /// Whether the finding is absent from the previous report.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<bool>,
+    /// Whether the target's sources import the package, with
+    /// `import_hints`; findings where that is unknown match neither value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imported: Option<bool>,
+    /// Whether the change in the pre_hook's `changed_files` introduced the
+    /// finding; findings of scans without them match neither value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub introduced: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    #[serde(default, rename = "match")]
+    pub matches: RuleMatch,
+    pub action: Action,
+}
+
+/// Ordered rules, the first matching one deciding a finding's action, and
+/// the action for findings none matches.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+    pub default: Action,
+}
+
+/// What rules see of a finding.
+pub struct Finding<'a> {
+    pub id: &'a str,
+    pub package: &'a str,
+    pub version: &'a str,
+    pub severity: Severity,
+    pub category: &'a str,
+    pub ecosystem: &'a str,
+    pub cwe: &'a [String],
+    pub kev: bool,
+    pub new: bool,
+    pub imported: Option<bool>,
+    pub introduced: Option<bool>,
+}
+
+/// The rule that decided one finding.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Decision {
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub rule: String,
+    pub action: Action,
+}
+
+/// Result of evaluating a policy, with the decision for every finding.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Evaluation {
+    pub policy: String,
+    pub passed: bool,
+    /// Findings per rule whose action is `fail`.
+    pub failed_by: BTreeMap<String, usize>,
+    /// Findings per rule whose action is `warn`.
+    pub warned_by: BTreeMap<String, usize>,
+    pub trace: Vec<Decision>,
+}
+
+impl RuleMatch {
+    fn matches(&self, finding: &Finding) -> bool {
+        // Severities order most severe first
+        self.min_severity.is_none_or(|min| finding.severity <= min)
+            && self.max_severity.is_none_or(|max| finding.severity >= max)
+            && (self.categories.is_empty() || self.categories.iter().any(|c| c == finding.category))
+            && (self.ecosystems.is_empty() || self.ecosystems.iter().any(|e| e == finding.ecosystem))
+            && (self.cwe.is_empty() || self.cwe.iter().any(|c| finding.cwe.iter().any(|f| f.eq_ignore_ascii_case(c))))
+            && self.kev.is_none_or(|kev| kev == finding.kev)
+            && self.new.is_none_or(|new| new == finding.new)
+            && self.imported.is_none_or(|imported| Some(imported) == finding.imported)
+            && self.introduced.is_none_or(|introduced| Some(introduced) == finding.introduced)
+    }
+}
+
+impl Policy {
+    /// Decide every finding by the first rule matching it. The policy fails
+    /// when any finding's action is `fail`.
+    pub fn evaluate(&self, name: &str, findings: &[Finding]) -> Evaluation {
+        let mut evaluation = Evaluation {
+            policy: name.to_string(),
+            passed: true,
+            failed_by: BTreeMap::new(),
+            warned_by: BTreeMap::new(),
+            trace: Vec::new(),
+        };
+        for finding in findings {
+            let (rule, action) = self
+                .rules
+                .iter()
+                .find(|rule| rule.matches.matches(finding))
+                .map_or((DEFAULT_RULE, self.default), |rule| (rule.name.as_str(), rule.action));
+            match action {
+                Action::Fail => *evaluation.failed_by.entry(rule.to_string()).or_default() += 1,
+                Action::Warn => *evaluation.warned_by.entry(rule.to_string()).or_default() += 1,
+                Action::Ignore => {}
+            }
+            evaluation.trace.push(Decision {
+                id: finding.id.to_string(),
+                package: finding.package.to_string(),
+                version: finding.version.to_string(),
+                rule: rule.to_string(),
+                action,
+            });
+        }
+        evaluation.passed = evaluation.failed_by.is_empty();
+        evaluation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(id: &str, severity: Severity, kev: bool, new: bool) -> Finding<'_> {
+        Finding {
+            id,
+            package: "pkg",
+            version: "1.0.0",
+            severity,
+            category: "vulnerability",
+            ecosystem: "npm",
+            cwe: &[],
+            kev,
+            new,
+            imported: None,
+            introduced: None,
+        }
+    }
+
+    #[test]
+    fn the_first_matching_rule_decides_and_is_traced() {
+        let policy: Policy = toml::from_str(
+            r#"
+            default = "ignore"
+
+            [[rules]]
+            name = "always-block-kev"
+            match = { kev = true }
+            action = "fail"
+
+            [[rules]]
+            name = "new-high"
+            match = { min_severity = "high", new = true }
+            action = "fail"
+
+            [[rules]]
+            name = "known-high"
+            match = { min_severity = "HIGH" }
+            action = "warn"
+            "#,
+        )
+        .unwrap();
+
+        let findings = [
+            finding("CVE-1", Severity::Low, true, false),
+            finding("CVE-2", Severity::Critical, false, true),
+            finding("CVE-3", Severity::High, false, false),
+            finding("CVE-4", Severity::Medium, false, true),
+        ];
+        let evaluation = policy.evaluate("release", &findings);
+        assert!(!evaluation.passed);
+        let rules: Vec<(&str, &str, Action)> =
+            evaluation.trace.iter().map(|d| (d.id.as_str(), d.rule.as_str(), d.action)).collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("CVE-1", "always-block-kev", Action::Fail),
+                ("CVE-2", "new-high", Action::Fail),
+                ("CVE-3", "known-high", Action::Warn),
+                ("CVE-4", DEFAULT_RULE, Action::Ignore),
+            ]
+        );
+        assert_eq!(evaluation.failed_by.len(), 2);
+        assert_eq!(evaluation.warned_by.get("known-high"), Some(&1));
+
+        let lenient = Policy::default().evaluate("dev", &findings[2..]);
+        assert!(lenient.passed);
+        assert_eq!(lenient.warned_by.get(DEFAULT_RULE), Some(&2));
+    }
+
+    #[test]
+    fn cwe_conditions_match_any_of_a_findings_ids() {
+        let policy: Policy = toml::from_str(
+            r#"
+            default = "fail"
+
+            [[rules]]
+            name = "redos"
+            match = { cwe = ["CWE-1333"] }
+            action = "warn"
+
+            [[rules]]
+            name = "memory-safety"
+            match = { cwe = ["CWE-119", "CWE-416", "cwe-787"] }
+            action = "fail"
+
+            [[rules]]
+            name = "untagged"
+            match = { cwe = ["UNKNOWN"] }
+            action = "ignore"
+            "#,
+        )
+        .unwrap();
+
+        let ids = |ids: &[&str]| -> Vec<String> { ids.iter().map(|id| id.to_string()).collect() };
+        let (redos, overflow, untagged) = (ids(&["CWE-400", "CWE-1333"]), ids(&["CWE-787"]), ids(&["UNKNOWN"]));
+        let findings = [("CVE-1", &redos), ("CVE-2", &overflow), ("CVE-3", &untagged)].map(|(id, cwe)| Finding {
+            cwe,
+            ..finding(id, Severity::High, false, false)
+        });
+        let evaluation = policy.evaluate("release", &findings);
+        let rules: Vec<&str> = evaluation.trace.iter().map(|d| d.rule.as_str()).collect();
+        assert_eq!(rules, ["redos", "memory-safety", "untagged"]);
+    }
+}
@@ -0,0 +1,259 @@
+use serde_json::{json, Value};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::fsutil;
+use crate::summary;
+
+/// How many of the slowest phases and files the debug logs list.
+const SLOWEST: usize = 10;
+
+/// Directory holding scan profiles, under the cache root.
+pub fn profiles_dir(cache_root: &Path) -> PathBuf {
+    cache_root.join("profiles")
+}
+
+/// When something profiled started; empty for a disabled profiler.
+#[derive(Clone, Copy)]
+pub struct Mark(Option<Instant>);
+
+struct Entry {
+    /// `phase`, or the phase a file was timed in.
+    category: &'static str,
+    name: String,
+    start: Duration,
+    duration: Duration,
+    bytes: Option<u64>,
+}
+
+struct Profile {
+    started: Instant,
+    entries: Vec<Entry>,
+}
+
+/// Records how long each scan phase and each dependency file in it took,
+/// and how large the files were, for `profile`.
+///
+/// Disabled, every call returns after a single branch: the clock isn't read
+/// and name closures are never run.
+pub struct Profiler {
+    profile: Option<Box<Profile>>,
+}
+
+impl Profiler {
+    pub fn disabled() -> Self {
+        Profiler { profile: None }
+    }
+
+    pub fn new(enabled: bool) -> Self {
+        if !enabled {
+            return Profiler::disabled();
+        }
+        Profiler {
+            profile: Some(Box::new(Profile {
+                started: Instant::now(),
+                entries: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn mark(&self) -> Mark {
+        Mark(self.profile.as_ref().map(|_| Instant::now()))
+    }
+
+    /// Record the phase `name`, from `mark` until now.
+    pub fn phase(&mut self, name: &'static str, mark: Mark) {
+        self.record("phase", mark, || (name.to_string(), None));
+    }
+
+    /// Record a file `phase` spent the time from `mark` until now on; `file`
+    /// gives its path and size.
+    pub fn file<F>(&mut self, phase: &'static str, mark: Mark, file: F)
+    where
+        F: FnOnce() -> (String, Option<u64>),
+    {
+        self.record(phase, mark, file);
+    }
+
+    fn record<F>(&mut self, category: &'static str, mark: Mark, entry: F)
+    where
+        F: FnOnce() -> (String, Option<u64>),
+    {
+        let (Some(profile), Mark(Some(started))) = (self.profile.as_mut(), mark) else {
+            return;
+        };
+        let (name, bytes) = entry();
+        profile.entries.push(Entry {
+            category,
+            name,
+            start: started.saturating_duration_since(profile.started),
+            duration: started.elapsed(),
+            bytes,
+        });
+    }
+
+    /// The slowest phases and files, for the debug logs.
+    pub fn log_slowest(&self, logs: &mut Vec<String>) {
+        let Some(profile) = &self.profile else {
+            return;
+        };
+        let (phases, files): (Vec<&Entry>, Vec<&Entry>) =
+            profile.entries.iter().partition(|entry| entry.category == "phase");
+        for (what, mut entries) in [("phases", phases), ("files", files)] {
+            if entries.is_empty() {
+                continue;
+            }
+            entries.sort_by(|a, b| b.duration.cmp(&a.duration).then_with(|| a.name.cmp(&b.name)));
+            logs.push(format!("  [debug] Slowest {}:", what));
+            for entry in entries.into_iter().take(SLOWEST) {
+                let mut line = format!("  [debug]   {:>8.1} ms  {}", entry.duration.as_secs_f64() * 1000.0, entry.name);
+                if entry.category != "phase" {
+                    line.push_str(&format!(" ({})", entry.category));
+                }
+                if let Some(bytes) = entry.bytes {
+                    line.push_str(&format!(", {} bytes", bytes));
+                }
+                logs.push(line);
+            }
+        }
+    }
+
+    /// Write the profile as a trace event file chrome://tracing and Perfetto
+    /// open, to `<target>.json` in `dir`. `None` when disabled.
+    pub fn write(&self, dir: &Path, target: &str) -> Option<io::Result<PathBuf>> {
+        let profile = self.profile.as_ref()?;
+        let path = dir.join(format!("{}.json", summary::file_stem(target)));
+        let written = fsutil::write_atomic(&path, profile.to_trace_events(target).to_string().as_bytes()).map(|_| path);
+        Some(written)
+    }
+}
+
+impl Profile {
+    /// The entries as complete (`X`) events of the Trace Event Format, in
+    /// microseconds since the scan started.
+    fn to_trace_events(&self, target: &str) -> Value {
+        let events: Vec<Value> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let mut event = json!({
+                    "name": entry.name,
+                    "cat": entry.category,
+                    "ph": "X",
+                    "ts": entry.start.as_micros() as u64,
+                    "dur": entry.duration.as_micros() as u64,
+                    "pid": 1,
+                    "tid": 1,
+                });
+                if let Some(bytes) = entry.bytes {
+                    event["args"] = json!({"bytes": bytes});
+                }
+                event
+            })
+            .collect();
+        json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+            "otherData": {"plugin": crate::PLUGIN_NAME, "version": crate::PLUGIN_VERSION, "target": target},
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::hint::black_box;
+
+    fn profiled() -> Profiler {
+        let mut profiler = Profiler::new(true);
+        let scan = profiler.mark();
+        for (file, bytes) in [("a/requirements.txt", 10), ("b/package-lock.json", 4000)] {
+            let mark = profiler.mark();
+            std::thread::sleep(Duration::from_millis(2));
+            profiler.file("extract", mark, || (file.to_string(), Some(bytes)));
+        }
+        profiler.phase("extract", scan);
+        profiler
+    }
+
+    #[test]
+    fn phases_and_files_become_trace_events_and_the_slowest_are_logged() {
+        let profiler = profiled();
+        let mut logs = Vec::new();
+        profiler.log_slowest(&mut logs);
+        assert_eq!(logs[0], "  [debug] Slowest phases:");
+        assert!(logs[1].ends_with("ms  extract"), "{:?}", logs);
+        assert_eq!(logs[2], "  [debug] Slowest files:");
+        assert!(logs[3..].iter().all(|line| line.contains(" (extract), ")), "{:?}", logs);
+
+        let dir = crate::testutil::scratch_dir("profiler-trace");
+        let path = profiler.write(&dir, "//app:main").unwrap().unwrap();
+        assert_eq!(path, dir.join("__app_main.json"));
+        let trace: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|e| e["ph"] == "X" && e["ts"].is_u64() && e["dur"].is_u64()));
+        // Files lie within their phase, so the viewer nests them under it
+        let (phase, file) = (&events[2], &events[1]);
+        assert_eq!((&phase["name"], &phase["cat"]), (&json!("extract"), &json!("phase")));
+        assert_eq!((&file["name"], &file["args"]["bytes"]), (&json!("b/package-lock.json"), &json!(4000)));
+        let end = |e: &Value| e["ts"].as_u64().unwrap() + e["dur"].as_u64().unwrap();
+        assert!(file["ts"].as_u64() >= phase["ts"].as_u64() && end(file) <= end(phase));
+        assert!(file["dur"].as_u64().unwrap() >= 2000);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_disabled_profiler_records_and_writes_nothing() {
+        let mut profiler = Profiler::new(false);
+        let mark = profiler.mark();
+        assert!(mark.0.is_none());
+        profiler.file("extract", mark, || unreachable!("names aren't built when disabled"));
+        profiler.phase("extract", mark);
+        let mut logs = Vec::new();
+        profiler.log_slowest(&mut logs);
+        assert!(logs.is_empty());
+        assert!(profiler.write(Path::new("/nonexistent"), "app").is_none());
+    }
+
+    /// Profiling a scan that doesn't ask for it costs a branch per call:
+    ///
+    ///   cargo test --release -- --ignored --nocapture profiler_overhead
+    #[test]
+    #[ignore]
+    fn profiler_overhead_benchmark() {
+        const CALLS: u32 = 10_000_000;
+
+        let time = |profiler: &mut Profiler| {
+            let started = Instant::now();
+            for i in 0..CALLS {
+                let mark = black_box(&*profiler).mark();
+                profiler.file("extract", mark, || (i.to_string(), Some(u64::from(i))));
+            }
+            started.elapsed()
+        };
+        let baseline = {
+            let started = Instant::now();
+            for i in 0..CALLS {
+                black_box(i);
+            }
+            started.elapsed()
+        };
+        let disabled = time(&mut Profiler::disabled());
+        let enabled = time(&mut Profiler::new(true));
+        let per_call = |elapsed: Duration| elapsed.as_nanos() as f64 / f64::from(CALLS);
+        println!(
+            "baseline {:.2} ns, disabled {:.2} ns, enabled {:.2} ns per file",
+            per_call(baseline),
+            per_call(disabled),
+            per_call(enabled)
+        );
+
+        // Next to reading even a small dependency file (microseconds), a
+        // nanosecond is nothing
+        assert!(per_call(disabled) - per_call(baseline) < 1.0);
+        assert!(disabled * 20 < enabled);
+    }
+}
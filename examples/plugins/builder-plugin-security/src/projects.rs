@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::ecosystems;
+use crate::fsutil;
+use crate::paths;
+
+/// Project of the workspace root itself.
+pub const ROOT_PROJECT: &str = ".";
+
+/// Finds the project each dependency file belongs to: the nearest directory,
+/// starting with the file's own, that contains a manifest the plugin reads
+/// (`requirements.txt`, `package.json`, `Cargo.toml`, `go.mod`). Lockfiles
+/// don't start a project, so `web/yarn.lock` belongs with
+/// `web/package.json`.
+///
+/// Projects are named by their workspace-relative directory; files outside
+/// every nested project belong to [`ROOT_PROJECT`].
+pub struct Projects {
+    root: PathBuf,
+    /// Whether a workspace-relative directory holds a manifest.
+    is_project: HashMap<String, bool>,
+}
+
+impl Projects {
+    pub fn new(root: &Path) -> Self {
+        Projects {
+            root: root.to_path_buf(),
+            is_project: HashMap::new(),
+        }
+    }
+
+    /// The project of a source entry. Files outside the workspace root are
+    /// their own project, named by their directory.
+    pub fn of(&mut self, source: &str) -> String {
+        let relative = paths::workspace_relative(&self.root, source);
+        let mut dir = match relative.rsplit_once('/') {
+            Some((dir, _)) => dir.to_string(),
+            None => return ROOT_PROJECT.to_string(),
+        };
+        if Path::new(&relative).is_absolute() || paths::is_drive_absolute(&relative) {
+            return dir;
+        }
+
+        loop {
+            if self.holds_manifest(&dir) {
+                return dir;
+            }
+            match dir.rsplit_once('/') {
+                Some((parent, _)) => dir = parent.to_string(),
+                None => return ROOT_PROJECT.to_string(),
+            }
+        }
+    }
+
+    fn holds_manifest(&mut self, dir: &str) -> bool {
+        if let Some(&known) = self.is_project.get(dir) {
+            return known;
+        }
+        let found = fsutil::read_dir(self.root.join(dir)).is_ok_and(|entries| {
+            entries.flatten().any(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                entry.path().is_file() && ecosystems::is_manifest(&format!("{}/{}", dir, name))
+            })
+        });
+        self.is_project.insert(dir.to_string(), found);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn files_belong_to_the_nearest_manifest_directory() {
+        let root = crate::testutil::scratch_dir("projects");
+        for file in [
+            "requirements.txt",
+            "services/api/requirements.txt",
+            "services/api/ui/package.json",
+            "services/api/ui/yarn.lock",
+            "tools/cli/Cargo.toml",
+            "tools/cli/vendor/go.sum",
+            "docs/go.sum",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let mut projects = Projects::new(&root);
+        assert_eq!(projects.of("requirements.txt"), ".");
+        assert_eq!(projects.of("services/api/requirements.txt"), "services/api");
+        assert_eq!(projects.of(r"services\api\ui\yarn.lock"), "services/api/ui");
+        // A lockfile alone doesn't make a project
+        assert_eq!(projects.of("tools/cli/vendor/go.sum"), "tools/cli");
+        assert_eq!(projects.of("docs/go.sum"), ".");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
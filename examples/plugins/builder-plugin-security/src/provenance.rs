@@ -0,0 +1,559 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::GzDecoder;
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1};
+use serde_json::Value;
+use sha2::{Digest, Sha512};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::config::ScanConfig;
+use crate::fsutil;
+use crate::http;
+use crate::report;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// Registry whose signing keys and package metadata packages are checked
+/// against.
+pub const REGISTRY: &str = "https://registry.npmjs.org";
+
+/// A publisher on the allowlist released a version without provenance.
+pub const PROVENANCE_MISSING: &str = "NPM-PROVENANCE-MISSING";
+/// The registry signature is missing for the local contents or fails.
+pub const SIGNATURE_INVALID: &str = "NPM-SIGNATURE-INVALID";
+/// An attestation doesn't match the package or its signature fails.
+pub const ATTESTATION_INVALID: &str = "NPM-ATTESTATION-INVALID";
+
+/// DER prefix of a P-256 `SubjectPublicKeyInfo`; the 65-byte uncompressed
+/// point follows it.
+pub(crate) const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+/// An installed or cached package version.
+#[derive(Debug, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// Subresource-integrity string of the local tarball, when known.
+    pub integrity: Option<String>,
+    /// Workspace-relative file the package was found through.
+    pub file: String,
+}
+
+/// The packages installed in `node_modules` under the workspace root.
+///
+/// npm 7+ records every installed package with its integrity in
+/// `node_modules/.package-lock.json`; without that file, the top-level and
+/// scoped packages' `package.json` files are read (older npm versions kept
+/// the integrity there as `_integrity`).
+pub fn installed(root: &Path) -> Vec<Package> {
+    let modules = root.join("node_modules");
+    let lockfile = "node_modules/.package-lock.json";
+    if let Some(lock) = read_json(&root.join(lockfile)) {
+        let Some(entries) = lock["packages"].as_object() else {
+            return Vec::new();
+        };
+        return entries
+            .iter()
+            .filter(|(_, entry)| entry["link"] != true)
+            .filter_map(|(path, entry)| {
+                let installed_as = path.rsplit_once("node_modules/")?.1;
+                Some(Package {
+                    name: entry["name"].as_str().unwrap_or(installed_as).to_string(),
+                    version: entry["version"].as_str()?.to_string(),
+                    integrity: entry["integrity"].as_str().map(str::to_string),
+                    file: lockfile.to_string(),
+                })
+            })
+            .collect();
+    }
+
+    let mut dirs = Vec::new();
+    for entry in fsutil::read_dir(&modules).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('@') {
+            dirs.extend(fsutil::read_dir(entry.path()).into_iter().flatten().flatten().map(|e| e.path()));
+        } else if !name.starts_with('.') {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    dirs.iter()
+        .filter_map(|dir| {
+            let manifest = read_json(&dir.join("package.json"))?;
+            let relative = dir.strip_prefix(root).ok()?.to_string_lossy().replace('\\', "/");
+            Some(Package {
+                name: manifest["name"].as_str()?.to_string(),
+                version: manifest["version"].as_str()?.to_string(),
+                integrity: manifest["_integrity"].as_str().map(str::to_string),
+                file: format!("{}/package.json", relative),
+            })
+        })
+        .collect()
+}
+
+/// The package tarballs (`*.tgz`) directly inside `dir`, as in a yarn
+/// offline mirror. Their integrity is computed from the file, so a
+/// tampered tarball fails the signature check.
+pub fn cached(dir: &Path, logs: &mut Vec<String>) -> Vec<Package> {
+    let mut tarballs: Vec<PathBuf> = fsutil::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|e| e == "tgz"))
+        .collect();
+    tarballs.sort();
+
+    tarballs
+        .iter()
+        .filter_map(|path| match read_tarball(path) {
+            Ok(package) => Some(package),
+            Err(e) => {
+                logs.push(format!("  ⚠ Skipping package tarball {}: {}", path.display(), e));
+                None
+            }
+        })
+        .collect()
+}
+
+fn read_tarball(path: &Path) -> Result<Package, String> {
+    let bytes = fsutil::read(path).map_err(|e| e.to_string())?;
+    let mut tar = Vec::new();
+    GzDecoder::new(bytes.as_slice()).read_to_end(&mut tar).map_err(|e| e.to_string())?;
+    let manifest = tar_entry(&tar, |name| name.matches('/').count() == 1 && name.ends_with("/package.json"))
+        .ok_or("no package.json in the tarball")?;
+    let manifest: Value = serde_json::from_slice(manifest).map_err(|e| format!("package.json: {}", e))?;
+    Ok(Package {
+        name: manifest["name"].as_str().ok_or("package.json has no name")?.to_string(),
+        version: manifest["version"].as_str().ok_or("package.json has no version")?.to_string(),
+        integrity: Some(format!("sha512-{}", BASE64.encode(Sha512::digest(&bytes)))),
+        file: path.display().to_string(),
+    })
+}
+
+/// Contents of the first regular file in a ustar archive whose path
+/// satisfies `wanted`.
+fn tar_entry(tar: &[u8], wanted: impl Fn(&str) -> bool) -> Option<&[u8]> {
+    let field = |header: &[u8], range: std::ops::Range<usize>| {
+        let bytes = &header[range];
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+    };
+
+    let mut offset = 0;
+    while offset + 512 <= tar.len() {
+        let header = &tar[offset..offset + 512];
+        if header.iter().all(|&b| b == 0) {
+            return None;
+        }
+        let size = usize::from_str_radix(&field(header, 124..136), 8).ok()?;
+        let prefix = field(header, 345..500);
+        let name = match field(header, 0..100) {
+            name if prefix.is_empty() => name,
+            name => format!("{}/{}", prefix, name),
+        };
+        let body = offset + 512;
+        if matches!(header[156], b'0' | 0) && wanted(&name) {
+            return tar.get(body..body + size);
+        }
+        offset = body + size.div_ceil(512) * 512;
+    }
+    None
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    serde_json::from_slice(&fsutil::read_checked(path).ok()?).ok()
+}
+
+/// Registry documents, read from the cache first. Nothing is fetched when
+/// `offline` is set.
+struct Registry {
+    cache: PathBuf,
+    agent: Option<http::Agent>,
+}
+
+impl Registry {
+    fn get(&self, cache_name: &str, url: &str) -> Result<Option<Value>, String> {
+        let cached = self.cache.join(cache_name);
+        if let Some(document) = read_json(&cached) {
+            return Ok(Some(document));
+        }
+        let Some(agent) = &self.agent else {
+            return Ok(None);
+        };
+        // Attestation URLs come from registry metadata; never follow them
+        // elsewhere
+        if !url.starts_with(&format!("{}/", REGISTRY)) {
+            return Err(format!("refusing to fetch {} (not on {})", url, REGISTRY));
+        }
+        let body = http::get(agent, url).map_err(|e| format!("{}: {}", url, e))?;
+        let document: Value = serde_json::from_str(&body).map_err(|e| format!("{}: {}", url, e))?;
+        fsutil::write_checked(&cached, body.as_bytes()).map_err(|e| format!("{}: {}", cached.display(), e))?;
+        Ok(Some(document))
+    }
+
+    /// The registry's signing keys by key id, as uncompressed P-256 points.
+    fn keys(&self) -> Result<HashMap<String, Vec<u8>>, String> {
+        let document = self
+            .get("keys.json", &format!("{}/-/npm/v1/keys", REGISTRY))?
+            .ok_or("registry keys are not cached")?;
+        Ok(document["keys"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|key| {
+                let spki = BASE64.decode(key["key"].as_str()?).ok()?;
+                let point = spki.strip_prefix(&P256_SPKI_PREFIX[..])?;
+                Some((key["keyid"].as_str()?.to_string(), point.to_vec()))
+            })
+            .collect())
+    }
+}
+
+/// Path segment of a package on the registry: `@scope%2fname` for scoped
+/// packages.
+fn registry_name(name: &str) -> String {
+    name.replace('/', "%2f")
+}
+
+/// Verify the registry signature and provenance of every package.
+///
+/// Findings are HIGH: a publisher on `npm_provenance_publishers` (an npm
+/// user, or an `@scope`) released the version without a provenance
+/// attestation; no registry signature covers the local contents; or an
+/// attestation names another package or digest, or its registry signature
+/// fails. The Sigstore certificate of a provenance attestation is not
+/// verified. Packages without registry metadata (offline, nothing cached)
+/// are counted but not checked.
+pub fn verify(packages: &[Package], config: &ScanConfig, cache: &Path, logs: &mut Vec<String>) -> Vec<Vulnerability> {
+    let agent = if config.offline {
+        None
+    } else {
+        match http::agent(config) {
+            Ok(agent) => Some(agent),
+            Err(e) => {
+                logs.push(format!("  ⚠ npm registry unavailable: {}", e));
+                None
+            }
+        }
+    };
+    let registry = Registry {
+        cache: cache.to_path_buf(),
+        agent,
+    };
+    let keys = registry.keys().unwrap_or_else(|e| {
+        logs.push(format!("  ⚠ Registry signatures not checked: {}", e));
+        HashMap::new()
+    });
+
+    let mut findings = Vec::new();
+    let mut unchecked = 0;
+    for package in packages {
+        let entry = format!("{}@{}", registry_name(&package.name), package.version);
+        let url = format!("{}/{}/{}", REGISTRY, registry_name(&package.name), package.version);
+        let manifest = match registry.get(&format!("manifests/{}.json", entry), &url) {
+            Ok(Some(manifest)) => manifest,
+            Ok(None) => {
+                unchecked += 1;
+                continue;
+            }
+            Err(e) => {
+                logs.push(format!("  ⚠ {}@{} not checked: {}", package.name, package.version, e));
+                unchecked += 1;
+                continue;
+            }
+        };
+
+        let attestation = |entry: &str| {
+            let url = manifest["dist"]["attestations"]["url"].as_str()?;
+            Some(registry.get(&format!("attestations/{}.json", entry), url))
+        };
+        let problems = check(package, &manifest, &keys, &config.npm_provenance_publishers, attestation(&entry));
+        findings.extend(problems.into_iter().map(|(id, description)| Vulnerability {
+            file: Some(package.file.clone()),
+            ..Vulnerability::new(id, Severity::High, &package.name, &package.version, description)
+        }));
+    }
+
+    logs.push(format!(
+        "  Checked signatures and provenance of {} npm packages: {} problems",
+        packages.len() - unchecked,
+        findings.len()
+    ));
+    if unchecked > 0 {
+        logs.push(format!("    {} packages not checked: no registry metadata (offline?)", unchecked));
+    }
+    findings
+}
+
+/// The problems with one package, as (finding id, description).
+fn check(
+    package: &Package,
+    manifest: &Value,
+    keys: &HashMap<String, Vec<u8>>,
+    publishers: &[String],
+    attestations: Option<Result<Option<Value>, String>>,
+) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    let dist = &manifest["dist"];
+    let integrity = dist["integrity"].as_str().unwrap_or_default();
+
+    match &package.integrity {
+        Some(local) if !integrity.is_empty() && local != integrity => problems.push((
+            SIGNATURE_INVALID,
+            format!("Local contents ({}) differ from what the registry signed ({})", local, integrity),
+        )),
+        _ if keys.is_empty() => {}
+        _ => {
+            let message = format!("{}@{}:{}", package.name, package.version, integrity);
+            let signatures = dist["signatures"].as_array().map(Vec::as_slice).unwrap_or_default();
+            if !signatures.iter().any(|s| verifies(keys, &s["keyid"], &s["sig"], message.as_bytes())) {
+                let detail = if signatures.is_empty() { "has no registry signature" } else { "registry signature fails" };
+                problems.push((SIGNATURE_INVALID, format!("{}@{} {}", package.name, package.version, detail)));
+            }
+        }
+    }
+
+    let has_provenance = dist["attestations"]["provenance"].is_object();
+    let publisher = manifest["_npmUser"]["name"].as_str().unwrap_or_default();
+    let expected = publishers.iter().find(|p| {
+        *p == publisher || (p.starts_with('@') && package.name.starts_with(&format!("{}/", p)))
+    });
+    if let (Some(expected), false) = (expected, has_provenance) {
+        problems.push((
+            PROVENANCE_MISSING,
+            format!("Published without provenance, which {} is expected to publish with", expected),
+        ));
+    }
+
+    match attestations {
+        Some(Ok(Some(bundle))) => {
+            if let Err(e) = check_attestations(package, &bundle, integrity, keys, has_provenance) {
+                problems.push((ATTESTATION_INVALID, e));
+            }
+        }
+        Some(Ok(None)) | None => {}
+        Some(Err(e)) => problems.push((ATTESTATION_INVALID, format!("Attestations unavailable: {}", e))),
+    }
+    problems
+}
+
+fn verifies(keys: &HashMap<String, Vec<u8>>, keyid: &Value, signature: &Value, message: &[u8]) -> bool {
+    let (Some(key), Some(signature)) = (keyid.as_str().and_then(|id| keys.get(id)), signature.as_str()) else {
+        return false;
+    };
+    BASE64.decode(signature).is_ok_and(|signature| {
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key)
+            .verify(message, &signature)
+            .is_ok()
+    })
+}
+
+/// Every attestation must be about this package and its contents; those
+/// signed with a registry key must verify.
+fn check_attestations(
+    package: &Package,
+    bundle: &Value,
+    integrity: &str,
+    keys: &HashMap<String, Vec<u8>>,
+    has_provenance: bool,
+) -> Result<(), String> {
+    let subject = format!("pkg:npm/{}@{}", package.name.replace('@', "%40"), package.version);
+    let digest = integrity
+        .split_whitespace()
+        .find_map(|hash| hash.strip_prefix("sha512-"))
+        .and_then(|hash| BASE64.decode(hash).ok())
+        .map(|hash| report::hex(&hash))
+        .ok_or("the registry gives no sha512 integrity")?;
+
+    let attestations = bundle["attestations"].as_array().map(Vec::as_slice).unwrap_or_default();
+    for attestation in attestations {
+        let predicate = attestation["predicateType"].as_str().unwrap_or("unknown predicate");
+        let envelope = &attestation["bundle"]["dsseEnvelope"];
+        let payload = envelope["payload"]
+            .as_str()
+            .and_then(|p| BASE64.decode(p).ok())
+            .ok_or_else(|| format!("{} attestation has no payload", predicate))?;
+        let statement: Value =
+            serde_json::from_slice(&payload).map_err(|e| format!("{} attestation: {}", predicate, e))?;
+        let covered = statement["subject"].as_array().into_iter().flatten().any(|s| {
+            s["name"].as_str() == Some(subject.as_str()) && s["digest"]["sha512"].as_str() == Some(digest.as_str())
+        });
+        if !covered {
+            return Err(format!("{} attestation is not about {} with this integrity", predicate, subject));
+        }
+
+        let payload_type = envelope["payloadType"].as_str().unwrap_or_default();
+        let mut signed = format!("DSSEv1 {} {} {} ", payload_type.len(), payload_type, payload.len()).into_bytes();
+        signed.extend_from_slice(&payload);
+        let signatures = envelope["signatures"].as_array().map(Vec::as_slice).unwrap_or_default();
+        let by_registry: Vec<&Value> =
+            signatures.iter().filter(|s| s["keyid"].as_str().is_some_and(|id| keys.contains_key(id))).collect();
+        if !by_registry.is_empty() && !by_registry.iter().any(|s| verifies(keys, &s["keyid"], &s["sig"], &signed)) {
+            return Err(format!("{} attestation signature fails", predicate));
+        }
+    }
+
+    let provenance_listed = attestations
+        .iter()
+        .any(|a| a["predicateType"].as_str().is_some_and(|p| p.starts_with("https://slsa.dev/provenance/")));
+    if has_provenance && !provenance_listed {
+        return Err("the registry lists provenance, but no provenance attestation was found".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+    use serde_json::json;
+    use std::fs;
+    use std::io::Write;
+
+    fn tarball(manifest: &str) -> Vec<u8> {
+        let mut header = [0u8; 512];
+        header[..20].copy_from_slice(b"package/package.json");
+        header[124..135].copy_from_slice(format!("{:011o}", manifest.len()).as_bytes());
+        header[156] = b'0';
+        let mut tar = header.to_vec();
+        tar.extend_from_slice(manifest.as_bytes());
+        tar.resize(512 + manifest.len().div_ceil(512) * 512 + 1024, 0);
+        let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&tar).unwrap();
+        gz.finish().unwrap()
+    }
+
+    #[test]
+    fn reads_name_version_and_integrity_of_cached_tarballs() {
+        let dir = crate::testutil::scratch_dir("provenance-tgz");
+        let tgz = tarball(r#"{"name": "@acme/util", "version": "1.0.0"}"#);
+        fs::write(dir.join("acme-util-1.0.0.tgz"), &tgz).unwrap();
+        fs::write(dir.join("broken-1.0.0.tgz"), b"not gzip").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let mut logs = Vec::new();
+        let packages = cached(&dir, &mut logs);
+        assert_eq!(
+            packages,
+            vec![Package {
+                name: "@acme/util".to_string(),
+                version: "1.0.0".to_string(),
+                integrity: Some(format!("sha512-{}", BASE64.encode(Sha512::digest(&tgz)))),
+                file: dir.join("acme-util-1.0.0.tgz").display().to_string(),
+            }]
+        );
+        assert_eq!(logs.len(), 1, "{:?}", logs);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verifies_signatures_provenance_and_attestations_from_the_cache() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+        let sign = |message: &[u8]| BASE64.encode(key.sign(&rng, message).unwrap());
+        let integrity = |name: &str| format!("sha512-{}", BASE64.encode(Sha512::digest(name)));
+
+        let root = crate::testutil::scratch_dir("provenance");
+        let write = |path: &str, content: Value| {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content.to_string()).unwrap();
+        };
+        let spki = [&P256_SPKI_PREFIX[..], key.public_key().as_ref()].concat();
+        write(".cache/keys.json", json!({"keys": [{"keyid": "SHA256:test", "key": BASE64.encode(spki)}]}));
+
+        // Registry metadata of name@version, signed unless `signed_as` says otherwise
+        let manifest = |name: &str, signed_as: &str, publisher: &str, attested: bool| {
+            let mut dist = json!({
+                "integrity": integrity(name),
+                "signatures": [{"keyid": "SHA256:test", "sig": sign(format!("{}:{}", signed_as, integrity(name)).as_bytes())}]
+            });
+            if attested {
+                dist["attestations"] = json!({
+                    "url": format!("{}/-/npm/v1/attestations/{}@1.0.0", REGISTRY, name),
+                    "provenance": {"predicateType": "https://slsa.dev/provenance/v1"}
+                });
+            }
+            let file = format!(".cache/manifests/{}@1.0.0.json", registry_name(name));
+            write(&file, json!({"_npmUser": {"name": publisher}, "dist": dist}));
+        };
+        let attestation = |name: &str, payload_signature: Option<&str>| {
+            let digest = report::hex(&BASE64.decode(integrity(name).strip_prefix("sha512-").unwrap()).unwrap());
+            let payload = json!({"subject": [{"name": format!("pkg:npm/{}@1.0.0", name), "digest": {"sha512": digest}}]})
+                .to_string();
+            let pae = format!("DSSEv1 28 application/vnd.in-toto+json {} {}", payload.len(), payload);
+            let signature = payload_signature.map_or_else(|| sign(pae.as_bytes()), |other| sign(other.as_bytes()));
+            let envelope = |keyid: &str, signature: &str| {
+                json!({"dsseEnvelope": {
+                    "payload": BASE64.encode(&payload),
+                    "payloadType": "application/vnd.in-toto+json",
+                    "signatures": [{"keyid": keyid, "sig": signature}]
+                }})
+            };
+            write(
+                &format!(".cache/attestations/{}@1.0.0.json", name),
+                json!({"attestations": [
+                    {
+                        "predicateType": "https://github.com/npm/attestation/tree/main/specs/publish/v0.1",
+                        "bundle": envelope("SHA256:test", &signature)
+                    },
+                    // Signed with a Sigstore certificate, which isn't verified
+                    {"predicateType": "https://slsa.dev/provenance/v1", "bundle": envelope("", "MEUCIQ")}
+                ]}),
+            );
+        };
+        manifest("left-pad", "left-pad@1.0.0", "camwest", true);
+        attestation("left-pad", None);
+        manifest("is-odd", "is-odd@1.0.0", "jonschlinkert", false);
+        manifest("is-even", "is-even@0.9.0", "jonschlinkert", false);
+        manifest("@acme/util", "@acme/util@1.0.0", "ci", false);
+        manifest("right-pad", "right-pad@1.0.0", "someone", true);
+        attestation("right-pad", Some("another payload"));
+
+        let entry = |name: &str, integrity: String| json!({"name": name, "version": "1.0.0", "integrity": integrity});
+        write(
+            "node_modules/.package-lock.json",
+            json!({"packages": {
+                "node_modules/left-pad": entry("left-pad", integrity("left-pad")),
+                "node_modules/is-odd": entry("is-odd", integrity("tampered")),
+                "node_modules/is-even": entry("is-even", integrity("is-even")),
+                "node_modules/@acme/util": entry("@acme/util", integrity("@acme/util")),
+                "node_modules/right-pad": entry("right-pad", integrity("right-pad")),
+                "node_modules/uncached": entry("uncached", integrity("uncached")),
+                "node_modules/local": {"resolved": "../local", "link": true}
+            }}),
+        );
+
+        let config = ScanConfig {
+            offline: true,
+            npm_provenance_publishers: vec!["@acme".to_string(), "camwest".to_string()],
+            ..ScanConfig::default()
+        };
+        let mut logs = Vec::new();
+        let findings = verify(&installed(&root), &config, &root.join(".cache"), &mut logs);
+        let mut found: Vec<(&str, &str)> = findings.iter().map(|f| (f.package.as_str(), f.id.as_str())).collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("@acme/util", PROVENANCE_MISSING),
+                ("is-even", SIGNATURE_INVALID),
+                ("is-odd", SIGNATURE_INVALID),
+                ("right-pad", ATTESTATION_INVALID),
+            ]
+        );
+        assert!(findings.iter().all(|f| f.severity == Severity::High));
+        assert!(logs.contains(&"    1 packages not checked: no registry metadata (offline?)".to_string()), "{:?}", logs);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
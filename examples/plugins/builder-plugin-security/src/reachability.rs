@@ -0,0 +1,201 @@
+use std::collections::BTreeSet;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::ecosystems::Ecosystem;
+
+/// What the import check can't see, as the report envelope states it.
+pub const LIMITATIONS: &str = "imports are read textually: dynamic imports (importlib, __import__, \
+require or import() of a computed name), imports made by other packages, and distributions whose import \
+name differs from their package name beyond a few well-known ones are not recognized; commented-out \
+imports count as imports";
+
+/// Note on findings in direct dependencies no source imports.
+pub const POSSIBLY_UNUSED: &str = "possibly unused: no source of the target imports this package";
+
+/// Source languages whose imports are checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Python,
+    JavaScript,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::Python, Language::JavaScript];
+
+    /// Language of a source file, by extension.
+    pub fn of(path: &str) -> Option<Language> {
+        let extension = path.rsplit_once('.')?.1;
+        match extension {
+            "py" | "pyi" => Some(Language::Python),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "mts" | "cts" => Some(Language::JavaScript),
+            _ => None,
+        }
+    }
+
+    /// Ecosystem whose packages the language's sources import.
+    pub fn ecosystem(self) -> Ecosystem {
+        match self {
+            Language::Python => Ecosystem::PyPI,
+            Language::JavaScript => Ecosystem::Npm,
+        }
+    }
+
+    /// Top-level modules or packages a source file imports.
+    pub fn imports(self, source: &str) -> Vec<String> {
+        match self {
+            Language::Python => python_imports(source),
+            Language::JavaScript => js_imports(source),
+        }
+    }
+}
+
+/// What the report envelope says about the import check.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Summary {
+    /// Languages whose sources were all read; findings in other ecosystems
+    /// carry no `imported`.
+    pub languages: Vec<Language>,
+    pub sources_read: usize,
+    pub limitations: &'static str,
+}
+
+/// Top-level modules of the `import` and `from ... import` statements of a
+/// Python file, wherever they appear (functions, `try` blocks). Relative
+/// imports are left out: they name the target's own modules.
+pub fn python_imports(source: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    let mut in_string = false;
+    for line in source.lines() {
+        // Skip docstrings and other triple-quoted strings
+        let quotes = line.matches("\"\"\"").count() + line.matches("'''").count();
+        let was_in_string = in_string;
+        in_string ^= quotes % 2 == 1;
+        if was_in_string || quotes > 0 {
+            continue;
+        }
+
+        let code = line.split('#').next().unwrap_or_default();
+        for statement in code.split(';').map(str::trim) {
+            if let Some(names) = statement.strip_prefix("import ") {
+                imports.extend(names.split(',').filter_map(|name| top_level(name.split_whitespace().next()?)));
+            } else if let Some(rest) = statement.strip_prefix("from ") {
+                let module = rest.split_whitespace().next().unwrap_or_default();
+                if rest.contains(" import") && !module.starts_with('.') {
+                    imports.extend(top_level(module));
+                }
+            }
+        }
+    }
+    imports
+}
+
+fn top_level(module: &str) -> Option<String> {
+    let name = module.split('.').next()?.trim_matches(|c| c == '(' || c == ')');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Packages named by the `import ... from`, `export ... from`, bare
+/// `import`, `require()` and `import()` specifiers of a JavaScript or
+/// TypeScript file that are string literals. Relative paths and URL-like
+/// specifiers (`node:fs`) are left out.
+pub fn js_imports(source: &str) -> Vec<String> {
+    let mut imports = Vec::new();
+    for keyword in ["from", "import", "require"] {
+        let mut rest = source;
+        while let Some(at) = rest.find(keyword) {
+            let before = rest[..at].chars().next_back();
+            let after = rest[at + keyword.len()..].trim_start();
+            rest = &rest[at + keyword.len()..];
+            if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$' || c == '.') {
+                continue;
+            }
+            let after = after.strip_prefix('(').map_or(after, str::trim_start);
+            let Some(quote) = after.chars().next().filter(|c| matches!(c, '"' | '\'' | '`')) else {
+                continue;
+            };
+            let Some((specifier, _)) = after[1..].split_once(quote) else {
+                continue;
+            };
+            imports.extend(js_package(specifier));
+        }
+    }
+    imports
+}
+
+/// The package a specifier resolves into: `lodash/fp` is `lodash`,
+/// `@babel/core/lib/x` is `@babel/core`.
+fn js_package(specifier: &str) -> Option<String> {
+    if specifier.starts_with(['.', '/']) || specifier.contains(':') || specifier.contains("${") {
+        return None;
+    }
+    let mut parts = specifier.split('/');
+    let first = parts.next().filter(|p| !p.is_empty())?;
+    if first.starts_with('@') {
+        let name = parts.next().filter(|p| !p.is_empty())?;
+        return Some(format!("{}/{}", first, name));
+    }
+    Some(first.to_string())
+}
+
+/// PyPI distributions imported under another name.
+const PYTHON_IMPORT_NAMES: &[(&str, &str)] = &[
+    ("beautifulsoup4", "bs4"),
+    ("djangorestframework", "rest_framework"),
+    ("opencv_python", "cv2"),
+    ("pillow", "pil"),
+    ("protobuf", "google"),
+    ("pycryptodome", "crypto"),
+    ("pyjwt", "jwt"),
+    ("python_dateutil", "dateutil"),
+    ("pyyaml", "yaml"),
+    ("scikit_learn", "sklearn"),
+];
+
+/// Whether `imports` (from sources of the ecosystem's language) include
+/// `package`. Python module names compare case-insensitively, with `-`
+/// and `.` in package names as `_`; `@types/x` counts as imported with
+/// `x`.
+pub fn is_imported(ecosystem: Ecosystem, package: &str, imports: &BTreeSet<String>) -> bool {
+    if ecosystem != Ecosystem::PyPI {
+        let untyped = package.strip_prefix("@types/").map(|name| match name.split_once("__") {
+            Some((scope, name)) => format!("@{}/{}", scope, name),
+            None => name.to_string(),
+        });
+        return imports.contains(package) || untyped.is_some_and(|name| imports.contains(&name));
+    }
+    let normalized = package.to_lowercase().replace(['-', '.'], "_");
+    let renamed = PYTHON_IMPORT_NAMES.iter().find(|(name, _)| *name == normalized).map(|(_, module)| *module);
+    imports.iter().any(|module| {
+        let module = module.to_lowercase();
+        module == normalized || Some(module.as_str()) == renamed
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_are_read_from_python_and_javascript_sources() {
+        let python = "\"\"\"Uses requests.\n\nimport flask\n\"\"\"\nimport os, yaml as y\nfrom django.db import models\n\
+from . import views\ndef f():\n    import numpy.linalg  # lazily\n";
+        assert_eq!(python_imports(python), vec!["os", "yaml", "django", "numpy"]);
+
+        let js = "import React from 'react';\nimport './styles.css';\nexport { pick } from \"lodash/fp\";\n\
+const core = require('@babel/core/lib/x');\nconst fs = require('node:fs');\nconst m = await import(`chalk`);\n\
+const dynamic = require(name);\nconst local = require('./local');\n";
+        let mut imports = js_imports(js);
+        imports.sort();
+        assert_eq!(imports, vec!["@babel/core", "chalk", "lodash", "react"]);
+
+        let imports: BTreeSet<String> = ["yaml", "Django", "lodash"].map(String::from).into();
+        assert!(is_imported(Ecosystem::PyPI, "PyYAML", &imports));
+        assert!(is_imported(Ecosystem::PyPI, "django", &imports));
+        assert!(!is_imported(Ecosystem::PyPI, "requests", &imports));
+        assert!(is_imported(Ecosystem::Npm, "@types/lodash", &imports));
+        assert!(!is_imported(Ecosystem::Npm, "express", &imports));
+    }
+}
@@ -0,0 +1,319 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::ScanConfig;
+use crate::ecosystems::{Dependency, Ecosystem};
+use crate::fsutil;
+
+/// The public registries and their download hosts. Packages from these get
+/// no [`Dependency::registry`].
+const PUBLIC: &[&str] = &[
+    "https://registry.npmjs.org",
+    "https://registry.yarnpkg.com",
+    "https://pypi.org",
+    "https://pypi.python.org",
+    "https://files.pythonhosted.org",
+];
+
+/// How advisories apply to a registry's packages.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trust {
+    /// A public registry or a declared mirror of one: matched as usual.
+    Public,
+    /// Declared in `internal_registries`: not matched.
+    Internal,
+    /// Declared in neither list: matched, as if it mirrored a public one.
+    Undeclared,
+}
+
+/// A package from an internal registry, listed in the report instead of
+/// being matched against advisories.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InternalPackage {
+    pub name: String,
+    pub version: String,
+    pub ecosystem: &'static str,
+    pub file: String,
+    pub line: usize,
+    pub registry: String,
+}
+
+impl InternalPackage {
+    pub fn new(dep: &Dependency) -> InternalPackage {
+        InternalPackage {
+            name: dep.name.clone(),
+            version: dep.version.clone(),
+            ecosystem: dep.ecosystem.as_str(),
+            file: dep.file.clone(),
+            line: dep.line,
+            registry: dep.registry.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// Registry settings in effect for one dependency file.
+#[derive(Default)]
+struct Settings {
+    /// Where packages come from unless a scope says otherwise: `registry`
+    /// in `.npmrc`, `index-url` for pip.
+    default: Option<String>,
+    /// `@scope:registry` entries of `.npmrc`.
+    scopes: BTreeMap<String, String>,
+    /// pip's `extra-index-url`s.
+    extra: Vec<String>,
+}
+
+/// Whether `url` is `registry` or somewhere under it.
+pub fn is_under(url: &str, registry: &str) -> bool {
+    let registry = registry.trim_end_matches('/');
+    url.strip_prefix(registry).is_some_and(|rest| rest.is_empty() || rest.starts_with(['/', '?', '#']))
+}
+
+/// How `registry` is declared in `public_registries` and
+/// `internal_registries`, by the longest prefix matching it; no registry
+/// is the public one.
+pub fn classify(registry: Option<&str>, config: &ScanConfig) -> Trust {
+    let Some(registry) = registry else {
+        return Trust::Public;
+    };
+    let longest = |declared: &[String]| {
+        declared
+            .iter()
+            .filter(|prefix| is_under(registry, prefix))
+            .map(|prefix| prefix.trim_end_matches('/').len())
+            .max()
+    };
+    match (longest(&config.public_registries), longest(&config.internal_registries)) {
+        (public, Some(internal)) if public.is_none_or(|public| internal >= public) => Trust::Internal,
+        (Some(_), _) => Trust::Public,
+        (None, _) => Trust::Undeclared,
+    }
+}
+
+/// Set the registry of the npm and PyPI dependencies `file` declares.
+///
+/// A yarn.lock `resolved` URL is attributed to the declared registry it is
+/// under, else the `.npmrc` one, else its origin. Other npm packages come
+/// from the registry the nearest `.npmrc` names for their scope, PyPI ones
+/// from the requirements file's `--index-url`, else that of the nearest
+/// `pip.conf`. Both are looked for from the file's directory up to `root`.
+pub fn attribute(root: &Path, file: &str, deps: &mut [Dependency], config: &ScanConfig, logs: &mut Vec<String>) {
+    let Some(ecosystem) = deps.first().map(|dep| dep.ecosystem) else {
+        return;
+    };
+    let settings = match ecosystem {
+        Ecosystem::Npm => nearest(root, file, ".npmrc").map(|text| npmrc(&text)).unwrap_or_default(),
+        Ecosystem::PyPI => {
+            let mut settings = nearest(root, file, "pip.conf").map(|text| pip_conf(&text)).unwrap_or_default();
+            if let Ok(text) = fsutil::read_to_string(root.join(file)) {
+                let own = requirements(&text);
+                settings.default = own.default.or(settings.default);
+                settings.extra.extend(own.extra);
+            }
+            settings
+        }
+        _ => return,
+    };
+    if !settings.extra.is_empty() {
+        logs.push(format!(
+            "  ⚠ {} also installs from {}; its packages are attributed to {} but may come from either",
+            file,
+            settings.extra.join(", "),
+            settings.default.as_deref().unwrap_or("PyPI")
+        ));
+    }
+
+    let declared: Vec<&String> = config.public_registries.iter().chain(&config.internal_registries).collect();
+    for dep in deps {
+        let configured = dep
+            .name
+            .split_once('/')
+            .and_then(|(scope, _)| settings.scopes.get(scope))
+            .or(settings.default.as_ref());
+        let registry = match &dep.resolved {
+            Some(url) => declared
+                .iter()
+                .filter(|prefix| is_under(url, prefix))
+                .max_by_key(|prefix| prefix.len())
+                .map(|prefix| prefix.as_str())
+                .or(configured.map(String::as_str).filter(|registry| is_under(url, registry)))
+                .unwrap_or_else(|| origin(url)),
+            None => match configured {
+                Some(registry) => registry,
+                None => continue,
+            },
+        };
+        let registry = registry.trim_end_matches('/');
+        if !PUBLIC.iter().any(|public| is_under(registry, public)) {
+            dep.registry = Some(registry.to_string());
+        }
+    }
+}
+
+/// The nearest `name` from `file`'s directory up to `root`.
+fn nearest(root: &Path, file: &str, name: &str) -> Option<String> {
+    Path::new(file).ancestors().skip(1).find_map(|dir| fsutil::read_to_string(root.join(dir).join(name)).ok())
+}
+
+fn npmrc(text: &str) -> Settings {
+    let mut settings = Settings::default();
+    for line in text.lines().map(str::trim).filter(|line| !line.starts_with(['#', ';'])) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match key.trim() {
+            "registry" => settings.default = Some(value),
+            key => {
+                if let Some(scope) = key.strip_suffix(":registry").filter(|scope| scope.starts_with('@')) {
+                    settings.scopes.insert(scope.to_string(), value);
+                }
+            }
+        }
+    }
+    settings
+}
+
+/// `index-url` and `extra-index-url` of the `[global]` and `[install]`
+/// sections; extra URLs may continue on indented lines.
+fn pip_conf(text: &str) -> Settings {
+    let mut settings = Settings::default();
+    let mut section = "";
+    let mut continues_extra = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+            section = name.trim();
+            continues_extra = false;
+            continue;
+        }
+        if !matches!(section, "global" | "install") {
+            continue;
+        }
+        if continues_extra && line.starts_with([' ', '\t']) {
+            settings.extra.extend(trimmed.split_whitespace().map(str::to_string));
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(['=', ':']) else {
+            continue;
+        };
+        let key = key.trim().replace('_', "-");
+        continues_extra = key == "extra-index-url";
+        match key.as_str() {
+            "index-url" => settings.default = Some(value.trim().to_string()),
+            "extra-index-url" => settings.extra.extend(value.split_whitespace().map(str::to_string)),
+            _ => {}
+        }
+    }
+    settings
+}
+
+/// `--index-url`/`-i` and `--extra-index-url` lines of a requirements file.
+fn requirements(text: &str) -> Settings {
+    let mut settings = Settings::default();
+    for line in text.lines() {
+        if let Some(url) = option(line, &["--index-url", "-i"]) {
+            settings.default = Some(url.to_string());
+        } else if let Some(url) = option(line, &["--extra-index-url"]) {
+            settings.extra.push(url.to_string());
+        }
+    }
+    settings
+}
+
+/// The value of the first of `names` that `line` sets, as `name value` or
+/// `name=value`.
+fn option<'a>(line: &'a str, names: &[&str]) -> Option<&'a str> {
+    let line = line.trim();
+    names.iter().find_map(|name| {
+        let rest = line.strip_prefix(name)?;
+        match rest.strip_prefix('=') {
+            Some(value) => value.split_whitespace().next(),
+            None if rest.starts_with([' ', '\t']) => rest.split_whitespace().next(),
+            None => None,
+        }
+    })
+}
+
+/// The scheme and host of a URL, or the whole string when it has none.
+fn origin(url: &str) -> &str {
+    match url.find("://") {
+        Some(i) => {
+            let end = url[i + 3..].find(['/', '?', '#']).map_or(url.len(), |end| i + 3 + end);
+            &url[..end]
+        }
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecosystems;
+    use std::fs;
+
+    #[test]
+    fn dependencies_are_attributed_to_the_registry_their_config_names() {
+        let root = crate::testutil::scratch_dir("registries");
+        fs::create_dir_all(root.join("web/app")).unwrap();
+        fs::create_dir_all(root.join("api")).unwrap();
+        let npmrc = "registry=https://mirror.ourorg.dev/npm/\n@ourorg:registry=\"https://npm.ourorg.dev\"\n";
+        fs::write(root.join("web/.npmrc"), npmrc).unwrap();
+        fs::write(root.join("pip.conf"), "[global]\nindex-url = https://pypi.ourorg.dev/simple\n").unwrap();
+        let requirements = "--extra-index-url=https://pypi.org/simple\nrequests==2.25.0\n";
+        fs::write(root.join("api/requirements.txt"), requirements).unwrap();
+        let config = ScanConfig {
+            public_registries: vec!["https://mirror.ourorg.dev/npm".to_string()],
+            internal_registries: vec![
+                "https://npm.ourorg.dev".to_string(),
+                "https://mirror.ourorg.dev/npm/own".to_string(),
+            ],
+            ..ScanConfig::default()
+        };
+
+        let package_json =
+            "{\n  \"dependencies\": {\n    \"@ourorg/ui\": \"1.2.0\",\n    \"lodash\": \"4.17.15\"\n  }\n}\n";
+        let file = "web/app/package.json";
+        let mut deps = ecosystems::extract_str(ecosystems::detect(file).unwrap(), file, package_json);
+        let mut logs = Vec::new();
+        attribute(&root, file, &mut deps, &config, &mut logs);
+        let registries: Vec<Option<&str>> = deps.iter().map(|dep| dep.registry.as_deref()).collect();
+        assert_eq!(registries, [Some("https://npm.ourorg.dev"), Some("https://mirror.ourorg.dev/npm")]);
+        let trust: Vec<Trust> = deps.iter().map(|dep| classify(dep.registry.as_deref(), &config)).collect();
+        assert_eq!(trust, [Trust::Internal, Trust::Public]);
+
+        let yarn_lock = concat!(
+            "lodash@^4.17.15:\n",
+            "  version \"4.17.15\"\n",
+            "  resolved \"https://registry.yarnpkg.com/lodash/-/lodash-4.17.15.tgz\"\n",
+            "\n",
+            "left-pad@^1.3.0:\n",
+            "  version \"1.3.0\"\n",
+            "  resolved \"https://cache.example.net/left-pad/-/left-pad-1.3.0.tgz\"\n",
+        );
+        let file = "web/yarn.lock";
+        let mut deps = ecosystems::extract_str(ecosystems::detect(file).unwrap(), file, yarn_lock);
+        attribute(&root, file, &mut deps, &config, &mut logs);
+        let registries: Vec<Option<&str>> = deps.iter().map(|dep| dep.registry.as_deref()).collect();
+        assert_eq!(registries, [None, Some("https://cache.example.net")]);
+        assert_eq!(classify(Some("https://cache.example.net"), &config), Trust::Undeclared);
+        assert_eq!(classify(Some("https://mirror.ourorg.dev/npm/own/x"), &config), Trust::Internal);
+        assert_eq!(classify(Some("https://npm.ourorg.dev.evil.com"), &config), Trust::Undeclared);
+
+        let file = "api/requirements.txt";
+        let mut deps = ecosystems::extract_str(ecosystems::detect(file).unwrap(), file, requirements);
+        attribute(&root, file, &mut deps, &config, &mut logs);
+        assert_eq!(deps[0].registry.as_deref(), Some("https://pypi.ourorg.dev/simple"));
+        assert_eq!(
+            logs,
+            ["  ⚠ api/requirements.txt also installs from https://pypi.org/simple; its packages are attributed to \
+              https://pypi.ourorg.dev/simple but may come from either"]
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+
+use crate::fingerprint;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// What `fail_on_regression` compares a scan with.
+#[derive(Clone)]
+pub enum Baseline {
+    /// The target has no scan in its history; this one becomes the
+    /// baseline and passes.
+    First,
+    /// The findings of the target's newest report in its history.
+    Previous(Vec<Vulnerability>),
+}
+
+impl Baseline {
+    /// The baseline with only the previous findings `keep` accepts.
+    pub fn only(&self, keep: impl Fn(&Vulnerability) -> bool) -> Baseline {
+        match self {
+            Baseline::First => Baseline::First,
+            Baseline::Previous(previous) => Baseline::Previous(previous.iter().filter(|v| keep(v)).cloned().collect()),
+        }
+    }
+}
+
+/// A severity with more findings than in the previous scan.
+pub struct Increase<'a> {
+    pub severity: Severity,
+    pub previous: usize,
+    pub current: usize,
+    /// The findings at this severity that weren't at it before: new ones,
+    /// and those whose severity rose.
+    pub responsible: Vec<&'a Vulnerability>,
+}
+
+impl Increase<'_> {
+    /// The increase as a policy violation.
+    pub fn describe(&self) -> String {
+        let responsible: Vec<String> = self
+            .responsible
+            .iter()
+            .map(|v| format!("{} in {} {}", v.id, v.package, v.version))
+            .collect();
+        format!(
+            "{} {} vulnerabilities, up from {} in the previous scan (fail_on_regression): {}",
+            self.current,
+            self.severity.as_str().to_lowercase(),
+            self.previous,
+            responsible.join(", ")
+        )
+    }
+}
+
+/// The severities `current` has more findings of than `previous`, with the
+/// findings responsible.
+///
+/// Findings are told apart by fingerprint, under their id or any alias: an
+/// advisory renamed to one of its aliases is the same finding rather than
+/// a fix and a new one.
+pub fn increases<'a>(previous: &[Vulnerability], current: &'a [Vulnerability]) -> Vec<Increase<'a>> {
+    Severity::ALL
+        .iter()
+        .filter_map(|&severity| {
+            let before: Vec<&Vulnerability> = previous.iter().filter(|v| v.severity == severity).collect();
+            let now: Vec<&Vulnerability> = current.iter().filter(|v| v.severity == severity).collect();
+            if now.len() <= before.len() {
+                return None;
+            }
+            let seen: HashSet<String> = before.iter().flat_map(|v| known_as(v)).collect();
+            Some(Increase {
+                severity,
+                previous: before.len(),
+                current: now.len(),
+                responsible: now.into_iter().filter(|v| !known_as(v).any(|fp| seen.contains(&fp))).collect(),
+            })
+        })
+        .collect()
+}
+
+/// The fingerprints a finding has under its id and each of its aliases.
+pub fn known_as(vuln: &Vulnerability) -> impl Iterator<Item = String> + '_ {
+    std::iter::once(&vuln.id)
+        .chain(&vuln.aliases)
+        .map(|id| fingerprint::compute(id, vuln.ecosystem(), &vuln.package, vuln.file.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn finding(id: &str, aliases: &[&str], severity: &str, package: &str) -> Vulnerability {
+        let finding = json!({
+            "id": id,
+            "aliases": aliases,
+            "severity": severity,
+            "package": package,
+            "version": "1.0.0",
+            "description": "",
+            "fixed_in": null,
+            "file": "requirements.txt",
+        });
+        serde_json::from_value(finding).unwrap()
+    }
+
+    #[test]
+    fn only_findings_entering_a_severity_count_as_its_regression() {
+        let previous = [
+            finding("PYSEC-2021-1", &["CVE-2021-1"], "HIGH", "django"),
+            finding("CVE-2021-2", &[], "MEDIUM", "requests"),
+            finding("CVE-2021-3", &[], "LOW", "urllib3"),
+        ];
+        // django's advisory renamed to its CVE, requests' rose to HIGH,
+        // urllib3's was fixed and flask's is new
+        let current = [
+            finding("CVE-2021-1", &[], "HIGH", "django"),
+            finding("CVE-2021-2", &[], "HIGH", "requests"),
+            finding("CVE-2021-4", &[], "LOW", "flask"),
+        ];
+        let found: Vec<(Severity, usize, usize, Vec<&str>)> = increases(&previous, &current)
+            .into_iter()
+            .map(|i| (i.severity, i.previous, i.current, i.responsible.iter().map(|v| v.package.as_str()).collect()))
+            .collect();
+        assert_eq!(found, [(Severity::High, 1, 2, vec!["requests"])]);
+        assert!(increases(&current, &current).is_empty());
+    }
+}
@@ -0,0 +1,909 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::ecosystems::{self, Dependency, Ecosystem, EcosystemScanner};
+use crate::fsutil;
+use crate::paths;
+use crate::report;
+use crate::sanitize;
+use crate::template::Template;
+
+/// Lines of unchanged context around each hunk, as in `diff -u`.
+const CONTEXT_LINES: usize = 3;
+
+/// Name of the pull request document `remediation_pr` writes into the
+/// cache directory.
+pub const PR_FILE_NAME: &str = "remediation-pr.json";
+
+/// Branch of that pull request unless `remediation_pr_branch` says
+/// otherwise: the same updates always get the same branch.
+pub const DEFAULT_PR_BRANCH: &str = "security/updates-{{digest}}";
+
+/// Its commit message unless `remediation_pr_commit_message` says
+/// otherwise.
+pub const DEFAULT_PR_COMMIT_MESSAGE: &str = "Update vulnerable dependencies: \
+    {{#each packages}}{{this}}{{#unless @last}}, {{/unless}}{{/each}}\n\n\
+    {{#each updates}}- {{package}} {{from}} to {{to}} in {{file}}\n{{/each}}";
+
+/// A version bump for one package declared in one manifest.
+pub struct Fix {
+    pub file: String,
+    pub package: String,
+    pub from: String,
+    pub to: String,
+    pub findings: Vec<String>,
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct Update {
+    pub package: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// One patch file and the findings it resolves.
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct PatchSummary {
+    pub patch: String,
+    pub file: String,
+    pub findings: Vec<String>,
+    pub updates: Vec<Update>,
+}
+
+/// A finding against a package and the version its advisory is fixed in.
+pub struct Needed<'a> {
+    pub ecosystem: Ecosystem,
+    pub package: &'a str,
+    pub version: &'a str,
+    pub fixed_in: &'a str,
+    /// The file declaring the vulnerable version.
+    pub file: Option<&'a str>,
+}
+
+/// The smallest update of one package that clears every finding against
+/// it, as the hook result and the report list them.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Recommendation {
+    pub package: String,
+    pub ecosystem: String,
+    /// The vulnerable versions declared, lowest first; usually one.
+    pub current: Vec<String>,
+    pub recommended: String,
+    /// Whether a declared constraint doesn't admit `recommended`, so more
+    /// than the lockfile has to change.
+    pub constraint_change_needed: bool,
+    pub findings_resolved: usize,
+    /// The files declaring the vulnerable versions, and those whose
+    /// constraint has to change.
+    pub files: Vec<String>,
+    /// The package's declarations in manifests and whether each admits
+    /// `recommended`; `None` for requirements that aren't understood.
+    pub constraints: Vec<ConstraintCheck>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConstraintCheck {
+    pub file: String,
+    pub constraint: String,
+    pub admits: Option<bool>,
+}
+
+#[derive(Serialize, Default, JsonSchema)]
+pub struct RemediationResult {
+    pub patches: Vec<PatchSummary>,
+    pub modified_files: Vec<String>,
+}
+
+/// Group fixes by manifest, rewrite the declared versions, and write one
+/// unified-diff patch per manifest into `patch_dir` plus a `summary.json`.
+///
+/// With `apply` set the rewritten manifests are also written in place.
+/// Only manifests are touched; lockfiles are left for the package manager.
+pub fn remediate(
+    workspace_root: &Path,
+    patch_dir: &Path,
+    fixes: &[Fix],
+    apply: bool,
+    logs: &mut Vec<String>,
+) -> RemediationResult {
+    let mut result = RemediationResult::default();
+
+    let mut by_file: BTreeMap<&str, Vec<&Fix>> = BTreeMap::new();
+    for fix in fixes {
+        by_file.entry(&fix.file).or_default().push(fix);
+    }
+
+    for (file, fixes) in by_file {
+        let Some(scanner) = ecosystems::detect(file).filter(|s| s.rewritable()) else {
+            continue;
+        };
+
+        let path = paths::join_source(workspace_root, file);
+        let Ok(original) = fsutil::read_to_string(&path) else {
+            logs.push(format!("  ⚠ Cannot read {} for remediation", file));
+            continue;
+        };
+
+        let (patched, updates) = rewrite_manifest(scanner, &original, &fixes);
+        if updates.is_empty() {
+            continue;
+        }
+
+        let patch_name = format!("{}.patch", file.trim_start_matches("./").replace('/', "__"));
+        let patch_path = patch_dir.join(&patch_name);
+        let diff = unified_diff(file, &original, &patched);
+
+        if fsutil::write_atomic(&patch_path, diff.as_bytes()).is_err() {
+            logs.push(format!("  ⚠ Cannot write patch {}", patch_path.display()));
+            continue;
+        }
+
+        if apply {
+            match fsutil::write_atomic(&path, patched.as_bytes()) {
+                Ok(()) => result.modified_files.push(file.to_string()),
+                Err(e) => logs.push(format!("  ⚠ Cannot apply fix to {}: {}", file, e)),
+            }
+        }
+
+        let mut findings: Vec<String> = fixes.iter().flat_map(|f| f.findings.iter().cloned()).collect();
+        findings.sort();
+        findings.dedup();
+
+        result.patches.push(PatchSummary {
+            patch: patch_path.display().to_string(),
+            file: file.to_string(),
+            findings,
+            updates,
+        });
+    }
+
+    if !result.patches.is_empty() {
+        let summary_path = patch_dir.join("summary.json");
+        if let Ok(summary) = serde_json::to_string_pretty(&result.patches) {
+            let _ = fsutil::write_atomic(&summary_path, summary.as_bytes());
+        }
+    }
+
+    result
+}
+
+/// A finding, against the version a file declares, that an update in the
+/// pull request fixes.
+pub struct Fixable<'a> {
+    pub ecosystem: Ecosystem,
+    pub package: &'a str,
+    pub version: &'a str,
+    pub file: &'a str,
+    pub id: &'a str,
+    pub severity: String,
+    /// Short title, already sanitized.
+    pub summary: String,
+    pub url: Option<&'a str>,
+}
+
+/// What the pull request is named after and what it may propose.
+pub struct PullRequestOptions<'a> {
+    /// The target scanned, or `workspace`.
+    pub target: &'a str,
+    pub branch: &'a str,
+    pub commit_message: &'a str,
+    pub allow_constraint_changes: bool,
+}
+
+/// The updates fixing a scan's findings as a pull request to open.
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct PullRequest {
+    pub branch: String,
+    /// The first line of `commit_message`.
+    pub title: String,
+    pub commit_message: String,
+    /// The description, in Markdown.
+    pub body: String,
+    /// The updates by the file declaring the vulnerable versions, in file
+    /// order.
+    pub files: Vec<FileUpdates>,
+    /// Updates a declared constraint doesn't admit, left out without
+    /// `allow_constraint_changes`.
+    pub excluded: Vec<ExcludedUpdate>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct FileUpdates {
+    pub file: String,
+    pub updates: Vec<ProposedUpdate>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct ProposedUpdate {
+    pub package: String,
+    pub ecosystem: String,
+    /// The vulnerable versions the file declares, lowest first.
+    pub from: Vec<String>,
+    pub to: String,
+    /// Whether `to` is a new major version (or minor, below 1.0) of one of
+    /// `from`, which may break the API.
+    pub breaking: bool,
+    /// The constraints to change for `to`, with `allow_constraint_changes`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub constraint_changes: Vec<ConstraintCheck>,
+    pub fixes: Vec<FixedFinding>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct FixedFinding {
+    pub id: String,
+    pub severity: String,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug, JsonSchema)]
+pub struct ExcludedUpdate {
+    pub package: String,
+    pub ecosystem: String,
+    pub current: Vec<String>,
+    pub to: String,
+    /// The constraints that don't admit `to`.
+    pub constraints: Vec<ConstraintCheck>,
+}
+
+/// The updates to one file by package and ecosystem.
+type PackageUpdates<'a> = BTreeMap<(&'a str, &'a str), (Ecosystem, ProposedUpdate)>;
+
+/// The pull request making the `recommendations` for `findings`, grouped by
+/// the file declaring each vulnerable version, with its branch and commit
+/// message rendered from the `options` templates. `None` when it would
+/// propose nothing.
+///
+/// Everything is ordered by file, package and advisory id and nothing
+/// depends on the time, so the same findings always give the same pull
+/// request.
+pub fn pull_request(
+    findings: &[Fixable],
+    recommendations: &[Recommendation],
+    options: &PullRequestOptions,
+) -> Result<Option<PullRequest>, String> {
+    let mut by_file: BTreeMap<&str, PackageUpdates> = BTreeMap::new();
+    let mut excluded: BTreeMap<(&str, &str), ExcludedUpdate> = BTreeMap::new();
+    for finding in findings {
+        let ecosystem = finding.ecosystem.as_str();
+        let Some(recommendation) =
+            recommendations.iter().find(|r| r.package == finding.package && r.ecosystem == ecosystem)
+        else {
+            continue;
+        };
+        let blocking: Vec<ConstraintCheck> =
+            recommendation.constraints.iter().filter(|c| c.admits == Some(false)).cloned().collect();
+        if recommendation.constraint_change_needed && !options.allow_constraint_changes {
+            excluded.entry((finding.package, ecosystem)).or_insert_with(|| ExcludedUpdate {
+                package: recommendation.package.clone(),
+                ecosystem: recommendation.ecosystem.clone(),
+                current: recommendation.current.clone(),
+                to: recommendation.recommended.clone(),
+                constraints: blocking,
+            });
+            continue;
+        }
+
+        let updates = by_file.entry(finding.file).or_default();
+        let (_, update) = updates.entry((finding.package, ecosystem)).or_insert_with(|| {
+            let update = ProposedUpdate {
+                package: recommendation.package.clone(),
+                ecosystem: recommendation.ecosystem.clone(),
+                from: Vec::new(),
+                to: recommendation.recommended.clone(),
+                breaking: false,
+                constraint_changes: blocking,
+                fixes: Vec::new(),
+            };
+            (finding.ecosystem, update)
+        });
+        if !update.from.iter().any(|version| version == finding.version) {
+            update.from.push(finding.version.to_string());
+        }
+        if !update.fixes.iter().any(|fixed| fixed.id == finding.id) {
+            update.fixes.push(FixedFinding {
+                id: finding.id.to_string(),
+                severity: finding.severity.clone(),
+                summary: finding.summary.clone(),
+                url: finding.url.filter(|url| sanitize::is_web_url(url)).map(str::to_string),
+            });
+        }
+    }
+
+    let files: Vec<FileUpdates> = by_file
+        .into_iter()
+        .map(|(file, updates)| FileUpdates {
+            file: file.to_string(),
+            updates: updates
+                .into_values()
+                .map(|(ecosystem, mut update)| {
+                    update.from.sort_by(|a, b| ecosystem.compare_versions(a, b));
+                    update.fixes.sort_by(|a, b| a.id.cmp(&b.id));
+                    update.breaking = update.from.iter().any(|from| crosses_major(ecosystem, from, &update.to));
+                    update
+                })
+                .collect(),
+        })
+        .collect();
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let excluded: Vec<ExcludedUpdate> = excluded.into_values().collect();
+
+    let updates: Vec<(&str, &ProposedUpdate)> =
+        files.iter().flat_map(|group| group.updates.iter().map(move |update| (group.file.as_str(), update))).collect();
+    let mut digest = Sha256::new();
+    for (file, update) in &updates {
+        digest.update(format!("{}\t{}\t{}\t{}\n", file, update.ecosystem, update.package, update.to));
+    }
+    let packages: BTreeSet<&str> = updates.iter().map(|(_, update)| update.package.as_str()).collect();
+    let fixed: BTreeSet<&str> =
+        updates.iter().flat_map(|(_, update)| update.fixes.iter().map(|fixed| fixed.id.as_str())).collect();
+    let data = json!({
+        "target": options.target,
+        "digest": &report::hex(&digest.finalize())[..8],
+        "packages": packages,
+        "files": files.iter().map(|group| &group.file).collect::<Vec<_>>(),
+        "update_count": updates.len(),
+        "finding_count": fixed.len(),
+        "updates": updates
+            .iter()
+            .map(|(file, update)| {
+                json!({
+                    "file": file,
+                    "package": update.package,
+                    "ecosystem": update.ecosystem,
+                    "from": update.from.join(", "),
+                    "to": update.to,
+                    "breaking": update.breaking,
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+    let render = |name: &str, source: &str| Template::parse(name, source).and_then(|template| template.render(&data));
+    let branch = git_ref_name(&render("remediation_pr_branch", options.branch)?);
+    let commit_message = render("remediation_pr_commit_message", options.commit_message)?.trim().to_string();
+    let title = commit_message.lines().next().unwrap_or_default().to_string();
+
+    Ok(Some(PullRequest {
+        branch,
+        title,
+        commit_message,
+        body: pr_body(&files, &excluded, updates.len(), fixed.len()),
+        files,
+        excluded,
+    }))
+}
+
+/// Whether `to` is past what `^from` admits: a new major version, or minor
+/// below 1.0, which may break the API.
+fn crosses_major(ecosystem: Ecosystem, from: &str, to: &str) -> bool {
+    let release = from.split(['-', '+']).next().unwrap_or(from);
+    caret_upper(release).is_some_and(|upper| ecosystem.compare_versions(to, &upper).is_ge())
+}
+
+/// `name` as git takes a branch name: anything but letters, digits and
+/// `._/-` made `-`, without empty or dot-led components.
+fn git_ref_name(name: &str) -> String {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '/' | '-');
+    let name: String = name.chars().map(|c| if allowed(c) { c } else { '-' }).collect();
+    let components: Vec<&str> = name
+        .split('/')
+        .map(|component| component.trim_start_matches('.').trim_end_matches(".lock").trim_end_matches('.'))
+        .filter(|component| !component.is_empty())
+        .collect();
+    components.join("/").replace("..", ".")
+}
+
+/// The pull request description: what it fixes, by file, each update with
+/// the advisories it fixes and what to look out for, then the updates left
+/// out.
+fn pr_body(files: &[FileUpdates], excluded: &[ExcludedUpdate], updates: usize, fixed: usize) -> String {
+    let plural = |count: usize, one: &str, many: &str| format!("{} {}", count, if count == 1 { one } else { many });
+    let mut body = format!(
+        "Updates {} to fix {}.\n",
+        plural(updates, "dependency", "dependencies"),
+        plural(fixed, "known vulnerability", "known vulnerabilities")
+    );
+    for group in files {
+        body.push_str(&format!("\n### `{}`\n\n", group.file));
+        for update in &group.updates {
+            body.push_str(&format!(
+                "- **{}** {} → {}, fixing {}\n",
+                update.package,
+                update.from.join(", "),
+                update.to,
+                plural(update.fixes.len(), "vulnerability", "vulnerabilities")
+            ));
+            if update.breaking {
+                body.push_str("  - ⚠️ New major version: check the changelog for breaking changes\n");
+            }
+            for check in &update.constraint_changes {
+                let change = format!("  - Changes `{}` in `{}` to admit {}\n", check.constraint, check.file, update.to);
+                body.push_str(&change);
+            }
+            for fixed in &update.fixes {
+                let id = match &fixed.url {
+                    Some(url) => format!("[{}]({})", fixed.id, url),
+                    None => fixed.id.clone(),
+                };
+                body.push_str(&format!("  - {} ({}): {}\n", id, fixed.severity, fixed.summary));
+            }
+        }
+    }
+    if !excluded.is_empty() {
+        body.push_str("\n### Not included\n\n");
+        body.push_str("A declared constraint doesn't admit these; set `allow_constraint_changes` to propose them:\n\n");
+        for update in excluded {
+            let constraints: Vec<String> =
+                update.constraints.iter().map(|c| format!("`{}` in `{}`", c.constraint, c.file)).collect();
+            body.push_str(&format!(
+                "- **{}** {} → {}: {}\n",
+                update.package,
+                update.current.join(", "),
+                update.to,
+                constraints.join(", ")
+            ));
+        }
+    }
+    body
+}
+
+/// Rewrite every declaration matched by `fixes`, returning the new content
+/// and the updates that were made.
+///
+/// Only the version number itself is replaced, so the constraint operator
+/// and any surrounding formatting stay as the author wrote them
+/// (`"^4.17.15"` becomes `"^4.17.21"`).
+fn rewrite_manifest(scanner: &dyn EcosystemScanner, content: &str, fixes: &[&Fix]) -> (String, Vec<Update>) {
+    let declared = ecosystems::extract_str(scanner, "", content);
+    let mut updates = Vec::new();
+    let mut patched = String::with_capacity(content.len());
+
+    for (number, raw) in (1..).zip(content.split_inclusive('\n')) {
+        let line = paths::strip_line_ending(raw);
+        let ending = &raw[line.len()..];
+
+        let fix = declared
+            .iter()
+            .find(|dep| dep.line == number)
+            .and_then(|dep| fixes.iter().find(|f| f.package == dep.name && f.from == dep.version));
+
+        match fix.and_then(|fix| replace_version(line, &fix.package, &fix.from, &fix.to).map(|l| (fix, l))) {
+            Some((fix, rewritten)) => {
+                patched.push_str(&rewritten);
+                updates.push(Update {
+                    package: fix.package.clone(),
+                    from: fix.from.clone(),
+                    to: fix.to.clone(),
+                });
+            }
+            None => patched.push_str(line),
+        }
+        patched.push_str(ending);
+    }
+
+    (patched, updates)
+}
+
+/// Replace the first occurrence of `from` after the package name.
+fn replace_version(line: &str, package: &str, from: &str, to: &str) -> Option<String> {
+    let name_end = line.find(package)? + package.len();
+    let offset = name_end + line[name_end..].find(from)?;
+    Some(format!("{}{}{}", &line[..offset], to, &line[offset + from.len()..]))
+}
+
+/// Render a `diff -u` style patch between two versions of a file whose
+/// changes are all in-place line replacements.
+pub fn unified_diff(file: &str, original: &str, patched: &str) -> String {
+    let old: Vec<&str> = original.split_inclusive('\n').collect();
+    let new: Vec<&str> = patched.split_inclusive('\n').collect();
+
+    let changed: Vec<usize> = (0..old.len()).filter(|&i| old[i] != new[i]).collect();
+
+    // Merge changes whose context windows touch into a single hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + CONTEXT_LINES + 1).min(old.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", file, file);
+    for (start, end) in hunks {
+        let len = end - start;
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", start + 1, len, start + 1, len));
+        for i in start..end {
+            if old[i] == new[i] {
+                push_diff_line(&mut out, ' ', old[i]);
+            } else {
+                push_diff_line(&mut out, '-', old[i]);
+                push_diff_line(&mut out, '+', new[i]);
+            }
+        }
+    }
+    out
+}
+
+fn push_diff_line(out: &mut String, marker: char, line: &str) {
+    out.push(marker);
+    match line.strip_suffix('\n') {
+        Some(content) => {
+            out.push_str(content);
+            out.push('\n');
+        }
+        None => {
+            out.push_str(line);
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+/// Per package, the highest fixed version among its findings (the lowest
+/// that clears them all), checked against the manifests' `declared`
+/// constraints. The updates resolving the most findings come first, then
+/// by package name and ecosystem.
+pub fn minimal_updates(needed: &[Needed], declared: &[Dependency]) -> Vec<Recommendation> {
+    struct Pending<'a> {
+        ecosystem: Ecosystem,
+        to: &'a str,
+        resolves: usize,
+        current: Vec<&'a str>,
+        files: BTreeSet<String>,
+    }
+    let mut by_package: BTreeMap<(&str, &str), Pending> = BTreeMap::new();
+    for need in needed {
+        let entry = by_package.entry((need.package, need.ecosystem.as_str())).or_insert(Pending {
+            ecosystem: need.ecosystem,
+            to: need.fixed_in,
+            resolves: 0,
+            current: Vec::new(),
+            files: BTreeSet::new(),
+        });
+        if need.ecosystem.compare_versions(need.fixed_in, entry.to).is_gt() {
+            entry.to = need.fixed_in;
+        }
+        entry.resolves += 1;
+        if !entry.current.contains(&need.version) {
+            entry.current.push(need.version);
+        }
+        entry.files.extend(need.file.map(str::to_string));
+    }
+
+    let mut recommendations: Vec<Recommendation> = by_package
+        .into_iter()
+        .map(|((package, _), mut pending)| {
+            let ecosystem = pending.ecosystem;
+            let constraints: Vec<ConstraintCheck> = declared
+                .iter()
+                .filter(|dep| dep.ecosystem == ecosystem && dep.name == package)
+                .filter_map(|dep| {
+                    let constraint = dep.constraint.as_ref()?;
+                    Some(ConstraintCheck {
+                        file: dep.file.clone(),
+                        constraint: constraint.clone(),
+                        admits: admits(ecosystem, constraint, pending.to),
+                    })
+                })
+                .collect();
+            let blocking = constraints.iter().filter(|c| c.admits == Some(false));
+            pending.files.extend(blocking.map(|c| c.file.clone()));
+            pending.current.sort_by(|a, b| ecosystem.compare_versions(a, b));
+            Recommendation {
+                package: package.to_string(),
+                ecosystem: ecosystem.as_str().to_string(),
+                current: pending.current.iter().map(|v| v.to_string()).collect(),
+                recommended: pending.to.to_string(),
+                constraint_change_needed: constraints.iter().any(|c| c.admits == Some(false)),
+                findings_resolved: pending.resolves,
+                files: pending.files.into_iter().collect(),
+                constraints,
+            }
+        })
+        .collect();
+    // Stable, so equal counts keep the map's order by name and ecosystem
+    recommendations.sort_by_key(|r| Reverse(r.findings_resolved));
+    recommendations
+}
+
+/// Whether `constraint`, as `ecosystem`'s manifests write requirements,
+/// admits `version`; `None` when the requirement isn't understood.
+///
+/// Handles npm ranges (`^`, `~`, comparisons, `||` alternatives, `*`),
+/// Cargo requirements (bare versions are caret requirements) and PyPI
+/// specifiers (`==`, `!=`, `~=`, comparisons, comma-separated). Anything
+/// else, such as npm hyphen ranges (`1.0.0 - 2.0.0`), is not understood.
+pub fn admits(ecosystem: Ecosystem, constraint: &str, version: &str) -> Option<bool> {
+    let compare = |a: &str, b: &str| ecosystem.compare_versions(a, b);
+    let mut any = false;
+    for alternative in constraint.split("||") {
+        let comparators: Vec<&str> = match ecosystem {
+            Ecosystem::Npm => alternative.split_whitespace().collect(),
+            _ => alternative.split(',').map(str::trim).filter(|c| !c.is_empty()).collect(),
+        };
+        if comparators.contains(&"-") {
+            return None;
+        }
+        let mut holds = true;
+        for comparator in comparators {
+            if matches!(comparator, "*" | "x" | "latest") {
+                continue;
+            }
+            let (op, bound) = ecosystems::split_range_operator(comparator);
+            // Wildcard versions (`1.2.x`, `==2.2.*`) aren't understood, nor
+            // is a bound that isn't a version at all
+            let versionlike = |c: char| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+' | '_' | '!');
+            if bound.is_empty() || bound.contains(['x', 'X', '*']) || !bound.chars().all(versionlike) {
+                return None;
+            }
+            let op = match op.trim() {
+                "" if ecosystem == Ecosystem::CratesIo => "^",
+                op => op,
+            };
+            let ordering = compare(version, bound);
+            holds &= match op {
+                "" | "=" | "==" | "===" => ordering.is_eq(),
+                "!=" => !ordering.is_eq(),
+                ">" => ordering.is_gt(),
+                ">=" => ordering.is_ge(),
+                "<" => ordering.is_lt(),
+                "<=" => ordering.is_le(),
+                "^" => ordering.is_ge() && compare(version, &caret_upper(bound)?).is_lt(),
+                "~" => ordering.is_ge() && compare(version, &tilde_upper(bound)?).is_lt(),
+                "~=" => ordering.is_ge() && compare(version, &compatible_upper(bound)?).is_lt(),
+                _ => return None,
+            };
+        }
+        any |= holds;
+    }
+    Some(any)
+}
+
+fn numeric_parts(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+fn bumped(parts: &[u64], index: usize) -> String {
+    let mut upper: Vec<u64> = parts[..=index].to_vec();
+    upper[index] += 1;
+    upper.resize(parts.len().max(3), 0);
+    upper.iter().map(u64::to_string).collect::<Vec<_>>().join(".")
+}
+
+/// `^1.2.3` admits `<2.0.0`, `^0.2.3` `<0.3.0`, `^0.0.3` `<0.0.4`.
+fn caret_upper(version: &str) -> Option<String> {
+    let parts = numeric_parts(version)?;
+    let index = parts.iter().position(|&part| part != 0).unwrap_or(parts.len() - 1);
+    Some(bumped(&parts, index))
+}
+
+/// `~1.2.3` admits `<1.3.0`, `~1` `<2.0.0`.
+fn tilde_upper(version: &str) -> Option<String> {
+    let parts = numeric_parts(version)?;
+    Some(bumped(&parts, if parts.len() > 1 { 1 } else { 0 }))
+}
+
+/// `~=2.2.1` admits `<2.3`, `~=2.2` `<3.0`.
+fn compatible_upper(version: &str) -> Option<String> {
+    let parts = numeric_parts(version)?;
+    (parts.len() > 1).then(|| bumped(&parts, parts.len() - 2))
+}
+
+/// Compare dotted version strings numerically where possible, for the
+/// ecosystems without a comparator of their own.
+///
+/// Build metadata after `+` is ignored. A segment starting with a digit
+/// ranks above one starting with a letter, so where one version ends and
+/// the other goes on with a tag (`1.0.0-rc1`, `1.0.0.beta`, `1.0-SNAPSHOT`)
+/// the tagged one is a prerelease and ranks below; a further number
+/// (`1.0.0-1`) ranks above.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let release = |version: &str| version.split('+').next().unwrap_or_default().to_string();
+    let (a, b) = (release(a), release(b));
+    let mut left = a.split(['.', '-']);
+    let mut right = b.split(['.', '-']);
+
+    loop {
+        let ordering = match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(x), None) => segment_key(x).map_or(Ordering::Less, |_| Ordering::Greater),
+            (None, Some(y)) => segment_key(y).map_or(Ordering::Greater, |_| Ordering::Less),
+            (Some(x), Some(y)) => match (segment_key(x), segment_key(y)) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (Some(_), None) => Ordering::Greater,
+                (None, Some(_)) => Ordering::Less,
+                (None, None) => x.cmp(y),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// A segment starting with a digit as its number and what follows it
+/// (`12rc1` as 12 and `rc1`); `None` for a tag starting with a letter. An
+/// empty segment is 0.
+fn segment_key(segment: &str) -> Option<(u64, &str)> {
+    let digits = segment.find(|c: char| !c.is_ascii_digit()).unwrap_or(segment.len());
+    match (digits, segment.is_empty()) {
+        (_, true) => Some((0, "")),
+        (0, false) => None,
+        (digits, false) => Some((segment[..digits].parse().unwrap_or(u64::MAX), &segment[digits..])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_clear_overlapping_advisories_and_check_the_constraints() {
+        let need = |ecosystem, package, version, fixed_in, file| Needed {
+            ecosystem,
+            package,
+            version,
+            fixed_in,
+            file: Some(file),
+        };
+        // Three lodash advisories whose ranges all cover 4.17.15
+        let needed = [
+            need(Ecosystem::Npm, "lodash", "4.17.15", "4.17.19", "web/package-lock.json"),
+            need(Ecosystem::Npm, "lodash", "4.17.15", "4.17.21", "web/package-lock.json"),
+            need(Ecosystem::Npm, "lodash", "4.17.11", "4.17.16", "api/package-lock.json"),
+            need(Ecosystem::PyPI, "django", "2.2.0", "2.2.24", "requirements.txt"),
+            need(Ecosystem::PyPI, "django", "2.2.0", "2.2.10", "requirements.txt"),
+            need(Ecosystem::CratesIo, "time", "0.1.45", "0.2.23", "Cargo.lock"),
+        ];
+        let extract =
+            |file: &str, content: &str| ecosystems::extract_str(ecosystems::detect(file).unwrap(), file, content);
+        let mut declared = extract(
+            "web/package.json",
+            "{\n  \"dependencies\": {\n    \"lodash\": \"^4.17.15\"\n  }\n}\n",
+        );
+        declared.extend(extract("requirements.txt", "django==2.2.0\n"));
+
+        let advice = minimal_updates(&needed, &declared);
+        let summary: Vec<(&str, &str, usize, bool)> = advice
+            .iter()
+            .map(|a| (a.package.as_str(), a.recommended.as_str(), a.findings_resolved, a.constraint_change_needed))
+            .collect();
+        let expected = [
+            ("lodash", "4.17.21", 3, false),
+            ("django", "2.2.24", 2, true),
+            ("time", "0.2.23", 1, false),
+        ];
+        assert_eq!(summary, expected);
+        assert_eq!(advice[0].current, ["4.17.11", "4.17.15"]);
+        assert_eq!(advice[0].files, ["api/package-lock.json", "web/package-lock.json"]);
+        assert_eq!(advice[0].constraints[0].admits, Some(true));
+        assert_eq!(advice[1].constraints[0].constraint, "==2.2.0");
+        assert_eq!(advice[1].constraints[0].admits, Some(false));
+        assert_eq!(advice[1].files, ["requirements.txt"]);
+        assert!(advice[2].constraints.is_empty());
+
+        assert_eq!(admits(Ecosystem::Npm, "^0.2.3", "0.2.9"), Some(true));
+        assert_eq!(admits(Ecosystem::Npm, "^0.2.3", "0.3.0"), Some(false));
+        assert_eq!(admits(Ecosystem::Npm, "~1.2.3", "1.3.0"), Some(false));
+        assert_eq!(admits(Ecosystem::Npm, ">=1.0.0 <1.5.0 || ^2.0.0", "2.4.1"), Some(true));
+        assert_eq!(admits(Ecosystem::CratesIo, "0.2", "0.2.23"), Some(true));
+        assert_eq!(admits(Ecosystem::CratesIo, "=0.2.10", "0.2.23"), Some(false));
+        assert_eq!(admits(Ecosystem::PyPI, ">=2.2,<3,!=2.2.5", "2.2.24"), Some(true));
+        assert_eq!(admits(Ecosystem::PyPI, "~=2.2.1", "2.3.0"), Some(false));
+        assert_eq!(admits(Ecosystem::PyPI, "==2.2.*", "2.2.24"), None);
+        assert_eq!(admits(Ecosystem::Npm, "1.0.0 - 2.0.0", "1.5.0"), None);
+        assert_eq!(admits(Ecosystem::Npm, "^3.0.0 || 1.0.0 - 2.0.0", "3.1.0"), None);
+        assert_eq!(admits(Ecosystem::Npm, ">= 1.0.0", "1.5.0"), None);
+        assert_eq!(admits(Ecosystem::PyPI, ">=2.2; python_version < '3.8'", "2.2.24"), None);
+    }
+
+    #[test]
+    fn prereleases_rank_below_their_release() {
+        for (lower, higher) in [
+            ("1.9.9", "1.10.0"),
+            ("1.0.0-rc1", "1.0.0"),
+            ("1.0.0.beta", "1.0.0"),
+            ("1.0-SNAPSHOT", "1.0"),
+            ("1.0.0-alpha", "1.0.0-beta"),
+            ("1.0.0-rc.2", "1.0.0-rc.10"),
+            ("1.0.0-rc1", "1.0.0.1"),
+            ("1.0.0", "1.0.0-1"),
+            ("1.0", "1.0.0"),
+            ("5.4.0", "5.4.0p1"),
+        ] {
+            assert_eq!(compare_versions(lower, higher), Ordering::Less, "{} < {}", lower, higher);
+            assert_eq!(compare_versions(higher, lower), Ordering::Greater, "{} > {}", higher, lower);
+        }
+        assert_eq!(compare_versions("1.0.0+build.5", "1.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn pull_requests_group_updates_by_file_and_leave_out_constraint_changes() {
+        let fixable = |ecosystem, package, version, file, id| Fixable {
+            ecosystem,
+            package,
+            version,
+            file,
+            id,
+            severity: "HIGH".to_string(),
+            summary: format!("{} in {}", id, package),
+            url: Some("https://osv.dev/vulnerability/x"),
+        };
+        let findings = [
+            fixable(Ecosystem::Npm, "lodash", "4.17.15", "web/package-lock.json", "GHSA-2"),
+            fixable(Ecosystem::Npm, "lodash", "4.17.15", "web/package-lock.json", "GHSA-1"),
+            fixable(Ecosystem::Npm, "lodash", "4.17.11", "api/package-lock.json", "GHSA-3"),
+            fixable(Ecosystem::PyPI, "django", "2.2.0", "requirements.txt", "PYSEC-1"),
+            fixable(Ecosystem::CratesIo, "time", "0.1.45", "Cargo.lock", "RUSTSEC-1"),
+        ];
+        let needed: Vec<Needed> = [("lodash", "4.17.21"), ("django", "2.2.24"), ("time", "0.2.23")]
+            .iter()
+            .flat_map(|&(package, fixed_in)| {
+                findings.iter().filter(move |f| f.package == package).map(move |f| Needed {
+                    ecosystem: f.ecosystem,
+                    package,
+                    version: f.version,
+                    fixed_in,
+                    file: Some(f.file),
+                })
+            })
+            .collect();
+        let requirements = ecosystems::detect("requirements.txt").unwrap();
+        let declared = ecosystems::extract_str(requirements, "requirements.txt", "django==2.2.0\n");
+        let recommendations = minimal_updates(&needed, &declared);
+        let mut options = PullRequestOptions {
+            target: "//app:server",
+            branch: DEFAULT_PR_BRANCH,
+            commit_message: DEFAULT_PR_COMMIT_MESSAGE,
+            allow_constraint_changes: false,
+        };
+
+        let pr = pull_request(&findings, &recommendations, &options).unwrap().unwrap();
+        let files: Vec<&str> = pr.files.iter().map(|group| group.file.as_str()).collect();
+        assert_eq!(files, ["Cargo.lock", "api/package-lock.json", "web/package-lock.json"]);
+        let time = &pr.files[0].updates[0];
+        assert_eq!((time.from.join(","), time.to.as_str(), time.breaking), ("0.1.45".to_string(), "0.2.23", true));
+        let lodash = &pr.files[2].updates[0];
+        let ids: Vec<&str> = lodash.fixes.iter().map(|fixed| fixed.id.as_str()).collect();
+        assert_eq!((ids, lodash.breaking), (vec!["GHSA-1", "GHSA-2"], false));
+        assert_eq!(pr.excluded[0].package, "django");
+        assert_eq!(pr.excluded[0].constraints[0].constraint, "==2.2.0");
+        assert!(pr.branch.starts_with("security/updates-") && pr.branch.len() == "security/updates-".len() + 8);
+        assert_eq!(pr.title, "Update vulnerable dependencies: lodash, time");
+        let last = pr.commit_message.lines().last().unwrap();
+        assert_eq!(last, "- lodash 4.17.15 to 4.17.21 in web/package-lock.json");
+        assert!(pr.body.starts_with("Updates 3 dependencies to fix 4 known vulnerabilities.\n"), "{}", pr.body);
+        assert!(pr.body.contains("  - [GHSA-1](https://osv.dev/vulnerability/x) (HIGH): GHSA-1 in lodash\n"));
+        assert!(pr.body.contains("- **time** 0.1.45 → 0.2.23, fixing 1 vulnerability\n  - ⚠️ New major version"));
+        assert!(pr.body.contains("### Not included\n") && pr.body.contains("- **django** 2.2.0 → 2.2.24: `==2.2.0`"));
+
+        // The same findings in any order give the same pull request
+        let mut shuffled: Vec<Fixable> =
+            findings.iter().rev().map(|f| fixable(f.ecosystem, f.package, f.version, f.file, f.id)).collect();
+        shuffled.swap(0, 2);
+        let again = pull_request(&shuffled, &recommendations, &options).unwrap().unwrap();
+        assert_eq!(serde_json::to_value(&again).unwrap(), serde_json::to_value(&pr).unwrap());
+
+        options.allow_constraint_changes = true;
+        options.branch = "deps/{{target}} {{update_count}}";
+        let pr = pull_request(&findings, &recommendations, &options).unwrap().unwrap();
+        assert!(pr.excluded.is_empty() && !pr.body.contains("Not included"));
+        assert_eq!(pr.files[2].updates[0].constraint_changes[0].constraint, "==2.2.0");
+        assert!(pr.body.contains("  - Changes `==2.2.0` in `requirements.txt` to admit 2.2.24\n"));
+        assert_eq!(pr.branch, "deps/app-server-4");
+
+        options.branch = "{{#if digest}}";
+        assert!(pull_request(&findings, &recommendations, &options).is_err());
+        assert!(pull_request(&findings[..0], &recommendations, &options).unwrap().is_none());
+    }
+}
@@ -0,0 +1,306 @@
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+
+use crate::iotrace::{self, Direction, Redactor};
+
+const USAGE: &str = "usage: builder-plugin-security --replay TRACE_FILE [--ignore KEY]...";
+
+struct ReplayOptions {
+    trace: String,
+    /// Object keys left out of the comparison, e.g. timings.
+    ignore: Vec<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<ReplayOptions, String> {
+    let mut trace = None;
+    let mut ignore = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--replay" => trace = Some(value()?),
+            "--ignore" => ignore.push(value()?),
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+    }
+
+    Ok(ReplayOptions {
+        trace: trace.ok_or("--replay needs a trace file")?,
+        ignore,
+    })
+}
+
+/// One request recorded in a trace and the response the plugin sent.
+struct Exchange {
+    /// Line of the request in the trace file.
+    line: usize,
+    request: Value,
+    /// `None` when the plugin never answered, e.g. because it crashed.
+    response: Option<Value>,
+}
+
+/// The requests and responses of a `--trace-io` file, paired in order.
+/// Inbound lines that weren't valid JSON are skipped, with the parse error
+/// that answered them.
+fn parse_trace(content: &str) -> Result<Vec<Exchange>, String> {
+    let mut exchanges: Vec<Exchange> = Vec::new();
+    let mut awaiting_response = false;
+    let mut unparseable = false;
+
+    for (number, line) in content.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (direction, body) = iotrace::parse_line(line).ok_or_else(|| format!("line {}: not a trace line", number))?;
+        if direction == Direction::Inbound && body.starts_with("[unparseable") {
+            awaiting_response = false;
+            unparseable = true;
+            continue;
+        }
+        if std::mem::take(&mut unparseable) && direction == Direction::Outbound {
+            // The parse error answering it; older traces have none
+            continue;
+        }
+        let message: Value = serde_json::from_str(body).map_err(|e| format!("line {}: {}", number, e))?;
+
+        match direction {
+            Direction::Inbound => {
+                exchanges.push(Exchange {
+                    line: number,
+                    request: message,
+                    response: None,
+                });
+                awaiting_response = true;
+            }
+            Direction::Outbound if awaiting_response => {
+                if let Some(exchange) = exchanges.last_mut() {
+                    exchange.response = Some(message);
+                }
+                awaiting_response = false;
+            }
+            Direction::Outbound => return Err(format!("line {}: response without a request", number)),
+        }
+    }
+    Ok(exchanges)
+}
+
+/// The first place where a replayed response differs from the recorded one.
+#[derive(Debug, PartialEq)]
+struct Divergence {
+    /// JSON Pointer (RFC 6901) to the differing value; empty for the root.
+    pointer: String,
+    /// `None` where one side has no value at all.
+    recorded: Option<Value>,
+    replayed: Option<Value>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |value: &Option<Value>| value.as_ref().map_or("(absent)".to_string(), Value::to_string);
+        writeln!(f, "  at:       {}", if self.pointer.is_empty() { "/" } else { &self.pointer })?;
+        writeln!(f, "  recorded: {}", show(&self.recorded))?;
+        write!(f, "  replayed: {}", show(&self.replayed))
+    }
+}
+
+/// Walk both values in order (object keys sorted) and return the first
+/// difference, skipping object keys named in `ignore`.
+fn first_divergence(recorded: &Value, replayed: &Value, ignore: &[String], pointer: &str) -> Option<Divergence> {
+    let differ = |recorded: Option<&Value>, replayed: Option<&Value>| Divergence {
+        pointer: pointer.to_string(),
+        recorded: recorded.cloned(),
+        replayed: replayed.cloned(),
+    };
+
+    match (recorded, replayed) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).filter(|k| !ignore.contains(k)).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter().find_map(|key| {
+                let child = format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                match (a.get(key), b.get(key)) {
+                    (Some(x), Some(y)) => first_divergence(x, y, ignore, &child),
+                    (x, y) => Some(Divergence {
+                        pointer: child,
+                        recorded: x.cloned(),
+                        replayed: y.cloned(),
+                    }),
+                }
+            })
+        }
+        (Value::Array(a), Value::Array(b)) => (0..a.len().max(b.len())).find_map(|i| {
+            let child = format!("{}/{}", pointer, i);
+            match (a.get(i), b.get(i)) {
+                (Some(x), Some(y)) => first_divergence(x, y, ignore, &child),
+                (x, y) => Some(Divergence {
+                    pointer: child,
+                    recorded: x.cloned(),
+                    replayed: y.cloned(),
+                }),
+            }
+        }),
+        (a, b) if a == b => None,
+        (a, b) => Some(differ(Some(a), Some(b))),
+    }
+}
+
+/// Feed every recorded request through `handle`, writing the responses to
+/// `out` as the plugin would, until one differs from its recording.
+///
+/// Replayed responses are redacted like the trace was before comparing, so
+/// secrets never count as differences. Returns the request that diverged,
+/// or `None` when the whole trace replays identically.
+fn replay(
+    exchanges: &[Exchange],
+    ignore: &[String],
+    redactor: &Redactor,
+    mut handle: impl FnMut(Value) -> Value,
+    out: &mut dyn Write,
+) -> io::Result<Option<(usize, Divergence)>> {
+    for exchange in exchanges {
+        let response = handle(exchange.request.clone());
+        if response.is_null() && exchange.response.is_none() {
+            // A notification, answered by neither
+            continue;
+        }
+        writeln!(out, "{}", response)?;
+
+        let mut redacted = response;
+        redactor.redact(&mut redacted);
+        let divergence = match &exchange.response {
+            Some(recorded) => first_divergence(recorded, &redacted, ignore, ""),
+            None => Some(Divergence {
+                pointer: String::new(),
+                recorded: None,
+                replayed: Some(redacted),
+            }),
+        };
+        if let Some(divergence) = divergence {
+            return Ok(Some((exchange.line, divergence)));
+        }
+    }
+    Ok(None)
+}
+
+/// Entry point of `--replay`. Returns the process exit code: 0 when every
+/// response matches its recording, 1 at the first divergence.
+pub fn run(args: &[String], handle: impl FnMut(Value) -> Value, out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(e) => {
+            let _ = writeln!(err, "replay: {}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    let exchanges = match fs::read_to_string(&options.trace)
+        .map_err(|e| e.to_string())
+        .and_then(|content| parse_trace(&content))
+    {
+        Ok(exchanges) => exchanges,
+        Err(e) => {
+            let _ = writeln!(err, "replay: {}: {}", options.trace, e);
+            return 2;
+        }
+    };
+
+    let redactor = Redactor::new(std::env::var(iotrace::REDACT_ENV).ok().as_deref());
+    match replay(&exchanges, &options.ignore, &redactor, handle, out) {
+        Ok(None) => {
+            let _ = writeln!(err, "replay: {} responses match the trace", exchanges.len());
+            0
+        }
+        Ok(Some((line, divergence))) => {
+            let method = exchanges
+                .iter()
+                .find(|e| e.line == line)
+                .and_then(|e| e.request["method"].as_str())
+                .unwrap_or("?");
+            let _ = writeln!(
+                err,
+                "replay: response to {} (line {}) differs from the recording\n{}",
+                method, line, divergence
+            );
+            1
+        }
+        Err(e) => {
+            let _ = writeln!(err, "replay: {}", e);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TRACE: &str = r#"2026-10-14T13:51:39.960Z --> {"id":1,"jsonrpc":"2.0","method":"plugin.info"}
+2026-10-14T13:51:39.961Z <-- {"id":1,"jsonrpc":"2.0","result":{"name":"security","took_ms":3}}
+2026-10-14T13:51:40.002Z --> [unparseable, 5 bytes: expected value at line 1 column 1]
+2026-10-14T13:51:40.003Z <-- {"error":{"code":-32700},"id":null,"jsonrpc":"2.0"}
+2026-10-14T13:51:40.100Z --> {"id":2,"jsonrpc":"2.0","method":"build.pre_hook","params":{"token":"[REDACTED]"}}
+2026-10-14T13:51:40.200Z <-- {"id":2,"jsonrpc":"2.0","result":{"logs":["a","b"],"token":"[REDACTED]"}}
+"#;
+
+    fn respond(logs: &'static [&'static str]) -> impl FnMut(Value) -> Value {
+        move |request| match request["method"].as_str() {
+            Some("plugin.info") => json!({"id": 1, "jsonrpc": "2.0", "result": {"name": "security", "took_ms": 9}}),
+            _ => json!({"id": 2, "jsonrpc": "2.0", "result": {"logs": logs, "token": "ghp_live"}}),
+        }
+    }
+
+    #[test]
+    fn replays_requests_and_reports_the_first_divergence() {
+        let exchanges = parse_trace(TRACE).unwrap();
+        assert_eq!(exchanges.iter().map(|e| e.line).collect::<Vec<_>>(), vec![1, 5]);
+        let redactor = Redactor::new(None);
+        let ignore = vec!["took_ms".to_string()];
+
+        let mut out = Vec::new();
+        let outcome = replay(&exchanges, &ignore, &redactor, respond(&["a", "b"]), &mut out).unwrap();
+        assert!(outcome.is_none());
+        // Responses go out as the plugin sent them, unredacted
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+
+        let outcome = replay(&exchanges, &ignore, &redactor, respond(&["a", "c", "d"]), &mut Vec::new()).unwrap();
+        let (line, divergence) = outcome.unwrap();
+        assert_eq!(line, 5);
+        assert_eq!(
+            divergence,
+            Divergence {
+                pointer: "/result/logs/1".to_string(),
+                recorded: Some(json!("b")),
+                replayed: Some(json!("c")),
+            }
+        );
+
+        let (line, divergence) = replay(&exchanges, &[], &redactor, respond(&["a", "b"]), &mut Vec::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!((line, divergence.pointer.as_str()), (1, "/result/took_ms"));
+    }
+
+    #[test]
+    fn unanswered_requests_diverge() {
+        let trace = "2026-10-14T13:51:39.960Z --> {\"id\":1,\"method\":\"build.pre_hook\"}\n";
+        let exchanges = parse_trace(trace).unwrap();
+        let (_, divergence) = replay(&exchanges, &[], &Redactor::new(None), |_| json!({"id": 1}), &mut Vec::new())
+            .unwrap()
+            .unwrap();
+        assert_eq!(divergence.recorded, None);
+        assert_eq!(divergence.to_string().lines().next(), Some("  at:       /"));
+
+        // A notification the plugin doesn't answer either replays as nothing
+        let exchanges = parse_trace("2026-10-14T13:51:39.960Z --> {\"method\":\"event.notify\"}\n").unwrap();
+        let mut out = Vec::new();
+        assert!(replay(&exchanges, &[], &Redactor::new(None), |_| Value::Null, &mut out).unwrap().is_none());
+        assert!(out.is_empty());
+
+        assert!(parse_trace("2026-10-14T13:51:39.961Z <-- {\"id\":1}\n").is_err());
+    }
+}
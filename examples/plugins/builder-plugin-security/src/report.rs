@@ -0,0 +1,518 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::ecosystems::DetectedBy;
+use crate::fsutil;
+use crate::lifecycle;
+use crate::reachability;
+use crate::registries::InternalPackage;
+use crate::remediate::Recommendation;
+use crate::severity::Severity;
+use crate::unscanned::NotScanned;
+
+/// Version of the report envelope layout, bumped on breaking changes.
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// Top-level structure of `security-report.json`.
+///
+/// Wraps the findings with enough metadata to answer "when was this scanned,
+/// against which database, and with which policy?".
+#[derive(Serialize, JsonSchema)]
+pub struct ReportEnvelope<'a, V: Serialize> {
+    pub schema_version: u32,
+    pub tool: ToolInfo,
+    pub scan: ScanTiming,
+    pub databases: Vec<DbSnapshot>,
+    /// Set when infrastructure errors may have kept findings out, whatever
+    /// `on_error` made of them.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub degraded_reasons: &'a [String],
+    /// How each finding's `fingerprint` is derived, so trackers keying off
+    /// it can verify them.
+    pub fingerprint_algorithm: &'static str,
+    /// With `import_hints`, the languages checked and what the check can't
+    /// see.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import_hints: Option<&'a reachability::Summary>,
+    pub files: &'a [ScannedFile],
+    /// Likely dependency files left out, and why.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub not_scanned: &'a [NotScanned],
+    /// Packages from `internal_registries`, which weren't matched against
+    /// advisories.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub internal_packages: &'a [InternalPackage],
+    pub policy: PolicySummary<'a>,
+    /// Findings per CWE id, `UNKNOWN` for those without CWE data. A
+    /// finding with several ids counts under each.
+    pub cwe_counts: BTreeMap<String, usize>,
+    /// Per-project counts, with `report_group_by: "project"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projects: Option<&'a [ProjectSummary]>,
+    /// With `changed_files` in the pre_hook params, which findings the
+    /// change introduced and which were there before it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<ChangeSummary<'a>>,
+    /// With `report_history`, how many findings are new, recurring,
+    /// regressed and resolved, and those fixed since the previous scan.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<&'a lifecycle::Summary>,
+    /// Per vulnerable package, the smallest update clearing its findings;
+    /// the log's recommendations render the same list.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub recommendations: &'a [Recommendation],
+    /// With `allowlist`, the dependencies it doesn't approve, kept apart
+    /// from the advisory findings whether or not they have any.
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub unapproved_dependencies: &'a [V],
+    pub vulnerabilities: &'a [V],
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ToolInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ScanTiming {
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_ms: u128,
+    pub phases: PhaseTimings,
+    /// Phases a time limit cut short; the findings only cover what they
+    /// completed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub timed_out: Vec<TimedOutPhase>,
+    /// How many package lookups the advisory query cache answered; absent
+    /// when no source caches queries, and in reproducible reports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_cache: Option<QueryCacheStats>,
+    /// Dependency files considered, by extraction outcome (`parsed`,
+    /// `not_found`, `unreadable`, `unsupported`, `excluded`, `too_large`).
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extraction: BTreeMap<&'static str, usize>,
+}
+
+/// Package versions answered from the advisory query cache, and those the
+/// sources were queried for.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub struct QueryCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// A scan phase stopped by its budget or the scan timeout.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TimedOutPhase {
+    pub phase: &'static str,
+    /// The setting whose limit was reached.
+    pub limit: &'static str,
+    /// How far the phase got, e.g. `3 of 7 dependency files read`.
+    pub completed: String,
+}
+
+/// Wall-clock time spent in each scan phase.
+#[derive(Serialize, Default, Clone, Copy, JsonSchema)]
+pub struct PhaseTimings {
+    pub extract_ms: u128,
+    pub query_ms: u128,
+    pub report_ms: u128,
+}
+
+/// Identifies the advisory data a scan was run against.
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct DbSnapshot {
+    pub source: String,
+    pub id: String,
+    /// SHA-256 of the snapshot's contents, for sources cached locally.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// When the snapshot was fetched; `None` for data compiled into the plugin.
+    pub fetched_at: Option<String>,
+    pub age_secs: Option<u64>,
+    /// After a partial refresh (`db_refresh = "partial"`), how current each
+    /// ecosystem's advisories are; absent when the whole database was. With
+    /// `db_refresh = "scheduled"`, the workspace's ecosystems and their
+    /// schedules.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub freshness: Vec<EcosystemFreshness>,
+}
+
+/// Where one ecosystem's advisories came from in a partial or scheduled
+/// refresh.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EcosystemFreshness {
+    pub ecosystem: &'static str,
+    /// Whether they are the database's current advisories.
+    pub fresh: bool,
+    /// The earlier snapshot serving them, when they aren't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    pub fetched_at: Option<String>,
+    /// With `db_refresh = "scheduled"`, when a scan of the workspace last
+    /// used the ecosystem.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_used: Option<String>,
+    /// With `db_refresh = "scheduled"`, from when a changed database
+    /// refreshes the ecosystem's advisories.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_refresh: Option<String>,
+}
+
+/// A dependency file that was read during the scan.
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct ScannedFile {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+    pub dependencies: usize,
+    /// Whether the format came from the file name or an override.
+    pub detected_by: DetectedBy,
+    /// The `ecosystem_overrides` entry that decided the format.
+    #[serde(rename = "override", skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+/// The findings of a scan given `changed_files`, in two sections.
+#[derive(Serialize, JsonSchema)]
+pub struct ChangeSummary<'a> {
+    pub changed_files: Vec<&'a str>,
+    pub introduced: Vec<FindingRef<'a>>,
+    pub pre_existing: Vec<FindingRef<'a>>,
+}
+
+/// Enough of a finding to find it in `vulnerabilities`.
+#[derive(Serialize, JsonSchema)]
+pub struct FindingRef<'a> {
+    pub id: &'a str,
+    pub package: &'a str,
+    pub version: &'a str,
+    pub severity: Severity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<&'a str>,
+    pub fingerprint: &'a str,
+}
+
+/// Findings in one project of the workspace.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProjectSummary {
+    /// Workspace-relative directory of the project; `.` for the root.
+    pub project: String,
+    /// Findings per severity label, including zeros.
+    pub counts: BTreeMap<String, usize>,
+    pub total: usize,
+}
+
+/// The fail policy that was in effect for the scan.
+#[derive(Serialize, JsonSchema)]
+pub struct PolicySummary<'a> {
+    pub fail_on_critical: bool,
+    pub fail_on_high: bool,
+    /// The named policy deciding pass or fail instead of the thresholds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+    pub ignore_vulnerabilities: &'a [String],
+}
+
+/// How report files are compressed (`report_compression`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// Leading bytes identifying compressed files, whatever they are named.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+impl Compression {
+    const ALL: [Compression; 3] = [Compression::None, Compression::Gzip, Compression::Zstd];
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Compression::None),
+            "gzip" => Ok(Compression::Gzip),
+            "zstd" if cfg!(feature = "zstd") => Ok(Compression::Zstd),
+            "zstd" => Err("report_compression 'zstd' needs a build with the `zstd` feature".to_string()),
+            other => Err(format!(
+                "report_compression: expected 'none', 'gzip' or 'zstd', got '{}'",
+                other
+            )),
+        }
+    }
+
+    /// `path` with this compression's extension appended.
+    fn path_for(self, path: &Path) -> PathBuf {
+        let extension = match self {
+            Compression::None => return path.to_path_buf(),
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        };
+        let mut name = path.as_os_str().to_os_string();
+        name.push(extension);
+        PathBuf::from(name)
+    }
+
+    fn compress(self, contents: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(contents.to_vec()),
+            Compression::Gzip => {
+                // flate2 leaves the header timestamp at zero, so equal
+                // reports compress to equal files
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(contents)?;
+                encoder.finish()
+            }
+            Compression::Zstd => zstd_encode(contents),
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_encode(contents: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::encode_all(contents, 0)
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decode(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::decode_all(bytes)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_encode(_: &[u8]) -> io::Result<Vec<u8>> {
+    Err(zstd_unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decode(_: &[u8]) -> io::Result<Vec<u8>> {
+    Err(zstd_unsupported())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "zstd needs a build with the `zstd` feature")
+}
+
+/// Atomically write a report to `path`, with the compression's extension
+/// appended, and remove its other variants so readers never pick up a
+/// stale one. Returns the path written.
+pub fn write_report(path: &Path, contents: &[u8], compression: Compression) -> io::Result<PathBuf> {
+    let target = compression.path_for(path);
+    fsutil::write_atomic(&target, &compression.compress(contents)?)?;
+    for other in Compression::ALL.iter().filter(|c| **c != compression) {
+        let _ = fsutil::remove_file(other.path_for(path));
+    }
+    Ok(target)
+}
+
+/// Read a report file, decompressing it if its content is gzip or zstd.
+///
+/// When `path` itself doesn't exist, its `.gz` and `.zst` variants are
+/// tried, so callers can keep using the plain report name.
+fn read_report(path: &Path) -> Result<(PathBuf, String), String> {
+    let path = Compression::ALL
+        .iter()
+        .map(|c| c.path_for(path))
+        .find(|candidate| candidate.is_file())
+        .unwrap_or_else(|| path.to_path_buf());
+    let describe = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+
+    let bytes = fsutil::read(&path).map_err(|e| describe(&e))?;
+    let bytes = if bytes.starts_with(GZIP_MAGIC) {
+        let mut decoded = Vec::new();
+        GzDecoder::new(&bytes[..]).read_to_end(&mut decoded).map_err(|e| describe(&e))?;
+        decoded
+    } else if bytes.starts_with(ZSTD_MAGIC) {
+        zstd_decode(&bytes).map_err(|e| describe(&e))?
+    } else {
+        bytes
+    };
+    let content = String::from_utf8(bytes).map_err(|e| describe(&e))?;
+    Ok((path, content))
+}
+
+/// Read the findings from a report in either the envelope or the legacy
+/// bare-array format, compressed or not. An envelope's unapproved
+/// dependencies come after its vulnerabilities.
+pub fn load_report<V: DeserializeOwned>(path: &Path) -> Result<Vec<V>, String> {
+    let (path, content) = read_report(path)?;
+    let path = path.as_path();
+    let value: Value = serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let findings = match value {
+        Value::Array(_) => value,
+        Value::Object(mut envelope) => {
+            let mut findings = Vec::new();
+            for key in ["vulnerabilities", "unapproved_dependencies"] {
+                if let Some(Value::Array(section)) = envelope.remove(key) {
+                    findings.extend(section);
+                }
+            }
+            Value::Array(findings)
+        }
+        _ => return Err(format!("{}: not a security report", path.display())),
+    };
+    serde_json::from_value(findings).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The metadata of a report in the envelope format, compressed or not: the
+/// envelope without its findings, and the path of the variant read.
+pub fn load_metadata(path: &Path) -> Result<(PathBuf, Value), String> {
+    let (path, content) = read_report(path)?;
+    match serde_json::from_str(&content) {
+        Ok(Value::Object(mut envelope)) => {
+            envelope.remove("vulnerabilities");
+            envelope.remove("unapproved_dependencies");
+            Ok((path, Value::Object(envelope)))
+        }
+        Ok(_) => Err(format!("{}: not a report envelope", path.display())),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Format a timestamp as RFC 3339 in UTC, e.g. `2024-06-01T12:00:00Z`.
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Parse an RFC 3339 timestamp, or a bare `YYYY-MM-DD` date as its
+/// midnight UTC. Fractional seconds are dropped.
+pub fn parse_timestamp(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = value.get(range)?;
+        digits.bytes().all(|b| b.is_ascii_digit()).then(|| digits.parse().ok())?
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    if value.get(4..5) != Some("-") || value.get(7..8) != Some("-") || !(1..=12).contains(&month) {
+        return None;
+    }
+    let mut secs = days_from_civil(year, month as u32, day as u32) * 86_400;
+    if let Some(time) = value.get(10..).filter(|time| !time.is_empty()) {
+        if !time.starts_with(['T', 't', ' ']) || value.get(13..14) != Some(":") || value.get(16..17) != Some(":") {
+            return None;
+        }
+        secs += number(11..13)? * 3600 + number(14..16)? * 60 + number(17..19)?;
+        let zone = value[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+        secs -= match zone {
+            "Z" | "z" => 0,
+            _ => {
+                let sign = match zone.get(..1)? {
+                    "+" => 1,
+                    "-" => -1,
+                    _ => return None,
+                };
+                let offset = zone.get(1..)?.split_once(':')?;
+                sign * (offset.0.parse::<i64>().ok()? * 3600 + offset.1.parse::<i64>().ok()? * 60)
+            }
+        };
+    }
+    Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+}
+
+/// The time reproducible reports are stamped with: `SOURCE_DATE_EPOCH`
+/// seconds, or the Unix epoch when it is unset or not a number.
+pub fn reproducible_time(source_date_epoch: Option<&str>) -> SystemTime {
+    source_date_epoch
+        .and_then(|secs| secs.trim().parse().ok())
+        .map_or(UNIX_EPOCH, |secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) civil date.
+///
+/// Howard Hinnant's `civil_from_days` algorithm; exact for the proleptic
+/// Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days since the Unix epoch of a civil date; the inverse of
+/// [`civil_from_days`].
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Lowercase hex encoding of a digest.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn formats_and_parses_rfc3339_utc() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        assert_eq!(format_timestamp(leap_day), "2024-02-29T12:34:56Z");
+        assert_eq!(parse_timestamp("2024-02-29T12:34:56Z"), Some(leap_day));
+        assert_eq!(parse_timestamp("2024-02-29T14:34:56.250+02:00"), Some(leap_day));
+        assert_eq!(parse_timestamp("1970-01-02"), Some(UNIX_EPOCH + Duration::from_secs(86_400)));
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn compressed_reports_read_back_under_the_plain_name() {
+        let dir = crate::testutil::scratch_dir("report-compression");
+        let path = dir.join("security-report.json");
+        let findings = br#"{"vulnerabilities": [{"id": "CVE-1"}]}"#;
+
+        let written = write_report(&path, findings, Compression::Gzip).unwrap();
+        assert_eq!(written, dir.join("security-report.json.gz"));
+        assert!(fs::read(&written).unwrap().starts_with(GZIP_MAGIC));
+        assert!(!path.exists());
+        let loaded: Vec<serde_json::Value> = load_report(&path).unwrap();
+        assert_eq!(loaded, vec![serde_json::json!({"id": "CVE-1"})]);
+
+        // Switching back replaces the stale compressed variant
+        write_report(&path, br#"[{"id": "CVE-2"}]"#, Compression::None).unwrap();
+        assert!(!written.exists());
+        let loaded: Vec<serde_json::Value> = load_report(&path).unwrap();
+        assert_eq!(loaded, vec![serde_json::json!({"id": "CVE-2"})]);
+
+        assert_eq!(Compression::parse("gzip"), Ok(Compression::Gzip));
+        assert!(Compression::parse("brotli").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
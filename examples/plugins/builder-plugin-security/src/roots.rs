@@ -0,0 +1,142 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+use crate::report;
+use crate::ScanResult;
+
+/// Directory under `report_dir` each root's reports and caches go in, by
+/// the root's hash.
+pub const ROOTS_DIR: &str = "roots";
+
+/// One root's scan, in the result of a `build.pre_hook` given
+/// `workspace.roots`.
+#[derive(Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct RootScan {
+    /// The root as the invocation's root sees it, e.g. `services/api`.
+    pub root: String,
+    /// Hash of `root` that its reports and caches are namespaced by.
+    pub hash: String,
+    /// The root's scan; its logs are in the combined result's.
+    #[serde(flatten)]
+    pub result: ScanResult,
+}
+
+/// What a scan of several roots found and decided. Serialized, it is the
+/// result of a `build.pre_hook` given `workspace.roots`.
+#[derive(Serialize, JsonSchema)]
+#[non_exhaustive]
+pub struct RootsResult {
+    /// Whether every root's scan passed its policy.
+    pub success: bool,
+    /// Every root's logs, each after a line naming the root.
+    pub logs: Vec<String>,
+    /// The files every root's scan wrote.
+    pub outputs: Vec<String>,
+    /// Each root's scan, in the order `workspace.roots` lists them.
+    pub roots: Vec<RootScan>,
+}
+
+/// The roots `workspace.roots` lists, relative to the invocation's root
+/// and without repeats.
+pub fn labels(base: &Path, roots: &[String]) -> Vec<String> {
+    let mut labels: Vec<String> = Vec::new();
+    for root in roots {
+        let label = paths::workspace_relative(base, root);
+        if !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
+    labels
+}
+
+/// Stable hash of a root, from its path relative to the invocation's root
+/// so it is the same on every checkout.
+pub fn hash(label: &str) -> String {
+    report::hex(&Sha256::digest(label.as_bytes()))[..16].to_string()
+}
+
+/// Where a root's reports and caches go: its own `report_dir` under the
+/// invocation's.
+pub fn report_dir(base: &Path, report_dir: &str, hash: &str) -> PathBuf {
+    base.join(report_dir).join(ROOTS_DIR).join(hash)
+}
+
+/// `path`, relative to the invocation's root, relative to `root` instead;
+/// `None` when it isn't under it.
+fn under(root: &str, path: &str) -> Option<String> {
+    if root == "." {
+        return Some(path.to_string());
+    }
+    path.strip_prefix(root)?.strip_prefix('/').map(str::to_string)
+}
+
+/// The files among `files` that belong to `label`, relative to it: those
+/// under it and under none of the other `labels` nested in it.
+pub fn files_of(base: &Path, labels: &[String], label: &str, files: &[String]) -> Vec<String> {
+    files
+        .iter()
+        .filter_map(|file| {
+            let file = paths::workspace_relative(base, file);
+            let owner = labels.iter().filter(|root| under(root, &file).is_some()).max_by_key(|root| root.len())?;
+            if owner == label {
+                under(label, &file)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// How many of `files` are under none of `labels`, and so aren't scanned.
+pub fn outside(base: &Path, labels: &[String], files: &[String]) -> usize {
+    let outside = |file: &String| {
+        let file = paths::workspace_relative(base, file);
+        labels.iter().all(|root| under(root, &file).is_none())
+    };
+    files.iter().filter(|file| outside(file)).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn files_go_to_the_innermost_root_they_are_under() {
+        let base = Path::new("/repo");
+        let labels = labels(base, &strings(&["services/api", "./services/api", "services", "/repo/web"]));
+        assert_eq!(labels, strings(&["services/api", "services", "web"]));
+
+        let files = strings(&[
+            "services/api/requirements.txt",
+            "services/worker/Cargo.lock",
+            "/repo/web/package-lock.json",
+            "tools/go.mod",
+            "services/apiary/go.mod",
+        ]);
+        assert_eq!(files_of(base, &labels, "services/api", &files), strings(&["requirements.txt"]));
+        assert_eq!(
+            files_of(base, &labels, "services", &files),
+            strings(&["worker/Cargo.lock", "apiary/go.mod"])
+        );
+        assert_eq!(files_of(base, &labels, "web", &files), strings(&["package-lock.json"]));
+        assert_eq!(outside(base, &labels, &files), 1);
+        assert_eq!(files_of(base, &strings(&["."]), ".", &files).len(), files.len());
+    }
+
+    #[test]
+    fn roots_are_namespaced_by_a_stable_hash() {
+        assert_eq!(hash("services/api"), hash("services/api"));
+        assert_ne!(hash("services/api"), hash("services/web"));
+        assert_eq!(hash("web").len(), 16);
+        let dir = report_dir(Path::new("/repo"), ".builder-cache", &hash("web"));
+        assert_eq!(dir, Path::new("/repo/.builder-cache/roots").join(hash("web")));
+    }
+}
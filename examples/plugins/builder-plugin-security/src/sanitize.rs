@@ -0,0 +1,232 @@
+const ELLIPSIS: char = '…';
+
+/// Longest advisory summary, in characters.
+pub const SUMMARY_CHARS: usize = 120;
+
+/// Sanitize advisory text for a log line: strip terminal escapes, control
+/// and bidi characters and markdown syntax, collapse whitespace, and truncate
+/// to `max_chars` characters including the ellipsis.
+///
+/// Descriptions come from third parties and can be kilobytes of markdown;
+/// file reports keep the original text.
+pub fn for_log(text: &str, max_chars: usize) -> String {
+    let plain = strip_markdown(&strip_escapes(text));
+    let collapsed = plain.split_whitespace().collect::<Vec<_>>().join(" ");
+    truncate(&collapsed, max_chars)
+}
+
+/// Short title of an advisory for logs, chat and annotations: the source's
+/// own `summary` if it has one, else the first sentence of `description`.
+/// Sanitized like [`for_log`] and cut at a word boundary to
+/// [`SUMMARY_CHARS`].
+pub fn summary(summary: &str, description: &str) -> String {
+    let text = match summary.trim() {
+        "" => first_sentence(description),
+        summary => summary.to_string(),
+    };
+    let plain = for_log(&text, usize::MAX);
+    if plain.chars().count() <= SUMMARY_CHARS {
+        return plain;
+    }
+    let cut: String = plain.chars().take(SUMMARY_CHARS).collect();
+    let words = cut.rsplit_once(' ').map_or(cut.as_str(), |(words, _)| words);
+    format!("{}{}", words.trim_end_matches([',', ';', ':', ' ']), ELLIPSIS)
+}
+
+/// The first sentence of the first paragraph of prose, past headings and
+/// code blocks.
+fn first_sentence(text: &str) -> String {
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut in_code = false;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code || (paragraph.is_empty() && line.starts_with('#')) {
+            continue;
+        }
+        if line.is_empty() {
+            if paragraph.is_empty() {
+                continue;
+            }
+            break;
+        }
+        paragraph.push(line);
+    }
+    let paragraph = paragraph.join(" ");
+    let end = [". ", "? ", "! "].iter().filter_map(|end| paragraph.find(end)).min();
+    match end {
+        Some(end) => paragraph[..=end].to_string(),
+        None => paragraph,
+    }
+}
+
+/// Whether a reference URL from an advisory is safe to emit: `http` or
+/// `https` with a host, and nothing but printable ASCII, so no other scheme
+/// (`javascript:`, `file:`), whitespace, escape or bidi character can reach
+/// a log or report.
+pub fn is_web_url(url: &str) -> bool {
+    let lower = url.get(..8).unwrap_or(url).to_ascii_lowercase();
+    let rest = if lower.starts_with("https://") {
+        &url[8..]
+    } else if lower.starts_with("http://") {
+        &url[7..]
+    } else {
+        return false;
+    };
+    !rest.is_empty() && !rest.starts_with('/') && url.chars().all(|c| c.is_ascii_graphic())
+}
+
+/// Remove ANSI/VT escape sequences and neutralize control characters.
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => match chars.next() {
+                // CSI: parameters and intermediates up to a final byte
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('\u{40}'..='\u{7e}').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC, DCS, APC, PM, SOS: up to BEL or ST (ESC \)
+                Some(']' | 'P' | '_' | '^' | 'X') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\u{7}' {
+                            break;
+                        }
+                        if c == '\u{1b}' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // Two-character sequences; a trailing ESC is just dropped
+                _ => {}
+            },
+            // 8-bit CSI
+            '\u{9b}' => {
+                for c in chars.by_ref() {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() && !is_bidi_control(c) => out.push(' '),
+            c if c.is_control() || is_bidi_control(c) => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Explicit directional formatting characters, which can make a log line
+/// display differently from what it contains.
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{061c}' | '\u{200e}' | '\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Reduce markdown to its text: drop code fences, heading/quote/list
+/// markers, emphasis and inline-code markers, and keep only link text.
+fn strip_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+
+    for word in text.split(' ') {
+        let word = word.trim_start_matches(['#', '>']);
+        let word = match word {
+            "-" | "*" | "+" => "",
+            _ => word,
+        };
+        if word.starts_with("```") || word.starts_with("~~~") {
+            out.push(' ');
+            continue;
+        }
+        out.push_str(&strip_links(word).replace(['`', '*'], "").replace("__", ""));
+        out.push(' ');
+    }
+    out
+}
+
+/// Replace `[text](url)` with `text`.
+fn strip_links(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut rest = word;
+    while let Some(start) = rest.find("](") {
+        let Some(end) = rest[start..].find(')') else {
+            break;
+        };
+        out.push_str(&rest[..start].replacen('[', "", 1));
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    out.truncate(out.trim_end().len());
+    out.push(ELLIPSIS);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_sequences_never_survive() {
+        let adversarial = "\u{1b}[2J\u{1b}[31mCritical\u{1b}[0m overflow\u{1b}]0;pwned\u{7} in \u{1b}]8;;http://evil\u{1b}\\parser\u{1b}]8;;\u{1b}\\\r\n\u{9b}1;1H\u{202e}txt.exe\u{0}\u{7}";
+        let sanitized = for_log(adversarial, 200);
+
+        assert_eq!(sanitized, "Critical overflow in parser txt.exe");
+        assert!(!sanitized.chars().any(|c| c.is_control() || is_bidi_control(c)));
+    }
+
+    #[test]
+    fn markdown_is_flattened_and_truncated() {
+        let description = "## Impact\n\nA **crafted** `Content-Type` header in [express](https://expressjs.com) allows:\n\n```js\nres.redirect(req.query.url)\n```\n\n- open redirects\n- phishing";
+        assert_eq!(
+            for_log(description, 200),
+            "Impact A crafted Content-Type header in express allows: res.redirect(req.query.url) open redirects phishing"
+        );
+        assert_eq!(for_log(description, 20), "Impact A crafted Co…");
+        assert_eq!(for_log(&"x".repeat(10_000), 10).chars().count(), 10);
+    }
+
+    #[test]
+    fn summaries_are_one_plain_sentence() {
+        let description = "## Impact\n\nA **crafted** `Content-Type` header in [express](https://expressjs.com)\n\
+                           allows open redirects. Upgrade to 4.19.2.\n\n- phishing";
+        assert_eq!(summary("", description), "A crafted Content-Type header in express allows open redirects.");
+        assert_eq!(summary("Open\nredirect in `res.location`", description), "Open redirect in res.location");
+
+        let long = summary("", &"word ".repeat(100));
+        assert!(long.chars().count() <= SUMMARY_CHARS && long.ends_with("word…"), "{}", long);
+    }
+
+    #[test]
+    fn only_plain_http_urls_are_web_urls() {
+        assert!(is_web_url("https://github.com/advisories/GHSA-jf85-cpcp-j695"));
+        assert!(is_web_url("HTTP://example.com/a?b=c#d"));
+        for url in [
+            "javascript:alert(1)",
+            "file:///etc/passwd",
+            "https://",
+            "https:///path",
+            "https://example.com/a b",
+            "https://example.com/\u{1b}]8;;x",
+            "https://exa\u{202e}mple.com",
+            "ftp://example.com",
+        ] {
+            assert!(!is_web_url(url), "{}", url);
+        }
+    }
+}
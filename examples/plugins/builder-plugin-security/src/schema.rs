@@ -0,0 +1,136 @@
+use schemars::generate::SchemaSettings;
+use schemars::{JsonSchema, Schema};
+use serde_json::{Map, Value};
+use std::io::Write;
+
+use crate::allowlist::AllowlistFile;
+use crate::config::ConfigFile;
+use crate::report::ReportEnvelope;
+use crate::suppressions::SuppressionFile;
+use crate::{RootsResult, ScanResult, Vulnerability};
+
+/// Documents `plugin.schema` describes, with what each one is.
+pub const DOCUMENTS: &[(&str, &str)] = &[
+    ("report", "the JSON report envelope (security-report.json)"),
+    ("hook-result", "the result of build.pre_hook"),
+    ("roots-result", "the result of build.pre_hook given workspace.roots"),
+    ("config", "the config file (.builder-security.toml), as parsed from TOML"),
+    ("suppressions", "the suppression file (.builder-security-ignore.yml), as parsed from YAML"),
+    ("allowlist", "an allowlist file (the allowlist setting), as parsed from TOML"),
+];
+
+/// JSON Schema (draft 2020-12) of the document named `name`.
+///
+/// Schemas come from the same serde types that write or read each
+/// document, so they can't drift from it: outputs are described as the
+/// plugin serializes them, inputs as it accepts them.
+pub fn document(name: &str) -> Option<Schema> {
+    match name {
+        "report" => Some(output::<ReportEnvelope<'static, Vulnerability>>()),
+        "hook-result" => Some(output::<ScanResult>()),
+        "roots-result" => Some(output::<RootsResult>()),
+        "config" => Some(input::<ConfigFile>()),
+        "suppressions" => Some(input::<SuppressionFile>()),
+        "allowlist" => Some(input::<AllowlistFile>()),
+        _ => None,
+    }
+}
+
+/// Every document's schema, by name.
+pub fn all() -> Map<String, Value> {
+    DOCUMENTS
+        .iter()
+        .filter_map(|(name, _)| Some((name.to_string(), document(name)?.to_value())))
+        .collect()
+}
+
+fn output<T: JsonSchema>() -> Schema {
+    SchemaSettings::draft2020_12().for_serialize().into_generator().into_root_schema_for::<T>()
+}
+
+fn input<T: JsonSchema>() -> Schema {
+    SchemaSettings::draft2020_12().for_deserialize().into_generator().into_root_schema_for::<T>()
+}
+
+/// The schemas `plugin.schema` returns for its `name` param: the named one
+/// only, or all of them without one.
+pub fn requested(name: Option<&str>) -> Result<Map<String, Value>, String> {
+    let Some(name) = name else {
+        return Ok(all());
+    };
+    let schema = document(name).ok_or_else(|| {
+        let names: Vec<&str> = DOCUMENTS.iter().map(|(name, _)| *name).collect();
+        format!("unknown schema '{}' (expected one of {})", name, names.join(", "))
+    })?;
+    Ok(Map::from_iter([(name.to_string(), schema.to_value())]))
+}
+
+const USAGE: &str = "usage: builder-plugin-security schema [report|hook-result|roots-result|config|suppressions|allowlist]";
+
+/// `builder-plugin-security schema [NAME]`: print the JSON Schema of one
+/// document, or an object of all of them by name.
+pub fn cli(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    if args.len() > 1 {
+        let _ = writeln!(err, "{}", USAGE);
+        return 2;
+    }
+    let schemas = match requested(args.first().map(String::as_str)) {
+        Ok(schemas) => schemas,
+        Err(e) => {
+            let _ = writeln!(err, "{}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let output = match args.first() {
+        Some(name) => schemas[name].clone(),
+        None => Value::Object(schemas),
+    };
+    let _ = writeln!(out, "{}", serde_json::to_string_pretty(&output).unwrap());
+    0
+}
+
+/// Assert `instance` is valid against the schema of document `name`.
+#[cfg(test)]
+pub fn assert_valid(name: &str, instance: &Value) {
+    let schema = document(name).unwrap().to_value();
+    let validator = jsonschema::validator_for(&schema).unwrap();
+    let errors: Vec<String> =
+        validator.iter_errors(instance).map(|e| format!("{} at {}", e, e.instance_path())).collect();
+    assert!(errors.is_empty(), "{} does not match its schema: {:?}", name, errors);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn documented_inputs_and_the_report_snapshot_match_their_schemas() {
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata");
+        let report: Value = serde_json::from_str(&std::fs::read_to_string(testdata.join("report.snapshot.json")).unwrap())
+            .unwrap();
+        assert_valid("report", &report);
+
+        // The README's example config file
+        let readme = include_str!("../README.md");
+        let example = readme.split("```toml\n").nth(1).unwrap().split("```").next().unwrap();
+        let config: Value = toml::from_str(example).unwrap();
+        assert_valid("config", &config);
+
+        let mut typo = config.clone();
+        typo["scan"]["exlude"] = Value::Array(Vec::new());
+        let schema = document("config").unwrap().to_value();
+        assert!(!jsonschema::is_valid(&schema, &typo));
+
+        let suppressions = crate::suppressions::parse(
+            "suppressions:\n  - id: CVE-2021-1234\n    package: lodash\n    reason: \"not reachable\"\n    \
+             expires: 2030-01-01\n  - id: GHSA-xxxx\n",
+        )
+        .unwrap();
+        let file = serde_json::to_value(SuppressionFile { suppressions }).unwrap();
+        assert_valid("suppressions", &file);
+
+        assert_eq!(all().len(), DOCUMENTS.len());
+        assert!(requested(Some("reprot")).unwrap_err().contains("expected one of report, hook-result, roots-result"));
+    }
+}
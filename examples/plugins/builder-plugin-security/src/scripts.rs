@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::fsutil;
+use crate::report;
+use crate::severity::Severity;
+use crate::summary;
+use crate::Vulnerability;
+
+/// Finding for a package with an install or build script that the previous
+/// build didn't have.
+pub const SCRIPT_ADDED: &str = "INSTALL-SCRIPT-ADDED";
+
+/// npm lifecycle scripts that run when a package is installed.
+const NPM_INSTALL_SCRIPTS: &[&str] = &["preinstall", "install", "postinstall"];
+
+/// Calls in a `setup.py` that run more than the `setup()` declaration.
+const SETUP_PY_EXECUTION: &[&str] = &["cmdclass", "subprocess", "os.system", "exec(", "eval(", "urlopen", "urllib"];
+
+/// A dependency that runs code of its own when installed or built.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScriptPackage {
+    pub ecosystem: String,
+    pub name: String,
+    pub version: String,
+    /// `preinstall`/`install`/`postinstall`, `build.rs` or `setup.py`.
+    pub scripts: Vec<String>,
+    /// SHA-256 of the script contents (npm script commands, the build
+    /// script or `setup.py`).
+    pub sha256: String,
+    /// Workspace-relative file declaring the script.
+    pub file: String,
+}
+
+/// Directory holding the per-target script baselines, under the cache root.
+pub fn baselines_dir(cache_root: &Path) -> PathBuf {
+    cache_root.join("history").join("install-scripts")
+}
+
+/// The script-bearing packages installed or vendored under each of `dirs`:
+/// `node_modules` (top-level and scoped packages), and the crates and
+/// Python packages directly under `vendor`.
+pub fn find(root: &Path, dirs: &[String]) -> Vec<ScriptPackage> {
+    let mut found = Vec::new();
+    for dir in dirs {
+        let base = if dir.is_empty() { root.to_path_buf() } else { root.join(dir) };
+        let relative = |path: &Path| {
+            let path = path.strip_prefix(root).unwrap_or(path);
+            path.to_string_lossy().replace('\\', "/")
+        };
+        for package in npm_packages(&base.join("node_modules")) {
+            found.extend(npm_scripts(&package.join("package.json"), &relative));
+        }
+        for package in subdirs(&base.join("vendor")) {
+            found.extend(cargo_build_script(&package, &relative));
+            found.extend(setup_py(&package, &relative));
+        }
+    }
+    found.sort_by(|a, b| (&a.ecosystem, &a.name, &a.file).cmp(&(&b.ecosystem, &b.name, &b.file)));
+    found
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = fsutil::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn npm_packages(modules: &Path) -> Vec<PathBuf> {
+    subdirs(modules)
+        .into_iter()
+        .flat_map(|dir| {
+            let scoped = dir.file_name().is_some_and(|name| name.to_string_lossy().starts_with('@'));
+            if scoped {
+                subdirs(&dir)
+            } else {
+                vec![dir]
+            }
+        })
+        .collect()
+}
+
+fn npm_scripts(manifest: &Path, relative: &dyn Fn(&Path) -> String) -> Option<ScriptPackage> {
+    let package: Value = serde_json::from_slice(&fsutil::read(manifest).ok()?).ok()?;
+    let mut hasher = Sha256::new();
+    let mut scripts = Vec::new();
+    for &script in NPM_INSTALL_SCRIPTS {
+        if let Some(command) = package["scripts"][script].as_str() {
+            hasher.update(format!("{}={}\n", script, command));
+            scripts.push(script.to_string());
+        }
+    }
+    if scripts.is_empty() {
+        return None;
+    }
+    Some(ScriptPackage {
+        ecosystem: "npm".to_string(),
+        name: package["name"].as_str()?.to_string(),
+        version: package["version"].as_str().unwrap_or_default().to_string(),
+        scripts,
+        sha256: report::hex(&hasher.finalize()),
+        file: relative(manifest),
+    })
+}
+
+/// A vendored crate's build script: `build.rs`, or the file its manifest's
+/// `build` key names.
+fn cargo_build_script(dir: &Path, relative: &dyn Fn(&Path) -> String) -> Option<ScriptPackage> {
+    let manifest: toml::Value = toml::from_str(&fsutil::read_to_string(dir.join("Cargo.toml")).ok()?).ok()?;
+    let package = manifest.get("package")?;
+    let script = match package.get("build") {
+        Some(toml::Value::Boolean(false)) => return None,
+        Some(toml::Value::String(path)) => dir.join(path),
+        _ => dir.join("build.rs"),
+    };
+    let contents = fsutil::read(&script).ok()?;
+    Some(ScriptPackage {
+        ecosystem: "crates.io".to_string(),
+        name: package.get("name")?.as_str()?.to_string(),
+        version: package.get("version").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+        scripts: vec!["build.rs".to_string()],
+        sha256: report::hex(&Sha256::digest(&contents)),
+        file: relative(&script),
+    })
+}
+
+/// A vendored Python package whose `setup.py` does more than declare the
+/// package. Name and version come from `PKG-INFO` when there is one.
+fn setup_py(dir: &Path, relative: &dyn Fn(&Path) -> String) -> Option<ScriptPackage> {
+    let script = dir.join("setup.py");
+    let contents = fsutil::read_to_string(&script).ok()?;
+    if !SETUP_PY_EXECUTION.iter().any(|call| contents.contains(call)) {
+        return None;
+    }
+    let info = fsutil::read_to_string(dir.join("PKG-INFO")).unwrap_or_default();
+    let field = |key: &str| info.lines().find_map(|line| line.strip_prefix(key).map(|v| v.trim().to_string()));
+    let name = field("Name:").unwrap_or_else(|| dir.file_name().unwrap_or_default().to_string_lossy().into_owned());
+    Some(ScriptPackage {
+        ecosystem: "PyPI".to_string(),
+        name,
+        version: field("Version:").unwrap_or_default(),
+        scripts: vec!["setup.py".to_string()],
+        sha256: report::hex(&Sha256::digest(contents.as_bytes())),
+        file: relative(&script),
+    })
+}
+
+/// The packages of the previous build of `target`, or `None` before its
+/// first one.
+pub fn load_baseline(dir: &Path, target: &str) -> Result<Option<Vec<ScriptPackage>>, String> {
+    let path = baseline_path(dir, target);
+    match fsutil::read_checked(&path) {
+        Ok(content) => serde_json::from_slice(&content).map(Some).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+/// Make `packages` the baseline the next build of `target` is compared to.
+pub fn save_baseline(dir: &Path, target: &str, packages: &[ScriptPackage]) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(packages).map_err(io::Error::other)?;
+    fsutil::write_checked(&baseline_path(dir, target), &json)
+}
+
+fn baseline_path(dir: &Path, target: &str) -> PathBuf {
+    dir.join(format!("{}.json", summary::file_stem(target)))
+}
+
+/// MEDIUM findings for the packages in `current` that had no install or
+/// build script in `previous`.
+pub fn added(previous: &[ScriptPackage], current: &[ScriptPackage]) -> Vec<Vulnerability> {
+    let known: BTreeSet<(&str, &str)> = previous.iter().map(|p| (p.ecosystem.as_str(), p.name.as_str())).collect();
+    current
+        .iter()
+        .filter(|p| !known.contains(&(p.ecosystem.as_str(), p.name.as_str())))
+        .map(|p| {
+            let summary = format!("New {} dependency runs {}", p.ecosystem, p.scripts.join(", "));
+            let description = format!("{} (sha256 {})", summary, p.sha256);
+            Vulnerability {
+                summary,
+                file: Some(p.file.clone()),
+                ..Vulnerability::new(SCRIPT_ADDED, Severity::Medium, &p.name, &p.version, description)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn finds_install_and_build_scripts_and_reports_new_ones() {
+        let root = crate::testutil::scratch_dir("scripts");
+        for (file, content) in [
+            (
+                "node_modules/esbuild/package.json",
+                r#"{"name":"esbuild","version":"0.19.0","scripts":{"postinstall":"node install.js"}}"#,
+            ),
+            ("node_modules/@acme/ui/package.json", r#"{"name":"@acme/ui","scripts":{"test":"jest"}}"#),
+            ("vendor/ring/Cargo.toml", "[package]\nname = \"ring\"\nversion = \"0.17.8\"\n"),
+            ("vendor/ring/build.rs", "fn main() {}\n"),
+            ("vendor/cfg-if/Cargo.toml", "[package]\nname = \"cfg-if\"\nversion = \"1.0.0\"\n"),
+            ("vendor/pkg/setup.py", "import subprocess\nsubprocess.run(['make'])\n"),
+            ("vendor/pkg/PKG-INFO", "Name: fastpkg\nVersion: 2.1\n"),
+            ("vendor/plain/setup.py", "from setuptools import setup\nsetup(name='plain')\n"),
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        let found = find(&root, &[String::new()]);
+        let names: Vec<(&str, &str, &str)> =
+            found.iter().map(|p| (p.ecosystem.as_str(), p.name.as_str(), p.file.as_str())).collect();
+        assert_eq!(
+            names,
+            vec![
+                ("PyPI", "fastpkg", "vendor/pkg/setup.py"),
+                ("crates.io", "ring", "vendor/ring/build.rs"),
+                ("npm", "esbuild", "node_modules/esbuild/package.json"),
+            ]
+        );
+        let esbuild = &found[2];
+        assert_eq!(esbuild.sha256, report::hex(&Sha256::digest(b"postinstall=node install.js\n")));
+
+        let dir = baselines_dir(&root.join("cache"));
+        assert_eq!(load_baseline(&dir, "//app:main").unwrap(), None);
+        save_baseline(&dir, "//app:main", &found[..2]).unwrap();
+        let previous = load_baseline(&dir, "//app:main").unwrap().unwrap();
+        let new: Vec<(String, Severity, String)> =
+            added(&previous, &found).into_iter().map(|v| (v.id, v.severity, v.package)).collect();
+        assert_eq!(new, vec![(SCRIPT_ADDED.to_string(), Severity::Medium, "esbuild".to_string())]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
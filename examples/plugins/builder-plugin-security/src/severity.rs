@@ -0,0 +1,89 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Normalized severity of a finding.
+///
+/// Sources disagree on labels (`moderate`, `Important`, `none`), so every
+/// label is mapped onto this fixed set when a finding is created. Anything
+/// unrecognized is kept as `Unknown` rather than dropped, so per-severity
+/// counts always add up to the total.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, JsonSchema)]
+#[serde(rename_all = "UPPERCASE", from = "String")]
+pub enum Severity {
+    // Declaration order is the report order: most severe first
+    Critical,
+    High,
+    Medium,
+    Low,
+    /// Reported for review only, e.g. advisories the scan found no use of.
+    Info,
+    Unknown,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 6] = [
+        Severity::Critical,
+        Severity::High,
+        Severity::Medium,
+        Severity::Low,
+        Severity::Info,
+        Severity::Unknown,
+    ];
+
+    /// Map a source label onto a severity, case-insensitively.
+    pub fn normalize(label: &str) -> Severity {
+        match label.trim().to_ascii_uppercase().as_str() {
+            "CRITICAL" => Severity::Critical,
+            "HIGH" | "IMPORTANT" | "SEVERE" => Severity::High,
+            "MEDIUM" | "MODERATE" => Severity::Medium,
+            "LOW" | "MINOR" => Severity::Low,
+            "INFO" | "INFORMATIONAL" | "NONE" => Severity::Info,
+            _ => Severity::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Critical => "CRITICAL",
+            Severity::High => "HIGH",
+            Severity::Medium => "MEDIUM",
+            Severity::Low => "LOW",
+            Severity::Info => "INFO",
+            Severity::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl From<String> for Severity {
+    fn from(label: String) -> Self {
+        Severity::normalize(&label)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_case_and_synonyms() {
+        assert_eq!(Severity::normalize("critical"), Severity::Critical);
+        assert_eq!(Severity::normalize("MODERATE"), Severity::Medium);
+        assert_eq!(Severity::normalize(" Important "), Severity::High);
+        assert_eq!(Severity::normalize("none"), Severity::Info);
+        assert_eq!(Severity::normalize("bogus"), Severity::Unknown);
+    }
+
+    #[test]
+    fn deserializes_through_normalization() {
+        let severity: Severity = serde_json::from_str("\"moderate\"").unwrap();
+        assert_eq!(severity, Severity::Medium);
+        assert_eq!(serde_json::to_string(&severity).unwrap(), "\"MEDIUM\"");
+    }
+}
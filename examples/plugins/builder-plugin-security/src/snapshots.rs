@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::cache;
+use crate::clock;
+use crate::config::{ScanConfig, CONFIG_FILE_NAME};
+use crate::fsutil;
+
+/// Snapshots kept besides the one in use; the least recently used are
+/// removed first.
+pub const SNAPSHOTS_KEPT: usize = 10;
+
+/// An advisory database snapshot cached under `db/snapshots`, next to its
+/// index (`<id>.kv`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// `<source>@<first 16 hex digits of sha256>`, as reports name it.
+    pub id: String,
+    pub source: String,
+    /// SHA-256 over the database's files, names and contents.
+    pub sha256: String,
+    /// Names, sizes and modification times of the files it was built from,
+    /// so an unchanged database is recognized without hashing it again.
+    pub fingerprint: String,
+    /// Modification time of the newest file: when the data was fetched.
+    pub fetched_at: Option<String>,
+    pub fetched_at_unix: Option<u64>,
+    pub last_used_unix: u64,
+    /// Size of the index when it was built, so a truncated one is noticed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_bytes: Option<u64>,
+    /// The only ecosystems whose advisories the index holds, for a
+    /// `db_refresh = "scheduled"` snapshot; empty when it holds them all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ecosystems: Vec<String>,
+}
+
+/// Directory of the snapshots, under the advisory cache (`db/`).
+pub fn snapshots_dir(db_cache: &Path) -> PathBuf {
+    db_cache.join("snapshots")
+}
+
+/// The index file of snapshot `id`.
+pub fn index_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.kv", file_stem(id)))
+}
+
+fn meta_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.json", file_stem(id)))
+}
+
+/// Ids are `source@hex`; keep them to characters every filesystem takes.
+fn file_stem(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '@') { c } else { '_' })
+        .collect()
+}
+
+/// Snapshot `id`, if it and its index are cached.
+pub fn load(dir: &Path, id: &str) -> Option<Snapshot> {
+    read(dir, &meta_path(dir, id)).map(|(snapshot, _)| snapshot).filter(|snapshot| snapshot.id == id)
+}
+
+/// The snapshot described by `meta` and the size of its index. A damaged
+/// description, or an index missing or of another size than it was built
+/// with, is removed with its index, for the next refresh to rebuild.
+fn read(dir: &Path, meta: &Path) -> Option<(Snapshot, u64)> {
+    let snapshot = match fsutil::read_checked(meta) {
+        Ok(json) => serde_json::from_slice::<Snapshot>(&json).ok(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(_) => None,
+    };
+    let index = snapshot.as_ref().map_or_else(|| meta.with_extension("kv"), |s| index_path(dir, &s.id));
+    let size = fs::metadata(&index).ok().map(|m| m.len());
+    match (snapshot, size) {
+        (Some(snapshot), Some(size)) if snapshot.index_bytes.is_none_or(|bytes| bytes == size) => {
+            Some((snapshot, size))
+        }
+        _ => {
+            let _ = fsutil::remove_file(index);
+            let _ = fsutil::remove_file(meta);
+            None
+        }
+    }
+}
+
+/// Every cached snapshot with the size of its index, most recently used
+/// first.
+pub fn list(dir: &Path) -> Vec<(Snapshot, u64)> {
+    let mut snapshots: Vec<(Snapshot, u64)> = fsutil::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|e| e == "json"))
+        .filter_map(|entry| read(dir, &entry.path()))
+        .collect();
+    snapshots.sort_by(|a, b| b.0.last_used_unix.cmp(&a.0.last_used_unix).then_with(|| a.0.id.cmp(&b.0.id)));
+    snapshots
+}
+
+/// The cached snapshot built from files with `fingerprint`, if any.
+pub fn find(dir: &Path, source: &str, fingerprint: &str) -> Option<Snapshot> {
+    list(dir)
+        .into_iter()
+        .map(|(snapshot, _)| snapshot)
+        .find(|snapshot| snapshot.source == source && snapshot.fingerprint == fingerprint)
+}
+
+/// Record that `snapshot` was used at `now`, removing the least recently
+/// used ones beyond [`SNAPSHOTS_KEPT`].
+pub fn touch(dir: &Path, snapshot: &mut Snapshot, now: SystemTime) -> io::Result<()> {
+    snapshot.last_used_unix = clock::unix_secs(now);
+    let json = serde_json::to_vec_pretty(snapshot).map_err(io::Error::other)?;
+    fsutil::write_checked(&meta_path(dir, &snapshot.id), &json)?;
+
+    for (old, _) in list(dir).into_iter().skip(SNAPSHOTS_KEPT + 1) {
+        let _ = fsutil::remove_file(index_path(dir, &old.id));
+        let _ = fsutil::remove_file(meta_path(dir, &old.id));
+    }
+    Ok(())
+}
+
+/// The index and metadata files of snapshot `id`.
+pub fn files(dir: &Path, id: &str) -> [PathBuf; 2] {
+    [index_path(dir, id), meta_path(dir, id)]
+}
+
+/// The least recently used snapshots whose removal brings the indexes
+/// within `budget` bytes. The most recently used one is never among them,
+/// even when it alone exceeds the budget.
+pub fn over_budget(dir: &Path, budget: u64) -> Vec<(Snapshot, u64)> {
+    let mut total = 0;
+    list(dir)
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (_, size))| {
+            total += size;
+            *i > 0 && total > budget
+        })
+        .map(|(_, snapshot)| snapshot)
+        .collect()
+}
+
+/// Fail unless the snapshot `db_snapshot` pins the scan to is cached, rather
+/// than letting the scan fall back to newer data.
+pub fn check_pinned(workspace_root: &Path, config: &ScanConfig) -> Result<(), String> {
+    check_pinned_in(&cache::cache_root(workspace_root, &config.report_dir).join("db"), config)
+}
+
+/// [`check_pinned`] against the advisory database cache `db_cache`.
+pub fn check_pinned_in(db_cache: &Path, config: &ScanConfig) -> Result<(), String> {
+    let Some(id) = &config.db_snapshot else {
+        return Ok(());
+    };
+    let dir = snapshots_dir(db_cache);
+    match load(&dir, id) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "db_snapshot '{}' is not cached locally; list cached snapshots with `builder-plugin-security snapshots`",
+            id
+        )),
+    }
+}
+
+/// Seconds as the largest whole unit: `45s`, `12m`, `5h`, `3d`.
+pub fn describe_age(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+const USAGE: &str = "usage: builder-plugin-security snapshots [--workspace DIR]";
+
+/// `builder-plugin-security snapshots`: list the cached advisory database
+/// snapshots a scan can be pinned to with `db_snapshot`.
+pub fn cli(args: &[String], out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut workspace = ".".to_string();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--workspace", Some(dir)) => workspace = dir.clone(),
+            _ => {
+                let _ = writeln!(err, "{}", USAGE);
+                return 2;
+            }
+        }
+    }
+
+    let root = Path::new(&workspace);
+    let config = match ScanConfig::resolve(Some(&root.join(CONFIG_FILE_NAME)), None, None, std::env::vars()) {
+        Ok(config) => config,
+        Err(e) => {
+            let _ = writeln!(err, "{}", e);
+            return 2;
+        }
+    };
+    let dir = snapshots_dir(&cache::cache_root(root, &config.report_dir).join("db"));
+    let snapshots = list(&dir);
+    if snapshots.is_empty() {
+        let _ = writeln!(out, "No advisory database snapshots in {}", dir.display());
+        return 0;
+    }
+
+    let now = clock::unix_secs(SystemTime::now());
+    let _ = writeln!(out, "{:<28} {:>10} {:>6}  FETCHED", "SNAPSHOT", "SIZE", "AGE");
+    for (snapshot, size) in snapshots {
+        let age = snapshot.fetched_at_unix.map_or("-".to_string(), |at| describe_age(now.saturating_sub(at)));
+        let _ = writeln!(
+            out,
+            "{:<28} {:>10} {:>6}  {}",
+            snapshot.id,
+            size,
+            age,
+            snapshot.fetched_at.as_deref().unwrap_or("-")
+        );
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn snapshot(id: &str, fingerprint: &str) -> Snapshot {
+        Snapshot {
+            id: id.to_string(),
+            source: "osv-local".to_string(),
+            sha256: "00".repeat(32),
+            fingerprint: fingerprint.to_string(),
+            fetched_at: None,
+            fetched_at_unix: None,
+            last_used_unix: 0,
+            index_bytes: None,
+            ecosystems: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn snapshots_are_found_by_id_and_fingerprint_and_pruned_by_use() {
+        let dir = crate::testutil::scratch_dir("snapshots");
+
+        for i in 0..SNAPSHOTS_KEPT + 2 {
+            let mut snap = snapshot(&format!("osv-local@{:016x}", i), &format!("fp{}", i));
+            fs::write(index_path(&dir, &snap.id), vec![b'x'; i]).unwrap();
+            touch(&dir, &mut snap, UNIX_EPOCH + Duration::from_secs(1000 + i as u64)).unwrap();
+        }
+        let listed = list(&dir);
+        assert_eq!(listed.len(), SNAPSHOTS_KEPT + 1);
+        assert_eq!(listed[0].0.id, format!("osv-local@{:016x}", SNAPSHOTS_KEPT + 1));
+        assert_eq!(listed[0].1, SNAPSHOTS_KEPT as u64 + 1);
+        // The least recently used one is gone
+        assert_eq!(load(&dir, "osv-local@0000000000000000"), None);
+        assert!(load(&dir, "osv-local@0000000000000001").is_some());
+        assert_eq!(find(&dir, "osv-local", "fp3").unwrap().id, "osv-local@0000000000000003");
+        assert_eq!(find(&dir, "osv-local", "fp0"), None);
+
+        fs::remove_file(index_path(&dir, "osv-local@0000000000000003")).unwrap();
+        assert_eq!(load(&dir, "osv-local@0000000000000003"), None);
+        assert_eq!(describe_age(7200), "2h");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::advisories::{BuiltinDemo, LocalDb, PrivateAdvisories};
+use crate::cache;
+use crate::config::ScanConfig;
+use crate::paths;
+use crate::report;
+use crate::snapshots;
+
+/// What `plugin.info` reports about the advisory sources: read from cache
+/// metadata alone, without refreshing or contacting any source.
+#[derive(Serialize, Debug)]
+pub struct Status {
+    /// One line for a host's plugin list: `osv-local 3d old; npm-registry
+    /// offline, cached metadata only`.
+    pub summary: String,
+    /// Whether any source is degraded, or none is configured.
+    pub degraded: bool,
+    pub sources: Vec<SourceStatus>,
+    /// Bytes in each cache directory (`db`, `scan`, ...).
+    pub cache_bytes: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SourceStatus {
+    pub source: String,
+    /// The snapshot the next scan reads, for sources that keep them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+    /// The last successful refresh, as RFC 3339.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refreshed_at: Option<String>,
+    /// When the data itself was fetched, as RFC 3339.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_secs: Option<u64>,
+    /// Bytes the source's data takes on disk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// Why the source is degraded: `never refreshed`, `offline, cached
+    /// metadata only`, ...
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub degraded: Vec<String>,
+}
+
+impl SourceStatus {
+    fn new(source: &str) -> Self {
+        SourceStatus {
+            source: source.to_string(),
+            snapshot: None,
+            refreshed_at: None,
+            fetched_at: None,
+            age_secs: None,
+            bytes: None,
+            degraded: Vec::new(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match (&self.degraded[..], self.age_secs) {
+            ([], Some(age)) => format!("{} {} old", self.source, snapshots::describe_age(age)),
+            ([], None) => self.source.clone(),
+            (degraded, _) => format!("{} {}", self.source, degraded.join(", ")),
+        }
+    }
+}
+
+/// The status of the sources `config` enables, from the cache under
+/// `workspace_root`; `None` while no scan has created the cache.
+pub fn status(workspace_root: &Path, config: &ScanConfig, now: SystemTime) -> Option<Status> {
+    let root = cache::cache_root(workspace_root, &config.report_dir);
+    if !root.is_dir() {
+        return None;
+    }
+    let caches = cache::stats(&root).ok()?;
+    let age = |at: SystemTime| now.duration_since(at).map_or(0, |age| age.as_secs());
+
+    let mut sources = Vec::new();
+    if config.db_path.is_some() || config.db_snapshot.is_some() {
+        let mut source = SourceStatus::new(LocalDb::SOURCE);
+        let dir = snapshots::snapshots_dir(&root.join("db"));
+        // The pinned snapshot, or the one the last refresh used
+        let current = match &config.db_snapshot {
+            Some(id) => snapshots::list(&dir).into_iter().find(|(snapshot, _)| &snapshot.id == id),
+            None => snapshots::list(&dir).into_iter().find(|(snapshot, _)| snapshot.source == LocalDb::SOURCE),
+        };
+        match (&current, &config.db_snapshot) {
+            (Some(_), _) => {}
+            (None, Some(id)) => source.degraded.push(format!("snapshot {} not cached", id)),
+            (None, None) => source.degraded.push("never refreshed".to_string()),
+        }
+        if let (Some(db_path), None) = (&config.db_path, &config.db_snapshot) {
+            if let Err(e) = fs::metadata(db_path) {
+                source.degraded.push(format!("db_path {} unreadable ({})", db_path, e));
+            }
+        }
+        if let Some((snapshot, bytes)) = current {
+            let refreshed = UNIX_EPOCH + Duration::from_secs(snapshot.last_used_unix);
+            source.refreshed_at = Some(report::format_timestamp(refreshed));
+            source.age_secs = snapshot.fetched_at_unix.map(|at| age(UNIX_EPOCH + Duration::from_secs(at)));
+            source.fetched_at = snapshot.fetched_at;
+            source.snapshot = Some(snapshot.id);
+            source.bytes = Some(bytes);
+        }
+        sources.push(source);
+    }
+    if let Some(path) = &config.extra_advisories {
+        // Read by every scan, so only the file itself has a status
+        let mut source = SourceStatus::new(PrivateAdvisories::SOURCE);
+        match fs::metadata(paths::join_source(workspace_root, path)) {
+            Ok(metadata) => {
+                let modified = metadata.modified().ok();
+                source.fetched_at = modified.map(report::format_timestamp);
+                source.age_secs = modified.map(age);
+                source.bytes = Some(metadata.len());
+            }
+            Err(e) => source.degraded.push(format!("{} unreadable ({})", path, e)),
+        }
+        sources.push(source);
+    }
+    for path in &config.import_results {
+        let mut source = SourceStatus::new(&format!("imported {}", path));
+        match fs::metadata(paths::join_source(workspace_root, path)) {
+            Ok(metadata) => {
+                let modified = metadata.modified().ok();
+                source.fetched_at = modified.map(report::format_timestamp);
+                source.age_secs = modified.map(age);
+                source.bytes = Some(metadata.len());
+            }
+            Err(e) => source.degraded.push(format!("unreadable ({})", e)),
+        }
+        sources.push(source);
+    }
+    if sources.is_empty() && config.demo_db {
+        let mut source = SourceStatus::new(BuiltinDemo::SOURCE);
+        source.degraded.push("demo data only".to_string());
+        sources.push(source);
+    }
+    if config.npm_provenance {
+        let mut source = SourceStatus::new("npm-registry");
+        let npm = caches.iter().find(|c| c.name == "npm");
+        source.age_secs = npm.and_then(|npm| npm.newest_age_secs);
+        source.bytes = npm.map(|npm| npm.bytes);
+        if config.offline {
+            source.degraded.push("offline, cached metadata only".to_string());
+        }
+        sources.push(source);
+    }
+
+    let summary = match sources.is_empty() {
+        true => "no advisory source configured".to_string(),
+        false => sources.iter().map(SourceStatus::describe).collect::<Vec<_>>().join("; "),
+    };
+    Some(Status {
+        summary,
+        degraded: sources.is_empty() || sources.iter().any(|source| !source.degraded.is_empty()),
+        sources,
+        cache_bytes: caches.iter().map(|c| (c.name, c.bytes)).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisories::AdvisorySource;
+
+    #[test]
+    fn status_comes_from_the_cache_and_is_absent_without_one() {
+        let root = crate::testutil::scratch_dir("status");
+        fs::create_dir_all(root.join("db")).unwrap();
+        fs::write(
+            root.join("db/GHSA-1.json"),
+            r#"{"id": "GHSA-1", "affected": [{"package": {"ecosystem": "npm", "name": "a"}, "versions": ["1.0.0"]}]}"#,
+        )
+        .unwrap();
+        let config = ScanConfig {
+            db_path: Some(root.join("db").display().to_string()),
+            extra_advisories: Some("advisories.toml".to_string()),
+            npm_provenance: true,
+            offline: true,
+            ..ScanConfig::default()
+        };
+        let now = SystemTime::now();
+        assert!(status(&root, &config, now).is_none());
+
+        let cache = cache::cache_root(&root, &config.report_dir).join("db");
+        let mut db = LocalDb::new(config.db_path.as_deref().unwrap(), "memory", 1);
+        let snapshot = db.refresh(&cache, now).unwrap();
+        let status = status(&root, &config, now + Duration::from_secs(3 * 86_400)).unwrap();
+
+        let [osv, private, npm] = &status.sources[..] else {
+            panic!("{:?}", status.sources);
+        };
+        assert_eq!(osv.snapshot.as_ref(), Some(&snapshot.id));
+        assert_eq!(osv.refreshed_at.as_deref(), Some(report::format_timestamp(now).as_str()));
+        assert!(osv.age_secs.unwrap() >= 3 * 86_400);
+        assert!(osv.bytes.unwrap() > 0);
+        assert!(private.degraded[0].starts_with("advisories.toml unreadable ("), "{:?}", private);
+        assert_eq!(npm.degraded, ["offline, cached metadata only"]);
+        assert!(status.degraded);
+        assert!(status.summary.starts_with("osv-local 3d old; private advisories.toml unreadable ("));
+        assert!(status.summary.ends_with("; npm-registry offline, cached metadata only"));
+        assert!(status.cache_bytes["db"] > 0);
+
+        // A new config pinned to a snapshot that was never cached
+        let pinned = ScanConfig {
+            db_snapshot: Some("osv-local@0000000000000000".to_string()),
+            ..ScanConfig::default()
+        };
+        let status = super::status(&root, &pinned, now).unwrap();
+        assert_eq!(status.summary, "osv-local snapshot osv-local@0000000000000000 not cached");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, Write};
+
+/// Take the process's stdout for the JSON-RPC stream alone. The returned
+/// file writes where stdout did; stdout itself is pointed at stderr, so
+/// whatever library code prints can't reach the host as a response.
+#[cfg(unix)]
+pub fn capture_stdout() -> io::Result<File> {
+    use std::os::fd::FromRawFd;
+
+    io::stdout().flush()?;
+    // SAFETY: duplicating and replacing descriptors 1 and 2, which the
+    // process always has; the duplicate is owned by the returned file alone.
+    unsafe {
+        let protocol = libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 0);
+        if protocol < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let protocol = File::from_raw_fd(protocol);
+        if libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(protocol)
+    }
+}
+
+/// Take the process's stdout for the JSON-RPC stream alone. The returned
+/// file writes where stdout did; stdout itself is pointed at stderr, so
+/// whatever library code prints can't reach the host as a response.
+#[cfg(windows)]
+pub fn capture_stdout() -> io::Result<File> {
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, RawHandle};
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetStdHandle(std_handle: u32, handle: RawHandle) -> i32;
+    }
+
+    io::stdout().flush()?;
+    let protocol = io::stdout().as_raw_handle();
+    if protocol.is_null() {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "the process has no stdout"));
+    }
+    // SAFETY: the standard handles are the process's; from here on only the
+    // returned file writes through the original stdout handle, and std
+    // looks the standard handle up on every write.
+    unsafe {
+        if SetStdHandle(STD_OUTPUT_HANDLE, io::stderr().as_raw_handle()) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from_raw_handle(protocol))
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn capture_stdout() -> io::Result<File> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "stdout can't be taken on this platform"))
+}
+
+/// The JSON-RPC stream: written line by line, each line one JSON-RPC
+/// message. A line that isn't one panics in debug builds, so tests catch
+/// the code path writing it, and is dropped with an error in release
+/// builds rather than corrupting the host's view of the stream.
+pub struct ProtocolOut<'a> {
+    inner: &'a mut dyn Write,
+    /// The start of a line not yet terminated.
+    pending: Vec<u8>,
+}
+
+impl<'a> ProtocolOut<'a> {
+    pub fn new(inner: &'a mut dyn Write) -> Self {
+        ProtocolOut {
+            inner,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// Whether a line (without its line ending) is a JSON-RPC message.
+fn is_message(line: &[u8]) -> bool {
+    serde_json::from_slice::<Value>(line).is_ok_and(|message| message["jsonrpc"] == "2.0")
+}
+
+impl Write for ProtocolOut<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            if !is_message(&line[..end]) {
+                let text = String::from_utf8_lossy(&line[..end]);
+                debug_assert!(false, "non-protocol bytes written to the JSON-RPC stream: {:?}", text);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("refusing to write a line that isn't a JSON-RPC message ({} bytes)", end),
+                ));
+            }
+            self.inner.write_all(&line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+    use std::{env, fs};
+
+    /// Set for the child process the stream test runs.
+    const CHILD_ENV: &str = "SECURITY_PLUGIN_NOISY_CHILD";
+
+    #[test]
+    fn protocol_out_passes_messages_whole_and_refuses_anything_else() {
+        let mut written = Vec::new();
+        let mut out = ProtocolOut::new(&mut written);
+        write!(out, r#"{{"jsonrpc": "2.0", "id": 1,"#).unwrap();
+        writeln!(out, r#" "result": {{}}}}"#).unwrap();
+        assert_eq!(written, b"{\"jsonrpc\": \"2.0\", \"id\": 1, \"result\": {}}\n");
+
+        let stray = std::panic::catch_unwind(|| {
+            let mut written = Vec::new();
+            let result = writeln!(ProtocolOut::new(&mut written), "Scanning 1 source files");
+            (result.is_err(), written.is_empty())
+        });
+        if cfg!(debug_assertions) {
+            assert!(stray.is_err());
+        } else {
+            assert_eq!(stray.unwrap(), (true, true));
+        }
+    }
+
+    #[test]
+    fn noisy_extractors_cannot_corrupt_the_protocol_stream() {
+        if env::var_os(CHILD_ENV).is_some() {
+            // The JSON-RPC loop as the binary runs it
+            let mut out = io::LineWriter::new(capture_stdout().unwrap());
+            let code = crate::plugin::run(&[], &mut io::stdin().lock(), &mut out, &mut io::stderr());
+            assert_eq!(code, 0);
+            return;
+        }
+        let root = crate::testutil::scratch_dir("noisy");
+        fs::write(root.join("deps.noisy"), "django==2.2.0\n").unwrap();
+
+        let requests = [
+            serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "plugin.info"}),
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "build.pre_hook",
+                "params": {
+                    "target": {"name": "app", "sources": ["deps.noisy"]},
+                    "workspace": {"root": root.display().to_string()}
+                }
+            }),
+        ];
+        let input: String = requests.iter().map(|r| format!("{}\nnot json\n", r)).collect();
+
+        // This test again, in a child process taking `input` on stdin and
+        // scanning `deps.noisy`, whose extractor prints to stdout
+        let name = "stdio::tests::noisy_extractors_cannot_corrupt_the_protocol_stream";
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args([name, "--exact", "--nocapture", "--test-threads=1", "--quiet"])
+            .env(CHILD_ENV, "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        let child = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8(child.stdout).unwrap();
+        let stderr = String::from_utf8(child.stderr).unwrap();
+        assert!(child.status.success(), "{}", stderr);
+
+        // Past the test harness's own header, responses and only responses
+        let (_, stream) = stdout.split_once("running 1 test\n").unwrap();
+        let responses: Vec<Value> = stream
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {:?}", e, line)))
+            .collect();
+        let ids: Vec<&Value> = responses.iter().map(|r| &r["id"]).collect();
+        // The lines that aren't JSON are answered with parse errors
+        assert_eq!(ids, [&1.into(), &Value::Null, &2.into(), &Value::Null]);
+        let logs = &responses[2]["result"]["logs"];
+        assert!(logs.as_array().unwrap().iter().any(|l| l == "  Total vulnerabilities: 1"), "{}", logs);
+        // The noise and the parse errors' diagnostics went to stderr instead
+        assert_eq!(stderr.matches(crate::ecosystems::NOISE).count(), 2, "{}", stderr);
+        assert_eq!(stderr.matches(" ERROR plugin: Parse error: ").count(), 2, "{}", stderr);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Serialize;
+use serde_json::json;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::fingerprint;
+use crate::fsutil;
+use crate::severity::Severity;
+use crate::Vulnerability;
+
+/// Method of the notification carrying one finding, for hosts that ask for
+/// it in the `plugin.info` params.
+pub const FINDING_NOTIFICATION: &str = "plugin.finding";
+
+/// One finding as the stream carries it: a line of the `findings_stream`
+/// file, or the params of a `plugin.finding` notification.
+#[derive(Serialize)]
+pub struct StreamedFinding<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<&'a str>,
+    /// With `workspace.roots`, the root the finding came from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<&'a str>,
+    pub fingerprint: &'a str,
+    /// Package URL with the version, e.g. `pkg:pypi/django@2.2.0`.
+    pub purl: String,
+    pub id: &'a str,
+    pub severity: Severity,
+    pub package: &'a str,
+    pub version: &'a str,
+    pub file: Option<&'a str>,
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl<'a> StreamedFinding<'a> {
+    fn of(vuln: &'a Vulnerability, target: Option<&'a str>, root: Option<&'a str>) -> Self {
+        StreamedFinding {
+            target,
+            root,
+            fingerprint: &vuln.fingerprint,
+            purl: format!("{}@{}", fingerprint::purl(vuln.ecosystem(), &vuln.package), vuln.version),
+            id: &vuln.id,
+            severity: vuln.severity,
+            package: &vuln.package,
+            version: &vuln.version,
+            file: vuln.file.as_deref(),
+            line: vuln.line,
+            column: vuln.column,
+        }
+    }
+}
+
+/// Where a scan's findings go as each dependency file's matching
+/// completes: the `findings_stream` file, as newline-delimited JSON, and
+/// `plugin.finding` notifications on the protocol stream. Both are flushed
+/// after every file, and carry the same findings the report ends up with.
+#[derive(Default)]
+pub struct FindingStream<'a> {
+    file: Option<(PathBuf, BufWriter<File>)>,
+    notifications: Option<&'a mut dyn Write>,
+    target: Option<String>,
+    root: Option<String>,
+}
+
+impl<'a> FindingStream<'a> {
+    /// A stream to `path`, created afresh, and to `notifications`; `None`
+    /// for either leaves it out. A file that can't be created is warned
+    /// about and left out.
+    pub fn open(
+        path: Option<&Path>,
+        notifications: Option<&'a mut dyn Write>,
+        target: Option<&str>,
+        logs: &mut Vec<String>,
+    ) -> Self {
+        let file = path.and_then(|path| {
+            let created = path.parent().map_or(Ok(()), fsutil::create_dir_all).and_then(|_| fsutil::create(path));
+            match created {
+                Ok(file) => Some((path.to_path_buf(), BufWriter::new(file))),
+                Err(e) => {
+                    logs.push(format!("  ⚠ Could not write the findings stream {}: {}", path.display(), e));
+                    None
+                }
+            }
+        });
+        FindingStream {
+            file,
+            notifications,
+            target: target.map(str::to_string),
+            root: None,
+        }
+    }
+
+    /// The stream, tagging each finding with the root of a multi-root scan.
+    pub fn root(mut self, root: Option<&str>) -> Self {
+        self.root = root.map(str::to_string);
+        self
+    }
+
+    /// Write the findings of one dependency file, ordered by their place
+    /// in it, and flush them.
+    pub fn emit(&mut self, findings: &[Vulnerability], logs: &mut Vec<String>) {
+        if findings.is_empty() || (self.file.is_none() && self.notifications.is_none()) {
+            return;
+        }
+        let mut ordered: Vec<&Vulnerability> = findings.iter().collect();
+        ordered.sort_by(|a, b| {
+            (a.file.as_deref(), a.line, a.column, &a.package, &a.id)
+                .cmp(&(b.file.as_deref(), b.line, b.column, &b.package, &b.id))
+        });
+        let (target, root) = (self.target.as_deref(), self.root.as_deref());
+        if let Some((path, file)) = &mut self.file {
+            let written: io::Result<()> = ordered
+                .iter()
+                .try_for_each(|vuln| {
+                    serde_json::to_writer(&mut *file, &StreamedFinding::of(vuln, target, root))?;
+                    file.write_all(b"\n")
+                })
+                .and_then(|_| file.flush());
+            if let Err(e) = written {
+                logs.push(format!("  ⚠ Findings stream {} stopped: {}", path.display(), e));
+                self.file = None;
+            }
+        }
+        if let Some(notifications) = &mut self.notifications {
+            let written: io::Result<()> = ordered
+                .iter()
+                .try_for_each(|vuln| {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": FINDING_NOTIFICATION,
+                        "params": StreamedFinding::of(vuln, target, root),
+                    });
+                    writeln!(notifications, "{}", notification)
+                })
+                .and_then(|_| notifications.flush());
+            if let Err(e) = written {
+                logs.push(format!("  ⚠ {} notifications stopped: {}", FINDING_NOTIFICATION, e));
+                self.notifications = None;
+            }
+        }
+    }
+
+    /// The stream file, once everything is written to it.
+    pub fn finish(self) -> Option<PathBuf> {
+        self.file.map(|(path, _)| path)
+    }
+}
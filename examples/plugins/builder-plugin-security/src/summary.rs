@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::fsutil;
+
+/// How much older than the build itself a pre_hook summary may be and still
+/// belong to it: covers the time between the pre_hook and the build start.
+pub const GRACE_SECS: u64 = 600;
+
+/// Compact per-target result of a pre_hook scan, handed to the post_hook.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TargetSummary {
+    pub target: String,
+    pub scanned_at: String,
+    /// Unix seconds of `scanned_at`, for freshness checks.
+    pub scanned_at_unix: u64,
+    /// Findings per severity label, including zeros.
+    pub counts: BTreeMap<String, usize>,
+    /// Findings per CWE id, `UNKNOWN` for those without CWE data. A
+    /// finding with several ids counts under each.
+    #[serde(default)]
+    pub cwe_counts: BTreeMap<String, usize>,
+    pub total: usize,
+    pub suppressed: usize,
+    /// Findings that weren't in the previous report.
+    pub new: usize,
+    /// Findings that were already in the previous report.
+    pub existing: usize,
+    pub policy: PolicyStatus,
+    /// Files written by the scan (report, metrics, patches).
+    pub reports: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PolicyStatus {
+    pub passed: bool,
+    pub violations: Vec<String>,
+}
+
+/// Directory holding per-target summaries, under the cache root.
+pub fn summaries_dir(cache_root: &Path) -> PathBuf {
+    cache_root.join("targets")
+}
+
+/// File name for a target; Builder names like `//app:main` aren't valid
+/// file names everywhere.
+pub fn file_stem(target: &str) -> String {
+    target
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect()
+}
+
+fn pending_path(dir: &Path, target: &str) -> PathBuf {
+    dir.join(format!("{}.pending.json", file_stem(target)))
+}
+
+fn consumed_path(dir: &Path, target: &str) -> PathBuf {
+    dir.join(format!("{}.json", file_stem(target)))
+}
+
+/// Record the summary of a pre_hook scan for the matching post_hook.
+pub fn record(dir: &Path, summary: &TargetSummary) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(summary).map_err(io::Error::other)?;
+    fsutil::write_atomic(&pending_path(dir, &summary.target), &json)
+}
+
+/// Drop the pending summary for a target, so the next post_hook doesn't
+/// take a scan made outside the build for its pre_hook's.
+pub fn discard(dir: &Path, target: &str) -> io::Result<()> {
+    match fsutil::remove_file(pending_path(dir, target)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        other => other,
+    }
+}
+
+/// Outcome of looking up the pre_hook summary for a post_hook.
+pub enum Lookup {
+    Found(TargetSummary),
+    /// No pre_hook recorded a summary since the last post_hook.
+    Missing,
+    /// A summary exists but predates this build.
+    Stale(TargetSummary),
+}
+
+/// Take the pending summary for a target.
+///
+/// The summary is moved aside once read, so a later build whose pre_hook
+/// was skipped reports `Missing` instead of the numbers of this one. A
+/// summary older than `build_secs + GRACE_SECS` belongs to an earlier,
+/// interrupted build and is reported as `Stale`.
+pub fn take(dir: &Path, target: &str, now_unix: u64, build_secs: u64) -> Result<Lookup, String> {
+    let pending = pending_path(dir, target);
+    let content = match fsutil::read_to_string(&pending) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Lookup::Missing),
+        Err(e) => return Err(format!("{}: {}", pending.display(), e)),
+    };
+    let summary: TargetSummary =
+        serde_json::from_str(&content).map_err(|e| format!("{}: {}", pending.display(), e))?;
+
+    let consumed = consumed_path(dir, target);
+    fsutil::rename(&pending, &consumed)
+        .map_err(|e| format!("{}: {}", pending.display(), e))?;
+
+    if summary.scanned_at_unix + build_secs + GRACE_SECS < now_unix {
+        return Ok(Lookup::Stale(summary));
+    }
+    Ok(Lookup::Found(summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn summary(target: &str, scanned_at_unix: u64) -> TargetSummary {
+        TargetSummary {
+            target: target.to_string(),
+            scanned_at: String::new(),
+            scanned_at_unix,
+            counts: BTreeMap::new(),
+            cwe_counts: BTreeMap::new(),
+            total: 0,
+            suppressed: 0,
+            new: 0,
+            existing: 0,
+            policy: PolicyStatus {
+                passed: true,
+                violations: Vec::new(),
+            },
+            reports: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn summaries_are_consumed_once_and_age_out() {
+        let dir = crate::testutil::scratch_dir("summary");
+
+        record(&dir, &summary("//app:main", 1_000)).unwrap();
+        assert!(matches!(take(&dir, "//app:main", 1_030, 20).unwrap(), Lookup::Found(s) if s.target == "//app:main"));
+        // A second post_hook without a new pre_hook must not reuse it
+        assert!(matches!(take(&dir, "//app:main", 1_040, 20).unwrap(), Lookup::Missing));
+
+        record(&dir, &summary("//app:main", 1_000)).unwrap();
+        assert!(matches!(take(&dir, "//app:main", 1_000 + GRACE_SECS + 100, 20).unwrap(), Lookup::Stale(_)));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
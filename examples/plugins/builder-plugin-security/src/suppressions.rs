@@ -0,0 +1,178 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+
+use crate::fsutil;
+
+/// Suppression file looked up in the workspace root.
+pub const SUPPRESSIONS_FILE_NAME: &str = ".builder-security-ignore.yml";
+
+const FILE_HEADER: &str = "# Security finding suppressions for builder-plugin-security.\n\
+# Entries can be added by hand or with `builder-plugin-security triage`.\n\
+suppressions:\n";
+
+/// One entry of `.builder-security-ignore.yml`.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Suppression {
+    /// Advisory id or alias of the findings suppressed; empty to suppress
+    /// by `cwe` alone.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    #[schemars(length(min = 1))]
+    pub id: String,
+    /// Restrict the suppression to one package; `None` matches any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Restrict the suppression to findings with this CWE id (`CWE-1333`,
+    /// or `UNKNOWN` for those without CWE data).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwe: Option<String>,
+    #[serde(default)]
+    pub reason: String,
+    /// Last day (`YYYY-MM-DD`) the suppression applies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<String>,
+}
+
+/// `.builder-security-ignore.yml` as the data it holds, for `plugin.schema`.
+#[derive(Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SuppressionFile {
+    #[serde(default)]
+    pub suppressions: Vec<Suppression>,
+}
+
+impl Suppression {
+    /// Whether the entry covers a finding known as `id`, in `package` and
+    /// with the CWE ids `cwe`.
+    pub fn matches(&self, id: &str, package: &str, cwe: &[String]) -> bool {
+        (self.id.is_empty() || self.id == id)
+            && self.package.as_deref().is_none_or(|p| p == package)
+            && self.cwe.as_deref().is_none_or(|c| cwe.iter().any(|f| f.eq_ignore_ascii_case(c)))
+    }
+
+    /// Whether the suppression has lapsed as of `today` (`YYYY-MM-DD`).
+    pub fn is_expired(&self, today: &str) -> bool {
+        // ISO dates compare correctly as strings
+        self.expires.as_deref().is_some_and(|expires| expires < today)
+    }
+}
+
+/// Load suppressions from `path`; a missing file yields no suppressions.
+///
+/// The file uses a small YAML subset: a top-level `suppressions:` list whose
+/// items are flat `key: value` maps. Comments and blank lines are ignored.
+pub fn load(path: &Path) -> Result<Vec<Suppression>, String> {
+    match fsutil::read_to_string(path) {
+        Ok(content) => parse(&content).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("{}: {}", path.display(), e)),
+    }
+}
+
+pub fn parse(content: &str) -> Result<Vec<Suppression>, String> {
+    let mut entries: Vec<Suppression> = Vec::new();
+
+    for (index, raw) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let line = raw.trim_end_matches('\r');
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed == "suppressions:" {
+            continue;
+        }
+
+        let (starts_entry, field) = match trimmed.strip_prefix("- ") {
+            Some(rest) => (true, rest.trim()),
+            None => (false, trimmed),
+        };
+
+        let (key, value) = field
+            .split_once(':')
+            .ok_or_else(|| format!("line {}: expected `key: value`", line_no))?;
+        let value = unquote(value.trim());
+
+        if starts_entry {
+            entries.push(Suppression::default());
+        }
+        let Some(entry) = entries.last_mut() else {
+            return Err(format!("line {}: `{}` outside of a list item", line_no, key.trim()));
+        };
+
+        match key.trim() {
+            "id" => entry.id = value,
+            "package" => entry.package = Some(value),
+            "cwe" => entry.cwe = Some(value),
+            "reason" => entry.reason = value,
+            "expires" => entry.expires = Some(value),
+            other => return Err(format!("line {}: unknown key `{}`", line_no, other)),
+        }
+    }
+
+    if let Some(position) = entries.iter().position(|e| e.id.is_empty() && e.cwe.is_none()) {
+        return Err(format!("entry {} has neither an id nor a cwe", position + 1));
+    }
+    Ok(entries)
+}
+
+/// Append entries to the suppression file, creating it if needed.
+///
+/// The existing text is kept byte for byte, so comments and ordering
+/// survive; new entries always go at the end of the `suppressions:` list.
+pub fn append(path: &Path, entries: &[Suppression]) -> io::Result<()> {
+    let mut content = match fsutil::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => FILE_HEADER.to_string(),
+        Err(e) => return Err(e),
+    };
+
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    if !content.lines().any(|l| l.trim_end() == "suppressions:") {
+        content.push_str("suppressions:\n");
+    }
+
+    for entry in entries {
+        let mut fields = Vec::new();
+        if !entry.id.is_empty() {
+            fields.push(format!("id: {}", entry.id));
+        }
+        if let Some(package) = &entry.package {
+            fields.push(format!("package: {}", quote(package)));
+        }
+        if let Some(cwe) = &entry.cwe {
+            fields.push(format!("cwe: {}", cwe));
+        }
+        fields.push(format!("reason: {}", quote(&entry.reason)));
+        if let Some(expires) = &entry.expires {
+            fields.push(format!("expires: {}", expires));
+        }
+        for (i, field) in fields.iter().enumerate() {
+            content.push_str(if i == 0 { "  - " } else { "    " });
+            content.push_str(field);
+            content.push('\n');
+        }
+    }
+
+    fsutil::write_atomic(path, content.as_bytes())
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn unquote(value: &str) -> String {
+    if let Some(inner) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return inner.replace("\\\"", "\"").replace("\\\\", "\\");
+    }
+    if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return inner.replace("''", "'");
+    }
+    // Unquoted scalars may carry a trailing comment
+    match value.find(" #") {
+        Some(at) => value[..at].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
@@ -0,0 +1,331 @@
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::ScanConfig;
+#[cfg(feature = "otel")]
+use crate::http;
+use crate::report;
+
+/// Path OTLP/HTTP collectors accept traces on.
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Handle to a started span. Spans of a disabled tracer are all `SpanId(0)`.
+#[derive(Clone, Copy)]
+pub struct SpanId(usize);
+
+/// A span attribute value.
+pub enum Attr {
+    Str(String),
+    Int(i64),
+}
+
+impl From<&str> for Attr {
+    fn from(value: &str) -> Self {
+        Attr::Str(value.to_string())
+    }
+}
+
+impl From<String> for Attr {
+    fn from(value: String) -> Self {
+        Attr::Str(value)
+    }
+}
+
+impl From<usize> for Attr {
+    fn from(value: usize) -> Self {
+        Attr::Int(value as i64)
+    }
+}
+
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+struct Span {
+    name: &'static str,
+    span_id: [u8; 8],
+    parent: Option<[u8; 8]>,
+    start_unix_nanos: u128,
+    started: Instant,
+    duration_nanos: Option<u128>,
+    attributes: Vec<(&'static str, Attr)>,
+}
+
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+struct Trace {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    service_name: String,
+    trace_id: [u8; 16],
+    /// Parent span from an incoming `TRACEPARENT`, if any.
+    remote_parent: Option<[u8; 8]>,
+    spans: Vec<Span>,
+    /// Indices of spans that are started but not ended, innermost last.
+    open: Vec<usize>,
+    seed: [u8; 32],
+}
+
+/// Collects spans for one hook invocation and exports them over OTLP/HTTP
+/// (JSON encoding).
+///
+/// Without a configured endpoint the tracer is disabled: every call returns
+/// after a single branch and attribute closures are never run.
+pub struct Tracer {
+    trace: Option<Box<Trace>>,
+}
+
+impl Tracer {
+    pub fn disabled() -> Self {
+        Tracer { trace: None }
+    }
+
+    /// Build a tracer from the `otlp_endpoint` setting or the standard
+    /// `OTEL_*` environment variables.
+    pub fn from_config(config: &ScanConfig, env: impl Fn(&str) -> Option<String>) -> Self {
+        if env("OTEL_SDK_DISABLED").is_some_and(|v| v.eq_ignore_ascii_case("true")) {
+            return Tracer::disabled();
+        }
+
+        let Some(endpoint) = endpoint(config, &env) else {
+            return Tracer::disabled();
+        };
+
+        let headers = env("OTEL_EXPORTER_OTLP_TRACES_HEADERS")
+            .or_else(|| env("OTEL_EXPORTER_OTLP_HEADERS"))
+            .map(|h| parse_headers(&h))
+            .unwrap_or_default();
+
+        let seed: [u8; 32] = Sha256::new()
+            .chain_update(std::process::id().to_le_bytes())
+            .chain_update(unix_nanos(SystemTime::now()).to_le_bytes())
+            .finalize()
+            .into();
+
+        let parent = env("TRACEPARENT").and_then(|tp| parse_traceparent(&tp));
+        let trace_id = parent.map_or_else(|| seed[..16].try_into().unwrap(), |(trace_id, _)| trace_id);
+
+        Tracer {
+            trace: Some(Box::new(Trace {
+                endpoint,
+                headers,
+                service_name: env("OTEL_SERVICE_NAME").unwrap_or_else(|| crate::PLUGIN_NAME.to_string()),
+                trace_id,
+                remote_parent: parent.map(|(_, span_id)| span_id),
+                spans: Vec::new(),
+                open: Vec::new(),
+                seed,
+            })),
+        }
+    }
+
+    /// Start a span as a child of the innermost open span.
+    pub fn start(&mut self, name: &'static str) -> SpanId {
+        let Some(trace) = self.trace.as_mut() else {
+            return SpanId(0);
+        };
+
+        let index = trace.spans.len();
+        let span_id: [u8; 8] = Sha256::new()
+            .chain_update(trace.seed)
+            .chain_update(index.to_le_bytes())
+            .finalize()[..8]
+            .try_into()
+            .unwrap();
+        let parent = match trace.open.last() {
+            Some(&open) => Some(trace.spans[open].span_id),
+            None => trace.remote_parent,
+        };
+
+        trace.spans.push(Span {
+            name,
+            span_id,
+            parent,
+            start_unix_nanos: unix_nanos(SystemTime::now()),
+            started: Instant::now(),
+            duration_nanos: None,
+            attributes: Vec::new(),
+        });
+        trace.open.push(index);
+        SpanId(index)
+    }
+
+    /// End a span, attaching the attributes produced by `attributes`.
+    pub fn end<F>(&mut self, span: SpanId, attributes: F)
+    where
+        F: FnOnce() -> Vec<(&'static str, Attr)>,
+    {
+        let Some(trace) = self.trace.as_mut() else {
+            return;
+        };
+
+        let Some(data) = trace.spans.get_mut(span.0) else {
+            return;
+        };
+        data.duration_nanos = Some(data.started.elapsed().as_nanos());
+        data.attributes = attributes();
+        trace.open.retain(|&open| open != span.0);
+    }
+
+    /// Send the collected spans. Failures are logged as warnings only;
+    /// tracing must never change the outcome of a scan.
+    pub fn export(self, config: &ScanConfig, logs: &mut Vec<String>) {
+        let Some(trace) = self.trace else {
+            return;
+        };
+        if trace.spans.is_empty() {
+            return;
+        }
+
+        #[cfg(feature = "otel")]
+        if let Err(e) = http::post_json(config, &trace.endpoint, &trace.to_otlp().to_string(), &trace.headers) {
+            logs.push(format!("  ⚠ Trace export to {} failed: {}", trace.endpoint, e));
+        }
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = config;
+            logs.push(format!("  ⚠ Traces not exported to {}: this build has no `otel` feature", trace.endpoint));
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+impl Trace {
+    /// Encode the spans as an OTLP `ExportTraceServiceRequest`.
+    fn to_otlp(&self) -> Value {
+        let spans: Vec<Value> = self
+            .spans
+            .iter()
+            .map(|span| {
+                let end = span.start_unix_nanos + span.duration_nanos.unwrap_or_else(|| span.started.elapsed().as_nanos());
+                let mut value = json!({
+                    "traceId": report::hex(&self.trace_id),
+                    "spanId": report::hex(&span.span_id),
+                    "name": span.name,
+                    "kind": 1,
+                    "startTimeUnixNano": span.start_unix_nanos.to_string(),
+                    "endTimeUnixNano": end.to_string(),
+                    "attributes": span.attributes.iter().map(|(key, value)| attribute(key, value)).collect::<Vec<_>>(),
+                });
+                if let Some(parent) = span.parent {
+                    value["parentSpanId"] = json!(report::hex(&parent));
+                }
+                value
+            })
+            .collect();
+
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        attribute("service.name", &Attr::Str(self.service_name.clone())),
+                        attribute("service.version", &Attr::from(crate::PLUGIN_VERSION)),
+                    ]
+                },
+                "scopeSpans": [{
+                    "scope": { "name": crate::PLUGIN_NAME, "version": crate::PLUGIN_VERSION },
+                    "spans": spans,
+                }]
+            }]
+        })
+    }
+}
+
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+fn attribute(key: &str, value: &Attr) -> Value {
+    let value = match value {
+        Attr::Str(s) => json!({ "stringValue": s }),
+        // OTLP/JSON encodes 64-bit integers as strings
+        Attr::Int(i) => json!({ "intValue": i.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// Where spans are exported: `otlp_endpoint`, else the standard OTLP
+/// environment variables.
+pub(crate) fn endpoint(config: &ScanConfig, env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    match &config.otlp_endpoint {
+        Some(base) => Some(traces_url(base)),
+        None => match env("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").filter(|v| !v.is_empty()) {
+            Some(url) => Some(url),
+            None => env("OTEL_EXPORTER_OTLP_ENDPOINT").filter(|v| !v.is_empty()).map(|base| traces_url(&base)),
+        },
+    }
+}
+
+fn traces_url(base: &str) -> String {
+    let base = base.trim_end_matches('/');
+    if base.ends_with(TRACES_PATH) {
+        base.to_string()
+    } else {
+        format!("{}{}", base, TRACES_PATH)
+    }
+}
+
+/// Parse `key1=value1,key2=value2` as used by `OTEL_EXPORTER_OTLP_HEADERS`.
+fn parse_headers(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .filter(|(k, _)| !k.is_empty())
+        .collect()
+}
+
+/// Parse a W3C `traceparent` (`00-<trace id>-<span id>-<flags>`).
+fn parse_traceparent(value: &str) -> Option<([u8; 16], [u8; 8])> {
+    let mut parts = value.trim().split('-');
+    let (_version, trace_id, span_id) = (parts.next()?, parts.next()?, parts.next()?);
+    Some((decode_hex(trace_id)?, decode_hex(span_id)?))
+}
+
+fn decode_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    // An all-zero id is invalid and means "no parent"
+    bytes.iter().any(|&b| b != 0).then_some(bytes)
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_nest_under_the_innermost_open_span() {
+        let config = ScanConfig {
+            otlp_endpoint: Some("http://collector:4318/".to_string()),
+            ..ScanConfig::default()
+        };
+        let mut tracer = Tracer::from_config(&config, |key| {
+            (key == "TRACEPARENT").then(|| "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string())
+        });
+
+        let hook = tracer.start("hook");
+        let child = tracer.start("extract");
+        tracer.end(child, || vec![("files", Attr::from(2usize))]);
+        tracer.end(hook, Vec::new);
+
+        let trace = tracer.trace.as_ref().unwrap();
+        assert_eq!(trace.endpoint, "http://collector:4318/v1/traces");
+        let otlp = trace.to_otlp();
+        let spans = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["traceId"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(spans[0]["parentSpanId"], "b7ad6b7169203331");
+        assert_eq!(spans[1]["parentSpanId"], spans[0]["spanId"]);
+        assert_eq!(spans[1]["attributes"][0]["value"]["intValue"], "2");
+    }
+
+    #[test]
+    fn no_endpoint_disables_tracing() {
+        let mut tracer = Tracer::from_config(&ScanConfig::default(), |_| None);
+        let span = tracer.start("hook");
+        tracer.end(span, || unreachable!("attributes are not built when disabled"));
+        assert!(tracer.trace.is_none());
+    }
+}
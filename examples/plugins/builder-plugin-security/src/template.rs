@@ -0,0 +1,410 @@
+use serde_json::Value;
+
+/// A report template: a subset of Handlebars with no partials and no
+/// helpers beyond the block ones, so rendering sees only the data it is
+/// given and never the file system.
+///
+/// - `{{path}}` inserts a value: strings as they are (no HTML escaping,
+///   the output being any text), objects and lists as compact JSON.
+///   Paths are `.`-separated fields or list indexes, relative to the
+///   current `#each` item (`this` itself) or, after `@root.`, to the data.
+/// - `{{#each path}}...{{else}}...{{/each}}` repeats over a list or an
+///   object, with `@index`, `@first`, `@last` and, for objects, `@key`.
+/// - `{{#if path}}...{{else}}...{{/if}}` and `{{#unless path}}`: `false`,
+///   `null`, `0`, `""`, `[]` and missing fields are false.
+/// - `{{! comment }}` and `{{!-- comment --}}` render nothing; `~` inside a
+///   tag's braces (`{{~`, `~}}`) trims the whitespace next to it.
+///
+/// Inserting or repeating over a missing field is an error naming the
+/// template line and the field, so a misspelled path never renders as
+/// silently empty.
+pub struct Template {
+    name: String,
+    nodes: Vec<Node>,
+}
+
+enum Node {
+    Text(String),
+    Value {
+        path: FieldPath,
+        line: usize,
+    },
+    Each {
+        path: FieldPath,
+        line: usize,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    If {
+        path: FieldPath,
+        negate: bool,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// A path as written, resolved against the data when rendering.
+struct FieldPath {
+    raw: String,
+    root: bool,
+    segments: Vec<String>,
+}
+
+/// A block being parsed: the tag that opened it and its nodes so far.
+struct Open {
+    helper: String,
+    path: FieldPath,
+    line: usize,
+    body: Vec<Node>,
+    otherwise: Option<Vec<Node>>,
+}
+
+/// One `#each` level while rendering.
+struct Frame<'a> {
+    value: &'a Value,
+    index: Option<(usize, usize)>,
+    key: Option<&'a str>,
+}
+
+impl FieldPath {
+    fn parse(raw: &str) -> Option<FieldPath> {
+        let (root, rest) = match raw.strip_prefix("@root") {
+            Some("") => (true, ""),
+            Some(rest) => (true, rest.strip_prefix('.')?),
+            None => (false, raw),
+        };
+        let rest = match rest.strip_prefix("this") {
+            Some("") => "",
+            Some(after) if after.starts_with('.') => &after[1..],
+            _ => rest,
+        };
+        let segments: Vec<String> =
+            if rest.is_empty() { Vec::new() } else { rest.split('.').map(str::to_string).collect() };
+        let valid = |segment: &String| {
+            !segment.is_empty() && segment.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '@'))
+        };
+        segments.iter().all(valid).then(|| FieldPath {
+            raw: raw.to_string(),
+            root,
+            segments,
+        })
+    }
+}
+
+impl Template {
+    /// Parse `source`; `name` (the template file) prefixes every error.
+    pub fn parse(name: &str, source: &str) -> Result<Template, String> {
+        let fail = |line: usize, message: String| format!("{}:{}: {}", name, line, message);
+        let mut stack: Vec<Open> = Vec::new();
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut rest = source;
+        let mut line = 1;
+        let mut trim_next = false;
+
+        loop {
+            let (text, tag) = match rest.find("{{") {
+                Some(at) => (&rest[..at], Some(at)),
+                None => (rest, None),
+            };
+            let mut text = if trim_next { text.trim_start() } else { text };
+            let Some(at) = tag else {
+                push_text(current(&mut stack, &mut nodes), text);
+                break;
+            };
+            line += rest[..at].matches('\n').count();
+            let after = &rest[at + 2..];
+
+            // Comments may hold `}}` when written `{{!-- --}}`
+            let (inner, consumed) = if let Some(comment) = after.strip_prefix("!--") {
+                let end = comment.find("--}}").ok_or_else(|| fail(line, "unclosed {{!-- comment".to_string()))?;
+                ("!", 2 + 3 + end + 4)
+            } else {
+                let end = after.find("}}").ok_or_else(|| fail(line, "unclosed {{".to_string()))?;
+                let consumed = 2 + end + 2 + usize::from(after[end + 2..].starts_with('}'));
+                (&after[..end], consumed)
+            };
+            let inner = inner.strip_prefix('{').unwrap_or(inner);
+            let (inner, trim_before) = match inner.strip_prefix('~') {
+                Some(inner) => (inner, true),
+                None => (inner, false),
+            };
+            let (inner, trim_after) = match inner.strip_suffix('~') {
+                Some(inner) => (inner, true),
+                None => (inner, false),
+            };
+            if trim_before {
+                text = text.trim_end();
+            }
+            push_text(current(&mut stack, &mut nodes), text);
+            let tag_line = line;
+            line += rest[at..at + consumed].matches('\n').count();
+            rest = &rest[at + consumed..];
+            trim_next = trim_after;
+
+            let inner = inner.trim();
+            if inner.starts_with('!') {
+                continue;
+            }
+            if inner.starts_with('>') {
+                let message = "partials aren't supported: templates can't include other files";
+                return Err(fail(tag_line, message.to_string()));
+            }
+            if let Some(open) = inner.strip_prefix('#') {
+                let (helper, argument) = open.split_once(char::is_whitespace).unwrap_or((open, ""));
+                if !matches!(helper, "each" | "if" | "unless") {
+                    let message = format!("unknown block {{{{#{}}}}} (expected each, if or unless)", helper);
+                    return Err(fail(tag_line, message));
+                }
+                let path = parse_path(argument.trim()).map_err(|e| fail(tag_line, e))?;
+                stack.push(Open {
+                    helper: helper.to_string(),
+                    path,
+                    line: tag_line,
+                    body: Vec::new(),
+                    otherwise: None,
+                });
+                continue;
+            }
+            if inner == "else" {
+                match stack.last_mut() {
+                    Some(open) if open.otherwise.is_none() => open.otherwise = Some(Vec::new()),
+                    _ => return Err(fail(tag_line, "{{else}} outside of a block".to_string())),
+                }
+                continue;
+            }
+            if let Some(close) = inner.strip_prefix('/') {
+                let Some(open) = stack.pop() else {
+                    return Err(fail(tag_line, format!("{{{{/{}}}}} closes no block", close)));
+                };
+                if close.trim() != open.helper {
+                    return Err(fail(
+                        tag_line,
+                        format!("{{{{/{}}}}} closes {{{{#{}}}}} of line {}", close.trim(), open.helper, open.line),
+                    ));
+                }
+                let otherwise = open.otherwise.unwrap_or_default();
+                let node = match open.helper.as_str() {
+                    "each" => Node::Each {
+                        path: open.path,
+                        line: open.line,
+                        body: open.body,
+                        otherwise,
+                    },
+                    helper => Node::If {
+                        path: open.path,
+                        negate: helper == "unless",
+                        body: open.body,
+                        otherwise,
+                    },
+                };
+                current(&mut stack, &mut nodes).push(node);
+                continue;
+            }
+            if let Some((helper, _)) = inner.split_once(char::is_whitespace) {
+                return Err(fail(tag_line, format!("unknown helper '{}'", helper)));
+            }
+            let path = parse_path(inner).map_err(|e| fail(tag_line, e))?;
+            current(&mut stack, &mut nodes).push(Node::Value { path, line: tag_line });
+        }
+
+        if let Some(open) = stack.last() {
+            return Err(fail(open.line, format!("{{{{#{}}}}} is never closed", open.helper)));
+        }
+        Ok(Template {
+            name: name.to_string(),
+            nodes,
+        })
+    }
+
+    /// Render the template over `data`.
+    pub fn render(&self, data: &Value) -> Result<String, String> {
+        let mut out = String::new();
+        let mut frames = vec![Frame {
+            value: data,
+            index: None,
+            key: None,
+        }];
+        self.render_nodes(&self.nodes, data, &mut frames, &mut out)?;
+        Ok(out)
+    }
+
+    fn render_nodes<'a>(
+        &self,
+        nodes: &'a [Node],
+        root: &'a Value,
+        frames: &mut Vec<Frame<'a>>,
+        out: &mut String,
+    ) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Value { path, line } => match self.resolve(path, root, frames, *line)? {
+                    Value::String(s) => out.push_str(&s),
+                    Value::Null => {}
+                    value => out.push_str(&value.to_string()),
+                },
+                Node::If {
+                    path,
+                    negate,
+                    body,
+                    otherwise,
+                } => {
+                    let truthy = self.resolve(path, root, frames, 0).is_ok_and(|value| is_truthy(&value));
+                    let branch = if truthy != *negate { body } else { otherwise };
+                    self.render_nodes(branch, root, frames, out)?;
+                }
+                Node::Each {
+                    path,
+                    line,
+                    body,
+                    otherwise,
+                } => {
+                    let items: Vec<(Option<&'a str>, &'a Value)> = match lookup(path, root, frames) {
+                        Err(missing) => return Err(self.missing(path, &missing, *line)),
+                        Ok(Value::Array(items)) => items.iter().map(|item| (None, item)).collect(),
+                        Ok(Value::Object(fields)) => fields.iter().map(|(k, v)| (Some(k.as_str()), v)).collect(),
+                        Ok(Value::Null) => Vec::new(),
+                        Ok(_) => {
+                            let message = format!("`{}` is neither a list nor an object to #each over", path.raw);
+                            return Err(format!("{}:{}: {}", self.name, line, message));
+                        }
+                    };
+                    if items.is_empty() {
+                        self.render_nodes(otherwise, root, frames, out)?;
+                    }
+                    let len = items.len();
+                    for (index, (key, value)) in items.into_iter().enumerate() {
+                        frames.push(Frame {
+                            value,
+                            index: Some((index, len)),
+                            key,
+                        });
+                        let rendered = self.render_nodes(body, root, frames, out);
+                        frames.pop();
+                        rendered?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The value at `path`, owned since `@index` and the like are made up.
+    fn resolve(&self, path: &FieldPath, root: &Value, frames: &[Frame], line: usize) -> Result<Value, String> {
+        if let [special] = path.segments.as_slice() {
+            if special.starts_with('@') && !path.root {
+                let frame = frames.iter().rev().find(|f| f.index.is_some());
+                let (index, len) = frame.and_then(|f| f.index).ok_or_else(|| {
+                    format!("{}:{}: `{}` outside of {{{{#each}}}}", self.name, line, path.raw)
+                })?;
+                return match special.as_str() {
+                    "@index" => Ok(Value::from(index)),
+                    "@first" => Ok(Value::from(index == 0)),
+                    "@last" => Ok(Value::from(index + 1 == len)),
+                    "@key" => Ok(frame.and_then(|f| f.key).map_or(Value::Null, Value::from)),
+                    other => Err(format!("{}:{}: unknown variable `{}`", self.name, line, other)),
+                };
+            }
+        }
+        lookup(path, root, frames).cloned().map_err(|missing| self.missing(path, &missing, line))
+    }
+
+    fn missing(&self, path: &FieldPath, segment: &str, line: usize) -> String {
+        format!("{}:{}: `{}` is missing: no field `{}`", self.name, line, path.raw, segment)
+    }
+}
+
+fn parse_path(raw: &str) -> Result<FieldPath, String> {
+    if raw.is_empty() {
+        return Err("empty {{}}".to_string());
+    }
+    FieldPath::parse(raw).ok_or_else(|| format!("'{}' is not a field path", raw))
+}
+
+/// The nodes being added to: the innermost open block's, else the top.
+fn current<'a>(stack: &'a mut [Open], nodes: &'a mut Vec<Node>) -> &'a mut Vec<Node> {
+    match stack.last_mut() {
+        Some(open) => open.otherwise.as_mut().unwrap_or(&mut open.body),
+        None => nodes,
+    }
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(text.to_string()));
+    }
+}
+
+/// The value `path` names, or the first segment that isn't there.
+fn lookup<'a>(path: &FieldPath, root: &'a Value, frames: &[Frame<'a>]) -> Result<&'a Value, String> {
+    let mut value = if path.root { root } else { frames.last().map_or(root, |f| f.value) };
+    for segment in &path.segments {
+        let next = match value {
+            Value::Object(fields) => fields.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        value = next.ok_or_else(|| segment.clone())?;
+    }
+    Ok(value)
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn templates_render_fields_blocks_and_loop_variables() {
+        let data = json!({
+            "summary": {"total": 2, "counts": {"CRITICAL": 1, "HIGH": 1}},
+            "metadata": {"target": "//app:main"},
+            "findings": [
+                {"id": "CVE-1", "package": "django", "fixed_in": "2.2.24", "cwe": ["CWE-89"]},
+                {"id": "CVE-2", "package": "lodash", "fixed_in": null, "cwe": ["UNKNOWN"]}
+            ]
+        });
+        let source = "{{! Jira comment }}h2. {{metadata.target}}: {{summary.total}} findings\n\
+            {{#each findings~}}\n\
+            {{@index}}. {{id}} in {{package}}{{#if fixed_in}}, fixed in {{fixed_in}}{{else}}, no fix{{/if}} \
+            {{cwe}}{{#unless @last}};{{/unless}} ({{@root.metadata.target}})\n\
+            {{/each~}}\n\
+            {{#each summary.counts}}{{@key}}={{this}} {{/each}}{{#each findings.1.fixed_in}}x{{else}}none{{/each}}";
+        let rendered = Template::parse("jira.hbs", source).unwrap().render(&data).unwrap();
+        assert_eq!(
+            rendered,
+            "h2. //app:main: 2 findings\n\
+             0. CVE-1 in django, fixed in 2.2.24 [\"CWE-89\"]; (//app:main)\n\
+             1. CVE-2 in lodash, no fix [\"UNKNOWN\"] (//app:main)\n\
+             CRITICAL=1 HIGH=1 none"
+        );
+    }
+
+    #[test]
+    fn template_errors_name_the_line_and_the_missing_field() {
+        let data = json!({"summary": {"total": 1}, "findings": [{"id": "CVE-1"}]});
+        let render = |source: &str| Template::parse("report.hbs", source).and_then(|t| t.render(&data));
+
+        let misspelled = render("Total: {{summary.total}}\n{{#each findings}}\n  {{id}} {{pakage}}\n{{/each}}");
+        assert_eq!(misspelled.unwrap_err(), "report.hbs:3: `pakage` is missing: no field `pakage`");
+        let nested = render("{{summary.counts.HIGH}}");
+        assert_eq!(nested.unwrap_err(), "report.hbs:1: `summary.counts.HIGH` is missing: no field `counts`");
+        assert_eq!(render("a\n{{#each findings}}\n").unwrap_err(), "report.hbs:2: {{#each}} is never closed");
+        assert_eq!(render("{{#if x}}{{/each}}").unwrap_err(), "report.hbs:1: {{/each}} closes {{#if}} of line 1");
+        assert!(render("{{> /etc/passwd}}").unwrap_err().contains("templates can't include other files"));
+        assert_eq!(render("{{lookup summary}}").unwrap_err(), "report.hbs:1: unknown helper 'lookup'");
+        // A missing field is false to #if, which is how optional fields are tested
+        assert_eq!(render("{{#if summary.fixed}}yes{{else}}no{{/if}}").unwrap(), "no");
+    }
+}
@@ -0,0 +1,264 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::report;
+use crate::sanitize;
+use crate::severity::Severity;
+use crate::suppressions::{self, Suppression};
+use crate::Vulnerability;
+
+/// Descriptions are shown sanitized but much less abridged than in logs.
+const DESCRIPTION_CHARS: usize = 2000;
+
+const USAGE: &str = "usage: builder-plugin-security triage [--workspace DIR] [--report FILE]
+       [--non-interactive --accept-all-below SEVERITY] [--reason TEXT] [--expires YYYY-MM-DD]";
+
+struct TriageOptions {
+    workspace: PathBuf,
+    report: Option<PathBuf>,
+    non_interactive: bool,
+    accept_below: Option<Severity>,
+    reason: Option<String>,
+    expires: Option<String>,
+}
+
+fn parse_args(args: &[String]) -> Result<TriageOptions, String> {
+    let mut options = TriageOptions {
+        workspace: PathBuf::from("."),
+        report: None,
+        non_interactive: false,
+        accept_below: None,
+        reason: None,
+        expires: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--workspace" => options.workspace = PathBuf::from(value()?),
+            "--report" => options.report = Some(PathBuf::from(value()?)),
+            "--non-interactive" => options.non_interactive = true,
+            "--accept-all-below" => {
+                let label = value()?;
+                let severity = Severity::normalize(&label);
+                if severity == Severity::Unknown {
+                    return Err(format!("unknown severity '{}'", label));
+                }
+                options.accept_below = Some(severity);
+            }
+            "--reason" => options.reason = Some(value()?),
+            "--expires" => {
+                let date = value()?;
+                if !is_date(&date) {
+                    return Err(format!("--expires: expected a date as YYYY-MM-DD, got '{}'", date));
+                }
+                options.expires = Some(date);
+            }
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+    }
+
+    if options.non_interactive && options.accept_below.is_none() {
+        return Err("--non-interactive requires --accept-all-below".to_string());
+    }
+    Ok(options)
+}
+
+/// Whether `value` is a calendar date as `YYYY-MM-DD`, the only form
+/// [`Suppression::is_expired`] compares correctly.
+fn is_date(value: &str) -> bool {
+    let round_trip = report::parse_timestamp(value).map(report::format_timestamp);
+    value.len() == 10 && round_trip.is_some_and(|timestamp| timestamp.starts_with(value))
+}
+
+/// Entry point of the `triage` subcommand. Returns the process exit code.
+pub fn run(args: &[String], input: &mut dyn BufRead, out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(e) => {
+            let _ = writeln!(err, "triage: {}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    let report_path = options
+        .report
+        .clone()
+        .unwrap_or_else(|| options.workspace.join(".builder-cache").join("security-report.json"));
+    let suppressions_path = options.workspace.join(suppressions::SUPPRESSIONS_FILE_NAME);
+
+    let result = report::load_report::<Vulnerability>(&report_path).and_then(|findings| {
+        let existing = suppressions::load(&suppressions_path)?;
+        let today = &report::format_timestamp(SystemTime::now())[..10];
+
+        let pending: Vec<Vulnerability> = findings
+            .into_iter()
+            .filter(|f| {
+                !existing
+                    .iter()
+                    .any(|s| s.matches(&f.id, &f.package, &f.cwe) && !s.is_expired(today))
+            })
+            .collect();
+
+        let accepted = if options.non_interactive {
+            accept_below(&pending, &options)
+        } else {
+            triage_interactively(&pending, &options, input, out).map_err(|e| e.to_string())?
+        };
+
+        if !accepted.is_empty() {
+            suppressions::append(&suppressions_path, &accepted)
+                .map_err(|e| format!("{}: {}", suppressions_path.display(), e))?;
+        }
+        let _ = writeln!(
+            out,
+            "{} findings reviewed, {} suppressions added to {}",
+            pending.len(),
+            accepted.len(),
+            suppressions_path.display()
+        );
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            let _ = writeln!(err, "triage: {}", e);
+            1
+        }
+    }
+}
+
+/// Bulk-accept every finding strictly less severe than the threshold.
+///
+/// `UNKNOWN` findings are never accepted in bulk, since their real severity
+/// could be anything.
+fn accept_below(findings: &[Vulnerability], options: &TriageOptions) -> Vec<Suppression> {
+    let Some(threshold) = options.accept_below else {
+        return Vec::new();
+    };
+
+    findings
+        .iter()
+        .filter(|f| f.severity > threshold && f.severity != Severity::Unknown)
+        .map(|f| Suppression {
+            id: f.id.clone(),
+            package: Some(f.package.clone()),
+            cwe: None,
+            reason: options
+                .reason
+                .clone()
+                .unwrap_or_else(|| format!("Baselined below {}", threshold)),
+            expires: options.expires.clone(),
+        })
+        .collect()
+}
+
+fn triage_interactively(
+    findings: &[Vulnerability],
+    options: &TriageOptions,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> io::Result<Vec<Suppression>> {
+    let mut accepted = Vec::new();
+
+    for (i, finding) in findings.iter().enumerate() {
+        writeln!(output, "\n[{}/{}] {} ({})", i + 1, findings.len(), finding.id, finding.severity)?;
+        writeln!(output, "  Package:     {} {}", finding.package, finding.version)?;
+        if let Some(file) = &finding.file {
+            writeln!(output, "  Declared in: {}", file)?;
+        }
+        writeln!(output, "  Fixed in:    {}", finding.fixed_in.as_deref().unwrap_or("no fix available"))?;
+        writeln!(output, "  {}", sanitize::for_log(&finding.description, DESCRIPTION_CHARS))?;
+
+        let Some(answer) = prompt(input, output, "Suppress? [y]es / [n]o / [q]uit: ")? else {
+            break;
+        };
+        match answer.as_str() {
+            "y" | "yes" => {
+                let reason = loop {
+                    let Some(reason) = prompt(input, output, "  Reason: ")? else {
+                        return Ok(accepted);
+                    };
+                    if !reason.is_empty() {
+                        break reason;
+                    }
+                    if let Some(default) = &options.reason {
+                        break default.clone();
+                    }
+                };
+                let expires = loop {
+                    let expires = prompt(input, output, "  Expires (YYYY-MM-DD, empty for never): ")?;
+                    let expires = expires.unwrap_or_default();
+                    if expires.is_empty() || is_date(&expires) {
+                        break expires;
+                    }
+                    writeln!(output, "  '{}' isn't a date as YYYY-MM-DD", expires)?;
+                };
+                accepted.push(Suppression {
+                    id: finding.id.clone(),
+                    package: Some(finding.package.clone()),
+                    cwe: None,
+                    reason,
+                    expires: if expires.is_empty() { options.expires.clone() } else { Some(expires) },
+                });
+            }
+            "q" | "quit" => break,
+            _ => {}
+        }
+    }
+
+    Ok(accepted)
+}
+
+/// Print a prompt and read one trimmed answer; `None` at end of input.
+fn prompt(input: &mut dyn BufRead, output: &mut dyn Write, text: &str) -> io::Result<Option<String>> {
+    write!(output, "{}", text)?;
+    output.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn options(args: &[&str]) -> Result<TriageOptions, String> {
+        parse_args(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn expiry_dates_must_be_iso_dates() {
+        assert_eq!(options(&["--expires", "2026-12-31"]).unwrap().expires.as_deref(), Some("2026-12-31"));
+        for bad in ["12/31/2026", "never", "2026-02-30", "2026-12-31T00:00:00Z"] {
+            let error = options(&["--expires", bad]).err().unwrap();
+            assert!(error.starts_with("--expires: expected a date as YYYY-MM-DD"), "{}", error);
+        }
+        let args = ["--expires".to_string(), "never".to_string()];
+        let mut err = Vec::new();
+        assert_eq!(run(&args, &mut io::empty(), &mut Vec::new(), &mut err), 2);
+
+        let finding: Vulnerability = serde_json::from_value(json!({
+            "id": "GHSA-1",
+            "severity": "LOW",
+            "package": "django",
+            "version": "2.2.0",
+            "description": "",
+            "fixed_in": null,
+        }))
+        .unwrap();
+        let mut input: &[u8] = b"y\nNot reachable\n12/31/2026\nnever\n2026-12-31\n";
+        let mut output = Vec::new();
+        let accepted = triage_interactively(&[finding], &options(&[]).unwrap(), &mut input, &mut output).unwrap();
+        assert_eq!(accepted[0].expires.as_deref(), Some("2026-12-31"));
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("  Expires (YYYY-MM-DD, empty for never): ").count(), 3);
+        assert!(output.contains("  '12/31/2026' isn't a date as YYYY-MM-DD"), "{}", output);
+    }
+}
@@ -0,0 +1,197 @@
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+
+use crate::fsutil;
+
+/// Dependency files of formats the plugin has no extractor for, by file
+/// name (or extension, for names starting with `.`).
+const UNSUPPORTED: &[(&str, &str)] = &[
+    ("pyproject.toml", "Python project metadata"),
+    ("setup.py", "setuptools script"),
+    ("setup.cfg", "setuptools configuration"),
+    ("Pipfile", "Pipenv manifest"),
+    ("Pipfile.lock", "Pipenv lockfile"),
+    ("poetry.lock", "Poetry lockfile"),
+    ("uv.lock", "uv lockfile"),
+    ("package-lock.json", "npm lockfile"),
+    ("npm-shrinkwrap.json", "npm lockfile"),
+    ("pnpm-lock.yaml", "pnpm lockfile"),
+    ("bun.lockb", "Bun lockfile"),
+    ("Cargo.lock", "Cargo lockfile"),
+    ("go.work", "Go workspace file"),
+    ("Gemfile", "Bundler manifest"),
+    ("Gemfile.lock", "Bundler lockfile"),
+    ("composer.json", "Composer manifest"),
+    ("composer.lock", "Composer lockfile"),
+    ("pom.xml", "Maven project"),
+    ("build.gradle", "Gradle build script"),
+    ("build.gradle.kts", "Gradle build script"),
+    ("gradle.lockfile", "Gradle lockfile"),
+    ("packages.config", "NuGet package list"),
+    ("packages.lock.json", "NuGet lockfile"),
+    (".csproj", "MSBuild project"),
+    ("mix.lock", "Mix lockfile"),
+    ("pubspec.lock", "Dart lockfile"),
+    ("Podfile.lock", "CocoaPods lockfile"),
+];
+
+/// How much of an unrecognized text file is read to guess its format.
+const SNIFF_BYTES: u64 = 4096;
+
+/// Why a file that looks like a dependency file wasn't (fully) scanned.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    UnsupportedFormat,
+    ParseError,
+    Excluded,
+    TooLarge,
+    /// The file has more lines, or larger records, than the parsers read.
+    ExceedsLimits,
+    OutsideWorkspace,
+    Unreadable,
+}
+
+/// A likely dependency file missing from the findings, and why.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NotScanned {
+    pub path: String,
+    pub reason: Reason,
+    pub detail: String,
+}
+
+impl NotScanned {
+    pub fn new(path: &str, reason: Reason, detail: impl Into<String>) -> Self {
+        NotScanned {
+            path: path.to_string(),
+            reason,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// What became of a dependency file the scan considered, so a file that
+/// contributed nothing says why.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ExtractionOutcome {
+    /// Read to the end, or up to a parse error also listed in `not_scanned`.
+    Parsed { count: usize },
+    NotFound,
+    /// Couldn't be resolved, opened or stat'ed, or resolves outside the
+    /// workspace root.
+    Unreadable { err: String },
+    /// A dependency file format no extractor reads.
+    Unsupported,
+    /// Left out by `include`/`exclude`, `ecosystem_overrides` or `ecosystems`.
+    Excluded {
+        /// The `exclude` or `ecosystem_overrides` glob; absent when the file
+        /// matched no `include` glob or its ecosystem isn't considered.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        glob: Option<String>,
+    },
+    /// Over `max_file_size`, or over the parsers' line or record limits.
+    TooLarge,
+}
+
+impl ExtractionOutcome {
+    /// The `outcome` tag, which the scan's counts are keyed by.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ExtractionOutcome::Parsed { .. } => "parsed",
+            ExtractionOutcome::NotFound => "not_found",
+            ExtractionOutcome::Unreadable { .. } => "unreadable",
+            ExtractionOutcome::Unsupported => "unsupported",
+            ExtractionOutcome::Excluded { .. } => "excluded",
+            ExtractionOutcome::TooLarge => "too_large",
+        }
+    }
+
+    /// One line for the debug log.
+    pub fn describe(&self) -> String {
+        match self {
+            ExtractionOutcome::Parsed { count } => format!("parsed, {} dependencies", count),
+            ExtractionOutcome::NotFound => "not found".to_string(),
+            ExtractionOutcome::Unreadable { err } => format!("unreadable: {}", err),
+            ExtractionOutcome::Unsupported => "unsupported format".to_string(),
+            ExtractionOutcome::Excluded { glob: Some(glob) } => format!("excluded by '{}'", glob),
+            ExtractionOutcome::Excluded { glob: None } => "excluded".to_string(),
+            ExtractionOutcome::TooLarge => "too large".to_string(),
+        }
+    }
+}
+
+/// The extraction outcome of one file.
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FileOutcome {
+    pub path: String,
+    #[serde(flatten)]
+    pub outcome: ExtractionOutcome,
+}
+
+/// The format a (separator-normalized) path without an extractor is known
+/// to be, going by its name only.
+pub fn unsupported_by_name(path: &str) -> Option<&'static str> {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    if name.contains("requirements") && (name.ends_with(".txt") || name.ends_with(".in")) {
+        return Some("pip requirements file (only names ending in requirements.txt are read)");
+    }
+    UNSUPPORTED
+        .iter()
+        .find(|(pattern, _)| if pattern.starts_with('.') { name.ends_with(pattern) } else { name == *pattern })
+        .map(|(_, format)| *format)
+}
+
+/// Like [`unsupported_by_name`], but text files with any other name are
+/// also sniffed for pinned `name==version` lines.
+pub fn unsupported(path: &str, file: &Path) -> Option<&'static str> {
+    if let Some(format) = unsupported_by_name(path) {
+        return Some(format);
+    }
+    if !(path.ends_with(".txt") || path.ends_with(".in")) {
+        return None;
+    }
+    let mut head = Vec::new();
+    fsutil::open(file).ok()?.take(SNIFF_BYTES).read_to_end(&mut head).ok()?;
+    String::from_utf8_lossy(&head)
+        .lines()
+        .any(is_pinned_requirement)
+        .then_some("pip requirements file (only names ending in requirements.txt are read)")
+}
+
+/// `name==1.2`, `name>=1.2`, ... with a package-name-like name.
+fn is_pinned_requirement(line: &str) -> bool {
+    let line = line.trim();
+    let Some(at) = ["==", ">=", "<=", "~=", "!="].iter().filter_map(|op| line.find(op)).min() else {
+        return false;
+    };
+    let (name, version) = (line[..at].trim(), line[at + 2..].trim());
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '[' | ']' | ','))
+        && version.starts_with(|c: char| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn recognizes_unsupported_formats_by_name_and_content() {
+        assert_eq!(unsupported_by_name("web/package-lock.json"), Some("npm lockfile"));
+        assert_eq!(unsupported_by_name("src/App/App.csproj"), Some("MSBuild project"));
+        assert!(unsupported_by_name("requirements-dev.txt").is_some());
+        assert_eq!(unsupported_by_name("README.md"), None);
+        assert_eq!(unsupported_by_name("docs/notes.txt"), None);
+
+        let dir = crate::testutil::scratch_dir("unscanned");
+        fs::write(dir.join("deps.txt"), "# pinned\nFlask==2.0.1\nclick>=8\n").unwrap();
+        fs::write(dir.join("notes.txt"), "Upgrade when 2.0 == stable\n").unwrap();
+        assert!(unsupported("ci/deps.txt", &dir.join("deps.txt")).is_some());
+        assert_eq!(unsupported("docs/notes.txt", &dir.join("notes.txt")), None);
+        assert_eq!(unsupported("missing.txt", &dir.join("missing.txt")), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
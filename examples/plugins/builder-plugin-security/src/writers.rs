@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[cfg(feature = "templates")]
+use crate::fsutil;
+#[cfg(feature = "sarif")]
+use crate::severity::Severity;
+use crate::summary::TargetSummary;
+#[cfg(feature = "templates")]
+use crate::template::Template;
+use crate::Vulnerability;
+
+/// Name of the JSON report in `report_dir`.
+pub const JSON_REPORT_FILE_NAME: &str = "security-report.json";
+
+/// What every report is written from.
+#[cfg_attr(not(feature = "templates"), allow(dead_code))]
+pub struct ReportData<'a> {
+    pub findings: &'a [Vulnerability],
+    /// The counts and policy outcome, as the post_hook hands them on.
+    pub summary: &'a TargetSummary,
+    /// The JSON report: the findings in the envelope recording the scan.
+    pub envelope: &'a str,
+}
+
+/// One of the `report_formats`.
+pub trait ReportWriter {
+    /// The `report_formats` entry writing this report.
+    fn format(&self) -> &str;
+    /// Name of the report in `report_dir`, before compression.
+    fn file_name(&self) -> String;
+    fn render(&self, data: &ReportData) -> Result<Vec<u8>, String>;
+}
+
+/// The JSON report, `security-report.json`.
+pub struct JsonWriter;
+
+impl ReportWriter for JsonWriter {
+    fn format(&self) -> &str {
+        "json"
+    }
+
+    fn file_name(&self) -> String {
+        JSON_REPORT_FILE_NAME.to_string()
+    }
+
+    fn render(&self, data: &ReportData) -> Result<Vec<u8>, String> {
+        Ok(data.envelope.as_bytes().to_vec())
+    }
+}
+
+/// The SARIF 2.1.0 log code scanning UIs import,
+/// `security-report.sarif`: a rule per advisory and a result per finding,
+/// located at its declaration when there is one.
+#[cfg(feature = "sarif")]
+pub struct SarifWriter;
+
+#[cfg(feature = "sarif")]
+impl ReportWriter for SarifWriter {
+    fn format(&self) -> &str {
+        "sarif"
+    }
+
+    fn file_name(&self) -> String {
+        "security-report.sarif".to_string()
+    }
+
+    fn render(&self, data: &ReportData) -> Result<Vec<u8>, String> {
+        use serde_json::{json, Value};
+
+        let mut rules: BTreeMap<&str, Value> = BTreeMap::new();
+        let mut results = Vec::new();
+        for finding in data.findings {
+            let text = match finding.summary.is_empty() {
+                true => &finding.description,
+                false => &finding.summary,
+            };
+            rules.entry(&finding.id).or_insert_with(|| {
+                let mut rule = json!({
+                    "id": finding.id,
+                    "shortDescription": {"text": text},
+                    "properties": {"tags": finding.cwe},
+                });
+                if let Some(url) = &finding.url {
+                    rule["helpUri"] = url.clone().into();
+                }
+                rule
+            });
+            let mut result = json!({
+                "ruleId": finding.id,
+                "level": match finding.severity {
+                    Severity::Critical | Severity::High => "error",
+                    Severity::Medium => "warning",
+                    Severity::Low | Severity::Info | Severity::Unknown => "note",
+                },
+                "message": {"text": format!("{} {}: {}", finding.package, finding.version, text)},
+                "partialFingerprints": {"builderSecurity/v1": finding.fingerprint},
+            });
+            if let Some(file) = &finding.file {
+                let mut region = json!({"startLine": finding.line.unwrap_or(1)});
+                if let Some(column) = finding.column {
+                    region["startColumn"] = column.into();
+                }
+                result["locations"] = json!([{
+                    "physicalLocation": {"artifactLocation": {"uri": file}, "region": region}
+                }]);
+            }
+            results.push(result);
+        }
+        let log = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {"driver": {
+                    "name": crate::PLUGIN_NAME,
+                    "version": crate::PLUGIN_VERSION,
+                    "rules": rules.into_values().collect::<Vec<_>>(),
+                }},
+                "results": results,
+            }],
+        });
+        serde_json::to_vec_pretty(&log).map_err(|e| e.to_string())
+    }
+}
+
+/// A report rendered from one of the `report_templates`, with the findings,
+/// the summary and the rest of the JSON report as `metadata`.
+#[cfg(feature = "templates")]
+pub struct TemplateWriter {
+    name: String,
+    template: Template,
+    extension: String,
+    target: String,
+}
+
+#[cfg(feature = "templates")]
+impl TemplateWriter {
+    /// The writer of template `name`, read from `path` once, here: rendering
+    /// has no access to files.
+    pub fn load(name: &str, path: &Path, target: &str) -> Result<Self, String> {
+        let source = fsutil::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let template = Template::parse(&file_name, &source)?;
+        // `jira.md.hbs` writes `security-report-jira.md`
+        let stem = file_name.strip_suffix(".hbs").unwrap_or(&file_name);
+        let extension = Path::new(stem).extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        Ok(TemplateWriter {
+            name: name.to_string(),
+            template,
+            extension,
+            target: target.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "templates")]
+impl ReportWriter for TemplateWriter {
+    fn format(&self) -> &str {
+        &self.name
+    }
+
+    fn file_name(&self) -> String {
+        format!("security-report-{}{}", self.name, self.extension)
+    }
+
+    fn render(&self, data: &ReportData) -> Result<Vec<u8>, String> {
+        let mut metadata: serde_json::Value = serde_json::from_str(data.envelope).map_err(|e| e.to_string())?;
+        if let Some(envelope) = metadata.as_object_mut() {
+            envelope.remove("vulnerabilities");
+            envelope.remove("unapproved_dependencies");
+            envelope.insert("target".to_string(), self.target.clone().into());
+        }
+        let context = serde_json::json!({
+            "findings": data.findings,
+            "summary": data.summary,
+            "metadata": metadata,
+        });
+        self.template.render(&context).map(String::into_bytes)
+    }
+}
+
+/// The writers of `formats`, with a warning for each that can't write:
+/// an unknown format, or a template that can't be read or parsed.
+/// Templates are looked up relative to `root`.
+pub fn for_formats(
+    formats: &[String],
+    templates: &BTreeMap<String, String>,
+    root: &Path,
+    target: &str,
+    logs: &mut Vec<String>,
+) -> Vec<Box<dyn ReportWriter>> {
+    #[cfg(not(feature = "templates"))]
+    let _ = (root, target);
+    let mut writers: Vec<Box<dyn ReportWriter>> = Vec::new();
+    for format in formats {
+        if format == "json" {
+            writers.push(Box::new(JsonWriter));
+            continue;
+        }
+        if format == "sarif" && !templates.contains_key(format) {
+            #[cfg(feature = "sarif")]
+            writers.push(Box::new(SarifWriter));
+            #[cfg(not(feature = "sarif"))]
+            logs.push("  ⚠ Report format 'sarif' needs a build with the `sarif` feature".to_string());
+            continue;
+        }
+        let Some(path) = templates.get(format) else {
+            logs.push(format!("  ⚠ Unsupported report format: {}", format));
+            continue;
+        };
+        #[cfg(feature = "templates")]
+        match TemplateWriter::load(format, &root.join(path), target) {
+            Ok(writer) => writers.push(Box::new(writer)),
+            Err(e) => logs.push(format!("  ⚠ Could not load report template '{}': {}", format, e)),
+        }
+        #[cfg(not(feature = "templates"))]
+        logs.push(format!("  ⚠ Report template '{}' ({}) needs a build with the `templates` feature", format, path));
+    }
+    writers
+}
@@ -0,0 +1,5 @@
+module example.com/svc
+
+go 1.21
+
+require golang.org/x/net v0.7.0